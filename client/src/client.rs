@@ -0,0 +1,384 @@
+use crate::types::{
+    AnalysisBatchResponse, AnalysisRequest, AnalysisResponse, BotMoveResponse, CacheClearResponse,
+    CacheStatsResponse, CancelResponse, GameRequest, GameReviewResponse, GameScoreResponse,
+    HealthResponse, HeatmapRequest, HumanComparisonRequest, HumanComparisonResponse, JobRequest,
+    JobResponse, OgsImportRequest, PolicyResponse, ProblemDetail, ReviewAnimationRequest,
+    ReviewGraphResponse, ReviewRequest, ReviewSgfRequest, ScoreEstimateResponse, ScoreRequest,
+    TradeRequest, TradeResponse, VersionResponse,
+};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Retries after the first attempt for connection failures and 429/502/503/504
+const DEFAULT_MAX_RETRIES: u32 = 2;
+
+/// Delay before the first retry; doubled for each further retry
+const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// The server answered with an RFC 7807 problem detail
+    #[error("{} ({}): {}", .0.title, .0.status, .0.detail)]
+    Api(Box<ProblemDetail>),
+
+    #[error("Unexpected {status} response: {body}")]
+    Status { status: u16, body: String },
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+/// Client for one katago-server instance
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    max_retries: u32,
+    retry_backoff: Duration,
+}
+
+impl Client {
+    /// Client for a server base URL such as `http://localhost:2718`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+        }
+    }
+
+    /// Use a preconfigured reqwest client (timeouts, proxies, TLS roots)
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Retry transient failures up to `max_retries` times, waiting `backoff`, then
+    /// twice as long for each further retry. `0` disables retries.
+    pub fn with_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// POST /api/v1/analysis
+    pub async fn analyze(&self, request: &AnalysisRequest) -> Result<AnalysisResponse> {
+        let response = self
+            .send(|| self.http.post(self.url("/api/v1/analysis")).json(request))
+            .await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/analysis/turns: one result per turn in `analyze_turns`
+    pub async fn analyze_turns(&self, request: &AnalysisRequest) -> Result<AnalysisBatchResponse> {
+        let response = self
+            .send(|| {
+                self.http
+                    .post(self.url("/api/v1/analysis/turns"))
+                    .json(request)
+            })
+            .await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/analysis/trade
+    pub async fn trade(&self, request: &TradeRequest) -> Result<TradeResponse> {
+        let response = self
+            .send(|| {
+                self.http
+                    .post(self.url("/api/v1/analysis/trade"))
+                    .json(request)
+            })
+            .await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/analysis/human
+    pub async fn compare_human(
+        &self,
+        request: &HumanComparisonRequest,
+    ) -> Result<HumanComparisonResponse> {
+        let response = self
+            .send(|| {
+                self.http
+                    .post(self.url("/api/v1/analysis/human"))
+                    .json(request)
+            })
+            .await?;
+        decode(response).await
+    }
+
+    /// GET /api/v1/health. An unhealthy server (503) is reported in the response,
+    /// not as an error, and is not retried.
+    pub async fn health(&self) -> Result<HealthResponse> {
+        let response = self.http.get(self.url("/api/v1/health")).send().await?;
+        if response.status() == StatusCode::SERVICE_UNAVAILABLE {
+            return Ok(response.json().await?);
+        }
+        decode(response).await
+    }
+
+    /// GET /api/v1/version
+    pub async fn version(&self) -> Result<VersionResponse> {
+        let response = self
+            .send(|| self.http.get(self.url("/api/v1/version")))
+            .await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/cache/clear
+    pub async fn clear_cache(&self) -> Result<CacheClearResponse> {
+        let response = self
+            .send(|| self.http.post(self.url("/api/v1/cache/clear")))
+            .await?;
+        decode(response).await
+    }
+
+    /// GET /api/v1/cache/stats
+    pub async fn cache_stats(&self) -> Result<CacheStatsResponse> {
+        let response = self
+            .send(|| self.http.get(self.url("/api/v1/cache/stats")))
+            .await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/analysis/{id}/cancel: abort the running query with requestId `id`
+    pub async fn cancel(&self, id: &str) -> Result<CancelResponse> {
+        let path = format!("/api/v1/analysis/{}/cancel", id);
+        let response = self.send(|| self.http.post(self.url(&path))).await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/game/move: the GTP bot's move after `request.moves`
+    pub async fn game_move(&self, request: &GameRequest) -> Result<BotMoveResponse> {
+        let response = self
+            .send(|| self.http.post(self.url("/api/v1/game/move")).json(request))
+            .await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/game/score: the GTP bot's ownership estimate
+    pub async fn game_score(&self, request: &GameRequest) -> Result<GameScoreResponse> {
+        let response = self
+            .send(|| self.http.post(self.url("/api/v1/game/score")).json(request))
+            .await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/score: estimated final score of a position
+    pub async fn score(&self, request: &ScoreRequest) -> Result<ScoreEstimateResponse> {
+        let response = self
+            .send(|| self.http.post(self.url("/api/v1/score")).json(request))
+            .await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/policy: the network's move priors, shaped like the board
+    pub async fn policy(&self, request: &AnalysisRequest) -> Result<PolicyResponse> {
+        let response = self
+            .send(|| self.http.post(self.url("/api/v1/policy")).json(request))
+            .await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/render/heatmap: the image bytes, SVG or PNG as the request's
+    /// `format` asks
+    pub async fn render_heatmap(&self, request: &HeatmapRequest) -> Result<Bytes> {
+        let response = self
+            .send(|| {
+                self.http
+                    .post(self.url("/api/v1/render/heatmap"))
+                    .json(request)
+            })
+            .await?;
+        Ok(check(response).await?.bytes().await?)
+    }
+
+    /// POST /api/v1/render/review: the reviewed game as an animated SVG or GIF,
+    /// as the request's `format` asks
+    pub async fn render_review(&self, request: &ReviewAnimationRequest) -> Result<Bytes> {
+        let response = self
+            .send(|| {
+                self.http
+                    .post(self.url("/api/v1/render/review"))
+                    .json(request)
+            })
+            .await?;
+        Ok(check(response).await?.bytes().await?)
+    }
+
+    /// POST /api/v1/review: per-move evaluations of an SGF game
+    pub async fn review(&self, request: &ReviewRequest) -> Result<GameReviewResponse> {
+        let response = self
+            .send(|| self.http.post(self.url("/api/v1/review")).json(request))
+            .await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/review with a katrain or lizzie `format`: the reviewed game as
+    /// an SGF file for that tool
+    pub async fn review_export(&self, request: &ReviewRequest) -> Result<String> {
+        let response = self
+            .send(|| self.http.post(self.url("/api/v1/review")).json(request))
+            .await?;
+        Ok(check(response).await?.text().await?)
+    }
+
+    /// POST /api/v1/import/ogs: per-move evaluations of a game from online-go.com
+    pub async fn import_ogs(&self, request: &OgsImportRequest) -> Result<GameReviewResponse> {
+        let response = self
+            .send(|| self.http.post(self.url("/api/v1/import/ogs")).json(request))
+            .await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/review/graph: Black's winrate and score lead at every turn
+    pub async fn review_graph(&self, request: &ReviewRequest) -> Result<ReviewGraphResponse> {
+        let response = self
+            .send(|| {
+                self.http
+                    .post(self.url("/api/v1/review/graph"))
+                    .json(request)
+            })
+            .await?;
+        decode(response).await
+    }
+
+    /// POST /api/v1/review/sgf: the annotated SGF as it is produced, one chunk per
+    /// reviewed move. Only starting the review is retried.
+    pub async fn review_sgf(
+        &self,
+        request: &ReviewSgfRequest,
+    ) -> Result<impl Stream<Item = Result<Bytes>>> {
+        let response = self
+            .send(|| self.http.post(self.url("/api/v1/review/sgf")).json(request))
+            .await?;
+        let response = check(response).await?;
+        Ok(response
+            .bytes_stream()
+            .map(|chunk| chunk.map_err(ClientError::from)))
+    }
+
+    /// POST /api/v1/jobs: start `request` in the background; poll it with [`Self::job`]
+    pub async fn submit_job(&self, request: &JobRequest) -> Result<JobResponse> {
+        let response = self
+            .send(|| self.http.post(self.url("/api/v1/jobs")).json(request))
+            .await?;
+        decode(response).await
+    }
+
+    /// GET /api/v1/jobs/{id}: the job's status and progress, and its result once done
+    pub async fn job(&self, id: &str) -> Result<JobResponse> {
+        let path = format!("/api/v1/jobs/{}", id);
+        let response = self.send(|| self.http.get(self.url(&path))).await?;
+        decode(response).await
+    }
+
+    /// DELETE /api/v1/jobs/{id}: cancel a running job
+    pub async fn cancel_job(&self, id: &str) -> Result<JobResponse> {
+        let path = format!("/api/v1/jobs/{}", id);
+        let response = self.send(|| self.http.delete(self.url(&path))).await?;
+        decode(response).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Send a request, rebuilding and retrying it after transient failures
+    async fn send(&self, build: impl Fn() -> RequestBuilder) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            let result = build().send().await;
+            let retryable = match &result {
+                Ok(response) => is_retryable_status(response.status()),
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+            if !retryable || attempt >= self.max_retries {
+                return Ok(result?);
+            }
+            tokio::time::sleep(
+                self.retry_backoff
+                    .saturating_mul(2u32.saturating_pow(attempt)),
+            )
+            .await;
+            attempt += 1;
+        }
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Pass successful responses through; turn error responses into [`ClientError`]
+async fn check(response: Response) -> Result<Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status().as_u16();
+    let body = response.text().await?;
+    Err(error_from_body(status, body))
+}
+
+async fn decode<T: DeserializeOwned>(response: Response) -> Result<T> {
+    Ok(check(response).await?.json().await?)
+}
+
+fn error_from_body(status: u16, body: String) -> ClientError {
+    match serde_json::from_str::<ProblemDetail>(&body) {
+        Ok(problem) => ClientError::Api(Box::new(problem)),
+        Err(_) => ClientError::Status { status, body },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problem_detail_becomes_api_error() {
+        let body = r#"{"type":"https://katago-server/problems/unknown-preset","title":"Unknown Preset","status":400,"detail":"No preset named 7x7"}"#;
+        match error_from_body(400, body.to_string()) {
+            ClientError::Api(problem) => {
+                assert_eq!(problem.title, "Unknown Preset");
+                assert_eq!(problem.status, 400);
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
+        assert!(matches!(
+            error_from_body(502, "Bad Gateway".to_string()),
+            ClientError::Status { status: 502, .. }
+        ));
+    }
+
+    #[test]
+    fn test_retryable_statuses() {
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable_status(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    #[test]
+    fn test_base_url_trailing_slash() {
+        let client = Client::new("http://localhost:2718/");
+        assert_eq!(
+            client.url("/api/v1/health"),
+            "http://localhost:2718/api/v1/health"
+        );
+    }
+}
@@ -0,0 +1,30 @@
+//! Typed client for the katago-server REST API.
+//!
+//! The request and response structs in [`types`] are the ones the server itself uses.
+//! With the default `http` feature, [`Client`] wraps reqwest with retries for transient
+//! failures, RFC 7807 problem details as typed errors, and streaming SGF reviews.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), katago_server_client::ClientError> {
+//! use katago_server_client::{types::{AnalysisRequest, MoveInput}, Client};
+//!
+//! let client = Client::new("http://localhost:2718");
+//! let analysis = client
+//!     .analyze(&AnalysisRequest {
+//!         moves: vec![MoveInput::Simple("D4".into()), MoveInput::Simple("Q16".into())],
+//!         max_visits: Some(100),
+//!         ..Default::default()
+//!     })
+//!     .await?;
+//! println!("{:?}", analysis.root_info.map(|root| root.winrate));
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod types;
+
+#[cfg(feature = "http")]
+mod client;
+
+#[cfg(feature = "http")]
+pub use client::{Client, ClientError, Result};
@@ -0,0 +1,1557 @@
+//! Request and response types of the katago-server REST API, shared by the server
+//! and the client so the two cannot drift apart.
+
+use serde::{Deserialize, Serialize};
+
+/// A move can be either a simple coordinate or an explicit [color, coordinate] pair
+/// This allows clients to specify exact colors for handicap games where alternation
+/// doesn't match the actual game (e.g., White plays first in handicap games)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum MoveInput {
+    /// Simple coordinate (e.g., "D4") - color inferred from position/alternation
+    Simple(String),
+    /// Explicit color and coordinate (e.g., ["W", "D4"] or ["B", "Q16"])
+    WithColor([String; 2]),
+}
+
+impl MoveInput {
+    /// Get the coordinate from the move
+    pub fn coord(&self) -> &str {
+        match self {
+            MoveInput::Simple(coord) => coord,
+            MoveInput::WithColor([_, coord]) => coord,
+        }
+    }
+
+    /// Get explicit color if provided, None for simple moves
+    pub fn color(&self) -> Option<&str> {
+        match self {
+            MoveInput::Simple(_) => None,
+            MoveInput::WithColor([color, _]) => Some(color),
+        }
+    }
+}
+
+/// Comprehensive analysis request supporting all KataGo features
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisRequest {
+    /// Moves played so far - can be simple coordinates (e.g., ["D4", "Q16"]) or
+    /// explicit color pairs (e.g., [["W", "D4"], ["B", "Q16"]]) for handicap games
+    #[serde(default)]
+    pub moves: Vec<MoveInput>,
+
+    /// Analyze a stored position (see /api/v1/positions) instead of moves/initialStones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position_id: Option<String>,
+
+    /// Game rules: a ruleset name ("tromp-taylor", "chinese", "japanese",
+    /// "korean", "aga", etc.) or KataGo's rules object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rules: Option<Rules>,
+
+    /// Komi value for the game
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub komi: Option<f32>,
+
+    /// Board width (typically 19)
+    #[serde(default = "default_board_size")]
+    pub board_x_size: u8,
+
+    /// Board height (typically 19)
+    #[serde(default = "default_board_size")]
+    pub board_y_size: u8,
+
+    /// Built-in board-size preset ("9x9", "13x13", "19x19"): sets the board size and
+    /// defaults for rules, komi, and maxVisits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+
+    /// Initial stones for handicap games
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_stones: Option<Vec<(String, String)>>,
+
+    /// Player to move at turn 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_player: Option<String>,
+
+    /// Board diagram, one string per row from the top: X Black, O White, . empty
+    /// (spaces ignored). Sets the board size and initialStones; Black moves first
+    /// unless initialPlayer says otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagram: Option<Vec<String>>,
+
+    /// SGF game record: the main line of its first game sets the board size,
+    /// initialStones, moves, komi, and rules (the request's komi and rules win)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sgf: Option<String>,
+
+    /// Accept setups that cannot arise from legal play (teaching or trick positions):
+    /// moves are placed without legality checks (onto occupied points, suicide, and
+    /// ko are allowed; captures are still resolved) and the resulting board is sent
+    /// to KataGo as initialStones only. Groups without liberties are still rejected;
+    /// there is no way to exempt one. Can't be combined with `analyze_turns`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerate_position: Option<bool>,
+
+    /// Reject moves that are not on the board with 400 Invalid Moves, and illegal
+    /// moves (suicide, ko) with 400 Illegal Move, instead of sending them to
+    /// KataGo (default: `[analysis] strict_validation`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict_validation: Option<bool>,
+
+    /// Name the known joseki each corner's moves follow (`josekis` in the result)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_josekis: Option<bool>,
+
+    /// Turns to analyze (0 = before the first move) on /api/v1/analysis/turns;
+    /// other endpoints analyze the final position
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analyze_turns: Option<Vec<u32>>,
+
+    // Analysis control parameters
+    /// Override config file visit limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_visits: Option<u32>,
+
+    /// Wall-clock limit of the search in seconds (KataGo's maxTime); the search
+    /// stops at whichever of this and maxVisits comes first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_time_secs: Option<f32>,
+
+    /// How long the search should take, in milliseconds: the server sets maxVisits
+    /// from the engine's recent search speed (an explicit maxVisits caps it), and
+    /// maxTime to the target unless maxTimeSecs is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_latency_ms: Option<u64>,
+
+    /// Temperature for root policy (>1 = more exploration)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root_policy_temperature: Option<f32>,
+
+    /// FPU reduction for exploration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root_fpu_reduction_max: Option<f32>,
+
+    /// Length of principal variation to return (KataGo's analysisPVLen)
+    #[serde(
+        default,
+        rename = "analysisPVLen",
+        alias = "analysisPvLen",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub analysis_pv_len: Option<u32>,
+
+    // Data request flags
+    /// Include territory ownership predictions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_ownership: Option<bool>,
+
+    /// Include ownership standard deviation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_ownership_stdev: Option<bool>,
+
+    /// Include ownership for each move candidate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_moves_ownership: Option<bool>,
+
+    /// Shape of the ownership arrays in the result: KataGo's flat array (default),
+    /// rows from the top, or a map by coordinate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ownership_format: Option<OwnershipFormat>,
+
+    /// Include raw neural network policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_policy: Option<bool>,
+
+    /// Include visit counts in principal variations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_pv_visits: Option<bool>,
+
+    // Move filtering
+    /// Moves KataGo must not search (KataGo's `avoidMoves`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avoid_moves: Option<Vec<MoveFilter>>,
+
+    /// The only moves KataGo may search (KataGo's `allowMoves`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_moves: Option<Vec<MoveFilter>>,
+
+    /// Confine the search to a local area (e.g. a corner life-and-death problem):
+    /// neither player may play outside it, though both may pass
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<Region>,
+
+    /// Human SL model to use (its file name, e.g. "b18c384nbt-humanv0.bin.gz") when
+    /// several are configured; the request goes to an engine that has it loaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub human_model: Option<String>,
+
+    /// Named model from `[katago.models]` (e.g. "fast") to analyze with; unset
+    /// uses the engine for the board size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    /// Engine tier: "fast" goes to the server's fast model, if it has one; unset
+    /// picks the tier by `priority`. Ignored when `model` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<Quality>,
+
+    /// Named analysis profile from the server's `[analysis.profiles]` (e.g. "fast"
+    /// or "deep"); its settings fill in the ones the request leaves unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+
+    // Advanced settings
+    /// Override search parameters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub override_settings: Option<serde_json::Value>,
+
+    /// Seconds between partial results on /api/v1/analysis/stream (other endpoints
+    /// return only the final result)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report_during_search_every: Option<f32>,
+
+    /// Query priority
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+
+    /// Optional request identifier
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+
+    /// URL the result is POSTed to once ready, instead of in the response (202
+    /// Accepted); only POST /api/v1/analysis takes it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+}
+
+fn default_board_size() -> u8 {
+    19
+}
+
+impl Default for AnalysisRequest {
+    fn default() -> Self {
+        Self {
+            moves: Vec::new(),
+            position_id: None,
+            rules: None,
+            komi: None,
+            board_x_size: default_board_size(),
+            board_y_size: default_board_size(),
+            preset: None,
+            initial_stones: None,
+            initial_player: None,
+            diagram: None,
+            sgf: None,
+            tolerate_position: None,
+            strict_validation: None,
+            include_josekis: None,
+            analyze_turns: None,
+            max_visits: None,
+            max_time_secs: None,
+            target_latency_ms: None,
+            root_policy_temperature: None,
+            root_fpu_reduction_max: None,
+            analysis_pv_len: None,
+            include_ownership: None,
+            include_ownership_stdev: None,
+            include_moves_ownership: None,
+            ownership_format: None,
+            include_policy: None,
+            include_pv_visits: None,
+            avoid_moves: None,
+            allow_moves: None,
+            region: None,
+            human_model: None,
+            model: None,
+            quality: None,
+            profile: None,
+            override_settings: None,
+            report_during_search_every: None,
+            priority: None,
+            request_id: None,
+            callback_url: None,
+        }
+    }
+}
+
+/// Engine tier of a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum Quality {
+    /// A quick answer from the fast tier (e.g. a hover preview)
+    Fast,
+    /// The usual engine for the board
+    Deep,
+}
+
+/// Points of the board the search is confined to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum Region {
+    /// The listed points, e.g. ["A1", "B1", "A2"]
+    Points(Vec<String>),
+    /// Every point of the rectangle with these opposite corners, e.g.
+    /// {"from": "A19", "to": "G13"}
+    Rectangle { from: String, to: String },
+}
+
+/// Rules of the game, as KataGo takes them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum Rules {
+    /// A named ruleset, e.g. "japanese" (GET /api/v1/rules lists them)
+    Named(String),
+    /// The individual rules, e.g. {"koRule": "POSITIONAL", "scoringRule": "AREA"}
+    Custom(RuleSettings),
+}
+
+impl Rules {
+    /// Name of a named ruleset; None for a rules object
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Rules::Named(name) => Some(name),
+            Rules::Custom(_) => None,
+        }
+    }
+}
+
+impl From<&str> for Rules {
+    fn from(name: &str) -> Self {
+        Rules::Named(name.to_string())
+    }
+}
+
+/// KataGo's rules object; rules left out take KataGo's defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RuleSettings {
+    /// "SIMPLE", "POSITIONAL", or "SITUATIONAL"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ko_rule: Option<String>,
+    /// "AREA" or "TERRITORY"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scoring_rule: Option<String>,
+    /// Points not counted: "NONE", "SEKI" (territory in seki), or "ALL" (one
+    /// point per group, as in stone scoring)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tax_rule: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multi_stone_suicide_legal: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_button: Option<bool>,
+    /// Points White gets per handicap stone: "0", "N-1", or "N"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub white_handicap_bonus: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct MoveFilter {
+    /// Player the filter applies to ("B" or "W")
+    pub player: String,
+    pub moves: Vec<String>,
+    /// Search depth the filter holds for; 1 restricts only the player's next move
+    pub until_depth: u32,
+}
+
+/// Shape of the ownership arrays of an analysis result
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum OwnershipFormat {
+    /// KataGo's array, row by row from the top row
+    #[default]
+    Flat,
+    /// One array per row, from the top row, each from column A
+    Rows,
+    /// Value by coordinate, e.g. {"D4": 0.83}
+    Map,
+}
+
+/// Ownership of every point (-1 White to 1 Black, from the perspective KataGo
+/// reports), shaped as the request's ownershipFormat asks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum Ownership {
+    Flat(Vec<f32>),
+    Rows(Vec<Vec<f32>>),
+    Map(std::collections::BTreeMap<String, f32>),
+}
+
+impl Ownership {
+    /// The values of a flat array
+    pub fn as_flat(&self) -> Option<&[f32]> {
+        match self {
+            Ownership::Flat(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+// GraphQL has no untagged unions of lists and maps; the shapes pass as JSON
+#[cfg(feature = "graphql")]
+async_graphql::scalar!(
+    Ownership,
+    "Ownership",
+    "A flat array, rows, or a coordinate map, as ownershipFormat asks"
+);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisResponse {
+    pub id: String,
+    pub turn_number: u32,
+    pub is_during_search: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub move_infos: Option<Vec<MoveInfo>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_info: Option<RootInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ownership: Option<Ownership>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ownership_stdev: Option<Ownership>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<Vec<f32>>,
+    /// Human SL model policy predictions (requires human model and includePolicy=true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub human_policy: Option<Vec<f32>>,
+    /// File name of the human SL model that produced the human fields, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub human_model: Option<String>,
+    /// Known josekis the corners follow, with includeJosekis
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub josekis: Option<Vec<JosekiMatch>>,
+    /// KataGo's warnings about this query, e.g. overrideSettings keys it ignored
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<EngineWarning>,
+    /// Where the server's time on the query went
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<AnalysisMeta>,
+}
+
+/// Timing of an analysis on the server, to tell a slow network from a full queue
+/// or a long search
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisMeta {
+    /// Time waiting for a slot in the analysis queue
+    pub queue_wait_ms: u64,
+    /// Time from sending the query to KataGo until this result arrived; for a
+    /// coalesced result, the time spent waiting for the running search
+    pub search_ms: u64,
+    /// Time from the engine receiving the request until this result, cache
+    /// lookups and queue included
+    pub total_ms: u64,
+    /// Visits the search actually made (rootInfo's), which can fall short of
+    /// maxVisits when it stops early
+    pub effective_visits: u32,
+    /// Effective visits per second of search time; absent for cached and
+    /// coalesced results, which saw only part of the search or none of it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visits_per_second: Option<f64>,
+    /// Whether the result came from the cache, without a search
+    pub cached: bool,
+    /// Whether the request waited for an identical search that was already
+    /// running, rather than starting one of its own
+    #[serde(default)]
+    pub coalesced: bool,
+}
+
+/// A corner whose moves so far follow a known joseki
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct JosekiMatch {
+    pub corner: BoardCorner,
+    pub name: String,
+    /// Moves the joseki library continues with from here
+    pub continuations: Vec<JosekiContinuation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[serde(rename_all = "camelCase")]
+pub enum BoardCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct JosekiContinuation {
+    /// "B" or "W"
+    pub player: String,
+    #[serde(rename = "move")]
+    #[cfg_attr(feature = "graphql", graphql(name = "move"))]
+    pub move_coord: String,
+    /// Name of the joseki after this move
+    pub name: String,
+}
+
+/// Results of one query analyzing several turns (POST /api/v1/analysis/turns)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisBatchResponse {
+    pub id: String,
+    /// One result per requested turn, in turn order
+    pub turns: Vec<AnalysisResponse>,
+}
+
+/// A warning KataGo reported for a query instead of failing it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct EngineWarning {
+    /// Query field the warning is about (e.g. "overrideSettings")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct MoveInfo {
+    pub move_coord: String,
+    pub visits: u32,
+    pub winrate: f32,
+    pub score_mean: f32,
+    pub score_stdev: f32,
+    pub score_lead: f32,
+    pub utility: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utility_lcb: Option<f32>,
+    pub lcb: f32,
+    pub prior: f32,
+    /// Human SL model prior for this move (requires human model)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub human_prior: Option<f32>,
+    pub order: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pv: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pv_visits: Option<Vec<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ownership: Option<Ownership>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct RootInfo {
+    pub winrate: f32,
+    pub score_lead: f32,
+    pub utility: f32,
+    pub visits: u32,
+    pub current_player: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_winrate: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_score_mean: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_st_score_error: Option<f32>,
+    // Human SL model fields (requires human model and humanSLProfile in overrideSettings)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub human_winrate: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub human_score_mean: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub human_score_stdev: Option<f32>,
+}
+
+/// Exchange (furikawari/trade) evaluation request
+///
+/// The base position is given with the usual analysis fields; the exchange is the set of
+/// stones each side gains. Both positions are evaluated as pure initialStones layouts so
+/// the comparison isn't skewed by move history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TradeRequest {
+    #[serde(flatten)]
+    pub position: AnalysisRequest,
+
+    /// Stones Black plays as part of the exchange, in order. The two sides' moves
+    /// alternate, starting with the player to move.
+    #[serde(default)]
+    pub black_moves: Vec<String>,
+
+    /// Stones White plays as part of the exchange
+    #[serde(default)]
+    pub white_moves: Vec<String>,
+}
+
+/// Position evaluation from Black's perspective
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TradeEvaluation {
+    pub winrate: f32,
+    pub score_lead: f32,
+    pub visits: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_move: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TradeResponse {
+    pub id: String,
+    pub without_exchange: TradeEvaluation,
+    pub with_exchange: TradeEvaluation,
+    /// Change in Black's score lead caused by the exchange (positive = Black profited)
+    pub score_lead_delta: f32,
+    /// Change in Black's winrate caused by the exchange
+    pub winrate_delta: f32,
+    /// "B", "W", or "even" when the score changes by less than half a point
+    pub beneficiary: String,
+}
+
+/// Compare the engine's moves with a human profile's in one position
+/// (POST /api/v1/analysis/human)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct HumanComparisonRequest {
+    #[serde(flatten)]
+    pub position: AnalysisRequest,
+
+    /// Human SL profile to compare with, e.g. "rank_5k" (GET /api/v1/human/profiles
+    /// lists them)
+    #[serde(rename = "humanSLProfile")]
+    pub human_sl_profile: String,
+
+    /// Moves listed on each side; defaults to 5
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_moves: Option<usize>,
+}
+
+/// A move with the engine's evaluation from the side to move's perspective
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ComparedMove {
+    #[serde(rename = "move")]
+    pub move_coord: String,
+    /// Probability that a player of the profile plays the move
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub human_prior: Option<f32>,
+    pub visits: u32,
+    /// None when the engine could not evaluate the move
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub winrate: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score_lead: Option<f32>,
+    /// Points lost against the engine's best move
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub point_loss: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct HumanComparisonResponse {
+    pub id: String,
+    #[serde(rename = "humanSLProfile")]
+    pub human_sl_profile: String,
+    pub current_player: String,
+    /// The engine's best moves, best first
+    pub engine_moves: Vec<ComparedMove>,
+    /// The profile's most likely moves, most likely first
+    pub human_moves: Vec<ComparedMove>,
+}
+
+/// Let the engine play a position out against itself (POST /api/v1/selfplay)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SelfPlayRequest {
+    /// Starting position; its maxVisits (or maxTimeSecs) is the search per move
+    #[serde(flatten)]
+    pub position: AnalysisRequest,
+
+    /// Moves to play before stopping, unless both players pass first; defaults
+    /// to 50, at most 500
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_moves: Option<u32>,
+}
+
+/// A move the engine chose, with its evaluation of the position before it from
+/// Black's perspective (`move` event of /api/v1/selfplay)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SelfPlayMove {
+    /// Number of the move in the game, counting the starting position's moves
+    pub move_number: u32,
+    pub player: String,
+    #[serde(rename = "move")]
+    pub move_coord: String,
+    pub winrate: f32,
+    pub score_lead: f32,
+    pub visits: u32,
+}
+
+/// Why a self-play game stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum SelfPlayEnd {
+    /// Both players passed in a row
+    TwoPasses,
+    /// maxMoves moves were played
+    MaxMoves,
+}
+
+/// The finished game (`result` event of /api/v1/selfplay)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SelfPlayResult {
+    pub request_id: String,
+    /// The moves played by the engine, in order
+    pub played: Vec<SelfPlayMove>,
+    /// The starting position's moves followed by the played ones, ready to send
+    /// back as an analysis request's `moves`
+    pub moves: Vec<MoveInput>,
+    pub end: SelfPlayEnd,
+}
+
+/// Answer (202 Accepted) to a request with a `callbackUrl`: the result is POSTed
+/// there, signed, once ready
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CallbackAccepted {
+    pub request_id: String,
+    pub callback_url: String,
+}
+
+/// Work to run in the background (POST /api/v1/jobs), polled with
+/// GET /api/v1/jobs/{id}. The `type` picks the endpoint whose body the rest is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum JobRequest {
+    /// As POST /api/v1/analysis
+    Analysis(AnalysisRequest),
+    /// As POST /api/v1/analysis/turns
+    Turns(AnalysisRequest),
+    /// As a JSON POST /api/v1/review
+    Review(ReviewRequest),
+}
+
+impl JobRequest {
+    pub fn kind(&self) -> JobKind {
+        match self {
+            JobRequest::Analysis(_) => JobKind::Analysis,
+            JobRequest::Turns(_) => JobKind::Turns,
+            JobRequest::Review(_) => JobKind::Review,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum JobKind {
+    Analysis,
+    Turns,
+    Review,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Positions of a job analyzed so far (turns of a review or of analyzeTurns)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct JobProgress {
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// State of a background job, with its result once completed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct JobResponse {
+    pub job_id: String,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub progress: JobProgress,
+    pub created_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    /// What the job's endpoint would have returned (completed jobs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    /// The problem detail the job's endpoint would have returned (failed jobs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ProblemDetail>,
+}
+
+/// Review an SGF game record move by move (POST /api/v1/review)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewRequest {
+    /// SGF text; the main line of its first game is reviewed
+    pub sgf: String,
+    /// Visits per analyzed position
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_visits: Option<u32>,
+    /// Winrate losses (0-1) tagging moves, instead of the server's `[review]` ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub winrate_loss: Option<LossThresholds>,
+    /// Point losses tagging moves, instead of the server's `[review]` ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_loss: Option<LossThresholds>,
+    /// URL the review is POSTed to once ready, instead of in the response (202
+    /// Accepted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    /// Return the review as a GameReviewResponse (default) or as an SGF file for
+    /// KaTrain or Lizzie
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ReviewFormat>,
+}
+
+/// Image format of a review animation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum AnimationFormat {
+    /// An SVG animated with SMIL, which browsers play
+    #[default]
+    Svg,
+    Gif,
+}
+
+/// Review a game and draw it move by move (POST /api/v1/render/review)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewAnimationRequest {
+    /// SGF text; the main line of its first game is reviewed
+    pub sgf: String,
+    /// Visits per analyzed position
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_visits: Option<u32>,
+    /// Winrate losses (0-1) tagging moves, instead of the server's `[review]` ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub winrate_loss: Option<LossThresholds>,
+    /// Point losses tagging moves, instead of the server's `[review]` ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_loss: Option<LossThresholds>,
+    /// Defaults to an animated SVG
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<AnimationFormat>,
+    /// Pixels between two lines of the board, 8 to 32 (default 16)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub point_size: Option<u32>,
+    /// How long each move is shown, in milliseconds: 100 to 10000 (default 1000)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_ms: Option<u32>,
+}
+
+/// Review a game from online-go.com (POST /api/v1/import/ogs)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct OgsImportRequest {
+    /// OGS game id, as in https://online-go.com/game/12345
+    pub game_id: u64,
+    /// Visits per analyzed position
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_visits: Option<u32>,
+    /// Winrate losses (0-1) tagging moves, instead of the server's `[review]` ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub winrate_loss: Option<LossThresholds>,
+    /// Point losses tagging moves, instead of the server's `[review]` ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_loss: Option<LossThresholds>,
+    /// URL the review is POSTed to once ready, instead of in the response (202
+    /// Accepted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    /// Return the review as a GameReviewResponse (default) or as an SGF file for
+    /// KaTrain or Lizzie
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ReviewFormat>,
+}
+
+/// What POST /api/v1/review returns
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum ReviewFormat {
+    /// A GameReviewResponse
+    #[default]
+    Json,
+    /// SGF with KaTrain's move comments and move quality tags
+    Katrain,
+    /// SGF with Lizzie's LZ analysis property on every move, and move quality tags
+    Lizzie,
+}
+
+/// Smallest loss at which a move is a blunder, a mistake, or an inaccuracy
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LossThresholds {
+    pub blunder: f32,
+    pub mistake: f32,
+    pub inaccuracy: f32,
+}
+
+/// How good a reviewed move was, from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[serde(rename_all = "camelCase")]
+pub enum MoveClassification {
+    /// The engine's preferred move
+    Best,
+    /// Another move losing less than the inaccuracy thresholds
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+/// Reviewed moves of one player by classification
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct ClassificationCounts {
+    pub best: u32,
+    pub good: u32,
+    pub inaccuracy: u32,
+    pub mistake: u32,
+    pub blunder: u32,
+}
+
+impl ClassificationCounts {
+    pub fn add(&mut self, classification: MoveClassification) {
+        let count = match classification {
+            MoveClassification::Best => &mut self.best,
+            MoveClassification::Good => &mut self.good,
+            MoveClassification::Inaccuracy => &mut self.inaccuracy,
+            MoveClassification::Mistake => &mut self.mistake,
+            MoveClassification::Blunder => &mut self.blunder,
+        };
+        *count += 1;
+    }
+}
+
+/// Classification counts of both players of a reviewed game
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct ReviewSummary {
+    pub black: ClassificationCounts,
+    pub white: ClassificationCounts,
+}
+
+/// Per-move evaluation of a game. Winrates and score leads are Black's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct GameReviewResponse {
+    pub board_x_size: u8,
+    pub board_y_size: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub komi: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub black_player: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub white_player: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    pub moves: Vec<MoveReview>,
+    /// How many moves of each class each player made
+    pub summary: ReviewSummary,
+}
+
+/// Black's evaluation of every position of a game, for plotting
+/// (POST /api/v1/review/graph). The arrays are parallel, one entry per turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewGraphResponse {
+    /// 0 = the position before the first move, n = after n moves
+    pub turns: Vec<u32>,
+    pub winrate: Vec<f32>,
+    pub score_lead: Vec<f32>,
+}
+
+/// One played move: the evaluation after it, and what the engine preferred instead
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct MoveReview {
+    /// Index of the move in the game (0 = first move)
+    pub turn: u32,
+    /// "B" or "W"
+    pub player: String,
+    #[serde(rename = "move")]
+    #[cfg_attr(feature = "graphql", graphql(name = "move"))]
+    pub move_coord: String,
+    /// Black's winrate after the move
+    pub winrate: f32,
+    /// Black's score lead after the move
+    pub score_lead: f32,
+    /// Visits of the search of the position after the move
+    #[serde(default)]
+    pub visits: u32,
+    /// The engine's preferred move in the position before this one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_move: Option<String>,
+    /// Black's winrate before the move, i.e. with the preferred move
+    pub best_winrate: f32,
+    /// Black's score lead before the move, i.e. with the preferred move
+    pub best_score_lead: f32,
+    /// Winrate the move cost its player (negative if it did better than expected)
+    pub winrate_loss: f32,
+    /// Points the move cost its player (negative if it did better than expected)
+    pub score_loss: f32,
+    /// `best` for the engine's preferred move, else the most severe tag whose
+    /// winrate or point threshold the loss reaches, or `good` if none
+    pub classification: MoveClassification,
+}
+
+/// Review a game into an annotated SGF, streamed turn by turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewSgfRequest {
+    /// Review an archived game instead of the position fields below
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_id: Option<String>,
+    /// Moves, initialStones, komi, rules, board size, and maxVisits as in an analysis request
+    #[serde(flatten)]
+    pub position: AnalysisRequest,
+}
+
+/// A game for the GTP bot (POST /api/v1/game/move and /api/v1/game/score)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct GameRequest {
+    /// Moves played so far, alternating from Black (e.g. "D4", "pass")
+    pub moves: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub komi: Option<f32>,
+    /// Client name; "kifucam" always plays Chinese rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// Echoed back in the response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Score only: estimate ownership (default true)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ownership: Option<bool>,
+}
+
+/// The bot's move (POST /api/v1/game/move)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct BotMoveResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// GTP coordinate, "pass", or "resign"
+    pub bot_move: String,
+    pub diagnostics: BotDiagnostics,
+}
+
+/// What KataGo reported while choosing its move (needs `ogsChatToStderr` and
+/// `logSearchInfo` in the GTP config)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct BotDiagnostics {
+    /// Winrate of the player to move, when KataGo reported one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub winprob: Option<f32>,
+    /// Score lead of the player to move
+    pub score: f32,
+    /// Best candidates with their playout selection values
+    pub best_ten: Vec<BotCandidate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct BotCandidate {
+    #[serde(rename = "move")]
+    pub move_coord: String,
+    pub psv: i32,
+}
+
+/// Ownership estimate of a position (POST /api/v1/game/score)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct GameScoreResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Ownership per point from -1 to 1, row by row from the top, for the side
+    /// set by `reportAnalysisWinratesAs` in the GTP config (by default the player
+    /// to move); empty without ownership
+    pub probs: Vec<f32>,
+}
+
+/// Estimate the final score of a position from KataGo's ownership
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreRequest {
+    #[serde(flatten)]
+    pub position: AnalysisRequest,
+
+    /// Ownership (0-1) at which a point counts for a player; points below it in
+    /// both directions are dame or unsettled (default 0.5)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ownership_threshold: Option<f32>,
+
+    /// Also count the position under both territory (Japanese) and area
+    /// (Chinese) scoring, with the same ownership and komi
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compare_scoring: Option<bool>,
+}
+
+/// Estimated final score. The score is Black's points minus White's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreEstimateResponse {
+    pub request_id: String,
+    pub rules: Rules,
+    /// "area" (stones and territory count) or "territory" (territory and prisoners)
+    pub scoring: String,
+    pub komi: f32,
+    /// Points counted for Black: stones and territory under area scoring, empty
+    /// points and dead White stones under territory scoring
+    pub black_territory: u32,
+    pub white_territory: u32,
+    /// White stones captured or dead (territory scoring; 0 under area scoring)
+    pub black_prisoners: u32,
+    /// Black stones captured or dead (territory scoring; 0 under area scoring)
+    pub white_prisoners: u32,
+    /// Black's points minus White's and komi: positive when Black is ahead
+    pub score: f32,
+    /// "B+3.5", "W+0.5", or "Draw"
+    pub result: String,
+    /// KataGo's own estimate of Black's lead, for comparison
+    pub score_lead: f32,
+    /// Owner of each point, row by row from the top: 1 Black, -1 White, 0 dame
+    /// or unsettled
+    pub territory: Vec<i8>,
+    /// The count under both scorings, when the request set compareScoring
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comparison: Option<ScoringComparison>,
+}
+
+/// One position counted under territory and under area scoring
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ScoringComparison {
+    /// Japanese scoring: territory and prisoners
+    pub territory_scoring: ScoringOutcome,
+    /// Chinese scoring: stones and territory
+    pub area_scoring: ScoringOutcome,
+    /// Whether the two scorings disagree on the winner (a draw counts as its own
+    /// outcome)
+    pub winner_differs: bool,
+}
+
+/// A position's score under one scoring
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ScoringOutcome {
+    /// Black's points before komi
+    pub black_points: u32,
+    pub white_points: u32,
+    /// Black's points minus White's and komi
+    pub score: f32,
+    /// "B+3.5", "W+0.5", or "Draw"
+    pub result: String,
+}
+
+/// The network's move priors for a position, without a search (POST /api/v1/policy)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyResponse {
+    pub request_id: String,
+    pub board_x_size: u8,
+    pub board_y_size: u8,
+    /// Player the priors are for ("B" or "W")
+    pub current_player: String,
+    /// Prior of each point, one row per line from the top; -1 where the move is
+    /// illegal
+    pub policy: Vec<Vec<f32>>,
+    pub pass_policy: f32,
+    /// The human SL model's priors, when the request used one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub human_policy: Option<Vec<Vec<f32>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub human_pass_policy: Option<f32>,
+}
+
+/// What a heatmap shades
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum HeatmapLayer {
+    /// Black's ownership in black, White's in white
+    #[default]
+    Ownership,
+    /// The network's move priors, relative to the most likely move
+    Policy,
+}
+
+/// Image format of a rendered heatmap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum ImageFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+/// Render ownership or policy over the board (POST /api/v1/render/heatmap)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct HeatmapRequest {
+    #[serde(flatten)]
+    pub position: AnalysisRequest,
+
+    /// Values to shade, e.g. the ownership of an earlier analysis: a flat array
+    /// (row-major from the top row; a trailing pass prior is ignored), rows, or a
+    /// coordinate map. Ownership is Black's. Without values, the position is
+    /// analyzed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub values: Option<Ownership>,
+
+    /// Defaults to ownership
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<HeatmapLayer>,
+
+    /// Defaults to SVG
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ImageFormat>,
+
+    /// Pixels between two lines of the board, 8 to 64 (default 32)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub point_size: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct VersionResponse {
+    pub server: ServerVersion,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub katago: Option<KatagoVersion>,
+    pub model: ModelInfo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct ServerVersion {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct KatagoVersion {
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CacheClearResponse {
+    pub status: String,
+    pub timestamp: String,
+}
+
+/// Response of GET /api/v1/cache/stats: the result cache, summed over engines
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStatsResponse {
+    /// Analyses answered from the cache
+    pub hits: u64,
+    /// Analyses that had to be searched
+    pub misses: u64,
+    /// Results currently cached
+    pub entries: usize,
+    /// The cache behind the in-memory one, if configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<CacheBackendStats>,
+    /// Analyses that waited for an identical search already running instead of
+    /// sending their own; their cache lookups count as misses
+    pub joined: u64,
+    /// hits / (hits + misses), 0 before the first analysis
+    pub hit_rate: f64,
+}
+
+/// The cache behind the in-memory one: `result_cache_dir` or Redis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CacheBackendStats {
+    /// "disk" or "redis"
+    pub kind: String,
+    /// Analyses answered by it after missing in memory
+    pub hits: u64,
+    /// Results it holds; unknown for Redis, which other servers share
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entries: Option<usize>,
+    /// Size of those results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+}
+
+/// Response of POST /api/v1/analysis/{id}/cancel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CancelResponse {
+    /// The cancelled query's requestId
+    pub id: String,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct HealthResponse {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// Seconds since the server started
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uptime: Option<u64>,
+    /// Seconds since the most recently (re)started KataGo process started
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine_uptime: Option<u64>,
+    /// Restarts of KataGo processes after they died
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restarts: Option<u64>,
+    /// Queries sent to KataGo and still awaiting a response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending: Option<usize>,
+    /// When an analysis last got its result from KataGo (RFC 3339); absent
+    /// until the first one does
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success: Option<String>,
+    /// Model filename of the primary engine
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+/// Message from a client on the /api/v1/analysis/subscribe WebSocket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SubscriptionRequest {
+    /// Start refining a position, replacing the connection's current subscription;
+    /// with a channel, spectators watching it receive the same events
+    Subscribe {
+        position: Box<AnalysisRequest>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        channel: Option<String>,
+    },
+    /// Append moves played since to the subscribed position and refine the new one,
+    /// for following a live game without resending it
+    Play { moves: Vec<MoveInput> },
+    /// Stop refining the current position
+    Unsubscribe,
+    /// Receive the events another connection publishes to a channel, instead of
+    /// refining a position of one's own
+    Watch { channel: String },
+}
+
+/// Message from the server on the /api/v1/analysis/subscribe WebSocket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SubscriptionEvent {
+    /// An evaluation that differs materially from the last one sent
+    Update {
+        visits: u32,
+        analysis: Box<AnalysisResponse>,
+    },
+    /// Refinement stopped after reaching the server's budget
+    Done { visits: u32, reason: DoneReason },
+    /// The subscription failed or the message was invalid
+    Error { problem: ProblemDetail },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum DoneReason {
+    MaxVisits,
+    TimeLimit,
+}
+
+// RFC 7807 Problem Details
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ProblemDetail {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// The offending moves of a 400 Invalid Moves problem
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invalid_moves: Option<Vec<InvalidMove>>,
+}
+
+/// A move that is not on the board, e.g. in column I or past the last row
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct InvalidMove {
+    /// Position in the request's `moves`, from 0
+    pub index: usize,
+    #[serde(rename = "move")]
+    pub move_coord: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_omits_unset_fields() {
+        let request = AnalysisRequest {
+            moves: vec![
+                MoveInput::Simple("D4".to_string()),
+                MoveInput::WithColor(["W".to_string(), "Q16".to_string()]),
+            ],
+            max_visits: Some(50),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "moves": ["D4", ["W", "Q16"]],
+                "boardXSize": 19,
+                "boardYSize": 19,
+                "maxVisits": 50
+            })
+        );
+
+        let back: AnalysisRequest = serde_json::from_value(json).unwrap();
+        assert_eq!(back.moves[1].color(), Some("W"));
+        assert_eq!(back.max_visits, Some(50));
+    }
+
+    #[test]
+    fn test_flattened_review_request_round_trip() {
+        let request = ReviewSgfRequest {
+            game_id: Some("g1".to_string()),
+            position: AnalysisRequest {
+                max_visits: Some(200),
+                ..Default::default()
+            },
+        };
+        let json = serde_json::to_string(&request).unwrap();
+        let back: ReviewSgfRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.game_id.as_deref(), Some("g1"));
+        assert_eq!(back.position.max_visits, Some(200));
+    }
+
+    #[test]
+    fn test_subscription_messages_are_tagged() {
+        let request: SubscriptionRequest =
+            serde_json::from_str(r#"{"type":"subscribe","position":{"moves":["D4"]}}"#).unwrap();
+        let SubscriptionRequest::Subscribe { position, channel } = request else {
+            panic!("expected subscribe");
+        };
+        assert_eq!(position.moves[0].coord(), "D4");
+        assert!(channel.is_none());
+
+        let request: SubscriptionRequest =
+            serde_json::from_str(r#"{"type":"watch","channel":"game-42"}"#).unwrap();
+        let SubscriptionRequest::Watch { channel } = request else {
+            panic!("expected watch");
+        };
+        assert_eq!(channel, "game-42");
+
+        let done = SubscriptionEvent::Done {
+            visits: 800,
+            reason: DoneReason::MaxVisits,
+        };
+        assert_eq!(
+            serde_json::to_value(&done).unwrap(),
+            serde_json::json!({"type": "done", "visits": 800, "reason": "maxVisits"})
+        );
+    }
+}
@@ -1,17 +1,29 @@
-use crate::api::{AnalysisRequest, AnalysisResponse, MoveInfo, RootInfo};
+use crate::api::{
+    AnalysisMeta, AnalysisRequest, AnalysisResponse, EngineWarning, InvalidMove, MoveFilter,
+    MoveInfo, Ownership, RootInfo, Rules,
+};
+use crate::cache_backend::{BackendCounts, CacheBackend};
+use crate::coalesce::InFlightQueries;
 use crate::config::KatagoConfig;
 use crate::error::{KatagoError, Result};
+use crate::joseki;
+use crate::processors::{Pipeline, ResultContext};
+use crate::queue::{RequestQueue, Ticket};
+use crate::result_cache::{CacheCounts, ResultCache};
+use crate::salvage::SalvageStore;
+use crate::throughput::{Throughput, ThroughputSnapshot, VisitRate};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io::{BufRead, BufReader, Write};
 use std::process::{Child, ChildStdin, Command, Stdio};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex as StdMutex};
 use std::thread;
-use std::time::Duration;
-use tokio::sync::oneshot;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::timeout;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 /// JSON request format for KataGo analysis engine
 #[derive(Debug, Serialize)]
@@ -20,7 +32,7 @@ struct AnalysisQuery {
     id: String,
     initial_stones: Vec<Vec<String>>,
     moves: Vec<Vec<String>>,
-    rules: String,
+    rules: Rules,
     komi: f32,
     board_x_size: u8,
     board_y_size: u8,
@@ -34,12 +46,92 @@ struct AnalysisQuery {
     include_policy: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     include_pv_visits: Option<bool>,
+    #[serde(rename = "analysisPVLen", skip_serializing_if = "Option::is_none")]
+    analysis_pv_len: Option<u32>,
+    /// Moves KataGo must not search, per player, down to `untilDepth`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    avoid_moves: Option<Vec<MoveFilter>>,
+    /// The only moves KataGo may search, per player, down to `untilDepth`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allow_moves: Option<Vec<MoveFilter>>,
     /// Override KataGo search/analysis settings per-request
     /// Supports all KataGo analysis config options including human SL settings:
     /// - humanSLProfile: e.g., "preaz_5k", "rank_3d", "proyear_2020"
     /// - humanSLChosenMoveProp, humanSLRootExploreProbWeightless, etc.
     #[serde(skip_serializing_if = "Option::is_none")]
     override_settings: Option<serde_json::Value>,
+    /// Seconds between partial results while the search runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report_during_search_every: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<i32>,
+}
+
+impl AnalysisQuery {
+    /// Everything but the id, identifying equivalent queries
+    fn canonical(&self) -> serde_json::Value {
+        canonical_query(serde_json::to_value(self).unwrap_or_default())
+    }
+
+    fn cache_key(&self) -> u64 {
+        hash_query(&self.canonical())
+    }
+}
+
+fn canonical_query(mut query: serde_json::Value) -> serde_json::Value {
+    if let Some(object) = query.as_object_mut() {
+        object.remove("id");
+        // Only when a query runs depends on its priority, not its result
+        object.remove("priority");
+    }
+    query
+}
+
+fn hash_query(query: &serde_json::Value) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    query.to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+fn query_cache_key(query: serde_json::Value) -> u64 {
+    hash_query(&canonical_query(query))
+}
+
+/// A human SL model's name in requests and responses: the model's file name
+pub fn human_model_name(path: &str) -> &str {
+    std::path::Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path)
+}
+
+/// A KataGo warning line (`{"id", "field", "warning"}`) as (query id, warning)
+fn parse_warning(value: &serde_json::Value) -> Option<(String, EngineWarning)> {
+    let message = value.get("warning")?.as_str()?;
+    let id = value.get("id")?.as_str()?;
+    let field = value
+        .get("field")
+        .and_then(|f| f.as_str())
+        .map(str::to_string);
+    Some((
+        id.to_string(),
+        EngineWarning {
+            field,
+            message: message.to_string(),
+        },
+    ))
+}
+
+/// Response line with its query's warnings attached as a `warnings` array
+fn with_warnings(mut response: serde_json::Value, warnings: Vec<EngineWarning>) -> String {
+    if let Some(object) = response.as_object_mut() {
+        object.insert(
+            "warnings".to_string(),
+            serde_json::to_value(warnings).unwrap_or_default(),
+        );
+    }
+    response.to_string()
 }
 
 /// JSON response format from KataGo analysis engine
@@ -51,6 +143,8 @@ struct AnalysisResult {
     #[serde(default)]
     turn_number: u32,
     #[serde(default)]
+    is_during_search: bool,
+    #[serde(default)]
     move_infos: Vec<KatagoMoveInfo>,
     #[serde(default)]
     root_info: Option<KatagoRootInfo>,
@@ -61,6 +155,9 @@ struct AnalysisResult {
     /// Human SL model policy (when human model is loaded and includePolicy=true)
     #[serde(default)]
     human_policy: Option<Vec<f32>>,
+    /// Warning lines KataGo sent for this query, attached by the reader thread
+    #[serde(default)]
+    warnings: Vec<EngineWarning>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -114,75 +211,428 @@ struct KatagoRootInfo {
     human_score_stdev: Option<f32>,
 }
 
-/// Keepalive interval in seconds - send periodic pings to keep KataGo alive
-const KEEPALIVE_INTERVAL_SECS: u64 = 30;
+/// Number of recent analysis failures kept for the operator console
+const MAX_RECENT_ERRORS: usize = 50;
+
+/// Queries whose warnings are held until their response arrives; beyond this the
+/// held warnings are dropped (they belong to queries that never got a response)
+const MAX_HELD_WARNINGS: usize = 1_000;
+
+/// Id of the version query that tells a newly started process has loaded its model
+const READY_PING_ID: &str = "model-switch-ready";
+
+/// Seconds beyond a request's maxTime that its response may take to arrive
+const MAX_TIME_GRACE_SECS: u64 = 5;
+
+/// How long a starting or replacement process may take to load its model
+const MODEL_STARTUP_TIMEOUT_SECS: u64 = 300;
+
+/// Counters for the JSON lines exchanged with KataGo over stdin/stdout
+#[derive(Debug, Default)]
+pub struct ProtocolStats {
+    bytes_written: AtomicU64,
+    bytes_read: AtomicU64,
+    queries_sent: AtomicU64,
+    responses_received: AtomicU64,
+    action_responses: AtomicU64,
+    unknown_id_responses: AtomicU64,
+    parse_failures: AtomicU64,
+    warning_lines: AtomicU64,
+    batches_written: AtomicU64,
+    batched_queries: AtomicU64,
+    largest_batch: AtomicU64,
+    write_failures: AtomicU64,
+}
+
+/// Point-in-time copy of [`ProtocolStats`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtocolStatsSnapshot {
+    pub bytes_written: u64,
+    pub bytes_read: u64,
+    /// Lines written to stdin: analysis queries, actions, and keepalive pings
+    pub queries_sent: u64,
+    /// Analysis responses delivered to a waiting request
+    pub responses_received: u64,
+    /// Responses to actions (query_version, clear_cache, keepalive pings)
+    pub action_responses: u64,
+    /// Responses for ids nobody is waiting on (e.g. timed out) or without an id
+    pub unknown_id_responses: u64,
+    /// Lines on stdout that were not JSON
+    pub parse_failures: u64,
+    /// Warning lines about a query (attached to its response as `warnings`)
+    pub warning_lines: u64,
+    /// Batches written after a batching window (batchWindowMs > 0)
+    pub batches_written: u64,
+    /// Queries written in those batches; divide by batchesWritten for the average size
+    pub batched_queries: u64,
+    pub largest_batch: u64,
+    /// Failed stdin writes while the process kept running; only their queries failed
+    pub write_failures: u64,
+}
+
+impl ProtocolStats {
+    fn record_write(&self, json: &str) {
+        // +1 for the newline terminating each query
+        self.bytes_written
+            .fetch_add(json.len() as u64 + 1, Ordering::Relaxed);
+        self.queries_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_batch(&self, size: usize) {
+        self.batches_written.fetch_add(1, Ordering::Relaxed);
+        self.batched_queries
+            .fetch_add(size as u64, Ordering::Relaxed);
+        self.largest_batch.fetch_max(size as u64, Ordering::Relaxed);
+    }
+
+    fn count(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ProtocolStatsSnapshot {
+        ProtocolStatsSnapshot {
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            queries_sent: self.queries_sent.load(Ordering::Relaxed),
+            responses_received: self.responses_received.load(Ordering::Relaxed),
+            action_responses: self.action_responses.load(Ordering::Relaxed),
+            unknown_id_responses: self.unknown_id_responses.load(Ordering::Relaxed),
+            parse_failures: self.parse_failures.load(Ordering::Relaxed),
+            warning_lines: self.warning_lines.load(Ordering::Relaxed),
+            batches_written: self.batches_written.load(Ordering::Relaxed),
+            batched_queries: self.batched_queries.load(Ordering::Relaxed),
+            largest_batch: self.largest_batch.load(Ordering::Relaxed),
+            write_failures: self.write_failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A failed analysis query, kept for operators
+#[derive(Debug, Clone)]
+pub struct EngineErrorRecord {
+    pub timestamp: String,
+    pub request_id: String,
+    pub message: String,
+}
+
+/// Where the reader thread delivers the response lines of a query
+enum Waiter {
+    /// Only the final response; partial results are skipped
+    Final(oneshot::Sender<String>),
+    /// Every line (partial results included) until `finals_left` final responses,
+    /// one per analyzed turn, or an error line arrived
+    Lines {
+        tx: mpsc::UnboundedSender<String>,
+        finals_left: usize,
+    },
+}
+
+impl Waiter {
+    /// Hand over a response line; returns the waiter again if it expects more lines
+    fn deliver(self, line: String, partial: bool, error: bool) -> Option<Waiter> {
+        match self {
+            Waiter::Final(sender) if partial && !error => Some(Waiter::Final(sender)),
+            Waiter::Final(sender) => {
+                if sender.send(line).is_err() {
+                    warn!("Waiter for a KataGo response is gone");
+                }
+                None
+            }
+            Waiter::Lines { tx, finals_left } => {
+                let finals_left = if partial {
+                    finals_left
+                } else {
+                    finals_left - 1
+                };
+                let open = tx.send(line).is_ok();
+                (open && !error && finals_left > 0).then_some(Waiter::Lines { tx, finals_left })
+            }
+        }
+    }
+}
+
+/// A query sent to KataGo and still waiting for (more of) its response
+struct PendingQuery {
+    waiter: Waiter,
+    /// When the query was sent, for the engine status
+    since: Instant,
+}
+
+impl PendingQuery {
+    fn new(waiter: Waiter) -> Self {
+        Self {
+            waiter,
+            since: Instant::now(),
+        }
+    }
+}
+
+type PendingRequests = Arc<StdMutex<HashMap<String, PendingQuery>>>;
+
+/// When a request reached the engine, waited in the queue, and went to KataGo,
+/// for the `meta` of its responses
+struct Timing {
+    received: Instant,
+    queue_wait: Duration,
+    /// None while queued, and for results served without a search
+    sent: Option<Instant>,
+    /// The request joined an identical search at `sent` instead of sending one
+    coalesced: bool,
+}
+
+impl Timing {
+    fn start() -> Self {
+        Self {
+            received: Instant::now(),
+            queue_wait: Duration::ZERO,
+            sent: None,
+            coalesced: false,
+        }
+    }
+
+    /// Note that the queue let the query through, which was waited for since `queued`
+    fn admitted(&mut self, queued: Instant) {
+        self.queue_wait = queued.elapsed();
+    }
+
+    /// Note that the request got the result of an identical search it started
+    /// waiting for at `joined`
+    fn coalesced(&mut self, joined: Instant) {
+        self.sent = Some(joined);
+        self.coalesced = true;
+    }
+
+    /// Note that the query is being sent to KataGo now
+    fn send(&mut self) -> Instant {
+        let now = Instant::now();
+        self.sent = Some(now);
+        now
+    }
+
+    /// Attach the timing so far to a response
+    fn stamp(&self, mut response: AnalysisResponse) -> AnalysisResponse {
+        let visits = response.root_info.as_ref().map_or(0, |root| root.visits);
+        let search = self.sent.map(|sent| sent.elapsed());
+        response.meta = Some(AnalysisMeta {
+            queue_wait_ms: self.queue_wait.as_millis() as u64,
+            search_ms: search.map_or(0, |search| search.as_millis() as u64),
+            total_ms: self.received.elapsed().as_millis() as u64,
+            effective_visits: visits,
+            visits_per_second: search
+                .filter(|search| !search.is_zero() && !self.coalesced)
+                .map(|search| visits as f64 / search.as_secs_f64()),
+            cached: self.sent.is_none(),
+            coalesced: self.coalesced,
+        });
+        response
+    }
+}
+
+/// Marks the error line handed to the waiter of a cancelled query
+const CANCELLED_FIELD: &str = "cancelled";
+
+/// Marks the error line handed to the waiters of queries failed by a restart
+const RESTARTED_FIELD: &str = "restarted";
+
+/// Id of the keepalive pings, whose answers only update `KeepaliveRecord`
+const KEEPALIVE_ID: &str = "keepalive";
+
+/// The most recent keepalive ping
+#[derive(Debug, Clone)]
+pub struct KeepaliveRecord {
+    pub sent_at: chrono::DateTime<chrono::Utc>,
+    /// Why the ping could not be written, if it couldn't
+    pub error: Option<String>,
+    /// When KataGo answered the ping; None while it hasn't
+    pub answered_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Outcome of a deliberate restart
+#[derive(Debug, Clone, Copy)]
+pub struct RestartReport {
+    /// Process id of the new KataGo process
+    pub pid: u32,
+    /// Queries in flight that failed with the old process
+    pub failed_queries: usize,
+}
+
+/// Engine state shared with the reader threads and the process monitor
+#[derive(Clone)]
+struct ProcessShared {
+    pending_requests: PendingRequests,
+    process_alive: Arc<AtomicBool>,
+    protocol_stats: Arc<ProtocolStats>,
+    salvage: Arc<SalvageStore>,
+    /// Bumped when the process is deliberately replaced, so the readers of the old
+    /// process don't mark the new one dead when their pipe closes
+    generation: Arc<AtomicU64>,
+    /// Restarts of the process after it died
+    restarts: Arc<AtomicU64>,
+    /// When the running process was started
+    started: Arc<StdMutex<Instant>>,
+    /// Restart attempts since a process last stayed up for `restart_stable_secs`,
+    /// up to `max_restart_attempts`
+    restart_attempts: Arc<AtomicU64>,
+    last_keepalive: Arc<StdMutex<Option<KeepaliveRecord>>>,
+}
 
 pub struct AnalysisEngine {
     config: KatagoConfig,
     process: Arc<StdMutex<Option<Child>>>,
     stdin: Arc<StdMutex<Option<ChildStdin>>>,
-    pending_requests: Arc<StdMutex<HashMap<String, oneshot::Sender<String>>>>,
+    pending_requests: PendingRequests,
     /// Flag indicating if KataGo process is alive
     process_alive: Arc<AtomicBool>,
+    /// Most recent analysis failures, newest last
+    recent_errors: StdMutex<VecDeque<EngineErrorRecord>>,
+    /// Shared with the reader threads and the process monitor
+    protocol_stats: Arc<ProtocolStats>,
+    /// Late results for timed-out queries, shared with the reader threads
+    salvage: Arc<SalvageStore>,
+    /// Queries (id, JSON) waiting for the current batching window to close
+    batch: Arc<StdMutex<Vec<(String, String)>>>,
+    generation: Arc<AtomicU64>,
+    restarts: Arc<AtomicU64>,
+    started: Arc<StdMutex<Instant>>,
+    restart_attempts: Arc<AtomicU64>,
+    last_keepalive: Arc<StdMutex<Option<KeepaliveRecord>>>,
+    /// When an analysis last got its result from KataGo
+    last_success: StdMutex<Option<chrono::DateTime<chrono::Utc>>>,
+    /// Queries that got no response within their time limit
+    timeouts: AtomicU64,
+    /// Human SL model the process is started with; None runs without it
+    human_model: Arc<StdMutex<Option<String>>>,
+    /// Model the process is started with; `config.model_path` until switched
+    model_path: Arc<StdMutex<String>>,
+    /// Held while the process is replaced on purpose (model switch, human model)
+    replacing: StdMutex<()>,
+    /// Post-processing applied to every converted result
+    processors: Pipeline,
+    /// Admission of queries, so a burst waits here instead of timing out in KataGo
+    queue: RequestQueue,
+    /// Final results of `analyze`, answering repeated positions without KataGo
+    results: ResultCache,
+    /// Searches of `analyze` running now, which identical requests wait for
+    in_flight: InFlightQueries,
+    /// Final results of `analyze` kept behind `results`: on disk or shared in Redis
+    backend: Option<Box<dyn CacheBackend>>,
+    /// Seconds a query may take; `config.move_timeout_secs` until a reload changes it
+    move_timeout_secs: AtomicU64,
+    /// KataGo's version and git hash, once query_version has answered
+    katago_version: StdMutex<Option<(String, Option<String>)>>,
+    /// Recent search speed, for requests with a latency target
+    visit_rate: VisitRate,
+    /// Searches finished, for the throughput report
+    throughput: Throughput,
+    /// Set once the warm-up analysis succeeded, i.e. the model is loaded
+    warmed_up: AtomicBool,
 }
 
 impl AnalysisEngine {
-    pub fn new(config: KatagoConfig) -> Result<Self> {
-        let pending_requests = Arc::new(StdMutex::new(HashMap::new()));
-        let process_alive = Arc::new(AtomicBool::new(false));
-
-        let mut engine = Self {
+    pub fn new(config: KatagoConfig, backend: Option<Box<dyn CacheBackend>>) -> Result<Self> {
+        let engine = Self {
             config: config.clone(),
             process: Arc::new(StdMutex::new(None)),
             stdin: Arc::new(StdMutex::new(None)),
-            pending_requests: pending_requests.clone(),
-            process_alive: process_alive.clone(),
+            pending_requests: Arc::new(StdMutex::new(HashMap::new())),
+            process_alive: Arc::new(AtomicBool::new(false)),
+            recent_errors: StdMutex::new(VecDeque::new()),
+            protocol_stats: Arc::new(ProtocolStats::default()),
+            salvage: Arc::new(SalvageStore::new()),
+            batch: Arc::new(StdMutex::new(Vec::new())),
+            generation: Arc::new(AtomicU64::new(0)),
+            restarts: Arc::new(AtomicU64::new(0)),
+            started: Arc::new(StdMutex::new(Instant::now())),
+            restart_attempts: Arc::new(AtomicU64::new(0)),
+            last_keepalive: Arc::new(StdMutex::new(None)),
+            last_success: StdMutex::new(None),
+            timeouts: AtomicU64::new(0),
+            human_model: Arc::new(StdMutex::new(if config.human_model_at_startup {
+                config.human_model_path.clone()
+            } else {
+                None
+            })),
+            model_path: Arc::new(StdMutex::new(config.model_path.clone())),
+            replacing: StdMutex::new(()),
+            processors: Pipeline::new(&config.result_processors),
+            queue: RequestQueue::new(config.max_in_flight, config.max_queued),
+            results: ResultCache::new(
+                config.result_cache_entries,
+                Duration::from_secs(config.result_cache_ttl_secs),
+            ),
+            in_flight: InFlightQueries::new(),
+            backend,
+            move_timeout_secs: AtomicU64::new(config.move_timeout_secs),
+            katago_version: StdMutex::new(None),
+            visit_rate: VisitRate::new(),
+            throughput: Throughput::new(),
+            warmed_up: AtomicBool::new(false),
         };
 
-        engine.start_process(pending_requests.clone())?;
-
-        // Wait a bit for initialization
-        thread::sleep(Duration::from_millis(500));
+        engine.start_process()?;
 
         // Start process monitor thread (handles keepalive + auto-restart)
-        let config_clone = config;
         let process_clone = engine.process.clone();
         let stdin_clone = engine.stdin.clone();
-        let pending_clone = pending_requests;
-        let alive_clone = process_alive;
+        let shared = engine.shared();
+        let human_model_clone = engine.human_model.clone();
+        let model_path_clone = engine.model_path.clone();
         thread::spawn(move || {
             Self::process_monitor_loop(
-                config_clone,
+                config,
                 process_clone,
                 stdin_clone,
-                pending_clone,
-                alive_clone,
+                shared,
+                human_model_clone,
+                model_path_clone,
             );
         });
 
         Ok(engine)
     }
 
+    fn shared(&self) -> ProcessShared {
+        ProcessShared {
+            pending_requests: self.pending_requests.clone(),
+            process_alive: self.process_alive.clone(),
+            protocol_stats: self.protocol_stats.clone(),
+            salvage: self.salvage.clone(),
+            generation: self.generation.clone(),
+            restarts: self.restarts.clone(),
+            started: self.started.clone(),
+            restart_attempts: self.restart_attempts.clone(),
+            last_keepalive: self.last_keepalive.clone(),
+        }
+    }
+
     /// Combined keepalive and process monitor loop
-    /// Sends periodic pings and restarts KataGo if it dies
+    /// Sends periodic pings (unless disabled) and restarts KataGo if it dies
     fn process_monitor_loop(
         config: KatagoConfig,
         process: Arc<StdMutex<Option<Child>>>,
         stdin: Arc<StdMutex<Option<ChildStdin>>>,
-        pending_requests: Arc<StdMutex<HashMap<String, oneshot::Sender<String>>>>,
-        process_alive: Arc<AtomicBool>,
+        shared: ProcessShared,
+        human_model: Arc<StdMutex<Option<String>>>,
+        model_path: Arc<StdMutex<String>>,
     ) {
-        const MAX_RESTART_ATTEMPTS: u32 = 5;
-        const RESTART_DELAY_SECS: u64 = 5;
-
-        let mut restart_count: u32 = 0;
+        let process_alive = shared.process_alive.clone();
+        let protocol_stats = shared.protocol_stats.clone();
+        let restart_attempts = shared.restart_attempts.clone();
+        let stable = Duration::from_secs(config.restart_stable_secs);
+
+        let interval = Duration::from_secs(config.keepalive_interval_secs.max(1));
+        if !config.keepalive_enabled {
+            info!("KataGo keepalive pings disabled");
+        }
 
         loop {
-            thread::sleep(Duration::from_secs(KEEPALIVE_INTERVAL_SECS));
+            thread::sleep(interval);
 
             // Check if process is dead and needs restart
             if !process_alive.load(Ordering::SeqCst) {
-                if restart_count >= MAX_RESTART_ATTEMPTS {
+                let restart_count = restart_attempts.load(Ordering::SeqCst);
+                if Self::restarts_exhausted(&config, restart_count) {
                     error!(
                         "KataGo has failed {} times, giving up on restarts",
                         restart_count
@@ -190,51 +640,74 @@ impl AnalysisEngine {
                     continue;
                 }
 
+                let delay = Self::restart_delay(&config, restart_count);
                 warn!(
-                    "KataGo process died, attempting restart (attempt {})",
+                    "KataGo process died, attempting restart in {}s (attempt {})",
+                    delay.as_secs(),
                     restart_count + 1
                 );
-                thread::sleep(Duration::from_secs(RESTART_DELAY_SECS));
+                thread::sleep(delay);
+                if process_alive.load(Ordering::SeqCst) {
+                    // Respawned meanwhile (e.g. the human model was toggled)
+                    continue;
+                }
+
+                // Holding the process lock until the new process is in place keeps a
+                // deliberate replacement from being killed here
+                let mut process = process.lock().unwrap();
+                if process_alive.load(Ordering::SeqCst) {
+                    continue;
+                }
 
                 // Clean up old process
-                if let Some(mut old_process) = process.lock().unwrap().take() {
+                if let Some(mut old_process) = process.take() {
                     let _ = old_process.kill();
                     let _ = old_process.wait();
                 }
 
                 // Attempt to restart
-                match Self::spawn_katago_process(&config) {
+                let human_model = human_model.lock().unwrap().clone();
+                let model_path = model_path.lock().unwrap().clone();
+                match Self::spawn_katago_process(&config, &model_path, human_model.as_deref()) {
                     Ok((child, new_stdin, stdout, stderr)) => {
                         *stdin.lock().unwrap() = Some(new_stdin);
-                        *process.lock().unwrap() = Some(child);
+                        *process = Some(child);
                         process_alive.store(true, Ordering::SeqCst);
+                        *shared.started.lock().unwrap() = Instant::now();
+                        drop(process);
 
                         // Start new reader threads
-                        Self::spawn_reader_threads(
-                            stdout,
-                            stderr,
-                            pending_requests.clone(),
-                            process_alive.clone(),
-                        );
+                        Self::spawn_reader_threads(stdout, stderr, shared.clone());
 
                         info!("KataGo restarted successfully");
-                        restart_count += 1;
+                        restart_attempts.fetch_add(1, Ordering::SeqCst);
+                        shared.restarts.fetch_add(1, Ordering::Relaxed);
 
                         // Wait for KataGo to initialize
                         thread::sleep(Duration::from_secs(5));
                     }
                     Err(e) => {
                         error!("Failed to restart KataGo: {}", e);
-                        restart_count += 1;
+                        restart_attempts.fetch_add(1, Ordering::SeqCst);
                     }
                 }
                 continue;
             }
 
+            // Only a process that stays up counts as recovered, so one that crashes
+            // shortly after each restart still backs off and eventually gives up
+            if shared.started.lock().unwrap().elapsed() >= stable {
+                restart_attempts.store(0, Ordering::SeqCst);
+            }
+
+            if !config.keepalive_enabled {
+                continue;
+            }
+
             // Process is alive, send keepalive ping
             let ping = serde_json::json!({
-                "id": "keepalive",
-                "action": "query_version"
+                "id": KEEPALIVE_ID,
+                "action": config.keepalive_action.as_str()
             });
 
             let json = match serde_json::to_string(&ping) {
@@ -245,28 +718,44 @@ impl AnalysisEngine {
                 }
             };
 
-            let mut stdin_guard = stdin.lock().unwrap();
-            if let Some(ref mut stdin_ref) = *stdin_guard {
-                if let Err(e) = writeln!(stdin_ref, "{}", json) {
-                    warn!("Failed to send keepalive ping: {}", e);
-                    process_alive.store(false, Ordering::SeqCst);
-                } else if let Err(e) = stdin_ref.flush() {
-                    warn!("Failed to flush keepalive ping: {}", e);
-                    process_alive.store(false, Ordering::SeqCst);
-                } else {
-                    debug!("Sent keepalive ping to KataGo");
-                    // Reset restart count on successful ping
-                    restart_count = 0;
-                }
-            } else {
-                debug!("No stdin available for keepalive ping");
+            // Recorded first, so an answer arriving right away finds the record
+            *shared.last_keepalive.lock().unwrap() = Some(KeepaliveRecord {
+                sent_at: chrono::Utc::now(),
+                error: None,
+                answered_at: None,
+            });
+            let sent =
+                Self::write_lines(&stdin, &process, &process_alive, &protocol_stats, &[json]);
+            if let (Err(e), Some(ping)) = (&sent, shared.last_keepalive.lock().unwrap().as_mut()) {
+                ping.error = Some(e.to_string());
+            }
+            match sent {
+                Ok(()) => debug!("Sent keepalive ping to KataGo"),
+                Err(e) => warn!("Failed to send keepalive ping: {}", e),
             }
         }
     }
 
+    /// Delay before restart attempt `attempts + 1`: `restart_delay_secs`, doubled
+    /// for each attempt since the process was last stable, up to
+    /// `max_restart_delay_secs`
+    fn restart_delay(config: &KatagoConfig, attempts: u64) -> Duration {
+        let secs = config
+            .restart_delay_secs
+            .saturating_mul(1 << attempts.min(32));
+        Duration::from_secs(secs.min(config.max_restart_delay_secs))
+    }
+
+    /// Whether the monitor has given up restarting after `attempts`
+    fn restarts_exhausted(config: &KatagoConfig, attempts: u64) -> bool {
+        config.max_restart_attempts > 0 && attempts >= config.max_restart_attempts
+    }
+
     /// Spawn the KataGo process and return handles to it
     fn spawn_katago_process(
         config: &KatagoConfig,
+        model_path: &str,
+        human_model: Option<&str>,
     ) -> Result<(
         Child,
         ChildStdin,
@@ -276,24 +765,26 @@ impl AnalysisEngine {
         info!("Starting KataGo analysis engine");
         info!(
             "Config: katago={}, model={}, human_model={:?}, config={}",
-            config.katago_path, config.model_path, config.human_model_path, config.config_path
+            config.katago_path, model_path, human_model, config.config_path
         );
 
         let mut command = Command::new(&config.katago_path);
-        command
-            .arg("analysis")
-            .arg("-model")
-            .arg(&config.model_path);
+        command.arg("analysis").arg("-model").arg(model_path);
 
         // Add human model if configured
-        if let Some(ref human_model) = config.human_model_path {
+        if let Some(human_model) = human_model {
             info!("Human SL model enabled: {}", human_model);
             command.arg("-human-model").arg(human_model);
         }
 
+        command.arg("-config").arg(&config.config_path);
+        // Devices and other settings that differ between engines sharing a config file
+        if let Some(overrides) = config.override_config_arg() {
+            info!("Config overrides: {}", overrides);
+            command.arg("-override-config").arg(overrides);
+        }
+
         let mut cmd = command
-            .arg("-config")
-            .arg(&config.config_path)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -317,10 +808,14 @@ impl AnalysisEngine {
     fn spawn_reader_threads(
         stdout: std::process::ChildStdout,
         stderr: std::process::ChildStderr,
-        pending_requests: Arc<StdMutex<HashMap<String, oneshot::Sender<String>>>>,
-        process_alive: Arc<AtomicBool>,
+        shared: ProcessShared,
     ) {
-        // Spawn stderr reader thread
+        Self::spawn_stderr_reader(stderr);
+        Self::spawn_stdout_reader(stdout, shared);
+    }
+
+    /// Log KataGo's stderr, so a chatty process never blocks on a full pipe
+    fn spawn_stderr_reader(stderr: std::process::ChildStderr) {
         thread::spawn(move || {
             let reader = BufReader::new(stderr);
             for line in reader.lines() {
@@ -336,49 +831,120 @@ impl AnalysisEngine {
             }
             debug!("KataGo analysis stderr closed");
         });
+    }
+
+    /// Route KataGo's response lines to the waiting queries
+    fn spawn_stdout_reader(stdout: impl std::io::Read + Send + 'static, shared: ProcessShared) {
+        let ProcessShared {
+            pending_requests,
+            process_alive,
+            protocol_stats,
+            salvage,
+            generation,
+            last_keepalive,
+            ..
+        } = shared;
+        let spawned_generation = generation.load(Ordering::SeqCst);
+        let is_current = move || generation.load(Ordering::SeqCst) == spawned_generation;
 
-        // Spawn stdout reader thread
         let process_alive_clone = process_alive;
         thread::spawn(move || {
             let mut reader = BufReader::new(stdout);
             let mut line = String::new();
+            // Warnings precede the response to their query and are attached to it
+            let mut held_warnings: HashMap<String, Vec<EngineWarning>> = HashMap::new();
             loop {
                 line.clear();
                 match reader.read_line(&mut line) {
                     Ok(0) => {
                         info!("KataGo analysis stdout closed (EOF)");
-                        // Mark process as dead
-                        process_alive_clone.store(false, Ordering::SeqCst);
+                        // Mark process as dead, unless it was replaced on purpose, and
+                        // fail its in-flight queries instead of letting them time out
+                        if is_current() {
+                            process_alive_clone.store(false, Ordering::SeqCst);
+                            pending_requests.lock().unwrap().clear();
+                        }
                         break;
                     }
-                    Ok(_) => {
+                    Ok(bytes) => {
+                        protocol_stats
+                            .bytes_read
+                            .fetch_add(bytes as u64, Ordering::Relaxed);
                         let trimmed = line.trim();
                         debug!("KataGo analysis raw output: {}", trimmed);
 
                         // Parse ID from response to route it
                         if let Ok(value) = serde_json::from_str::<serde_json::Value>(trimmed) {
-                            if let Some(id) = value.get("id").and_then(|id| id.as_str()) {
+                            if value.get("action").is_some() {
+                                // Reply to query_version, clear_cache, or a keepalive ping
+                                ProtocolStats::count(&protocol_stats.action_responses);
+                                debug!("Received action response: {}", trimmed);
+                                let id = value.get("id").and_then(|id| id.as_str());
+                                if id == Some(KEEPALIVE_ID) {
+                                    if let Some(ping) = last_keepalive.lock().unwrap().as_mut() {
+                                        ping.answered_at = Some(chrono::Utc::now());
+                                    }
+                                }
+                                let pending =
+                                    id.and_then(|id| pending_requests.lock().unwrap().remove(id));
+                                if let Some(pending) = pending {
+                                    pending.waiter.deliver(trimmed.to_string(), false, false);
+                                }
+                            } else if let Some(warning) = parse_warning(&value) {
+                                let (id, warning) = warning;
+                                ProtocolStats::count(&protocol_stats.warning_lines);
+                                warn!("KataGo warning for query {}: {}", id, warning.message);
+                                if held_warnings.len() >= MAX_HELD_WARNINGS
+                                    && !held_warnings.contains_key(&id)
+                                {
+                                    held_warnings.clear();
+                                }
+                                held_warnings.entry(id).or_default().push(warning);
+                            } else if let Some(id) = value.get("id").and_then(|id| id.as_str()) {
+                                let response = match held_warnings.remove(id) {
+                                    Some(warnings) => with_warnings(value.clone(), warnings),
+                                    None => trimmed.to_string(),
+                                };
+                                let partial = value.get("isDuringSearch")
+                                    == Some(&serde_json::Value::Bool(true));
+                                let error = value.get("error").is_some();
                                 let mut requests = pending_requests.lock().unwrap();
-                                if let Some(sender) = requests.remove(id) {
-                                    if sender.send(trimmed.to_string()).is_err() {
-                                        warn!("Failed to send response to waiter for ID: {}", id);
+                                if let Some(PendingQuery { waiter, since }) = requests.remove(id) {
+                                    if !partial {
+                                        ProtocolStats::count(&protocol_stats.responses_received);
                                     }
+                                    if let Some(waiter) = waiter.deliver(response, partial, error) {
+                                        requests
+                                            .insert(id.to_string(), PendingQuery { waiter, since });
+                                    }
+                                } else if partial {
+                                    debug!("Dropped partial result for unknown ID: {}", id);
                                 } else {
                                     // This might be a log message or unexpected response
-                                    debug!("Received response for unknown or timed-out ID: {}", id);
+                                    ProtocolStats::count(&protocol_stats.unknown_id_responses);
+                                    if salvage.offer(id, &response) {
+                                        info!("Salvaged late result for timed-out query {}", id);
+                                    } else {
+                                        debug!("Received response for unknown ID: {}", id);
+                                    }
                                 }
                             } else {
-                                // Maybe a log line or something without ID (like query_version response)
+                                // Maybe a log line or something without ID
+                                ProtocolStats::count(&protocol_stats.unknown_id_responses);
                                 debug!("Received JSON without ID: {}", trimmed);
                             }
                         } else {
                             // Not JSON, probably a log line
+                            ProtocolStats::count(&protocol_stats.parse_failures);
                             debug!("Received non-JSON output: {}", trimmed);
                         }
                     }
                     Err(e) => {
                         error!("Error reading from KataGo analysis: {}", e);
-                        process_alive_clone.store(false, Ordering::SeqCst);
+                        if is_current() {
+                            process_alive_clone.store(false, Ordering::SeqCst);
+                            pending_requests.lock().unwrap().clear();
+                        }
                         break;
                     }
                 }
@@ -387,184 +953,663 @@ impl AnalysisEngine {
         });
     }
 
-    fn start_process(
-        &mut self,
-        pending_requests: Arc<StdMutex<HashMap<String, oneshot::Sender<String>>>>,
-    ) -> Result<()> {
-        let (cmd, stdin, stdout, stderr) = Self::spawn_katago_process(&self.config)?;
+    fn start_process(&self) -> Result<()> {
+        let human_model = self.human_model.lock().unwrap().clone();
+        let model_path = self.model_path();
+        let (cmd, stdin, stdout, stderr) =
+            Self::spawn_katago_process(&self.config, &model_path, human_model.as_deref())?;
 
         *self.stdin.lock().unwrap() = Some(stdin);
         *self.process.lock().unwrap() = Some(cmd);
 
         // Mark process as alive
         self.process_alive.store(true, Ordering::SeqCst);
+        *self.started.lock().unwrap() = Instant::now();
 
         // Spawn reader threads
-        Self::spawn_reader_threads(stdout, stderr, pending_requests, self.process_alive.clone());
+        Self::spawn_reader_threads(stdout, stderr, self.shared());
 
         Ok(())
     }
 
-    fn send_query(&self, query: &AnalysisQuery) -> Result<()> {
-        // Check if process is alive before sending
-        if !self.process_alive.load(Ordering::SeqCst) {
-            return Err(KatagoError::ProcessDied);
-        }
-
-        let json = serde_json::to_string(query)?;
-        debug!("Sending analysis query: {}", json);
-
-        let mut stdin = self.stdin.lock().unwrap();
-        let stdin = stdin.as_mut().ok_or(KatagoError::ProcessDied)?;
+    /// Configured human SL model, whether or not it is loaded
+    pub fn human_model_path(&self) -> Option<&str> {
+        self.config.human_model_path.as_deref()
+    }
 
-        writeln!(stdin, "{}", json)?;
-        debug!("Written query to stdin, flushing...");
-        match stdin.flush() {
-            Ok(_) => debug!("Stdin flushed successfully"),
-            Err(e) => {
-                error!("Failed to flush stdin: {}", e);
-                self.process_alive.store(false, Ordering::SeqCst);
-                return Err(KatagoError::ProcessDied);
-            }
-        }
-        Ok(())
+    /// Whether the running process has the human SL model loaded
+    pub fn human_model_loaded(&self) -> bool {
+        self.human_model.lock().unwrap().is_some()
     }
 
-    /// Check if KataGo process is running
-    pub fn is_alive(&self) -> bool {
-        self.process_alive.load(Ordering::SeqCst)
+    /// Name of the loaded human SL model, as requests select it
+    pub fn loaded_human_model(&self) -> Option<String> {
+        self.human_model
+            .lock()
+            .unwrap()
+            .as_deref()
+            .map(|path| human_model_name(path).to_string())
     }
 
-    /// Validates if a move coordinate is valid for the given board size
-    /// Go coordinates: A-Z (excluding I), 1-boardSize
-    fn is_valid_move(move_str: &str, board_x_size: u8, board_y_size: u8) -> bool {
-        if move_str.len() < 2 {
-            return false;
+    /// Load or unload the configured human SL model by restarting KataGo with or
+    /// without `-human-model`. Queries in flight on the old process fail.
+    pub fn set_human_model(&self, loaded: bool) -> Result<()> {
+        let wanted = if loaded {
+            Some(self.config.human_model_path.clone().ok_or_else(|| {
+                KatagoError::InvalidCommand("no human_model_path is configured".to_string())
+            })?)
+        } else {
+            None
+        };
+        let _replacing = self.replacing.lock().unwrap();
+        {
+            let mut human_model = self.human_model.lock().unwrap();
+            if *human_model == wanted {
+                return Ok(());
+            }
+            *human_model = wanted;
         }
+        // Cached results were computed with or without the human model's fields
+        self.results.clear();
+        info!(
+            "{} human SL model, restarting KataGo",
+            if loaded { "Loading" } else { "Unloading" }
+        );
 
-        // Handle special case "pass"
-        if move_str.eq_ignore_ascii_case("pass") {
-            return true;
+        // Holding the process lock keeps the monitor from restarting concurrently
+        let mut process = self.process.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        if let Some(mut old_process) = process.take() {
+            let _ = old_process.kill();
+            let _ = old_process.wait();
         }
+        // Fail queries sent to the old process now instead of at their timeout
+        self.pending_requests.lock().unwrap().clear();
+
+        let human_model = self.human_model.lock().unwrap().clone();
+        let model_path = self.model_path();
+        match Self::spawn_katago_process(&self.config, &model_path, human_model.as_deref()) {
+            Ok((child, stdin, stdout, stderr)) => {
+                *self.stdin.lock().unwrap() = Some(stdin);
+                *process = Some(child);
+                self.process_alive.store(true, Ordering::SeqCst);
+                *self.started.lock().unwrap() = Instant::now();
+                Self::spawn_reader_threads(stdout, stderr, self.shared());
+                Ok(())
+            }
+            Err(e) => {
+                // The monitor keeps retrying with the new setting
+                *self.stdin.lock().unwrap() = None;
+                self.process_alive.store(false, Ordering::SeqCst);
+                Err(e)
+            }
+        }
+    }
 
-        // Parse column (letter) and row (number)
-        let col_char = move_str.chars().next().unwrap().to_ascii_uppercase();
-        let row_str = &move_str[1..];
-
-        // Validate column (A-Z, excluding I)
-        // Column A=1, B=2, ..., H=8, J=9, K=10, ...
-        let col_num = if col_char < 'I' {
-            col_char as u8 - b'A' + 1
-        } else if col_char > 'I' {
-            col_char as u8 - b'A' // B is offset by 1 less because we skip I
-        } else {
-            // I is not a valid column in Go
-            return false;
+    /// Switch to another model without downtime: start KataGo with it, wait until
+    /// it answers, then send new queries there. The old process finishes the
+    /// queries it has (or is killed after the move timeout). Returns the previous
+    /// model's path; on failure the old process keeps serving.
+    pub fn switch_model(&self, model_path: &str) -> Result<String> {
+        let _replacing = self.replacing.lock().unwrap();
+        let human_model = self.human_model.lock().unwrap().clone();
+        info!("Switching KataGo to model {}", model_path);
+        let (mut child, mut stdin, stdout, stderr) =
+            Self::spawn_katago_process(&self.config, model_path, human_model.as_deref())?;
+        Self::spawn_stderr_reader(stderr);
+        let stdout = match Self::wait_until_ready(&mut stdin, stdout) {
+            Ok(stdout) => stdout,
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
         };
 
-        if col_num > board_x_size {
-            return false;
-        }
+        let (old_process, old_stdin) = {
+            // Holding the process lock keeps the monitor from restarting concurrently
+            let mut process = self.process.lock().unwrap();
+            // The old reader keeps delivering its responses, but its EOF no longer
+            // marks the engine dead
+            self.generation.fetch_add(1, Ordering::SeqCst);
+            let old_stdin = self.stdin.lock().unwrap().replace(stdin);
+            let old_process = process.replace(child);
+            self.process_alive.store(true, Ordering::SeqCst);
+            *self.started.lock().unwrap() = Instant::now();
+            Self::spawn_stdout_reader(stdout, self.shared());
+            (old_process, old_stdin)
+        };
+        let previous = std::mem::replace(
+            &mut *self.model_path.lock().unwrap(),
+            model_path.to_string(),
+        );
+        self.results.clear();
 
-        // Validate row
-        if let Ok(row_num) = row_str.parse::<u8>() {
-            row_num >= 1 && row_num <= board_y_size
-        } else {
-            false
+        // KataGo answers the queries it has, then exits once its stdin closes
+        drop(old_stdin);
+        if let Some(old_process) = old_process {
+            let grace = Duration::from_secs(self.move_timeout_secs());
+            thread::spawn(move || Self::drain(old_process, grace));
         }
+        info!("Switched KataGo from model {} to {}", previous, model_path);
+        Ok(previous)
     }
 
-    /// Returns the last valid column letter for a given board size
-    fn column_letter_for_size(board_size: u8) -> char {
-        // A=1, B=2, ..., H=8, J=9, K=10, ...
-        if board_size <= 8 {
-            (b'A' + board_size - 1) as char
-        } else {
-            // Skip 'I' after H
-            (b'A' + board_size) as char
+    /// Kill KataGo and start it again, e.g. when it stopped answering. Queries in
+    /// flight fail right away with `KatagoError::Restarted`, and new ones fail with
+    /// `ProcessDied` until the new process has loaded its model, which is when this
+    /// returns. If it fails to load, the monitor keeps retrying.
+    pub fn restart(&self) -> Result<RestartReport> {
+        let _replacing = self.replacing.lock().unwrap();
+        info!("Restarting KataGo on request");
+
+        // Holding the process lock keeps the monitor from restarting concurrently
+        let mut process = self.process.lock().unwrap();
+        self.process_alive.store(false, Ordering::SeqCst);
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.stdin.lock().unwrap() = None;
+        if let Some(mut old_process) = process.take() {
+            let _ = old_process.kill();
+            let _ = old_process.wait();
         }
-    }
+        let failed_queries = self.fail_pending();
+
+        let human_model = self.human_model.lock().unwrap().clone();
+        let model_path = self.model_path();
+        let (mut child, mut stdin, stdout, stderr) =
+            Self::spawn_katago_process(&self.config, &model_path, human_model.as_deref())?;
+        Self::spawn_stderr_reader(stderr);
+        let stdout = match Self::wait_until_ready(&mut stdin, stdout) {
+            Ok(stdout) => stdout,
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        };
 
-    async fn wait_for_response(&self, id: &str, timeout_secs: u64) -> Result<AnalysisResult> {
-        let (tx, rx) = oneshot::channel();
+        let pid = child.id();
+        self.restart_attempts.store(0, Ordering::SeqCst);
+        *self.stdin.lock().unwrap() = Some(stdin);
+        *process = Some(child);
+        self.process_alive.store(true, Ordering::SeqCst);
+        *self.started.lock().unwrap() = Instant::now();
+        Self::spawn_stdout_reader(stdout, self.shared());
+        info!(
+            "Restarted KataGo as process {}, failing {} queries in flight",
+            pid, failed_queries
+        );
+        Ok(RestartReport {
+            pid,
+            failed_queries,
+        })
+    }
 
-        {
-            let mut requests = self.pending_requests.lock().unwrap();
-            requests.insert(id.to_string(), tx);
+    /// Fail every query waiting for the process with `KatagoError::Restarted`;
+    /// returns how many there were
+    fn fail_pending(&self) -> usize {
+        let waiters: Vec<_> = self.pending_requests.lock().unwrap().drain().collect();
+        let failed = waiters.len();
+        for (id, pending) in waiters {
+            let line = serde_json::json!({
+                "id": id,
+                "error": "KataGo was restarted",
+                RESTARTED_FIELD: true,
+            });
+            pending.waiter.deliver(line.to_string(), false, true);
         }
+        failed
+    }
 
-        let duration = Duration::from_secs(timeout_secs);
+    /// Ask a starting process for its version and wait for the answer, which comes
+    /// once the model is loaded. Returns stdout with the lines after the answer.
+    fn wait_until_ready(
+        stdin: &mut ChildStdin,
+        stdout: std::process::ChildStdout,
+    ) -> Result<BufReader<std::process::ChildStdout>> {
+        let ping = serde_json::json!({ "id": READY_PING_ID, "action": "query_version" });
+        writeln!(stdin, "{}", ping)?;
+        stdin.flush()?;
 
-        match timeout(duration, rx).await {
-            Ok(Ok(response)) => {
-                // Parse the response
-                match serde_json::from_str::<AnalysisResult>(&response) {
-                    Ok(result) => Ok(result),
-                    Err(e) => {
-                        // Check for error response
-                        if let Ok(error) = serde_json::from_str::<serde_json::Value>(&response) {
-                            if let Some(err_msg) = error.get("error") {
-                                error!("KataGo returned error: {}", err_msg);
-                                return Err(KatagoError::ResponseError(err_msg.to_string()));
-                            }
+        let (tx, rx) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match reader.read_line(&mut line) {
+                    Ok(0) | Err(_) => {
+                        let _ = tx.send(None);
+                        return;
+                    }
+                    Ok(_) => {
+                        let ready = serde_json::from_str::<serde_json::Value>(&line)
+                            .is_ok_and(|value| value["id"] == READY_PING_ID);
+                        if ready {
+                            let _ = tx.send(Some(reader));
+                            return;
                         }
-                        Err(KatagoError::ParseError(e.to_string()))
                     }
                 }
             }
-            Ok(Err(_)) => {
-                // Sender dropped (process died?)
-                Err(KatagoError::ProcessDied)
-            }
-            Err(_) => {
-                // Timeout
-                {
-                    let mut requests = self.pending_requests.lock().unwrap();
-                    requests.remove(id);
-                }
-                Err(KatagoError::Timeout(timeout_secs))
-            }
+        });
+        match rx.recv_timeout(Duration::from_secs(MODEL_STARTUP_TIMEOUT_SECS)) {
+            Ok(Some(reader)) => Ok(reader),
+            Ok(None) => Err(KatagoError::ProcessStartFailed(
+                "KataGo exited while loading the model".to_string(),
+            )),
+            Err(_) => Err(KatagoError::ProcessStartFailed(format!(
+                "KataGo did not become ready within {} seconds",
+                MODEL_STARTUP_TIMEOUT_SECS
+            ))),
         }
     }
 
-    pub async fn analyze(&self, request: &AnalysisRequest) -> Result<AnalysisResponse> {
-        let request_id = request
-            .request_id
-            .clone()
-            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
-
-        // Validate moves for the given board size
-        for mv in &request.moves {
-            if !Self::is_valid_move(mv.coord(), request.board_x_size, request.board_y_size) {
-                warn!(
-                    "Invalid move '{}' for {}x{} board (valid columns: A-{}, skipping I)",
-                    mv.coord(),
-                    request.board_x_size,
-                    request.board_y_size,
-                    Self::column_letter_for_size(request.board_x_size)
-                );
+    /// Wait for a replaced process to exit, killing it after `grace`
+    fn drain(mut process: Child, grace: Duration) {
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = process.try_wait() {
+                info!("Replaced KataGo process exited");
+                return;
             }
+            thread::sleep(Duration::from_millis(100));
         }
+        warn!("Replaced KataGo process still busy, killing it");
+        let _ = process.kill();
+        let _ = process.wait();
+    }
 
-        // Convert moves to KataGo format: [["b", "D4"], ["w", "Q16"], ...]
-        // Note: KataGo requires lowercase b/w (confirmed by Python implementation and testing)
-        //
-        // Moves can be provided in two formats:
-        // 1. Simple: ["D4", "Q16"] - colors inferred from alternation starting with initial_player
-        // 2. Explicit: [["W", "D4"], ["B", "Q16"]] - colors specified directly
-        //
-        // If ANY move has explicit color, we use explicit colors for ALL moves
-        // (mixing formats is not supported)
-        let has_explicit_colors = request.moves.iter().any(|m| m.color().is_some());
+    fn send_query(&self, id: &str, json: String) -> Result<()> {
+        // Check if process is alive before sending
+        if !self.process_alive.load(Ordering::SeqCst) {
+            return Err(KatagoError::ProcessDied);
+        }
 
-        let katago_moves = if has_explicit_colors {
-            // Use explicit colors from the request
-            request
-                .moves
-                .iter()
-                .map(|mv| {
-                    let color = mv
-                        .color()
+        let _span = info_span!("stdin_write", query_id = id).entered();
+        debug!("Sending analysis query: {}", json);
+
+        if self.config.batch_window_ms == 0 {
+            return Self::write_lines(
+                &self.stdin,
+                &self.process,
+                &self.process_alive,
+                &self.protocol_stats,
+                &[json],
+            );
+        }
+
+        // The first query of a window schedules the write for the whole batch
+        let opens_window = {
+            let mut batch = self.batch.lock().unwrap();
+            batch.push((id.to_string(), json));
+            batch.len() == 1
+        };
+        if opens_window {
+            let window = Duration::from_millis(self.config.batch_window_ms);
+            let batch = self.batch.clone();
+            let stdin = self.stdin.clone();
+            let process = self.process.clone();
+            let alive = self.process_alive.clone();
+            let stats = self.protocol_stats.clone();
+            let pending = self.pending_requests.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(window).await;
+                let queries = std::mem::take(&mut *batch.lock().unwrap());
+                let (ids, lines): (Vec<String>, Vec<String>) = queries.into_iter().unzip();
+                debug!("Writing batch of {} queries", lines.len());
+                stats.record_batch(lines.len());
+                if let Err(e) = Self::write_lines(&stdin, &process, &alive, &stats, &lines) {
+                    // Dropping the waiters' senders fails them now instead of at timeout
+                    warn!("Failed to write batch of {} queries: {}", ids.len(), e);
+                    let mut pending = pending.lock().unwrap();
+                    for id in &ids {
+                        pending.remove(id);
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Write query lines to KataGo's stdin with a single flush.
+    ///
+    /// A failed write only marks the engine dead if KataGo has actually exited;
+    /// while the process runs, just the queries of this write fail.
+    fn write_lines(
+        stdin: &StdMutex<Option<ChildStdin>>,
+        process: &StdMutex<Option<Child>>,
+        alive: &AtomicBool,
+        stats: &ProtocolStats,
+        lines: &[String],
+    ) -> Result<()> {
+        let written = {
+            let mut stdin = stdin.lock().unwrap();
+            let stdin = stdin.as_mut().ok_or(KatagoError::ProcessDied)?;
+            let written = lines
+                .iter()
+                .try_for_each(|json| {
+                    writeln!(stdin, "{}", json)?;
+                    stats.record_write(json);
+                    Ok(())
+                })
+                .and_then(|()| stdin.flush());
+            if written.is_err() {
+                // Terminate a partially written line so the next query parses
+                let _ = stdin.write_all(b"\n").and_then(|()| stdin.flush());
+            }
+            written
+        };
+        // Probe after releasing stdin: restarts lock the process before stdin
+        let Err(e) = written else {
+            debug!("Wrote {} queries to stdin", lines.len());
+            return Ok(());
+        };
+        if Self::process_exited(process) {
+            error!("Failed to write to KataGo, which has exited: {}", e);
+            alive.store(false, Ordering::SeqCst);
+            Err(KatagoError::ProcessDied)
+        } else {
+            warn!("Failed to write to KataGo, which is still running: {}", e);
+            ProtocolStats::count(&stats.write_failures);
+            Err(KatagoError::IoError(e))
+        }
+    }
+
+    /// Whether KataGo has exited (or was never started)
+    fn process_exited(process: &StdMutex<Option<Child>>) -> bool {
+        match process.lock().unwrap().as_mut() {
+            Some(child) => !matches!(child.try_wait(), Ok(None)),
+            None => true,
+        }
+    }
+
+    /// Check if KataGo process is running
+    pub fn is_alive(&self) -> bool {
+        self.process_alive.load(Ordering::SeqCst)
+    }
+
+    /// Whether the warm-up analysis succeeded
+    pub fn is_warmed_up(&self) -> bool {
+        self.warmed_up.load(Ordering::SeqCst)
+    }
+
+    /// Number of queries sent to KataGo and still awaiting a response
+    pub fn pending_count(&self) -> usize {
+        self.pending_requests.lock().unwrap().len()
+    }
+
+    /// Ids of the queries awaiting a response and how long they have, oldest first
+    pub fn pending_queries(&self) -> Vec<(String, Duration)> {
+        let mut queries: Vec<_> = self
+            .pending_requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, pending)| (id.clone(), pending.since.elapsed()))
+            .collect();
+        queries.sort_by_key(|(_, age)| std::cmp::Reverse(*age));
+        queries
+    }
+
+    /// Process id of the running KataGo process
+    pub fn pid(&self) -> Option<u32> {
+        self.process.lock().unwrap().as_ref().map(Child::id)
+    }
+
+    /// Restart attempts since the process last stayed up for `restart_stable_secs`
+    pub fn restart_attempts(&self) -> u64 {
+        self.restart_attempts.load(Ordering::SeqCst)
+    }
+
+    /// Restart attempts before the monitor gives up; 0 never gives up
+    pub fn max_restart_attempts(&self) -> u64 {
+        self.config.max_restart_attempts
+    }
+
+    /// Whether the monitor gave up restarting the dead process, until an admin
+    /// restarts it
+    pub fn restarts_given_up(&self) -> bool {
+        !self.is_alive() && Self::restarts_exhausted(&self.config, self.restart_attempts())
+    }
+
+    pub fn last_keepalive(&self) -> Option<KeepaliveRecord> {
+        self.last_keepalive.lock().unwrap().clone()
+    }
+
+    /// Queries waiting for a place in KataGo
+    pub fn queued_count(&self) -> usize {
+        self.queue.queued()
+    }
+
+    /// Queries admitted to KataGo and not yet answered
+    pub fn in_flight_count(&self) -> usize {
+        self.queue.in_flight()
+    }
+
+    /// Times the process was restarted after dying
+    pub fn restart_count(&self) -> u64 {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    /// Seconds since the running process was started
+    pub fn uptime_secs(&self) -> u64 {
+        self.started.lock().unwrap().elapsed().as_secs()
+    }
+
+    /// When an analysis last got its result from KataGo, if any has yet
+    pub fn last_success(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        *self.last_success.lock().unwrap()
+    }
+
+    fn record_success(&self) {
+        *self.last_success.lock().unwrap() = Some(chrono::Utc::now());
+    }
+
+    /// Record a search's result that was sent to KataGo at `sent`
+    fn record_search(&self, result: &AnalysisResult, sent: Instant) {
+        self.record_success();
+        if let Some(root) = &result.root_info {
+            self.visit_rate.record(root.visits, sent.elapsed());
+        }
+        let visits = result.root_info.as_ref().map_or(0, |root| root.visits);
+        self.throughput.record(visits, sent);
+    }
+
+    /// Visits a search can do within `target` at this engine's recent speed;
+    /// None until it has measured one
+    pub fn visit_budget(&self, target: Duration) -> Option<u32> {
+        self.visit_rate.budget(target)
+    }
+
+    pub fn visits_per_second(&self) -> Option<f64> {
+        self.visit_rate.visits_per_second()
+    }
+
+    /// Rolling visit rate, utilization, and latency by visit count of the
+    /// searches KataGo ran; keepalive pings don't count as searches
+    pub fn throughput(&self) -> ThroughputSnapshot {
+        let running: Vec<Instant> = self
+            .pending_requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(id, _)| id.as_str() != KEEPALIVE_ID)
+            .map(|(_, pending)| pending.since)
+            .collect();
+        self.throughput.snapshot(&running)
+    }
+
+    /// Queries that timed out
+    pub fn timeout_count(&self) -> u64 {
+        self.timeouts.load(Ordering::Relaxed)
+    }
+
+    fn move_timeout_secs(&self) -> u64 {
+        self.move_timeout_secs.load(Ordering::Relaxed)
+    }
+
+    /// Apply the settings of a reloaded `[katago]` section that take effect
+    /// without restarting KataGo (see `Config::reload`)
+    pub fn apply_settings(&self, config: &KatagoConfig) {
+        self.move_timeout_secs
+            .store(config.move_timeout_secs, Ordering::Relaxed);
+        self.queue.set_max_queued(config.max_queued);
+        self.results.resize(
+            config.result_cache_entries,
+            Duration::from_secs(config.result_cache_ttl_secs),
+        );
+    }
+
+    /// Key of a query's result in the cache backend. Unlike the in-memory cache,
+    /// results there outlive the process and its models (and are shared with other
+    /// servers), so the key names the models too.
+    fn backend_key(&self, query: &AnalysisQuery) -> String {
+        serde_json::json!({
+            "model": *self.model_path.lock().unwrap(),
+            "humanModel": *self.human_model.lock().unwrap(),
+            "query": query.canonical(),
+        })
+        .to_string()
+    }
+
+    /// Kind and counters of the cache backend, if there is one
+    pub fn backend_counts(&self) -> Option<(&'static str, BackendCounts)> {
+        self.backend
+            .as_ref()
+            .map(|backend| (backend.kind(), backend.counts()))
+    }
+
+    /// Requests answered by an identical request's search instead of their own
+    pub fn joined_count(&self) -> u64 {
+        self.in_flight.joined_count()
+    }
+
+    /// Hits, misses, and entries of the result cache
+    pub fn result_cache_counts(&self) -> CacheCounts {
+        self.results.counts()
+    }
+
+    /// Counters for the stdin/stdout protocol since startup (across restarts)
+    pub fn protocol_stats(&self) -> ProtocolStatsSnapshot {
+        self.protocol_stats.snapshot()
+    }
+
+    /// Recent analysis failures, newest first
+    pub fn recent_errors(&self) -> Vec<EngineErrorRecord> {
+        self.recent_errors
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    fn record_error(&self, request_id: &str, error: &KatagoError) {
+        if let KatagoError::Timeout(_) = error {
+            self.timeouts.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut errors = self.recent_errors.lock().unwrap();
+        if errors.len() == MAX_RECENT_ERRORS {
+            errors.pop_front();
+        }
+        errors.push_back(EngineErrorRecord {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            request_id: request_id.to_string(),
+            message: error.to_string(),
+        });
+    }
+
+    /// The request's moves that are not on its board, with their index in `moves`
+    pub fn invalid_moves(request: &AnalysisRequest) -> Vec<InvalidMove> {
+        request
+            .moves
+            .iter()
+            .enumerate()
+            .filter(|(_, mv)| {
+                !Self::is_valid_move(mv.coord(), request.board_x_size, request.board_y_size)
+            })
+            .map(|(index, mv)| InvalidMove {
+                index,
+                move_coord: mv.coord().to_string(),
+            })
+            .collect()
+    }
+
+    /// Validates if a move coordinate is valid for the given board size
+    /// Go coordinates: A-Z (excluding I), 1-boardSize
+    fn is_valid_move(move_str: &str, board_x_size: u8, board_y_size: u8) -> bool {
+        if move_str.len() < 2 || !move_str.is_ascii() {
+            return false;
+        }
+
+        // Handle special case "pass"
+        if move_str.eq_ignore_ascii_case("pass") {
+            return true;
+        }
+
+        // Parse column (letter) and row (number)
+        let col_char = move_str.chars().next().unwrap().to_ascii_uppercase();
+        let row_str = &move_str[1..];
+        if !col_char.is_ascii_uppercase() {
+            return false;
+        }
+
+        // Validate column (A-Z, excluding I)
+        // Column A=1, B=2, ..., H=8, J=9, K=10, ...
+        let col_num = if col_char < 'I' {
+            col_char as u8 - b'A' + 1
+        } else if col_char > 'I' {
+            col_char as u8 - b'A' // B is offset by 1 less because we skip I
+        } else {
+            // I is not a valid column in Go
+            return false;
+        };
+
+        if col_num > board_x_size {
+            return false;
+        }
+
+        // Validate row
+        if let Ok(row_num) = row_str.parse::<u8>() {
+            row_num >= 1 && row_num <= board_y_size
+        } else {
+            false
+        }
+    }
+
+    /// Returns the last valid column letter for a given board size
+    fn column_letter_for_size(board_size: u8) -> char {
+        // A=1, B=2, ..., H=8, J=9, K=10, ...
+        if board_size <= 8 {
+            (b'A' + board_size - 1) as char
+        } else {
+            // Skip 'I' after H
+            (b'A' + board_size) as char
+        }
+    }
+
+    /// Convert request moves to KataGo format: [["b", "D4"], ["w", "Q16"], ...]
+    /// Note: KataGo requires lowercase b/w (confirmed by Python implementation and testing)
+    ///
+    /// Moves can be provided in two formats:
+    /// 1. Simple: ["D4", "Q16"] - colors inferred from alternation starting with initial_player
+    /// 2. Explicit: [["W", "D4"], ["B", "Q16"]] - colors specified directly
+    ///
+    /// If ANY move has explicit color, we use explicit colors for ALL moves
+    /// (mixing formats is not supported)
+    pub fn katago_moves(request: &AnalysisRequest) -> Vec<Vec<String>> {
+        let has_explicit_colors = request.moves.iter().any(|m| m.color().is_some());
+
+        if has_explicit_colors {
+            // Use explicit colors from the request
+            request
+                .moves
+                .iter()
+                .map(|mv| {
+                    let color = mv
+                        .color()
                         .expect("mixed move formats not supported")
                         .to_lowercase();
                     vec![color, mv.coord().to_string()]
@@ -572,35 +1617,62 @@ impl AnalysisEngine {
                 .collect()
         } else {
             // Infer colors from alternation
-            let has_handicap = request
-                .initial_stones
-                .as_ref()
-                .map(|s| !s.is_empty())
-                .unwrap_or(false);
-            // Use initial_player if provided, otherwise infer from handicap
-            let first_player = request
-                .initial_player
-                .as_ref()
-                .map(|p| p.to_lowercase())
-                .unwrap_or_else(|| {
-                    if has_handicap {
-                        "w".to_string() // White plays first in handicap games
-                    } else {
-                        "b".to_string() // Black plays first normally
-                    }
-                });
-            let mut color = first_player.as_str();
+            let mut color = Self::first_player(request);
             let mut moves = Vec::new();
             for mv in &request.moves {
                 moves.push(vec![color.to_string(), mv.coord().to_string()]);
                 color = if color == "b" { "w" } else { "b" };
             }
             moves
-        };
+        }
+    }
+
+    /// Rules sent to KataGo: the request's, else guessed from komi. Only 6.5 is
+    /// taken for territory scoring; whole komi (7 on 9x9, 0) is as common under
+    /// area rules, and 0.5 is handicap komi under either.
+    pub fn rules(request: &AnalysisRequest) -> Rules {
+        request.rules.clone().unwrap_or_else(|| {
+            if (Self::komi(request) - 6.5).abs() < 0.01 {
+                "japanese".into()
+            } else {
+                "chinese".into()
+            }
+        })
+    }
+
+    pub fn komi(request: &AnalysisRequest) -> f32 {
+        request.komi.unwrap_or(7.5)
+    }
 
-        // Convert initial_stones from API format (tuples) to KataGo format (vecs)
-        // API: [("B", "D16"), ("B", "Q4")] -> KataGo: [["B", "D16"], ["B", "Q4"]]
-        let initial_stones: Vec<Vec<String>> = request
+    /// Player to move at turn 0 ("b" or "w")
+    /// Uses initial_player if provided, otherwise infers from handicap stones
+    fn first_player(request: &AnalysisRequest) -> &'static str {
+        let has_handicap = request
+            .initial_stones
+            .as_ref()
+            .map(|s| !s.is_empty())
+            .unwrap_or(false);
+        match request.initial_player.as_deref() {
+            Some(p) if p.eq_ignore_ascii_case("w") || p.eq_ignore_ascii_case("white") => "w",
+            Some(_) => "b",
+            None if has_handicap => "w", // White plays first in handicap games
+            None => "b",                 // Black plays first normally
+        }
+    }
+
+    /// Player to move after all request moves have been played ("b" or "w")
+    pub fn next_player(request: &AnalysisRequest) -> &'static str {
+        match Self::katago_moves(request).last() {
+            Some(last) if last[0] == "b" => "w",
+            Some(_) => "b",
+            None => Self::first_player(request),
+        }
+    }
+
+    /// Convert initial_stones from API format (tuples) to KataGo format (vecs)
+    /// API: [("B", "D16"), ("B", "Q4")] -> KataGo: [["B", "D16"], ["B", "Q4"]]
+    pub fn katago_initial_stones(request: &AnalysisRequest) -> Vec<Vec<String>> {
+        request
             .initial_stones
             .as_ref()
             .map(|stones| {
@@ -609,55 +1681,467 @@ impl AnalysisEngine {
                     .map(|(color, coord)| vec![color.clone(), coord.clone()])
                     .collect()
             })
-            .unwrap_or_default();
+            .unwrap_or_default()
+    }
 
-        let query = AnalysisQuery {
-            id: request_id.clone(),
-            initial_stones,
-            moves: katago_moves,
-            rules: request.rules.clone().unwrap_or_else(|| {
-                // Auto-detect rules from komi
-                let komi = request.komi.unwrap_or(7.5);
-                if komi == komi.floor() || (komi - 6.5).abs() < 0.01 {
-                    "japanese".to_string()
-                } else {
-                    "chinese".to_string()
+    /// Wait for the queue to let a query with `priority` through to KataGo; the
+    /// query must be answered before the ticket is dropped
+    async fn admit(&self, priority: Option<i32>) -> Result<Ticket> {
+        self.queue
+            .admit(priority.unwrap_or(0))
+            .instrument(info_span!("queue_wait"))
+            .await
+            .map_err(|full| {
+                warn!("Rejecting query: {} queries already waiting", full.queued);
+                KatagoError::QueueFull(full.queued)
+            })
+    }
+
+    /// Seconds to wait for a query's response: `move_timeout_secs`, or longer when
+    /// the request allows the search more time with maxTimeSecs
+    fn timeout_secs(&self, request: &AnalysisRequest) -> u64 {
+        let move_timeout = self.move_timeout_secs();
+        match request.max_time_secs {
+            Some(max_time) => move_timeout.max(max_time.ceil() as u64 + MAX_TIME_GRACE_SECS),
+            None => move_timeout,
+        }
+    }
+
+    /// Send query `id` and wait for its response line. The waiter is registered
+    /// before the write, so a response that arrives immediately still finds it.
+    async fn query(
+        &self,
+        id: &str,
+        json: String,
+        cache_key: u64,
+        timeout_secs: u64,
+    ) -> Result<String> {
+        let (tx, rx) = oneshot::channel();
+        self.pending_requests
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), PendingQuery::new(Waiter::Final(tx)));
+        if let Err(e) = self.send_query(id, json) {
+            self.pending_requests.lock().unwrap().remove(id);
+            return Err(e);
+        }
+        self.wait_for_response(id, rx, cache_key, timeout_secs)
+            .await
+    }
+
+    /// Wait for the response line to query `id`. On timeout the query is handed to the
+    /// salvage store under `cache_key`, so a late result can still serve a retry.
+    async fn wait_for_response(
+        &self,
+        id: &str,
+        rx: oneshot::Receiver<String>,
+        cache_key: u64,
+        timeout_secs: u64,
+    ) -> Result<String> {
+        let duration = Duration::from_secs(timeout_secs);
+
+        let wait = timeout(duration, rx).instrument(info_span!("katago_response", query_id = id));
+        match wait.await {
+            Ok(Ok(response)) => {
+                debug!("Received response to query {}", id);
+                Ok(response)
+            }
+            Ok(Err(_)) => {
+                // Sender dropped (process died?)
+                Err(KatagoError::ProcessDied)
+            }
+            Err(_) => {
+                // Timeout
+                {
+                    let mut requests = self.pending_requests.lock().unwrap();
+                    requests.remove(id);
                 }
+                self.salvage.expect(id, cache_key);
+                Err(KatagoError::Timeout(timeout_secs))
+            }
+        }
+    }
+
+    fn parse_result(response: &str) -> Result<AnalysisResult> {
+        // Check for an error response first: every result field is optional, so an
+        // error line would otherwise parse as an empty result
+        if let Ok(error) = serde_json::from_str::<serde_json::Value>(response) {
+            if error[CANCELLED_FIELD].as_bool() == Some(true) {
+                let id = error["id"].as_str().unwrap_or_default();
+                return Err(KatagoError::Cancelled(id.to_string()));
+            }
+            if error[RESTARTED_FIELD].as_bool() == Some(true) {
+                let id = error["id"].as_str().unwrap_or_default();
+                return Err(KatagoError::Restarted(id.to_string()));
+            }
+            if let Some(err_msg) = error.get("error") {
+                error!("KataGo returned error: {}", err_msg);
+                return Err(KatagoError::ResponseError(err_msg.to_string()));
+            }
+        }
+        serde_json::from_str::<AnalysisResult>(response)
+            .map_err(|e| KatagoError::ParseError(e.to_string()))
+    }
+
+    pub async fn analyze(&self, request: &AnalysisRequest) -> Result<AnalysisResponse> {
+        self.search(request, true).await
+    }
+
+    /// Analyze with a new search, even if a result for the query is cached or
+    /// being searched; the result replaces the cached one
+    pub async fn reanalyze(&self, request: &AnalysisRequest) -> Result<AnalysisResponse> {
+        self.search(request, false).await
+    }
+
+    async fn search(&self, request: &AnalysisRequest, cached: bool) -> Result<AnalysisResponse> {
+        let mut timing = Timing::start();
+        let request_id = request
+            .request_id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let query = Self::build_query(request, &request_id, None, None);
+
+        let cache_key = query.cache_key();
+        let backend_key = self.backend.as_ref().map(|_| self.backend_key(&query));
+        if cached {
+            if let Some(response) = self.results.get(cache_key) {
+                debug!("Serving cached result for request {}", request_id);
+                let result = Self::parse_result(&response)?;
+                return Ok(timing.stamp(self.to_response(request, &request_id, result)));
+            }
+            if let (Some(backend), Some(key)) = (&self.backend, &backend_key) {
+                if let Some(response) = backend.get(key).await {
+                    debug!(
+                        "Serving result from the {} cache for request {}",
+                        backend.kind(),
+                        request_id
+                    );
+                    let result = Self::parse_result(&response)?;
+                    self.results.insert(cache_key, response);
+                    return Ok(timing.stamp(self.to_response(request, &request_id, result)));
+                }
+            }
+        }
+
+        // A retry of a query that timed out may already have its late result
+        let result = match self.salvage.get(cache_key).filter(|_| cached) {
+            Some(response) => {
+                info!("Serving salvaged result for request {}", request_id);
+                let result = Self::parse_result(&response)?;
+                // Cached like a search's result, for other servers and later retries
+                self.remember(cache_key, backend_key.as_deref(), response)
+                    .await;
+                result
+            }
+            None => {
+                let priority = request.priority.unwrap_or(0);
+                if cached {
+                    let joined = Instant::now();
+                    if let Some(response) = self.in_flight.join(cache_key, priority).await {
+                        debug!("Request {} joined an identical search", request_id);
+                        timing.coalesced(joined);
+                        let result = Self::parse_result(&response)?;
+                        return Ok(timing.stamp(self.to_response(request, &request_id, result)));
+                    }
+                }
+                let queued = Instant::now();
+                let _ticket = self.admit(request.priority).await?;
+                timing.admitted(queued);
+                // Identical requests arriving from now on wait for this search
+                let leader = self.in_flight.lead(cache_key, &request_id, priority);
+                let json = serde_json::to_string(&query)?;
+                let sent = timing.send();
+                let response = self
+                    .query(&request_id, json, cache_key, self.timeout_secs(request))
+                    .await
+                    .inspect_err(|e| self.record_error(&request_id, e))?;
+                let result = Self::parse_result(&response)?;
+                self.record_search(&result, sent);
+                if let Some(leader) = leader {
+                    leader.finish(&response);
+                }
+                self.remember(cache_key, backend_key.as_deref(), response)
+                    .await;
+                result
+            }
+        };
+        Ok(timing.stamp(self.to_response(request, &request_id, result)))
+    }
+
+    /// Cache a result in memory and, when configured, in the backend
+    async fn remember(&self, cache_key: u64, backend_key: Option<&str>, response: String) {
+        if let (Some(backend), Some(key)) = (&self.backend, backend_key) {
+            backend.insert(key, &response).await;
+        }
+        self.results.insert(cache_key, response);
+    }
+
+    /// Analyze with `reportDuringSearchEvery`: every partial result KataGo reports
+    /// while it searches is sent to `results`, followed by the final one. If the
+    /// receiver goes away, KataGo is told to stop the search.
+    pub async fn analyze_partial(
+        &self,
+        request: &AnalysisRequest,
+        report_every: f32,
+        results: mpsc::Sender<AnalysisResponse>,
+    ) -> Result<()> {
+        let request_id = request
+            .request_id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let mut timing = Timing::start();
+        let query = Self::build_query(request, &request_id, None, Some(report_every));
+        let json = serde_json::to_string(&query)?;
+        let queued = Instant::now();
+        let _ticket = self.admit(request.priority).await?;
+        timing.admitted(queued);
+        let sent = timing.send();
+        let mut rx = self.send_for_lines(&request_id, json, 1)?;
+
+        let timeout_secs = self.timeout_secs(request);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(timeout_secs);
+        loop {
+            let line = self
+                .next_line(&request_id, &mut rx, deadline, timeout_secs)
+                .await?;
+            let result = Self::parse_result(&line)?;
+            let is_final = !result.is_during_search;
+            if is_final {
+                self.record_search(&result, sent);
+            }
+            let response = timing.stamp(self.to_response(request, &request_id, result));
+            if results.send(response).await.is_err() {
+                if !is_final {
+                    self.terminate(&request_id);
+                }
+                return Ok(());
+            }
+            if is_final {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Analyze several turns of the game in one query (`analyzeTurns`), returning
+    /// the results in turn order. Each turn gets the timeout of a single query.
+    pub async fn analyze_turns(&self, request: &AnalysisRequest) -> Result<Vec<AnalysisResponse>> {
+        let request_id = request
+            .request_id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let mut timing = Timing::start();
+        let turns = Self::turns_to_analyze(request)?;
+        let query = Self::build_query(request, &request_id, Some(turns.clone()), None);
+        let json = serde_json::to_string(&query)?;
+        let queued = Instant::now();
+        let _ticket = self.admit(request.priority).await?;
+        timing.admitted(queued);
+        let sent = timing.send();
+        let mut rx = self.send_for_lines(&request_id, json, turns.len())?;
+
+        let timeout_secs = self.timeout_secs(request) * turns.len() as u64;
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(timeout_secs);
+        let mut responses = Vec::with_capacity(turns.len());
+        while responses.len() < turns.len() {
+            let line = self
+                .next_line(&request_id, &mut rx, deadline, timeout_secs)
+                .await?;
+            let result = Self::parse_result(&line)?;
+            if !result.is_during_search {
+                responses.push(timing.stamp(self.to_response(request, &request_id, result)));
+            }
+        }
+        self.record_success();
+        let visits = responses
+            .iter()
+            .filter_map(|response| response.root_info.as_ref())
+            .map(|root| root.visits)
+            .sum();
+        self.throughput.record(visits, sent);
+        responses.sort_by_key(|response| response.turn_number);
+        Ok(responses)
+    }
+
+    /// A request's analyzeTurns, sorted and without duplicates
+    fn turns_to_analyze(request: &AnalysisRequest) -> Result<Vec<u32>> {
+        let mut turns = request.analyze_turns.clone().unwrap_or_default();
+        turns.sort_unstable();
+        turns.dedup();
+        let last = request.moves.len() as u32;
+        match turns.last() {
+            None => Err(KatagoError::InvalidCommand(
+                "analyzeTurns must list at least one turn".to_string(),
+            )),
+            Some(&turn) if turn > last => Err(KatagoError::InvalidCommand(format!(
+                "analyzeTurns: turn {} is past the end of the game ({} moves)",
+                turn, last
+            ))),
+            Some(_) => Ok(turns),
+        }
+    }
+
+    /// Send query `id` with a waiter that receives all its response lines, until
+    /// `finals` final responses arrived
+    fn send_for_lines(
+        &self,
+        id: &str,
+        json: String,
+        finals: usize,
+    ) -> Result<mpsc::UnboundedReceiver<String>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.pending_requests.lock().unwrap().insert(
+            id.to_string(),
+            PendingQuery::new(Waiter::Lines {
+                tx,
+                finals_left: finals,
             }),
-            komi: request.komi.unwrap_or(7.5),
+        );
+        if let Err(e) = self.send_query(id, json) {
+            self.pending_requests.lock().unwrap().remove(id);
+            self.record_error(id, &e);
+            return Err(e);
+        }
+        Ok(rx)
+    }
+
+    /// Next response line of a query sent with `send_for_lines`
+    async fn next_line(
+        &self,
+        id: &str,
+        rx: &mut mpsc::UnboundedReceiver<String>,
+        deadline: tokio::time::Instant,
+        timeout_secs: u64,
+    ) -> Result<String> {
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(line)) => Ok(line),
+            // Sender dropped (process died?)
+            Ok(None) => Err(KatagoError::ProcessDied),
+            Err(_) => {
+                self.pending_requests.lock().unwrap().remove(id);
+                Err(KatagoError::Timeout(timeout_secs))
+            }
+        }
+        .inspect_err(|e| self.record_error(id, e))
+    }
+
+    /// Cancel query `id`: KataGo stops searching it and its caller gets
+    /// `KatagoError::Cancelled` right away. False if the query isn't running here.
+    pub fn cancel(&self, id: &str) -> bool {
+        let Some(pending) = self.pending_requests.lock().unwrap().remove(id) else {
+            return false;
+        };
+        info!("Cancelling query {}", id);
+        self.terminate(id);
+        // An error line ends every kind of waiter; native callers see it as is
+        let line = serde_json::json!({
+            "id": id,
+            "error": "Query cancelled",
+            CANCELLED_FIELD: true,
+        });
+        pending.waiter.deliver(line.to_string(), false, true);
+        true
+    }
+
+    /// Ask KataGo to stop searching query `id`; it still sends a final response
+    fn terminate(&self, id: &str) {
+        self.pending_requests.lock().unwrap().remove(id);
+        let terminate = serde_json::json!({
+            "id": format!("terminate-{}", id),
+            "action": "terminate",
+            "terminateId": id,
+        });
+        if let Err(e) = Self::write_lines(
+            &self.stdin,
+            &self.process,
+            &self.process_alive,
+            &self.protocol_stats,
+            &[terminate.to_string()],
+        ) {
+            warn!("Failed to terminate query {}: {}", id, e);
+        }
+    }
+
+    /// KataGo query for a request
+    fn build_query(
+        request: &AnalysisRequest,
+        request_id: &str,
+        analyze_turns: Option<Vec<u32>>,
+        report_during_search_every: Option<f32>,
+    ) -> AnalysisQuery {
+        // Validate moves for the given board size (strict validation rejected them already)
+        for invalid in Self::invalid_moves(request) {
+            warn!(
+                "Invalid move '{}' for {}x{} board (valid columns: A-{}, skipping I)",
+                invalid.move_coord,
+                request.board_x_size,
+                request.board_y_size,
+                Self::column_letter_for_size(request.board_x_size)
+            );
+        }
+
+        let katago_moves = Self::katago_moves(request);
+        let initial_stones = Self::katago_initial_stones(request);
+
+        AnalysisQuery {
+            id: request_id.to_string(),
+            initial_stones,
+            moves: katago_moves,
+            rules: Self::rules(request),
+            komi: Self::komi(request),
             board_x_size: request.board_x_size,
             board_y_size: request.board_y_size,
-            // Let analyzeTurns default to analyzing the final position
-            analyze_turns: None,
-            // Always include maxVisits - KataGo requires this to start analysis
-            // Default to 10 for fast CPU execution (increase for GPU or stronger analysis)
+            // Without analyzeTurns, KataGo analyzes the final position
+            analyze_turns,
+            // Always include maxVisits - KataGo requires this to start analysis.
+            // The pool fills in `[analysis] default_max_visits` before this.
             max_visits: Some(request.max_visits.unwrap_or(10)),
             include_ownership: request.include_ownership,
             include_policy: request.include_policy,
             include_pv_visits: request.include_pv_visits,
+            analysis_pv_len: request.analysis_pv_len,
+            avoid_moves: request.avoid_moves.clone(),
+            allow_moves: request.allow_moves.clone(),
             // Pass through override settings (e.g., humanSLProfile for human-style analysis)
-            override_settings: request.override_settings.clone(),
-        };
-
-        self.send_query(&query)?;
-
-        let result = self
-            .wait_for_response(&request_id, self.config.move_timeout_secs)
-            .await?;
+            override_settings: Self::override_settings(request),
+            report_during_search_every,
+            priority: request.priority,
+        }
+    }
 
-        // Warn if KataGo returned empty move infos (might indicate invalid position/moves)
-        if result.move_infos.is_empty() {
-            warn!(
-                "KataGo returned empty moveInfos for request {}: board={}x{}, moves={:?}",
-                request_id, request.board_x_size, request.board_y_size, request.moves
-            );
-            if result.root_info.is_none() {
-                warn!("No rootInfo either - the position may be invalid or moves may be illegal");
+    /// The request's overrideSettings, plus the settings it sets with its own fields;
+    /// an entry already in overrideSettings wins
+    fn override_settings(request: &AnalysisRequest) -> Option<serde_json::Value> {
+        let mut settings = request.override_settings.clone();
+        let fields = [
+            ("maxTime", request.max_time_secs.map(Into::into)),
+            (
+                "rootPolicyTemperature",
+                request.root_policy_temperature.map(Into::into),
+            ),
+            (
+                "rootFpuReductionMax",
+                request.root_fpu_reduction_max.map(Into::into),
+            ),
+        ];
+        for (name, value) in fields {
+            let Some(value) = value else { continue };
+            let settings = settings.get_or_insert_with(|| serde_json::json!({}));
+            if let Some(settings) = settings.as_object_mut() {
+                settings.entry(name).or_insert(value);
             }
         }
+        settings
+    }
 
+    /// API response for a KataGo result, after the engine's result processors
+    fn to_response(
+        &self,
+        request: &AnalysisRequest,
+        request_id: &str,
+        result: AnalysisResult,
+    ) -> AnalysisResponse {
         // Convert KataGo response to our API format
-        let move_infos = result
+        let move_infos: Vec<MoveInfo> = result
             .move_infos
             .into_iter()
             .map(|mi| MoveInfo {
@@ -693,69 +2177,176 @@ impl AnalysisEngine {
             human_score_stdev: ri.human_score_stdev,
         });
 
-        Ok(AnalysisResponse {
-            id: request_id,
+        let mut response = AnalysisResponse {
+            id: request_id.to_string(),
             turn_number: result.turn_number,
-            is_during_search: false,
+            is_during_search: result.is_during_search,
             move_infos: Some(move_infos),
             root_info,
-            ownership: result.ownership,
+            ownership: result.ownership.map(Ownership::Flat),
             ownership_stdev: None, // Not provided by basic analysis
             policy: result.policy,
             human_policy: result.human_policy,
-        })
+            human_model: None,
+            josekis: None,
+            warnings: result.warnings,
+            meta: None,
+        };
+        if request.include_josekis == Some(true) {
+            let moves = Self::katago_moves(request);
+            let played = moves.len().min(response.turn_number as usize);
+            response.josekis = Some(joseki::recognize(
+                request.board_x_size,
+                request.board_y_size,
+                &Self::katago_initial_stones(request),
+                &moves[..played],
+            ));
+        }
+        let context = ResultContext {
+            request,
+            human_model: self.loaded_human_model(),
+        };
+        self.processors.run(&context, &mut response);
+        response
+    }
+
+    /// Run a query in KataGo's native JSON schema and return KataGo's response line
+    /// unchanged, except that the id is KataGo-internal (callers restore their own).
+    /// Error responses from KataGo are returned as lines too, not as errors.
+    pub async fn analyze_native(&self, mut query: serde_json::Value) -> Result<String> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let object = query
+            .as_object_mut()
+            .ok_or_else(|| KatagoError::InvalidCommand("query must be a JSON object".into()))?;
+        object.insert("id".to_string(), serde_json::Value::String(id.clone()));
+
+        let cache_key = query_cache_key(query.clone());
+        if let Some(response) = self.salvage.get(cache_key) {
+            info!("Serving salvaged result for native query {}", id);
+            return Ok(response);
+        }
+        let priority = query["priority"].as_i64().map(|p| p as i32);
+        let _ticket = self.admit(priority).await?;
+        let json = serde_json::to_string(&query)?;
+        self.query(&id, json, cache_key, self.move_timeout_secs())
+            .await
+            .inspect_err(|e| self.record_error(&id, e))
     }
 
+    /// Clear the server's result cache and KataGo's neural network cache
     pub async fn clear_cache(&self) -> Result<()> {
         info!("Clearing KataGo analysis cache");
+        self.results.clear();
+        if let Some(backend) = &self.backend {
+            backend.clear().await;
+        }
         let query = serde_json::json!({
             "id": uuid::Uuid::new_v4().to_string(),
             "action": "clear_cache"
         });
 
         let json = serde_json::to_string(&query)?;
-        let mut stdin = self.stdin.lock().unwrap();
-        let stdin = stdin.as_mut().ok_or(KatagoError::ProcessDied)?;
-
-        writeln!(stdin, "{}", json)?;
-        stdin.flush()?;
-        Ok(())
+        Self::write_lines(
+            &self.stdin,
+            &self.process,
+            &self.process_alive,
+            &self.protocol_stats,
+            &[json],
+        )
     }
 
+    /// KataGo's version and git hash. The first successful query_version is cached:
+    /// the binary stays the same across restarts and model switches.
     pub async fn query_version(&self) -> Result<(String, Option<String>)> {
-        // KataGo requires an 'id' field for all requests including query_version
-        let query = serde_json::json!({
-            "id": "query_version",
-            "action": "query_version"
-        });
+        if let Some(version) = self.katago_version.lock().unwrap().clone() {
+            return Ok(version);
+        }
 
+        let id = format!("query_version-{}", uuid::Uuid::new_v4());
+        let query = serde_json::json!({ "id": id, "action": "query_version" });
+        let (tx, rx) = oneshot::channel();
+        self.pending_requests
+            .lock()
+            .unwrap()
+            .insert(id.clone(), PendingQuery::new(Waiter::Final(tx)));
         let json = serde_json::to_string(&query)?;
-
-        // For action commands, we can't use the pending_requests tracking
-        // because the response doesn't have an id. Instead, we just send
-        // the command and check if the process is still alive.
-        {
-            let mut stdin = self.stdin.lock().unwrap();
-            let stdin = stdin.as_mut().ok_or(KatagoError::ProcessDied)?;
-            writeln!(stdin, "{}", json)?;
-            stdin.flush()?;
-            debug!("Sent query_version command");
+        if let Err(e) = Self::write_lines(
+            &self.stdin,
+            &self.process,
+            &self.process_alive,
+            &self.protocol_stats,
+            &[json],
+        ) {
+            self.pending_requests.lock().unwrap().remove(&id);
+            return Err(e);
         }
+        debug!("Sent query_version command");
 
-        // Give KataGo a moment to respond, then check if process is alive
-        tokio::time::sleep(Duration::from_millis(100)).await;
+        let timeout_secs = self.move_timeout_secs();
+        let response = match timeout(Duration::from_secs(timeout_secs), rx).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err(KatagoError::ProcessDied),
+            Err(_) => {
+                self.pending_requests.lock().unwrap().remove(&id);
+                return Err(KatagoError::Timeout(timeout_secs));
+            }
+        };
+        let version = Self::parse_version(&response)?;
+        info!("KataGo version {}", version.0);
+        *self.katago_version.lock().unwrap() = Some(version.clone());
+        Ok(version)
+    }
 
-        if !self.process_alive.load(Ordering::SeqCst) {
-            return Err(KatagoError::ProcessDied);
+    /// Analyze an empty `size`x`size` board with a single visit, which KataGo
+    /// answers once its model is loaded, and mark the engine warmed up. The query
+    /// bypasses the result cache and the search statistics.
+    pub async fn warm_up(&self, size: u8) -> Result<()> {
+        let request = AnalysisRequest {
+            board_x_size: size,
+            board_y_size: size,
+            max_visits: Some(1),
+            ..Default::default()
+        };
+        let id = format!("warm-up-{}", uuid::Uuid::new_v4());
+        let json = serde_json::to_string(&Self::build_query(&request, &id, None, None))?;
+        let (tx, rx) = oneshot::channel();
+        self.pending_requests
+            .lock()
+            .unwrap()
+            .insert(id.clone(), PendingQuery::new(Waiter::Final(tx)));
+        if let Err(e) = self.send_query(&id, json) {
+            self.pending_requests.lock().unwrap().remove(&id);
+            return Err(e);
         }
+        let response = match timeout(Duration::from_secs(MODEL_STARTUP_TIMEOUT_SECS), rx).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err(KatagoError::ProcessDied),
+            Err(_) => {
+                self.pending_requests.lock().unwrap().remove(&id);
+                return Err(KatagoError::Timeout(MODEL_STARTUP_TIMEOUT_SECS));
+            }
+        };
+        Self::parse_result(&response)?;
+        self.warmed_up.store(true, Ordering::SeqCst);
+        Ok(())
+    }
 
-        // Return a placeholder - the actual version info will be in the response
-        // but since we can't easily correlate it, we return what we know from startup logs
-        Ok(("1.15.0".to_string(), None))
+    /// `version` and `git_hash` of a query_version response
+    fn parse_version(response: &str) -> Result<(String, Option<String>)> {
+        let value: serde_json::Value = serde_json::from_str(response)?;
+        if let Some(err_msg) = value.get("error") {
+            return Err(KatagoError::ResponseError(err_msg.to_string()));
+        }
+        let version = value["version"].as_str().ok_or_else(|| {
+            KatagoError::ParseError("query_version response has no version".to_string())
+        })?;
+        let git_hash = value["git_hash"].as_str().map(str::to_string);
+        Ok((version.to_string(), git_hash))
     }
 
-    pub fn model_path(&self) -> &str {
-        &self.config.model_path
+    /// Model the process runs (changed by `switch_model`)
+    pub fn model_path(&self) -> String {
+        self.model_path.lock().unwrap().clone()
     }
 }
 
@@ -771,6 +2362,269 @@ impl Drop for AnalysisEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api::MoveInput;
+
+    fn query(id: &str, max_visits: u32) -> AnalysisQuery {
+        AnalysisQuery {
+            id: id.to_string(),
+            initial_stones: vec![],
+            moves: vec![vec!["b".to_string(), "D4".to_string()]],
+            rules: "chinese".into(),
+            komi: 7.5,
+            board_x_size: 19,
+            board_y_size: 19,
+            analyze_turns: None,
+            max_visits: Some(max_visits),
+            include_ownership: None,
+            include_policy: None,
+            include_pv_visits: None,
+            analysis_pv_len: None,
+            avoid_moves: None,
+            allow_moves: None,
+            override_settings: None,
+            report_during_search_every: None,
+            priority: None,
+        }
+    }
+
+    #[test]
+    fn test_cache_key_ignores_id() {
+        assert_eq!(query("a", 100).cache_key(), query("b", 100).cache_key());
+        assert_ne!(query("a", 100).cache_key(), query("a", 200).cache_key());
+        let urgent = AnalysisQuery {
+            priority: Some(10),
+            ..query("a", 100)
+        };
+        assert_eq!(urgent.cache_key(), query("a", 100).cache_key());
+    }
+
+    #[test]
+    fn test_priority_reaches_the_query() {
+        let request = AnalysisRequest {
+            priority: Some(-10),
+            ..Default::default()
+        };
+        let query =
+            serde_json::to_value(AnalysisEngine::build_query(&request, "q", None, None)).unwrap();
+        assert_eq!(query["priority"], -10);
+
+        let unset = serde_json::to_value(AnalysisEngine::build_query(
+            &AnalysisRequest::default(),
+            "q",
+            None,
+            None,
+        ))
+        .unwrap();
+        assert!(unset.get("priority").is_none());
+    }
+
+    #[test]
+    fn test_move_filters_reach_the_query() {
+        let request: AnalysisRequest = serde_json::from_value(serde_json::json!({
+            "moves": ["D4"],
+            "avoidMoves": [{"player": "W", "moves": ["Q16", "Q4"], "untilDepth": 1}],
+            "allowMoves": [{"player": "W", "moves": ["C3"], "untilDepth": 1}],
+        }))
+        .unwrap();
+        let query =
+            serde_json::to_value(AnalysisEngine::build_query(&request, "q", None, None)).unwrap();
+        assert_eq!(
+            query["avoidMoves"],
+            serde_json::json!([{"player": "W", "moves": ["Q16", "Q4"], "untilDepth": 1}])
+        );
+        assert_eq!(query["allowMoves"][0]["moves"], serde_json::json!(["C3"]));
+
+        let unfiltered = serde_json::to_value(AnalysisEngine::build_query(
+            &AnalysisRequest::default(),
+            "q",
+            None,
+            None,
+        ))
+        .unwrap();
+        assert!(unfiltered.get("avoidMoves").is_none());
+    }
+
+    #[test]
+    fn test_rules_are_guessed_from_komi_only_when_unset() {
+        let with_komi = |komi: f32| AnalysisRequest {
+            komi: Some(komi),
+            ..Default::default()
+        };
+        assert_eq!(AnalysisEngine::rules(&with_komi(6.5)), "japanese".into());
+        assert_eq!(AnalysisEngine::rules(&with_komi(7.0)), "chinese".into());
+        assert_eq!(AnalysisEngine::rules(&with_komi(0.5)), "chinese".into());
+
+        let request = AnalysisRequest {
+            rules: Some("aga".into()),
+            ..with_komi(6.5)
+        };
+        assert_eq!(AnalysisEngine::rules(&request), "aga".into());
+    }
+
+    #[test]
+    fn test_search_controls_reach_the_query() {
+        let request = AnalysisRequest {
+            analysis_pv_len: Some(20),
+            root_policy_temperature: Some(1.5),
+            root_fpu_reduction_max: Some(0.0),
+            override_settings: Some(serde_json::json!({ "humanSLProfile": "rank_3d" })),
+            ..Default::default()
+        };
+        let query =
+            serde_json::to_value(AnalysisEngine::build_query(&request, "q", None, None)).unwrap();
+        assert_eq!(query["analysisPVLen"], 20);
+        assert_eq!(query["overrideSettings"]["rootPolicyTemperature"], 1.5);
+        assert_eq!(query["overrideSettings"]["rootFpuReductionMax"], 0.0);
+        assert_eq!(query["overrideSettings"]["humanSLProfile"], "rank_3d");
+        assert!(query["overrideSettings"].get("analysisPVLen").is_none());
+
+        // A setting spelled out in overrideSettings wins
+        let request = AnalysisRequest {
+            root_policy_temperature: Some(1.5),
+            override_settings: Some(serde_json::json!({ "rootPolicyTemperature": 2.0 })),
+            ..Default::default()
+        };
+        let query =
+            serde_json::to_value(AnalysisEngine::build_query(&request, "q", None, None)).unwrap();
+        assert_eq!(query["overrideSettings"]["rootPolicyTemperature"], 2.0);
+
+        let query = serde_json::to_value(AnalysisEngine::build_query(
+            &AnalysisRequest::default(),
+            "q",
+            None,
+            None,
+        ))
+        .unwrap();
+        assert!(query.get("overrideSettings").is_none());
+    }
+
+    #[test]
+    fn test_max_time_is_sent_as_override_setting() {
+        let request = AnalysisRequest {
+            max_time_secs: Some(30.5),
+            ..Default::default()
+        };
+        let query =
+            serde_json::to_value(AnalysisEngine::build_query(&request, "q", None, None)).unwrap();
+        assert_eq!(query["overrideSettings"]["maxTime"], 30.5);
+    }
+
+    #[test]
+    fn test_line_waiter_counts_final_responses() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let waiter = Waiter::Lines { tx, finals_left: 2 };
+        let waiter = waiter
+            .deliver("partial".into(), true, false)
+            .and_then(|w| w.deliver("turn 0".into(), false, false))
+            .expect("one final response still expected");
+        assert!(waiter.deliver("turn 1".into(), false, false).is_none());
+        for expected in ["partial", "turn 0", "turn 1"] {
+            assert_eq!(rx.try_recv().unwrap(), expected);
+        }
+
+        // An error line ends the query however many turns were requested
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let waiter = Waiter::Lines { tx, finals_left: 3 };
+        assert!(waiter.deliver("error".into(), false, true).is_none());
+    }
+
+    #[test]
+    fn test_restart_delay_backs_off_exponentially() {
+        let config = KatagoConfig::default();
+        let delays: Vec<u64> = (0..8)
+            .map(|attempts| AnalysisEngine::restart_delay(&config, attempts).as_secs())
+            .collect();
+        assert_eq!(delays, [5, 10, 20, 40, 80, 160, 300, 300]);
+        assert_eq!(AnalysisEngine::restart_delay(&config, 1000).as_secs(), 300);
+    }
+
+    #[test]
+    fn test_zero_max_restart_attempts_never_gives_up() {
+        let mut config = KatagoConfig::default();
+        assert!(!AnalysisEngine::restarts_exhausted(&config, 4));
+        assert!(AnalysisEngine::restarts_exhausted(&config, 5));
+        config.max_restart_attempts = 0;
+        assert!(!AnalysisEngine::restarts_exhausted(&config, 1000));
+    }
+
+    #[test]
+    fn test_cancelled_and_restarted_lines_are_not_engine_errors() {
+        let cancelled = r#"{"id":"q1","error":"Query cancelled","cancelled":true}"#;
+        assert!(matches!(
+            AnalysisEngine::parse_result(cancelled),
+            Err(KatagoError::Cancelled(id)) if id == "q1"
+        ));
+        let restarted = r#"{"id":"q2","error":"KataGo was restarted","restarted":true}"#;
+        assert!(matches!(
+            AnalysisEngine::parse_result(restarted),
+            Err(KatagoError::Restarted(id)) if id == "q2"
+        ));
+        let error = r#"{"id":"q1","error":"bad query"}"#;
+        assert!(matches!(
+            AnalysisEngine::parse_result(error),
+            Err(KatagoError::ResponseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_analyze_turns_are_checked() {
+        let mut request = AnalysisRequest {
+            moves: vec![
+                MoveInput::Simple("D4".into()),
+                MoveInput::Simple("Q16".into()),
+            ],
+            analyze_turns: Some(vec![2, 0, 2]),
+            ..Default::default()
+        };
+        assert_eq!(
+            AnalysisEngine::turns_to_analyze(&request).unwrap(),
+            vec![0, 2]
+        );
+
+        request.analyze_turns = Some(vec![3]);
+        assert!(AnalysisEngine::turns_to_analyze(&request).is_err());
+        request.analyze_turns = Some(vec![]);
+        assert!(AnalysisEngine::turns_to_analyze(&request).is_err());
+    }
+
+    #[test]
+    fn test_protocol_stats_snapshot() {
+        let stats = ProtocolStats::default();
+        stats.record_write(r#"{"id":"a"}"#);
+        ProtocolStats::count(&stats.unknown_id_responses);
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.queries_sent, 1);
+        assert_eq!(snapshot.bytes_written, 11);
+        assert_eq!(snapshot.unknown_id_responses, 1);
+        assert_eq!(snapshot.responses_received, 0);
+    }
+
+    #[test]
+    fn test_warnings_attach_to_response() {
+        let line = r#"{"id":"q1","field":"overrideSettings","warning":"Unknown config params: humanSLProfil"}"#;
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        let (id, warning) = parse_warning(&value).unwrap();
+        assert_eq!(id, "q1");
+        assert_eq!(warning.field.as_deref(), Some("overrideSettings"));
+
+        let response: serde_json::Value =
+            serde_json::from_str(r#"{"id":"q1","turnNumber":0,"moveInfos":[]}"#).unwrap();
+        assert!(parse_warning(&response).is_none());
+        let merged = with_warnings(response, vec![warning.clone()]);
+        let result = AnalysisEngine::parse_result(&merged).unwrap();
+        assert_eq!(result.warnings, vec![warning]);
+    }
+
+    #[test]
+    fn test_batch_stats() {
+        let stats = ProtocolStats::default();
+        stats.record_batch(3);
+        stats.record_batch(1);
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.batches_written, 2);
+        assert_eq!(snapshot.batched_queries, 4);
+        assert_eq!(snapshot.largest_batch, 3);
+    }
 
     #[test]
     fn test_move_validation_9x9_board() {
@@ -803,6 +2657,21 @@ mod tests {
         assert!(!AnalysisEngine::is_valid_move("U1", 19, 19)); // U would be column 20
         assert!(!AnalysisEngine::is_valid_move("A20", 19, 19)); // Row 20 doesn't exist
         assert!(!AnalysisEngine::is_valid_move("I5", 19, 19)); // I is never valid
+        assert!(!AnalysisEngine::is_valid_move("1A", 19, 19));
+        assert!(!AnalysisEngine::is_valid_move("é5", 19, 19));
+    }
+
+    #[test]
+    fn test_invalid_moves_are_listed_with_their_index() {
+        let request: AnalysisRequest =
+            serde_json::from_value(serde_json::json!({ "moves": ["D4", "I5", "Q16", "A20"] }))
+                .unwrap();
+        let invalid = AnalysisEngine::invalid_moves(&request);
+        let listed: Vec<(usize, &str)> = invalid
+            .iter()
+            .map(|m| (m.index, m.move_coord.as_str()))
+            .collect();
+        assert_eq!(listed, [(1, "I5"), (3, "A20")]);
     }
 
     #[test]
@@ -811,4 +2680,38 @@ mod tests {
         assert_eq!(AnalysisEngine::column_letter_for_size(19), 'T'); // A-H, J-T
         assert_eq!(AnalysisEngine::column_letter_for_size(5), 'E');
     }
+
+    #[test]
+    fn test_human_model_name_is_file_name() {
+        assert_eq!(
+            human_model_name("/models/b18c384nbt-humanv0.bin.gz"),
+            "b18c384nbt-humanv0.bin.gz"
+        );
+        assert_eq!(human_model_name("humanv0.bin.gz"), "humanv0.bin.gz");
+    }
+
+    #[test]
+    fn test_version_is_parsed_from_query_version_response() {
+        let response =
+            r#"{"id":"v","action":"query_version","version":"1.15.3","git_hash":"abc123"}"#;
+        assert_eq!(
+            AnalysisEngine::parse_version(response).unwrap(),
+            ("1.15.3".to_string(), Some("abc123".to_string()))
+        );
+        assert!(AnalysisEngine::parse_version(r#"{"id":"v","error":"bad"}"#).is_err());
+    }
+
+    #[test]
+    fn test_write_failure_probe_tells_exited_from_running() {
+        let running = StdMutex::new(Some(Command::new("sleep").arg("5").spawn().unwrap()));
+        assert!(!AnalysisEngine::process_exited(&running));
+        let mut child = running.lock().unwrap().take().unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let mut exited = Command::new("true").spawn().unwrap();
+        exited.wait().unwrap();
+        assert!(AnalysisEngine::process_exited(&StdMutex::new(Some(exited))));
+        assert!(AnalysisEngine::process_exited(&StdMutex::new(None)));
+    }
 }
@@ -0,0 +1,715 @@
+use crate::api::{GameReviewResponse, MoveClassification};
+use crate::board::{Board, BoardError, Stone};
+use crate::games::Game;
+use crate::render::{hex, Canvas, Grid, Rgb, BLACK, BOARD_COLOR, LINE_COLOR, WHITE};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::ops::RangeInclusive;
+
+/// Pixels between two lines when the request sets none
+pub const DEFAULT_POINT_SIZE: u32 = 16;
+
+/// Point sizes a request may ask for: every frame of a GIF is a full image
+pub const POINT_SIZES: RangeInclusive<u32> = 8..=32;
+
+/// How long each move is shown when the request doesn't say
+pub const DEFAULT_FRAME_MS: u32 = 1000;
+
+pub const FRAME_MS: RangeInclusive<u32> = 100..=10_000;
+
+/// The engine's move: its preferred next move, and a played move it agreed with
+const ENGINE_COLOR: Rgb = [0x20, 0x80, 0xf0];
+const GOOD_COLOR: Rgb = [0x30, 0xb0, 0x40];
+const INACCURACY_COLOR: Rgb = [0xf0, 0xc0, 0x20];
+const MISTAKE_COLOR: Rgb = [0xf0, 0x70, 0x20];
+const BLUNDER_COLOR: Rgb = [0xe0, 0x20, 0x20];
+
+/// Colors of a GIF frame; anti-aliased edges take the nearest one
+const PALETTE: [Rgb; 9] = [
+    BOARD_COLOR,
+    LINE_COLOR,
+    BLACK,
+    WHITE,
+    ENGINE_COLOR,
+    GOOD_COLOR,
+    INACCURACY_COLOR,
+    MISTAKE_COLOR,
+    BLUNDER_COLOR,
+];
+
+/// Bits of a GIF palette index: 16 colors, of which PALETTE uses the first ones
+const GIF_INDEX_BITS: u8 = 4;
+
+/// The board before the first move of a game and after each move
+pub fn replay(game: &Game) -> Result<Vec<Board>, BoardError> {
+    let mut board = Board::new(game.board_x_size, game.board_y_size);
+    for (stone, coord) in &game.initial_stones {
+        board.place(*stone, coord)?;
+    }
+    let mut boards = vec![board.clone()];
+    for (stone, coord) in &game.moves {
+        board.play(*stone, coord)?;
+        boards.push(board.clone());
+    }
+    Ok(boards)
+}
+
+/// A reviewed game drawn move by move: the board with the last move marked in
+/// the color of its classification, a ring on the engine's preferred next move,
+/// and Black's winrate as a bar under the board
+pub struct ReviewAnimation<'a> {
+    pub grid: Grid,
+    /// One frame each, from [`replay`]
+    pub boards: &'a [Board],
+    pub review: &'a GameReviewResponse,
+    pub frame_ms: u32,
+}
+
+impl ReviewAnimation<'_> {
+    /// The board and a row under it for the winrate bar
+    fn dimensions(&self) -> (u32, u32) {
+        let (width, height) = self.grid.dimensions();
+        (width, height + self.grid.point_size)
+    }
+
+    /// Left, top, width, and height of the winrate bar
+    fn bar(&self) -> (f32, f32, f32, f32) {
+        let p = self.grid.point_size as f32;
+        let (width, height) = self.grid.dimensions();
+        (p / 2.0, height as f32 + p * 0.1, width as f32 - p, p * 0.6)
+    }
+
+    /// Black's winrate in a frame: before the first move, then after each move
+    fn winrate(&self, frame: usize) -> f32 {
+        let moves = &self.review.moves;
+        let winrate = match frame {
+            0 => moves.first().map(|m| m.best_winrate),
+            _ => moves.get(frame - 1).map(|m| m.winrate),
+        };
+        winrate.unwrap_or(0.5).clamp(0.0, 1.0)
+    }
+
+    /// The move leading to a frame, in the color of its classification
+    fn last_move(&self, frame: usize) -> Option<((f32, f32), Rgb)> {
+        let reviewed = self.review.moves.get(frame.checked_sub(1)?)?;
+        let color = match reviewed.classification {
+            MoveClassification::Best => ENGINE_COLOR,
+            MoveClassification::Good => GOOD_COLOR,
+            MoveClassification::Inaccuracy => INACCURACY_COLOR,
+            MoveClassification::Mistake => MISTAKE_COLOR,
+            MoveClassification::Blunder => BLUNDER_COLOR,
+        };
+        Some((self.grid.point(&reviewed.move_coord)?, color))
+    }
+
+    /// The engine's preferred move in a frame's position
+    fn engine_move(&self, frame: usize) -> Option<(f32, f32)> {
+        let best = self.review.moves.get(frame)?.best_move.as_deref()?;
+        self.grid.point(best)
+    }
+
+    /// An animated SVG, looping through the frames. Each stone is drawn once and
+    /// shown while it is on the board; the markers and the bar move from frame to
+    /// frame. Without animation support, it shows the final position.
+    pub fn svg(&self) -> String {
+        let frames = self.boards.len();
+        let last = frames - 1;
+        let duration = format!("{}s", frames as f32 * self.frame_ms as f32 / 1000.0);
+        let (width, height) = self.dimensions();
+        let p = self.grid.point_size as f32;
+        // Discrete animation through one value per frame
+        let animate = |attribute: &str, values: &[String]| {
+            format!(
+                r#"<animate attributeName="{attribute}" values="{}" dur="{duration}" calcMode="discrete" repeatCount="indefinite"/>"#,
+                values.join(";")
+            )
+        };
+        let visible = |shown: bool| if shown { "visible" } else { "hidden" }.to_string();
+
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+        );
+        let _ = write!(
+            svg,
+            r#"<rect width="{width}" height="{height}" fill="{}"/>"#,
+            hex(BOARD_COLOR)
+        );
+        self.grid.svg_lines(&mut svg);
+        self.grid.svg_coordinates(&mut svg);
+
+        for row in 0..self.grid.height {
+            let y = self.grid.height - 1 - row;
+            for x in 0..self.grid.width {
+                let center = self.grid.center(x, row);
+                for (stone, start, end) in stone_spans(self.boards, x, y) {
+                    if start == 0 && end == frames {
+                        self.grid.svg_stone(&mut svg, center, stone, "", "");
+                        continue;
+                    }
+                    let key_time = |frame: usize| format!("{:.6}", frame as f32 / frames as f32);
+                    let (values, key_times) = match (start, end) {
+                        (0, _) => ("visible;hidden", format!("0;{}", key_time(end))),
+                        (_, end) if end == frames => {
+                            ("hidden;visible", format!("0;{}", key_time(start)))
+                        }
+                        _ => (
+                            "hidden;visible;hidden",
+                            format!("0;{};{}", key_time(start), key_time(end)),
+                        ),
+                    };
+                    let attributes = if end == frames {
+                        ""
+                    } else {
+                        r#" visibility="hidden""#
+                    };
+                    let animation = format!(
+                        r#"<animate attributeName="visibility" values="{values}" keyTimes="{key_times}" dur="{duration}" calcMode="discrete" repeatCount="indefinite"/>"#
+                    );
+                    self.grid
+                        .svg_stone(&mut svg, center, stone, attributes, &animation);
+                }
+            }
+        }
+
+        let last_moves: Vec<_> = (0..frames).map(|frame| self.last_move(frame)).collect();
+        let (center, color) = last_moves[last].unwrap_or(((0.0, 0.0), ENGINE_COLOR));
+        let _ = write!(
+            svg,
+            r#"<circle cx="{}" cy="{}" r="{}" fill="{}" visibility="{}">{}{}{}{}</circle>"#,
+            center.0,
+            center.1,
+            p * 0.2,
+            hex(color),
+            visible(last_moves[last].is_some()),
+            animate(
+                "cx",
+                &last_moves
+                    .iter()
+                    .map(|m| m.map_or(0.0, |(c, _)| c.0).to_string())
+                    .collect::<Vec<_>>()
+            ),
+            animate(
+                "cy",
+                &last_moves
+                    .iter()
+                    .map(|m| m.map_or(0.0, |(c, _)| c.1).to_string())
+                    .collect::<Vec<_>>()
+            ),
+            animate(
+                "fill",
+                &last_moves
+                    .iter()
+                    .map(|m| hex(m.map_or(ENGINE_COLOR, |(_, color)| color)))
+                    .collect::<Vec<_>>()
+            ),
+            animate(
+                "visibility",
+                &last_moves
+                    .iter()
+                    .map(|m| visible(m.is_some()))
+                    .collect::<Vec<_>>()
+            ),
+        );
+
+        let engine_moves: Vec<_> = (0..frames).map(|frame| self.engine_move(frame)).collect();
+        let center = engine_moves[last].unwrap_or((0.0, 0.0));
+        let _ = write!(
+            svg,
+            r#"<circle cx="{}" cy="{}" r="{}" fill="none" stroke="{}" stroke-width="{}" visibility="{}">{}{}{}</circle>"#,
+            center.0,
+            center.1,
+            p * 0.3,
+            hex(ENGINE_COLOR),
+            p * 0.12,
+            visible(engine_moves[last].is_some()),
+            animate(
+                "cx",
+                &engine_moves
+                    .iter()
+                    .map(|m| m.map_or(0.0, |c| c.0).to_string())
+                    .collect::<Vec<_>>()
+            ),
+            animate(
+                "cy",
+                &engine_moves
+                    .iter()
+                    .map(|m| m.map_or(0.0, |c| c.1).to_string())
+                    .collect::<Vec<_>>()
+            ),
+            animate(
+                "visibility",
+                &engine_moves
+                    .iter()
+                    .map(|m| visible(m.is_some()))
+                    .collect::<Vec<_>>()
+            ),
+        );
+
+        let (left, top, bar_width, bar_height) = self.bar();
+        let black_width = |frame| self.winrate(frame) * bar_width;
+        let _ = write!(
+            svg,
+            r#"<rect x="{left}" y="{top}" width="{bar_width}" height="{bar_height}" fill="{}" stroke="{}" stroke-width="1"/>"#,
+            hex(WHITE),
+            hex(BLACK)
+        );
+        let _ = write!(
+            svg,
+            r#"<rect x="{left}" y="{top}" width="{}" height="{bar_height}" fill="{}">{}</rect>"#,
+            black_width(last),
+            hex(BLACK),
+            animate(
+                "width",
+                &(0..frames)
+                    .map(|frame| black_width(frame).to_string())
+                    .collect::<Vec<_>>()
+            )
+        );
+        let middle = left + bar_width / 2.0;
+        let _ = write!(
+            svg,
+            r#"<line x1="{middle}" y1="{top}" x2="{middle}" y2="{}" stroke="{}" stroke-width="1"/>"#,
+            top + bar_height,
+            hex(ENGINE_COLOR)
+        );
+        svg.push_str("</svg>");
+        svg
+    }
+
+    fn draw(&self, frame: usize) -> Canvas {
+        let (width, height) = self.dimensions();
+        let p = self.grid.point_size as f32;
+        let mut canvas = Canvas::new(width, height, BOARD_COLOR);
+        self.grid.draw_lines(&mut canvas);
+        let board = &self.boards[frame];
+        for row in 0..self.grid.height {
+            for x in 0..self.grid.width {
+                if let Some(stone) = board.stone(x, self.grid.height - 1 - row) {
+                    self.grid
+                        .draw_stone(&mut canvas, self.grid.center(x, row), stone);
+                }
+            }
+        }
+        if let Some(((cx, cy), color)) = self.last_move(frame) {
+            canvas.circle(cx, cy, p * 0.2, color);
+        }
+        if let Some((cx, cy)) = self.engine_move(frame) {
+            canvas.ring(cx, cy, p * 0.3, (p * 0.12).max(1.0), ENGINE_COLOR);
+        }
+
+        let (left, top, bar_width, bar_height) = self.bar();
+        let (right, bottom) = (left + bar_width, top + bar_height);
+        canvas.rect(left - 1.0, top - 1.0, right + 1.0, bottom + 1.0, BLACK, 1.0);
+        canvas.rect(left, top, right, bottom, WHITE, 1.0);
+        let black = left + self.winrate(frame) * bar_width;
+        canvas.rect(left, top, black, bottom, BLACK, 1.0);
+        let middle = (left + right) / 2.0;
+        canvas.rect(middle - 0.5, top, middle + 0.5, bottom, ENGINE_COLOR, 1.0);
+        canvas
+    }
+
+    /// A looping GIF, one frame per position. Each frame after the first stores
+    /// only the rectangle that changed.
+    pub fn gif(&self) -> Vec<u8> {
+        let (width, height) = self.dimensions();
+        let mut nearest = HashMap::new();
+        let frames: Vec<Vec<u8>> = (0..self.boards.len())
+            .map(|frame| {
+                self.draw(frame)
+                    .pixels
+                    .chunks(3)
+                    .map(|pixel| {
+                        let color = [pixel[0], pixel[1], pixel[2]];
+                        *nearest.entry(color).or_insert_with(|| palette_index(color))
+                    })
+                    .collect()
+            })
+            .collect();
+        encode_gif(
+            width as u16,
+            height as u16,
+            &frames,
+            (self.frame_ms / 10) as u16,
+        )
+    }
+}
+
+/// The stones that stood on point (x, y), with the frames they stood there:
+/// color, first frame, and the frame after the last
+fn stone_spans(boards: &[Board], x: u8, y: u8) -> Vec<(Stone, usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for frame in 1..=boards.len() {
+        let previous = boards[frame - 1].stone(x, y);
+        if frame < boards.len() && boards[frame].stone(x, y) == previous {
+            continue;
+        }
+        if let Some(stone) = previous {
+            spans.push((stone, start, frame));
+        }
+        start = frame;
+    }
+    spans
+}
+
+fn palette_index([r, g, b]: Rgb) -> u8 {
+    let distance = |[pr, pg, pb]: Rgb| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, pr) + d(g, pg) + d(b, pb)
+    };
+    (0..PALETTE.len())
+        .min_by_key(|&i| distance(PALETTE[i]))
+        .unwrap_or(0) as u8
+}
+
+/// A GIF89a of palette-indexed frames, looping forever. `delay` is in hundredths
+/// of a second.
+fn encode_gif(width: u16, height: u16, frames: &[Vec<u8>], delay: u16) -> Vec<u8> {
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend_from_slice(&width.to_le_bytes());
+    gif.extend_from_slice(&height.to_le_bytes());
+    // Global color table of 2^GIF_INDEX_BITS colors, 8 bits per channel
+    gif.extend_from_slice(&[0xf0 | (GIF_INDEX_BITS - 1), 0, 0]);
+    for index in 0..1 << GIF_INDEX_BITS {
+        gif.extend_from_slice(PALETTE.get(index).unwrap_or(&BLACK));
+    }
+    gif.extend_from_slice(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+    let width = width as usize;
+    let mut previous: Option<&Vec<u8>> = None;
+    for frame in frames {
+        let (left, top, right, bottom) = match previous {
+            None => (0, 0, width, frame.len() / width),
+            Some(previous) => changed_rectangle(previous, frame, width).unwrap_or((0, 0, 1, 1)),
+        };
+        // Graphic control: leave the frame in place for the next one to draw over
+        gif.extend_from_slice(&[0x21, 0xf9, 0x04, 0x04]);
+        gif.extend_from_slice(&delay.to_le_bytes());
+        gif.extend_from_slice(&[0x00, 0x00]);
+
+        gif.push(0x2c);
+        for value in [left, top, right - left, bottom - top] {
+            gif.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        gif.push(0x00);
+        let pixels: Vec<u8> = (top..bottom)
+            .flat_map(|y| frame[y * width + left..y * width + right].iter().copied())
+            .collect();
+        gif.push(GIF_INDEX_BITS);
+        for block in lzw_encode(&pixels, GIF_INDEX_BITS).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend_from_slice(block);
+        }
+        gif.push(0x00);
+        previous = Some(frame);
+    }
+    gif.push(0x3b);
+    gif
+}
+
+/// Left, top, right, and bottom (exclusive) of the pixels that differ between two
+/// frames; None if none do
+fn changed_rectangle(
+    previous: &[u8],
+    frame: &[u8],
+    width: usize,
+) -> Option<(usize, usize, usize, usize)> {
+    let mut changed: Option<(usize, usize, usize, usize)> = None;
+    for (index, (a, b)) in previous.iter().zip(frame).enumerate() {
+        if a == b {
+            continue;
+        }
+        let (x, y) = (index % width, index / width);
+        changed = Some(match changed {
+            None => (x, y, x + 1, y + 1),
+            Some((left, top, right, bottom)) => {
+                (left.min(x), top.min(y), right.max(x + 1), bottom.max(y + 1))
+            }
+        });
+    }
+    changed
+}
+
+/// Variable-length LZW as GIF uses it: codes of `min_code_size + 1` bits growing
+/// to 12, packed from the least significant bit, and a clear code whenever the
+/// table is full
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    const MAX_CODES: u16 = 4096;
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut output = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut size = min_code_size + 1;
+    let mut next = end + 1;
+
+    output.write(clear, size);
+    let Some((&first, rest)) = indices.split_first() else {
+        output.write(end, size);
+        return output.finish();
+    };
+    let mut prefix = first as u16;
+    for &index in rest {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        output.write(prefix, size);
+        table.insert((prefix, index), next);
+        next += 1;
+        // The decoder adds each entry one code later, and widens its codes when
+        // the next entry would not fit
+        if next > 1 << size {
+            size += 1;
+        }
+        if next == MAX_CODES {
+            output.write(clear, size);
+            table.clear();
+            size = min_code_size + 1;
+            next = end + 1;
+        }
+        prefix = index as u16;
+    }
+    output.write(prefix, size);
+    if next == 1 << size && size < 12 {
+        size += 1;
+    }
+    output.write(end, size);
+    output.finish()
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{MoveReview, ReviewSummary};
+    use crate::games::GameInfo;
+
+    /// GIF's LZW decoding, to check the encoder against
+    fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let end = clear + 1;
+        let initial: Vec<Vec<u8>> = (0..clear + 2).map(|i| vec![i as u8]).collect();
+        let mut table = initial.clone();
+        let mut size = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let (mut buffer, mut bits, mut bytes) = (0u32, 0u8, data.iter());
+        let mut output = Vec::new();
+        loop {
+            while bits < size {
+                buffer |= (*bytes.next().expect("end code") as u32) << bits;
+                bits += 8;
+            }
+            let code = (buffer & ((1 << size) - 1)) as usize;
+            buffer >>= size;
+            bits -= size;
+            if code == clear {
+                table = initial.clone();
+                size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return output;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [previous.as_slice(), &previous[..1]].concat(),
+                (None, None) => panic!("unknown first code {}", code),
+            };
+            output.extend_from_slice(&entry);
+            if let Some(previous) = previous {
+                if table.len() < 4096 {
+                    table.push([previous.as_slice(), &entry[..1]].concat());
+                }
+            }
+            previous = Some(entry);
+            if table.len() == 1 << size && size < 12 {
+                size += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn test_lzw_round_trip() {
+        // Long and varied enough to fill the table several times
+        let mut seed = 12345u32;
+        let noisy: Vec<u8> = (0..100_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                ((seed >> 16) % 9) as u8
+            })
+            .collect();
+        let flat = vec![3u8; 50_000];
+        for indices in [noisy, flat, vec![1], vec![]] {
+            let encoded = lzw_encode(&indices, GIF_INDEX_BITS);
+            assert_eq!(lzw_decode(&encoded, GIF_INDEX_BITS), indices);
+        }
+    }
+
+    fn reviewed(turn: u32, player: &str, coord: &str, winrate: f32) -> MoveReview {
+        MoveReview {
+            turn,
+            player: player.to_string(),
+            move_coord: coord.to_string(),
+            winrate,
+            score_lead: 0.0,
+            visits: 10,
+            best_move: Some("C3".to_string()),
+            best_winrate: 0.5,
+            best_score_lead: 0.0,
+            winrate_loss: 0.0,
+            score_loss: 0.0,
+            classification: MoveClassification::Good,
+        }
+    }
+
+    fn capture_game() -> (Game, GameReviewResponse) {
+        let game = Game {
+            id: String::new(),
+            info: GameInfo::default(),
+            board_x_size: 5,
+            board_y_size: 5,
+            komi: None,
+            rules: None,
+            initial_stones: vec![],
+            initial_player: Stone::Black,
+            // White's A1 stone is captured by B1
+            moves: vec![
+                (Stone::Black, "A2".to_string()),
+                (Stone::White, "A1".to_string()),
+                (Stone::Black, "B1".to_string()),
+            ],
+            source: None,
+            visibility: Default::default(),
+            owner_token: None,
+            share_token: None,
+        };
+        let review = GameReviewResponse {
+            board_x_size: 5,
+            board_y_size: 5,
+            komi: None,
+            rules: None,
+            black_player: None,
+            white_player: None,
+            result: None,
+            moves: vec![
+                reviewed(0, "B", "A2", 0.625),
+                reviewed(1, "W", "A1", 0.75),
+                reviewed(2, "B", "B1", 0.875),
+            ],
+            summary: ReviewSummary::default(),
+        };
+        (game, review)
+    }
+
+    #[test]
+    fn test_stones_are_shown_while_on_the_board() {
+        let (game, review) = capture_game();
+        let boards = replay(&game).unwrap();
+        assert_eq!(boards.len(), 4);
+        assert_eq!(stone_spans(&boards, 0, 1), [(Stone::Black, 1, 4)]);
+        assert_eq!(stone_spans(&boards, 0, 0), [(Stone::White, 2, 3)]);
+
+        let animation = ReviewAnimation {
+            grid: Grid {
+                width: 5,
+                height: 5,
+                point_size: 10,
+            },
+            boards: &boards,
+            review: &review,
+            frame_ms: 500,
+        };
+        let svg = animation.svg();
+        assert!(
+            svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="60" height="70""#)
+        );
+        // A1 appears on the third frame and is captured on the fourth
+        assert!(svg.contains(
+            r#"visibility="hidden"><animate attributeName="visibility" values="hidden;visible;hidden" keyTimes="0;0.500000;0.750000" dur="2s""#
+        ));
+        assert!(svg.contains(r#"values="hidden;visible" keyTimes="0;0.250000""#));
+        // Black's share of the bar: 50% before the first move, then after each
+        assert!(svg.contains(r#"values="25;31.25;37.5;43.75""#));
+        assert_eq!(animation.winrate(0), 0.5);
+        assert_eq!(animation.last_move(0), None);
+        assert_eq!(animation.last_move(1), Some(((10.0, 40.0), GOOD_COLOR)));
+        assert_eq!(animation.engine_move(3), None);
+
+        let gif = animation.gif();
+        assert!(gif.starts_with(b"GIF89a"));
+        assert_eq!(&gif[6..10], &[60, 0, 70, 0]);
+        let images = gif_images(&gif);
+        assert_eq!(images.len(), 4);
+        // The first frame is whole, the others only what changed
+        assert_eq!(images[0], (0, 0, 60, 70));
+        assert!(images[1..].iter().all(|&(_, _, w, h)| w < 60 && h <= 70));
+    }
+
+    /// Position and size of each image of a GIF, checking that its pixels decode
+    fn gif_images(gif: &[u8]) -> Vec<(u16, u16, u16, u16)> {
+        let word = |at: usize| u16::from_le_bytes([gif[at], gif[at + 1]]);
+        let sub_blocks = |mut at: usize| {
+            let mut data = Vec::new();
+            while gif[at] != 0 {
+                data.extend_from_slice(&gif[at + 1..at + 1 + gif[at] as usize]);
+                at += 1 + gif[at] as usize;
+            }
+            (data, at + 1)
+        };
+        let mut images = Vec::new();
+        let mut at = 13 + 3 * 16;
+        loop {
+            match gif[at] {
+                0x21 => at = sub_blocks(at + 2).1,
+                0x2c => {
+                    let image = (word(at + 1), word(at + 3), word(at + 5), word(at + 7));
+                    let (data, after) = sub_blocks(at + 11);
+                    let pixels = lzw_decode(&data, gif[at + 10]);
+                    assert_eq!(pixels.len(), image.2 as usize * image.3 as usize);
+                    images.push(image);
+                    at = after;
+                }
+                0x3b => return images,
+                other => panic!("unexpected block {:#x}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_illegal_record_is_not_replayed() {
+        let (mut game, _) = capture_game();
+        game.moves.push((Stone::White, "A2".to_string()));
+        assert_eq!(
+            replay(&game).unwrap_err(),
+            BoardError::Occupied("A2".to_string())
+        );
+    }
+}
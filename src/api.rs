@@ -1,303 +1,820 @@
-use crate::analysis_engine::AnalysisEngine;
+use crate::analysis_engine::{AnalysisEngine, ProtocolStatsSnapshot};
+use crate::animation::{self, ReviewAnimation};
+use crate::audit::{AuditEntry, AuditKind, AuditLog};
+use crate::board::{Board, BoardError, Stone};
+use crate::compat::katago_analysis;
+use crate::config::{Config, RequestConfig, ReviewConfig, CONFIG_FILE};
+use crate::console::{console, console_analyze};
+use crate::engine_pool::EnginePool;
+use crate::games::{
+    new_token, DuplicateRecord, Game, GameDatabase, GameInfo, GameQuery, PositionHit, Visibility,
+};
+use crate::graphql;
+use crate::guess::{evaluate_guess, GuessSession};
+use crate::handicap::HandicapSetup;
+use crate::history::{AnalysisHistory, StoredAnalysis};
+use crate::human_compare::{self, DEFAULT_MAX_MOVES};
+use crate::human_profiles::{self, HumanProfilesResponse};
+use crate::ingest::{run_import, ImportJob};
+use crate::jobs::{work, JobRunner, Jobs, PreparedJob, Progress};
+use crate::katago_bot::KatagoBot;
+use crate::legality::LegalityError;
+use crate::metrics::{metrics, track_requests, HttpMetrics};
+use crate::msgpack::{Body, Format};
+use crate::ogs::{Ogs, OgsError};
+use crate::openapi::{openapi, swagger_ui};
+use crate::partial::{stream_get, stream_post};
+use crate::play::{PlaySession, PlaySessions};
+use crate::positions::{Position, PositionEdit, PositionStore};
+use crate::presets::{Preset, PRESETS};
+use crate::protobuf;
+use crate::render;
+use crate::review::{
+    export_review_sgf, review_moves, review_summary, stream_annotated_sgf, winrate_graph,
+};
+use crate::rules::{self, RuleSet, RULE_SETS};
+use crate::schema::{schema_by_name, schema_index, validate_response};
+use crate::scoring;
+use crate::selfplay::selfplay;
+use crate::sgf;
+use crate::store::BoundedStore;
+use crate::subscriptions::{subscribe, watch_channel, Subscriptions};
+use crate::telemetry;
+use crate::throughput::{self, ThroughputSnapshot};
+use crate::webhooks::{Outcome, Webhooks};
 use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
+    extract::{DefaultBodyLimit, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{get, post, put},
     Json, Router,
 };
+// Wire types shared with the katago-server-client crate
+pub use katago_server_client::types::{
+    AnalysisBatchResponse, AnalysisMeta, AnalysisRequest, AnalysisResponse, AnimationFormat,
+    BoardCorner, BotCandidate, BotDiagnostics, BotMoveResponse, CacheBackendStats,
+    CacheClearResponse, CacheStatsResponse, CallbackAccepted, CancelResponse, ComparedMove,
+    DoneReason, EngineWarning, GameRequest, GameReviewResponse, GameScoreResponse, HealthResponse,
+    HeatmapLayer, HeatmapRequest, HumanComparisonRequest, HumanComparisonResponse, ImageFormat,
+    InvalidMove, JobKind, JobProgress, JobRequest, JobResponse, JobStatus, JosekiContinuation,
+    JosekiMatch, KatagoVersion, LossThresholds, ModelInfo, MoveClassification, MoveFilter,
+    MoveInfo, MoveInput, MoveReview, OgsImportRequest, Ownership, OwnershipFormat, PolicyResponse,
+    ProblemDetail, Quality, Region, ReviewAnimationRequest, ReviewFormat, ReviewGraphResponse,
+    ReviewRequest, ReviewSgfRequest, ReviewSummary, RootInfo, RuleSettings, Rules,
+    ScoreEstimateResponse, ScoreRequest, ScoringComparison, ScoringOutcome, SelfPlayEnd,
+    SelfPlayMove, SelfPlayRequest, SelfPlayResult, ServerVersion, SubscriptionEvent,
+    SubscriptionRequest, TradeEvaluation, TradeRequest, TradeResponse, VersionResponse,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tracing::error;
-
-pub type AppState = Arc<AnalysisEngine>;
-
-/// A move can be either a simple coordinate or an explicit [color, coordinate] pair
-/// This allows clients to specify exact colors for handicap games where alternation
-/// doesn't match the actual game (e.g., White plays first in handicap games)
-#[derive(Debug, Clone, Deserialize)]
-#[serde(untagged)]
-pub enum MoveInput {
-    /// Simple coordinate (e.g., "D4") - color inferred from position/alternation
-    Simple(String),
-    /// Explicit color and coordinate (e.g., ["W", "D4"] or ["B", "Q16"])
-    WithColor([String; 2]),
-}
-
-impl MoveInput {
-    /// Get the coordinate from the move
-    pub fn coord(&self) -> &str {
-        match self {
-            MoveInput::Simple(coord) => coord,
-            MoveInput::WithColor([_, coord]) => coord,
-        }
-    }
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Instant;
+use tokio_stream::StreamExt;
+use tower_http::compression::predicate::{DefaultPredicate, NotForContentType, Predicate};
+use tower_http::compression::CompressionLayer;
+use tower_http::limit::RequestBodyLimitLayer;
+use tracing::{error, info, warn, Instrument};
 
-    /// Get explicit color if provided, None for simple moves
-    pub fn color(&self) -> Option<&str> {
-        match self {
-            MoveInput::Simple(_) => None,
-            MoveInput::WithColor([color, _]) => Some(color),
-        }
-    }
+/// Shared state for all handlers
+#[derive(Clone)]
+pub struct AppState {
+    pub engine: Arc<EnginePool>,
+    pub positions: Arc<PositionStore>,
+    pub games: Arc<GameDatabase>,
+    pub guess_sessions: Arc<BoundedStore<GuessSession>>,
+    /// Games played against the engine
+    pub play_sessions: Arc<PlaySessions>,
+    pub import_jobs: Arc<BoundedStore<ImportJob>>,
+    /// Background work started with POST /api/v1/jobs
+    pub jobs: Arc<Jobs>,
+    /// Bearer token for /api/v1/admin/* (admin endpoints are disabled when None)
+    pub admin_token: Option<Arc<str>>,
+    pub subscriptions: Arc<Subscriptions>,
+    /// Check JSON responses against their schemas (debug mode)
+    pub validate_responses: bool,
+    /// Serve Swagger UI at /api/docs
+    pub swagger_ui: bool,
+    /// Compress responses for clients that accept gzip or brotli
+    pub compression: bool,
+    /// Largest request body accepted
+    pub max_body_bytes: usize,
+    /// Request counts and latencies exported at /metrics
+    pub metrics: Arc<HttpMetrics>,
+    /// KataGo in GTP mode for /api/v1/game/* (None unless `[gtp] enabled`)
+    pub bot: Option<Arc<KatagoBot>>,
+    /// SQLite record of completed analyses (None unless `[history] database_path`)
+    pub history: Option<Arc<AnalysisHistory>>,
+    /// Log of analysis requests for replay (None unless `[audit] log_path`)
+    pub audit: Option<Arc<AuditLog>>,
+    /// Configuration in effect: the startup one with reloaded live settings
+    pub config: Arc<StdMutex<Config>>,
+    /// When the server started, for the health check's uptime
+    pub started: Instant,
+    /// Delivery of `callbackUrl` results (None unless `[webhooks] secret`)
+    pub webhooks: Option<Arc<Webhooks>>,
+    /// Games fetched for /api/v1/import/ogs (None when `[ogs] enabled` is false)
+    pub ogs: Option<Arc<Ogs>>,
 }
 
 // ============================================================================
 // New V1 API Types
 // ============================================================================
 
-/// Comprehensive analysis request supporting all KataGo features
-#[derive(Debug, Deserialize)]
+/// Add/remove stones on a stored position to derive a new one
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-#[allow(dead_code)] // Some fields reserved for future enhancements
-pub struct AnalysisRequest {
-    /// Moves played so far - can be simple coordinates (e.g., ["D4", "Q16"]) or
-    /// explicit color pairs (e.g., [["W", "D4"], ["B", "Q16"]]) for handicap games
-    pub moves: Vec<MoveInput>,
+pub struct EditPositionRequest {
+    /// Stones to add as [color, coordinate] pairs (e.g., [["B", "C3"]])
+    #[serde(default)]
+    pub add: Vec<(String, String)>,
 
-    /// Game rules: "tromp-taylor", "chinese", "japanese", "korean", "aga", etc.
+    /// Coordinates to clear
     #[serde(default)]
-    pub rules: Option<String>,
+    pub remove: Vec<String>,
 
-    /// Komi value for the game
+    /// Player to move in the derived position (defaults to the parent's)
     #[serde(default)]
-    pub komi: Option<f32>,
+    pub player_to_move: Option<String>,
+}
 
-    /// Board width (typically 19)
-    #[serde(default = "default_board_size")]
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionResponse {
+    pub position_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
     pub board_x_size: u8,
-
-    /// Board height (typically 19)
-    #[serde(default = "default_board_size")]
     pub board_y_size: u8,
+    pub player_to_move: String,
+    pub initial_stones: Vec<(String, String)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<Rules>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub komi: Option<f32>,
+}
 
-    /// Initial stones for handicap games
-    #[serde(default)]
-    pub initial_stones: Option<Vec<(String, String)>>,
+impl From<&Position> for PositionResponse {
+    fn from(position: &Position) -> Self {
+        Self {
+            position_id: position.id.clone(),
+            parent_id: position.parent_id.clone(),
+            board_x_size: position.board.width(),
+            board_y_size: position.board.height(),
+            player_to_move: position.player_to_move.as_str().to_string(),
+            initial_stones: position.board.to_initial_stones(),
+            rules: position.rules.clone(),
+            komi: position.komi,
+        }
+    }
+}
 
-    /// Player to move at turn 0
-    #[serde(default)]
-    pub initial_player: Option<String>,
+/// Upload a game record to the archive
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateGameRequest {
+    /// Moves, initialStones, komi, rules, and board size as in an analysis request
+    #[serde(flatten)]
+    pub position: AnalysisRequest,
 
-    /// Which turns to analyze (defaults to final position)
     #[serde(default)]
-    pub analyze_turns: Option<Vec<u32>>,
-
-    // Analysis control parameters
-    /// Override config file visit limit
+    pub black_player: Option<String>,
     #[serde(default)]
-    pub max_visits: Option<u32>,
-
-    /// Temperature for root policy (>1 = more exploration)
+    pub white_player: Option<String>,
     #[serde(default)]
-    pub root_policy_temperature: Option<f32>,
-
-    /// FPU reduction for exploration
+    pub event: Option<String>,
     #[serde(default)]
-    pub root_fpu_reduction_max: Option<f32>,
-
-    /// Length of principal variation to return
+    pub date: Option<String>,
     #[serde(default)]
-    pub analysis_pv_len: Option<u32>,
-
-    // Data request flags
-    /// Include territory ownership predictions
+    pub result: Option<String>,
+    /// "private", "link", or "public" (default)
     #[serde(default)]
-    pub include_ownership: Option<bool>,
+    pub visibility: Option<String>,
+}
 
-    /// Include ownership standard deviation
-    #[serde(default)]
-    pub include_ownership_stdev: Option<bool>,
+/// Change who can see a game (owner only)
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct VisibilityRequest {
+    pub visibility: String,
+}
 
-    /// Include ownership for each move candidate
-    #[serde(default)]
-    pub include_moves_ownership: Option<bool>,
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSummary {
+    pub game_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub black_player: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub white_player: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    pub board_x_size: u8,
+    pub board_y_size: u8,
+    pub move_count: usize,
+    /// File the game was imported from, for bulk-imported records
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
 
-    /// Include raw neural network policy
-    #[serde(default)]
-    pub include_policy: Option<bool>,
+impl From<&Game> for GameSummary {
+    fn from(game: &Game) -> Self {
+        Self {
+            game_id: game.id.clone(),
+            black_player: game.info.black_player.clone(),
+            white_player: game.info.white_player.clone(),
+            event: game.info.event.clone(),
+            date: game.info.date.clone(),
+            result: game.info.result.clone(),
+            board_x_size: game.board_x_size,
+            board_y_size: game.board_y_size,
+            move_count: game.moves.len(),
+            source: game.source.clone(),
+        }
+    }
+}
 
-    /// Include visit counts in principal variations
-    #[serde(default)]
-    pub include_pv_visits: Option<bool>,
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GameResponse {
+    #[serde(flatten)]
+    pub summary: GameSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub komi: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<String>,
+    pub initial_stones: Vec<(String, String)>,
+    pub moves: Vec<(String, String)>,
+    pub visibility: String,
+    /// Only returned to the owner, and only once the game is link-shareable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_url: Option<String>,
+    /// Only returned when the game is uploaded; send as X-Owner-Token to manage it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_token: Option<String>,
+}
 
-    // Move filtering
-    /// Moves to avoid considering
-    #[serde(default)]
-    pub avoid_moves: Option<Vec<MoveFilter>>,
+impl From<&Game> for GameResponse {
+    fn from(game: &Game) -> Self {
+        Self {
+            summary: GameSummary::from(game),
+            komi: game.komi,
+            rules: game.rules.clone(),
+            initial_stones: game.initial_stones(),
+            moves: game
+                .moves
+                .iter()
+                .map(|(stone, coord)| (stone.as_str().to_string(), coord.clone()))
+                .collect(),
+            visibility: game.visibility.as_str().to_string(),
+            share_url: None,
+            owner_token: None,
+        }
+    }
+}
 
-    /// Only consider these moves
-    #[serde(default)]
-    pub allow_moves: Option<Vec<MoveFilter>>,
+impl GameResponse {
+    /// Full view for the game's owner, including the share link
+    fn for_owner(game: &Game) -> Self {
+        let mut response = Self::from(game);
+        if game.visibility == Visibility::Link {
+            response.share_url = game
+                .share_token
+                .as_ref()
+                .map(|token| format!("/api/v1/shared/{}", token));
+        }
+        response
+    }
+}
 
-    // Advanced settings
-    /// Override search parameters
-    #[serde(default)]
-    pub override_settings: Option<serde_json::Value>,
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GameListResponse {
+    /// Total number of games in the archive (before filtering)
+    pub archive_size: usize,
+    pub games: Vec<GameSummary>,
+}
 
-    /// Report partial results during search (seconds)
+/// Archive search filters for GET /api/v1/games
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSearchParams {
+    /// Case-insensitive substring match on either player
     #[serde(default)]
-    pub report_during_search_every: Option<f32>,
-
-    /// Query priority
+    pub player: Option<String>,
     #[serde(default)]
-    pub priority: Option<i32>,
+    pub event: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
 
-    /// Optional request identifier
+/// Filters for GET /api/v1/analyses
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryParams {
+    /// RFC 3339 time; only analyses completed at or after it are listed
+    #[serde(default)]
+    pub since: Option<String>,
     #[serde(default)]
-    pub request_id: Option<String>,
+    pub limit: Option<usize>,
 }
 
-fn default_board_size() -> u8 {
-    19
+/// Response of GET /api/v1/analyses
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AnalysisHistoryResponse {
+    /// Oldest first
+    pub analyses: Vec<StoredAnalysis>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-#[allow(dead_code)] // Reserved for future move filtering support
-pub struct MoveFilter {
-    pub player: String,
-    pub moves: Vec<String>,
-    pub until_depth: u32,
+pub struct PositionMatch {
+    pub game_id: String,
+    /// Number of moves played before the position arose
+    pub turn: usize,
+    /// Move played from this position in the archived game
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_move: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct AnalysisResponse {
-    pub id: String,
-    pub turn_number: u32,
-    pub is_during_search: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub move_infos: Option<Vec<MoveInfo>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub root_info: Option<RootInfo>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ownership: Option<Vec<f32>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ownership_stdev: Option<Vec<f32>>,
+pub struct PositionSearchResponse {
+    pub total: usize,
+    pub matches: Vec<PositionMatch>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateResponse {
+    pub original_id: String,
+    /// Id of the flagged copy; absent when the copy was merged into the original
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub policy: Option<Vec<f32>>,
-    /// Human SL model policy predictions (requires human model and includePolicy=true)
+    pub duplicate_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub human_policy: Option<Vec<f32>>,
+    pub source: Option<String>,
+    /// "same-moves" (merged) or "same-positions" (rotated/mirrored copy, flagged)
+    pub kind: String,
+}
+
+impl From<&DuplicateRecord> for DuplicateResponse {
+    fn from(record: &DuplicateRecord) -> Self {
+        Self {
+            original_id: record.original_id.clone(),
+            duplicate_id: record.duplicate_id.clone(),
+            source: record.source.clone(),
+            kind: record.kind.as_str().to_string(),
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct MoveInfo {
-    pub move_coord: String,
-    pub visits: u32,
-    pub winrate: f32,
-    pub score_mean: f32,
-    pub score_stdev: f32,
-    pub score_lead: f32,
-    pub utility: f32,
+pub struct DuplicateListResponse {
+    pub total: usize,
+    pub duplicates: Vec<DuplicateResponse>,
+}
+
+/// Load or unload the human SL model
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct HumanModelRequest {
+    pub loaded: bool,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HumanModelResponse {
+    /// Whether human_model_path is set, i.e. the model can be loaded
+    pub configured: bool,
+    pub loaded: bool,
+}
+
+/// Response of POST /api/v1/admin/config/reload: changed settings by dotted name
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigReloadResponse {
+    /// Changes now in effect
+    pub applied: Vec<String>,
+    /// Changes ignored until the server is restarted
+    pub restart_required: Vec<String>,
+}
+
+/// Switch an engine to another model file
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelSwitchRequest {
+    /// Path of the model on the server
+    pub model_path: String,
+    /// Engine to switch (default: the primary engine)
+    #[serde(default)]
+    pub engine: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelSwitchResponse {
+    pub engine: String,
+    pub model_path: String,
+    pub previous_model_path: String,
+}
+
+/// Query string of the admin endpoints acting on one engine
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct EngineParams {
+    /// Engine name (default: the primary engine)
+    #[serde(default)]
+    pub engine: Option<String>,
+}
+
+/// Response of GET /api/v1/admin/engine
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineStatusResponse {
+    pub engine: String,
+    /// Process id of the KataGo process; absent while none is running
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub utility_lcb: Option<f32>,
-    pub lcb: f32,
-    pub prior: f32,
-    /// Human SL model prior for this move (requires human model)
+    pub pid: Option<u32>,
+    pub alive: bool,
+    /// Seconds since the running process started
+    pub uptime_secs: u64,
+    /// Restarts the monitor attempted since a process last stayed up for
+    /// `restart_stable_secs`
+    pub restart_attempts: u64,
+    /// 0 when the monitor never gives up
+    pub max_restart_attempts: u64,
+    /// The monitor stopped restarting KataGo; only an admin restart revives it
+    pub restarts_given_up: bool,
+    /// Queries sent to KataGo and still awaiting a response, oldest first
+    pub pending: Vec<PendingQueryStatus>,
+    /// Recent search speed, which targetLatencyMs budgets visits with; absent
+    /// until a search was measured
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub human_prior: Option<f32>,
-    pub order: u32,
+    pub visits_per_second: Option<f64>,
+    /// Absent until the first ping, and with keepalive disabled
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub pv: Option<Vec<String>>,
+    pub last_keepalive: Option<KeepaliveStatus>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingQueryStatus {
+    pub id: String,
+    /// Milliseconds since the query was sent
+    pub age_ms: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct KeepaliveStatus {
+    /// When the ping was sent (RFC 3339)
+    pub sent_at: String,
+    /// Why the ping could not be written to KataGo
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub pv_visits: Option<Vec<u32>>,
+    pub error: Option<String>,
+    /// When KataGo answered (RFC 3339); absent while it hasn't
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ownership: Option<Vec<f32>>,
+    pub answered_at: Option<String>,
+}
+
+/// Response of POST /api/v1/admin/engine/restart
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineRestartResponse {
+    pub engine: String,
+    /// Process id of the new KataGo process
+    pub pid: u32,
+    /// How long the new process took to load its model and answer
+    pub ready_after_ms: u64,
+    /// Queries in flight that failed with the old process
+    pub failed_queries: usize,
+}
+
+/// Response of GET /api/v1/admin/throughput
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ThroughputResponse {
+    /// Seconds the visit rates and utilizations look back over
+    pub window_secs: u64,
+    /// Every engine, the primary first
+    pub engines: Vec<EngineThroughput>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineThroughput {
+    pub engine: String,
+    #[serde(flatten)]
+    pub throughput: ThroughputSnapshot,
+}
+
+/// A GTP command for the bot, sent as given
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GtpCommandRequest {
+    /// One command line, e.g. "kata-get-param playoutDoublingAdvantage"
+    pub command: String,
+}
+
+/// Response of POST /api/v1/admin/gtp
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GtpCommandResponse {
+    pub command: String,
+    /// Whether KataGo answered with `=` rather than `?`
+    pub success: bool,
+    /// KataGo's reply as it wrote it, lines joined by newlines, without the empty
+    /// line that ends it
+    pub response: String,
+}
+
+/// Analyze a request from the audit log again
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayRequest {
+    pub request_id: String,
 }
 
-#[derive(Debug, Serialize)]
+/// Response of POST /api/v1/admin/replay: the logged results next to those of
+/// the current engine and model
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct RootInfo {
-    pub winrate: f32,
-    pub score_lead: f32,
-    pub utility: f32,
-    pub visits: u32,
-    pub current_player: String,
+pub struct ReplayResponse {
+    pub request_id: String,
+    /// The request and its outcome as logged
+    pub original: AuditEntry,
+    /// Results of a new search, one per turn like the original's
+    pub replayed: Vec<AnalysisResponse>,
+    /// Best move and evaluation of each turn, before and now
+    pub comparison: Vec<ReplayComparison>,
+}
+
+/// How a turn's evaluation changed on replay
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayComparison {
+    pub turn_number: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub raw_winrate: Option<f32>,
+    pub best_move_before: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub raw_score_mean: Option<f32>,
+    pub best_move_after: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub raw_st_score_error: Option<f32>,
-    // Human SL model fields (requires human model and humanSLProfile in overrideSettings)
+    pub winrate_before: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub human_winrate: Option<f32>,
+    pub winrate_after: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub human_score_mean: Option<f32>,
+    pub score_lead_before: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score_lead_after: Option<f32>,
+}
+
+/// Start a bulk SGF import from a path on the server
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportRequest {
+    /// Directory, .sgf file, or .tar/.tar.gz/.tgz archive
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportJobResponse {
+    pub job_id: String,
+    pub path: String,
+    pub status: String,
+    pub files_scanned: usize,
+    pub games_imported: usize,
+    pub positions_indexed: usize,
+    pub duplicates_merged: usize,
+    pub duplicates_flagged: usize,
+    pub error_count: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    pub started_at: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub human_score_stdev: Option<f32>,
+    pub finished_at: Option<String>,
+}
+
+impl From<&ImportJob> for ImportJobResponse {
+    fn from(job: &ImportJob) -> Self {
+        Self {
+            job_id: job.id.clone(),
+            path: job.path.clone(),
+            status: job.status.as_str().to_string(),
+            files_scanned: job.files_scanned,
+            games_imported: job.games_imported,
+            positions_indexed: job.positions_indexed,
+            duplicates_merged: job.duplicates_merged,
+            duplicates_flagged: job.duplicates_flagged,
+            error_count: job.error_count,
+            errors: job.errors.clone(),
+            started_at: job.started_at.clone(),
+            finished_at: job.finished_at.clone(),
+        }
+    }
+}
+
+/// Start guessing the moves of an archived game
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateGuessSessionRequest {
+    pub game_id: String,
+    /// Move index to start guessing from (default 0)
+    #[serde(default)]
+    pub start_turn: usize,
+    /// Visits used to rank guesses
+    #[serde(default)]
+    pub max_visits: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GuessRequest {
+    #[serde(rename = "move")]
+    pub guess: String,
 }
 
-#[derive(Debug, Serialize)]
-pub struct VersionResponse {
-    pub server: ServerVersion,
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GuessSessionResponse {
+    pub session_id: String,
+    pub game_id: String,
+    /// Index of the move to guess next
+    pub turn: usize,
+    pub total_turns: usize,
+    pub finished: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub katago: Option<KatagoVersion>,
-    pub model: ModelInfo,
+    pub player_to_move: Option<String>,
+    /// Moves played before `turn` as [color, coordinate] pairs
+    pub moves: Vec<(String, String)>,
+    pub initial_stones: Vec<(String, String)>,
+    pub score: u32,
+    pub guesses: u32,
+    pub correct: u32,
 }
 
-#[derive(Debug, Serialize)]
-pub struct ServerVersion {
-    pub name: String,
-    pub version: String,
+impl GuessSessionResponse {
+    fn new(session: &GuessSession, game: &Game) -> Self {
+        let finished = session.turn >= game.moves.len();
+        Self {
+            session_id: session.id.clone(),
+            game_id: game.id.clone(),
+            turn: session.turn,
+            total_turns: game.moves.len(),
+            finished,
+            player_to_move: (!finished).then(|| game.player_at(session.turn).as_str().to_string()),
+            moves: game.moves[..session.turn.min(game.moves.len())]
+                .iter()
+                .map(|(stone, coord)| (stone.as_str().to_string(), coord.clone()))
+                .collect(),
+            initial_stones: game.initial_stones(),
+            score: session.score,
+            guesses: session.guesses,
+            correct: session.correct,
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct KatagoVersion {
-    pub version: String,
+pub struct GuessResponse {
+    #[serde(rename = "move")]
+    pub guess: String,
+    pub actual_move: String,
+    pub correct: bool,
+    pub points: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine_rank: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual_engine_rank: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub git_hash: Option<String>,
+    pub policy_rank: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub human_policy_rank: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_move: Option<String>,
+    pub session: GuessSessionResponse,
 }
 
-#[derive(Debug, Serialize)]
+/// Start a game against the engine
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct ModelInfo {
-    pub name: String,
+pub struct CreatePlaySessionRequest {
+    /// Board size (default 19)
+    #[serde(default)]
+    pub board_size: Option<u8>,
+    /// Defaults to 0.5 in handicap games, else the server's default komi
+    #[serde(default)]
+    pub komi: Option<f32>,
+    #[serde(default)]
+    pub rules: Option<Rules>,
+    /// Black's handicap stones, placed on the star points (default 0)
+    #[serde(default)]
+    pub handicap: u8,
+    /// Color the client plays, "B" or "W" (default "B")
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Visits the engine searches for each of its moves
+    #[serde(default)]
+    pub max_visits: Option<u32>,
+    /// Play like a human of this profile, e.g. "rank_5k" (needs a human model)
+    #[serde(default, rename = "humanSLProfile")]
+    pub human_sl_profile: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct CacheClearResponse {
-    pub status: String,
-    pub timestamp: String,
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PlayMoveRequest {
+    /// The client's move, e.g. "D4" or "pass"
+    #[serde(rename = "move")]
+    pub move_coord: String,
 }
 
-#[derive(Debug, Serialize)]
-pub struct HealthResponse {
-    pub status: String,
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaySessionResponse {
+    pub session_id: String,
+    pub board_size: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub komi: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub timestamp: Option<String>,
+    pub rules: Option<Rules>,
+    pub handicap: u8,
+    pub initial_stones: Vec<(String, String)>,
+    /// Color the client plays
+    pub color: String,
+    /// Moves played so far as [color, coordinate] pairs
+    pub moves: Vec<(String, String)>,
+    /// Both players passed
+    pub finished: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub uptime: Option<u64>,
+    pub player_to_move: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_visits: Option<u32>,
+    #[serde(rename = "humanSLProfile", skip_serializing_if = "Option::is_none")]
+    pub human_sl_profile: Option<String>,
+}
+
+impl From<&PlaySession> for PlaySessionResponse {
+    fn from(session: &PlaySession) -> Self {
+        let finished = session.finished();
+        Self {
+            session_id: session.id.clone(),
+            board_size: session.board_size,
+            komi: session.komi,
+            rules: session.rules.clone(),
+            handicap: session.initial_stones.len() as u8,
+            initial_stones: session.initial_stones.clone(),
+            color: session.human_color.as_str().to_string(),
+            moves: session
+                .moves
+                .iter()
+                .map(|(stone, coord)| (stone.as_str().to_string(), coord.clone()))
+                .collect(),
+            finished,
+            player_to_move: (!finished).then(|| session.player_to_move().as_str().to_string()),
+            max_visits: session.max_visits,
+            human_sl_profile: session.human_sl_profile.clone(),
+        }
+    }
 }
 
-// RFC 7807 Problem Details
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct ProblemDetail {
-    #[serde(rename = "type")]
-    pub problem_type: String,
-    pub title: String,
-    pub status: u16,
-    pub detail: String,
+pub struct PlayMoveResponse {
+    #[serde(rename = "move")]
+    pub move_coord: String,
+    /// Stones the move captured
+    pub captured: Vec<String>,
+    /// The engine's reply; None if the move ended the game
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub instance: Option<String>,
+    pub bot_move: Option<String>,
+    pub bot_captured: Vec<String>,
+    /// The engine's evaluation before its reply, from Black's perspective
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub request_id: Option<String>,
+    pub evaluation: Option<TradeEvaluation>,
+    pub session: PlaySessionResponse,
 }
 
+/// Seconds a client should wait before retrying when the analysis queue is full
+const QUEUE_RETRY_AFTER_SECS: u64 = 1;
+
+/// Analyses listed by GET /api/v1/analyses without a limit, and at most with one
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+const MAX_HISTORY_LIMIT: usize = 1_000;
+
 // Custom error type for API responses with RFC 7807 support
 pub struct ApiError {
-    problem: ProblemDetail,
+    problem: Box<ProblemDetail>,
+    /// Sent as the Retry-After header
+    retry_after: Option<u64>,
 }
 
 impl ApiError {
     pub fn new(status: StatusCode, title: &str, detail: &str) -> Self {
         Self {
-            problem: ProblemDetail {
+            problem: Box::new(ProblemDetail {
                 problem_type: format!(
                     "https://katago-server/problems/{}",
                     title.to_lowercase().replace(' ', "-")
@@ -307,15 +824,38 @@ impl ApiError {
                 detail: detail.to_string(),
                 instance: None,
                 request_id: None,
-            },
+                invalid_moves: None,
+            }),
+            retry_after: None,
         }
     }
 
+    /// Ask the client to retry after `secs` seconds
+    pub fn with_retry_after(mut self, secs: u64) -> Self {
+        self.retry_after = Some(secs);
+        self
+    }
+
     pub fn with_request_id(mut self, request_id: String) -> Self {
         self.problem.request_id = Some(request_id);
         self
     }
 
+    /// The problem document, for errors reported outside an HTTP response
+    pub fn into_problem(mut self) -> ProblemDetail {
+        error!("API error: {}", self.problem.detail);
+        self.fill_request_id();
+        *self.problem
+    }
+
+    /// Tag the problem with the X-Request-Id of the request being handled, unless
+    /// the handler already set the request's own id
+    fn fill_request_id(&mut self) {
+        if self.problem.request_id.is_none() {
+            self.problem.request_id = REQUEST_ID.try_with(Clone::clone).ok();
+        }
+    }
+
     #[allow(dead_code)] // May be useful for future error context
     pub fn with_instance(mut self, instance: String) -> Self {
         self.problem.instance = Some(instance);
@@ -323,165 +863,3252 @@ impl ApiError {
     }
 }
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
-        error!("API error: {}", self.problem.detail);
-        let status =
-            StatusCode::from_u16(self.problem.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-        let mut headers = HeaderMap::new();
-        headers.insert("Content-Type", "application/problem+json".parse().unwrap());
+impl IntoResponse for ApiError {
+    fn into_response(mut self) -> Response {
+        error!("API error: {}", self.problem.detail);
+        self.fill_request_id();
+        let status =
+            StatusCode::from_u16(self.problem.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Type", "application/problem+json".parse().unwrap());
+        if let Some(secs) = self.retry_after {
+            headers.insert("Retry-After", secs.into());
+        }
+
+        (status, headers, Json(self.problem)).into_response()
+    }
+}
+
+/// Header correlating a request with its response and log lines
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest X-Request-Id taken from a client; longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    /// X-Request-Id of the request being handled
+    pub(crate) static REQUEST_ID: String;
+}
+
+/// X-Request-Id of the request being handled, or a new id outside a request
+pub(crate) fn current_request_id() -> String {
+    REQUEST_ID
+        .try_with(Clone::clone)
+        .unwrap_or_else(|_| uuid::Uuid::new_v4().to_string())
+}
+
+/// Middleware taking the client's X-Request-Id, or generating one, and handling
+/// the request in a tracing span carrying it. The id is echoed in the response
+/// headers and in every problem detail.
+pub async fn propagate_request_id(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| {
+            (1..=MAX_REQUEST_ID_LEN).contains(&id.len()) && id.bytes().all(|b| b.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let span = tracing::info_span!("request", request_id = %request_id);
+    telemetry::continue_trace(&span, request.headers());
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(request))
+        .instrument(span)
+        .await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+impl From<crate::error::KatagoError> for ApiError {
+    fn from(err: crate::error::KatagoError) -> Self {
+        use crate::error::KatagoError;
+        match err {
+            KatagoError::Timeout(secs) => ApiError::new(
+                StatusCode::GATEWAY_TIMEOUT,
+                "Analysis Timeout",
+                &format!("KataGo analysis timed out after {} seconds", secs),
+            ),
+            KatagoError::ProcessDied => ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Service Unavailable",
+                "KataGo process has died unexpectedly",
+            ),
+            KatagoError::ParseError(msg) => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Parse Error",
+                &format!("Failed to parse KataGo response: {}", msg),
+            ),
+            KatagoError::ProcessStartFailed(msg) => ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Service Unavailable",
+                &format!("Failed to start KataGo: {}", msg),
+            ),
+            KatagoError::InvalidCommand(msg) => ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Invalid Request",
+                &format!("Invalid command: {}", msg),
+            ),
+            err @ KatagoError::Cancelled(_) => {
+                ApiError::new(StatusCode::CONFLICT, "Analysis Cancelled", &err.to_string())
+            }
+            err @ KatagoError::Restarted(_) => ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Engine Restarted",
+                &err.to_string(),
+            ),
+            err @ KatagoError::QueueFull(_) => ApiError::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too Many Requests",
+                &format!("{}; retry later", err),
+            )
+            .with_retry_after(QUEUE_RETRY_AFTER_SECS),
+            err @ KatagoError::UnsupportedBoardSize { .. } => ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Unsupported Board Size",
+                &err.to_string(),
+            ),
+            KatagoError::InvalidMoves {
+                width,
+                height,
+                moves,
+            } => {
+                let listed: Vec<String> = moves
+                    .iter()
+                    .map(|m| format!("{} (index {})", m.move_coord, m.index))
+                    .collect();
+                let mut error = ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "Invalid Moves",
+                    &format!(
+                        "Moves not on the {}x{} board: {}",
+                        width,
+                        height,
+                        listed.join(", ")
+                    ),
+                );
+                error.problem.invalid_moves = Some(moves);
+                error
+            }
+            KatagoError::IllegalPosition(err @ LegalityError::Setup(_)) => ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Invalid Position",
+                &err.to_string(),
+            ),
+            KatagoError::IllegalPosition(err) => {
+                let mut error =
+                    ApiError::new(StatusCode::BAD_REQUEST, "Illegal Move", &err.to_string());
+                if let LegalityError::Move {
+                    index, move_coord, ..
+                } = err
+                {
+                    error.problem.invalid_moves = Some(vec![InvalidMove { index, move_coord }]);
+                }
+                error
+            }
+            err @ KatagoError::UnknownModel { .. } => {
+                ApiError::new(StatusCode::BAD_REQUEST, "Unknown Model", &err.to_string())
+            }
+            err @ KatagoError::UnknownProfile { .. } => {
+                ApiError::new(StatusCode::BAD_REQUEST, "Unknown Profile", &err.to_string())
+            }
+            err @ KatagoError::UnavailableHumanModel { .. } => ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Unknown Human Model",
+                &err.to_string(),
+            ),
+            KatagoError::ResponseError(msg) => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "KataGo Error",
+                &format!("KataGo returned error: {}", msg),
+            ),
+            KatagoError::IoError(err) => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal Error",
+                &format!("IO error: {}", err),
+            ),
+            KatagoError::JsonError(err) => ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "JSON Error",
+                &format!("JSON parsing error: {}", err),
+            ),
+        }
+    }
+}
+
+impl From<BoardError> for ApiError {
+    fn from(err: BoardError) -> Self {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Position",
+            &err.to_string(),
+        )
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Internal Error",
+            &err.to_string(),
+        )
+    }
+}
+
+pub fn create_router(state: AppState) -> Router {
+    let admin = Router::new()
+        .route("/api/v1/admin/import", post(v1_admin_import))
+        .route("/api/v1/admin/import/{id}", get(v1_admin_import_status))
+        .route("/api/v1/admin/duplicates", get(v1_admin_duplicates))
+        .route(
+            "/api/v1/admin/human-model",
+            get(v1_admin_human_model).put(v1_admin_set_human_model),
+        )
+        .route("/api/v1/admin/config/reload", post(v1_admin_reload_config))
+        .route("/api/v1/admin/model", post(v1_admin_switch_model))
+        .route("/api/v1/admin/replay", post(v1_admin_replay))
+        .route("/api/v1/admin/gtp", post(v1_admin_gtp))
+        .route("/api/v1/admin/engine", get(v1_admin_engine_status))
+        .route("/api/v1/admin/throughput", get(v1_admin_throughput))
+        .route(
+            "/api/v1/admin/engine/restart",
+            post(v1_admin_restart_engine),
+        )
+        .route("/console", get(console))
+        .route("/console/analyze", post(console_analyze))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_admin));
+
+    let validate_responses = state.validate_responses;
+    let (compression, max_body_bytes) = (state.compression, state.max_body_bytes);
+    let docs = if state.swagger_ui {
+        Router::new().route("/api/docs", get(swagger_ui))
+    } else {
+        Router::new()
+    };
+    let graphql = post(graphql::execute).with_state(graphql::schema(state.clone()));
+    let router = Router::new()
+        .merge(admin)
+        .merge(docs)
+        .route("/graphql", graphql)
+        .route("/api/v1/analysis", post(v1_analysis))
+        .route("/api/v1/analysis.proto", get(protobuf::schema))
+        .route("/api/v1/analysis/turns", post(v1_analysis_turns))
+        .route("/api/v1/analysis/{id}/cancel", post(v1_analysis_cancel))
+        .route("/api/v1/analysis/trade", post(v1_trade))
+        .route("/api/v1/analysis/human", post(v1_human_comparison))
+        .route("/api/v1/game/move", post(v1_game_move))
+        .route("/api/v1/game/score", post(v1_game_score))
+        .route("/api/v1/score", post(v1_score))
+        .route("/api/v1/policy", post(v1_policy))
+        .route("/api/v1/render/heatmap", post(v1_render_heatmap))
+        .route("/api/v1/render/review", post(v1_render_review))
+        .route("/api/v1/analysis/stream", get(stream_get).post(stream_post))
+        .route("/api/v1/analysis/subscribe", get(subscribe))
+        .route("/api/v1/analysis/channels/{channel}", get(watch_channel))
+        .route("/api/v1/selfplay", post(selfplay))
+        .route("/api/v1/presets", get(v1_presets))
+        .route("/api/v1/rules", get(v1_rules))
+        .route("/api/v1/handicap", get(v1_handicap))
+        .route("/api/v1/human/profiles", get(v1_human_profiles))
+        .route("/api/v1/katago/analysis", post(katago_analysis))
+        .route("/api/v1/positions", post(v1_create_position))
+        .route("/api/v1/positions/{id}", get(v1_get_position))
+        .route("/api/v1/positions/{id}/edit", post(v1_edit_position))
+        .route("/api/v1/games", post(v1_create_game).get(v1_list_games))
+        .route("/api/v1/games/search/position", post(v1_search_position))
+        .route("/api/v1/review", post(v1_review))
+        .route("/api/v1/review/graph", post(v1_review_graph))
+        .route("/api/v1/review/sgf", post(v1_review_sgf))
+        .route("/api/v1/import/ogs", post(v1_import_ogs))
+        .route("/api/v1/jobs", post(v1_create_job))
+        .route("/api/v1/jobs/{id}", get(v1_get_job).delete(v1_cancel_job))
+        .route("/api/v1/games/{id}", get(v1_get_game))
+        .route("/api/v1/games/{id}/visibility", put(v1_set_game_visibility))
+        .route("/api/v1/shared/{token}", get(v1_get_shared_game))
+        .route("/api/v1/guess/sessions", post(v1_create_guess_session))
+        .route("/api/v1/guess/sessions/{id}", get(v1_get_guess_session))
+        .route("/api/v1/guess/sessions/{id}/guess", post(v1_guess))
+        .route("/api/v1/play/sessions", post(v1_create_play_session))
+        .route(
+            "/api/v1/play/sessions/{id}",
+            get(v1_get_play_session).delete(v1_end_play_session),
+        )
+        .route("/api/v1/play/sessions/{id}/move", post(v1_play_move))
+        .route("/api/v1/health", get(v1_health))
+        .route("/api/v1/engine/protocol", get(v1_engine_protocol))
+        .route("/api/v1/version", get(v1_version))
+        .route("/api/v1/analyses", get(v1_list_analyses))
+        .route("/api/v1/cache/clear", post(v1_cache_clear))
+        .route("/api/v1/cache/stats", get(v1_cache_stats))
+        .route("/api/v1/schema", get(schema_index))
+        .route("/api/v1/schema/{name}", get(schema_by_name))
+        .route("/api/v1/openapi.json", get(openapi))
+        .route("/metrics", get(metrics))
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            track_requests,
+        ))
+        .with_state(state);
+
+    let router = if validate_responses {
+        router.route_layer(middleware::from_fn(validate_response))
+    } else {
+        router
+    };
+    // The limit applies to every body, read by an extractor or not
+    let router = router
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(max_body_bytes))
+        .layer(middleware::from_fn_with_state(
+            max_body_bytes,
+            body_too_large_problem,
+        ));
+    let router = if compression {
+        // Streams stay uncompressed so each chunk reaches the client as it is sent
+        let streams = DefaultPredicate::new()
+            .and(NotForContentType::const_new("application/x-ndjson"))
+            .and(NotForContentType::const_new("application/x-go-sgf"));
+        router.layer(CompressionLayer::new().compress_when(streams))
+    } else {
+        router
+    };
+    router.layer(middleware::from_fn(propagate_request_id))
+}
+
+/// Replace the plain-text 413 of an oversized body with a problem detail
+async fn body_too_large_problem(
+    State(max_body_bytes): State<usize>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    let is_problem = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/problem+json"));
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE || is_problem {
+        return response;
+    }
+    ApiError::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "Request Body Too Large",
+        &format!(
+            "Request bodies are limited to {} bytes ([server] max_body_bytes)",
+            max_body_bytes
+        ),
+    )
+    .into_response()
+}
+
+// ============================================================================
+// V1 API Handlers
+// ============================================================================
+
+#[axum::debug_handler]
+async fn v1_analysis(
+    State(state): State<AppState>,
+    format: Format,
+    headers: HeaderMap,
+    Body(mut request): Body<AnalysisRequest>,
+) -> std::result::Result<Response, ApiError> {
+    let request_id = request
+        .request_id
+        .clone()
+        .unwrap_or_else(current_request_id);
+
+    if request.analyze_turns.is_some() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            "analyzeTurns returns one result per turn; use POST /api/v1/analysis/turns",
+        )
+        .with_request_id(request_id));
+    }
+    let callback_url = request.callback_url.take();
+    let protobuf = protobuf::accepted(&headers);
+    if protobuf {
+        // The schema has ownership as the flat array only
+        request.ownership_format = None;
+    }
+    let mut request = resolve_position(&state.positions, request)
+        .map_err(|e| e.with_request_id(request_id.clone()))?;
+    request.request_id = Some(request_id.clone());
+
+    let analysis = run_analysis(state.clone(), request);
+    match callback_url {
+        Some(url) => respond_later(&state, url, request_id, analysis),
+        None if protobuf => Ok(protobuf::respond(&analysis.await?)),
+        None => Ok(format.respond(&analysis.await?)),
+    }
+}
+
+/// Analyze a request whose position is resolved and whose requestId is set, and
+/// record it in the history
+pub(crate) async fn run_analysis(
+    state: AppState,
+    request: AnalysisRequest,
+) -> std::result::Result<AnalysisResponse, ApiError> {
+    let request_id = request.request_id.clone().unwrap_or_default();
+    let started = Instant::now();
+    let response = state
+        .engine
+        .analyze(&request)
+        .await
+        .map_err(|e| ApiError::from(e).with_request_id(request_id));
+    audit(
+        &state,
+        AuditKind::Analysis,
+        &request,
+        response.as_ref().map(std::slice::from_ref),
+        started,
+    );
+    let response = response?;
+    record_analyses(&state, &request, &[&response], started);
+    let mut response = response;
+    shape_ownership(&mut response, &request);
+    Ok(response)
+}
+
+/// Answer 202 Accepted at once and POST the outcome of `work` to the callback URL
+/// once it is done: the result, or the problem detail the endpoint would have
+/// returned
+fn respond_later<T, F>(
+    state: &AppState,
+    callback_url: String,
+    request_id: String,
+    work: F,
+) -> std::result::Result<Response, ApiError>
+where
+    T: Serialize,
+    F: std::future::Future<Output = std::result::Result<T, ApiError>> + Send + 'static,
+{
+    let Some(webhooks) = state.webhooks.clone() else {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Callbacks Disabled",
+            "callbackUrl needs a [webhooks] secret to sign callbacks with",
+        )
+        .with_request_id(request_id));
+    };
+    let url = webhooks.parse_url(&callback_url).map_err(|detail| {
+        ApiError::new(StatusCode::BAD_REQUEST, "Invalid Callback URL", &detail)
+            .with_request_id(request_id.clone())
+    })?;
+
+    let id = request_id.clone();
+    let delivery = async move {
+        let (outcome, body) = match work.await {
+            Ok(result) => (Outcome::Completed, serde_json::to_vec(&result)),
+            Err(mut error) => {
+                error.fill_request_id();
+                (Outcome::Failed, serde_json::to_vec(&error.problem))
+            }
+        };
+        match body {
+            Ok(body) => webhooks.deliver(url, &id, outcome, body).await,
+            Err(e) => error!("Failed to serialize the callback of {}: {}", id, e),
+        }
+    };
+    tokio::spawn(
+        REQUEST_ID
+            .scope(request_id.clone(), delivery)
+            .in_current_span(),
+    );
+    let accepted = CallbackAccepted {
+        request_id,
+        callback_url,
+    };
+    Ok((StatusCode::ACCEPTED, Json(accepted)).into_response())
+}
+
+/// Analyze the turns listed in analyzeTurns with a single KataGo query
+#[axum::debug_handler]
+async fn v1_analysis_turns(
+    State(state): State<AppState>,
+    format: Format,
+    Body(request): Body<AnalysisRequest>,
+) -> std::result::Result<Response, ApiError> {
+    let request_id = request
+        .request_id
+        .clone()
+        .unwrap_or_else(current_request_id);
+    let mut request = resolve_position(&state.positions, request)
+        .map_err(|e| e.with_request_id(request_id.clone()))?;
+    request.request_id = Some(request_id);
+    Ok(format.respond(&run_turns(state, request).await?))
+}
+
+/// Analyze the turns of a request whose position is resolved and whose requestId
+/// is set, and record them in the history
+async fn run_turns(
+    state: AppState,
+    request: AnalysisRequest,
+) -> std::result::Result<AnalysisBatchResponse, ApiError> {
+    let request_id = request.request_id.clone().unwrap_or_default();
+    let started = Instant::now();
+    let turns = state
+        .engine
+        .analyze_turns(&request)
+        .await
+        .map_err(|e| ApiError::from(e).with_request_id(request_id.clone()));
+    audit(
+        &state,
+        AuditKind::Turns,
+        &request,
+        turns.as_deref(),
+        started,
+    );
+    let turns = turns?;
+    record_analyses(&state, &request, &turns.iter().collect::<Vec<_>>(), started);
+    let mut turns = turns;
+    for turn in &mut turns {
+        shape_ownership(turn, &request);
+    }
+    Ok(AnalysisBatchResponse {
+        id: request_id,
+        turns,
+    })
+}
+
+/// Reshape the ownership arrays of a response into the request's ownershipFormat.
+/// The history keeps KataGo's flat arrays.
+pub fn shape_ownership(response: &mut AnalysisResponse, request: &AnalysisRequest) {
+    let format = request.ownership_format.unwrap_or_default();
+    if format == OwnershipFormat::Flat {
+        return;
+    }
+    let (width, height) = (request.board_x_size, request.board_y_size);
+    let shape = |ownership: &mut Option<Ownership>| {
+        if let Some(Ownership::Flat(values)) = ownership.take() {
+            *ownership = Some(reshaped_ownership(values, format, width, height));
+        }
+    };
+    shape(&mut response.ownership);
+    shape(&mut response.ownership_stdev);
+    for info in response.move_infos.iter_mut().flatten() {
+        shape(&mut info.ownership);
+    }
+}
+
+/// A flat ownership array (row-major from the top row) as rows from the top row,
+/// or keyed by coordinate. An array of the wrong length stays flat.
+fn reshaped_ownership(
+    values: Vec<f32>,
+    format: OwnershipFormat,
+    width: u8,
+    height: u8,
+) -> Ownership {
+    let points = width as usize * height as usize;
+    if values.len() != points || points == 0 {
+        return Ownership::Flat(values);
+    }
+    match format {
+        OwnershipFormat::Flat => Ownership::Flat(values),
+        OwnershipFormat::Rows => {
+            Ownership::Rows(values.chunks(width as usize).map(<[f32]>::to_vec).collect())
+        }
+        OwnershipFormat::Map => Ownership::Map(
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    let x = (index % width as usize) as u8;
+                    let y = height - 1 - (index / width as usize) as u8;
+                    (Board::coord_name(x, y), value)
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Add completed analyses to the history, if one is kept. The write happens in the
+/// background; a failure is logged without failing the request.
+fn record_analyses(
+    state: &AppState,
+    request: &AnalysisRequest,
+    responses: &[&AnalysisResponse],
+    started: Instant,
+) {
+    let Some(history) = state.history.clone() else {
+        return;
+    };
+    let duration = started.elapsed();
+    let request = request.clone();
+    let responses: Vec<AnalysisResponse> = responses.iter().map(|&r| r.clone()).collect();
+    tokio::task::spawn_blocking(move || {
+        for response in &responses {
+            if let Err(e) = history.record(&request, response, duration) {
+                warn!("Failed to record analysis {}: {}", response.id, e);
+            }
+        }
+    });
+}
+
+/// Append a finished analysis request to the audit log, if one is kept. Like the
+/// history, the write happens in the background and a failure is only logged.
+fn audit(
+    state: &AppState,
+    kind: AuditKind,
+    request: &AnalysisRequest,
+    outcome: std::result::Result<&[AnalysisResponse], &ApiError>,
+    started: Instant,
+) {
+    let Some(log) = state.audit.clone() else {
+        return;
+    };
+    let outcome = outcome
+        .map(<[AnalysisResponse]>::to_vec)
+        .map_err(|e| (*e.problem).clone());
+    let entry = AuditEntry::new(kind, request, outcome, started.elapsed());
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = log.append(&entry) {
+            warn!("Failed to log request {}: {}", entry.request_id, e);
+        }
+    });
+}
+
+/// GET /api/v1/analyses?since=...&limit=...
+///
+/// Analyses recorded in the `[history]` database, oldest first.
+#[axum::debug_handler]
+async fn v1_list_analyses(
+    State(state): State<AppState>,
+    Query(params): Query<HistoryParams>,
+) -> std::result::Result<Json<AnalysisHistoryResponse>, ApiError> {
+    let history = state.history.clone().ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "History Disabled",
+            "No analysis history is kept; set [history] database_path to record one",
+        )
+    })?;
+    let since = match &params.since {
+        Some(since) => chrono::DateTime::parse_from_rfc3339(since)
+            .map_err(|e| {
+                ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "Invalid Request",
+                    &format!("since must be an RFC 3339 time: {}", e),
+                )
+            })?
+            .to_utc(),
+        None => chrono::DateTime::UNIX_EPOCH,
+    };
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .min(MAX_HISTORY_LIMIT);
+
+    let analyses = tokio::task::spawn_blocking(move || history.since(since, limit))
+        .await
+        .map_err(|e| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal Error",
+                &e.to_string(),
+            )
+        })?
+        .map_err(|e| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "History Error",
+                &format!("Failed to read the analysis history: {}", e),
+            )
+        })?;
+    Ok(Json(AnalysisHistoryResponse { analyses }))
+}
+
+/// Replace a request's positionId with the stored stones, player to move, and settings,
+/// then fill in the defaults of its preset
+pub(crate) fn resolve_position(
+    positions: &PositionStore,
+    mut request: AnalysisRequest,
+) -> std::result::Result<AnalysisRequest, ApiError> {
+    let preset = match request.preset.take() {
+        Some(name) => Some(Preset::find(&name).ok_or_else(|| unknown_preset(&name))?),
+        None => None,
+    };
+
+    if let Some(position_id) = request.position_id.take() {
+        let position = positions
+            .get(&position_id)
+            .ok_or_else(|| position_not_found(&position_id))?;
+
+        request.initial_stones = Some(position.board.to_initial_stones());
+        request.initial_player = Some(position.player_to_move.as_str().to_string());
+        request.board_x_size = position.board.width();
+        request.board_y_size = position.board.height();
+        request.rules = request.rules.or(position.rules);
+        request.komi = request.komi.or(position.komi);
+
+        if let Some(preset) = preset {
+            if request.board_x_size != preset.board_size
+                || request.board_y_size != preset.board_size
+            {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "Preset Mismatch",
+                    &format!(
+                        "Preset {} is for {}x{} boards but position {} is {}x{}",
+                        preset.name,
+                        preset.board_size,
+                        preset.board_size,
+                        position_id,
+                        request.board_x_size,
+                        request.board_y_size
+                    ),
+                ));
+            }
+        }
+    }
+
+    if let Some(text) = request.sgf.take() {
+        set_up_sgf(&mut request, &text, preset)?;
+    }
+    if let Some(rows) = request.diagram.take() {
+        set_up_diagram(&mut request, &rows, preset)?;
+    }
+    if let Some(preset) = preset {
+        preset.apply(&mut request);
+    }
+    if let Some(rules) = request.rules.as_mut() {
+        rules::normalize(rules)
+            .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, "Invalid Rules", &e))?;
+    }
+    if let Some(max_time) = request.max_time_secs {
+        if !(max_time > 0.0 && max_time.is_finite()) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Invalid Request",
+                "maxTimeSecs must be a positive number of seconds",
+            ));
+        }
+    }
+    if request.analysis_pv_len == Some(0) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            "analysisPVLen must be at least 1",
+        ));
+    }
+    if let Some(temperature) = request.root_policy_temperature {
+        if !(temperature > 0.0 && temperature.is_finite()) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Invalid Request",
+                "rootPolicyTemperature must be a positive number",
+            ));
+        }
+    }
+    if let Some(reduction) = request.root_fpu_reduction_max {
+        if !(reduction >= 0.0 && reduction.is_finite()) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Invalid Request",
+                "rootFpuReductionMax must be a number of at least 0",
+            ));
+        }
+    }
+    if request.target_latency_ms == Some(0) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            "targetLatencyMs must be a positive number of milliseconds",
+        ));
+    }
+    if request.tolerate_position == Some(true) {
+        flatten_to_setup(&mut request)?;
+    }
+    if let Some(region) = request.region.take() {
+        restrict_to_region(&mut request, &region)?;
+    }
+    Ok(request)
+}
+
+/// Search depth a region holds for: the whole search
+const REGION_DEPTH: u32 = 1000;
+
+/// Confine the search to a region by making every point outside it an avoidMoves
+/// entry for both players (KataGo takes allowMoves for a single player only)
+fn restrict_to_region(
+    request: &mut AnalysisRequest,
+    region: &Region,
+) -> std::result::Result<(), ApiError> {
+    let board = Board::new(request.board_x_size, request.board_y_size);
+    let parse = |coord: &str| {
+        board
+            .parse_point(coord)
+            .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, "Invalid Region", &e.to_string()))
+    };
+    let inside: HashSet<(u8, u8)> = match region {
+        Region::Points(points) => points
+            .iter()
+            .map(|coord| parse(coord))
+            .collect::<std::result::Result<_, _>>()?,
+        Region::Rectangle { from, to } => {
+            let (from, to) = (parse(from)?, parse(to)?);
+            let xs = from.0.min(to.0)..=from.0.max(to.0);
+            let ys = from.1.min(to.1)..=from.1.max(to.1);
+            xs.flat_map(|x| ys.clone().map(move |y| (x, y))).collect()
+        }
+    };
+    if inside.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Region",
+            "The region has no points",
+        ));
+    }
+
+    let outside: Vec<String> = (0..board.height())
+        .flat_map(|y| (0..board.width()).map(move |x| (x, y)))
+        .filter(|point| !inside.contains(point))
+        .map(|(x, y)| Board::coord_name(x, y))
+        .collect();
+    if outside.is_empty() {
+        return Ok(());
+    }
+    let filters = ["B", "W"].map(|player| MoveFilter {
+        player: player.to_string(),
+        moves: outside.clone(),
+        until_depth: REGION_DEPTH,
+    });
+    request
+        .avoid_moves
+        .get_or_insert_with(Vec::new)
+        .extend(filters);
+    Ok(())
+}
+
+/// Replace initialStones and moves with the resulting board as setup stones only.
+/// Moves are placed without legality checks (see [`Board::place_move`]), so only
+/// a board with a group left without liberties is rejected.
+fn flatten_to_setup(request: &mut AnalysisRequest) -> std::result::Result<(), ApiError> {
+    if request.analyze_turns.is_some() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            "toleratePosition sends the position without its moves, so it can't be combined with analyzeTurns",
+        ));
+    }
+    let mut board = Board::from_setup(
+        request.board_x_size,
+        request.board_y_size,
+        &AnalysisEngine::katago_initial_stones(request),
+        &[],
+    )?;
+    for mv in AnalysisEngine::katago_moves(request) {
+        let [color, coord] = mv.as_slice() else {
+            return Err(BoardError::InvalidCoordinate(mv.join(" ")).into());
+        };
+        let stone = Stone::parse(color).ok_or_else(|| BoardError::InvalidColor(color.clone()))?;
+        board.place_move(stone, coord)?;
+    }
+    board.check_liberties()?;
+    request.initial_player = Some(AnalysisEngine::next_player(request).to_uppercase());
+    request.initial_stones = Some(board.to_initial_stones());
+    request.moves.clear();
+    Ok(())
+}
+
+/// Replace the request's position with the main line of an SGF game: its board
+/// size, setup stones, and moves, and its komi and rules unless the request sets them
+fn set_up_sgf(
+    request: &mut AnalysisRequest,
+    text: &str,
+    preset: Option<&Preset>,
+) -> std::result::Result<(), ApiError> {
+    if !request.moves.is_empty()
+        || request.initial_stones.is_some()
+        || request.initial_player.is_some()
+        || request.diagram.is_some()
+    {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            "sgf sets the position; leave out moves, initialStones, initialPlayer, diagram, and positionId",
+        ));
+    }
+    let sgf = sgf::parse(text)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, "Invalid SGF", &e.to_string()))?;
+    let game = Game::from_sgf(sgf, None);
+    if let Some(preset) = preset {
+        if game.board_x_size != preset.board_size || game.board_y_size != preset.board_size {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Preset Mismatch",
+                &format!(
+                    "Preset {} is for {}x{} boards but the SGF is {}x{}",
+                    preset.name,
+                    preset.board_size,
+                    preset.board_size,
+                    game.board_x_size,
+                    game.board_y_size
+                ),
+            ));
+        }
+    }
+    let position = game.analysis_request(game.moves.len(), None);
+    request.moves = position.moves;
+    request.initial_stones = position.initial_stones;
+    request.initial_player = position.initial_player;
+    request.board_x_size = position.board_x_size;
+    request.board_y_size = position.board_y_size;
+    request.komi = request.komi.or(position.komi);
+    request.rules = request.rules.take().or(position.rules);
+    Ok(())
+}
+
+/// Replace the request's board with a diagram's: its size and stones, with Black
+/// to move unless the request says otherwise
+fn set_up_diagram(
+    request: &mut AnalysisRequest,
+    rows: &[String],
+    preset: Option<&Preset>,
+) -> std::result::Result<(), ApiError> {
+    if request.initial_stones.is_some() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            "diagram sets the stones; leave out initialStones and positionId",
+        ));
+    }
+    let board = Board::from_diagram(rows)?;
+    board.check_liberties()?;
+    if let Some(preset) = preset {
+        if board.width() != preset.board_size || board.height() != preset.board_size {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Preset Mismatch",
+                &format!(
+                    "Preset {} is for {}x{} boards but the diagram is {}x{}",
+                    preset.name,
+                    preset.board_size,
+                    preset.board_size,
+                    board.width(),
+                    board.height()
+                ),
+            ));
+        }
+    }
+    request.board_x_size = board.width();
+    request.board_y_size = board.height();
+    request.initial_stones = Some(board.to_initial_stones());
+    request
+        .initial_player
+        .get_or_insert_with(|| "B".to_string());
+    Ok(())
+}
+
+fn unknown_preset(name: &str) -> ApiError {
+    let names: Vec<&str> = PRESETS.iter().map(|p| p.name).collect();
+    ApiError::new(
+        StatusCode::BAD_REQUEST,
+        "Unknown Preset",
+        &format!("No preset named {} (available: {})", name, names.join(", ")),
+    )
+}
+
+/// Built-in board-size presets
+#[axum::debug_handler]
+async fn v1_presets() -> Json<&'static [Preset]> {
+    Json(PRESETS)
+}
+
+/// Named rulesets KataGo accepts, with the rules each stands for
+#[axum::debug_handler]
+async fn v1_rules() -> Json<&'static [RuleSet]> {
+    Json(RULE_SETS)
+}
+
+/// Query string of GET /api/v1/handicap
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HandicapParams {
+    pub stones: u8,
+    /// Defaults to 19
+    #[serde(default)]
+    pub board_size: Option<u8>,
+}
+
+/// GET /api/v1/handicap?stones=N&boardSize=19
+///
+/// Standard placement of N handicap stones, with the komi and first player of a
+/// handicap game, to copy into an analysis request
+#[axum::debug_handler]
+async fn v1_handicap(
+    Query(params): Query<HandicapParams>,
+) -> std::result::Result<Json<HandicapSetup>, ApiError> {
+    let board_size = params.board_size.unwrap_or(19);
+    crate::handicap::setup(board_size, params.stones)
+        .map(Json)
+        .map_err(|detail| ApiError::new(StatusCode::BAD_REQUEST, "Invalid Handicap", &detail))
+}
+
+/// humanSLProfile values for `overrideSettings`, so clients needn't guess them.
+/// Without a loaded human model no profile has an effect, and none are listed.
+#[axum::debug_handler]
+async fn v1_human_profiles(State(state): State<AppState>) -> Json<HumanProfilesResponse> {
+    let human_models = state.engine.human_models();
+    let profiles = if human_models.is_empty() {
+        Vec::new()
+    } else {
+        human_profiles::profiles()
+    };
+    Json(HumanProfilesResponse {
+        human_models,
+        profiles,
+    })
+}
+
+fn position_not_found(position_id: &str) -> ApiError {
+    ApiError::new(
+        StatusCode::NOT_FOUND,
+        "Position Not Found",
+        &format!("No stored position with id {}", position_id),
+    )
+}
+
+fn parse_stone(color: &str) -> std::result::Result<Stone, ApiError> {
+    Stone::parse(color).ok_or_else(|| ApiError::from(BoardError::InvalidColor(color.to_string())))
+}
+
+#[axum::debug_handler]
+async fn v1_create_position(
+    State(state): State<AppState>,
+    Json(request): Json<AnalysisRequest>,
+) -> std::result::Result<(StatusCode, Json<PositionResponse>), ApiError> {
+    let request = resolve_position(&state.positions, request)?;
+    let board = Board::from_setup(
+        request.board_x_size,
+        request.board_y_size,
+        &AnalysisEngine::katago_initial_stones(&request),
+        &AnalysisEngine::katago_moves(&request),
+    )?;
+    let player_to_move = parse_stone(AnalysisEngine::next_player(&request))?;
+
+    let position = Position {
+        id: uuid::Uuid::new_v4().to_string(),
+        parent_id: None,
+        board,
+        player_to_move,
+        rules: request.rules,
+        komi: request.komi,
+    };
+    let response = PositionResponse::from(&position);
+    state.positions.insert(position.id.clone(), position);
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[axum::debug_handler]
+async fn v1_get_position(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<PositionResponse>, ApiError> {
+    let position = state
+        .positions
+        .get(&id)
+        .ok_or_else(|| position_not_found(&id))?;
+    Ok(Json(PositionResponse::from(&position)))
+}
+
+#[axum::debug_handler]
+async fn v1_edit_position(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<EditPositionRequest>,
+) -> std::result::Result<(StatusCode, Json<PositionResponse>), ApiError> {
+    let position = state
+        .positions
+        .get(&id)
+        .ok_or_else(|| position_not_found(&id))?;
+
+    let edit = PositionEdit {
+        add: request
+            .add
+            .iter()
+            .map(|(color, coord)| Ok((parse_stone(color)?, coord.clone())))
+            .collect::<std::result::Result<_, ApiError>>()?,
+        remove: request.remove,
+        player_to_move: request
+            .player_to_move
+            .as_deref()
+            .map(parse_stone)
+            .transpose()?,
+    };
+    let derived = position.derive(&edit)?;
+    let response = PositionResponse::from(&derived);
+    state.positions.insert(derived.id.clone(), derived);
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Reject admin requests without the configured bearer token
+async fn require_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> std::result::Result<Response, ApiError> {
+    let Some(expected) = state.admin_token.as_deref() else {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "Admin Disabled",
+            "Admin endpoints are disabled; set server.admin_token to enable them",
+        ));
+    };
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match provided {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => {
+            Ok(next.run(request).await)
+        }
+        _ => Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "Unauthorized",
+            "A valid admin bearer token is required",
+        )),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[axum::debug_handler]
+async fn v1_admin_import(
+    State(state): State<AppState>,
+    Json(request): Json<ImportRequest>,
+) -> (StatusCode, Json<ImportJobResponse>) {
+    let job = ImportJob::new(&request.path);
+    let response = ImportJobResponse::from(&job);
+    state.import_jobs.insert(job.id.clone(), job.clone());
+
+    let games = state.games.clone();
+    let jobs = state.import_jobs.clone();
+    tokio::task::spawn_blocking(move || {
+        let path = std::path::PathBuf::from(&request.path);
+        run_import(&path, &games, job, |progress| {
+            jobs.update(&progress.id, |stored| *stored = progress.clone());
+        });
+    });
+
+    (StatusCode::ACCEPTED, Json(response))
+}
+
+#[axum::debug_handler]
+async fn v1_admin_import_status(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<ImportJobResponse>, ApiError> {
+    let job = state.import_jobs.get(&id).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "Import Not Found",
+            &format!("No import job with id {}", id),
+        )
+    })?;
+    Ok(Json(ImportJobResponse::from(&job)))
+}
+
+#[axum::debug_handler]
+async fn v1_admin_duplicates(State(state): State<AppState>) -> Json<DuplicateListResponse> {
+    let duplicates: Vec<DuplicateResponse> = state
+        .games
+        .duplicates()
+        .iter()
+        .map(DuplicateResponse::from)
+        .collect();
+    Json(DuplicateListResponse {
+        total: duplicates.len(),
+        duplicates,
+    })
+}
+
+fn human_model_status(engine: &AnalysisEngine) -> HumanModelResponse {
+    HumanModelResponse {
+        configured: engine.human_model_path().is_some(),
+        loaded: engine.human_model_loaded(),
+    }
+}
+
+#[axum::debug_handler]
+async fn v1_admin_human_model(State(state): State<AppState>) -> Json<HumanModelResponse> {
+    Json(human_model_status(state.engine.primary()))
+}
+
+/// Restart the primary engine with or without the human model
+#[axum::debug_handler]
+async fn v1_admin_set_human_model(
+    State(state): State<AppState>,
+    Json(request): Json<HumanModelRequest>,
+) -> std::result::Result<Json<HumanModelResponse>, ApiError> {
+    let engine = state.engine.primary().clone();
+    tokio::task::spawn_blocking(move || {
+        engine.set_human_model(request.loaded)?;
+        Ok::<_, ApiError>(human_model_status(&engine))
+    })
+    .await
+    .map_err(|e| ApiError::from(anyhow::Error::from(e)))?
+    .map(Json)
+}
+
+/// POST /api/v1/admin/config/reload
+///
+/// Re-read the configuration file (with environment overrides) and apply the
+/// settings that don't need KataGo restarted: timeouts, queue and cache sizes,
+/// and subscription budgets. Other changes are reported, not applied.
+#[axum::debug_handler]
+async fn v1_admin_reload_config(
+    State(state): State<AppState>,
+) -> std::result::Result<Json<ConfigReloadResponse>, ApiError> {
+    let mut reloaded = Config::from_file(CONFIG_FILE).map_err(|e| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Configuration",
+            &format!("Failed to load {}: {}", CONFIG_FILE, e),
+        )
+    })?;
+    reloaded.apply_env_overrides();
+
+    let mut config = state.config.lock().unwrap();
+    let changes = config.reload(&reloaded);
+    state.engine.apply_settings(&config.katago);
+    state.engine.set_defaults(config.analysis.clone());
+    state.subscriptions.set_config(config.subscriptions.clone());
+    info!(
+        "Reloaded {}: applied {:?}, restart required for {:?}",
+        CONFIG_FILE, changes.applied, changes.restart_required
+    );
+    Ok(Json(ConfigReloadResponse {
+        applied: changes.applied,
+        restart_required: changes.restart_required,
+    }))
+}
+
+/// POST /api/v1/admin/model
+///
+/// Start KataGo with another model and switch to it once it has loaded. Queries
+/// already sent finish on the old model; if the new one fails to load, the old
+/// one keeps serving.
+#[axum::debug_handler]
+async fn v1_admin_switch_model(
+    State(state): State<AppState>,
+    Json(request): Json<ModelSwitchRequest>,
+) -> std::result::Result<Json<ModelSwitchResponse>, ApiError> {
+    let name = request.engine.unwrap_or_else(|| "default".to_string());
+    let engine = find_engine(&state, &name)?;
+    if !std::path::Path::new(&request.model_path).is_file() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Model Not Found",
+            &format!("No model file at {}", request.model_path),
+        ));
+    }
+
+    let model_path = request.model_path;
+    tokio::task::spawn_blocking(move || match engine.switch_model(&model_path) {
+        Ok(previous_model_path) => Ok(ModelSwitchResponse {
+            engine: name,
+            model_path,
+            previous_model_path,
+        }),
+        // The old process is still serving, so this is the request's fault
+        Err(crate::error::KatagoError::ProcessStartFailed(detail)) => Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Model Load Failed",
+            &detail,
+        )),
+        Err(e) => Err(ApiError::from(e)),
+    })
+    .await
+    .map_err(|e| ApiError::from(anyhow::Error::from(e)))?
+    .map(Json)
+}
+
+/// POST /api/v1/admin/replay
+///
+/// Search a logged request again with the current engines and models, bypassing
+/// the result cache, and compare the results with the logged ones
+#[axum::debug_handler]
+async fn v1_admin_replay(
+    State(state): State<AppState>,
+    Json(replay): Json<ReplayRequest>,
+) -> std::result::Result<Json<ReplayResponse>, ApiError> {
+    let log = state.audit.clone().ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "Audit Log Disabled",
+            "No audit log is kept; set [audit] log_path to record one",
+        )
+    })?;
+    let request_id = replay.request_id.clone();
+    let original = tokio::task::spawn_blocking(move || log.find(&request_id))
+        .await
+        .map_err(|e| ApiError::from(anyhow::Error::from(e)))?
+        .map_err(|e| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Audit Log Error",
+                &format!("Failed to read the audit log: {}", e),
+            )
+        })?
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::NOT_FOUND,
+                "Request Not Found",
+                &format!("No request {} in the audit log", replay.request_id),
+            )
+        })?;
+
+    // A query id of its own, so the replay never collides with a live request
+    let mut request = original.request.clone();
+    request.request_id = Some(format!("replay-{}", uuid::Uuid::new_v4()));
+    let replayed = match original.kind {
+        AuditKind::Analysis => state.engine.reanalyze(&request).await.map(|r| vec![r]),
+        AuditKind::Turns => state.engine.analyze_turns(&request).await,
+    }
+    .map_err(|e| ApiError::from(e).with_request_id(replay.request_id.clone()))?;
+
+    let best = |response: Option<&AnalysisResponse>| {
+        let best = response
+            .and_then(|r| r.move_infos.as_deref())
+            .and_then(|infos| infos.iter().min_by_key(|info| info.order));
+        let root = response.and_then(|r| r.root_info.as_ref());
+        (
+            best.map(|info| info.move_coord.clone()),
+            root.map(|root| root.winrate),
+            root.map(|root| root.score_lead),
+        )
+    };
+    let comparison = replayed
+        .iter()
+        .map(|after| {
+            let before = original
+                .responses
+                .iter()
+                .find(|r| r.turn_number == after.turn_number);
+            let (best_move_before, winrate_before, score_lead_before) = best(before);
+            let (best_move_after, winrate_after, score_lead_after) = best(Some(after));
+            ReplayComparison {
+                turn_number: after.turn_number,
+                best_move_before,
+                best_move_after,
+                winrate_before,
+                winrate_after,
+                score_lead_before,
+                score_lead_after,
+            }
+        })
+        .collect();
+    Ok(Json(ReplayResponse {
+        request_id: replay.request_id,
+        original,
+        replayed,
+        comparison,
+    }))
+}
+
+/// GTP commands that stream output until stopped or end the process, which
+/// the passthrough can't answer
+const UNFORWARDABLE_GTP_COMMANDS: &[&str] = &[
+    "quit",
+    "analyze",
+    "genmove_analyze",
+    "kata-analyze",
+    "kata-genmove_analyze",
+    "kata-search_analyze",
+    "kata-search_analyze_cancellable",
+    "lz-analyze",
+    "lz-genmove_analyze",
+];
+
+/// POST /api/v1/admin/gtp
+///
+/// Forward one GTP command to the bot and return KataGo's raw reply, e.g. to read
+/// or change a search parameter with kata-get-param/kata-set-param. The command
+/// waits for any game the bot is playing.
+#[axum::debug_handler]
+async fn v1_admin_gtp(
+    State(state): State<AppState>,
+    Json(request): Json<GtpCommandRequest>,
+) -> std::result::Result<Json<GtpCommandResponse>, ApiError> {
+    let bot = gtp_bot(&state)?;
+    let command = request.command.trim();
+    let invalid =
+        |detail: &str| ApiError::new(StatusCode::BAD_REQUEST, "Invalid GTP Command", detail);
+    if command.is_empty() {
+        return Err(invalid("command is empty"));
+    }
+    if command.contains(['\n', '\r']) {
+        return Err(invalid("Send one command per request"));
+    }
+    // GTP allows a numeric id before the command name
+    let mut words = command.split_whitespace().peekable();
+    words.next_if(|word| word.bytes().all(|b| b.is_ascii_digit()));
+    let name = words.next().unwrap_or_default();
+    if UNFORWARDABLE_GTP_COMMANDS.contains(&name) {
+        return Err(invalid(&format!(
+            "{} can't be forwarded: it streams output until stopped or ends the bot",
+            name
+        )));
+    }
+
+    let response = bot.raw_command(command).await?;
+    Ok(Json(GtpCommandResponse {
+        command: command.to_string(),
+        success: response.starts_with('='),
+        response,
+    }))
+}
+
+/// GET /api/v1/admin/engine?engine=NAME
+///
+/// State of an engine's KataGo process and of the monitor restarting it
+#[axum::debug_handler]
+async fn v1_admin_engine_status(
+    State(state): State<AppState>,
+    Query(params): Query<EngineParams>,
+) -> std::result::Result<Json<EngineStatusResponse>, ApiError> {
+    let name = params.engine.unwrap_or_else(|| "default".to_string());
+    let engine = find_engine(&state, &name)?;
+    Ok(Json(EngineStatusResponse {
+        engine: name,
+        pid: engine.pid(),
+        alive: engine.is_alive(),
+        uptime_secs: engine.uptime_secs(),
+        restart_attempts: engine.restart_attempts(),
+        max_restart_attempts: engine.max_restart_attempts(),
+        restarts_given_up: engine.restarts_given_up(),
+        pending: engine
+            .pending_queries()
+            .into_iter()
+            .map(|(id, age)| PendingQueryStatus {
+                id,
+                age_ms: age.as_millis() as u64,
+            })
+            .collect(),
+        visits_per_second: engine.visits_per_second(),
+        last_keepalive: engine.last_keepalive().map(|ping| KeepaliveStatus {
+            sent_at: ping.sent_at.to_rfc3339(),
+            error: ping.error,
+            answered_at: ping.answered_at.map(|at| at.to_rfc3339()),
+        }),
+    }))
+}
+
+/// GET /api/v1/admin/throughput
+///
+/// How much each engine's KataGo process searched recently, how busy it was, and
+/// how long searches of each size take, for sizing pools and default visits
+#[axum::debug_handler]
+async fn v1_admin_throughput(State(state): State<AppState>) -> Json<ThroughputResponse> {
+    Json(ThroughputResponse {
+        window_secs: throughput::WINDOW.as_secs(),
+        engines: state
+            .engine
+            .named()
+            .map(|(name, engine)| EngineThroughput {
+                engine: name.to_string(),
+                throughput: engine.throughput(),
+            })
+            .collect(),
+    })
+}
+
+/// POST /api/v1/admin/engine/restart?engine=NAME
+///
+/// Kill an engine's KataGo process and start it again, for a process that hangs
+/// while still running. Queries in flight fail with 503 Engine Restarted; the
+/// response comes once the new process has loaded its model.
+#[axum::debug_handler]
+async fn v1_admin_restart_engine(
+    State(state): State<AppState>,
+    Query(params): Query<EngineParams>,
+) -> std::result::Result<Json<EngineRestartResponse>, ApiError> {
+    let name = params.engine.unwrap_or_else(|| "default".to_string());
+    let engine = find_engine(&state, &name)?;
+    tokio::task::spawn_blocking(move || {
+        let started = std::time::Instant::now();
+        let report = engine.restart()?;
+        Ok::<_, ApiError>(EngineRestartResponse {
+            engine: name,
+            pid: report.pid,
+            ready_after_ms: started.elapsed().as_millis() as u64,
+            failed_queries: report.failed_queries,
+        })
+    })
+    .await
+    .map_err(|e| ApiError::from(anyhow::Error::from(e)))?
+    .map(Json)
+}
+
+/// Engine by its `[[engines]]` name, "default" being the primary one
+fn find_engine(state: &AppState, name: &str) -> std::result::Result<Arc<AnalysisEngine>, ApiError> {
+    state
+        .engine
+        .named()
+        .find(|(engine_name, _)| *engine_name == name)
+        .map(|(_, engine)| engine.clone())
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::NOT_FOUND,
+                "Engine Not Found",
+                &format!("No engine named '{}'", name),
+            )
+        })
+}
+
+/// Header carrying the secret returned when a game is uploaded
+const OWNER_TOKEN_HEADER: &str = "x-owner-token";
+
+fn is_owner(game: &Game, headers: &HeaderMap) -> bool {
+    let provided = headers
+        .get(OWNER_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok());
+    match (game.owner_token.as_deref(), provided) {
+        (Some(expected), Some(token)) => constant_time_eq(token.as_bytes(), expected.as_bytes()),
+        _ => false,
+    }
+}
+
+/// Look up a game by id for a caller: public games, or any game for its owner.
+/// Non-public games are reported as not found so ids don't leak their existence.
+fn readable_game(
+    games: &GameDatabase,
+    id: &str,
+    headers: &HeaderMap,
+) -> std::result::Result<Game, ApiError> {
+    games
+        .get(id)
+        .filter(|game| game.visibility == Visibility::Public || is_owner(game, headers))
+        .ok_or_else(|| game_not_found(id))
+}
+
+fn parse_visibility(value: &str) -> std::result::Result<Visibility, ApiError> {
+    Visibility::parse(value).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Visibility",
+            &format!(
+                "Unknown visibility '{}' (expected private, link, or public)",
+                value
+            ),
+        )
+    })
+}
+
+fn game_not_found(game_id: &str) -> ApiError {
+    ApiError::new(
+        StatusCode::NOT_FOUND,
+        "Game Not Found",
+        &format!("No archived game with id {}", game_id),
+    )
+}
+
+fn session_not_found(session_id: &str) -> ApiError {
+    ApiError::new(
+        StatusCode::NOT_FOUND,
+        "Session Not Found",
+        &format!("No guess session with id {}", session_id),
+    )
+}
+
+#[axum::debug_handler]
+async fn v1_create_game(
+    State(state): State<AppState>,
+    Json(request): Json<CreateGameRequest>,
+) -> std::result::Result<(StatusCode, Json<GameResponse>), ApiError> {
+    let visibility = match request.visibility.as_deref() {
+        None => Visibility::Public,
+        Some(value) => parse_visibility(value)?,
+    };
+    let owner_token = new_token();
+
+    let info = GameInfo {
+        black_player: request.black_player,
+        white_player: request.white_player,
+        event: request.event,
+        date: request.date,
+        result: request.result,
+    };
+    // The visibility is set before the game is stored so a private upload is
+    // never listed, not even briefly
+    let game = Game {
+        owner_token: Some(owner_token.clone()),
+        visibility,
+        share_token: (visibility == Visibility::Link).then(new_token),
+        ..game_from_position(&request.position, info)?
+    };
+
+    // Uploads have a fresh owner, so they are never merged into another game
+    let insertion = state.games.insert(game);
+    let stored = state
+        .games
+        .get(&insertion.game_id)
+        .ok_or_else(|| game_not_found(&insertion.game_id))?;
+    let mut response = GameResponse::for_owner(&stored);
+    response.owner_token = Some(owner_token);
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Build a (public, unowned) game record from analysis request position fields,
+/// replaying it once so only legal, well-formed records are accepted
+fn game_from_position(
+    position: &AnalysisRequest,
+    info: GameInfo,
+) -> std::result::Result<Game, ApiError> {
+    let initial_stones = AnalysisEngine::katago_initial_stones(position);
+    let moves = AnalysisEngine::katago_moves(position);
+    Board::from_setup(
+        position.board_x_size,
+        position.board_y_size,
+        &initial_stones,
+        &moves,
+    )?;
+
+    let to_stones =
+        |pairs: Vec<Vec<String>>| -> std::result::Result<Vec<(Stone, String)>, ApiError> {
+            pairs
+                .into_iter()
+                .map(|pair| Ok((parse_stone(&pair[0])?, pair[1].to_uppercase())))
+                .collect()
+        };
+    let first_player = match position.moves.first() {
+        Some(_) => moves[0][0].as_str(),
+        None => AnalysisEngine::next_player(position),
+    };
+
+    Ok(Game {
+        id: uuid::Uuid::new_v4().to_string(),
+        info,
+        board_x_size: position.board_x_size,
+        board_y_size: position.board_y_size,
+        komi: position.komi,
+        // SGF's RU property only holds a ruleset name
+        rules: position
+            .rules
+            .as_ref()
+            .and_then(|rules| rules.name().map(str::to_string)),
+        initial_stones: to_stones(initial_stones)?,
+        initial_player: parse_stone(first_player)?,
+        moves: to_stones(moves)?,
+        source: None,
+        visibility: Visibility::Public,
+        owner_token: None,
+        share_token: None,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReviewParams {
+    max_visits: Option<u32>,
+    callback_url: Option<String>,
+    format: Option<ReviewFormat>,
+}
+
+/// The ReviewRequest of a review body and its game: either a JSON ReviewRequest or
+/// the SGF file itself (any other content type, with `?maxVisits=` in the query string)
+fn review_body(
+    headers: &HeaderMap,
+    params: ReviewParams,
+    body: String,
+) -> std::result::Result<(ReviewRequest, Game), ApiError> {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let request = if is_json {
+        serde_json::from_str::<ReviewRequest>(&body).map_err(|e| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Invalid Review Request",
+                &e.to_string(),
+            )
+        })?
+    } else {
+        ReviewRequest {
+            sgf: body,
+            max_visits: params.max_visits,
+            callback_url: params.callback_url,
+            format: params.format,
+            ..Default::default()
+        }
+    };
+
+    let game = review_game(&request)?;
+    Ok((request, game))
+}
+
+/// The game of a review request's SGF
+pub(crate) fn review_game(request: &ReviewRequest) -> std::result::Result<Game, ApiError> {
+    let sgf = sgf::parse(&request.sgf)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, "Invalid SGF", &e.to_string()))?;
+    Ok(Game::from_sgf(sgf, None))
+}
+
+/// Review an SGF game move by move. The body is either a JSON ReviewRequest or the
+/// SGF file itself (any other content type, with `?maxVisits=`, `?callbackUrl=` and
+/// `?format=` in the query string). The katrain and lizzie formats return the
+/// reviewed game as an SGF file.
+#[axum::debug_handler]
+async fn v1_review(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ReviewParams>,
+    body: String,
+) -> std::result::Result<Response, ApiError> {
+    let (request, game) = review_body(&headers, params, body)?;
+    review_response(&state, request, game).await
+}
+
+/// POST /api/v1/import/ogs
+///
+/// Review a game from online-go.com by its id: the server fetches the SGF from the
+/// OGS API and reviews it as POST /api/v1/review would
+#[axum::debug_handler]
+async fn v1_import_ogs(
+    State(state): State<AppState>,
+    Json(request): Json<OgsImportRequest>,
+) -> std::result::Result<Response, ApiError> {
+    let ogs = state.ogs.as_ref().ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "OGS Import Disabled",
+            "Fetching games from OGS is disabled; set [ogs] enabled = true to allow it",
+        )
+    })?;
+    let sgf = ogs.fetch_sgf(request.game_id).await.map_err(|e| match e {
+        OgsError::NotFound(_) => {
+            ApiError::new(StatusCode::NOT_FOUND, "OGS Game Not Found", &e.to_string())
+        }
+        _ => ApiError::new(StatusCode::BAD_GATEWAY, "OGS Unavailable", &e.to_string()),
+    })?;
+    let request = ReviewRequest {
+        sgf,
+        max_visits: request.max_visits,
+        winrate_loss: request.winrate_loss,
+        score_loss: request.score_loss,
+        callback_url: request.callback_url,
+        format: request.format,
+    };
+    let game = review_game(&request)?;
+    review_response(&state, request, game).await
+}
+
+/// The response to a review request: the review, its SGF file in a KaTrain or
+/// Lizzie format, or 202 Accepted with a callbackUrl
+async fn review_response(
+    state: &AppState,
+    mut request: ReviewRequest,
+    game: Game,
+) -> std::result::Result<Response, ApiError> {
+    let callback_url = request.callback_url.take();
+    let format = request.format.take().unwrap_or_default();
+    if format != ReviewFormat::Json {
+        if callback_url.is_some() {
+            return Err(sgf_format_not_sent());
+        }
+        let (exported, max_visits) = (game.clone(), request.max_visits);
+        let review = run_review(state, request, game, |_| {}).await?;
+        return Ok(sgf_file(export_review_sgf(
+            &exported, &review, format, max_visits,
+        )));
+    }
+    let review = run_review(state, request, game, |_| {});
+    match callback_url {
+        Some(url) => respond_later(state, url, current_request_id(), review),
+        None => Ok(Json(review.await?).into_response()),
+    }
+}
+
+/// POST /api/v1/render/review
+///
+/// Review an SGF game like POST /api/v1/review and draw it move by move, as an
+/// animated SVG or GIF
+#[axum::debug_handler]
+async fn v1_render_review(
+    State(state): State<AppState>,
+    Json(request): Json<ReviewAnimationRequest>,
+) -> std::result::Result<Response, ApiError> {
+    let point_size = request.point_size.unwrap_or(animation::DEFAULT_POINT_SIZE);
+    let frame_ms = request.frame_ms.unwrap_or(animation::DEFAULT_FRAME_MS);
+    let range = |name: &str, range: &std::ops::RangeInclusive<u32>| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            &format!(
+                "{} must be between {} and {}",
+                name,
+                range.start(),
+                range.end()
+            ),
+        )
+    };
+    if !animation::POINT_SIZES.contains(&point_size) {
+        return Err(range("pointSize", &animation::POINT_SIZES));
+    }
+    if !animation::FRAME_MS.contains(&frame_ms) {
+        return Err(range("frameMs", &animation::FRAME_MS));
+    }
+
+    let review_request = ReviewRequest {
+        sgf: request.sgf,
+        max_visits: request.max_visits,
+        winrate_loss: request.winrate_loss,
+        score_loss: request.score_loss,
+        ..Default::default()
+    };
+    let game = review_game(&review_request)?;
+    // A record that can't be replayed fails before the review runs
+    let boards = animation::replay(&game).map_err(ApiError::from)?;
+    let grid = render::Grid {
+        width: game.board_x_size,
+        height: game.board_y_size,
+        point_size,
+    };
+    let review = run_review(&state, review_request, game, |_| {}).await?;
+
+    let animation = ReviewAnimation {
+        grid,
+        boards: &boards,
+        review: &review,
+        frame_ms,
+    };
+    Ok(match request.format.unwrap_or_default() {
+        AnimationFormat::Svg => {
+            ([(header::CONTENT_TYPE, "image/svg+xml")], animation.svg()).into_response()
+        }
+        AnimationFormat::Gif => {
+            ([(header::CONTENT_TYPE, "image/gif")], animation.gif()).into_response()
+        }
+    })
+}
+
+/// Callbacks and jobs deliver JSON, not the SGF file of a review format
+fn sgf_format_not_sent() -> ApiError {
+    ApiError::new(
+        StatusCode::BAD_REQUEST,
+        "Invalid Request",
+        "The katrain and lizzie formats return an SGF file from POST /api/v1/review, \
+         without a callbackUrl or a job",
+    )
+}
+
+/// An SGF file download
+fn sgf_file(body: impl Into<axum::body::Body>) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "application/x-go-sgf; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"review.sgf\"",
+            ),
+        ],
+        body.into(),
+    )
+        .into_response()
+}
+
+/// Review `game`, with the request's loss thresholds or the configured ones.
+/// `progress` is told how many positions are analyzed after each one.
+pub(crate) fn run_review(
+    state: &AppState,
+    request: ReviewRequest,
+    game: Game,
+    progress: impl FnMut(usize) + Send + 'static,
+) -> impl std::future::Future<Output = std::result::Result<GameReviewResponse, ApiError>> {
+    let configured = state.config.lock().unwrap().review.clone();
+    let thresholds = ReviewConfig {
+        winrate_loss: request.winrate_loss.unwrap_or(configured.winrate_loss),
+        score_loss: request.score_loss.unwrap_or(configured.score_loss),
+    };
+    let engine = state.engine.clone();
+    async move {
+        let moves = review_moves(engine, &game, request.max_visits, &thresholds, progress).await?;
+        Ok(GameReviewResponse {
+            board_x_size: game.board_x_size,
+            board_y_size: game.board_y_size,
+            komi: game.komi,
+            rules: game.rules,
+            black_player: game.info.black_player,
+            white_player: game.info.white_player,
+            result: game.info.result,
+            summary: review_summary(&moves),
+            moves,
+        })
+    }
+}
+
+/// POST /api/v1/jobs
+///
+/// Start an analysis, an analyzeTurns analysis or a review in the background: 202
+/// Accepted with the job, whose URL is in Location. The request is checked first,
+/// so a malformed one fails at once instead of as a failed job.
+#[axum::debug_handler]
+async fn v1_create_job(
+    State(state): State<AppState>,
+    Json(request): Json<JobRequest>,
+) -> std::result::Result<Response, ApiError> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let prepared = prepare_job(&state, &id, request)?;
+    let job = state.jobs.start(id, prepared);
+    let location = format!("/api/v1/jobs/{}", job.job_id);
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, location)],
+        Json(job),
+    )
+        .into_response())
+}
+
+/// Check the request of job `id` and prepare its runs
+fn prepare_job(
+    state: &AppState,
+    id: &str,
+    request: JobRequest,
+) -> std::result::Result<PreparedJob, ApiError> {
+    let kind = request.kind();
+    match request {
+        JobRequest::Analysis(request) | JobRequest::Turns(request) => {
+            no_callback(&request.callback_url)?;
+            if kind == JobKind::Analysis && request.analyze_turns.is_some() {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "Invalid Request",
+                    "analyzeTurns returns one result per turn; use a job of type turns",
+                ));
+            }
+            let mut request = resolve_position(&state.positions, request)?;
+            // The job's queries are cancelled by its id
+            request.request_id = Some(id.to_string());
+            let state = state.clone();
+            let (request, total, runner): (_, _, JobRunner) = if kind == JobKind::Analysis {
+                let run = request.clone();
+                let runner = Box::new(move |_| work(run_analysis(state.clone(), run.clone())));
+                (JobRequest::Analysis(request), 1, runner)
+            } else {
+                let turns = request.analyze_turns.clone().unwrap_or_default();
+                let total = turns.iter().collect::<HashSet<_>>().len();
+                let run = request.clone();
+                let runner = Box::new(move |_| work(run_turns(state.clone(), run.clone())));
+                (JobRequest::Turns(request), total, runner)
+            };
+            Ok(PreparedJob {
+                request,
+                total,
+                runner,
+            })
+        }
+        JobRequest::Review(request) => {
+            no_callback(&request.callback_url)?;
+            if request.format.is_some_and(|f| f != ReviewFormat::Json) {
+                return Err(sgf_format_not_sent());
+            }
+            let game = review_game(&request)?;
+            let total = game.moves.len() + 1;
+            let state = state.clone();
+            let run = request.clone();
+            let runner = Box::new(move |progress: Progress| {
+                work(run_review(
+                    &state,
+                    run.clone(),
+                    game.clone(),
+                    move |completed| progress.set(completed),
+                ))
+            });
+            Ok(PreparedJob {
+                request: JobRequest::Review(request),
+                total,
+                runner,
+            })
+        }
+    }
+}
+
+/// Run again the jobs a previous server left unfinished in the `[jobs]` database
+pub fn resume_jobs(state: &AppState) {
+    for (job, request) in state.jobs.unfinished() {
+        let prepared = prepare_job(state, &job.job_id, request).unwrap_or_else(|error| {
+            // Fails the job with the problem the request now has
+            let problem = error.into_problem();
+            PreparedJob {
+                request: JobRequest::Analysis(AnalysisRequest::default()),
+                total: job.progress.total,
+                runner: Box::new(move |_| Box::pin(std::future::ready(Err(problem.clone())))),
+            }
+        });
+        state.jobs.resume(job, prepared);
+    }
+}
+
+/// Jobs are polled for their result instead
+fn no_callback(callback_url: &Option<String>) -> std::result::Result<(), ApiError> {
+    match callback_url {
+        Some(_) => Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            "A job's result is fetched from GET /api/v1/jobs/{id}, not sent to a callbackUrl",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// GET /api/v1/jobs/{id}
+///
+/// The job's status and progress, with its result once completed or its problem
+/// detail once failed.
+#[axum::debug_handler]
+async fn v1_get_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<JobResponse>, ApiError> {
+    Ok(Json(state.jobs.get(&id)?))
+}
+
+/// DELETE /api/v1/jobs/{id}
+///
+/// Cancel a running job (409 once it finished). KataGo stops searching the job's
+/// analyses; a review stops analyzing further turns.
+#[axum::debug_handler]
+async fn v1_cancel_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<JobResponse>, ApiError> {
+    let job = state.jobs.cancel(&id)?;
+    if job.kind != JobKind::Review {
+        state.engine.cancel(&id);
+    }
+    Ok(Json(job))
+}
+
+/// Black's winrate and score lead at every turn of an SGF game, taking the same
+/// body as POST /api/v1/review
+#[axum::debug_handler]
+async fn v1_review_graph(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ReviewParams>,
+    body: String,
+) -> std::result::Result<Json<ReviewGraphResponse>, ApiError> {
+    let (request, game) = review_body(&headers, params, body)?;
+    let graph = winrate_graph(state.engine.clone(), &game, request.max_visits).await?;
+    Ok(Json(graph))
+}
+
+/// Stream an annotated SGF (chunked), one move node per analyzed turn
+#[axum::debug_handler]
+async fn v1_review_sgf(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<ReviewSgfRequest>,
+) -> std::result::Result<Response, ApiError> {
+    let game = match &request.game_id {
+        Some(id) => readable_game(&state.games, id, &headers)?,
+        None => game_from_position(&request.position, GameInfo::default())?,
+    };
+    let chunks = stream_annotated_sgf(state.engine.clone(), game, request.position.max_visits);
+    let body = axum::body::Body::from_stream(
+        tokio_stream::wrappers::ReceiverStream::new(chunks).map(Ok::<_, std::convert::Infallible>),
+    );
+    Ok(sgf_file(body))
+}
+
+#[axum::debug_handler]
+async fn v1_list_games(
+    State(state): State<AppState>,
+    Query(params): Query<GameSearchParams>,
+) -> Json<GameListResponse> {
+    let query = GameQuery {
+        player: params.player,
+        event: params.event,
+        limit: params.limit,
+    };
+    Json(GameListResponse {
+        archive_size: state.games.len(),
+        games: state
+            .games
+            .search(&query)
+            .iter()
+            .map(GameSummary::from)
+            .collect(),
+    })
+}
+
+/// Find archived games that reached the given position (same stones, same side to move)
+#[axum::debug_handler]
+async fn v1_search_position(
+    State(state): State<AppState>,
+    Json(request): Json<AnalysisRequest>,
+) -> std::result::Result<Json<PositionSearchResponse>, ApiError> {
+    let request = resolve_position(&state.positions, request)?;
+    let board = Board::from_setup(
+        request.board_x_size,
+        request.board_y_size,
+        &AnalysisEngine::katago_initial_stones(&request),
+        &AnalysisEngine::katago_moves(&request),
+    )?;
+    let to_move = parse_stone(AnalysisEngine::next_player(&request))?;
+
+    let hits = state.games.find_position(&board, to_move);
+    let matches = hits
+        .iter()
+        .map(|PositionHit { game_id, turn }| PositionMatch {
+            game_id: game_id.clone(),
+            turn: *turn,
+            next_move: state
+                .games
+                .get(game_id)
+                .and_then(|game| game.moves.get(*turn).map(|(_, coord)| coord.clone())),
+        })
+        .collect();
+
+    Ok(Json(PositionSearchResponse {
+        total: hits.len(),
+        matches,
+    }))
+}
+
+#[axum::debug_handler]
+async fn v1_get_game(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> std::result::Result<Json<GameResponse>, ApiError> {
+    let game = readable_game(&state.games, &id, &headers)?;
+    if is_owner(&game, &headers) {
+        return Ok(Json(GameResponse::for_owner(&game)));
+    }
+    Ok(Json(GameResponse::from(&game)))
+}
+
+#[axum::debug_handler]
+async fn v1_set_game_visibility(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<VisibilityRequest>,
+) -> std::result::Result<Json<GameResponse>, ApiError> {
+    let visibility = parse_visibility(&request.visibility)?;
+    let game = readable_game(&state.games, &id, &headers)?;
+    if !is_owner(&game, &headers) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "Not Game Owner",
+            "Changing visibility requires the game's X-Owner-Token",
+        ));
+    }
+    let game = state
+        .games
+        .set_visibility(&id, visibility)
+        .ok_or_else(|| game_not_found(&id))?;
+    Ok(Json(GameResponse::for_owner(&game)))
+}
+
+#[axum::debug_handler]
+async fn v1_get_shared_game(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> std::result::Result<Json<GameResponse>, ApiError> {
+    let game = state.games.get_shared(&token).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "Share Link Not Found",
+            "This share link is invalid or has been revoked",
+        )
+    })?;
+    Ok(Json(GameResponse::from(&game)))
+}
+
+/// Largest board a play session takes: columns A-Z without I
+const MAX_PLAY_BOARD_SIZE: u8 = 25;
+
+fn play_session_not_found(session_id: &str) -> ApiError {
+    ApiError::new(
+        StatusCode::NOT_FOUND,
+        "Session Not Found",
+        &format!("No play session with id {}", session_id),
+    )
+}
+
+/// Let the engine choose its move in a session and play it there; returns the
+/// move, the stones it captured, and the evaluation it was chosen from
+async fn engine_reply(
+    state: &AppState,
+    session: &mut PlaySession,
+) -> std::result::Result<(String, Vec<String>, TradeEvaluation), ApiError> {
+    let response = state.engine.analyze(&session.analysis_request()).await?;
+    let evaluation = black_perspective(&response).ok_or_else(|| missing_root_info(&session.id))?;
+    let reply = session.choose_move(&response).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "KataGo Error",
+            "KataGo returned no moves for the position",
+        )
+    })?;
+    let captured = session.play(&reply).map_err(|e| {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "KataGo Error",
+            &format!("KataGo chose {}, which can't be played: {}", reply, e),
+        )
+    })?;
+    Ok((reply, captured, evaluation))
+}
+
+#[axum::debug_handler]
+async fn v1_create_play_session(
+    State(state): State<AppState>,
+    Json(request): Json<CreatePlaySessionRequest>,
+) -> std::result::Result<(StatusCode, Json<PlaySessionResponse>), ApiError> {
+    let invalid = |detail: &str| ApiError::new(StatusCode::BAD_REQUEST, "Invalid Request", detail);
+    let board_size = request.board_size.unwrap_or(19);
+    if !(2..=MAX_PLAY_BOARD_SIZE).contains(&board_size) {
+        return Err(invalid(&format!(
+            "boardSize must be between 2 and {}",
+            MAX_PLAY_BOARD_SIZE
+        )));
+    }
+    let human_color = match request.color.as_deref() {
+        None => Stone::Black,
+        Some(color) => Stone::parse(color).ok_or_else(|| invalid("color must be B or W"))?,
+    };
+    let (initial_stones, komi) = match request.handicap {
+        0 => (Vec::new(), request.komi),
+        stones => {
+            let setup = crate::handicap::setup(board_size, stones).map_err(|detail| {
+                ApiError::new(StatusCode::BAD_REQUEST, "Invalid Handicap", &detail)
+            })?;
+            (
+                setup.initial_stones,
+                Some(request.komi.unwrap_or(setup.komi)),
+            )
+        }
+    };
+    let mut rules = request.rules;
+    if let Some(rules) = rules.as_mut() {
+        rules::normalize(rules)
+            .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, "Invalid Rules", &e))?;
+    }
+    if let Some(profile) = &request.human_sl_profile {
+        if !human_profiles::is_profile(profile) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Unknown Human Profile",
+                &format!(
+                    "No humanSLProfile named '{}' (GET /api/v1/human/profiles lists them)",
+                    profile
+                ),
+            ));
+        }
+    }
+
+    let mut session = PlaySession::new(
+        uuid::Uuid::new_v4().to_string(),
+        board_size,
+        initial_stones,
+        human_color,
+    )?;
+    session.komi = komi;
+    session.rules = rules;
+    session.max_visits = request.max_visits;
+    session.human_sl_profile = request.human_sl_profile;
+    let engine = state.engine.route_request(&session.analysis_request())?;
+    if session.human_sl_profile.is_some() && engine.loaded_human_model().is_none() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Human Model Not Loaded",
+            "The engine for this board size has no human model loaded",
+        ));
+    }
+    // The engine opens when the client plays White in an even game, or Black
+    // in a handicap game
+    if session.player_to_move() != human_color {
+        engine_reply(&state, &mut session).await?;
+    }
+    let response = PlaySessionResponse::from(&session);
+    state.play_sessions.insert(session);
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[axum::debug_handler]
+async fn v1_get_play_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<PlaySessionResponse>, ApiError> {
+    let session = state
+        .play_sessions
+        .get(&id)
+        .ok_or_else(|| play_session_not_found(&id))?;
+    Ok(Json(PlaySessionResponse::from(&session)))
+}
+
+#[axum::debug_handler]
+async fn v1_end_play_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<PlaySessionResponse>, ApiError> {
+    let session = state
+        .play_sessions
+        .remove(&id)
+        .ok_or_else(|| play_session_not_found(&id))?;
+    Ok(Json(PlaySessionResponse::from(&session)))
+}
+
+#[axum::debug_handler]
+async fn v1_play_move(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<PlayMoveRequest>,
+) -> std::result::Result<Json<PlayMoveResponse>, ApiError> {
+    let mut session = state
+        .play_sessions
+        .get(&id)
+        .ok_or_else(|| play_session_not_found(&id))?;
+    if session.finished() {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "Game Finished",
+            "Both players passed, so the game is over",
+        ));
+    }
+
+    let turn = session.moves.len();
+    let captured = session
+        .play(&request.move_coord)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, "Illegal Move", &e.to_string()))?;
+    let move_coord = session.moves[turn].1.clone();
+    let (bot_move, bot_captured, evaluation) = if session.finished() {
+        (None, Vec::new(), None)
+    } else {
+        let (reply, captured, evaluation) = engine_reply(&state, &mut session).await?;
+        (Some(reply), captured, Some(evaluation))
+    };
+
+    // Only keep the moves if no concurrent move changed the session meanwhile
+    let session = state
+        .play_sessions
+        .update(&id, |stored| {
+            (stored.moves.len() == turn).then(|| {
+                *stored = session;
+                PlaySessionResponse::from(&*stored)
+            })
+        })
+        .ok_or_else(|| play_session_not_found(&id))?
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::CONFLICT,
+                "Move Conflict",
+                "Another move was played in this session by a concurrent request",
+            )
+        })?;
+
+    Ok(Json(PlayMoveResponse {
+        move_coord,
+        captured,
+        bot_move,
+        bot_captured,
+        evaluation,
+        session,
+    }))
+}
+
+#[axum::debug_handler]
+async fn v1_create_guess_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<CreateGuessSessionRequest>,
+) -> std::result::Result<(StatusCode, Json<GuessSessionResponse>), ApiError> {
+    let game = readable_game(&state.games, &request.game_id, &headers)?;
+
+    let session = GuessSession {
+        id: uuid::Uuid::new_v4().to_string(),
+        game_id: game.id.clone(),
+        turn: request.start_turn.min(game.moves.len()),
+        score: 0,
+        guesses: 0,
+        correct: 0,
+        max_visits: request.max_visits,
+    };
+    let response = GuessSessionResponse::new(&session, &game);
+    state.guess_sessions.insert(session.id.clone(), session);
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[axum::debug_handler]
+async fn v1_get_guess_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<GuessSessionResponse>, ApiError> {
+    let session = state
+        .guess_sessions
+        .get(&id)
+        .ok_or_else(|| session_not_found(&id))?;
+    let game = state
+        .games
+        .get(&session.game_id)
+        .ok_or_else(|| game_not_found(&session.game_id))?;
+    Ok(Json(GuessSessionResponse::new(&session, &game)))
+}
+
+#[axum::debug_handler]
+async fn v1_guess(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<GuessRequest>,
+) -> std::result::Result<Json<GuessResponse>, ApiError> {
+    let session = state
+        .guess_sessions
+        .get(&id)
+        .ok_or_else(|| session_not_found(&id))?;
+    let game = state
+        .games
+        .get(&session.game_id)
+        .ok_or_else(|| game_not_found(&session.game_id))?;
+
+    let turn = session.turn;
+    let Some((_, actual)) = game.moves.get(turn) else {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "Session Finished",
+            "All moves of this game have already been guessed",
+        ));
+    };
+
+    let board = Board::new(game.board_x_size, game.board_y_size);
+    if !request.guess.eq_ignore_ascii_case("pass") {
+        board.parse_point(&request.guess)?;
+    }
+
+    let analysis_request = AnalysisRequest {
+        include_policy: Some(true),
+        ..game.analysis_request(turn, session.max_visits)
+    };
+    let analysis = state.engine.analyze(&analysis_request).await?;
+    let evaluation = evaluate_guess(&board, &request.guess, actual, &analysis);
+
+    // Only score the guess if no concurrent guess advanced the session meanwhile
+    let session = state
+        .guess_sessions
+        .update(&id, |session| {
+            (session.turn == turn).then(|| {
+                session.record(&evaluation);
+                session.clone()
+            })
+        })
+        .flatten()
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::CONFLICT,
+                "Guess Conflict",
+                "This move was already guessed in a concurrent request",
+            )
+        })?;
+
+    Ok(Json(GuessResponse {
+        guess: request.guess,
+        actual_move: actual.clone(),
+        correct: evaluation.correct,
+        points: evaluation.points,
+        engine_rank: evaluation.engine_rank,
+        actual_engine_rank: evaluation.actual_engine_rank,
+        policy_rank: evaluation.policy_rank,
+        human_policy_rank: evaluation.human_policy_rank,
+        best_move: analysis
+            .move_infos
+            .as_ref()
+            .and_then(|infos| infos.first())
+            .map(|mi| mi.move_coord.clone()),
+        session: GuessSessionResponse::new(&session, &game),
+    }))
+}
+
+/// The GTP bot, or 404 when it is not enabled (not 503: retrying won't help)
+fn gtp_bot(state: &AppState) -> std::result::Result<&Arc<KatagoBot>, ApiError> {
+    state.bot.as_ref().ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "GTP Bot Disabled",
+            "The GTP bot is not running; set [gtp] enabled = true to start it",
+        )
+    })
+}
+
+fn bot_config(request: &GameRequest) -> RequestConfig {
+    RequestConfig {
+        komi: request.komi,
+        client: request.client.clone(),
+        request_id: request.request_id.clone(),
+        ownership: request.ownership,
+    }
+}
+
+/// POST /api/v1/game/move: KataGo's genmove reply for the position after `moves`
+#[axum::debug_handler]
+async fn v1_game_move(
+    State(state): State<AppState>,
+    Json(request): Json<GameRequest>,
+) -> std::result::Result<Json<BotMoveResponse>, ApiError> {
+    let request_id = request.request_id.clone();
+    let with_id = |e: ApiError| match &request_id {
+        Some(id) => e.with_request_id(id.clone()),
+        None => e,
+    };
+    let bot = gtp_bot(&state).map_err(with_id)?;
+    let (bot_move, diagnostics) = bot
+        .select_move(&request.moves, &bot_config(&request))
+        .await
+        .map_err(|e| with_id(e.into()))?;
+
+    Ok(Json(BotMoveResponse {
+        request_id: request.request_id,
+        bot_move,
+        diagnostics: BotDiagnostics {
+            // KataGo reports -1 until it has evaluated the position
+            winprob: (diagnostics.winprob >= 0.0).then_some(diagnostics.winprob),
+            score: diagnostics.score,
+            best_ten: diagnostics
+                .best_ten
+                .into_iter()
+                .map(|candidate| BotCandidate {
+                    move_coord: candidate.mv,
+                    psv: candidate.psv,
+                })
+                .collect(),
+        },
+    }))
+}
+
+/// POST /api/v1/game/score: ownership estimate of the position after `moves`
+#[axum::debug_handler]
+async fn v1_game_score(
+    State(state): State<AppState>,
+    Json(request): Json<GameRequest>,
+) -> std::result::Result<Json<GameScoreResponse>, ApiError> {
+    let request_id = request.request_id.clone();
+    let with_id = |e: ApiError| match &request_id {
+        Some(id) => e.with_request_id(id.clone()),
+        None => e,
+    };
+    let bot = gtp_bot(&state).map_err(with_id)?;
+    let probs = bot
+        .score(&request.moves, &bot_config(&request))
+        .await
+        .map_err(|e| with_id(e.into()))?;
+
+    Ok(Json(GameScoreResponse {
+        request_id: request.request_id,
+        probs,
+    }))
+}
+
+/// Visits for a score estimate when the request sets none: ownership needs more
+/// search to settle than a winrate does
+const SCORE_VISITS: u32 = 200;
+
+/// Estimate the final score: KataGo's ownership decides who owns each point,
+/// which is counted under the rules' area or territory scoring and komi applied
+#[axum::debug_handler]
+async fn v1_score(
+    State(state): State<AppState>,
+    format: Format,
+    Body(request): Body<ScoreRequest>,
+) -> std::result::Result<Response, ApiError> {
+    let request_id = request
+        .position
+        .request_id
+        .clone()
+        .unwrap_or_else(current_request_id);
+    let threshold = request
+        .ownership_threshold
+        .unwrap_or(scoring::DEFAULT_OWNERSHIP_THRESHOLD);
+    if !(threshold > 0.0 && threshold <= 1.0) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            "ownershipThreshold must be above 0 and at most 1",
+        )
+        .with_request_id(request_id));
+    }
+
+    let mut position = resolve_position(&state.positions, request.position)
+        .map_err(|e| e.with_request_id(request_id.clone()))?;
+    position.request_id = Some(request_id.clone());
+    position.include_ownership = Some(true);
+    position.max_visits = position.max_visits.or(Some(SCORE_VISITS));
+    // Rules and komi of the count must be the ones KataGo analyzed with
+    let position = state.engine.with_defaults(&position);
+    let (board, captured) = Board::replay(
+        position.board_x_size,
+        position.board_y_size,
+        &AnalysisEngine::katago_initial_stones(&position),
+        &AnalysisEngine::katago_moves(&position),
+    )
+    .map_err(|e| ApiError::from(e).with_request_id(request_id.clone()))?;
+
+    let response = state
+        .engine
+        .analyze(&position)
+        .await
+        .map_err(|e| ApiError::from(e).with_request_id(request_id.clone()))?;
+    let evaluation = black_perspective(&response).ok_or_else(|| missing_root_info(&request_id))?;
+    // Ownership is the player to move's, like the winrate
+    let white_to_move = response
+        .root_info
+        .as_ref()
+        .is_some_and(|root| root.current_player.eq_ignore_ascii_case("W"));
+    let ownership: Vec<f32> = response
+        .ownership
+        .as_ref()
+        .and_then(Ownership::as_flat)
+        .unwrap_or_default()
+        .iter()
+        .map(|value| if white_to_move { -value } else { *value })
+        .collect();
+    if ownership.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "KataGo Error",
+            "KataGo returned no ownership for the position",
+        )
+        .with_request_id(request_id));
+    }
+
+    let rules = AnalysisEngine::rules(&position);
+    let komi = AnalysisEngine::komi(&position);
+    let area_scoring = scoring::is_area_scoring(&rules);
+    let count = scoring::count(&board, captured, &ownership, threshold, area_scoring);
+    let score = count.score(komi);
+    let comparison = request
+        .compare_scoring
+        .unwrap_or(false)
+        .then(|| scoring::compare(&board, captured, &ownership, threshold, komi));
+    Ok(format.respond(&ScoreEstimateResponse {
+        request_id,
+        rules,
+        scoring: if area_scoring { "area" } else { "territory" }.to_string(),
+        komi,
+        black_territory: count.black_territory,
+        white_territory: count.white_territory,
+        black_prisoners: count.black_prisoners,
+        white_prisoners: count.white_prisoners,
+        score,
+        result: scoring::result(score),
+        score_lead: evaluation.score_lead,
+        territory: count.territory,
+        comparison,
+    }))
+}
+
+/// POST /api/v1/policy
+///
+/// The raw policy of a position from a one-visit query, reshaped into rows.
+#[axum::debug_handler]
+async fn v1_policy(
+    State(state): State<AppState>,
+    format: Format,
+    Body(request): Body<AnalysisRequest>,
+) -> std::result::Result<Response, ApiError> {
+    let request_id = request
+        .request_id
+        .clone()
+        .unwrap_or_else(current_request_id);
+    if request.analyze_turns.is_some() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            "analyzeTurns is not supported by POST /api/v1/policy",
+        )
+        .with_request_id(request_id));
+    }
+
+    let mut position = resolve_position(&state.positions, request)
+        .map_err(|e| e.with_request_id(request_id.clone()))?;
+    position.request_id = Some(request_id.clone());
+    // The policy comes from the root's network evaluation; searching adds nothing
+    position.max_visits = Some(1);
+    position.max_time_secs = None;
+    position.target_latency_ms = None;
+    position.include_policy = Some(true);
+
+    let response = state
+        .engine
+        .analyze(&position)
+        .await
+        .map_err(|e| ApiError::from(e).with_request_id(request_id.clone()))?;
+    let current_player = response
+        .root_info
+        .as_ref()
+        .ok_or_else(|| missing_root_info(&request_id))?
+        .current_player
+        .clone();
+    let width = position.board_x_size as usize;
+    let Some((policy, pass_policy)) = response
+        .policy
+        .as_deref()
+        .and_then(|policy| board_rows(policy, width, position.board_y_size as usize))
+    else {
+        return Err(ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "KataGo Error",
+            "KataGo returned no policy for the position",
+        )
+        .with_request_id(request_id));
+    };
+    let human = response
+        .human_policy
+        .as_deref()
+        .and_then(|policy| board_rows(policy, width, position.board_y_size as usize));
+
+    Ok(format.respond(&PolicyResponse {
+        request_id,
+        board_x_size: position.board_x_size,
+        board_y_size: position.board_y_size,
+        current_player,
+        policy,
+        pass_policy,
+        human_policy: human.as_ref().map(|(rows, _)| rows.clone()),
+        human_pass_policy: human.map(|(_, pass)| pass),
+    }))
+}
+
+/// A KataGo policy array (row-major from the top, pass last) as board rows and the
+/// pass prior; None if its length doesn't match the board
+fn board_rows(policy: &[f32], width: usize, height: usize) -> Option<(Vec<Vec<f32>>, f32)> {
+    let (&pass, points) = policy.split_last()?;
+    if points.len() != width * height {
+        return None;
+    }
+    Some((points.chunks(width).map(<[f32]>::to_vec).collect(), pass))
+}
 
-        (status, headers, Json(self.problem)).into_response()
+/// POST /api/v1/render/heatmap
+///
+/// Draws ownership or policy over the board of a position, as SVG or PNG: the
+/// request's values, or those of an analysis of the position.
+#[axum::debug_handler]
+async fn v1_render_heatmap(
+    State(state): State<AppState>,
+    Json(request): Json<HeatmapRequest>,
+) -> std::result::Result<Response, ApiError> {
+    let request_id = request
+        .position
+        .request_id
+        .clone()
+        .unwrap_or_else(current_request_id);
+    let invalid = |detail: &str| {
+        ApiError::new(StatusCode::BAD_REQUEST, "Invalid Request", detail)
+            .with_request_id(request_id.clone())
+    };
+    let point_size = request.point_size.unwrap_or(render::DEFAULT_POINT_SIZE);
+    if !render::POINT_SIZES.contains(&point_size) {
+        return Err(invalid(&format!(
+            "pointSize must be between {} and {}",
+            render::POINT_SIZES.start(),
+            render::POINT_SIZES.end()
+        )));
     }
+    let layer = request.layer.unwrap_or_default();
+
+    let mut position = resolve_position(&state.positions, request.position)
+        .map_err(|e| e.with_request_id(request_id.clone()))?;
+    position.request_id = Some(request_id.clone());
+    let board = Board::from_setup(
+        position.board_x_size,
+        position.board_y_size,
+        &AnalysisEngine::katago_initial_stones(&position),
+        &AnalysisEngine::katago_moves(&position),
+    )
+    .map_err(|e| ApiError::from(e).with_request_id(request_id.clone()))?;
+    let values = match request.values {
+        Some(values) => board_values(values, &board).ok_or_else(|| {
+            invalid(&format!(
+                "values must give one value per point of the {}x{} board",
+                board.width(),
+                board.height()
+            ))
+        })?,
+        None => heatmap_values(&state, position, layer).await?,
+    };
+
+    let heatmap = render::Heatmap {
+        board: &board,
+        values: &values,
+        layer,
+        point_size,
+    };
+    Ok(match request.format.unwrap_or_default() {
+        ImageFormat::Svg => {
+            ([(header::CONTENT_TYPE, "image/svg+xml")], heatmap.svg()).into_response()
+        }
+        ImageFormat::Png => ([(header::CONTENT_TYPE, "image/png")], heatmap.png()).into_response(),
+    })
 }
 
-impl From<crate::error::KatagoError> for ApiError {
-    fn from(err: crate::error::KatagoError) -> Self {
-        use crate::error::KatagoError;
-        match err {
-            KatagoError::Timeout(secs) => ApiError::new(
-                StatusCode::GATEWAY_TIMEOUT,
-                "Analysis Timeout",
-                &format!("KataGo analysis timed out after {} seconds", secs),
-            ),
-            KatagoError::ProcessDied => ApiError::new(
-                StatusCode::SERVICE_UNAVAILABLE,
-                "Service Unavailable",
-                "KataGo process has died unexpectedly",
-            ),
-            KatagoError::ParseError(msg) => ApiError::new(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Parse Error",
-                &format!("Failed to parse KataGo response: {}", msg),
-            ),
-            KatagoError::ProcessStartFailed(msg) => ApiError::new(
-                StatusCode::SERVICE_UNAVAILABLE,
-                "Service Unavailable",
-                &format!("Failed to start KataGo: {}", msg),
-            ),
-            KatagoError::InvalidCommand(msg) => ApiError::new(
-                StatusCode::BAD_REQUEST,
-                "Invalid Request",
-                &format!("Invalid command: {}", msg),
-            ),
-            KatagoError::ResponseError(msg) => ApiError::new(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "KataGo Error",
-                &format!("KataGo returned error: {}", msg),
-            ),
-            KatagoError::IoError(err) => ApiError::new(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Internal Error",
-                &format!("IO error: {}", err),
-            ),
-            KatagoError::JsonError(err) => ApiError::new(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "JSON Error",
-                &format!("JSON parsing error: {}", err),
-            ),
+/// Heatmap values in KataGo's order (row-major from the top row), from a flat
+/// array, rows, or a coordinate map; None if they don't fit the board
+fn board_values(values: Ownership, board: &Board) -> Option<Vec<f32>> {
+    let points = board.width() as usize * board.height() as usize;
+    match values {
+        // A policy array ends with the pass prior
+        Ownership::Flat(mut values) if values.len() == points || values.len() == points + 1 => {
+            values.truncate(points);
+            Some(values)
+        }
+        Ownership::Flat(_) => None,
+        Ownership::Rows(rows) => {
+            let fits = rows.len() == board.height() as usize
+                && rows.iter().all(|row| row.len() == board.width() as usize);
+            fits.then(|| rows.concat())
+        }
+        Ownership::Map(map) => {
+            let mut values = vec![0.0; points];
+            for (coord, value) in map {
+                let index = board.policy_index(&coord).ok().filter(|&i| i < points)?;
+                values[index] = value;
+            }
+            Some(values)
         }
     }
 }
 
-impl From<anyhow::Error> for ApiError {
-    fn from(err: anyhow::Error) -> Self {
-        ApiError::new(
+/// Analyze a position for a heatmap: Black's ownership after a short search, or
+/// the policy of the root
+async fn heatmap_values(
+    state: &AppState,
+    mut position: AnalysisRequest,
+    layer: HeatmapLayer,
+) -> std::result::Result<Vec<f32>, ApiError> {
+    let request_id = position.request_id.clone().unwrap_or_default();
+    match layer {
+        HeatmapLayer::Ownership => {
+            position.include_ownership = Some(true);
+            position.max_visits = position.max_visits.or(Some(SCORE_VISITS));
+        }
+        HeatmapLayer::Policy => {
+            position.max_visits = Some(1);
+            position.max_time_secs = None;
+            position.target_latency_ms = None;
+            position.include_policy = Some(true);
+        }
+    }
+    let response = state
+        .engine
+        .analyze(&position)
+        .await
+        .map_err(|e| ApiError::from(e).with_request_id(request_id.clone()))?;
+    let points = position.board_x_size as usize * position.board_y_size as usize;
+    let values = match layer {
+        HeatmapLayer::Ownership => {
+            // Ownership is the player to move's, like the winrate
+            let white_to_move = response
+                .root_info
+                .as_ref()
+                .is_some_and(|root| root.current_player.eq_ignore_ascii_case("W"));
+            response
+                .ownership
+                .as_ref()
+                .and_then(Ownership::as_flat)
+                .map(|values| {
+                    values
+                        .iter()
+                        .map(|value| if white_to_move { -value } else { *value })
+                        .collect::<Vec<f32>>()
+                })
+        }
+        HeatmapLayer::Policy => response.policy,
+    };
+    match values {
+        Some(mut values) if values.len() >= points => {
+            values.truncate(points);
+            Ok(values)
+        }
+        _ => Err(ApiError::new(
             StatusCode::INTERNAL_SERVER_ERROR,
-            "Internal Error",
-            &err.to_string(),
+            "KataGo Error",
+            "KataGo returned no values for the heatmap",
         )
+        .with_request_id(request_id)),
     }
 }
 
-pub fn create_router(engine: AppState) -> Router {
-    Router::new()
-        .route("/api/v1/analysis", post(v1_analysis))
-        .route("/api/v1/health", get(v1_health))
-        .route("/api/v1/version", get(v1_version))
-        .route("/api/v1/cache/clear", post(v1_cache_clear))
-        .with_state(engine)
+#[axum::debug_handler]
+async fn v1_trade(
+    State(state): State<AppState>,
+    Json(request): Json<TradeRequest>,
+) -> std::result::Result<Json<TradeResponse>, ApiError> {
+    let request_id = request
+        .position
+        .request_id
+        .clone()
+        .unwrap_or_else(current_request_id);
+
+    let position = &resolve_position(&state.positions, request.position)
+        .map_err(|e| e.with_request_id(request_id.clone()))?;
+    let board = Board::from_setup(
+        position.board_x_size,
+        position.board_y_size,
+        &AnalysisEngine::katago_initial_stones(position),
+        &AnalysisEngine::katago_moves(position),
+    )
+    .map_err(|e| ApiError::from(e).with_request_id(request_id.clone()))?;
+    let to_move = AnalysisEngine::next_player(position);
+
+    let traded = play_exchange(
+        &board,
+        Stone::parse(to_move).unwrap_or(Stone::Black),
+        &request.black_moves,
+        &request.white_moves,
+    )
+    .map_err(|e| e.with_request_id(request_id.clone()))?;
+
+    let without = layout_request(position, &board, to_move, format!("{}-without", request_id));
+    let with = layout_request(position, &traded, to_move, format!("{}-with", request_id));
+
+    let (without, with) =
+        tokio::try_join!(state.engine.analyze(&without), state.engine.analyze(&with))
+            .map_err(|e| ApiError::from(e).with_request_id(request_id.clone()))?;
+
+    let without_exchange =
+        black_perspective(&without).ok_or_else(|| missing_root_info(&request_id))?;
+    let with_exchange = black_perspective(&with).ok_or_else(|| missing_root_info(&request_id))?;
+
+    let score_lead_delta = with_exchange.score_lead - without_exchange.score_lead;
+    let beneficiary = if score_lead_delta >= 0.5 {
+        "B"
+    } else if score_lead_delta <= -0.5 {
+        "W"
+    } else {
+        "even"
+    };
+
+    Ok(Json(TradeResponse {
+        id: request_id,
+        winrate_delta: with_exchange.winrate - without_exchange.winrate,
+        score_lead_delta,
+        beneficiary: beneficiary.to_string(),
+        without_exchange,
+        with_exchange,
+    }))
 }
 
-// ============================================================================
-// V1 API Handlers
-// ============================================================================
+/// Play an exchange on a board, alternating from the player to move; once one
+/// side's moves run out the other side's remaining moves follow. The two lists
+/// don't say how the moves interleave, so an exchange whose captures would change
+/// if either side's moves were played as a block first is rejected as ambiguous.
+fn play_exchange(
+    board: &Board,
+    to_move: Stone,
+    black_moves: &[String],
+    white_moves: &[String],
+) -> std::result::Result<Board, ApiError> {
+    let (first, second) = match to_move {
+        Stone::Black => ((Stone::Black, black_moves), (Stone::White, white_moves)),
+        Stone::White => ((Stone::White, white_moves), (Stone::Black, black_moves)),
+    };
+    let block = |(stone, moves): (Stone, &[String])| -> Vec<(Stone, String)> {
+        moves.iter().map(|coord| (stone, coord.clone())).collect()
+    };
+    let (first, second) = (block(first), block(second));
+    let mut alternating = Vec::new();
+    for i in 0..first.len().max(second.len()) {
+        alternating.extend(first.get(i).cloned());
+        alternating.extend(second.get(i).cloned());
+    }
+    let play = |moves: &[(Stone, String)]| {
+        let mut board = board.clone();
+        for (stone, coord) in moves {
+            board.play(*stone, coord)?;
+        }
+        Ok::<_, BoardError>(board)
+    };
 
+    let traded = play(&alternating)?;
+    let first_then_second = play(&[first.clone(), second.clone()].concat());
+    let second_then_first = play(&[second, first].concat());
+    for other in [first_then_second, second_then_first] {
+        if !other.is_ok_and(|other| other.stones() == traded.stones()) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Ambiguous Exchange",
+                "The order of the exchange's moves changes which stones are captured",
+            ));
+        }
+    }
+    Ok(traded)
+}
+
+/// Re-encode a board as a history-free analysis request (initialStones only)
+fn layout_request(
+    template: &AnalysisRequest,
+    board: &Board,
+    to_move: &str,
+    request_id: String,
+) -> AnalysisRequest {
+    let mut request = template.clone();
+    request.moves = Vec::new();
+    request.initial_stones = Some(board.to_initial_stones());
+    request.initial_player = Some(to_move.to_uppercase());
+    request.analyze_turns = None;
+    request.request_id = Some(request_id);
+    request
+}
+
+/// The engine's best moves next to the most likely moves of a humanSLProfile,
+/// with the points each one loses
 #[axum::debug_handler]
-async fn v1_analysis(
-    State(engine): State<AppState>,
-    Json(request): Json<AnalysisRequest>,
-) -> std::result::Result<Json<AnalysisResponse>, ApiError> {
+async fn v1_human_comparison(
+    State(state): State<AppState>,
+    Json(request): Json<HumanComparisonRequest>,
+) -> std::result::Result<Json<HumanComparisonResponse>, ApiError> {
     let request_id = request
+        .position
         .request_id
         .clone()
-        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        .unwrap_or_else(current_request_id);
+    let profile = request.human_sl_profile;
+    if !human_profiles::is_profile(&profile) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Unknown Human Profile",
+            &format!(
+                "No humanSLProfile named '{}' (GET /api/v1/human/profiles lists them)",
+                profile
+            ),
+        )
+        .with_request_id(request_id));
+    }
+    if request.position.analyze_turns.is_some() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            "analyzeTurns is not supported by POST /api/v1/analysis/human",
+        )
+        .with_request_id(request_id));
+    }
 
-    // Use JSON analysis engine for full move analysis
-    let response = engine
-        .analyze(&request)
-        .await
+    let mut position = resolve_position(&state.positions, request.position)
+        .map_err(|e| e.with_request_id(request_id.clone()))?;
+    position.request_id = Some(request_id.clone());
+    position.include_policy = Some(true);
+    let mut settings = match position.override_settings.take() {
+        Some(serde_json::Value::Object(settings)) => settings,
+        _ => serde_json::Map::new(),
+    };
+    settings.insert("humanSLProfile".to_string(), profile.clone().into());
+    position.override_settings = Some(settings.into());
+
+    let engine = state
+        .engine
+        .route_request(&position)
         .map_err(|e| ApiError::from(e).with_request_id(request_id.clone()))?;
+    if engine.loaded_human_model().is_none() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Human Model Not Loaded",
+            "The engine for this position has no human model loaded",
+        )
+        .with_request_id(request_id));
+    }
+    let max_moves = request.max_moves.unwrap_or(DEFAULT_MAX_MOVES);
+    let comparison = human_compare::compare(&state.engine, &position, &profile, max_moves)
+        .await
+        .map_err(|e| ApiError::from(e).with_request_id(request_id))?;
+    Ok(Json(comparison))
+}
+
+/// Convert a side-to-move evaluation into Black's perspective
+pub fn black_perspective(response: &AnalysisResponse) -> Option<TradeEvaluation> {
+    let root = response.root_info.as_ref()?;
+    let white_to_move = root.current_player.eq_ignore_ascii_case("W");
+    let best_move = response
+        .move_infos
+        .as_ref()
+        .and_then(|infos| infos.first())
+        .map(|mi| mi.move_coord.clone());
+    Some(TradeEvaluation {
+        winrate: if white_to_move {
+            1.0 - root.winrate
+        } else {
+            root.winrate
+        },
+        score_lead: if white_to_move {
+            -root.score_lead
+        } else {
+            root.score_lead
+        },
+        visits: root.visits,
+        best_move,
+    })
+}
 
-    Ok(Json(response))
+fn missing_root_info(request_id: &str) -> ApiError {
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "KataGo Error",
+        "KataGo returned no rootInfo for the position",
+    )
+    .with_request_id(request_id.to_string())
 }
 
 #[axum::debug_handler]
 async fn v1_health(
-    State(engine): State<AppState>,
+    State(state): State<AppState>,
 ) -> std::result::Result<Json<HealthResponse>, (axum::http::StatusCode, Json<HealthResponse>)> {
+    let response = health_report(&state);
+    if state.engine.is_alive() {
+        Ok(Json(response))
+    } else {
+        Err((axum::http::StatusCode::SERVICE_UNAVAILABLE, Json(response)))
+    }
+}
+
+/// Liveness, uptimes, and restarts of the engines
+pub(crate) fn health_report(state: &AppState) -> HealthResponse {
     use chrono::Utc;
 
-    let is_alive = engine.is_alive();
-    let status = if is_alive { "healthy" } else { "unhealthy" };
+    let status = if state.engine.is_alive() {
+        "healthy"
+    } else {
+        "unhealthy"
+    };
+    let engines: Vec<_> = state.engine.named().map(|(_, engine)| engine).collect();
+    let model = std::path::Path::new(&state.engine.primary().model_path())
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_string);
 
-    let response = HealthResponse {
+    HealthResponse {
         status: status.to_string(),
         timestamp: Some(Utc::now().to_rfc3339()),
-        uptime: None,
-    };
+        uptime: Some(state.started.elapsed().as_secs()),
+        engine_uptime: engines.iter().map(|e| e.uptime_secs()).min(),
+        restarts: Some(engines.iter().map(|e| e.restart_count()).sum()),
+        pending: Some(engines.iter().map(|e| e.pending_count()).sum()),
+        last_success: engines
+            .iter()
+            .filter_map(|e| e.last_success())
+            .max()
+            .map(|t| t.to_rfc3339()),
+        model,
+    }
+}
 
-    if is_alive {
-        Ok(Json(response))
-    } else {
-        Err((axum::http::StatusCode::SERVICE_UNAVAILABLE, Json(response)))
+/// GET /livez: the server process is up. Restarting it wouldn't help KataGo, whose
+/// crashes the engine monitor handles.
+async fn livez() -> &'static str {
+    "ok"
+}
+
+/// GET /readyz: 200 once every engine runs and has answered its warm-up analysis,
+/// else 503 listing the engines that aren't ready
+async fn readyz(State(state): State<AppState>) -> (StatusCode, String) {
+    if state.engine.is_ready() {
+        return (StatusCode::OK, "ok".to_string());
     }
+    let engines: Vec<String> = state
+        .engine
+        .named()
+        .map(|(name, engine)| {
+            let status = if !engine.is_alive() {
+                "not running"
+            } else if !engine.is_warmed_up() {
+                "warming up"
+            } else {
+                "ready"
+            };
+            format!("engine {}: {}", name, status)
+        })
+        .collect();
+    (StatusCode::SERVICE_UNAVAILABLE, engines.join("\n"))
+}
+
+/// Stdin/stdout protocol counters, e.g. to spot responses for timed-out ids piling up
+#[axum::debug_handler]
+async fn v1_engine_protocol(State(state): State<AppState>) -> Json<ProtocolStatsSnapshot> {
+    Json(state.engine.primary().protocol_stats())
 }
 
 #[axum::debug_handler]
 async fn v1_version(
-    State(engine): State<AppState>,
+    State(state): State<AppState>,
 ) -> std::result::Result<Json<VersionResponse>, ApiError> {
+    Ok(Json(version_report(&state).await))
+}
+
+/// Versions of the server, KataGo, and the primary engine's model
+pub(crate) async fn version_report(state: &AppState) -> VersionResponse {
     // Get model name (filename only, not full path for security)
-    let model_name = std::path::Path::new(engine.model_path())
+    let model_name = std::path::Path::new(&state.engine.primary().model_path())
         .file_name()
         .and_then(|n| n.to_str())
         .unwrap_or("unknown")
         .to_string();
 
     // Query KataGo version from the analysis engine
-    let katago_info = engine
+    let katago_info = state
+        .engine
+        .primary()
         .query_version()
         .await
         .ok()
         .map(|(version, git_hash)| KatagoVersion { version, git_hash });
 
-    Ok(Json(VersionResponse {
+    VersionResponse {
         server: ServerVersion {
             name: "katago-server".to_string(),
             version: env!("CARGO_PKG_VERSION").to_string(),
         },
         katago: katago_info,
         model: ModelInfo { name: model_name },
+    }
+}
+
+/// POST /api/v1/analysis/{id}/cancel
+///
+/// Stops the running analysis whose requestId is `id`. KataGo terminates the
+/// search, and the request waiting for it fails with 409 Analysis Cancelled.
+async fn v1_analysis_cancel(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<CancelResponse>, ApiError> {
+    if !state.engine.cancel(&id) {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "Query Not Found",
+            &format!("No running analysis with requestId {}", id),
+        ));
+    }
+    Ok(Json(CancelResponse {
+        id,
+        status: "cancelled".to_string(),
     }))
 }
 
 #[axum::debug_handler]
 async fn v1_cache_clear(
-    State(engine): State<AppState>,
+    State(state): State<AppState>,
 ) -> std::result::Result<Json<CacheClearResponse>, ApiError> {
     use chrono::Utc;
 
-    engine.clear_cache().await?;
+    state.engine.clear_cache().await?;
+    if let Some(bot) = &state.bot {
+        bot.clear_cache().await?;
+    }
 
     Ok(Json(CacheClearResponse {
         status: "cleared".to_string(),
@@ -489,6 +4116,35 @@ async fn v1_cache_clear(
     }))
 }
 
+/// GET /api/v1/cache/stats
+///
+/// Hits and misses of the result cache in front of KataGo, which answers
+/// repeated analyses of a position without searching it again.
+async fn v1_cache_stats(State(state): State<AppState>) -> Json<CacheStatsResponse> {
+    let counts = state.engine.result_cache_counts();
+    let lookups = counts.hits + counts.misses;
+    Json(CacheStatsResponse {
+        hits: counts.hits,
+        misses: counts.misses,
+        entries: counts.entries,
+        backend: state
+            .engine
+            .backend_counts()
+            .map(|(kind, counts)| CacheBackendStats {
+                kind: kind.to_string(),
+                hits: counts.hits,
+                entries: counts.entries,
+                bytes: counts.bytes,
+            }),
+        joined: state.engine.joined_count(),
+        hit_rate: if lookups == 0 {
+            0.0
+        } else {
+            counts.hits as f64 / lookups as f64
+        },
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -508,7 +4164,7 @@ mod tests {
         assert_eq!(request.moves[1].coord(), "Q16");
         assert!(request.moves[0].color().is_none()); // Simple format
         assert_eq!(request.komi, Some(7.5));
-        assert_eq!(request.rules, Some("chinese".to_string()));
+        assert_eq!(request.rules, Some("chinese".into()));
         assert_eq!(request.include_ownership, Some(true));
         assert_eq!(request.include_policy, Some(false));
     }
@@ -581,6 +4237,10 @@ mod tests {
             ownership_stdev: None,
             policy: None,
             human_policy: None,
+            human_model: None,
+            josekis: None,
+            warnings: Vec::new(),
+            meta: None,
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -589,6 +4249,313 @@ mod tests {
         assert!(json.contains("\"winrate\":0.523"));
     }
 
+    #[test]
+    fn test_analysis_request_with_position_id() {
+        let json = r#"{"positionId": "abc-123", "maxVisits": 50}"#;
+        let request: AnalysisRequest = serde_json::from_str(json).unwrap();
+        assert!(request.moves.is_empty());
+        assert_eq!(request.position_id.as_deref(), Some("abc-123"));
+    }
+
+    #[test]
+    fn test_region_becomes_avoid_moves_for_both_players() {
+        let positions = PositionStore::new();
+        let request: AnalysisRequest =
+            serde_json::from_str(r#"{"preset": "9x9", "region": {"from": "J9", "to": "A2"}}"#)
+                .unwrap();
+        let Ok(request) = resolve_position(&positions, request) else {
+            panic!("region request should resolve");
+        };
+        assert!(request.region.is_none());
+        let avoid = request.avoid_moves.unwrap();
+        assert_eq!(avoid.len(), 2);
+        assert_eq!(
+            (avoid[0].player.as_str(), avoid[1].player.as_str()),
+            ("B", "W")
+        );
+        // Only the bottom row is outside the rectangle
+        assert_eq!(avoid[0].moves.len(), 9);
+        assert!(avoid[1].moves.iter().all(|coord| coord.ends_with('1')));
+
+        let listed: AnalysisRequest =
+            serde_json::from_str(r#"{"boardXSize": 9, "boardYSize": 9, "region": ["A1", "B1"]}"#)
+                .unwrap();
+        let Ok(listed) = resolve_position(&positions, listed) else {
+            panic!("region request should resolve");
+        };
+        assert_eq!(listed.avoid_moves.unwrap()[0].moves.len(), 79);
+
+        let outside: AnalysisRequest =
+            serde_json::from_str(r#"{"boardXSize": 9, "boardYSize": 9, "region": ["K10"]}"#)
+                .unwrap();
+        assert!(resolve_position(&positions, outside).is_err());
+    }
+
+    #[test]
+    fn test_diagram_becomes_initial_stones() {
+        let positions = PositionStore::new();
+        let request: AnalysisRequest = serde_json::from_value(serde_json::json!({
+            "diagram": ["X O . .", ". . . .", ". . . .", ". . . X"],
+            "moves": ["C3"],
+        }))
+        .unwrap();
+        let Ok(request) = resolve_position(&positions, request) else {
+            panic!("diagram request should resolve");
+        };
+        assert_eq!((request.board_x_size, request.board_y_size), (4, 4));
+        assert_eq!(request.initial_player.as_deref(), Some("B"));
+        let stones = request.initial_stones.unwrap();
+        assert_eq!(stones.len(), 3);
+        assert!(stones.contains(&("W".to_string(), "B4".to_string())));
+        assert!(stones.contains(&("B".to_string(), "D1".to_string())));
+
+        let mismatch: AnalysisRequest =
+            serde_json::from_value(serde_json::json!({ "preset": "9x9", "diagram": ["..", ".."] }))
+                .unwrap();
+        assert!(resolve_position(&positions, mismatch).is_err());
+    }
+
+    #[test]
+    fn test_sgf_becomes_the_position() {
+        let positions = PositionStore::new();
+        let resolve = |fields: serde_json::Value| {
+            let request: AnalysisRequest = serde_json::from_value(fields).unwrap();
+            resolve_position(&positions, request)
+        };
+        let sgf = "(;GM[1]SZ[9]KM[0.5]RU[Japanese]HA[2]AB[cc][gg];W[ee];B[ce])";
+        let Ok(request) = resolve(serde_json::json!({ "sgf": sgf })) else {
+            panic!("sgf request should resolve");
+        };
+        assert_eq!((request.board_x_size, request.board_y_size), (9, 9));
+        assert_eq!(request.komi, Some(0.5));
+        assert_eq!(request.initial_player.as_deref(), Some("W"));
+        let stones = request.initial_stones.unwrap();
+        assert!(stones.contains(&("B".to_string(), "C7".to_string())));
+        assert!(stones.contains(&("B".to_string(), "G3".to_string())));
+        let moves: Vec<(Option<&str>, &str)> = request
+            .moves
+            .iter()
+            .map(|m| (m.color(), m.coord()))
+            .collect();
+        assert_eq!(moves, [(Some("w"), "E5"), (Some("b"), "C5")]);
+
+        let Ok(request) = resolve(serde_json::json!({ "sgf": sgf, "komi": 6.5 })) else {
+            panic!("sgf request should resolve");
+        };
+        assert_eq!(request.komi, Some(6.5));
+
+        assert!(resolve(serde_json::json!({ "sgf": sgf, "moves": ["D4"] })).is_err());
+        assert!(resolve(serde_json::json!({ "sgf": sgf, "preset": "13x13" })).is_err());
+        assert!(resolve(serde_json::json!({ "sgf": "(;GM[1]SZ[9" })).is_err());
+    }
+
+    #[test]
+    fn test_resolve_position_applies_preset() {
+        let positions = PositionStore::new();
+        let request: AnalysisRequest =
+            serde_json::from_str(r#"{"preset": "9x9", "moves": ["E5"]}"#).unwrap();
+        let Ok(request) = resolve_position(&positions, request) else {
+            panic!("preset request should resolve");
+        };
+        assert_eq!(request.board_x_size, 9);
+        assert_eq!(request.komi, Some(7.0));
+
+        positions.insert(
+            "p19".to_string(),
+            Position {
+                id: "p19".to_string(),
+                parent_id: None,
+                board: Board::new(19, 19),
+                player_to_move: Stone::Black,
+                rules: None,
+                komi: None,
+            },
+        );
+        let mismatched = AnalysisRequest {
+            preset: Some("9x9".to_string()),
+            position_id: Some("p19".to_string()),
+            ..Default::default()
+        };
+        assert!(resolve_position(&positions, mismatched).is_err());
+
+        let unknown = AnalysisRequest {
+            preset: Some("7x7".to_string()),
+            ..Default::default()
+        };
+        assert!(resolve_position(&positions, unknown).is_err());
+    }
+
+    #[test]
+    fn test_search_controls_are_validated() {
+        let positions = PositionStore::new();
+        let resolves = |controls: serde_json::Value| {
+            let request: AnalysisRequest = serde_json::from_value(controls).unwrap();
+            resolve_position(&positions, request).is_ok()
+        };
+        assert!(resolves(serde_json::json!({
+            "analysisPVLen": 5, "rootPolicyTemperature": 1.2, "rootFpuReductionMax": 0
+        })));
+        assert!(!resolves(serde_json::json!({ "analysisPVLen": 0 })));
+        assert!(!resolves(serde_json::json!({ "rootPolicyTemperature": 0 })));
+        assert!(!resolves(
+            serde_json::json!({ "rootFpuReductionMax": -0.1 })
+        ));
+    }
+
+    #[test]
+    fn test_tolerate_position_flattens_to_setup() {
+        let positions = PositionStore::new();
+        let request: AnalysisRequest = serde_json::from_str(
+            r#"{"toleratePosition": true, "boardXSize": 9, "boardYSize": 9,
+                "initialStones": [["W", "A2"]], "moves": [["B", "A1"], ["B", "B1"]]}"#,
+        )
+        .unwrap();
+        let Ok(request) = resolve_position(&positions, request) else {
+            panic!("tolerated setup should resolve");
+        };
+        assert!(request.moves.is_empty());
+        assert_eq!(request.initial_player.as_deref(), Some("W"));
+        assert_eq!(request.initial_stones.as_ref().map(Vec::len), Some(3));
+
+        // Moves onto stones and ko recaptures are placed as given; the recapture
+        // at B2 captures C2 again
+        let replayed: AnalysisRequest = serde_json::from_str(
+            r#"{"toleratePosition": true, "boardXSize": 9, "boardYSize": 9,
+                "initialStones": [["B", "B3"], ["B", "A2"], ["B", "B1"],
+                                  ["W", "C3"], ["W", "B2"], ["W", "D2"], ["W", "C1"]],
+                "moves": [["B", "C2"], ["W", "B2"], ["B", "E5"], ["W", "E5"]]}"#,
+        )
+        .unwrap();
+        let Ok(replayed) = resolve_position(&positions, replayed) else {
+            panic!("illegal moves should be tolerated");
+        };
+        let stones = replayed.initial_stones.unwrap();
+        assert!(stones.contains(&("W".to_string(), "B2".to_string())));
+        assert!(!stones.iter().any(|(_, coord)| coord == "C2"));
+        assert!(stones.contains(&("W".to_string(), "E5".to_string())));
+
+        let turns: AnalysisRequest = serde_json::from_str(
+            r#"{"toleratePosition": true, "moves": [["B", "D4"]], "analyzeTurns": [0, 1]}"#,
+        )
+        .unwrap();
+        assert!(resolve_position(&positions, turns).is_err());
+
+        // A setup stone without liberties is still rejected
+        let dead: AnalysisRequest = serde_json::from_str(
+            r#"{"toleratePosition": true, "boardXSize": 9, "boardYSize": 9,
+                "initialStones": [["B", "A1"], ["W", "A2"], ["W", "B1"]]}"#,
+        )
+        .unwrap();
+        assert!(resolve_position(&positions, dead).is_err());
+    }
+
+    #[test]
+    fn test_edit_position_request_deserialization() {
+        let json = r#"{"add": [["B", "C3"], ["W", "D4"]], "remove": ["Q16"], "playerToMove": "W"}"#;
+        let request: EditPositionRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(request.add[1], ("W".to_string(), "D4".to_string()));
+        assert_eq!(request.remove, vec!["Q16".to_string()]);
+        assert_eq!(request.player_to_move.as_deref(), Some("W"));
+    }
+
+    #[test]
+    fn test_create_game_request_deserialization() {
+        let json = r#"{
+            "moves": [["B", "Q16"], ["W", "D4"]],
+            "komi": 6.5,
+            "blackPlayer": "Shusaku",
+            "whitePlayer": "Gennan Inseki",
+            "result": "B+2"
+        }"#;
+        let request: CreateGameRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(request.position.moves.len(), 2);
+        assert_eq!(request.black_player.as_deref(), Some("Shusaku"));
+        assert_eq!(request.result.as_deref(), Some("B+2"));
+    }
+
+    #[test]
+    fn test_guess_request_uses_move_key() {
+        let request: GuessRequest = serde_json::from_str(r#"{"move": "R16"}"#).unwrap();
+        assert_eq!(request.guess, "R16");
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+    }
+
+    #[test]
+    fn test_trade_request_deserialization() {
+        let json = r#"{
+            "moves": ["D4", "Q16"],
+            "komi": 6.5,
+            "blackMoves": ["C16"],
+            "whiteMoves": ["R4"]
+        }"#;
+        let request: TradeRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(request.position.moves.len(), 2);
+        assert_eq!(request.position.komi, Some(6.5));
+        assert_eq!(request.black_moves, vec!["C16".to_string()]);
+        assert_eq!(request.white_moves, vec!["R4".to_string()]);
+    }
+
+    fn exchange(moves: &[&str]) -> Vec<String> {
+        moves.iter().map(|coord| coord.to_string()).collect()
+    }
+
+    #[test]
+    fn test_exchange_captures_unless_the_order_decides_them() {
+        // White's D4 stone is in atari at D5
+        let stones = |extra: &[(&str, &str)]| -> Vec<Vec<String>> {
+            [("W", "D4"), ("B", "C4"), ("B", "D3"), ("B", "E4")]
+                .iter()
+                .chain(extra)
+                .map(|(color, coord)| vec![color.to_string(), coord.to_string()])
+                .collect()
+        };
+        let board = Board::from_setup(9, 9, &stones(&[]), &[]).unwrap();
+        let traded = play_exchange(
+            &board,
+            Stone::Black,
+            &exchange(&["D5", "G7"]),
+            &exchange(&["G3", "C7"]),
+        )
+        .ok()
+        .unwrap();
+        let stones_after = traded.stones();
+        assert!(!stones_after.contains(&(Stone::White, "D4".to_string())));
+        assert_eq!(stones_after.len(), 7);
+
+        // Black's E4 is in atari at E5 too: whoever plays first captures
+        let board = Board::from_setup(9, 9, &stones(&[("W", "F4"), ("W", "E3")]), &[]).unwrap();
+        let error = play_exchange(&board, Stone::Black, &exchange(&["D5"]), &exchange(&["E5"]))
+            .unwrap_err();
+        assert_eq!(error.problem.status, 400);
+        assert_eq!(error.problem.title, "Ambiguous Exchange");
+    }
+
+    #[test]
+    fn test_black_perspective_flips_for_white() {
+        let response: AnalysisResponse = serde_json::from_value(serde_json::json!({
+            "id": "t",
+            "turnNumber": 0,
+            "isDuringSearch": false,
+            "rootInfo": {
+                "winrate": 0.7,
+                "scoreLead": 3.0,
+                "utility": 0.0,
+                "visits": 10,
+                "currentPlayer": "W",
+            },
+        }))
+        .unwrap();
+        let eval = black_perspective(&response).unwrap();
+        assert!((eval.winrate - 0.3).abs() < 1e-6);
+        assert_eq!(eval.score_lead, -3.0);
+    }
+
     #[test]
     fn test_version_response_serialization() {
         let response = VersionResponse {
@@ -621,6 +4588,7 @@ mod tests {
             detail: "KataGo analysis timed out after 20 seconds".to_string(),
             instance: Some("/api/v1/analysis".to_string()),
             request_id: Some("req-123".to_string()),
+            invalid_moves: None,
         };
 
         let json = serde_json::to_string(&problem).unwrap();
@@ -628,4 +4596,58 @@ mod tests {
         assert!(json.contains("\"status\":504"));
         assert!(json.contains("\"requestId\":\"req-123\""));
     }
+
+    #[test]
+    fn test_policy_is_reshaped_into_rows() {
+        let policy = [0.1, 0.2, 0.3, -1.0, 0.4, 0.5, 0.6];
+        let (rows, pass) = board_rows(&policy, 3, 2).unwrap();
+        assert_eq!(rows, [vec![0.1, 0.2, 0.3], vec![-1.0, 0.4, 0.5]]);
+        assert_eq!(pass, 0.6);
+        assert!(board_rows(&policy, 3, 3).is_none());
+        assert!(board_rows(&[], 3, 2).is_none());
+    }
+
+    #[test]
+    fn test_ownership_is_reshaped_by_format() {
+        // 3x2: the top row (y = 2) first
+        let values = vec![0.1, 0.2, 0.3, -0.4, -0.5, -0.6];
+        assert_eq!(
+            reshaped_ownership(values.clone(), OwnershipFormat::Rows, 3, 2),
+            Ownership::Rows(vec![vec![0.1, 0.2, 0.3], vec![-0.4, -0.5, -0.6]])
+        );
+        let Ownership::Map(map) = reshaped_ownership(values.clone(), OwnershipFormat::Map, 3, 2)
+        else {
+            panic!("expected a map");
+        };
+        assert_eq!(
+            (map["A2"], map["C2"], map["A1"], map["C1"]),
+            (0.1, 0.3, -0.4, -0.6)
+        );
+        assert_eq!(map.len(), 6);
+        // Not the board's size
+        assert_eq!(
+            reshaped_ownership(values.clone(), OwnershipFormat::Map, 3, 3),
+            Ownership::Flat(values)
+        );
+
+        let mut response: AnalysisResponse = serde_json::from_value(serde_json::json!({
+            "id": "a",
+            "turnNumber": 0,
+            "isDuringSearch": false,
+            "ownership": [0.1, 0.2, 0.3, 0.4],
+            "moveInfos": [],
+        }))
+        .unwrap();
+        let request = AnalysisRequest {
+            board_x_size: 2,
+            board_y_size: 2,
+            ownership_format: Some(OwnershipFormat::Rows),
+            ..Default::default()
+        };
+        shape_ownership(&mut response, &request);
+        assert_eq!(
+            serde_json::to_value(&response.ownership).unwrap(),
+            serde_json::json!([[0.1f32, 0.2f32], [0.3f32, 0.4f32]])
+        );
+    }
 }
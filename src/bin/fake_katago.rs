@@ -0,0 +1,354 @@
+//! Stand-in for `katago analysis` used by the integration tests.
+//!
+//! Speaks KataGo's JSON analysis protocol on stdin/stdout and answers with
+//! deterministic, made-up evaluations. Each query can script a failure mode through
+//! its `overrideSettings`:
+//!
+//! - `"fakeKatago": "error"` answers with a KataGo error line
+//! - `"fakeKatago": "warning"` sends a warning line before the response
+//! - `"fakeKatago": "garbage"` writes a non-JSON line before the response
+//! - `"fakeKatago": "hang"` never answers
+//! - `"fakeKatago": "crash"` exits immediately with status 1
+//! - `"fakeDelayMs": 500` answers after a delay (queries are answered concurrently)
+//!
+//! The root of a result reports the query's `maxVisits` as its visits, and the
+//! moves of `allowMoves` replace the usual candidates. Candidates already played
+//! are left out, and pass is suggested once none remain.
+//!
+//! `FAKE_KATAGO_LATENCY_MS` delays every answer and `FAKE_KATAGO_STARTUP_MS` delays
+//! reading stdin, like model loading. With `-human-model`, queries that set
+//! `humanSLProfile` also get human-model fields. Like KataGo, it answers the
+//! queries in flight before exiting when stdin closes.
+//!
+//! Started as `fake_katago gtp`, it speaks a little GTP instead: `genmove` always
+//! answers Q16 (with chat and search info on stderr), `kata-analyze` reports an
+//! even ownership map, `play` rejects moves off the board, and the setup commands
+//! the bot sends are accepted. `kata-get-param` and `kata-set-param` know
+//! `playoutDoublingAdvantage`, and `list_commands` answers over several lines. `fake_katago version` prints a version like
+//! `katago version` does.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const COLUMNS: &[u8] = b"ABCDEFGHJKLMNOPQRST";
+
+fn env_ms(name: &str) -> u64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
+
+fn main() {
+    match std::env::args().nth(1).as_deref() {
+        Some("gtp") => return gtp(),
+        Some("version") => {
+            println!("KataGo v1.16.0 (fake)");
+            println!("Git revision: fake");
+            return;
+        }
+        _ => {}
+    }
+    let human_model = std::env::args().any(|arg| arg == "-human-model");
+    let latency = env_ms("FAKE_KATAGO_LATENCY_MS");
+    thread::sleep(Duration::from_millis(env_ms("FAKE_KATAGO_STARTUP_MS")));
+    eprintln!("Started, ready to begin handling requests");
+
+    let stdout = Arc::new(Mutex::new(io::stdout()));
+    let mut in_flight: Vec<thread::JoinHandle<()>> = Vec::new();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let query: Value = match serde_json::from_str(&line) {
+            Ok(query) => query,
+            Err(e) => {
+                let error = json!({ "error": format!("Could not parse input line as json request: {}", e) });
+                write_line(&stdout, &error.to_string());
+                continue;
+            }
+        };
+
+        let settings = query.get("overrideSettings").cloned().unwrap_or_default();
+        let behavior = settings["fakeKatago"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        if behavior == "crash" {
+            std::process::exit(1);
+        }
+        if behavior == "hang" {
+            continue;
+        }
+
+        let delay = latency + settings["fakeDelayMs"].as_u64().unwrap_or(0);
+        let stdout = stdout.clone();
+        in_flight.retain(|handle| !handle.is_finished());
+        in_flight.push(thread::spawn(move || {
+            thread::sleep(Duration::from_millis(delay));
+            for line in answer(&query, &behavior, human_model) {
+                write_line(&stdout, &line);
+            }
+        }));
+    }
+    for handle in in_flight {
+        let _ = handle.join();
+    }
+}
+
+fn gtp() {
+    let mut board_size = 19;
+    let mut params: HashMap<String, String> =
+        HashMap::from([("playoutDoublingAdvantage".to_string(), "0.0".to_string())]);
+    let mut stdout = io::stdout();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            continue;
+        };
+        let reply = match command {
+            "genmove" => {
+                eprintln!(
+                    "MALKOVICH:Visits 20 Winrate 55.00% ScoreLead 1.5 ScoreStdev 12.0 PV Q16 D4"
+                );
+                eprintln!(
+                    "Q16  : T   5.00c W   5.00c S  0.50c ( +1.5) PSV      12 N      10  --  Q16 D4"
+                );
+                eprintln!(
+                    "D4   : T   3.00c W   3.00c S  0.30c ( +1.2) PSV       5 N       6  --  D4"
+                );
+                // Let the bot read stderr before the reply, as KataGo logs before answering
+                thread::sleep(Duration::from_millis(50));
+                Ok("Q16".to_string())
+            }
+            "play" => match args.get(1) {
+                Some(mv) if on_board(mv, board_size) => Ok(String::new()),
+                _ => Err("illegal move"),
+            },
+            "boardsize" => match args.first().and_then(|size| size.parse().ok()) {
+                Some(size) => {
+                    board_size = size;
+                    Ok(String::new())
+                }
+                None => Err("unacceptable size"),
+            },
+            "kata-analyze" => {
+                let ownership = vec!["0.0"; board_size * board_size].join(" ");
+                let _ = write!(
+                    stdout,
+                    "=\ninfo move D4 visits 10 winrate 0.5 scoreLead 0.5 order 0 pv D4 ownership {}\n",
+                    ownership
+                );
+                let _ = stdout.flush();
+                continue;
+            }
+            "kata-get-param" => args
+                .first()
+                .and_then(|name| params.get(*name).cloned())
+                .ok_or("Invalid parameter"),
+            "kata-set-param" => match args {
+                [name, value] if params.contains_key(*name) => {
+                    params.insert(name.to_string(), value.to_string());
+                    Ok(String::new())
+                }
+                _ => Err("Invalid parameter"),
+            },
+            "list_commands" => {
+                Ok(["genmove", "play", "kata-get-param", "kata-set-param"].join("\n"))
+            }
+            "komi" | "clear_board" | "clear_cache" | "kata-set-rules" | "name" => Ok(String::new()),
+            _ => Err("unknown command"),
+        };
+        let _ = match reply {
+            Ok(reply) => write!(stdout, "= {}\n\n", reply),
+            Err(message) => write!(stdout, "? {}\n\n", message),
+        };
+        let _ = stdout.flush();
+    }
+}
+
+/// Whether a GTP coordinate (or pass) is on a board of `size`
+fn on_board(mv: &str, size: usize) -> bool {
+    if mv.eq_ignore_ascii_case("pass") {
+        return true;
+    }
+    let mut chars = mv.chars();
+    let column = chars.next().map(|c| c.to_ascii_uppercase() as u8);
+    let row: Option<usize> = chars.as_str().parse().ok();
+    match (
+        column.and_then(|c| COLUMNS.iter().position(|&col| col == c)),
+        row,
+    ) {
+        (Some(x), Some(y)) => x < size && (1..=size).contains(&y),
+        _ => false,
+    }
+}
+
+fn write_line(stdout: &Mutex<io::Stdout>, line: &str) {
+    let mut stdout = stdout.lock().unwrap();
+    let _ = writeln!(stdout, "{}", line);
+    let _ = stdout.flush();
+}
+
+/// The lines KataGo would write for a query
+fn answer(query: &Value, behavior: &str, human_model: bool) -> Vec<String> {
+    let id = query["id"].clone();
+    if let Some(action) = query["action"].as_str() {
+        let response = match action {
+            "query_version" => {
+                json!({ "id": id, "action": action, "version": "1.16.0", "git_hash": "fake" })
+            }
+            "query_models" => json!({
+                "id": id,
+                "action": action,
+                "models": [{ "name": "fake.bin.gz", "internalName": "fake", "maxBatchSize": 8,
+                             "usesHumanSLProfile": false, "version": 15, "usingFP16": "false" }],
+            }),
+            "clear_cache" | "terminate" | "terminate_all" => json!({ "id": id, "action": action }),
+            other => json!({ "id": id, "error": format!("Unknown action: {}", other) }),
+        };
+        return vec![response.to_string()];
+    }
+
+    let mut lines = Vec::new();
+    match behavior {
+        "error" => {
+            let error = json!({ "id": id, "error": "Scripted error from fake KataGo" });
+            return vec![error.to_string()];
+        }
+        "warning" => lines.push(
+            json!({ "id": id, "field": "overrideSettings", "warning": "Unknown config params: fakeKatago" })
+                .to_string(),
+        ),
+        "garbage" => lines.push("this is not json".to_string()),
+        _ => {}
+    }
+
+    let moves = query["moves"].as_array().cloned().unwrap_or_default();
+    let turns: Vec<u64> = match query["analyzeTurns"].as_array() {
+        Some(turns) => turns.iter().filter_map(Value::as_u64).collect(),
+        None => vec![moves.len() as u64],
+    };
+    let partial = query.get("reportDuringSearchEvery").is_some();
+    for turn in turns {
+        let mut response = evaluation(query, turn, human_model);
+        if partial {
+            response["isDuringSearch"] = json!(true);
+            lines.push(response.to_string());
+            response["isDuringSearch"] = json!(false);
+        }
+        lines.push(response.to_string());
+    }
+    lines
+}
+
+/// A made-up but well-formed analysis of the position after `turn` moves
+fn evaluation(query: &Value, turn: u64, human_model: bool) -> Value {
+    let id = query["id"].clone();
+    let width = query["boardXSize"].as_u64().unwrap_or(19) as usize;
+    let height = query["boardYSize"].as_u64().unwrap_or(19) as usize;
+    let moves = query["moves"].as_array().cloned().unwrap_or_default();
+    let last_color = moves
+        .get((turn as usize).wrapping_sub(1))
+        .and_then(|mv| mv[0].as_str())
+        .map(str::to_uppercase);
+    let to_move = match last_color.as_deref() {
+        Some("B") => "W".to_string(),
+        Some(_) => "B".to_string(),
+        None => query["initialPlayer"]
+            .as_str()
+            .unwrap_or("B")
+            .to_uppercase(),
+    };
+    let human = human_model && query["overrideSettings"]["humanSLProfile"].is_string();
+    let winrate = 0.45 + 0.01 * (turn % 10) as f64;
+
+    // allowMoves narrows the candidates to the moves it lists
+    let candidates: Vec<String> = match query["allowMoves"][0]["moves"].as_array() {
+        Some(allowed) => allowed
+            .iter()
+            .filter_map(|mv| Some(mv.as_str()?.to_string()))
+            .collect(),
+        None => [
+            (3.min(width - 1), 3.min(height - 1)),
+            (width.saturating_sub(4), height.saturating_sub(4)),
+            (width / 2, height / 2),
+        ]
+        .iter()
+        .map(|&(x, y)| format!("{}{}", COLUMNS[x] as char, y + 1))
+        // Points already played are not suggested again; with none left, pass
+        .filter(|coord| {
+            !moves[..(turn as usize).min(moves.len())]
+                .iter()
+                .any(|mv| mv[1].as_str() == Some(coord.as_str()))
+        })
+        .chain(std::iter::once("pass".to_string()))
+        .take(3)
+        .collect(),
+    };
+    let move_infos: Vec<Value> = candidates
+        .into_iter()
+        .enumerate()
+        .map(|(order, coord)| {
+            let mut info = json!({
+                "move": coord,
+                "visits": 10u64.saturating_sub(order as u64 * 3),
+                "winrate": winrate - 0.02 * order as f64,
+                "scoreMean": 1.5 - order as f64,
+                "scoreStdev": 12.0,
+                "scoreLead": 1.5 - order as f64,
+                "utility": 0.1,
+                "utilityLcb": 0.05,
+                "lcb": winrate - 0.05,
+                "prior": 0.3 - 0.1 * order as f64,
+                "order": order,
+                "pv": [coord],
+            });
+            if human {
+                info["humanPrior"] = json!(0.2);
+            }
+            info
+        })
+        .collect();
+
+    let mut root_info = json!({
+        "winrate": winrate,
+        "scoreLead": 1.5,
+        "utility": 0.1,
+        "visits": query["maxVisits"].as_u64().unwrap_or(17),
+        "currentPlayer": to_move,
+        "rawWinrate": winrate,
+        "rawScoreMean": 1.5,
+        "rawStScoreError": 2.0,
+    });
+    if human {
+        root_info["humanWinrate"] = json!(winrate);
+        root_info["humanScoreMean"] = json!(1.0);
+        root_info["humanScoreStdev"] = json!(10.0);
+    }
+
+    let mut response = json!({
+        "id": id,
+        "turnNumber": turn,
+        "isDuringSearch": false,
+        "moveInfos": move_infos,
+        "rootInfo": root_info,
+    });
+    if query["includeOwnership"].as_bool() == Some(true) {
+        response["ownership"] = json!(vec![0.0; width * height]);
+    }
+    if query["includePolicy"].as_bool() == Some(true) {
+        let uniform = 1.0 / (width * height + 1) as f64;
+        response["policy"] = json!(vec![uniform; width * height + 1]);
+        if human {
+            response["humanPolicy"] = json!(vec![uniform; width * height + 1]);
+        }
+    }
+    response
+}
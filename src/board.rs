@@ -0,0 +1,596 @@
+use thiserror::Error;
+
+/// Stone color on the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stone {
+    Black,
+    White,
+}
+
+impl Stone {
+    /// Parse a color as used in the API ("B"/"W", "b"/"w", "black"/"white")
+    pub fn parse(color: &str) -> Option<Self> {
+        match color.to_ascii_lowercase().as_str() {
+            "b" | "black" => Some(Stone::Black),
+            "w" | "white" => Some(Stone::White),
+            _ => None,
+        }
+    }
+
+    pub fn opponent(self) -> Self {
+        match self {
+            Stone::Black => Stone::White,
+            Stone::White => Stone::Black,
+        }
+    }
+
+    /// Uppercase single-letter color as used in initialStones
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Stone::Black => "B",
+            Stone::White => "W",
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum BoardError {
+    #[error("Invalid coordinate '{0}'")]
+    InvalidCoordinate(String),
+
+    #[error("Invalid color '{0}'")]
+    InvalidColor(String),
+
+    #[error("Point {0} is already occupied")]
+    Occupied(String),
+
+    #[error("Point {0} is empty")]
+    Empty(String),
+
+    #[error("Playing {0} would be suicide")]
+    Suicide(String),
+
+    #[error("Playing {0} would retake the ko at once")]
+    Ko(String),
+
+    #[error("Playing {0} would repeat an earlier position")]
+    Superko(String),
+
+    #[error("Stone at {0} has no liberties")]
+    NoLiberties(String),
+
+    #[error("Invalid board diagram: {0}")]
+    InvalidDiagram(String),
+}
+
+/// Widest and tallest board a diagram may draw: columns A-Z without I
+const MAX_DIAGRAM_SIZE: usize = 25;
+
+/// Stones each player has captured
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Prisoners {
+    pub black: u32,
+    pub white: u32,
+}
+
+/// Minimal Go board model used to edit positions server-side
+///
+/// Coordinates use GTP notation: columns A-T skipping I, rows counted from the bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+    width: u8,
+    height: u8,
+    points: Vec<Option<Stone>>,
+}
+
+impl Board {
+    pub fn new(width: u8, height: u8) -> Self {
+        Self {
+            width,
+            height,
+            points: vec![None; width as usize * height as usize],
+        }
+    }
+
+    /// Build a board from KataGo-format initial stones and moves ([color, coord] pairs)
+    pub fn from_setup(
+        width: u8,
+        height: u8,
+        initial_stones: &[Vec<String>],
+        moves: &[Vec<String>],
+    ) -> Result<Self, BoardError> {
+        Self::replay(width, height, initial_stones, moves).map(|(board, _)| board)
+    }
+
+    /// Like [`Board::from_setup`], also counting the stones Black and White captured
+    pub fn replay(
+        width: u8,
+        height: u8,
+        initial_stones: &[Vec<String>],
+        moves: &[Vec<String>],
+    ) -> Result<(Self, Prisoners), BoardError> {
+        let mut board = Board::new(width, height);
+        for stone in initial_stones {
+            let (color, coord) = Self::split_pair(stone)?;
+            board.place(color, coord)?;
+        }
+        let mut prisoners = Prisoners::default();
+        for mv in moves {
+            let (color, coord) = Self::split_pair(mv)?;
+            let captured = board.play(color, coord)?.len() as u32;
+            match color {
+                Stone::Black => prisoners.black += captured,
+                Stone::White => prisoners.white += captured,
+            }
+        }
+        Ok((board, prisoners))
+    }
+
+    fn split_pair(pair: &[String]) -> Result<(Stone, &str), BoardError> {
+        match pair {
+            [color, coord] => {
+                let stone =
+                    Stone::parse(color).ok_or_else(|| BoardError::InvalidColor(color.clone()))?;
+                Ok((stone, coord.as_str()))
+            }
+            _ => Err(BoardError::InvalidCoordinate(pair.join(" "))),
+        }
+    }
+
+    /// Read a board diagram, one string per row from the top: `X` (or `B`) for
+    /// Black, `O` (or `W`) for White, `.` or `+` for empty points. Spaces are ignored.
+    pub fn from_diagram(rows: &[String]) -> Result<Self, BoardError> {
+        let rows: Vec<Vec<char>> = rows
+            .iter()
+            .map(|row| row.chars().filter(|c| !c.is_whitespace()).collect())
+            .collect();
+        let height = rows.len();
+        let width = rows.first().map_or(0, Vec::len);
+        if !(2..=MAX_DIAGRAM_SIZE).contains(&height) || !(2..=MAX_DIAGRAM_SIZE).contains(&width) {
+            return Err(BoardError::InvalidDiagram(format!(
+                "{}x{} is not a board size from 2x2 to {}x{}",
+                width, height, MAX_DIAGRAM_SIZE, MAX_DIAGRAM_SIZE
+            )));
+        }
+
+        let mut board = Board::new(width as u8, height as u8);
+        for (row_index, row) in rows.iter().enumerate() {
+            if row.len() != width {
+                return Err(BoardError::InvalidDiagram(format!(
+                    "row {} has {} points, the first row {}",
+                    row_index + 1,
+                    row.len(),
+                    width
+                )));
+            }
+            let y = (height - 1 - row_index) as u8;
+            for (x, &point) in row.iter().enumerate() {
+                let stone = match point.to_ascii_uppercase() {
+                    'X' | 'B' => Stone::Black,
+                    'O' | 'W' => Stone::White,
+                    '.' | '+' => continue,
+                    other => {
+                        return Err(BoardError::InvalidDiagram(format!(
+                            "'{}' in row {} is not X, O, or .",
+                            other,
+                            row_index + 1
+                        )))
+                    }
+                };
+                let idx = board.index(x as u8, y);
+                board.points[idx] = Some(stone);
+            }
+        }
+        Ok(board)
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    /// Parse a GTP coordinate (e.g., "D4") into zero-based (x, y) with y=0 at the bottom
+    pub fn parse_point(&self, coord: &str) -> Result<(u8, u8), BoardError> {
+        let invalid = || BoardError::InvalidCoordinate(coord.to_string());
+        let mut chars = coord.chars();
+        let col_char = chars.next().ok_or_else(invalid)?.to_ascii_uppercase();
+        if !col_char.is_ascii_uppercase() || col_char == 'I' {
+            return Err(invalid());
+        }
+        let x = if col_char < 'I' {
+            col_char as u8 - b'A'
+        } else {
+            col_char as u8 - b'A' - 1
+        };
+        let row: u8 = chars.as_str().parse().map_err(|_| invalid())?;
+        if x >= self.width || row == 0 || row > self.height {
+            return Err(invalid());
+        }
+        Ok((x, row - 1))
+    }
+
+    /// Format zero-based (x, y) as a GTP coordinate
+    pub fn coord_name(x: u8, y: u8) -> String {
+        let col = if x < 8 { b'A' + x } else { b'A' + x + 1 };
+        format!("{}{}", col as char, y + 1)
+    }
+
+    /// Index into KataGo's policy/ownership arrays (row-major from the top row; pass last)
+    pub fn policy_index(&self, coord: &str) -> Result<usize, BoardError> {
+        if coord.eq_ignore_ascii_case("pass") {
+            return Ok(self.width as usize * self.height as usize);
+        }
+        let (x, y) = self.parse_point(coord)?;
+        Ok((self.height - 1 - y) as usize * self.width as usize + x as usize)
+    }
+
+    /// The stone at zero-based (x, y), y=0 at the bottom
+    pub fn stone(&self, x: u8, y: u8) -> Option<Stone> {
+        self.points[self.index(x, y)]
+    }
+
+    fn index(&self, x: u8, y: u8) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// Put a stone on an empty point without resolving captures (setup stones)
+    pub fn place(&mut self, stone: Stone, coord: &str) -> Result<(), BoardError> {
+        let (x, y) = self.parse_point(coord)?;
+        let idx = self.index(x, y);
+        if self.points[idx].is_some() {
+            return Err(BoardError::Occupied(coord.to_string()));
+        }
+        self.points[idx] = Some(stone);
+        Ok(())
+    }
+
+    /// Remove the stone at a point, returning its color
+    pub fn remove(&mut self, coord: &str) -> Result<Stone, BoardError> {
+        let (x, y) = self.parse_point(coord)?;
+        let idx = self.index(x, y);
+        self.points[idx]
+            .take()
+            .ok_or_else(|| BoardError::Empty(coord.to_string()))
+    }
+
+    /// Play a move, removing captured opponent stones. Returns the captured points.
+    /// "pass" is accepted and leaves the board unchanged.
+    pub fn play(&mut self, stone: Stone, coord: &str) -> Result<Vec<String>, BoardError> {
+        self.play_under(stone, coord, false)
+    }
+
+    /// Like [`Board::play`], for rules where suicide of more than one stone is
+    /// legal: the move then removes the player's own group. Suicide of a single
+    /// stone is illegal under all rules.
+    pub fn play_under(
+        &mut self,
+        stone: Stone,
+        coord: &str,
+        multi_stone_suicide_legal: bool,
+    ) -> Result<Vec<String>, BoardError> {
+        if coord.eq_ignore_ascii_case("pass") {
+            return Ok(Vec::new());
+        }
+        self.place(stone, coord)?;
+        let (x, y) = self.parse_point(coord)?;
+
+        let mut captured = Vec::new();
+        for (nx, ny) in self.neighbors(x, y) {
+            if self.points[self.index(nx, ny)] == Some(stone.opponent()) {
+                let (group, liberties) = self.group_at(nx, ny);
+                if liberties == 0 {
+                    for (gx, gy) in group {
+                        let idx = self.index(gx, gy);
+                        self.points[idx] = None;
+                        captured.push(Self::coord_name(gx, gy));
+                    }
+                }
+            }
+        }
+
+        let (group, liberties) = self.group_at(x, y);
+        if liberties == 0 {
+            if multi_stone_suicide_legal && group.len() > 1 {
+                for (gx, gy) in group {
+                    let idx = self.index(gx, gy);
+                    self.points[idx] = None;
+                }
+                return Ok(captured);
+            }
+            let idx = self.index(x, y);
+            self.points[idx] = None;
+            return Err(BoardError::Suicide(coord.to_string()));
+        }
+
+        Ok(captured)
+    }
+
+    /// Put a stone on a point without checking the move is legal: a stone already
+    /// there is replaced, and suicide and ko are allowed. Opponent groups left
+    /// without liberties are captured; the player's own group stays even if it
+    /// has none. "pass" leaves the board unchanged.
+    pub fn place_move(&mut self, stone: Stone, coord: &str) -> Result<(), BoardError> {
+        if coord.eq_ignore_ascii_case("pass") {
+            return Ok(());
+        }
+        let (x, y) = self.parse_point(coord)?;
+        let idx = self.index(x, y);
+        self.points[idx] = Some(stone);
+        for (nx, ny) in self.neighbors(x, y) {
+            if self.points[self.index(nx, ny)] == Some(stone.opponent()) {
+                let (group, liberties) = self.group_at(nx, ny);
+                if liberties == 0 {
+                    for (gx, gy) in group {
+                        let idx = self.index(gx, gy);
+                        self.points[idx] = None;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail if any group on the board has no liberties (e.g., after setup edits)
+    pub fn check_liberties(&self) -> Result<(), BoardError> {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.points[self.index(x, y)].is_some() && self.group_at(x, y).1 == 0 {
+                    return Err(BoardError::NoLiberties(Self::coord_name(x, y)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// All stones on the board as (color, coordinate) pairs
+    pub fn stones(&self) -> Vec<(Stone, String)> {
+        let mut stones = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if let Some(stone) = self.points[self.index(x, y)] {
+                    stones.push((stone, Self::coord_name(x, y)));
+                }
+            }
+        }
+        stones
+    }
+
+    /// Hash of the stone layout and side to move, used to index positions
+    pub fn position_hash(&self, to_move: Stone) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.width.hash(&mut hasher);
+        self.height.hash(&mut hasher);
+        self.points.hash(&mut hasher);
+        to_move.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Stones in KataGo initialStones format: [["B", "D4"], ["W", "Q16"], ...]
+    pub fn to_initial_stones(&self) -> Vec<(String, String)> {
+        self.stones()
+            .into_iter()
+            .map(|(stone, coord)| (stone.as_str().to_string(), coord))
+            .collect()
+    }
+
+    fn neighbors(&self, x: u8, y: u8) -> Vec<(u8, u8)> {
+        let mut result = Vec::with_capacity(4);
+        if x > 0 {
+            result.push((x - 1, y));
+        }
+        if x + 1 < self.width {
+            result.push((x + 1, y));
+        }
+        if y > 0 {
+            result.push((x, y - 1));
+        }
+        if y + 1 < self.height {
+            result.push((x, y + 1));
+        }
+        result
+    }
+
+    /// Flood-fill the group at (x, y), returning its points and liberty count
+    fn group_at(&self, x: u8, y: u8) -> (Vec<(u8, u8)>, usize) {
+        let color = self.points[self.index(x, y)];
+        let mut visited = vec![false; self.points.len()];
+        let mut liberty_seen = vec![false; self.points.len()];
+        let mut stack = vec![(x, y)];
+        let mut group = Vec::new();
+        let mut liberties = 0;
+        visited[self.index(x, y)] = true;
+
+        while let Some((cx, cy)) = stack.pop() {
+            group.push((cx, cy));
+            for (nx, ny) in self.neighbors(cx, cy) {
+                let idx = self.index(nx, ny);
+                match self.points[idx] {
+                    None if !liberty_seen[idx] => {
+                        liberty_seen[idx] = true;
+                        liberties += 1;
+                    }
+                    c if c == color && !visited[idx] => {
+                        visited[idx] = true;
+                        stack.push((nx, ny));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        (group, liberties)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stone_at(board: &Board, coord: &str) -> Option<Stone> {
+        board
+            .stones()
+            .into_iter()
+            .find(|(_, c)| c == coord)
+            .map(|(stone, _)| stone)
+    }
+
+    fn pairs(list: &[(&str, &str)]) -> Vec<Vec<String>> {
+        list.iter()
+            .map(|(c, m)| vec![c.to_string(), m.to_string()])
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_point_skips_i() {
+        let board = Board::new(19, 19);
+        assert_eq!(board.parse_point("A1").unwrap(), (0, 0));
+        assert_eq!(board.parse_point("H8").unwrap(), (7, 7));
+        assert_eq!(board.parse_point("J9").unwrap(), (8, 8));
+        assert_eq!(board.parse_point("t19").unwrap(), (18, 18));
+        assert!(board.parse_point("I5").is_err());
+        assert!(board.parse_point("U1").is_err());
+        assert!(board.parse_point("A20").is_err());
+        assert!(board.parse_point("A0").is_err());
+    }
+
+    #[test]
+    fn test_coord_name_roundtrip() {
+        let board = Board::new(19, 19);
+        for coord in ["A1", "H8", "J9", "Q16", "T19"] {
+            let (x, y) = board.parse_point(coord).unwrap();
+            assert_eq!(Board::coord_name(x, y), coord);
+        }
+    }
+
+    #[test]
+    fn test_policy_index_top_row_first() {
+        let board = Board::new(19, 19);
+        assert_eq!(board.policy_index("A19").unwrap(), 0);
+        assert_eq!(board.policy_index("T19").unwrap(), 18);
+        assert_eq!(board.policy_index("A1").unwrap(), 18 * 19);
+        assert_eq!(board.policy_index("pass").unwrap(), 361);
+    }
+
+    #[test]
+    fn test_position_hash() {
+        let a = Board::from_setup(9, 9, &pairs(&[("B", "E5")]), &[]).unwrap();
+        let b = Board::from_setup(9, 9, &[], &pairs(&[("B", "E5")])).unwrap();
+        assert_eq!(a.position_hash(Stone::White), b.position_hash(Stone::White));
+        assert_ne!(a.position_hash(Stone::White), a.position_hash(Stone::Black));
+    }
+
+    #[test]
+    fn test_capture_single_stone() {
+        let mut board = Board::from_setup(
+            9,
+            9,
+            &[],
+            &pairs(&[
+                ("B", "E5"),
+                ("W", "E6"),
+                ("B", "D6"),
+                ("W", "A1"),
+                ("B", "F6"),
+            ]),
+        )
+        .unwrap();
+        let captured = board.play(Stone::Black, "E7").unwrap();
+        assert_eq!(captured, vec!["E6".to_string()]);
+        assert!(!board.stones().iter().any(|(_, c)| c == "E6"));
+    }
+
+    #[test]
+    fn test_suicide_rejected() {
+        let mut board = Board::from_setup(9, 9, &pairs(&[("B", "A2"), ("B", "B1")]), &[]).unwrap();
+        assert_eq!(
+            board.play(Stone::White, "A1"),
+            Err(BoardError::Suicide("A1".to_string()))
+        );
+        assert_eq!(board.stones().len(), 2);
+        assert_eq!(
+            board.play_under(Stone::White, "A1", true),
+            Err(BoardError::Suicide("A1".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_multi_stone_suicide_removes_the_group_where_legal() {
+        let setup = pairs(&[("W", "A1"), ("B", "A2"), ("B", "B2"), ("B", "C1")]);
+        let mut board = Board::from_setup(9, 9, &setup, &[]).unwrap();
+        assert_eq!(
+            board.play(Stone::White, "B1"),
+            Err(BoardError::Suicide("B1".to_string()))
+        );
+        assert_eq!(board.play_under(Stone::White, "B1", true), Ok(Vec::new()));
+        assert_eq!(stone_at(&board, "A1"), None);
+        assert_eq!(board.stones().len(), 3);
+    }
+
+    #[test]
+    fn test_occupied_point_rejected() {
+        let mut board = Board::new(9, 9);
+        board.place(Stone::Black, "C3").unwrap();
+        assert_eq!(
+            board.place(Stone::White, "C3"),
+            Err(BoardError::Occupied("C3".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_get_and_remove() {
+        let mut board = Board::new(9, 9);
+        board.place(Stone::Black, "C3").unwrap();
+        assert_eq!(stone_at(&board, "C3"), Some(Stone::Black));
+        assert_eq!(board.remove("C3").unwrap(), Stone::Black);
+        assert_eq!(stone_at(&board, "C3"), None);
+        assert_eq!(board.remove("C3"), Err(BoardError::Empty("C3".to_string())));
+    }
+
+    #[test]
+    fn test_check_liberties() {
+        let mut board = Board::from_setup(9, 9, &pairs(&[("B", "A2"), ("B", "B1")]), &[]).unwrap();
+        assert!(board.check_liberties().is_ok());
+        board.place(Stone::White, "A1").unwrap();
+        assert_eq!(
+            board.check_liberties(),
+            Err(BoardError::NoLiberties("A1".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_from_diagram_reads_rows_from_the_top() {
+        let rows = ["X . O", ". . .", "+ + x"].map(String::from);
+        let board = Board::from_diagram(&rows).unwrap();
+        assert_eq!((board.width(), board.height()), (3, 3));
+        assert_eq!(stone_at(&board, "A3"), Some(Stone::Black));
+        assert_eq!(stone_at(&board, "C3"), Some(Stone::White));
+        assert_eq!(stone_at(&board, "C1"), Some(Stone::Black));
+        assert_eq!(board.stones().len(), 3);
+
+        let ragged = ["X..", ".."].map(String::from);
+        assert!(matches!(
+            Board::from_diagram(&ragged),
+            Err(BoardError::InvalidDiagram(_))
+        ));
+        let unknown = ["X?", ".."].map(String::from);
+        assert!(Board::from_diagram(&unknown).is_err());
+        assert!(Board::from_diagram(&[]).is_err());
+    }
+
+    #[test]
+    fn test_to_initial_stones() {
+        let board =
+            Board::from_setup(19, 19, &pairs(&[("B", "D4")]), &pairs(&[("W", "Q16")])).unwrap();
+        assert_eq!(
+            board.to_initial_stones(),
+            vec![
+                ("B".to_string(), "D4".to_string()),
+                ("W".to_string(), "Q16".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::config::{CacheBackendKind, CacheConfig, KatagoConfig};
+use crate::disk_cache::DiskCache;
+use crate::redis_cache::RedisCache;
+use anyhow::Context;
+use std::future::Future;
+use std::pin::Pin;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A store of completed analyses behind an engine's in-memory cache, keyed by the
+/// query and the models that answer it. Backends are best-effort: a failure is
+/// logged and counts as a miss, so the query is simply searched.
+pub trait CacheBackend: Send + Sync {
+    /// Name reported by GET /api/v1/cache/stats
+    fn kind(&self) -> &'static str;
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<String>>;
+    fn insert<'a>(&'a self, key: &'a str, response: &'a str) -> BoxFuture<'a, ()>;
+    fn clear(&self) -> BoxFuture<'_, ()>;
+    fn counts(&self) -> BackendCounts;
+}
+
+/// Counters of a backend, summed over engines for GET /api/v1/cache/stats
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BackendCounts {
+    pub hits: u64,
+    /// None when the backend can't tell cheaply (it is shared with other servers)
+    pub entries: Option<usize>,
+    pub bytes: Option<u64>,
+}
+
+impl std::ops::Add for BackendCounts {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        fn sum<T: std::ops::Add<Output = T>>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, b) => a.or(b),
+            }
+        }
+        Self {
+            hits: self.hits + other.hits,
+            entries: sum(self.entries, other.entries),
+            bytes: sum(self.bytes, other.bytes),
+        }
+    }
+}
+
+/// FNV-1a, which unlike the standard library's hasher is the same in every build,
+/// so stored keys stay valid across upgrades and agree between servers
+pub fn stable_hash(key: &str) -> u64 {
+    key.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The backend `[cache]` configures for an engine, if any
+pub fn build(
+    katago: &KatagoConfig,
+    cache: &CacheConfig,
+) -> anyhow::Result<Option<Box<dyn CacheBackend>>> {
+    match cache.backend {
+        CacheBackendKind::Memory => {
+            let Some(dir) = &katago.result_cache_dir else {
+                return Ok(None);
+            };
+            let disk = DiskCache::open(dir, katago.result_cache_dir_max_mb * 1024 * 1024)
+                .with_context(|| format!("Failed to open result cache directory {}", dir))?;
+            Ok(Some(Box::new(disk)))
+        }
+        CacheBackendKind::Redis => {
+            anyhow::ensure!(
+                katago.result_cache_dir.is_none(),
+                "result_cache_dir can't be combined with the redis cache backend"
+            );
+            Ok(Some(Box::new(RedisCache::new(&cache.redis)?)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stable_hash_is_fnv1a() {
+        assert_eq!(stable_hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(stable_hash("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_counts_sum_what_backends_know() {
+        let disk = BackendCounts {
+            hits: 2,
+            entries: Some(3),
+            bytes: Some(100),
+        };
+        let sum = BackendCounts::default() + disk + disk;
+        assert_eq!((sum.hits, sum.entries, sum.bytes), (4, Some(6), Some(200)));
+
+        let redis = BackendCounts {
+            hits: 1,
+            ..Default::default()
+        };
+        assert_eq!((redis + redis).entries, None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex as StdMutex;
+use tokio::sync::broadcast;
+
+/// Queries KataGo is searching now, keyed by the query's position/settings, so an
+/// identical request waits for the running search instead of sending its own.
+#[derive(Default)]
+pub struct InFlightQueries {
+    queries: StdMutex<HashMap<u64, InFlight>>,
+    /// Requests answered by another request's search
+    joined: AtomicU64,
+}
+
+struct InFlight {
+    /// Id of the query searching, so only its own guard unregisters it
+    leader: String,
+    priority: i32,
+    tx: broadcast::Sender<String>,
+}
+
+/// Registration of a running search; unregisters it when dropped, which tells
+/// the requests waiting on it to search themselves unless it was answered
+pub struct Leader<'a> {
+    queries: &'a InFlightQueries,
+    key: u64,
+    id: String,
+}
+
+impl InFlightQueries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for an identical query that is searching now; None when there is none,
+    /// it runs at a lower priority than `priority`, or it failed
+    pub async fn join(&self, key: u64, priority: i32) -> Option<String> {
+        let mut rx = {
+            let queries = self.queries.lock().unwrap();
+            let running = queries.get(&key)?;
+            // Waiting behind a background search would hold an urgent request back
+            if running.priority < priority {
+                return None;
+            }
+            running.tx.subscribe()
+        };
+        let response = rx.recv().await.ok()?;
+        self.joined.fetch_add(1, Ordering::Relaxed);
+        Some(response)
+    }
+
+    /// Register query `id` as searching under `key`; None if an identical one
+    /// already is (it was admitted alongside), in which case both just run
+    pub fn lead(&self, key: u64, id: &str, priority: i32) -> Option<Leader<'_>> {
+        let mut queries = self.queries.lock().unwrap();
+        if queries.contains_key(&key) {
+            return None;
+        }
+        queries.insert(
+            key,
+            InFlight {
+                leader: id.to_string(),
+                priority,
+                tx: broadcast::channel(1).0,
+            },
+        );
+        Some(Leader {
+            queries: self,
+            key,
+            id: id.to_string(),
+        })
+    }
+
+    /// Requests that were answered by an identical request's search
+    pub fn joined_count(&self) -> u64 {
+        self.joined.load(Ordering::Relaxed)
+    }
+}
+
+impl Leader<'_> {
+    /// Hand the search's response line to every request waiting on it
+    pub fn finish(self, response: &str) {
+        if let Some(running) = self.queries.queries.lock().unwrap().get(&self.key) {
+            // Nobody waiting is fine
+            let _ = running.tx.send(response.to_string());
+        }
+    }
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        let mut queries = self.queries.queries.lock().unwrap();
+        if queries.get(&self.key).is_some_and(|q| q.leader == self.id) {
+            queries.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_identical_request_gets_the_running_response() {
+        let queries = InFlightQueries::new();
+        assert_eq!(queries.join(1, 0).await, None);
+
+        let leader = queries.lead(1, "a", 0).unwrap();
+        assert!(queries.lead(1, "b", 0).is_none());
+        let (joined, ()) = tokio::join!(queries.join(1, 0), async {
+            tokio::task::yield_now().await;
+            leader.finish(r#"{"id":"a"}"#);
+        });
+        assert_eq!(joined.as_deref(), Some(r#"{"id":"a"}"#));
+        assert_eq!(queries.joined_count(), 1);
+
+        // Finished searches are not joined again
+        assert_eq!(queries.join(1, 0).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_failed_or_background_search_is_not_joined() {
+        let queries = InFlightQueries::new();
+        let leader = queries.lead(1, "a", -10).unwrap();
+        assert_eq!(queries.join(1, 0).await, None);
+
+        let (joined, ()) = tokio::join!(queries.join(1, -10), async {
+            tokio::task::yield_now().await;
+            drop(leader);
+        });
+        assert_eq!(joined, None);
+        assert_eq!(queries.joined_count(), 0);
+        assert!(queries.lead(1, "b", 0).is_some());
+    }
+}
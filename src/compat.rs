@@ -0,0 +1,239 @@
+use crate::api::AppState;
+use crate::engine_pool::EnginePool;
+use axum::{
+    body::Body,
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Map, Value};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, StreamExt};
+use tracing::debug;
+
+/// Response lines buffered ahead of a slow client
+const RESPONSE_BUFFER_LINES: usize = 64;
+
+/// POST /api/v1/katago/analysis
+///
+/// Accepts KataGo analysis-engine queries exactly as written to `katago analysis` stdin,
+/// one JSON object per line, and streams KataGo's response lines back as NDJSON in
+/// completion order, with the caller's ids.
+pub async fn katago_analysis(State(state): State<AppState>, body: String) -> Response {
+    let (tx, rx) = mpsc::channel(RESPONSE_BUFFER_LINES);
+    for line in body.lines().filter(|l| !l.trim().is_empty()) {
+        match parse_query(line) {
+            Ok(query) => {
+                tokio::spawn(run_query(state.engine.clone(), query, tx.clone()));
+            }
+            Err(error_line) => {
+                let _ = tx.send(error_line).await;
+            }
+        }
+    }
+    drop(tx);
+
+    let lines = ReceiverStream::new(rx)
+        .map(|mut line| {
+            line.push('\n');
+            line
+        })
+        .map(Ok::<_, Infallible>);
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response()
+}
+
+/// A query line as KataGo would see it, with its (caller-chosen) id
+#[derive(Debug)]
+struct NativeQuery {
+    id: Value,
+    fields: Map<String, Value>,
+}
+
+/// Parse one input line; malformed lines become KataGo-style error lines
+fn parse_query(line: &str) -> Result<NativeQuery, String> {
+    let value: Value = serde_json::from_str(line).map_err(|e| {
+        json!({ "error": format!("Could not parse input line as json request: {}", e) }).to_string()
+    })?;
+    let Value::Object(mut fields) = value else {
+        return Err(
+            json!({ "error": "Request line was valid json but was not an object" }).to_string(),
+        );
+    };
+    let id = fields
+        .remove("id")
+        .ok_or_else(|| json!({ "error": "Request did not specify an id" }).to_string())?;
+    Ok(NativeQuery { id, fields })
+}
+
+async fn run_query(engine: Arc<EnginePool>, query: NativeQuery, tx: mpsc::Sender<String>) {
+    let NativeQuery { id, mut fields } = query;
+
+    if let Some(action) = fields.get("action").and_then(Value::as_str) {
+        let line = match action {
+            "clear_cache" => match engine.clear_cache().await {
+                Ok(()) => json!({ "id": id, "action": "clear_cache" }),
+                Err(e) => json!({ "id": id, "error": e.to_string() }),
+            },
+            other => json!({
+                "id": id,
+                "error": format!("Action {} is not supported over HTTP", other),
+            }),
+        };
+        let _ = tx.send(line.to_string()).await;
+        return;
+    }
+
+    // Partial results would need several responses per query; only final ones are sent
+    if fields.remove("reportDuringSearchEvery").is_some() {
+        let warning = json!({
+            "id": id,
+            "field": "reportDuringSearchEvery",
+            "warning": "Ignored over HTTP: only final results are reported",
+        });
+        let _ = tx.send(warning.to_string()).await;
+    }
+
+    let size = |field: &str| {
+        fields
+            .get(field)
+            .and_then(Value::as_u64)
+            .and_then(|v| u8::try_from(v).ok())
+            .unwrap_or(19)
+    };
+    let target = match engine.route(size("boardXSize"), size("boardYSize")) {
+        Ok(target) => target.clone(),
+        Err(e) => {
+            let _ = tx
+                .send(json!({ "id": id, "error": e.to_string() }).to_string())
+                .await;
+            return;
+        }
+    };
+
+    // One engine query per analyzed turn, each answered by exactly one final response
+    let mut tasks = Vec::new();
+    for turn_query in split_turns(fields) {
+        let target = target.clone();
+        let tx = tx.clone();
+        let id = id.clone();
+        tasks.push(tokio::spawn(async move {
+            let lines = match target.analyze_native(Value::Object(turn_query)).await {
+                Ok(response) => native_lines(&response, &id),
+                Err(e) => vec![json!({ "id": id, "error": e.to_string() }).to_string()],
+            };
+            for line in lines {
+                if tx.send(line).await.is_err() {
+                    debug!("Client disconnected from native analysis stream");
+                    return;
+                }
+            }
+        }));
+    }
+    for task in tasks {
+        let _ = task.await;
+    }
+}
+
+/// Split a query with several analyzeTurns into one query per turn
+fn split_turns(fields: Map<String, Value>) -> Vec<Map<String, Value>> {
+    let turns = match fields.get("analyzeTurns") {
+        Some(Value::Array(turns)) if turns.len() > 1 => turns.clone(),
+        _ => return vec![fields],
+    };
+    turns
+        .into_iter()
+        .map(|turn| {
+            let mut single = fields.clone();
+            single.insert("analyzeTurns".to_string(), Value::Array(vec![turn]));
+            single
+        })
+        .collect()
+}
+
+/// KataGo's lines for a response with the caller's id in place of the internal one:
+/// warnings attached by the engine are sent as separate warning lines first, as
+/// KataGo does
+fn native_lines(response: &str, id: &Value) -> Vec<String> {
+    let Ok(Value::Object(mut object)) = serde_json::from_str::<Value>(response) else {
+        return vec![json!({ "id": id, "error": "Unparseable response from KataGo" }).to_string()];
+    };
+    let mut lines = Vec::new();
+    if let Some(Value::Array(warnings)) = object.remove("warnings") {
+        for warning in warnings {
+            let mut line = json!({ "id": id, "warning": warning["message"] });
+            if let Some(field) = warning.get("field") {
+                line["field"] = field.clone();
+            }
+            lines.push(line.to_string());
+        }
+    }
+    object.insert("id".to_string(), id.clone());
+    lines.push(Value::Object(object).to_string());
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query_errors_match_katago() {
+        let query = parse_query(r#"{"id":"q1","moves":[["B","D4"]],"maxVisits":10}"#).unwrap();
+        assert_eq!(query.id, json!("q1"));
+        assert!(!query.fields.contains_key("id"));
+
+        let bad = parse_query("not json").unwrap_err();
+        assert!(bad.contains("Could not parse input line as json request"));
+        let no_id = parse_query(r#"{"moves":[]}"#).unwrap_err();
+        assert!(no_id.contains("id"));
+    }
+
+    #[test]
+    fn test_split_turns() {
+        let fields = parse_query(r#"{"id":"q","analyzeTurns":[0,2,4]}"#)
+            .unwrap()
+            .fields;
+        let queries = split_turns(fields);
+        assert_eq!(queries.len(), 3);
+        assert_eq!(queries[1]["analyzeTurns"], json!([2]));
+
+        let single = parse_query(r#"{"id":"q","analyzeTurns":[3]}"#)
+            .unwrap()
+            .fields;
+        assert_eq!(split_turns(single).len(), 1);
+    }
+
+    #[test]
+    fn test_native_lines_restore_caller_id() {
+        let lines = native_lines(
+            r#"{"id":"internal-uuid","turnNumber":2,"moveInfos":[]}"#,
+            &json!("mine"),
+        );
+        assert_eq!(lines.len(), 1);
+        let value: Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(value["id"], "mine");
+        assert_eq!(value["turnNumber"], 2);
+    }
+
+    #[test]
+    fn test_native_lines_split_out_warnings() {
+        let lines = native_lines(
+            r#"{"id":"internal-uuid","turnNumber":0,"warnings":[{"field":"overrideSettings","message":"Unknown config params: x"}]}"#,
+            &json!("mine"),
+        );
+        assert_eq!(lines.len(), 2);
+        let warning: Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(
+            warning,
+            json!({"id": "mine", "field": "overrideSettings", "warning": "Unknown config params: x"})
+        );
+        let response: Value = serde_json::from_str(&lines[1]).unwrap();
+        assert!(response.get("warnings").is_none());
+    }
+}
@@ -1,7 +1,38 @@
+use crate::api::{AnalysisRequest, LossThresholds, Quality, Rules};
+use crate::error::KatagoError;
+use crate::processors::{default_processors, ProcessorConfig};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 
-#[derive(Debug, Clone, Deserialize)]
+/// Configuration file read at startup and by POST /api/v1/admin/config/reload
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// Settings a reload applies to the running server; changes to any other setting
+/// only take effect after a restart
+const LIVE_SETTINGS: &[&str] = &[
+    "katago.move_timeout_secs",
+    "katago.max_queued",
+    "katago.result_cache_entries",
+    "katago.result_cache_ttl_secs",
+    "subscriptions.max_visits",
+    "subscriptions.max_secs",
+    "subscriptions.max_active",
+    "review.winrate_loss.blunder",
+    "review.winrate_loss.mistake",
+    "review.winrate_loss.inaccuracy",
+    "review.score_loss.blunder",
+    "review.score_loss.mistake",
+    "review.score_loss.inaccuracy",
+    "analysis.default_max_visits",
+    "analysis.default_rules",
+    "analysis.default_komi",
+    "analysis.default_pv_len",
+    "analysis.max_allowed_visits",
+    "analysis.strict_validation",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct KatagoConfig {
     pub katago_path: String,
@@ -9,8 +40,64 @@ pub struct KatagoConfig {
     /// Optional path to human SL model for human-style analysis
     /// When set, KataGo is started with -human-model flag
     pub human_model_path: Option<String>,
+    /// Load the human model at startup; when false it stays unloaded (freeing VRAM)
+    /// until loaded via PUT /api/v1/admin/human-model
+    pub human_model_at_startup: bool,
     pub config_path: String,
     pub move_timeout_secs: u64,
+    /// Send periodic keepalive pings to KataGo
+    pub keepalive_enabled: bool,
+    /// Seconds between keepalive pings; also how often the process monitor
+    /// checks for a dead engine (even with keepalive disabled)
+    pub keepalive_interval_secs: u64,
+    /// Action sent as the keepalive ping
+    pub keepalive_action: KeepaliveAction,
+    /// Failed restarts in a row after which the monitor gives up on a dead
+    /// KataGo (0 = never give up)
+    pub max_restart_attempts: u64,
+    /// Seconds before the first restart of a dead KataGo; doubled for every
+    /// further attempt
+    pub restart_delay_secs: u64,
+    /// Longest delay between restart attempts
+    pub max_restart_delay_secs: u64,
+    /// Seconds a restarted KataGo must stay up before its restart attempts are
+    /// forgotten
+    pub restart_stable_secs: u64,
+    /// Board sizes this engine serves (e.g. [19]); unset serves any size not
+    /// claimed by an `[[engines]]` entry
+    pub board_sizes: Option<Vec<u8>>,
+    /// Milliseconds to collect queries before writing them to KataGo together,
+    /// so more positions share a neural network batch (0 = write immediately)
+    pub batch_window_ms: u64,
+    /// Queries sent to this engine's KataGo at once; more wait in the queue
+    pub max_in_flight: usize,
+    /// Queries that may wait for KataGo, highest priority first; beyond that,
+    /// requests are rejected with 429 Too Many Requests
+    pub max_queued: usize,
+    /// Completed analyses kept to answer repeated queries without searching
+    /// again (0 disables the cache)
+    pub result_cache_entries: usize,
+    /// Seconds a cached analysis is served
+    pub result_cache_ttl_secs: u64,
+    /// Directory keeping completed analyses across restarts, behind the
+    /// in-memory cache (unset keeps them in memory only)
+    pub result_cache_dir: Option<String>,
+    /// Megabytes of results kept in `result_cache_dir`; least recently used
+    /// ones are deleted beyond that
+    pub result_cache_dir_max_mb: u64,
+    /// Post-processing steps applied to every analysis result, in order
+    pub result_processors: Vec<ProcessorConfig>,
+    /// Named models (e.g. "strong", "fast") requests select with `model`; each
+    /// runs its own KataGo with the other settings of `[katago]`
+    pub models: BTreeMap<String, ModelConfig>,
+    /// GPUs this engine's KataGo runs on, one neural net server thread each
+    /// (e.g. [1] or [0, 1]); unset keeps the devices of `config_path`
+    pub gpu_devices: Option<Vec<u32>>,
+    /// KataGo build the `gpu_devices` indices are for
+    pub gpu_backend: GpuBackend,
+    /// KataGo config entries set for this engine only, over those of
+    /// `config_path` (passed as `-override-config`)
+    pub override_config: BTreeMap<String, String>,
 }
 
 impl Default for KatagoConfig {
@@ -19,17 +106,185 @@ impl Default for KatagoConfig {
             katago_path: "./katago".to_string(),
             model_path: "./model.bin.gz".to_string(),
             human_model_path: None,
+            human_model_at_startup: true,
             config_path: "./analysis_config.cfg".to_string(),
             move_timeout_secs: 20,
+            keepalive_enabled: true,
+            keepalive_interval_secs: 30,
+            keepalive_action: KeepaliveAction::QueryVersion,
+            max_restart_attempts: 5,
+            restart_delay_secs: 5,
+            max_restart_delay_secs: 300,
+            restart_stable_secs: 300,
+            board_sizes: None,
+            batch_window_ms: 0,
+            max_in_flight: 16,
+            max_queued: 64,
+            result_cache_entries: 1000,
+            result_cache_ttl_secs: 3600,
+            result_cache_dir: None,
+            result_cache_dir_max_mb: 1024,
+            result_processors: default_processors(),
+            models: BTreeMap::new(),
+            gpu_devices: None,
+            gpu_backend: GpuBackend::Cuda,
+            override_config: BTreeMap::new(),
+        }
+    }
+}
+
+impl KatagoConfig {
+    /// KataGo config entries this engine sets over its config file: the device
+    /// entries for `gpu_devices`, then `override_config`
+    pub fn config_overrides(&self) -> BTreeMap<String, String> {
+        let mut overrides = BTreeMap::new();
+        if let Some(devices) = &self.gpu_devices {
+            overrides.insert(
+                "numNNServerThreadsPerModel".to_string(),
+                devices.len().to_string(),
+            );
+            for (thread, device) in devices.iter().enumerate() {
+                overrides.insert(
+                    format!(
+                        "{}DeviceToUseThread{}",
+                        self.gpu_backend.key_prefix(),
+                        thread
+                    ),
+                    device.to_string(),
+                );
+            }
+        }
+        overrides.extend(self.override_config.clone());
+        overrides
+    }
+
+    /// Reject overrides KataGo's comma-separated `-override-config` can't carry
+    pub fn check_config_overrides(&self) -> anyhow::Result<()> {
+        if self.gpu_devices.as_ref().is_some_and(Vec::is_empty) {
+            anyhow::bail!("gpu_devices must list at least one device");
+        }
+        for (key, value) in &self.override_config {
+            anyhow::ensure!(
+                !key.is_empty() && !key.contains([',', '=']) && !value.contains(','),
+                "override_config entry {} = {:?} can't be passed to KataGo",
+                key,
+                value
+            );
+        }
+        Ok(())
+    }
+
+    /// The `-override-config` argument, if the engine sets any entries
+    pub fn override_config_arg(&self) -> Option<String> {
+        let overrides = self.config_overrides();
+        if overrides.is_empty() {
+            return None;
         }
+        let entries: Vec<String> = overrides
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        Some(entries.join(","))
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// KataGo GPU backend, which names its device settings differently
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GpuBackend {
+    Cuda,
+    Opencl,
+    Tensorrt,
+}
+
+impl GpuBackend {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "cuda" => Some(GpuBackend::Cuda),
+            "opencl" => Some(GpuBackend::Opencl),
+            "tensorrt" => Some(GpuBackend::Tensorrt),
+            _ => None,
+        }
+    }
+
+    /// Prefix of the backend's `...DeviceToUseThreadN` settings
+    fn key_prefix(self) -> &'static str {
+        match self {
+            GpuBackend::Cuda => "cuda",
+            GpuBackend::Opencl => "opencl",
+            GpuBackend::Tensorrt => "trt",
+        }
+    }
+}
+
+/// A model selectable per request, under `[katago.models.<name>]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelConfig {
+    pub model_path: String,
+    /// Defaults to the `[katago]` config file
+    #[serde(default)]
+    pub config_path: Option<String>,
+    /// Human SL model loaded alongside, for requests that also set `humanModel`
+    #[serde(default)]
+    pub human_model_path: Option<String>,
+    /// Defaults to the `[katago]` devices
+    #[serde(default)]
+    pub gpu_devices: Option<Vec<u32>>,
+    /// Entries over those of `[katago] override_config`
+    #[serde(default)]
+    pub override_config: BTreeMap<String, String>,
+}
+
+/// KataGo analysis action used as the keepalive ping payload
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeepaliveAction {
+    QueryVersion,
+    QueryModels,
+}
+
+impl KeepaliveAction {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "query_version" => Some(KeepaliveAction::QueryVersion),
+            "query_models" => Some(KeepaliveAction::QueryModels),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeepaliveAction::QueryVersion => "query_version",
+            KeepaliveAction::QueryModels => "query_models",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// "unix:<path>" listens on a Unix domain socket instead of host and port
+    pub listen: Option<String>,
+    /// Bearer token required by /api/v1/admin/* endpoints
+    /// Admin endpoints are disabled when unset
+    pub admin_token: Option<String>,
+    /// Debug mode: check JSON responses against the published schemas and turn
+    /// non-conforming ones into 500 errors
+    pub validate_responses: bool,
+    /// Serve Swagger UI for the OpenAPI document at /api/docs
+    pub swagger_ui: bool,
+    /// PEM certificate chain; with `tls_key_path`, the server speaks HTTPS
+    pub tls_cert_path: Option<String>,
+    /// PEM private key of the certificate
+    pub tls_key_path: Option<String>,
+    /// Seconds between checks for a renewed certificate or key
+    pub tls_reload_secs: u64,
+    /// Compress responses with gzip or brotli for clients that accept it
+    pub compression: bool,
+    /// Largest request body accepted; larger ones are answered with 413
+    pub max_body_bytes: usize,
 }
 
 impl Default for ServerConfig {
@@ -37,16 +292,554 @@ impl Default for ServerConfig {
         Self {
             host: "::".to_string(),
             port: 2718,
+            listen: None,
+            admin_token: None,
+            validate_responses: false,
+            swagger_ui: false,
+            tls_cert_path: None,
+            tls_key_path: None,
+            tls_reload_secs: 60,
+            compression: true,
+            max_body_bytes: 2 * 1024 * 1024,
         }
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+// Manual Debug so the admin token never ends up in logs
+impl std::fmt::Debug for ServerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("listen", &self.listen)
+            .field(
+                "admin_token",
+                &self.admin_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("validate_responses", &self.validate_responses)
+            .field("swagger_ui", &self.swagger_ui)
+            .field("tls_cert_path", &self.tls_cert_path)
+            .field("tls_key_path", &self.tls_key_path)
+            .field("tls_reload_secs", &self.tls_reload_secs)
+            .field("compression", &self.compression)
+            .field("max_body_bytes", &self.max_body_bytes)
+            .finish()
+    }
+}
+
+/// Budget for background refinement of subscribed positions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubscriptionConfig {
+    /// Visits at which refinement of a position stops
+    pub max_visits: u32,
+    /// Seconds after which refinement of a position stops
+    pub max_secs: u64,
+    /// Subscriptions refining at the same time, across all connections
+    pub max_active: usize,
+}
+
+impl Default for SubscriptionConfig {
+    fn default() -> Self {
+        Self {
+            max_visits: 10_000,
+            max_secs: 600,
+            max_active: 16,
+        }
+    }
+}
+
+/// KataGo in GTP mode, playing moves for /api/v1/game/*. Other settings are
+/// inherited from `[katago]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GtpConfig {
+    /// Start the GTP engine (it costs a second model in memory)
+    pub enabled: bool,
+    /// GTP config file (not the analysis config)
+    pub config_path: String,
+    /// Defaults to the `[katago]` model
+    pub model_path: Option<String>,
+}
+
+impl Default for GtpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            config_path: "./gtp_config.cfg".to_string(),
+            model_path: None,
+        }
+    }
+}
+
+impl GtpConfig {
+    /// Engine settings for the GTP process
+    pub fn katago_config(&self, katago: &KatagoConfig) -> KatagoConfig {
+        KatagoConfig {
+            config_path: self.config_path.clone(),
+            model_path: self
+                .model_path
+                .clone()
+                .unwrap_or_else(|| katago.model_path.clone()),
+            ..katago.clone()
+        }
+    }
+}
+
+/// Losses at which POST /api/v1/review tags a move as a blunder, mistake, or
+/// inaccuracy; a move gets the most severe tag either loss reaches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReviewConfig {
+    /// Winrate the move cost its player (0-1)
+    pub winrate_loss: LossThresholds,
+    /// Points the move cost its player
+    pub score_loss: LossThresholds,
+}
+
+impl Default for ReviewConfig {
+    fn default() -> Self {
+        Self {
+            winrate_loss: LossThresholds {
+                blunder: 0.2,
+                mistake: 0.1,
+                inaccuracy: 0.05,
+            },
+            score_loss: LossThresholds {
+                blunder: 5.0,
+                mistake: 2.5,
+                inaccuracy: 1.0,
+            },
+        }
+    }
+}
+
+/// Search settings for analysis requests that leave them out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisConfig {
+    pub default_max_visits: u32,
+    /// Unset guesses the rules from komi (japanese for 6.5 or whole komi, else chinese)
+    pub default_rules: Option<String>,
+    pub default_komi: f32,
+    /// Moves in each principal variation (KataGo's analysisPVLen); unset keeps
+    /// the KataGo config's
+    pub default_pv_len: Option<u32>,
+    /// Visits a request may ask for at most; larger requests are cut down to it
+    pub max_allowed_visits: Option<u32>,
+    /// Reject requests with moves that are not on the board (400 Invalid Moves)
+    /// or illegal under their rules (400 Illegal Move) unless they set
+    /// `strictValidation: false`; otherwise such moves are only logged and KataGo
+    /// returns an empty analysis
+    pub strict_validation: bool,
+    /// Named settings requests select with `profile` (e.g. "fast", "deep"), under
+    /// `[analysis.profiles.<name>]`
+    pub profiles: BTreeMap<String, AnalysisProfile>,
+}
+
+impl Default for AnalysisConfig {
+    fn default() -> Self {
+        Self {
+            default_max_visits: 10,
+            default_rules: None,
+            default_komi: 7.5,
+            default_pv_len: None,
+            max_allowed_visits: None,
+            strict_validation: false,
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+impl AnalysisConfig {
+    /// Fill in the settings of the profile a request names, before the defaults
+    pub fn apply_profile(&self, request: &mut AnalysisRequest) -> Result<(), KatagoError> {
+        let Some(name) = request.profile.take() else {
+            return Ok(());
+        };
+        let profile = self
+            .profiles
+            .get(&name)
+            .ok_or_else(|| KatagoError::UnknownProfile {
+                name,
+                available: self.profiles.keys().cloned().collect::<Vec<_>>().join(", "),
+            })?;
+        profile.apply(request);
+        Ok(())
+    }
+
+    /// Fill in visits, rules, komi, PV length, and strict validation the request
+    /// leaves unset (except maxVisits with a latency target), and cap its visits
+    /// at `max_allowed_visits`
+    pub fn apply(&self, request: &mut AnalysisRequest) {
+        // A latency target gets its visits from the engine's speed once routed
+        if request.target_latency_ms.is_none() {
+            request.max_visits.get_or_insert(self.default_max_visits);
+        }
+        if let (Some(visits), Some(max)) = (&mut request.max_visits, self.max_allowed_visits) {
+            *visits = (*visits).min(max);
+        }
+        request.komi.get_or_insert(self.default_komi);
+        if request.rules.is_none() {
+            request.rules = self.default_rules.as_deref().map(Rules::from);
+        }
+        if request.analysis_pv_len.is_none() {
+            request.analysis_pv_len = self.default_pv_len;
+        }
+        if self.strict_validation {
+            request.strict_validation.get_or_insert(true);
+        }
+    }
+}
+
+/// Analysis settings selected together by name with a request's `profile`; the
+/// request's own settings win
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisProfile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_visits: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_time_secs: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analysis_pv_len: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_ownership: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_ownership_stdev: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_moves_ownership: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_policy: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_pv_visits: Option<bool>,
+    /// `[katago.models]` entry to analyze with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Human SL model, by file name, when several are configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub human_model: Option<String>,
+    /// humanSLProfile sent in `overrideSettings` (e.g. "preaz_8k")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub human_sl_profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<Quality>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+}
+
+impl AnalysisProfile {
+    /// Fill in the settings the request leaves unset
+    pub fn apply(&self, request: &mut AnalysisRequest) {
+        fn fill<T: Clone>(field: &mut Option<T>, value: &Option<T>) {
+            if field.is_none() {
+                *field = value.clone();
+            }
+        }
+        fill(&mut request.max_visits, &self.max_visits);
+        fill(&mut request.max_time_secs, &self.max_time_secs);
+        fill(&mut request.analysis_pv_len, &self.analysis_pv_len);
+        fill(&mut request.include_ownership, &self.include_ownership);
+        fill(
+            &mut request.include_ownership_stdev,
+            &self.include_ownership_stdev,
+        );
+        fill(
+            &mut request.include_moves_ownership,
+            &self.include_moves_ownership,
+        );
+        fill(&mut request.include_policy, &self.include_policy);
+        fill(&mut request.include_pv_visits, &self.include_pv_visits);
+        fill(&mut request.model, &self.model);
+        fill(&mut request.human_model, &self.human_model);
+        fill(&mut request.quality, &self.quality);
+        fill(&mut request.priority, &self.priority);
+        if let Some(human_sl_profile) = &self.human_sl_profile {
+            let settings = request
+                .override_settings
+                .get_or_insert_with(|| serde_json::json!({}));
+            if let Some(settings) = settings.as_object_mut() {
+                settings
+                    .entry("humanSLProfile")
+                    .or_insert_with(|| human_sl_profile.clone().into());
+            }
+        }
+    }
+}
+
+/// Persistent record of completed analyses
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// SQLite database recording every completed analysis; unset keeps no history
+    pub database_path: Option<String>,
+}
+
+/// Append-only log of analysis requests, replayed by POST /api/v1/admin/replay
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// File every analysis request and its outcome is appended to, one JSON line
+    /// each; unset keeps no audit log
+    pub log_path: Option<String>,
+}
+
+/// Games played against the engine through /api/v1/play/sessions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayConfig {
+    /// Seconds a session may go unused before it is discarded
+    pub session_ttl_secs: u64,
+    /// Sessions kept at once; creating one more discards the least recently used
+    pub max_sessions: usize,
+}
+
+impl Default for PlayConfig {
+    fn default() -> Self {
+        Self {
+            session_ttl_secs: 3600,
+            max_sessions: 1000,
+        }
+    }
+}
+
+/// Background jobs of POST /api/v1/jobs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobsConfig {
+    /// SQLite database keeping jobs across restarts, so unfinished ones resume;
+    /// unset keeps jobs in memory only
+    pub database_path: Option<String>,
+    /// Runs of a job whose engine died or restarted under it before it fails
+    pub max_attempts: u32,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            database_path: None,
+            max_attempts: 3,
+        }
+    }
+}
+
+/// Games fetched from online-go.com for POST /api/v1/import/ogs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OgsConfig {
+    /// Allow fetching games from OGS; off by default so the server makes no
+    /// outbound requests unless asked to. False answers the endpoint with 404.
+    pub enabled: bool,
+    /// Base URL of the OGS REST API
+    pub api_url: String,
+    /// Seconds OGS has to send a game
+    pub timeout_secs: u64,
+}
+
+impl Default for OgsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            api_url: "https://online-go.com".to_string(),
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// OpenTelemetry export of request traces
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP collector receiving the traces, e.g. "http://localhost:4318";
+    /// unset exports nothing
+    pub otlp_endpoint: Option<String>,
+    /// service.name of the exported traces
+    pub service_name: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: "katago-server".to_string(),
+        }
+    }
+}
+
+/// A fast tier for cheap requests (e.g. hover previews), so they don't queue
+/// behind deep searches on the same engine
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TierConfig {
+    /// `[katago.models]` entry answering requests with `"quality": "fast"`;
+    /// unset sends them to the usual engine
+    pub fast_model: Option<String>,
+    /// Requests without `quality` go to the fast tier when their priority is
+    /// below this; unset routes by `quality` only
+    pub fast_below_priority: Option<i32>,
+}
+
+/// Where completed analyses are kept behind each engine's in-memory cache
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub backend: CacheBackendKind,
+    pub redis: RedisConfig,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackendKind {
+    /// This process only, plus `[katago] result_cache_dir` when set
+    #[default]
+    Memory,
+    /// A Redis server shared by every instance pointing at it
+    Redis,
+}
+
+/// Redis server of `backend = "redis"`
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedisConfig {
+    /// redis://host:port/db
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Prepended to every key, so several deployments can share a server
+    pub key_prefix: String,
+    /// Seconds Redis keeps a result (0 = until evicted by Redis)
+    pub ttl_secs: u64,
+    /// Milliseconds a Redis command may take before it counts as a miss
+    pub timeout_ms: u64,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        Self {
+            url: "redis://127.0.0.1:6379".to_string(),
+            username: None,
+            password: None,
+            key_prefix: "katago-server:".to_string(),
+            ttl_secs: 86400,
+            timeout_ms: 500,
+        }
+    }
+}
+
+impl std::fmt::Debug for RedisConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisConfig")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("key_prefix", &self.key_prefix)
+            .field("ttl_secs", &self.ttl_secs)
+            .field("timeout_ms", &self.timeout_ms)
+            .finish()
+    }
+}
+
+/// Callbacks of requests that set `callbackUrl`, answered with 202 and delivered
+/// when the result is ready
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Key of the HMAC-SHA256 signature of every callback; requests with a
+    /// `callbackUrl` are rejected while it is unset
+    pub secret: Option<String>,
+    /// Deliveries tried before a callback is dropped
+    pub max_attempts: u32,
+    /// Milliseconds before the first retry; doubled for each further retry
+    pub retry_backoff_ms: u64,
+    /// Seconds a callback URL has to answer a delivery
+    pub timeout_secs: u64,
+    /// Hosts (names or addresses) callbacks may reach even though they are
+    /// loopback, link-local, or private; all other callbacks must go to public
+    /// addresses
+    pub allowed_hosts: Vec<String>,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            secret: None,
+            max_attempts: 5,
+            retry_backoff_ms: 1000,
+            timeout_secs: 10,
+            allowed_hosts: Vec::new(),
+        }
+    }
+}
+
+impl std::fmt::Debug for WebhookConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookConfig")
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .field("max_attempts", &self.max_attempts)
+            .field("retry_backoff_ms", &self.retry_backoff_ms)
+            .field("timeout_secs", &self.timeout_secs)
+            .field("allowed_hosts", &self.allowed_hosts)
+            .finish()
+    }
+}
+
+/// An additional engine (e.g. a small-board net) serving specific board sizes.
+/// Settings not listed here are inherited from `[katago]`, except the human model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineRouteConfig {
+    pub name: String,
+    pub model_path: String,
+    #[serde(default)]
+    pub config_path: Option<String>,
+    /// Human SL model for requests selecting it by file name with `humanModel`
+    #[serde(default)]
+    pub human_model_path: Option<String>,
+    /// Square board sizes routed to this engine; may be left empty for an engine
+    /// with a human model, which then only answers requests selecting that model
+    #[serde(default)]
+    pub board_sizes: Vec<u8>,
+    /// Defaults to the `[katago]` devices
+    #[serde(default)]
+    pub gpu_devices: Option<Vec<u32>>,
+    /// Entries over those of `[katago] override_config`
+    #[serde(default)]
+    pub override_config: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
     #[serde(default)]
     pub server: ServerConfig,
     #[serde(default)]
     pub katago: KatagoConfig,
+    #[serde(default)]
+    pub engines: Vec<EngineRouteConfig>,
+    #[serde(default)]
+    pub subscriptions: SubscriptionConfig,
+    #[serde(default)]
+    pub gtp: GtpConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub play: PlayConfig,
+    #[serde(default)]
+    pub review: ReviewConfig,
+    #[serde(default)]
+    pub analysis: AnalysisConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub tiers: TierConfig,
+    #[serde(default)]
+    pub webhooks: WebhookConfig,
+    #[serde(default)]
+    pub jobs: JobsConfig,
+    #[serde(default)]
+    pub ogs: OgsConfig,
 }
 
 impl Config {
@@ -67,6 +860,43 @@ impl Config {
                 self.server.port = p;
             }
         }
+        if let Ok(listen) = std::env::var("KATAGO_SERVER_LISTEN") {
+            self.server.listen = Some(listen);
+        }
+        if let Ok(token) = std::env::var("KATAGO_ADMIN_TOKEN") {
+            self.server.admin_token = Some(token);
+        }
+        if let Ok(validate) = std::env::var("KATAGO_VALIDATE_RESPONSES") {
+            if let Ok(v) = validate.parse() {
+                self.server.validate_responses = v;
+            }
+        }
+        if let Ok(swagger_ui) = std::env::var("KATAGO_SWAGGER_UI") {
+            if let Ok(v) = swagger_ui.parse() {
+                self.server.swagger_ui = v;
+            }
+        }
+        if let Ok(compression) = std::env::var("KATAGO_COMPRESSION") {
+            if let Ok(c) = compression.parse() {
+                self.server.compression = c;
+            }
+        }
+        if let Ok(bytes) = std::env::var("KATAGO_MAX_BODY_BYTES") {
+            if let Ok(b) = bytes.parse() {
+                self.server.max_body_bytes = b;
+            }
+        }
+        if let Ok(path) = std::env::var("KATAGO_TLS_CERT_PATH") {
+            self.server.tls_cert_path = Some(path);
+        }
+        if let Ok(path) = std::env::var("KATAGO_TLS_KEY_PATH") {
+            self.server.tls_key_path = Some(path);
+        }
+        if let Ok(secs) = std::env::var("KATAGO_TLS_RELOAD_SECS") {
+            if let Ok(s) = secs.parse() {
+                self.server.tls_reload_secs = s;
+            }
+        }
         if let Ok(path) = std::env::var("KATAGO_KATAGO_PATH") {
             self.katago.katago_path = path;
         }
@@ -76,6 +906,11 @@ impl Config {
         if let Ok(path) = std::env::var("KATAGO_HUMAN_MODEL_PATH") {
             self.katago.human_model_path = Some(path);
         }
+        if let Ok(at_startup) = std::env::var("KATAGO_HUMAN_MODEL_AT_STARTUP") {
+            if let Ok(a) = at_startup.parse() {
+                self.katago.human_model_at_startup = a;
+            }
+        }
         if let Ok(path) = std::env::var("KATAGO_CONFIG_PATH") {
             self.katago.config_path = path;
         }
@@ -84,6 +919,245 @@ impl Config {
                 self.katago.move_timeout_secs = t;
             }
         }
+        if let Ok(enabled) = std::env::var("KATAGO_KEEPALIVE_ENABLED") {
+            if let Ok(e) = enabled.parse() {
+                self.katago.keepalive_enabled = e;
+            }
+        }
+        if let Ok(interval) = std::env::var("KATAGO_KEEPALIVE_INTERVAL_SECS") {
+            if let Ok(i) = interval.parse() {
+                self.katago.keepalive_interval_secs = i;
+            }
+        }
+        if let Ok(action) = std::env::var("KATAGO_KEEPALIVE_ACTION") {
+            if let Some(a) = KeepaliveAction::parse(&action) {
+                self.katago.keepalive_action = a;
+            }
+        }
+        if let Ok(max) = std::env::var("KATAGO_MAX_RESTART_ATTEMPTS") {
+            if let Ok(m) = max.parse() {
+                self.katago.max_restart_attempts = m;
+            }
+        }
+        if let Ok(delay) = std::env::var("KATAGO_RESTART_DELAY_SECS") {
+            if let Ok(d) = delay.parse() {
+                self.katago.restart_delay_secs = d;
+            }
+        }
+        if let Ok(delay) = std::env::var("KATAGO_MAX_RESTART_DELAY_SECS") {
+            if let Ok(d) = delay.parse() {
+                self.katago.max_restart_delay_secs = d;
+            }
+        }
+        if let Ok(stable) = std::env::var("KATAGO_RESTART_STABLE_SECS") {
+            if let Ok(s) = stable.parse() {
+                self.katago.restart_stable_secs = s;
+            }
+        }
+        if let Ok(window) = std::env::var("KATAGO_BATCH_WINDOW_MS") {
+            if let Ok(w) = window.parse() {
+                self.katago.batch_window_ms = w;
+            }
+        }
+        if let Ok(max) = std::env::var("KATAGO_MAX_IN_FLIGHT") {
+            if let Ok(m) = max.parse() {
+                self.katago.max_in_flight = m;
+            }
+        }
+        if let Ok(max) = std::env::var("KATAGO_MAX_QUEUED") {
+            if let Ok(m) = max.parse() {
+                self.katago.max_queued = m;
+            }
+        }
+        if let Ok(entries) = std::env::var("KATAGO_RESULT_CACHE_ENTRIES") {
+            if let Ok(e) = entries.parse() {
+                self.katago.result_cache_entries = e;
+            }
+        }
+        if let Ok(ttl) = std::env::var("KATAGO_RESULT_CACHE_TTL_SECS") {
+            if let Ok(t) = ttl.parse() {
+                self.katago.result_cache_ttl_secs = t;
+            }
+        }
+        if let Ok(devices) = std::env::var("KATAGO_GPU_DEVICES") {
+            let parsed: Result<Vec<u32>, _> =
+                devices.split(',').map(|d| d.trim().parse()).collect();
+            if let Ok(d) = parsed {
+                self.katago.gpu_devices = Some(d);
+            }
+        }
+        if let Ok(backend) = std::env::var("KATAGO_GPU_BACKEND") {
+            if let Some(b) = GpuBackend::parse(&backend) {
+                self.katago.gpu_backend = b;
+            }
+        }
+        if let Ok(dir) = std::env::var("KATAGO_RESULT_CACHE_DIR") {
+            self.katago.result_cache_dir = Some(dir);
+        }
+        if let Ok(max) = std::env::var("KATAGO_RESULT_CACHE_DIR_MAX_MB") {
+            if let Ok(m) = max.parse() {
+                self.katago.result_cache_dir_max_mb = m;
+            }
+        }
+        if let Ok(visits) = std::env::var("KATAGO_SUBSCRIPTION_MAX_VISITS") {
+            if let Ok(v) = visits.parse() {
+                self.subscriptions.max_visits = v;
+            }
+        }
+        if let Ok(secs) = std::env::var("KATAGO_SUBSCRIPTION_MAX_SECS") {
+            if let Ok(s) = secs.parse() {
+                self.subscriptions.max_secs = s;
+            }
+        }
+        if let Ok(active) = std::env::var("KATAGO_MAX_SUBSCRIPTIONS") {
+            if let Ok(a) = active.parse() {
+                self.subscriptions.max_active = a;
+            }
+        }
+        if let Ok(enabled) = std::env::var("KATAGO_GTP_ENABLED") {
+            if let Ok(e) = enabled.parse() {
+                self.gtp.enabled = e;
+            }
+        }
+        if let Ok(path) = std::env::var("KATAGO_GTP_CONFIG_PATH") {
+            self.gtp.config_path = path;
+        }
+        if let Ok(path) = std::env::var("KATAGO_HISTORY_DATABASE_PATH") {
+            self.history.database_path = Some(path);
+        }
+        if let Ok(path) = std::env::var("KATAGO_AUDIT_LOG_PATH") {
+            self.audit.log_path = Some(path);
+        }
+        if let Ok(ttl) = std::env::var("KATAGO_PLAY_SESSION_TTL_SECS") {
+            if let Ok(t) = ttl.parse() {
+                self.play.session_ttl_secs = t;
+            }
+        }
+        if let Ok(max) = std::env::var("KATAGO_MAX_PLAY_SESSIONS") {
+            if let Ok(m) = max.parse() {
+                self.play.max_sessions = m;
+            }
+        }
+        if let Ok(path) = std::env::var("KATAGO_JOBS_DATABASE_PATH") {
+            self.jobs.database_path = Some(path);
+        }
+        if let Ok(enabled) = std::env::var("KATAGO_OGS_ENABLED") {
+            if let Ok(e) = enabled.parse() {
+                self.ogs.enabled = e;
+            }
+        }
+        if let Ok(url) = std::env::var("KATAGO_OGS_API_URL") {
+            self.ogs.api_url = url;
+        }
+        if let Ok(visits) = std::env::var("KATAGO_DEFAULT_MAX_VISITS") {
+            if let Ok(v) = visits.parse() {
+                self.analysis.default_max_visits = v;
+            }
+        }
+        if let Ok(rules) = std::env::var("KATAGO_DEFAULT_RULES") {
+            self.analysis.default_rules = Some(rules);
+        }
+        if let Ok(komi) = std::env::var("KATAGO_DEFAULT_KOMI") {
+            if let Ok(k) = komi.parse() {
+                self.analysis.default_komi = k;
+            }
+        }
+        if let Ok(len) = std::env::var("KATAGO_DEFAULT_PV_LEN") {
+            if let Ok(l) = len.parse() {
+                self.analysis.default_pv_len = Some(l);
+            }
+        }
+        if let Ok(strict) = std::env::var("KATAGO_STRICT_VALIDATION") {
+            if let Ok(s) = strict.parse() {
+                self.analysis.strict_validation = s;
+            }
+        }
+        if let Ok(visits) = std::env::var("KATAGO_MAX_ALLOWED_VISITS") {
+            if let Ok(v) = visits.parse() {
+                self.analysis.max_allowed_visits = Some(v);
+            }
+        }
+        if let Ok(endpoint) = std::env::var("KATAGO_OTLP_ENDPOINT") {
+            self.telemetry.otlp_endpoint = Some(endpoint);
+        }
+        if let Ok(name) = std::env::var("KATAGO_OTLP_SERVICE_NAME") {
+            self.telemetry.service_name = name;
+        }
+        if let Ok(secret) = std::env::var("KATAGO_WEBHOOK_SECRET") {
+            self.webhooks.secret = Some(secret);
+        }
+        if let Ok(hosts) = std::env::var("KATAGO_WEBHOOK_ALLOWED_HOSTS") {
+            self.webhooks.allowed_hosts = hosts
+                .split(',')
+                .map(|host| host.trim().to_string())
+                .filter(|host| !host.is_empty())
+                .collect();
+        }
+        if let Ok(model) = std::env::var("KATAGO_FAST_MODEL") {
+            self.tiers.fast_model = Some(model);
+        }
+        if let Ok(priority) = std::env::var("KATAGO_FAST_BELOW_PRIORITY") {
+            if let Ok(p) = priority.parse() {
+                self.tiers.fast_below_priority = Some(p);
+            }
+        }
+        if let Ok(backend) = std::env::var("KATAGO_CACHE_BACKEND") {
+            match backend.as_str() {
+                "memory" => self.cache.backend = CacheBackendKind::Memory,
+                "redis" => self.cache.backend = CacheBackendKind::Redis,
+                _ => {}
+            }
+        }
+        if let Ok(url) = std::env::var("KATAGO_REDIS_URL") {
+            self.cache.redis.url = url;
+        }
+        if let Ok(username) = std::env::var("KATAGO_REDIS_USERNAME") {
+            self.cache.redis.username = Some(username);
+        }
+        if let Ok(password) = std::env::var("KATAGO_REDIS_PASSWORD") {
+            self.cache.redis.password = Some(password);
+        }
+        if let Ok(prefix) = std::env::var("KATAGO_REDIS_KEY_PREFIX") {
+            self.cache.redis.key_prefix = prefix;
+        }
+        if let Ok(ttl) = std::env::var("KATAGO_REDIS_TTL_SECS") {
+            if let Ok(t) = ttl.parse() {
+                self.cache.redis.ttl_secs = t;
+            }
+        }
+    }
+
+    /// Take over the live settings of a reloaded configuration. Returns the changed
+    /// settings, split into the ones now in effect and the ones needing a restart
+    /// (which stay as they were, so later reloads still report them).
+    pub fn reload(&mut self, reloaded: &Config) -> ReloadChanges {
+        let (current, new) = (settings(self), settings(reloaded));
+        let mut changes = ReloadChanges::default();
+        for (name, value) in &new {
+            if current.get(name) == Some(value) {
+                continue;
+            }
+            if is_live(name) {
+                changes.applied.push(name.clone());
+            } else {
+                changes.restart_required.push(name.clone());
+            }
+        }
+        changes.restart_required.extend(
+            current
+                .keys()
+                .filter(|name| !new.contains_key(*name) && !is_live(name))
+                .cloned(),
+        );
+
+        self.katago.move_timeout_secs = reloaded.katago.move_timeout_secs;
+        self.katago.max_queued = reloaded.katago.max_queued;
+        self.katago.result_cache_entries = reloaded.katago.result_cache_entries;
+        self.katago.result_cache_ttl_secs = reloaded.katago.result_cache_ttl_secs;
+        self.subscriptions = reloaded.subscriptions.clone();
+        self.review = reloaded.review.clone();
+        self.analysis = reloaded.analysis.clone();
+        changes
     }
 
     #[allow(dead_code)] // Used in tests and for standalone env-only config loading
@@ -94,8 +1168,54 @@ impl Config {
     }
 }
 
+/// Whether a reload applies a setting to the running server
+fn is_live(name: &str) -> bool {
+    LIVE_SETTINGS.contains(&name) || name.starts_with("analysis.profiles.")
+}
+
+/// Settings changed by a configuration reload, as dotted names (e.g.
+/// "katago.move_timeout_secs")
+#[derive(Debug, Default, PartialEq)]
+pub struct ReloadChanges {
+    pub applied: Vec<String>,
+    pub restart_required: Vec<String>,
+}
+
+/// Every setting of a configuration by dotted name; lists (such as `engines`)
+/// count as one setting
+fn settings(config: &Config) -> BTreeMap<String, serde_json::Value> {
+    fn flatten(
+        prefix: &str,
+        value: serde_json::Value,
+        out: &mut BTreeMap<String, serde_json::Value>,
+    ) {
+        match value {
+            serde_json::Value::Object(fields) => {
+                for (name, value) in fields {
+                    let name = if prefix.is_empty() {
+                        name
+                    } else {
+                        format!("{}.{}", prefix, name)
+                    };
+                    flatten(&name, value, out);
+                }
+            }
+            value => {
+                out.insert(prefix.to_string(), value);
+            }
+        }
+    }
+    let mut out = BTreeMap::new();
+    flatten(
+        "",
+        serde_json::to_value(config).unwrap_or_default(),
+        &mut out,
+    );
+    out
+}
+
+/// Game settings of a GTP bot request
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[allow(dead_code)] // Kept for potential future GTP mode support
 pub struct RequestConfig {
     #[serde(default)]
     pub komi: Option<f32>,
@@ -127,6 +1247,19 @@ mod tests {
         let config = ServerConfig::default();
         assert_eq!(config.host, "::");
         assert_eq!(config.port, 2718);
+        assert!(config.admin_token.is_none());
+        assert!(!config.validate_responses);
+    }
+
+    #[test]
+    fn test_server_config_debug_redacts_admin_token() {
+        let config = ServerConfig {
+            admin_token: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains("s3cret"));
+        assert!(debug.contains("<redacted>"));
     }
 
     #[test]
@@ -135,8 +1268,71 @@ mod tests {
         assert_eq!(config.katago_path, "./katago");
         assert_eq!(config.model_path, "./model.bin.gz");
         assert!(config.human_model_path.is_none());
+        assert!(config.human_model_at_startup);
         assert_eq!(config.config_path, "./analysis_config.cfg");
         assert_eq!(config.move_timeout_secs, 20);
+        assert!(config.keepalive_enabled);
+        assert_eq!(config.keepalive_interval_secs, 30);
+        assert_eq!(config.keepalive_action, KeepaliveAction::QueryVersion);
+        assert_eq!(config.max_restart_attempts, 5);
+        assert_eq!(config.restart_delay_secs, 5);
+        assert_eq!(config.max_restart_delay_secs, 300);
+        assert_eq!(config.restart_stable_secs, 300);
+        assert_eq!(config.batch_window_ms, 0);
+        assert_eq!(config.max_in_flight, 16);
+        assert_eq!(config.max_queued, 64);
+        assert!(config.result_cache_dir.is_none());
+        assert_eq!(config.result_cache_dir_max_mb, 1024);
+    }
+
+    #[test]
+    fn test_gtp_config_inherits_engine_settings() {
+        let katago = KatagoConfig {
+            move_timeout_secs: 5,
+            ..Default::default()
+        };
+        let gtp = GtpConfig::default().katago_config(&katago);
+        assert_eq!(gtp.config_path, "./gtp_config.cfg");
+        assert_eq!(gtp.model_path, katago.model_path);
+        assert_eq!(gtp.move_timeout_secs, 5);
+    }
+
+    #[test]
+    fn test_reload_applies_live_settings_only() {
+        let mut running = Config::default();
+        let reloaded: Config = toml::from_str(
+            r#"
+            [katago]
+            move_timeout_secs = 60
+            model_path = "./bigger.bin.gz"
+
+            [subscriptions]
+            max_active = 4
+
+            [analysis.profiles.fast]
+            max_visits = 50
+            "#,
+        )
+        .unwrap();
+
+        let changes = running.reload(&reloaded);
+        assert_eq!(
+            changes.applied,
+            [
+                "analysis.profiles.fast.max_visits",
+                "katago.move_timeout_secs",
+                "subscriptions.max_active"
+            ]
+        );
+        assert_eq!(changes.restart_required, ["katago.model_path"]);
+        assert_eq!(running.katago.move_timeout_secs, 60);
+        assert_eq!(running.subscriptions.max_active, 4);
+        assert_eq!(running.katago.model_path, "./model.bin.gz");
+
+        // The pending restart is still reported; the applied change is not
+        let changes = running.reload(&reloaded);
+        assert!(changes.applied.is_empty());
+        assert_eq!(changes.restart_required, ["katago.model_path"]);
     }
 
     #[test]
@@ -218,4 +1414,271 @@ model_path = "/custom/model.bin.gz"
         assert_eq!(config.katago.model_path, "/custom/model.bin.gz");
         assert_eq!(config.katago.katago_path, "./katago"); // default
     }
+
+    #[test]
+    fn test_engine_routes_toml() {
+        let toml_str = r#"
+[katago]
+model_path = "/models/b18.bin.gz"
+board_sizes = [19]
+
+[[engines]]
+name = "small"
+model_path = "/models/b10-9x9.bin.gz"
+board_sizes = [9, 13]
+"#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.katago.board_sizes, Some(vec![19]));
+        assert_eq!(config.engines.len(), 1);
+        assert_eq!(config.engines[0].name, "small");
+        assert_eq!(config.engines[0].board_sizes, vec![9, 13]);
+        assert!(config.engines[0].config_path.is_none());
+        assert!(config.engines[0].human_model_path.is_none());
+        assert!(Config::default().engines.is_empty());
+    }
+
+    #[test]
+    fn test_named_models_toml() {
+        let toml_str = r#"
+[katago]
+model_path = "/models/b28.bin.gz"
+
+[katago.models.fast]
+model_path = "/models/b10.bin.gz"
+
+[katago.models.human]
+model_path = "/models/b18.bin.gz"
+human_model_path = "/models/b18-humanv0.bin.gz"
+"#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let names: Vec<&str> = config.katago.models.keys().map(String::as_str).collect();
+        assert_eq!(names, ["fast", "human"]);
+        assert_eq!(
+            config.katago.models["fast"].model_path,
+            "/models/b10.bin.gz"
+        );
+        assert!(config.katago.models["fast"].config_path.is_none());
+        assert!(config.katago.models["human"].human_model_path.is_some());
+        assert!(KatagoConfig::default().models.is_empty());
+    }
+
+    #[test]
+    fn test_analysis_defaults_fill_in_unset_fields() {
+        let toml_str = r#"
+[analysis]
+default_max_visits = 500
+default_rules = "japanese"
+default_komi = 6.5
+default_pv_len = 20
+max_allowed_visits = 2000
+"#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+
+        let mut request = AnalysisRequest::default();
+        config.analysis.apply(&mut request);
+        assert_eq!(request.max_visits, Some(500));
+        assert_eq!(request.rules, Some("japanese".into()));
+        assert_eq!(request.komi, Some(6.5));
+        assert_eq!(request.analysis_pv_len, Some(20));
+
+        let mut request = AnalysisRequest {
+            max_visits: Some(10_000),
+            komi: Some(0.5),
+            analysis_pv_len: Some(5),
+            ..Default::default()
+        };
+        config.analysis.apply(&mut request);
+        assert_eq!(request.max_visits, Some(2000));
+        assert_eq!(request.komi, Some(0.5));
+        assert_eq!(request.analysis_pv_len, Some(5));
+
+        // Visits of a latency target are budgeted once the engine is known
+        let mut request = AnalysisRequest {
+            target_latency_ms: Some(500),
+            ..Default::default()
+        };
+        config.analysis.apply(&mut request);
+        assert_eq!(request.max_visits, None);
+    }
+
+    #[test]
+    fn test_analysis_profiles_fill_in_unset_fields() {
+        let toml_str = r#"
+[analysis.profiles.deep]
+max_visits = 5000
+include_ownership = true
+include_policy = true
+
+[analysis.profiles.teach]
+human_model = "b18c384nbt-humanv0.bin.gz"
+human_sl_profile = "preaz_8k"
+"#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+
+        let mut request = AnalysisRequest {
+            profile: Some("deep".to_string()),
+            include_policy: Some(false),
+            ..Default::default()
+        };
+        config.analysis.apply_profile(&mut request).unwrap();
+        assert_eq!(request.profile, None);
+        assert_eq!(request.max_visits, Some(5000));
+        assert_eq!(request.include_ownership, Some(true));
+        assert_eq!(request.include_policy, Some(false));
+
+        let mut request = AnalysisRequest {
+            profile: Some("teach".to_string()),
+            override_settings: Some(serde_json::json!({ "rootNumSymmetriesToSample": 2 })),
+            ..Default::default()
+        };
+        config.analysis.apply_profile(&mut request).unwrap();
+        assert_eq!(
+            request.human_model.as_deref(),
+            Some("b18c384nbt-humanv0.bin.gz")
+        );
+        assert_eq!(
+            request.override_settings,
+            Some(serde_json::json!({
+                "rootNumSymmetriesToSample": 2,
+                "humanSLProfile": "preaz_8k"
+            }))
+        );
+
+        let mut request = AnalysisRequest {
+            profile: Some("quick".to_string()),
+            ..Default::default()
+        };
+        let error = config.analysis.apply_profile(&mut request).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No analysis profile named 'quick' is configured (available: deep, teach)"
+        );
+    }
+
+    #[test]
+    fn test_keepalive_toml() {
+        let toml_str = r#"
+[katago]
+keepalive_enabled = false
+keepalive_interval_secs = 120
+keepalive_action = "query_models"
+"#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert!(!config.katago.keepalive_enabled);
+        assert_eq!(config.katago.keepalive_interval_secs, 120);
+        assert_eq!(config.katago.keepalive_action, KeepaliveAction::QueryModels);
+        assert_eq!(
+            KeepaliveAction::parse("query_models"),
+            Some(KeepaliveAction::QueryModels)
+        );
+        assert_eq!(KeepaliveAction::parse("ping"), None);
+    }
+
+    #[test]
+    fn test_human_model_deferred_toml() {
+        let toml_str = r#"
+[katago]
+human_model_path = "/models/human.bin.gz"
+human_model_at_startup = false
+"#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            config.katago.human_model_path.as_deref(),
+            Some("/models/human.bin.gz")
+        );
+        assert!(!config.katago.human_model_at_startup);
+    }
+
+    #[test]
+    fn test_subscriptions_toml() {
+        let toml_str = r#"
+[subscriptions]
+max_visits = 2000
+"#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.subscriptions.max_visits, 2000);
+        assert_eq!(config.subscriptions.max_secs, 600); // default
+        assert_eq!(config.subscriptions.max_active, 16); // default
+    }
+
+    #[test]
+    fn test_redis_cache_toml() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.cache.backend, CacheBackendKind::Memory);
+
+        let toml_str = r#"
+[cache]
+backend = "redis"
+
+[cache.redis]
+url = "redis://cache.internal:6380/2"
+password = "s3cret"
+"#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.cache.backend, CacheBackendKind::Redis);
+        assert_eq!(config.cache.redis.url, "redis://cache.internal:6380/2");
+        assert_eq!(config.cache.redis.key_prefix, "katago-server:"); // default
+        assert_eq!(config.cache.redis.ttl_secs, 86400); // default
+        let debug = format!("{:?}", config.cache);
+        assert!(!debug.contains("s3cret"));
+    }
+
+    #[test]
+    fn test_gpu_devices_become_config_overrides() {
+        let toml_str = r#"
+[katago]
+gpu_devices = [0, 1]
+override_config = { nnMaxBatchSize = "32" }
+
+[[engines]]
+name = "small"
+model_path = "./small.bin.gz"
+board_sizes = [9]
+gpu_devices = [2]
+override_config = { nnMaxBatchSize = "8" }
+"#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.katago.gpu_backend, GpuBackend::Cuda);
+        assert_eq!(
+            config.katago.override_config_arg().as_deref(),
+            Some(
+                "cudaDeviceToUseThread0=0,cudaDeviceToUseThread1=1,\
+                 nnMaxBatchSize=32,numNNServerThreadsPerModel=2"
+            )
+        );
+        assert_eq!(config.engines[0].gpu_devices, Some(vec![2]));
+        assert!(KatagoConfig::default().override_config_arg().is_none());
+
+        let opencl = KatagoConfig {
+            gpu_devices: Some(vec![3]),
+            gpu_backend: GpuBackend::Opencl,
+            // An explicit entry wins over the one gpu_devices implies
+            override_config: BTreeMap::from([(
+                "numNNServerThreadsPerModel".to_string(),
+                "2".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let overrides = opencl.config_overrides();
+        assert_eq!(overrides["openclDeviceToUseThread0"], "3");
+        assert_eq!(overrides["numNNServerThreadsPerModel"], "2");
+    }
+
+    #[test]
+    fn test_config_overrides_are_checked() {
+        let mut katago = KatagoConfig {
+            gpu_devices: Some(vec![]),
+            ..Default::default()
+        };
+        assert!(katago.check_config_overrides().is_err());
+        katago.gpu_devices = None;
+        katago
+            .override_config
+            .insert("logDir".to_string(), "a,b".to_string());
+        assert!(katago.check_config_overrides().is_err());
+        katago
+            .override_config
+            .insert("logDir".to_string(), "logs".to_string());
+        assert!(katago.check_config_overrides().is_ok());
+    }
 }
@@ -0,0 +1,383 @@
+use crate::analysis_engine::{EngineErrorRecord, ProtocolStatsSnapshot};
+use crate::api::{AnalysisRequest, AnalysisResponse, AppState, MoveInput};
+use axum::{extract::State, Form};
+use maud::{html, Markup, DOCTYPE};
+use serde::Deserialize;
+
+/// Visit cap for console analyses unless the operator asks for more
+const DEFAULT_CONSOLE_VISITS: u32 = 200;
+
+/// Number of candidate moves shown in the console result table
+const CONSOLE_TOP_MOVES: usize = 10;
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2rem; max-width: 60rem; color: #222; }
+h1 { font-size: 1.4rem; } h2 { font-size: 1.1rem; margin-top: 2rem; }
+table { border-collapse: collapse; } td, th { padding: 0.2rem 0.8rem; border-bottom: 1px solid #ddd; text-align: left; }
+.ok { color: #17803d; } .bad { color: #b42318; }
+input[type=text] { width: 30rem; } label { display: block; margin: 0.4rem 0; }
+";
+
+/// Quick-analysis form posted from the console
+#[derive(Debug, Default, Deserialize)]
+pub struct ConsoleAnalysisForm {
+    /// Moves separated by spaces or commas, e.g. "D4 Q16 C3"
+    #[serde(default)]
+    pub moves: String,
+    #[serde(default)]
+    pub komi: Option<f32>,
+    #[serde(default)]
+    pub board_size: Option<u8>,
+    #[serde(default)]
+    pub max_visits: Option<u32>,
+}
+
+impl ConsoleAnalysisForm {
+    fn to_request(&self) -> AnalysisRequest {
+        let size = self.board_size.unwrap_or(19);
+        AnalysisRequest {
+            moves: parse_moves(&self.moves),
+            komi: self.komi,
+            board_x_size: size,
+            board_y_size: size,
+            max_visits: Some(self.max_visits.unwrap_or(DEFAULT_CONSOLE_VISITS)),
+            ..Default::default()
+        }
+    }
+}
+
+fn parse_moves(input: &str) -> Vec<MoveInput> {
+    input
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|m| !m.is_empty())
+        .map(|m| MoveInput::Simple(m.to_uppercase()))
+        .collect()
+}
+
+/// Snapshot of server state shown at the top of the console
+pub struct ConsoleStatus {
+    /// One entry per configured engine, in configuration order
+    pub engines: Vec<EngineConsoleStatus>,
+    pub archived_games: usize,
+}
+
+/// Process state of one engine of the pool
+pub struct EngineConsoleStatus {
+    pub name: String,
+    /// Whether the KataGo process is running
+    pub alive: bool,
+    pub pending_queries: usize,
+    /// Queries waiting for a place in KataGo
+    pub queued_queries: usize,
+    pub model: String,
+    pub protocol: ProtocolStatsSnapshot,
+    /// Newest first
+    pub recent_errors: Vec<EngineErrorRecord>,
+}
+
+impl ConsoleStatus {
+    fn collect(state: &AppState) -> Self {
+        let engines = state
+            .engine
+            .named()
+            .map(|(name, engine)| EngineConsoleStatus {
+                name: name.to_string(),
+                alive: engine.is_alive(),
+                pending_queries: engine.pending_count(),
+                queued_queries: engine.queued_count(),
+                model: std::path::Path::new(&engine.model_path())
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("unknown")
+                    .to_string(),
+                protocol: engine.protocol_stats(),
+                recent_errors: engine.recent_errors(),
+            })
+            .collect();
+        Self {
+            engines,
+            archived_games: state.games.len(),
+        }
+    }
+
+    /// Every engine's recent errors with the engine's name, newest first
+    fn recent_errors(&self) -> Vec<(&str, &EngineErrorRecord)> {
+        let mut errors: Vec<_> = self
+            .engines
+            .iter()
+            .flat_map(|engine| {
+                engine
+                    .recent_errors
+                    .iter()
+                    .map(move |record| (engine.name.as_str(), record))
+            })
+            .collect();
+        // RFC 3339 timestamps in UTC sort chronologically as text
+        errors.sort_by(|a, b| b.1.timestamp.cmp(&a.1.timestamp));
+        errors
+    }
+}
+
+/// GET /console
+pub async fn console(State(state): State<AppState>) -> Markup {
+    render_page(
+        &ConsoleStatus::collect(&state),
+        &ConsoleAnalysisForm::default(),
+        None,
+    )
+}
+
+/// POST /console/analyze
+pub async fn console_analyze(
+    State(state): State<AppState>,
+    Form(form): Form<ConsoleAnalysisForm>,
+) -> Markup {
+    let result = state
+        .engine
+        .analyze(&form.to_request())
+        .await
+        .map_err(|e| e.to_string());
+    render_page(&ConsoleStatus::collect(&state), &form, Some(&result))
+}
+
+fn render_page(
+    status: &ConsoleStatus,
+    form: &ConsoleAnalysisForm,
+    result: Option<&Result<AnalysisResponse, String>>,
+) -> Markup {
+    html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                title { "KataGo Server Console" }
+                style { (STYLE) }
+            }
+            body {
+                h1 { "KataGo Server Console" }
+                table {
+                    tr { th { "Engine" } th { "Status" } th { "Queries in flight" } th { "Queries queued" } th { "Model" } }
+                    @for engine in &status.engines {
+                        tr {
+                            td { (engine.name) }
+                            td {
+                                @if engine.alive {
+                                    span.ok { "running" }
+                                } @else {
+                                    span.bad { "down" }
+                                }
+                            }
+                            td { (engine.pending_queries) }
+                            td { (engine.queued_queries) }
+                            td { (engine.model) }
+                        }
+                    }
+                }
+                p { "Archived games: " (status.archived_games) }
+
+                h2 { "Engine protocol" }
+                table {
+                    tr {
+                        th {}
+                        @for engine in &status.engines { th { (engine.name) } }
+                    }
+                    (protocol_row(status, "Queries sent", |p| p.queries_sent.to_string()))
+                    (protocol_row(status, "Responses received", |p| p.responses_received.to_string()))
+                    (protocol_row(status, "Action responses", |p| p.action_responses.to_string()))
+                    (protocol_row(status, "Unknown-id responses", |p| p.unknown_id_responses.to_string()))
+                    (protocol_row(status, "Unparseable lines", |p| p.parse_failures.to_string()))
+                    (protocol_row(status, "Query warnings", |p| p.warning_lines.to_string()))
+                    (protocol_row(status, "Failed writes (engine running)", |p| p.write_failures.to_string()))
+                    @if status.engines.iter().any(|engine| engine.protocol.batches_written > 0) {
+                        (protocol_row(status, "Batches (average / largest size)", |p| {
+                            let average = p.batched_queries as f64 / p.batches_written.max(1) as f64;
+                            format!("{} ({:.1} / {})", p.batches_written, average, p.largest_batch)
+                        }))
+                    }
+                    (protocol_row(status, "Bytes written / read", |p| format!("{} / {}", p.bytes_written, p.bytes_read)))
+                }
+
+                h2 { "Quick analysis" }
+                form method="post" action="/console/analyze" {
+                    label { "Moves " input type="text" name="moves" value=(form.moves) placeholder="D4 Q16 C3"; }
+                    label { "Komi " input type="number" step="0.5" name="komi" value=[form.komi]; }
+                    label { "Board size " input type="number" min="2" max="25" name="board_size" value=(form.board_size.unwrap_or(19)); }
+                    label { "Max visits " input type="number" min="1" name="max_visits" value=(form.max_visits.unwrap_or(DEFAULT_CONSOLE_VISITS)); }
+                    button type="submit" { "Analyze" }
+                }
+                @match result {
+                    Some(Ok(response)) => (render_analysis(response)),
+                    Some(Err(message)) => p.bad { "Analysis failed: " (message) },
+                    None => {}
+                }
+
+                h2 { "Recent errors" }
+                @let errors = status.recent_errors();
+                @if errors.is_empty() {
+                    p { "None" }
+                } @else {
+                    table {
+                        tr { th { "Time" } th { "Engine" } th { "Request" } th { "Error" } }
+                        @for (engine, record) in errors {
+                            tr { td { (record.timestamp) } td { (engine) } td { (record.request_id) } td { (record.message) } }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// One protocol counter, a column per engine
+fn protocol_row(
+    status: &ConsoleStatus,
+    label: &str,
+    value: impl Fn(&ProtocolStatsSnapshot) -> String,
+) -> Markup {
+    html! {
+        tr {
+            th { (label) }
+            @for engine in &status.engines { td { (value(&engine.protocol)) } }
+        }
+    }
+}
+
+fn render_analysis(response: &AnalysisResponse) -> Markup {
+    html! {
+        @if let Some(root) = &response.root_info {
+            p {
+                (root.current_player) " to move: winrate "
+                (format!("{:.1}%", root.winrate * 100.0))
+                ", score lead " (format!("{:+.1}", root.score_lead))
+                " (" (root.visits) " visits)"
+            }
+        }
+        @if let Some(moves) = &response.move_infos {
+            table {
+                tr { th { "Move" } th { "Visits" } th { "Winrate" } th { "Score lead" } th { "Prior" } th { "PV" } }
+                @for info in moves.iter().take(CONSOLE_TOP_MOVES) {
+                    tr {
+                        td { (info.move_coord) }
+                        td { (info.visits) }
+                        td { (format!("{:.1}%", info.winrate * 100.0)) }
+                        td { (format!("{:+.1}", info.score_lead)) }
+                        td { (format!("{:.1}%", info.prior * 100.0)) }
+                        td { (info.pv.as_deref().unwrap_or_default().join(" ")) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(name: &str, alive: bool, errors: &[(&str, &str)]) -> EngineConsoleStatus {
+        EngineConsoleStatus {
+            name: name.to_string(),
+            alive,
+            pending_queries: 3,
+            queued_queries: 0,
+            model: format!("{}.bin.gz", name),
+            protocol: ProtocolStatsSnapshot {
+                bytes_written: 0,
+                bytes_read: 0,
+                queries_sent: 0,
+                responses_received: 0,
+                action_responses: 0,
+                unknown_id_responses: 7,
+                parse_failures: 0,
+                warning_lines: 0,
+                batches_written: 0,
+                batched_queries: 0,
+                largest_batch: 0,
+                write_failures: 0,
+            },
+            recent_errors: errors
+                .iter()
+                .map(|(timestamp, message)| EngineErrorRecord {
+                    timestamp: timestamp.to_string(),
+                    request_id: "req-1".to_string(),
+                    message: message.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    fn status() -> ConsoleStatus {
+        ConsoleStatus {
+            engines: vec![engine(
+                "kata1",
+                false,
+                &[("2025-01-01T00:00:00Z", "Command timeout after 20 seconds")],
+            )],
+            archived_games: 12,
+        }
+    }
+
+    #[test]
+    fn test_parse_moves() {
+        let moves = parse_moves("d4, Q16\nc3  ");
+        let coords: Vec<&str> = moves.iter().map(|m| m.coord()).collect();
+        assert_eq!(coords, vec!["D4", "Q16", "C3"]);
+    }
+
+    #[test]
+    fn test_form_defaults() {
+        let request = ConsoleAnalysisForm {
+            moves: "D4".to_string(),
+            board_size: Some(9),
+            ..Default::default()
+        }
+        .to_request();
+        assert_eq!(request.board_x_size, 9);
+        assert_eq!(request.board_y_size, 9);
+        assert_eq!(request.max_visits, Some(DEFAULT_CONSOLE_VISITS));
+    }
+
+    #[test]
+    fn test_render_page_shows_status_and_errors() {
+        let page = render_page(&status(), &ConsoleAnalysisForm::default(), None).into_string();
+        assert!(page.contains("down"));
+        assert!(page.contains("kata1.bin.gz"));
+        assert!(page.contains("<td>7</td>"));
+        assert!(page.contains("Command timeout after 20 seconds"));
+    }
+
+    #[test]
+    fn test_render_page_shows_every_engine() {
+        let status = ConsoleStatus {
+            engines: vec![
+                engine("main", true, &[("2025-01-01T00:00:00Z", "first failure")]),
+                engine(
+                    "small",
+                    false,
+                    &[("2025-01-02T00:00:00Z", "second failure")],
+                ),
+            ],
+            archived_games: 0,
+        };
+        let page = render_page(&status, &ConsoleAnalysisForm::default(), None).into_string();
+        assert!(page.contains("main.bin.gz") && page.contains("small.bin.gz"));
+        assert!(page.contains("running") && page.contains("down"));
+        let (first, second) = (
+            page.find("first failure").unwrap(),
+            page.find("second failure").unwrap(),
+        );
+        assert!(second < first, "errors are newest first across engines");
+    }
+
+    #[test]
+    fn test_render_page_escapes_input() {
+        let form = ConsoleAnalysisForm {
+            moves: "<script>".to_string(),
+            ..Default::default()
+        };
+        let failed = Err("bad <move>".to_string());
+        let page = render_page(&status(), &form, Some(&failed)).into_string();
+        assert!(!page.contains("<script>"));
+        assert!(page.contains("bad &lt;move&gt;"));
+    }
+}
@@ -0,0 +1,289 @@
+use crate::cache_backend::{stable_hash, BackendCounts, BoxFuture, CacheBackend};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+/// Completed analyses kept as files in a directory, so they survive restarts. Each
+/// file is named by a stable hash of its key and holds the key on its first line
+/// (to tell hash collisions apart), then the raw KataGo response line. Least
+/// recently used files are deleted once the directory exceeds its size limit.
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    state: Mutex<DiskState>,
+    hits: AtomicU64,
+}
+
+struct DiskState {
+    /// File hash -> (file size, last use)
+    entries: HashMap<u64, (u64, u64)>,
+    /// Last use -> file hash, oldest first
+    recency: BTreeMap<u64, u64>,
+    next_use: u64,
+    bytes: u64,
+}
+
+impl DiskState {
+    fn touch(&mut self, hash: u64, size: u64) {
+        if let Some((old_size, last_use)) = self.entries.remove(&hash) {
+            self.recency.remove(&last_use);
+            self.bytes -= old_size;
+        }
+        self.entries.insert(hash, (size, self.next_use));
+        self.recency.insert(self.next_use, hash);
+        self.next_use += 1;
+        self.bytes += size;
+    }
+
+    fn forget(&mut self, hash: u64) {
+        if let Some((size, last_use)) = self.entries.remove(&hash) {
+            self.recency.remove(&last_use);
+            self.bytes -= size;
+        }
+    }
+}
+
+fn file_hash(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?.strip_suffix(".json")?;
+    u64::from_str_radix(name, 16).ok()
+}
+
+impl DiskCache {
+    /// Open (creating it if needed) a cache directory of up to `max_bytes`,
+    /// indexing the files already there by modification time
+    pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let mut files: Vec<(SystemTime, u64, u64)> = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "tmp") {
+                // Left behind by a write that was interrupted
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            let Some(hash) = file_hash(&path) else {
+                continue;
+            };
+            let metadata = fs::metadata(&path)?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((modified, hash, metadata.len()));
+        }
+        files.sort();
+
+        let mut state = DiskState {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            next_use: 0,
+            bytes: 0,
+        };
+        for (_, hash, size) in files {
+            state.touch(hash, size);
+        }
+        let cache = Self {
+            dir,
+            max_bytes,
+            state: Mutex::new(state),
+            hits: AtomicU64::new(0),
+        };
+        cache.evict(&mut cache.state.lock().unwrap());
+        {
+            let state = cache.state.lock().unwrap();
+            info!(
+                "Opened result cache directory {} ({} results, {} bytes)",
+                cache.dir.display(),
+                state.entries.len(),
+                state.bytes
+            );
+        }
+        Ok(cache)
+    }
+
+    fn path(&self, hash: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", hash))
+    }
+
+    /// Cached response for `key`, counting a hit
+    pub fn get(&self, key: &str) -> Option<String> {
+        let hash = stable_hash(key);
+        let mut state = self.state.lock().unwrap();
+        let size = state.entries.get(&hash)?.0;
+        let contents = match fs::read_to_string(self.path(hash)) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("Failed to read cached result {:016x}: {}", hash, e);
+                state.forget(hash);
+                return None;
+            }
+        };
+        let (stored_key, response) = contents.split_once('\n')?;
+        if stored_key != key {
+            return None;
+        }
+        state.touch(hash, size);
+        // Recency survives a restart as the file's modification time
+        let _ = fs::File::options()
+            .write(true)
+            .open(self.path(hash))
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(response.to_string())
+    }
+
+    /// Store a successful response under `key`, deleting the least recently used
+    /// files beyond the size limit. Failures are logged; the cache is best-effort.
+    pub fn insert(&self, key: &str, response: &str) {
+        let hash = stable_hash(key);
+        let contents = format!("{}\n{}", key, response);
+        let size = contents.len() as u64;
+        if size > self.max_bytes {
+            return;
+        }
+        // Written aside and renamed, so a crash never leaves a truncated result
+        let path = self.path(hash);
+        let tmp = path.with_extension("tmp");
+        if let Err(e) = fs::write(&tmp, contents).and_then(|()| fs::rename(&tmp, &path)) {
+            warn!("Failed to write cached result {}: {}", path.display(), e);
+            let _ = fs::remove_file(&tmp);
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.touch(hash, size);
+        self.evict(&mut state);
+    }
+
+    fn evict(&self, state: &mut DiskState) {
+        while state.bytes > self.max_bytes {
+            let Some((_, oldest)) = state.recency.pop_first() else {
+                break;
+            };
+            if let Some((size, _)) = state.entries.remove(&oldest) {
+                state.bytes -= size;
+            }
+            if let Err(e) = fs::remove_file(self.path(oldest)) {
+                warn!("Failed to evict cached result {:016x}: {}", oldest, e);
+            }
+        }
+    }
+
+    /// Delete every cached result
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        for &hash in state.entries.keys() {
+            let _ = fs::remove_file(self.path(hash));
+        }
+        state.entries.clear();
+        state.recency.clear();
+        state.bytes = 0;
+    }
+
+    pub fn counts(&self) -> BackendCounts {
+        let state = self.state.lock().unwrap();
+        BackendCounts {
+            hits: self.hits.load(Ordering::Relaxed),
+            entries: Some(state.entries.len()),
+            bytes: Some(state.bytes),
+        }
+    }
+}
+
+impl CacheBackend for DiskCache {
+    fn kind(&self) -> &'static str {
+        "disk"
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<String>> {
+        Box::pin(std::future::ready(DiskCache::get(self, key)))
+    }
+
+    fn insert<'a>(&'a self, key: &'a str, response: &'a str) -> BoxFuture<'a, ()> {
+        DiskCache::insert(self, key, response);
+        Box::pin(std::future::ready(()))
+    }
+
+    fn clear(&self) -> BoxFuture<'_, ()> {
+        DiskCache::clear(self);
+        Box::pin(std::future::ready(()))
+    }
+
+    fn counts(&self) -> BackendCounts {
+        DiskCache::counts(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "katago-disk-cache-{}-{}",
+            name,
+            uuid::Uuid::new_v4()
+        ))
+    }
+
+    #[test]
+    fn test_results_survive_reopening() {
+        let dir = temp_dir("reopen");
+        let cache = DiskCache::open(&dir, 1 << 20).unwrap();
+        assert!(cache.get("a").is_none());
+        cache.insert("a", r#"{"id":"1"}"#);
+        assert_eq!(cache.get("a").as_deref(), Some(r#"{"id":"1"}"#));
+        drop(cache);
+
+        let cache = DiskCache::open(&dir, 1 << 20).unwrap();
+        assert_eq!(cache.get("a").as_deref(), Some(r#"{"id":"1"}"#));
+        assert_eq!(
+            cache.counts(),
+            BackendCounts {
+                hits: 1,
+                entries: Some(1),
+                bytes: Some(12)
+            }
+        );
+
+        cache.clear();
+        assert!(cache.get("a").is_none());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_least_recently_used_files_are_evicted_by_size() {
+        let dir = temp_dir("evict");
+        // Room for two entries of 1 + 1 + 8 bytes
+        let cache = DiskCache::open(&dir, 20).unwrap();
+        cache.insert("a", "response");
+        cache.insert("b", "response");
+        assert!(cache.get("a").is_some());
+        cache.insert("c", "response");
+
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some() && cache.get("c").is_some());
+        assert_eq!(cache.counts().bytes, Some(20));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // Reopening with a smaller limit evicts the oldest files
+        drop(cache);
+        let cache = DiskCache::open(&dir, 10).unwrap();
+        assert_eq!(cache.counts().entries, Some(1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_hash_collisions_are_not_served() {
+        let dir = temp_dir("collision");
+        let cache = DiskCache::open(&dir, 1 << 20).unwrap();
+        cache.insert("a", "response");
+        // Another key's file under the same name is not this key's result
+        fs::write(cache.path(stable_hash("a")), "b\nresponse").unwrap();
+        assert!(cache.get("a").is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,556 @@
+use crate::analysis_engine::AnalysisEngine;
+use crate::api::{AnalysisRequest, AnalysisResponse, Quality};
+use crate::cache_backend::{self, BackendCounts};
+use crate::config::{AnalysisConfig, Config, KatagoConfig, TierConfig};
+use crate::error::{KatagoError, Result};
+use crate::legality;
+use crate::result_cache::CacheCounts;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+/// Wait before warming up an engine again after a failed attempt
+const WARM_UP_RETRY: Duration = Duration::from_secs(5);
+
+/// `[katago] override_config` with an engine's own entries over it
+fn merged_overrides(
+    katago: &KatagoConfig,
+    own: &BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    let mut merged = katago.override_config.clone();
+    merged.extend(own.clone());
+    merged
+}
+
+/// An engine and the board sizes it serves (None = any size; empty = only requests
+/// selecting its model or human model, at any size)
+struct PooledEngine {
+    name: String,
+    board_sizes: Option<Vec<u8>>,
+    /// Name under `[katago.models]` for engines requests select with `model`
+    model: Option<String>,
+    engine: Arc<AnalysisEngine>,
+}
+
+/// The configured analysis engines, routed by board size or model name.
+/// The first engine is the primary one from `[katago]`.
+pub struct EnginePool {
+    engines: Vec<PooledEngine>,
+    /// `[analysis]` defaults applied to every request before it is routed
+    defaults: StdMutex<AnalysisConfig>,
+    tiers: TierConfig,
+}
+
+impl EnginePool {
+    /// Start the primary engine, every `[[engines]]` entry, and every
+    /// `[katago.models]` entry
+    pub fn start(config: &Config) -> anyhow::Result<Self> {
+        let start_engine = |katago: KatagoConfig| -> anyhow::Result<Arc<AnalysisEngine>> {
+            katago.check_config_overrides()?;
+            let backend = cache_backend::build(&katago, &config.cache)?;
+            Ok(Arc::new(AnalysisEngine::new(katago, backend)?))
+        };
+        let mut engines = vec![PooledEngine {
+            name: "default".to_string(),
+            board_sizes: config.katago.board_sizes.clone(),
+            model: None,
+            engine: start_engine(config.katago.clone())?,
+        }];
+        for route in &config.engines {
+            anyhow::ensure!(
+                !route.board_sizes.is_empty() || route.human_model_path.is_some(),
+                "Engine '{}' must list at least one board size or a human model",
+                route.name
+            );
+            info!(
+                "Starting engine '{}' for board sizes {:?} (human model: {:?})",
+                route.name, route.board_sizes, route.human_model_path
+            );
+            let engine_config = KatagoConfig {
+                model_path: route.model_path.clone(),
+                config_path: route
+                    .config_path
+                    .clone()
+                    .unwrap_or_else(|| config.katago.config_path.clone()),
+                // The human model costs VRAM; engines only load one they list
+                human_model_path: route.human_model_path.clone(),
+                // Only the primary engine's human model can be loaded later
+                human_model_at_startup: true,
+                board_sizes: Some(route.board_sizes.clone()),
+                gpu_devices: route
+                    .gpu_devices
+                    .clone()
+                    .or_else(|| config.katago.gpu_devices.clone()),
+                override_config: merged_overrides(&config.katago, &route.override_config),
+                ..config.katago.clone()
+            };
+            engines.push(PooledEngine {
+                name: route.name.clone(),
+                board_sizes: Some(route.board_sizes.clone()),
+                model: None,
+                engine: start_engine(engine_config)?,
+            });
+        }
+        for (name, model) in &config.katago.models {
+            anyhow::ensure!(
+                engines.iter().all(|e| e.name != *name),
+                "Model '{}' has the name of an engine",
+                name
+            );
+            info!(
+                "Starting engine for model '{}' ({})",
+                name, model.model_path
+            );
+            let engine_config = KatagoConfig {
+                model_path: model.model_path.clone(),
+                config_path: model
+                    .config_path
+                    .clone()
+                    .unwrap_or_else(|| config.katago.config_path.clone()),
+                human_model_path: model.human_model_path.clone(),
+                human_model_at_startup: true,
+                board_sizes: Some(Vec::new()),
+                gpu_devices: model
+                    .gpu_devices
+                    .clone()
+                    .or_else(|| config.katago.gpu_devices.clone()),
+                override_config: merged_overrides(&config.katago, &model.override_config),
+                ..config.katago.clone()
+            };
+            engines.push(PooledEngine {
+                name: name.clone(),
+                board_sizes: Some(Vec::new()),
+                model: Some(name.clone()),
+                engine: start_engine(engine_config)?,
+            });
+        }
+        if let Some(fast) = &config.tiers.fast_model {
+            anyhow::ensure!(
+                config.katago.models.contains_key(fast),
+                "[tiers] fast_model '{}' is not a [katago.models] entry",
+                fast
+            );
+        }
+        Ok(Self {
+            engines,
+            defaults: StdMutex::new(config.analysis.clone()),
+            tiers: config.tiers.clone(),
+        })
+    }
+
+    /// The engine from `[katago]`
+    pub fn primary(&self) -> &Arc<AnalysisEngine> {
+        &self.engines[0].engine
+    }
+
+    /// Engine for a board: one that lists the size explicitly, else the first
+    /// unrestricted engine. Rectangular boards only go to unrestricted engines.
+    pub fn route(&self, width: u8, height: u8) -> Result<&Arc<AnalysisEngine>> {
+        route_index(
+            self.engines.iter().map(|e| e.board_sizes.as_deref()),
+            width,
+            height,
+        )
+        .map(|i| &self.engines[i].engine)
+        .ok_or_else(|| KatagoError::UnsupportedBoardSize {
+            width,
+            height,
+            supported: self.supported_sizes(),
+        })
+    }
+
+    /// Engine for a board that has the named human model loaded
+    pub fn route_human(&self, width: u8, height: u8, name: &str) -> Result<&Arc<AnalysisEngine>> {
+        self.engines
+            .iter()
+            .find(|e| {
+                e.engine.loaded_human_model().as_deref() == Some(name)
+                    && serves_human(e.board_sizes.as_deref(), width, height)
+            })
+            .map(|e| &e.engine)
+            .ok_or_else(|| KatagoError::UnavailableHumanModel {
+                name: name.to_string(),
+                width,
+                height,
+                available: self.human_models().join(", "),
+            })
+    }
+
+    /// Engine of a `[katago.models]` entry, which must have the human model if
+    /// one is named too
+    pub fn route_model(
+        &self,
+        name: &str,
+        human_model: Option<&str>,
+        width: u8,
+        height: u8,
+    ) -> Result<&Arc<AnalysisEngine>> {
+        let engine = self
+            .engines
+            .iter()
+            .find(|e| e.model.as_deref() == Some(name))
+            .map(|e| &e.engine)
+            .ok_or_else(|| KatagoError::UnknownModel {
+                name: name.to_string(),
+                available: self.models().join(", "),
+            })?;
+        match human_model {
+            Some(human) if engine.loaded_human_model().as_deref() != Some(human) => {
+                Err(KatagoError::UnavailableHumanModel {
+                    name: human.to_string(),
+                    width,
+                    height,
+                    available: self.human_models().join(", "),
+                })
+            }
+            _ => Ok(engine),
+        }
+    }
+
+    /// Engine for a request: by model if it names one, else by human model if it
+    /// names one, else the fast model for fast-tier requests, else by board size
+    pub fn route_request(&self, request: &AnalysisRequest) -> Result<&Arc<AnalysisEngine>> {
+        let (width, height) = (request.board_x_size, request.board_y_size);
+        match (&request.model, &request.human_model) {
+            (Some(model), human) => self.route_model(model, human.as_deref(), width, height),
+            (None, Some(name)) => self.route_human(width, height, name),
+            (None, None) => match fast_model(&self.tiers, request.quality, request.priority) {
+                Some(model) => self.route_model(model, None, width, height),
+                None => self.route(width, height),
+            },
+        }
+    }
+
+    /// The request with the `[analysis]` defaults filled in
+    pub fn with_defaults(&self, request: &AnalysisRequest) -> AnalysisRequest {
+        let mut request = request.clone();
+        self.defaults.lock().unwrap().apply(&mut request);
+        request
+    }
+
+    /// The request with its profile and the defaults, checked by strict
+    /// validation if it asks for it: moves off the board, then a replay for
+    /// illegal moves
+    fn prepare(&self, request: &AnalysisRequest) -> Result<AnalysisRequest> {
+        let mut request = request.clone();
+        self.defaults.lock().unwrap().apply_profile(&mut request)?;
+        let request = self.with_defaults(&request);
+        if request.strict_validation == Some(true) {
+            let moves = AnalysisEngine::invalid_moves(&request);
+            if !moves.is_empty() {
+                return Err(KatagoError::InvalidMoves {
+                    width: request.board_x_size,
+                    height: request.board_y_size,
+                    moves,
+                });
+            }
+            legality::check(&request)?;
+        }
+        Ok(request)
+    }
+
+    /// Set maxVisits of a request with a latency target from the speed of the
+    /// engine it was routed to (the `[analysis]` default until that is measured),
+    /// and maxTime to the target as a backstop
+    fn budget_visits(&self, engine: &AnalysisEngine, request: &mut AnalysisRequest) {
+        let Some(target_ms) = request.target_latency_ms else {
+            return;
+        };
+        let target = Duration::from_millis(target_ms);
+        let defaults = self.defaults.lock().unwrap();
+        let budget = engine
+            .visit_budget(target)
+            .unwrap_or(defaults.default_max_visits);
+        let visits = request.max_visits.map_or(budget, |max| max.min(budget));
+        request.max_visits = Some(
+            defaults
+                .max_allowed_visits
+                .map_or(visits, |max| visits.min(max)),
+        );
+        request.max_time_secs.get_or_insert(target.as_secs_f32());
+    }
+
+    pub async fn analyze(&self, request: &AnalysisRequest) -> Result<AnalysisResponse> {
+        let mut request = self.prepare(request)?;
+        let engine = self.route_request(&request)?;
+        self.budget_visits(engine, &mut request);
+        engine.analyze(&request).await
+    }
+
+    /// See [`AnalysisEngine::reanalyze`]
+    pub async fn reanalyze(&self, request: &AnalysisRequest) -> Result<AnalysisResponse> {
+        let mut request = self.prepare(request)?;
+        let engine = self.route_request(&request)?;
+        self.budget_visits(engine, &mut request);
+        engine.reanalyze(&request).await
+    }
+
+    /// See [`AnalysisEngine::analyze_turns`]
+    pub async fn analyze_turns(&self, request: &AnalysisRequest) -> Result<Vec<AnalysisResponse>> {
+        let mut request = self.prepare(request)?;
+        let engine = self.route_request(&request)?;
+        self.budget_visits(engine, &mut request);
+        engine.analyze_turns(&request).await
+    }
+
+    /// See [`AnalysisEngine::analyze_partial`]
+    pub async fn analyze_partial(
+        &self,
+        request: &AnalysisRequest,
+        report_every: f32,
+        results: mpsc::Sender<AnalysisResponse>,
+    ) -> Result<()> {
+        let mut request = self.prepare(request)?;
+        let engine = self.route_request(&request)?;
+        self.budget_visits(engine, &mut request);
+        engine
+            .analyze_partial(&request, report_every, results)
+            .await
+    }
+
+    /// Names of the `[katago.models]` entries
+    pub fn models(&self) -> Vec<String> {
+        self.engines
+            .iter()
+            .filter_map(|e| e.model.clone())
+            .collect()
+    }
+
+    /// Names of the loaded human models
+    pub fn human_models(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .engines
+            .iter()
+            .filter_map(|e| e.engine.loaded_human_model())
+            .collect();
+        names.dedup();
+        names
+    }
+
+    /// Whether every engine's process is running
+    pub fn is_alive(&self) -> bool {
+        self.engines.iter().all(|e| e.engine.is_alive())
+    }
+
+    /// Every engine with its name and the smallest board size it serves
+    pub fn smallest_boards(&self) -> impl Iterator<Item = (&str, u8, &Arc<AnalysisEngine>)> {
+        self.engines.iter().map(|e| {
+            let size = e
+                .board_sizes
+                .as_deref()
+                .and_then(|sizes| sizes.iter().min().copied())
+                .unwrap_or(9);
+            (e.name.as_str(), size, &e.engine)
+        })
+    }
+
+    /// Every engine with its name, the primary first
+    pub fn named(&self) -> impl Iterator<Item = (&str, &Arc<AnalysisEngine>)> {
+        self.engines.iter().map(|e| (e.name.as_str(), &e.engine))
+    }
+
+    /// Warm up every engine at once, on the smallest board it serves, retrying
+    /// failed attempts until each has answered
+    pub async fn warm_up(&self) {
+        let mut warming = JoinSet::new();
+        for (name, size, engine) in self.smallest_boards() {
+            let (name, engine) = (name.to_string(), engine.clone());
+            warming.spawn(async move {
+                let started = Instant::now();
+                loop {
+                    match engine.warm_up(size).await {
+                        Ok(()) => break,
+                        Err(e) => {
+                            warn!(
+                                "Warm-up of engine '{}' failed, retrying in {}s: {}",
+                                name,
+                                WARM_UP_RETRY.as_secs(),
+                                e
+                            );
+                            tokio::time::sleep(WARM_UP_RETRY).await;
+                        }
+                    }
+                }
+                info!(
+                    "Engine '{}' warmed up in {} ms",
+                    name,
+                    started.elapsed().as_millis()
+                );
+            });
+        }
+        warming.join_all().await;
+    }
+
+    /// Whether every engine's process is running and has warmed up
+    pub fn is_ready(&self) -> bool {
+        self.engines
+            .iter()
+            .all(|e| e.engine.is_alive() && e.engine.is_warmed_up())
+    }
+
+    /// Engine names and liveness, for status pages
+    pub fn statuses(&self) -> Vec<(String, bool)> {
+        self.engines
+            .iter()
+            .map(|e| (e.name.clone(), e.engine.is_alive()))
+            .collect()
+    }
+
+    /// Cancel the running query `id` on whichever engine has it; false if none does
+    pub fn cancel(&self, id: &str) -> bool {
+        self.engines.iter().any(|pooled| pooled.engine.cancel(id))
+    }
+
+    pub async fn clear_cache(&self) -> Result<()> {
+        for pooled in &self.engines {
+            pooled.engine.clear_cache().await?;
+        }
+        Ok(())
+    }
+
+    /// Apply reloaded `[katago]` settings to every engine (they inherit them)
+    pub fn apply_settings(&self, config: &KatagoConfig) {
+        for pooled in &self.engines {
+            pooled.engine.apply_settings(config);
+        }
+    }
+
+    /// Apply reloaded `[analysis]` defaults to the requests that follow
+    pub fn set_defaults(&self, defaults: AnalysisConfig) {
+        *self.defaults.lock().unwrap() = defaults;
+    }
+
+    /// Result cache counters summed over the engines
+    pub fn result_cache_counts(&self) -> CacheCounts {
+        self.engines
+            .iter()
+            .map(|e| e.engine.result_cache_counts())
+            .fold(CacheCounts::default(), |sum, counts| sum + counts)
+    }
+
+    /// Kind of the cache backend and its counters summed over the engines, if
+    /// one is configured
+    pub fn backend_counts(&self) -> Option<(&'static str, BackendCounts)> {
+        self.engines
+            .iter()
+            .filter_map(|e| e.engine.backend_counts())
+            .reduce(|(kind, sum), (_, counts)| (kind, sum + counts))
+    }
+
+    /// Requests that joined an identical search, summed over the engines
+    pub fn joined_count(&self) -> u64 {
+        self.engines.iter().map(|e| e.engine.joined_count()).sum()
+    }
+
+    fn supported_sizes(&self) -> String {
+        let mut sizes: Vec<u8> = Vec::new();
+        for pooled in &self.engines {
+            match &pooled.board_sizes {
+                None => return "any".to_string(),
+                Some(list) => sizes.extend(list),
+            }
+        }
+        sizes.sort_unstable();
+        sizes.dedup();
+        sizes
+            .iter()
+            .map(|s| format!("{}x{}", s, s))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn route_index<'a>(
+    engines: impl Iterator<Item = Option<&'a [u8]>> + Clone,
+    width: u8,
+    height: u8,
+) -> Option<usize> {
+    let explicit = engines
+        .clone()
+        .position(|sizes| width == height && sizes.is_some_and(|s| s.contains(&width)));
+    explicit.or_else(|| engines.clone().position(|sizes| sizes.is_none()))
+}
+
+/// The fast tier's model if a request belongs there: by its `quality`, else by
+/// its priority
+fn fast_model(tiers: &TierConfig, quality: Option<Quality>, priority: Option<i32>) -> Option<&str> {
+    let fast = match quality {
+        Some(quality) => quality == Quality::Fast,
+        None => tiers
+            .fast_below_priority
+            .is_some_and(|below| priority.unwrap_or(0) < below),
+    };
+    tiers.fast_model.as_deref().filter(|_| fast)
+}
+
+/// Whether an engine may answer a request selecting its human model
+fn serves_human(sizes: Option<&[u8]>, width: u8, height: u8) -> bool {
+    match sizes {
+        None | Some([]) => true,
+        Some(sizes) => width == height && sizes.contains(&width),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_prefers_explicit_sizes() {
+        let small: &[u8] = &[9, 13];
+        let engines = [None, Some(small)];
+        assert_eq!(route_index(engines.iter().copied(), 9, 9), Some(1));
+        assert_eq!(route_index(engines.iter().copied(), 19, 19), Some(0));
+        // Rectangular boards fall back to the unrestricted engine
+        assert_eq!(route_index(engines.iter().copied(), 9, 13), Some(0));
+    }
+
+    #[test]
+    fn test_route_rejects_unserved_sizes() {
+        let big: &[u8] = &[19];
+        let small: &[u8] = &[9];
+        let engines = [Some(big), Some(small)];
+        assert_eq!(route_index(engines.iter().copied(), 9, 9), Some(1));
+        assert_eq!(route_index(engines.iter().copied(), 13, 13), None);
+    }
+
+    #[test]
+    fn test_human_only_engines_are_not_routed_by_size() {
+        let human_only: &[u8] = &[];
+        let engines = [Some(human_only), None];
+        assert_eq!(route_index(engines.iter().copied(), 19, 19), Some(1));
+        assert!(serves_human(Some(human_only), 9, 13));
+        assert!(serves_human(Some(&[19]), 19, 19));
+        assert!(!serves_human(Some(&[19]), 13, 13));
+    }
+
+    #[test]
+    fn test_fast_tier_by_quality_then_priority() {
+        let tiers = TierConfig {
+            fast_model: Some("small".to_string()),
+            fast_below_priority: Some(0),
+        };
+        assert_eq!(
+            fast_model(&tiers, Some(Quality::Fast), Some(10)),
+            Some("small")
+        );
+        assert_eq!(fast_model(&tiers, Some(Quality::Deep), Some(-5)), None);
+        assert_eq!(fast_model(&tiers, None, Some(-5)), Some("small"));
+        assert_eq!(fast_model(&tiers, None, None), None);
+
+        // Without a threshold only `quality` picks the tier
+        let by_quality = TierConfig {
+            fast_below_priority: None,
+            ..tiers
+        };
+        assert_eq!(fast_model(&by_quality, None, Some(-5)), None);
+        // Without a fast model every request is deep
+        assert_eq!(
+            fast_model(&TierConfig::default(), Some(Quality::Fast), None),
+            None
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::api::InvalidMove;
+use crate::legality::LegalityError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -28,6 +30,46 @@ pub enum KatagoError {
     #[allow(dead_code)]
     #[error("KataGo returned error: {0}")]
     ResponseError(String),
+
+    #[error("Query {0} was cancelled")]
+    Cancelled(String),
+
+    #[error("KataGo was restarted before query {0} finished")]
+    Restarted(String),
+
+    #[error("Analysis queue is full ({0} requests waiting)")]
+    QueueFull(usize),
+
+    #[error("No engine is configured for {width}x{height} boards (supported: {supported})")]
+    UnsupportedBoardSize {
+        width: u8,
+        height: u8,
+        supported: String,
+    },
+
+    #[error("{count} moves are not on the {width}x{height} board", count = .moves.len())]
+    InvalidMoves {
+        width: u8,
+        height: u8,
+        moves: Vec<InvalidMove>,
+    },
+
+    #[error(transparent)]
+    IllegalPosition(#[from] LegalityError),
+
+    #[error("No model named '{name}' is configured (available: {available})")]
+    UnknownModel { name: String, available: String },
+
+    #[error("No analysis profile named '{name}' is configured (available: {available})")]
+    UnknownProfile { name: String, available: String },
+
+    #[error("No engine with human model '{name}' serves {width}x{height} boards (loaded human models: {available})")]
+    UnavailableHumanModel {
+        name: String,
+        width: u8,
+        height: u8,
+        available: String,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, KatagoError>;
@@ -57,6 +99,21 @@ mod tests {
         assert_eq!(error.to_string(), "Command timeout after 30 seconds");
     }
 
+    #[test]
+    fn test_cancelled_error() {
+        let error = KatagoError::Cancelled("req-1".to_string());
+        assert_eq!(error.to_string(), "Query req-1 was cancelled");
+    }
+
+    #[test]
+    fn test_queue_full_error() {
+        let error = KatagoError::QueueFull(64);
+        assert_eq!(
+            error.to_string(),
+            "Analysis queue is full (64 requests waiting)"
+        );
+    }
+
     #[test]
     fn test_parse_error() {
         let error = KatagoError::ParseError("invalid json".to_string());
@@ -0,0 +1,738 @@
+use crate::api::{AnalysisRequest, MoveInput, Rules};
+use crate::board::{Board, Stone};
+use crate::sgf::SgfGame;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tracing::debug;
+
+/// Descriptive game record information (SGF-style PB/PW/EV/DT/RE)
+#[derive(Debug, Clone, Default)]
+pub struct GameInfo {
+    pub black_player: Option<String>,
+    pub white_player: Option<String>,
+    pub event: Option<String>,
+    pub date: Option<String>,
+    pub result: Option<String>,
+}
+
+impl GameInfo {
+    /// Fill fields this record lacks from another copy of the same game
+    fn merge_missing(&mut self, other: GameInfo) {
+        self.black_player = self.black_player.take().or(other.black_player);
+        self.white_player = self.white_player.take().or(other.white_player);
+        self.event = self.event.take().or(other.event);
+        self.date = self.date.take().or(other.date);
+        self.result = self.result.take().or(other.result);
+    }
+}
+
+/// Who can see an archived game
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Visibility {
+    /// Only the owner (holder of the owner token)
+    Private,
+    /// Anyone holding the share token; not listed or searchable
+    Link,
+    /// Listed, searchable, and readable by id
+    #[default]
+    Public,
+}
+
+impl Visibility {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "private" => Some(Visibility::Private),
+            "link" => Some(Visibility::Link),
+            "public" => Some(Visibility::Public),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Private => "private",
+            Visibility::Link => "link",
+            Visibility::Public => "public",
+        }
+    }
+}
+
+/// Unguessable token for owner and share links (256 random bits, hex)
+pub fn new_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// A complete game record stored in the archive
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub id: String,
+    pub info: GameInfo,
+    pub board_x_size: u8,
+    pub board_y_size: u8,
+    pub komi: Option<f32>,
+    pub rules: Option<String>,
+    pub initial_stones: Vec<(Stone, String)>,
+    /// Player to move before the first move
+    pub initial_player: Stone,
+    pub moves: Vec<(Stone, String)>,
+    /// Where the record was imported from (file path), if not uploaded directly
+    pub source: Option<String>,
+    pub visibility: Visibility,
+    /// Secret returned to the uploader; None for bulk-imported games
+    pub owner_token: Option<String>,
+    /// Token for share links, generated the first time the game is made link-shareable
+    pub share_token: Option<String>,
+}
+
+impl Game {
+    /// Build an archive record from a parsed SGF main line
+    pub fn from_sgf(sgf: SgfGame, source: Option<String>) -> Self {
+        let initial_player = sgf
+            .initial_player
+            .or_else(|| sgf.moves.first().map(|(stone, _)| *stone))
+            .unwrap_or(if sgf.initial_stones.is_empty() {
+                Stone::Black
+            } else {
+                Stone::White
+            });
+        Game {
+            id: uuid::Uuid::new_v4().to_string(),
+            info: GameInfo {
+                black_player: sgf.black_player,
+                white_player: sgf.white_player,
+                event: sgf.event,
+                date: sgf.date,
+                result: sgf.result,
+            },
+            board_x_size: sgf.board_x_size,
+            board_y_size: sgf.board_y_size,
+            komi: sgf.komi,
+            rules: sgf.rules,
+            initial_stones: sgf.initial_stones,
+            initial_player,
+            moves: sgf.moves,
+            source,
+            visibility: Visibility::Public,
+            owner_token: None,
+            share_token: None,
+        }
+    }
+
+    /// Moves in KataGo format ([["b", "D4"], ...]) up to (not including) `turn`
+    pub fn katago_moves_until(&self, turn: usize) -> Vec<Vec<String>> {
+        self.moves
+            .iter()
+            .take(turn)
+            .map(|(stone, coord)| vec![stone.as_str().to_lowercase(), coord.clone()])
+            .collect()
+    }
+
+    /// Analysis request for the position before move `turn`
+    pub fn analysis_request(&self, turn: usize, max_visits: Option<u32>) -> AnalysisRequest {
+        AnalysisRequest {
+            moves: self
+                .katago_moves_until(turn)
+                .into_iter()
+                .map(|pair| MoveInput::WithColor([pair[0].clone(), pair[1].clone()]))
+                .collect(),
+            initial_stones: Some(self.initial_stones()),
+            initial_player: Some(self.initial_player.as_str().to_string()),
+            board_x_size: self.board_x_size,
+            board_y_size: self.board_y_size,
+            komi: self.komi,
+            rules: self.rules.as_deref().map(Rules::from),
+            max_visits,
+            ..Default::default()
+        }
+    }
+
+    /// Initial stones in API format ([("B", "D4"), ...])
+    pub fn initial_stones(&self) -> Vec<(String, String)> {
+        self.initial_stones
+            .iter()
+            .map(|(stone, coord)| (stone.as_str().to_string(), coord.clone()))
+            .collect()
+    }
+
+    /// Player to move at `turn`
+    pub fn player_at(&self, turn: usize) -> Stone {
+        match self.moves.get(turn) {
+            Some((stone, _)) => *stone,
+            None => match self.moves.last() {
+                Some((stone, _)) => stone.opponent(),
+                None => self.initial_player,
+            },
+        }
+    }
+
+    /// Hash of the position before each move (and after the last), stopping at the
+    /// first move that cannot be replayed
+    fn position_hashes(&self) -> Vec<u64> {
+        let mut board = Board::new(self.board_x_size, self.board_y_size);
+        for (stone, coord) in &self.initial_stones {
+            if board.place(*stone, coord).is_err() {
+                return Vec::new();
+            }
+        }
+        let mut hashes = vec![board.position_hash(self.player_at(0))];
+        for (turn, (stone, coord)) in self.moves.iter().enumerate() {
+            if let Err(e) = board.play(*stone, coord) {
+                debug!("Stopped indexing game {} at move {}: {}", self.id, turn, e);
+                break;
+            }
+            hashes.push(board.position_hash(self.player_at(turn + 1)));
+        }
+        hashes
+    }
+
+    /// Fingerprints of the setup and move sequence: exact, and canonical over the
+    /// board's rotations/reflections (square boards only) to catch transformed re-uploads
+    fn fingerprints(&self) -> (u64, u64) {
+        let symmetries = if self.board_x_size == self.board_y_size {
+            8
+        } else {
+            1
+        };
+        let exact = self.sequence_hash(0);
+        let canonical = (1..symmetries)
+            .map(|s| self.sequence_hash(s))
+            .fold(exact, u64::min);
+        (exact, canonical)
+    }
+
+    fn sequence_hash(&self, symmetry: u8) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let board = Board::new(self.board_x_size, self.board_y_size);
+        let max = self.board_x_size.saturating_sub(1);
+        // Unparseable coordinates (passes, malformed input) are hashed as written
+        let transform = |coord: &str| match board.parse_point(coord) {
+            Ok((x, y)) => {
+                let (x, y) = apply_symmetry(symmetry, x, y, max);
+                Board::coord_name(x, y)
+            }
+            Err(_) => coord.to_uppercase(),
+        };
+
+        let mut setup: Vec<(Stone, String)> = self
+            .initial_stones
+            .iter()
+            .map(|(stone, coord)| (*stone, transform(coord)))
+            .collect();
+        setup.sort();
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.board_x_size.hash(&mut hasher);
+        self.board_y_size.hash(&mut hasher);
+        setup.hash(&mut hasher);
+        for (stone, coord) in &self.moves {
+            stone.hash(&mut hasher);
+            transform(coord).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+/// One of the eight board symmetries (bit 2: transpose, bit 0: flip x, bit 1: flip y)
+fn apply_symmetry(symmetry: u8, x: u8, y: u8, max: u8) -> (u8, u8) {
+    let (x, y) = if symmetry & 4 != 0 { (y, x) } else { (x, y) };
+    let x = if symmetry & 1 != 0 { max - x } else { x };
+    let y = if symmetry & 2 != 0 { max - y } else { y };
+    (x, y)
+}
+
+/// How an inserted game duplicates one already in the archive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuplicateKind {
+    /// Identical setup and moves (merged into the original when the owner matches)
+    SameMoves,
+    /// Same position sequence up to rotation/reflection
+    SamePositions,
+}
+
+impl DuplicateKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DuplicateKind::SameMoves => "same-moves",
+            DuplicateKind::SamePositions => "same-positions",
+        }
+    }
+}
+
+/// A suspected duplicate found while inserting a game
+#[derive(Debug, Clone)]
+pub struct DuplicateRecord {
+    /// The game that was already in the archive
+    pub original_id: String,
+    /// The stored copy, or None if it was merged into the original
+    pub duplicate_id: Option<String>,
+    /// Where the duplicate was imported from
+    pub source: Option<String>,
+    pub kind: DuplicateKind,
+}
+
+/// Result of inserting a game into the archive
+#[derive(Debug, Clone, PartialEq)]
+pub struct Insertion {
+    /// Id of the stored game (the original's id when merged)
+    pub game_id: String,
+    pub positions_indexed: usize,
+    pub duplicate: Option<DuplicateKind>,
+    /// The game was merged into an existing record rather than stored
+    pub merged: bool,
+}
+
+/// Where an indexed position occurs in the archive
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionHit {
+    pub game_id: String,
+    /// Number of moves played before the position arose
+    pub turn: usize,
+}
+
+/// Metadata filters for archive searches (case-insensitive substring matches)
+#[derive(Debug, Default)]
+pub struct GameQuery {
+    /// Matches either player
+    pub player: Option<String>,
+    pub event: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// In-memory game archive with a position index over every imported move
+#[derive(Default)]
+pub struct GameDatabase {
+    inner: RwLock<DatabaseInner>,
+}
+
+#[derive(Default)]
+struct DatabaseInner {
+    games: HashMap<String, Game>,
+    positions: HashMap<u64, Vec<PositionHit>>,
+    /// Exact fingerprint -> game id
+    by_moves: HashMap<u64, String>,
+    /// Canonical (symmetry-normalized) fingerprint -> first game id seen
+    by_positions: HashMap<u64, String>,
+    duplicates: Vec<DuplicateRecord>,
+    /// Share token -> game id
+    shares: HashMap<String, String>,
+}
+
+impl GameDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a game and index its positions. An exact copy of an archived game with
+    /// the same owner is merged into it (filling missing game info) instead of being
+    /// stored again; other copies, including rotated or mirrored ones, are stored but
+    /// flagged. Games without moves are never treated as duplicates.
+    pub fn insert(&self, mut game: Game) -> Insertion {
+        let fingerprints = (!game.moves.is_empty()).then(|| game.fingerprints());
+        let hashes = game.position_hashes();
+        let mut guard = self.inner.write().unwrap();
+        let inner = &mut *guard;
+
+        let mut duplicate = None;
+        if let Some((exact, canonical)) = fingerprints {
+            let same_moves = inner
+                .by_moves
+                .get(&exact)
+                .and_then(|id| inner.games.get_mut(id));
+            if let Some(original) = same_moves {
+                // Never merge across owners: that would expose one owner's game to another
+                if original.owner_token == game.owner_token {
+                    debug!("Merging duplicate game {} into {}", game.id, original.id);
+                    original.info.merge_missing(std::mem::take(&mut game.info));
+                    inner.duplicates.push(DuplicateRecord {
+                        original_id: original.id.clone(),
+                        duplicate_id: None,
+                        source: game.source,
+                        kind: DuplicateKind::SameMoves,
+                    });
+                    return Insertion {
+                        game_id: original.id.clone(),
+                        positions_indexed: 0,
+                        duplicate: Some(DuplicateKind::SameMoves),
+                        merged: true,
+                    };
+                }
+                debug!(
+                    "Flagging game {} as a duplicate of {}",
+                    game.id, original.id
+                );
+                inner.duplicates.push(DuplicateRecord {
+                    original_id: original.id.clone(),
+                    duplicate_id: Some(game.id.clone()),
+                    source: game.source.clone(),
+                    kind: DuplicateKind::SameMoves,
+                });
+                duplicate = Some(DuplicateKind::SameMoves);
+            } else if let Some(original_id) = inner.by_positions.get(&canonical) {
+                debug!(
+                    "Flagging game {} as a duplicate of {}",
+                    game.id, original_id
+                );
+                inner.duplicates.push(DuplicateRecord {
+                    original_id: original_id.clone(),
+                    duplicate_id: Some(game.id.clone()),
+                    source: game.source.clone(),
+                    kind: DuplicateKind::SamePositions,
+                });
+                duplicate = Some(DuplicateKind::SamePositions);
+            }
+            inner
+                .by_moves
+                .entry(exact)
+                .or_insert_with(|| game.id.clone());
+            inner
+                .by_positions
+                .entry(canonical)
+                .or_insert_with(|| game.id.clone());
+        }
+
+        for (turn, hash) in hashes.iter().enumerate() {
+            inner.positions.entry(*hash).or_default().push(PositionHit {
+                game_id: game.id.clone(),
+                turn,
+            });
+        }
+        if let Some(token) = &game.share_token {
+            inner.shares.insert(token.clone(), game.id.clone());
+        }
+        let game_id = game.id.clone();
+        inner.games.insert(game.id.clone(), game);
+        Insertion {
+            game_id,
+            positions_indexed: hashes.len(),
+            duplicate,
+            merged: false,
+        }
+    }
+
+    /// Change a game's visibility, generating a share token the first time it is
+    /// made link-shareable. Returns the updated game.
+    pub fn set_visibility(&self, id: &str, visibility: Visibility) -> Option<Game> {
+        let mut guard = self.inner.write().unwrap();
+        let inner = &mut *guard;
+        let game = inner.games.get_mut(id)?;
+        game.visibility = visibility;
+        if visibility == Visibility::Link && game.share_token.is_none() {
+            let token = new_token();
+            inner.shares.insert(token.clone(), game.id.clone());
+            game.share_token = Some(token);
+        }
+        Some(game.clone())
+    }
+
+    /// Game behind a share token, unless it has since been made private
+    pub fn get_shared(&self, token: &str) -> Option<Game> {
+        let inner = self.inner.read().unwrap();
+        let game = inner.games.get(inner.shares.get(token)?)?;
+        (game.visibility != Visibility::Private).then(|| game.clone())
+    }
+
+    /// Suspected duplicates in the order they were found
+    pub fn duplicates(&self) -> Vec<DuplicateRecord> {
+        self.inner.read().unwrap().duplicates.clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Game> {
+        self.inner.read().unwrap().games.get(id).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().games.len()
+    }
+
+    /// Public games matching the query, ordered by date then id for stable listing
+    pub fn search(&self, query: &GameQuery) -> Vec<Game> {
+        let contains = |field: &Option<String>, needle: &str| {
+            field
+                .as_deref()
+                .is_some_and(|f| f.to_lowercase().contains(&needle.to_lowercase()))
+        };
+        let inner = self.inner.read().unwrap();
+        let mut games: Vec<Game> = inner
+            .games
+            .values()
+            .filter(|g| g.visibility == Visibility::Public)
+            .filter(|g| {
+                query.player.as_deref().is_none_or(|p| {
+                    contains(&g.info.black_player, p) || contains(&g.info.white_player, p)
+                })
+            })
+            .filter(|g| {
+                query
+                    .event
+                    .as_deref()
+                    .is_none_or(|e| contains(&g.info.event, e))
+            })
+            .cloned()
+            .collect();
+        games.sort_by(|a, b| (&a.info.date, &a.id).cmp(&(&b.info.date, &b.id)));
+        if let Some(limit) = query.limit {
+            games.truncate(limit);
+        }
+        games
+    }
+
+    /// Occurrences of a board position with the given side to move in public games
+    pub fn find_position(&self, board: &Board, to_move: Stone) -> Vec<PositionHit> {
+        let inner = self.inner.read().unwrap();
+        inner
+            .positions
+            .get(&board.position_hash(to_move))
+            .into_iter()
+            .flatten()
+            .filter(|hit| {
+                inner
+                    .games
+                    .get(&hit.game_id)
+                    .is_some_and(|g| g.visibility == Visibility::Public)
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game() -> Game {
+        Game {
+            id: "g1".to_string(),
+            info: GameInfo {
+                black_player: Some("Honinbo Shusaku".to_string()),
+                white_player: Some("Gennan Inseki".to_string()),
+                event: Some("Castle Game".to_string()),
+                ..Default::default()
+            },
+            board_x_size: 19,
+            board_y_size: 19,
+            komi: Some(6.5),
+            rules: None,
+            initial_stones: vec![(Stone::Black, "D4".to_string())],
+            initial_player: Stone::White,
+            moves: vec![
+                (Stone::White, "Q16".to_string()),
+                (Stone::Black, "Q4".to_string()),
+            ],
+            source: None,
+            visibility: Visibility::Public,
+            owner_token: None,
+            share_token: None,
+        }
+    }
+
+    #[test]
+    fn test_katago_moves_until() {
+        let game = game();
+        assert_eq!(
+            game.katago_moves_until(1),
+            vec![vec!["w".to_string(), "Q16".to_string()]]
+        );
+        assert_eq!(game.katago_moves_until(10).len(), 2);
+        assert_eq!(
+            game.initial_stones(),
+            vec![("B".to_string(), "D4".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_analysis_request() {
+        let request = game().analysis_request(1, Some(50));
+        assert_eq!(request.moves.len(), 1);
+        assert_eq!(request.moves[0].color(), Some("w"));
+        assert_eq!(request.initial_player.as_deref(), Some("W"));
+        assert_eq!(request.max_visits, Some(50));
+        assert_eq!(request.komi, Some(6.5));
+    }
+
+    #[test]
+    fn test_player_at() {
+        let game = game();
+        assert_eq!(game.player_at(0), Stone::White);
+        assert_eq!(game.player_at(1), Stone::Black);
+        assert_eq!(game.player_at(2), Stone::White);
+    }
+
+    #[test]
+    fn test_insert_indexes_every_position() {
+        let db = GameDatabase::new();
+        assert_eq!(db.insert(game()).positions_indexed, 3);
+        assert_eq!(db.len(), 1);
+
+        let mut board = Board::new(19, 19);
+        board.place(Stone::Black, "D4").unwrap();
+        board.place(Stone::White, "Q16").unwrap();
+        assert_eq!(
+            db.find_position(&board, Stone::Black),
+            vec![PositionHit {
+                game_id: "g1".to_string(),
+                turn: 1
+            }]
+        );
+        assert!(db.find_position(&board, Stone::White).is_empty());
+    }
+
+    #[test]
+    fn test_search_by_player_and_event() {
+        let db = GameDatabase::new();
+        db.insert(game());
+        let by_player = GameQuery {
+            player: Some("shusaku".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(db.search(&by_player).len(), 1);
+        let by_event = GameQuery {
+            event: Some("meijin".to_string()),
+            ..Default::default()
+        };
+        assert!(db.search(&by_event).is_empty());
+    }
+
+    #[test]
+    fn test_exact_duplicate_is_merged() {
+        let db = GameDatabase::new();
+        let mut original = game();
+        original.info.date = None;
+        db.insert(original);
+
+        let mut copy = game();
+        copy.id = "g2".to_string();
+        copy.info.date = Some("1846-09-11".to_string());
+        copy.source = Some("club/upload.sgf".to_string());
+        let insertion = db.insert(copy);
+
+        assert_eq!(insertion.game_id, "g1");
+        assert_eq!(insertion.duplicate, Some(DuplicateKind::SameMoves));
+        assert_eq!(insertion.positions_indexed, 0);
+        assert_eq!(db.len(), 1);
+        assert_eq!(
+            db.get("g1").unwrap().info.date.as_deref(),
+            Some("1846-09-11")
+        );
+
+        let duplicates = db.duplicates();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].original_id, "g1");
+        assert_eq!(duplicates[0].duplicate_id, None);
+        assert_eq!(duplicates[0].source.as_deref(), Some("club/upload.sgf"));
+    }
+
+    #[test]
+    fn test_mirrored_duplicate_is_flagged() {
+        let db = GameDatabase::new();
+        db.insert(game());
+
+        // Reflected left-right: D4 -> Q4, Q16 -> D16, Q4 -> D4
+        let mut mirrored = game();
+        mirrored.id = "g2".to_string();
+        mirrored.initial_stones = vec![(Stone::Black, "Q4".to_string())];
+        mirrored.moves = vec![
+            (Stone::White, "D16".to_string()),
+            (Stone::Black, "D4".to_string()),
+        ];
+        let insertion = db.insert(mirrored);
+
+        assert_eq!(insertion.game_id, "g2");
+        assert_eq!(insertion.duplicate, Some(DuplicateKind::SamePositions));
+        assert_eq!(db.len(), 2);
+        assert_eq!(db.duplicates()[0].duplicate_id.as_deref(), Some("g2"));
+    }
+
+    #[test]
+    fn test_distinct_games_are_not_duplicates() {
+        let db = GameDatabase::new();
+        db.insert(game());
+        let mut other = game();
+        other.id = "g2".to_string();
+        other.moves[1].1 = "C16".to_string();
+        assert_eq!(db.insert(other).duplicate, None);
+
+        // Move-less records are never flagged
+        let mut empty = game();
+        empty.id = "g3".to_string();
+        empty.moves.clear();
+        db.insert(empty.clone());
+        empty.id = "g4".to_string();
+        assert_eq!(db.insert(empty).duplicate, None);
+        assert!(db.duplicates().is_empty());
+    }
+
+    #[test]
+    fn test_copies_from_other_owners_are_flagged_not_merged() {
+        let db = GameDatabase::new();
+        let mut original = game();
+        original.owner_token = Some("alice".to_string());
+        db.insert(original);
+
+        let mut copy = game();
+        copy.id = "g2".to_string();
+        copy.owner_token = Some("bob".to_string());
+        let insertion = db.insert(copy);
+
+        assert_eq!(insertion.game_id, "g2");
+        assert!(!insertion.merged);
+        assert_eq!(insertion.duplicate, Some(DuplicateKind::SameMoves));
+        assert_eq!(db.len(), 2);
+    }
+
+    #[test]
+    fn test_visibility_hides_games_from_listing_and_search() {
+        let db = GameDatabase::new();
+        let mut private = game();
+        private.visibility = Visibility::Private;
+        db.insert(private);
+
+        assert!(db.search(&GameQuery::default()).is_empty());
+        let mut board = Board::new(19, 19);
+        board.place(Stone::Black, "D4").unwrap();
+        assert!(db.find_position(&board, Stone::White).is_empty());
+
+        let shared = db.set_visibility("g1", Visibility::Link).unwrap();
+        let token = shared.share_token.clone().unwrap();
+        assert_eq!(token.len(), 64);
+        assert!(db.search(&GameQuery::default()).is_empty());
+        assert_eq!(db.get_shared(&token).unwrap().id, "g1");
+
+        // The token is stable and stops working while the game is private
+        db.set_visibility("g1", Visibility::Private);
+        assert!(db.get_shared(&token).is_none());
+        let relinked = db.set_visibility("g1", Visibility::Link).unwrap();
+        assert_eq!(relinked.share_token, Some(token));
+        assert!(db.get_shared("not-a-token").is_none());
+
+        db.set_visibility("g1", Visibility::Public);
+        assert_eq!(db.search(&GameQuery::default()).len(), 1);
+    }
+
+    #[test]
+    fn test_games_stored_link_shareable_are_reachable_only_by_token() {
+        let db = GameDatabase::new();
+        let mut shared = game();
+        shared.visibility = Visibility::Link;
+        shared.share_token = Some(new_token());
+        let token = shared.share_token.clone().unwrap();
+        db.insert(shared);
+
+        assert!(db.search(&GameQuery::default()).is_empty());
+        assert_eq!(db.get_shared(&token).unwrap().id, "g1");
+        let relinked = db.set_visibility("g1", Visibility::Link).unwrap();
+        assert_eq!(relinked.share_token, Some(token));
+    }
+
+    #[test]
+    fn test_from_sgf_infers_first_player() {
+        let sgf = crate::sgf::parse("(;SZ[9]AB[cc][gg];W[ee])").unwrap();
+        let game = Game::from_sgf(sgf, Some("a.sgf".to_string()));
+        assert_eq!(game.initial_player, Stone::White);
+        assert_eq!(game.moves.len(), 1);
+        assert_eq!(game.source.as_deref(), Some("a.sgf"));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::api::{
+    current_request_id, health_report, resolve_position, review_game, run_analysis, run_review,
+    version_report, AnalysisRequest, AnalysisResponse, ApiError, AppState, GameReviewResponse,
+    HealthResponse, ReviewFormat, ReviewRequest, VersionResponse,
+};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, ErrorExtensions, Object, Schema, Value,
+};
+use axum::{extract::State, http::StatusCode, Json};
+
+/// Deepest selection a query may make; the response types nest four levels
+const MAX_DEPTH: usize = 8;
+
+pub type ApiSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// The schema, resolving against the server's engines
+pub fn schema(state: AppState) -> ApiSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(state)
+        .limit_depth(MAX_DEPTH)
+        .finish()
+}
+
+/// POST /graphql
+pub async fn execute(
+    State(schema): State<ApiSchema>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(request).await)
+}
+
+/// A problem detail as a GraphQL error, with the problem's fields as extensions
+fn problem(error: ApiError) -> async_graphql::Error {
+    let problem = error.into_problem();
+    async_graphql::Error::new(problem.detail).extend_with(|_, extensions| {
+        extensions.set("type", problem.problem_type.clone());
+        extensions.set("title", problem.title.clone());
+        extensions.set("status", problem.status);
+        if let Some(request_id) = &problem.request_id {
+            extensions.set("requestId", request_id.clone());
+        }
+        if let Some(invalid_moves) = &problem.invalid_moves {
+            extensions.set(
+                "invalidMoves",
+                Value::from_json(serde_json::to_value(invalid_moves).unwrap_or_default())
+                    .unwrap_or_default(),
+            );
+        }
+    })
+}
+
+fn unsupported(field: &str, detail: &str) -> async_graphql::Error {
+    problem(ApiError::new(
+        StatusCode::BAD_REQUEST,
+        "Invalid Request",
+        &format!("{} is not supported over GraphQL; {}", field, detail),
+    ))
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Analyze a position. `request` is an analysis request as the JSON body of
+    /// POST /api/v1/analysis takes it; `maxMoves` keeps the best moves only.
+    async fn analysis(
+        &self,
+        ctx: &Context<'_>,
+        request: async_graphql::Json<AnalysisRequest>,
+        max_moves: Option<usize>,
+    ) -> async_graphql::Result<AnalysisResponse> {
+        let state = ctx.data_unchecked::<AppState>();
+        let request = request.0;
+        if request.analyze_turns.is_some() {
+            return Err(unsupported(
+                "analyzeTurns",
+                "use POST /api/v1/analysis/turns",
+            ));
+        }
+        if request.callback_url.is_some() {
+            return Err(unsupported("callbackUrl", "use POST /api/v1/analysis"));
+        }
+        let request_id = request
+            .request_id
+            .clone()
+            .unwrap_or_else(current_request_id);
+        let mut request = resolve_position(&state.positions, request)
+            .map_err(|e| problem(e.with_request_id(request_id.clone())))?;
+        request.request_id = Some(request_id);
+
+        let mut response = run_analysis(state.clone(), request)
+            .await
+            .map_err(problem)?;
+        if let (Some(moves), Some(max_moves)) = (response.move_infos.as_mut(), max_moves) {
+            moves.sort_by_key(|info| info.order);
+            moves.truncate(max_moves);
+        }
+        Ok(response)
+    }
+
+    /// Review an SGF game move by move, as POST /api/v1/review does with a JSON
+    /// body
+    async fn review(
+        &self,
+        ctx: &Context<'_>,
+        request: async_graphql::Json<ReviewRequest>,
+    ) -> async_graphql::Result<GameReviewResponse> {
+        let state = ctx.data_unchecked::<AppState>();
+        let request = request.0;
+        if request.callback_url.is_some() {
+            return Err(unsupported("callbackUrl", "use POST /api/v1/review"));
+        }
+        if request
+            .format
+            .is_some_and(|format| format != ReviewFormat::Json)
+        {
+            return Err(unsupported("An SGF format", "use POST /api/v1/review"));
+        }
+        let game = review_game(&request).map_err(problem)?;
+        run_review(state, request, game, |_| {})
+            .await
+            .map_err(problem)
+    }
+
+    /// Liveness, uptimes, and restarts of the engines, as GET /api/v1/health
+    async fn health(&self, ctx: &Context<'_>) -> HealthResponse {
+        health_report(ctx.data_unchecked::<AppState>())
+    }
+
+    /// Versions of the server, KataGo, and the model, as GET /api/v1/version
+    async fn version(&self, ctx: &Context<'_>) -> VersionResponse {
+        version_report(ctx.data_unchecked::<AppState>()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_exposes_the_response_fields() {
+        let sdl = Schema::build(Query, EmptyMutation, EmptySubscription)
+            .finish()
+            .sdl();
+        for field in [
+            "analysis(request: JSON!, maxMoves: Int): AnalysisResponse!",
+            "review(request: JSON!): GameReviewResponse!",
+            "health: HealthResponse!",
+            "version: VersionResponse!",
+            "moveInfos: [MoveInfo!]",
+            "ownership: Ownership",
+            "classification: MoveClassification!",
+        ] {
+            assert!(sdl.contains(field), "{} missing from\n{}", field, sdl);
+        }
+        // Renamed like the JSON fields
+        assert!(sdl.contains("move: String!"));
+    }
+
+    #[test]
+    fn test_problems_become_error_extensions() {
+        let error = problem(
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Invalid Position",
+                "D4 is occupied",
+            )
+            .with_request_id("r1".to_string()),
+        );
+        assert_eq!(error.message, "D4 is occupied");
+        let extensions = serde_json::to_value(error.extensions.unwrap()).unwrap();
+        assert_eq!(extensions["status"], 400);
+        assert_eq!(extensions["title"], "Invalid Position");
+        assert_eq!(extensions["requestId"], "r1");
+    }
+}
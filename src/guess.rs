@@ -0,0 +1,190 @@
+use crate::api::AnalysisResponse;
+use crate::board::Board;
+
+/// Points for guessing the game move exactly
+const POINTS_EXACT: u32 = 10;
+/// Points for guessing the engine's top move (when it differs from the game move)
+const POINTS_ENGINE_BEST: u32 = 6;
+/// Points for guessing one of the engine's top three moves
+const POINTS_ENGINE_TOP3: u32 = 3;
+/// Points for guessing one of the human model's three most likely moves
+const POINTS_HUMAN_TOP3: u32 = 1;
+
+/// A guess-the-next-move session over an archived game
+#[derive(Debug, Clone)]
+pub struct GuessSession {
+    pub id: String,
+    pub game_id: String,
+    /// Index of the move the player is guessing next
+    pub turn: usize,
+    pub score: u32,
+    pub guesses: u32,
+    pub correct: u32,
+    pub max_visits: Option<u32>,
+}
+
+impl GuessSession {
+    /// Record a scored guess and advance to the next move
+    pub fn record(&mut self, evaluation: &GuessEvaluation) {
+        self.guesses += 1;
+        if evaluation.correct {
+            self.correct += 1;
+        }
+        self.score += evaluation.points;
+        self.turn += 1;
+    }
+}
+
+/// How a guess compares to the game move and the engine/human rankings
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuessEvaluation {
+    pub correct: bool,
+    /// 1-based rank of the guess among the engine's candidate moves
+    pub engine_rank: Option<u32>,
+    /// 1-based rank of the actual game move among the engine's candidate moves
+    pub actual_engine_rank: Option<u32>,
+    /// 1-based rank of the guess in the raw policy
+    pub policy_rank: Option<u32>,
+    /// 1-based rank of the guess in the human SL policy (when a human model is loaded)
+    pub human_policy_rank: Option<u32>,
+    pub points: u32,
+}
+
+/// Score a guess against the actual game move and an analysis of the position
+pub fn evaluate_guess(
+    board: &Board,
+    guess: &str,
+    actual: &str,
+    analysis: &AnalysisResponse,
+) -> GuessEvaluation {
+    let correct = guess.eq_ignore_ascii_case(actual);
+    let engine_rank = rank_in_engine(analysis, guess);
+    let policy_rank = analysis
+        .policy
+        .as_deref()
+        .and_then(|policy| rank_in_policy(board, policy, guess));
+    let human_policy_rank = analysis
+        .human_policy
+        .as_deref()
+        .and_then(|policy| rank_in_policy(board, policy, guess));
+
+    let points = if correct {
+        POINTS_EXACT
+    } else if engine_rank == Some(1) {
+        POINTS_ENGINE_BEST
+    } else if engine_rank.is_some_and(|r| r <= 3) {
+        POINTS_ENGINE_TOP3
+    } else if human_policy_rank.is_some_and(|r| r <= 3) {
+        POINTS_HUMAN_TOP3
+    } else {
+        0
+    };
+
+    GuessEvaluation {
+        correct,
+        engine_rank,
+        actual_engine_rank: rank_in_engine(analysis, actual),
+        policy_rank,
+        human_policy_rank,
+        points,
+    }
+}
+
+fn rank_in_engine(analysis: &AnalysisResponse, coord: &str) -> Option<u32> {
+    analysis
+        .move_infos
+        .as_ref()?
+        .iter()
+        .find(|mi| mi.move_coord.eq_ignore_ascii_case(coord))
+        .map(|mi| mi.order + 1)
+}
+
+/// 1-based rank of a move in a policy array (number of strictly better moves + 1)
+fn rank_in_policy(board: &Board, policy: &[f32], coord: &str) -> Option<u32> {
+    let value = *policy.get(board.policy_index(coord).ok()?)?;
+    if value < 0.0 {
+        // KataGo reports illegal moves as -1
+        return None;
+    }
+    Some(policy.iter().filter(|&&p| p > value).count() as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analysis(moves: &[&str], policy: Option<Vec<f32>>) -> AnalysisResponse {
+        let move_infos: Vec<_> = moves
+            .iter()
+            .enumerate()
+            .map(|(order, coord)| {
+                serde_json::json!({
+                    "moveCoord": coord,
+                    "visits": 10,
+                    "winrate": 0.5,
+                    "scoreMean": 0.0,
+                    "scoreStdev": 0.0,
+                    "scoreLead": 0.0,
+                    "utility": 0.0,
+                    "lcb": 0.5,
+                    "prior": 0.1,
+                    "order": order,
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "id": "test",
+            "turnNumber": 0,
+            "isDuringSearch": false,
+            "moveInfos": move_infos,
+            "policy": policy,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_exact_guess_scores_full_points() {
+        let board = Board::new(9, 9);
+        let eval = evaluate_guess(&board, "e5", "E5", &analysis(&["C3", "E5"], None));
+        assert!(eval.correct);
+        assert_eq!(eval.points, POINTS_EXACT);
+        assert_eq!(eval.engine_rank, Some(2));
+        assert_eq!(eval.actual_engine_rank, Some(2));
+    }
+
+    #[test]
+    fn test_engine_best_guess_scores_partial_points() {
+        let board = Board::new(9, 9);
+        let eval = evaluate_guess(&board, "C3", "E5", &analysis(&["C3", "E5"], None));
+        assert!(!eval.correct);
+        assert_eq!(eval.points, POINTS_ENGINE_BEST);
+    }
+
+    #[test]
+    fn test_policy_rank() {
+        let board = Board::new(2, 2);
+        // Policy layout: A2, B2, A1, B1, pass
+        let policy = vec![0.1, 0.5, 0.3, -1.0, 0.1];
+        let eval = evaluate_guess(&board, "A1", "B2", &analysis(&[], Some(policy)));
+        assert_eq!(eval.policy_rank, Some(2));
+        assert_eq!(eval.points, 0);
+    }
+
+    #[test]
+    fn test_record_advances_session() {
+        let mut session = GuessSession {
+            id: "s".to_string(),
+            game_id: "g".to_string(),
+            turn: 3,
+            score: 0,
+            guesses: 0,
+            correct: 0,
+            max_visits: None,
+        };
+        let board = Board::new(9, 9);
+        session.record(&evaluate_guess(&board, "E5", "E5", &analysis(&[], None)));
+        assert_eq!(session.turn, 4);
+        assert_eq!(session.score, POINTS_EXACT);
+        assert_eq!((session.guesses, session.correct), (1, 1));
+    }
+}
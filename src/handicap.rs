@@ -0,0 +1,125 @@
+use crate::board::Board;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Komi of a handicap game: half a point, so it can't end in a draw
+pub const HANDICAP_KOMI: f32 = 0.5;
+
+/// Standard handicap setup of GET /api/v1/handicap, ready to use in an analysis
+/// request
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HandicapSetup {
+    pub board_size: u8,
+    pub stones: u8,
+    /// Black's stones on the star points, in GTP fixed_handicap order
+    pub initial_stones: Vec<(String, String)>,
+    /// White moves first in a handicap game
+    pub initial_player: String,
+    pub komi: f32,
+}
+
+/// Most handicap stones a board takes: the corners on small and even boards,
+/// nine star points on odd boards from 9x9
+fn max_stones(board_size: u8) -> u8 {
+    match board_size {
+        0..=6 => 0,
+        7 => 4,
+        size if size % 2 == 0 => 4,
+        _ => 9,
+    }
+}
+
+/// Handicap stones on the star points as GTP's fixed_handicap places them: the
+/// corners, then the side points, with the center for odd counts
+pub fn setup(board_size: u8, stones: u8) -> Result<HandicapSetup, String> {
+    let max = max_stones(board_size);
+    if max == 0 || board_size > 25 {
+        return Err(format!(
+            "No handicap placement for {}x{} boards (sizes 7 to 25 have one)",
+            board_size, board_size
+        ));
+    }
+    if !(2..=max).contains(&stones) {
+        return Err(format!(
+            "A {}x{} board takes 2 to {} handicap stones, not {}",
+            board_size, board_size, max, stones
+        ));
+    }
+
+    // Star points are on the third line up to 12x12, the fourth from 13x13
+    let edge = if board_size < 13 { 2 } else { 3 };
+    let (low, high, mid) = (edge, board_size - 1 - edge, board_size / 2);
+    let corners = [(low, low), (high, high), (low, high), (high, low)];
+    let mut points: Vec<(u8, u8)> = corners.into_iter().take(stones.min(4) as usize).collect();
+    if stones >= 6 {
+        points.extend([(low, mid), (high, mid)]);
+    }
+    if stones >= 8 {
+        points.extend([(mid, low), (mid, high)]);
+    }
+    if stones % 2 == 1 && stones >= 5 {
+        points.push((mid, mid));
+    }
+
+    Ok(HandicapSetup {
+        board_size,
+        stones,
+        initial_stones: points
+            .into_iter()
+            .map(|(x, y)| ("B".to_string(), Board::coord_name(x, y)))
+            .collect(),
+        initial_player: "W".to_string(),
+        komi: HANDICAP_KOMI,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coords(setup: &HandicapSetup) -> Vec<&str> {
+        setup
+            .initial_stones
+            .iter()
+            .map(|(_, coord)| coord.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_19x19_follows_gtp_fixed_handicap() {
+        assert_eq!(coords(&setup(19, 2).unwrap()), ["D4", "Q16"]);
+        assert_eq!(coords(&setup(19, 3).unwrap()), ["D4", "Q16", "D16"]);
+        assert_eq!(
+            coords(&setup(19, 5).unwrap()),
+            ["D4", "Q16", "D16", "Q4", "K10"]
+        );
+        assert_eq!(
+            coords(&setup(19, 8).unwrap()),
+            ["D4", "Q16", "D16", "Q4", "D10", "Q10", "K4", "K16"]
+        );
+        let nine = setup(19, 9).unwrap();
+        assert_eq!(nine.initial_stones.len(), 9);
+        assert_eq!(nine.initial_player, "W");
+        assert_eq!(nine.komi, 0.5);
+    }
+
+    #[test]
+    fn test_small_boards_use_the_third_line() {
+        assert_eq!(coords(&setup(9, 2).unwrap()), ["C3", "G7"]);
+        assert_eq!(
+            coords(&setup(9, 5).unwrap()),
+            ["C3", "G7", "C7", "G3", "E5"]
+        );
+        assert_eq!(coords(&setup(13, 2).unwrap()), ["D4", "K10"]);
+    }
+
+    #[test]
+    fn test_impossible_handicaps_are_rejected() {
+        assert!(setup(19, 1).is_err());
+        assert!(setup(19, 10).is_err());
+        assert!(setup(7, 5).is_err());
+        assert!(setup(10, 5).is_err());
+        assert!(setup(5, 2).is_err());
+    }
+}
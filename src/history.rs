@@ -0,0 +1,154 @@
+use crate::api::{AnalysisRequest, AnalysisResponse};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A completed analysis as recorded in the history
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredAnalysis {
+    pub request_id: String,
+    /// When the analysis completed (RFC 3339, UTC)
+    pub created_at: String,
+    /// Time from receiving the request to the result, in milliseconds
+    pub duration_ms: u64,
+    pub request: AnalysisRequest,
+    pub response: AnalysisResponse,
+}
+
+/// Every completed analysis (request, response, timing) in a SQLite database, so
+/// results of batch jobs survive server restarts
+pub struct AnalysisHistory {
+    connection: Mutex<Connection>,
+}
+
+impl AnalysisHistory {
+    /// Open (or create) the database at `path`
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS analyses (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 request_id TEXT NOT NULL,
+                 created_at TEXT NOT NULL,
+                 duration_ms INTEGER NOT NULL,
+                 request TEXT NOT NULL,
+                 response TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS analyses_created_at ON analyses (created_at);",
+        )?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Record an analysis that just completed
+    pub fn record(
+        &self,
+        request: &AnalysisRequest,
+        response: &AnalysisResponse,
+        duration: Duration,
+    ) -> rusqlite::Result<()> {
+        let to_json = |value: serde_json::Result<String>| {
+            value.map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+        };
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO analyses (request_id, created_at, duration_ms, request, response)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                response.id,
+                timestamp(Utc::now()),
+                duration.as_millis() as i64,
+                to_json(serde_json::to_string(request))?,
+                to_json(serde_json::to_string(response))?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Up to `limit` analyses completed at or after `since`, oldest first
+    pub fn since(
+        &self,
+        since: DateTime<Utc>,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<StoredAnalysis>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT request_id, created_at, duration_ms, request, response FROM analyses
+             WHERE created_at >= ?1 ORDER BY created_at, id LIMIT ?2",
+        )?;
+        let rows = statement.query_map(params![timestamp(since), limit as i64], |row| {
+            Ok(StoredAnalysis {
+                request_id: row.get(0)?,
+                created_at: row.get(1)?,
+                duration_ms: row.get::<_, i64>(2)? as u64,
+                request: from_json(3, row.get(3)?)?,
+                response: from_json(4, row.get(4)?)?,
+            })
+        })?;
+        rows.collect()
+    }
+}
+
+/// A JSON column's value
+fn from_json<T: serde::de::DeserializeOwned>(column: usize, text: String) -> rusqlite::Result<T> {
+    serde_json::from_str(&text).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(column, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+/// Fixed-width UTC timestamps, so they sort as text in the order they happened
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(id: &str) -> AnalysisResponse {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "turnNumber": 0,
+            "isDuringSearch": false,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_analyses_are_listed_since_a_time() {
+        let history = AnalysisHistory::open(":memory:").unwrap();
+        let request = AnalysisRequest::default();
+        history
+            .record(&request, &response("a"), Duration::from_millis(120))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        let between = Utc::now();
+        std::thread::sleep(Duration::from_millis(5));
+        history
+            .record(&request, &response("b"), Duration::from_millis(80))
+            .unwrap();
+
+        let all = history.since(DateTime::UNIX_EPOCH, 10).unwrap();
+        let ids: Vec<&str> = all.iter().map(|a| a.request_id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+        assert_eq!(all[0].duration_ms, 120);
+        assert_eq!(all[1].response.id, "b");
+
+        let recent = history.since(between, 10).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].request_id, "b");
+        assert_eq!(history.since(DateTime::UNIX_EPOCH, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_timestamps_sort_as_text() {
+        let early = DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z").unwrap();
+        let late = DateTime::parse_from_rfc3339("2026-01-02T03:04:05.5+00:00").unwrap();
+        assert!(timestamp(early.to_utc()) < timestamp(late.to_utc()));
+        assert_eq!(timestamp(early.to_utc()), "2026-01-02T03:04:05.000Z");
+    }
+}
@@ -0,0 +1,171 @@
+use crate::api::{AnalysisRequest, ComparedMove, HumanComparisonResponse, MoveFilter, MoveInfo};
+use crate::board::Board;
+use crate::engine_pool::EnginePool;
+use crate::error::{KatagoError, Result};
+
+/// Moves listed on each side when the request doesn't say
+pub const DEFAULT_MAX_MOVES: usize = 5;
+
+/// The engine's best moves next to the most likely moves of the request's
+/// humanSLProfile. Likely moves the search didn't visit are evaluated by a second
+/// query restricted to them, so every human move gets its point loss.
+pub async fn compare(
+    engine: &EnginePool,
+    request: &AnalysisRequest,
+    profile: &str,
+    max_moves: usize,
+) -> Result<HumanComparisonResponse> {
+    let id = request.request_id.clone().unwrap_or_default();
+    let response = engine.analyze(request).await?;
+    let root = response
+        .root_info
+        .ok_or_else(|| KatagoError::ParseError("No rootInfo in the analysis".to_string()))?;
+    let human_policy = response
+        .human_policy
+        .ok_or_else(|| KatagoError::ParseError("No humanPolicy in the analysis".to_string()))?;
+    let infos = response.move_infos.unwrap_or_default();
+    let best = infos.first().map(|info| info.score_lead);
+
+    let likely = likely_moves(
+        &human_policy,
+        request.board_x_size,
+        request.board_y_size,
+        max_moves,
+    );
+    let unvisited: Vec<String> = likely
+        .iter()
+        .filter(|(coord, _)| find(&infos, coord).is_none())
+        .map(|(coord, _)| coord.clone())
+        .collect();
+    let mut restricted = Vec::new();
+    if !unvisited.is_empty() {
+        let mut only = request.clone();
+        only.request_id = Some(format!("{}-human", id));
+        only.include_policy = None;
+        only.allow_moves = Some(vec![MoveFilter {
+            player: root.current_player.clone(),
+            moves: unvisited,
+            until_depth: 1,
+        }]);
+        restricted = engine.analyze(&only).await?.move_infos.unwrap_or_default();
+    }
+
+    let engine_moves = infos
+        .iter()
+        .take(max_moves)
+        .map(|info| compared(&info.move_coord, info.human_prior, Some(info), best))
+        .collect();
+    let human_moves = likely
+        .iter()
+        .map(|(coord, prior)| {
+            let info = find(&infos, coord).or_else(|| find(&restricted, coord));
+            compared(coord, Some(*prior), info, best)
+        })
+        .collect();
+    Ok(HumanComparisonResponse {
+        id,
+        human_sl_profile: profile.to_string(),
+        current_player: root.current_player,
+        engine_moves,
+        human_moves,
+    })
+}
+
+fn find<'a>(infos: &'a [MoveInfo], coord: &str) -> Option<&'a MoveInfo> {
+    infos
+        .iter()
+        .find(|info| info.move_coord.eq_ignore_ascii_case(coord))
+}
+
+fn compared(
+    coord: &str,
+    human_prior: Option<f32>,
+    info: Option<&MoveInfo>,
+    best: Option<f32>,
+) -> ComparedMove {
+    ComparedMove {
+        move_coord: coord.to_string(),
+        human_prior,
+        visits: info.map_or(0, |info| info.visits),
+        winrate: info.map(|info| info.winrate),
+        score_lead: info.map(|info| info.score_lead),
+        point_loss: info
+            .zip(best)
+            .map(|(info, best)| (best - info.score_lead).max(0.0)),
+    }
+}
+
+/// The `count` most probable legal moves of a policy array (row-major from the top
+/// row, pass last), with their probabilities
+fn likely_moves(policy: &[f32], width: u8, height: u8, count: usize) -> Vec<(String, f32)> {
+    let points = width as usize * height as usize;
+    let mut moves: Vec<(usize, f32)> = policy
+        .iter()
+        .copied()
+        .enumerate()
+        .take(points + 1)
+        .filter(|&(_, p)| p >= 0.0)
+        .collect();
+    moves.sort_by(|a, b| b.1.total_cmp(&a.1));
+    moves
+        .into_iter()
+        .take(count)
+        .map(|(index, p)| {
+            let coord = if index == points {
+                "pass".to_string()
+            } else {
+                let x = (index % width as usize) as u8;
+                let y = height - 1 - (index / width as usize) as u8;
+                Board::coord_name(x, y)
+            };
+            (coord, p)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_likely_moves_skip_illegal_points() {
+        // 3x3: rows from the top, then pass
+        let policy = [0.1, -1.0, 0.0, 0.0, 0.4, 0.0, 0.0, 0.0, 0.3, 0.2];
+        let moves = likely_moves(&policy, 3, 3, 4);
+        let coords: Vec<&str> = moves.iter().map(|(coord, _)| coord.as_str()).collect();
+        assert_eq!(coords, ["B2", "C1", "pass", "A3"]);
+        assert!(likely_moves(&[-1.0; 10], 3, 3, 4).is_empty());
+    }
+
+    #[test]
+    fn test_point_loss_is_measured_from_the_best_move() {
+        let info = MoveInfo {
+            move_coord: "C3".to_string(),
+            visits: 12,
+            winrate: 0.4,
+            score_mean: -0.5,
+            score_stdev: 10.0,
+            score_lead: -0.5,
+            utility: 0.0,
+            utility_lcb: None,
+            lcb: 0.35,
+            prior: 0.1,
+            human_prior: Some(0.3),
+            order: 2,
+            pv: None,
+            pv_visits: None,
+            ownership: None,
+        };
+        let move_ = compared("C3", Some(0.3), Some(&info), Some(1.5));
+        assert_eq!(move_.point_loss, Some(2.0));
+        assert_eq!(move_.visits, 12);
+        // Better than the best by noise is no loss
+        assert_eq!(
+            compared("C3", None, Some(&info), Some(-1.0)).point_loss,
+            Some(0.0)
+        );
+
+        let unevaluated = compared("pass", Some(0.01), None, Some(1.5));
+        assert_eq!((unevaluated.visits, unevaluated.point_loss), (0, None));
+    }
+}
@@ -0,0 +1,86 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Kyu ranks from weakest to strongest, then dan ranks
+const RANKS: &[&str] = &[
+    "20k", "19k", "18k", "17k", "16k", "15k", "14k", "13k", "12k", "11k", "10k", "9k", "8k", "7k",
+    "6k", "5k", "4k", "3k", "2k", "1k", "1d", "2d", "3d", "4d", "5d", "6d", "7d", "8d", "9d",
+];
+
+/// Years of professional games the human SL model was trained on
+const PRO_YEARS: std::ops::RangeInclusive<u16> = 1800..=2023;
+
+/// A humanSLProfile value for `overrideSettings`
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HumanProfile {
+    pub name: String,
+    /// "rank", "preaz" or "proyear"
+    pub kind: &'static str,
+    pub description: String,
+}
+
+/// Response of GET /api/v1/human/profiles
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HumanProfilesResponse {
+    /// File names of the loaded human models
+    pub human_models: Vec<String>,
+    /// Empty when no human model is loaded
+    pub profiles: Vec<HumanProfile>,
+}
+
+fn rank_name(rank: &str) -> String {
+    let (number, kind) = rank.split_at(rank.len() - 1);
+    match kind {
+        "k" => format!("{} kyu", number),
+        _ => format!("{} dan", number),
+    }
+}
+
+/// Every profile KataGo's human SL model accepts: modern ranks, ranks before
+/// AlphaZero changed how people play, and professional play by year
+pub fn profiles() -> Vec<HumanProfile> {
+    let ranks = RANKS.iter().map(|rank| HumanProfile {
+        name: format!("rank_{}", rank),
+        kind: "rank",
+        description: format!("{} player", rank_name(rank)),
+    });
+    let preaz = RANKS.iter().map(|rank| HumanProfile {
+        name: format!("preaz_{}", rank),
+        kind: "preaz",
+        description: format!("{} player before AlphaZero", rank_name(rank)),
+    });
+    let pro_years = PRO_YEARS.map(|year| HumanProfile {
+        name: format!("proyear_{}", year),
+        kind: "proyear",
+        description: format!("Professional player of {}", year),
+    });
+    ranks.chain(preaz).chain(pro_years).collect()
+}
+
+/// Whether KataGo's human SL model accepts `name` as a humanSLProfile
+pub fn is_profile(name: &str) -> bool {
+    profiles().iter().any(|profile| profile.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_cover_ranks_and_years() {
+        let profiles = profiles();
+        assert_eq!(profiles.len(), 29 + 29 + 224);
+        let find = |name: &str| profiles.iter().find(|p| p.name == name);
+        assert_eq!(find("rank_20k").unwrap().description, "20 kyu player");
+        assert_eq!(
+            find("preaz_5k").unwrap().description,
+            "5 kyu player before AlphaZero"
+        );
+        assert_eq!(find("rank_9d").unwrap().kind, "rank");
+        assert_eq!(find("proyear_2023").unwrap().kind, "proyear");
+        assert!(find("rank_10d").is_none() && find("proyear_1799").is_none());
+        assert!(is_profile("rank_1d") && !is_profile("rank_1D"));
+    }
+}
@@ -0,0 +1,309 @@
+use crate::games::{Game, GameDatabase};
+use crate::sgf;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Maximum number of per-file errors kept on a job (the count is always exact)
+const MAX_RECORDED_ERRORS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+impl ImportStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ImportStatus::Running => "running",
+            ImportStatus::Completed => "completed",
+            ImportStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Progress of a bulk SGF import
+#[derive(Debug, Clone)]
+pub struct ImportJob {
+    pub id: String,
+    pub path: String,
+    pub status: ImportStatus,
+    pub files_scanned: usize,
+    pub games_imported: usize,
+    pub positions_indexed: usize,
+    /// Exact copies of archived games, merged instead of stored
+    pub duplicates_merged: usize,
+    /// Other suspected duplicates (e.g. rotated/mirrored copies), stored but listed
+    pub duplicates_flagged: usize,
+    pub error_count: usize,
+    pub errors: Vec<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+
+impl ImportJob {
+    pub fn new(path: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            path: path.to_string(),
+            status: ImportStatus::Running,
+            files_scanned: 0,
+            games_imported: 0,
+            positions_indexed: 0,
+            duplicates_merged: 0,
+            duplicates_flagged: 0,
+            error_count: 0,
+            errors: Vec::new(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            finished_at: None,
+        }
+    }
+
+    fn record_error(&mut self, error: String) {
+        self.error_count += 1;
+        if self.errors.len() < MAX_RECORDED_ERRORS {
+            self.errors.push(error);
+        }
+    }
+
+    fn finish(&mut self, status: ImportStatus) {
+        self.status = status;
+        self.finished_at = Some(chrono::Utc::now().to_rfc3339());
+    }
+}
+
+/// Import every SGF under `path` (a directory, a single .sgf, or a .tar/.tar.gz/.tgz
+/// archive) into the database. `report` is called after each file with the job state.
+/// Blocking; run on a blocking thread.
+pub fn run_import(
+    path: &Path,
+    db: &GameDatabase,
+    mut job: ImportJob,
+    mut report: impl FnMut(&ImportJob),
+) -> ImportJob {
+    info!("Importing SGF games from {}", path.display());
+
+    let mut import_file = |job: &mut ImportJob, name: &str, contents: &str| {
+        job.files_scanned += 1;
+        match sgf::parse_collection(contents) {
+            Ok(games) => {
+                for game in games {
+                    let insertion = db.insert(Game::from_sgf(game, Some(name.to_string())));
+                    if insertion.merged {
+                        job.duplicates_merged += 1;
+                        continue;
+                    }
+                    if insertion.duplicate.is_some() {
+                        job.duplicates_flagged += 1;
+                    }
+                    job.positions_indexed += insertion.positions_indexed;
+                    job.games_imported += 1;
+                }
+            }
+            Err(e) => job.record_error(format!("{}: {}", name, e)),
+        }
+        report(job);
+    };
+
+    let result = if is_archive(path) {
+        import_archive(path, &mut job, &mut import_file)
+    } else if path.is_dir() {
+        sgf_files(path).map(|files| {
+            for file in files {
+                let name = file.display().to_string();
+                match fs::read(&file) {
+                    Ok(bytes) => import_file(&mut job, &name, &String::from_utf8_lossy(&bytes)),
+                    Err(e) => job.record_error(format!("{}: {}", name, e)),
+                }
+            }
+        })
+    } else {
+        fs::read(path).map(|bytes| {
+            import_file(
+                &mut job,
+                &path.display().to_string(),
+                &String::from_utf8_lossy(&bytes),
+            )
+        })
+    };
+
+    match result {
+        Ok(()) => {
+            info!(
+                "SGF import {} finished: {} games, {} positions, {} duplicates merged, {} flagged, {} errors",
+                job.id,
+                job.games_imported,
+                job.positions_indexed,
+                job.duplicates_merged,
+                job.duplicates_flagged,
+                job.error_count
+            );
+            job.finish(ImportStatus::Completed);
+        }
+        Err(e) => {
+            warn!("SGF import {} failed: {}", job.id, e);
+            job.record_error(format!("{}: {}", path.display(), e));
+            job.finish(ImportStatus::Failed);
+        }
+    }
+    report(&job);
+    job
+}
+
+fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy().to_lowercase();
+    name.ends_with(".tar") || name.ends_with(".tar.gz") || name.ends_with(".tgz")
+}
+
+fn is_sgf(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("sgf"))
+}
+
+/// All .sgf files under a directory, recursively, in sorted order
+fn sgf_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if is_sgf(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn import_archive(
+    path: &Path,
+    job: &mut ImportJob,
+    import_file: &mut impl FnMut(&mut ImportJob, &str, &str),
+) -> std::io::Result<()> {
+    let file = fs::File::open(path)?;
+    let name = path.to_string_lossy().to_lowercase();
+    let reader: Box<dyn Read> = if name.ends_with(".tar") {
+        Box::new(file)
+    } else {
+        Box::new(flate2::read::GzDecoder::new(file))
+    };
+
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        if !is_sgf(&entry_path) {
+            continue;
+        }
+        let entry_name = format!("{}:{}", path.display(), entry_path.display());
+        let mut bytes = Vec::new();
+        match entry.read_to_end(&mut bytes) {
+            Ok(_) => import_file(job, &entry_name, &String::from_utf8_lossy(&bytes)),
+            Err(e) => job.record_error(format!("{}: {}", entry_name, e)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("katago-ingest-{}-{}", name, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_import_directory_recursively() {
+        let dir = temp_dir("dir");
+        fs::create_dir_all(dir.join("1846")).unwrap();
+        fs::write(dir.join("a.sgf"), "(;SZ[9];B[ee];W[cc])").unwrap();
+        fs::write(dir.join("1846/b.SGF"), "(;PB[Shusaku];B[pd])(;B[dd])").unwrap();
+        fs::write(dir.join("broken.sgf"), "(;B[ee]").unwrap();
+        fs::write(dir.join("notes.txt"), "not a game").unwrap();
+
+        let db = GameDatabase::new();
+        let mut reports = 0;
+        let job = run_import(&dir, &db, ImportJob::new("test"), |_| reports += 1);
+
+        assert_eq!(job.status, ImportStatus::Completed);
+        assert_eq!(job.files_scanned, 3);
+        assert_eq!(job.games_imported, 3);
+        assert_eq!(job.positions_indexed, 3 + 2 + 2);
+        assert_eq!(job.error_count, 1);
+        assert_eq!(db.len(), 3);
+        assert_eq!(reports, 4);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_detects_duplicates() {
+        let dir = temp_dir("dup");
+        fs::write(dir.join("a.sgf"), "(;SZ[9]PB[Lee];B[cc];W[gg])").unwrap();
+        fs::write(dir.join("b.sgf"), "(;SZ[9]PB[Lee];B[cc];W[gg])").unwrap();
+        // Same game reflected along the diagonal
+        fs::write(
+            dir.join("c.sgf"),
+            "(;SZ[9];B[cc];W[gg])(;SZ[9];B[gc];W[cg])",
+        )
+        .unwrap();
+
+        let db = GameDatabase::new();
+        let job = run_import(&dir, &db, ImportJob::new("test"), |_| {});
+        assert_eq!(job.games_imported, 2);
+        assert_eq!(job.duplicates_merged, 2);
+        assert_eq!(job.duplicates_flagged, 1);
+        assert_eq!(db.len(), 2);
+        assert_eq!(db.duplicates().len(), 3);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_tar_gz_archive() {
+        let dir = temp_dir("tar");
+        let archive_path = dir.join("games.tar.gz");
+        {
+            let file = fs::File::create(&archive_path).unwrap();
+            let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            let mut builder = tar::Builder::new(encoder);
+            let data = b"(;SZ[19];B[pd];W[dp])";
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, "pro/game1.sgf", &data[..])
+                .unwrap();
+            builder.into_inner().unwrap().finish().unwrap();
+        }
+
+        let db = GameDatabase::new();
+        let job = run_import(&archive_path, &db, ImportJob::new("test"), |_| {});
+        assert_eq!(job.status, ImportStatus::Completed);
+        assert_eq!(job.games_imported, 1);
+        assert_eq!(job.positions_indexed, 3);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_path_fails_job() {
+        let db = GameDatabase::new();
+        let job = run_import(
+            Path::new("/nonexistent/katago-games"),
+            &db,
+            ImportJob::new("test"),
+            |_| {},
+        );
+        assert_eq!(job.status, ImportStatus::Failed);
+        assert!(job.finished_at.is_some());
+    }
+}
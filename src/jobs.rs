@@ -0,0 +1,534 @@
+use crate::api::{
+    current_request_id, ApiError, JobProgress, JobRequest, JobResponse, JobStatus, ProblemDetail,
+    REQUEST_ID,
+};
+use crate::config::JobsConfig;
+use crate::store::BoundedStore;
+use axum::http::StatusCode;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::AbortHandle;
+use tracing::{info, warn, Instrument};
+
+/// Wait before running a job again after its engine died or restarted
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// One run of a job: its result as JSON, or the problem detail it failed with
+pub type JobWork = Pin<Box<dyn Future<Output = Result<serde_json::Value, ProblemDetail>> + Send>>;
+
+/// Starts a run of a job, reporting to the given progress; called again to retry
+pub type JobRunner = Box<dyn Fn(Progress) -> JobWork + Send + Sync>;
+
+/// A checked job request, ready to run
+pub struct PreparedJob {
+    /// The request as it runs (e.g. with its position resolved), for resuming it
+    /// after a restart
+    pub request: JobRequest,
+    /// Positions the job analyzes
+    pub total: usize,
+    pub runner: JobRunner,
+}
+
+/// The run of an endpoint's work, with its response as the job's result
+pub fn work<T, F>(work: F) -> JobWork
+where
+    T: Serialize,
+    F: Future<Output = Result<T, ApiError>> + Send + 'static,
+{
+    Box::pin(async move {
+        work.await
+            .and_then(|result| {
+                serde_json::to_value(result).map_err(|e| {
+                    ApiError::new(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Internal Server Error",
+                        &e.to_string(),
+                    )
+                })
+            })
+            .map_err(ApiError::into_problem)
+    })
+}
+
+/// A background job and the task running it
+#[derive(Clone)]
+struct Job {
+    response: JobResponse,
+    task: Option<AbortHandle>,
+}
+
+/// Jobs started with POST /api/v1/jobs, the oldest forgotten once full. With a
+/// database, every job is also written there, so finished ones can still be polled
+/// and unfinished ones run again after a restart.
+pub struct Jobs {
+    store: BoundedStore<Job>,
+    database: Option<JobDatabase>,
+    max_attempts: u32,
+}
+
+/// Reports how many positions of a job are analyzed
+#[derive(Clone)]
+pub struct Progress {
+    jobs: Arc<Jobs>,
+    id: String,
+}
+
+impl Progress {
+    pub fn set(&self, completed: usize) {
+        self.jobs.store.update(&self.id, |job| {
+            job.response.progress.completed = completed;
+        });
+    }
+}
+
+impl Jobs {
+    /// Jobs kept in memory only
+    #[cfg(test)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            store: BoundedStore::with_capacity(capacity),
+            database: None,
+            max_attempts: JobsConfig::default().max_attempts,
+        }
+    }
+
+    /// Jobs as configured, with the last `capacity` finished ones of the database
+    /// (if any) loaded back
+    pub fn open(capacity: usize, config: &JobsConfig) -> rusqlite::Result<Self> {
+        let database = match &config.database_path {
+            Some(path) => Some(JobDatabase::open(path, capacity)?),
+            None => None,
+        };
+        let jobs = Self {
+            store: BoundedStore::with_capacity(capacity),
+            database,
+            max_attempts: config.max_attempts.max(1),
+        };
+        if let Some(database) = &jobs.database {
+            for (response, _) in database.load()? {
+                if response.status != JobStatus::Running {
+                    jobs.store.insert(
+                        response.job_id.clone(),
+                        Job {
+                            response,
+                            task: None,
+                        },
+                    );
+                }
+            }
+        }
+        Ok(jobs)
+    }
+
+    /// Jobs the database lists as running: the server stopped before they finished
+    pub fn unfinished(&self) -> Vec<(JobResponse, JobRequest)> {
+        let Some(database) = &self.database else {
+            return Vec::new();
+        };
+        match database.load() {
+            Ok(jobs) => jobs
+                .into_iter()
+                .filter(|(response, _)| response.status == JobStatus::Running)
+                .filter_map(|(response, request)| Some((response, request?)))
+                .collect(),
+            Err(e) => {
+                warn!("Failed to read unfinished jobs: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Run `job` in the background as new job `id`
+    pub fn start(self: &Arc<Self>, id: String, job: PreparedJob) -> JobResponse {
+        let response = JobResponse {
+            job_id: id,
+            kind: job.request.kind(),
+            status: JobStatus::Running,
+            progress: JobProgress {
+                completed: 0,
+                total: job.total,
+            },
+            created_at: chrono::Utc::now().to_rfc3339(),
+            finished_at: None,
+            result: None,
+            error: None,
+        };
+        if let Some(database) = &self.database {
+            if let Err(e) = database.insert(&response, &job.request) {
+                warn!("Failed to store job {}: {}", response.job_id, e);
+            }
+        }
+        self.run(response.clone(), job.runner);
+        response
+    }
+
+    /// Run unfinished job `response` again from the start, e.g. after a restart
+    pub fn resume(self: &Arc<Self>, mut response: JobResponse, job: PreparedJob) {
+        info!("Resuming job {}", response.job_id);
+        response.progress = JobProgress {
+            completed: 0,
+            total: job.total,
+        };
+        self.run(response, job.runner);
+    }
+
+    /// Spawn the task of job `response`. A run failing because its engine died or
+    /// restarted (503) runs again, up to `max_attempts` runs. The result or problem
+    /// detail is kept on the job, unless the job was cancelled first. The work
+    /// keeps the X-Request-Id of the request starting it.
+    fn run(self: &Arc<Self>, response: JobResponse, runner: JobRunner) {
+        let id = response.job_id.clone();
+        self.store.insert(
+            id.clone(),
+            Job {
+                response,
+                task: None,
+            },
+        );
+
+        let jobs = self.clone();
+        let job_id = id.clone();
+        let run = async move {
+            let mut attempt = 1;
+            let outcome = loop {
+                let outcome = runner(jobs.progress(&job_id)).await;
+                match &outcome {
+                    Err(problem)
+                        if problem.status == StatusCode::SERVICE_UNAVAILABLE.as_u16()
+                            && attempt < jobs.max_attempts =>
+                    {
+                        warn!(
+                            "Job {} failed (attempt {}/{}), running it again: {}",
+                            job_id, attempt, jobs.max_attempts, problem.detail
+                        );
+                        tokio::time::sleep(RETRY_DELAY).await;
+                        attempt += 1;
+                    }
+                    _ => break outcome,
+                }
+            };
+            jobs.finish(&job_id, |response| match outcome {
+                Ok(result) => {
+                    response.status = JobStatus::Completed;
+                    response.progress.completed = response.progress.total;
+                    response.result = Some(result);
+                }
+                Err(problem) => {
+                    response.status = JobStatus::Failed;
+                    response.error = Some(problem);
+                }
+            });
+        };
+        let task = tokio::spawn(
+            REQUEST_ID
+                .scope(current_request_id(), run)
+                .in_current_span(),
+        );
+        self.store.update(&id, |job| {
+            if job.response.status == JobStatus::Running {
+                job.task = Some(task.abort_handle());
+            }
+        });
+    }
+
+    fn progress(self: &Arc<Self>, id: &str) -> Progress {
+        Progress {
+            jobs: self.clone(),
+            id: id.to_string(),
+        }
+    }
+
+    /// Apply `outcome` to job `id` if it is still running, and write it to the
+    /// database. The finished job, or None if it already finished.
+    fn finish(&self, id: &str, outcome: impl FnOnce(&mut JobResponse)) -> Option<JobResponse> {
+        let finished = self.store.update(id, |job| {
+            if job.response.status != JobStatus::Running {
+                return None;
+            }
+            outcome(&mut job.response);
+            job.response.finished_at = Some(chrono::Utc::now().to_rfc3339());
+            if let Some(task) = job.task.take() {
+                task.abort();
+            }
+            Some(job.response.clone())
+        })??;
+        if let Some(database) = &self.database {
+            if let Err(e) = database.finish(&finished) {
+                warn!("Failed to store job {}: {}", id, e);
+            }
+        }
+        Some(finished)
+    }
+
+    /// Job `id`, or 404 Job Not Found
+    pub fn get(&self, id: &str) -> Result<JobResponse, ApiError> {
+        self.store
+            .get(id)
+            .map(|job| job.response)
+            .ok_or_else(|| not_found(id))
+    }
+
+    /// Stop running job `id` and mark it cancelled. 409 Job Not Running if it
+    /// already finished.
+    pub fn cancel(&self, id: &str) -> Result<JobResponse, ApiError> {
+        self.get(id)?;
+        self.finish(id, |response| response.status = JobStatus::Cancelled)
+            .ok_or_else(|| {
+                ApiError::new(
+                    StatusCode::CONFLICT,
+                    "Job Not Running",
+                    &format!("Job {} is no longer running", id),
+                )
+            })
+    }
+}
+
+fn not_found(id: &str) -> ApiError {
+    ApiError::new(
+        StatusCode::NOT_FOUND,
+        "Job Not Found",
+        &format!("No job with id {}", id),
+    )
+}
+
+/// Jobs in a SQLite database: each job's state, and the request of unfinished ones
+struct JobDatabase {
+    connection: Mutex<Connection>,
+}
+
+impl JobDatabase {
+    /// Open (or create) the database at `path`, keeping the last `capacity`
+    /// finished jobs
+    fn open(path: &str, capacity: usize) -> rusqlite::Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                 seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                 id TEXT NOT NULL UNIQUE,
+                 finished INTEGER NOT NULL,
+                 job TEXT NOT NULL,
+                 request TEXT
+             );",
+        )?;
+        connection.execute(
+            "DELETE FROM jobs WHERE finished = 1 AND seq NOT IN
+                 (SELECT seq FROM jobs WHERE finished = 1 ORDER BY seq DESC LIMIT ?1)",
+            params![capacity as i64],
+        )?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn insert(&self, job: &JobResponse, request: &JobRequest) -> rusqlite::Result<()> {
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO jobs (id, finished, job, request) VALUES (?1, 0, ?2, ?3)",
+            params![job.job_id, to_json(job)?, to_json(request)?],
+        )?;
+        Ok(())
+    }
+
+    /// Record the final state of a job; its request is no longer needed
+    fn finish(&self, job: &JobResponse) -> rusqlite::Result<()> {
+        self.connection.lock().unwrap().execute(
+            "UPDATE jobs SET finished = 1, job = ?2, request = NULL WHERE id = ?1",
+            params![job.job_id, to_json(job)?],
+        )?;
+        Ok(())
+    }
+
+    /// Every stored job, oldest first, with its request while unfinished
+    fn load(&self) -> rusqlite::Result<Vec<(JobResponse, Option<JobRequest>)>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT job, request FROM jobs ORDER BY seq")?;
+        let rows = statement.query_map([], |row| {
+            let request: Option<String> = row.get(1)?;
+            Ok((
+                from_json(0, row.get(0)?)?,
+                request.map(|text| from_json(1, text)).transpose()?,
+            ))
+        })?;
+        rows.collect()
+    }
+
+    #[cfg(test)]
+    fn request(&self, id: &str) -> rusqlite::Result<Option<String>> {
+        use rusqlite::OptionalExtension;
+        self.connection
+            .lock()
+            .unwrap()
+            .query_row("SELECT request FROM jobs WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map(Option::flatten)
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> rusqlite::Result<String> {
+    serde_json::to_string(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+/// A JSON column's value
+fn from_json<T: serde::de::DeserializeOwned>(column: usize, text: String) -> rusqlite::Result<T> {
+    serde_json::from_str(&text).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(column, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ReviewRequest;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    async fn finished(jobs: &Jobs, id: &str) -> JobResponse {
+        for _ in 0..300 {
+            let job = jobs.get(id).map_err(ApiError::into_problem).unwrap();
+            if job.status != JobStatus::Running {
+                return job;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("job {} did not finish", id);
+    }
+
+    fn review() -> JobRequest {
+        JobRequest::Review(ReviewRequest {
+            sgf: "(;SZ[9];B[ee])".to_string(),
+            ..Default::default()
+        })
+    }
+
+    fn prepared<T: Serialize + Send + 'static>(
+        run: impl Fn(Progress) -> Result<T, ApiError> + Send + Sync + 'static,
+    ) -> PreparedJob {
+        PreparedJob {
+            request: review(),
+            total: 3,
+            runner: Box::new(move |progress| {
+                let outcome = run(progress);
+                work(async move { outcome })
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jobs_keep_results_and_problems() {
+        let jobs = Arc::new(Jobs::with_capacity(10));
+        jobs.start(
+            "a".to_string(),
+            prepared(|progress| {
+                progress.set(1);
+                Ok(vec![1, 2, 3])
+            }),
+        );
+        let done = finished(&jobs, "a").await;
+        assert_eq!(done.status, JobStatus::Completed);
+        assert_eq!(done.progress.completed, 3);
+        assert_eq!(done.result, Some(serde_json::json!([1, 2, 3])));
+        assert!(done.finished_at.is_some());
+
+        jobs.start(
+            "b".to_string(),
+            prepared(|_| Err::<(), _>(ApiError::new(StatusCode::BAD_REQUEST, "Bad", "no"))),
+        );
+        let failed = finished(&jobs, "b").await;
+        assert_eq!(failed.status, JobStatus::Failed);
+        assert_eq!(failed.error.unwrap().status, 400);
+        assert!(jobs.get("c").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_jobs_stay_cancelled() {
+        let jobs = Arc::new(Jobs::with_capacity(10));
+        jobs.start(
+            "a".to_string(),
+            PreparedJob {
+                request: review(),
+                total: 1,
+                runner: Box::new(|_| {
+                    work(async {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        Ok(())
+                    })
+                }),
+            },
+        );
+        let cancelled = jobs.cancel("a").map_err(ApiError::into_problem).unwrap();
+        assert_eq!(cancelled.status, JobStatus::Cancelled);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let job = jobs.get("a").map_err(ApiError::into_problem).unwrap();
+        assert_eq!(job.status, JobStatus::Cancelled);
+        assert!(jobs.cancel("a").is_err());
+        assert!(jobs.cancel("missing").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_runs_failed_by_the_engine_are_retried() {
+        let jobs = Arc::new(Jobs::with_capacity(10));
+        let runs = Arc::new(AtomicU32::new(0));
+        let counted = runs.clone();
+        jobs.start(
+            "a".to_string(),
+            prepared(move |_| match counted.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(ApiError::new(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Engine Restarted",
+                    "restarted",
+                )),
+                _ => Ok("done"),
+            }),
+        );
+        let done = finished(&jobs, "a").await;
+        assert_eq!(done.status, JobStatus::Completed);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stored_jobs_outlive_the_server() {
+        let path = std::env::temp_dir().join(format!("katago-jobs-{}.db", uuid::Uuid::new_v4()));
+        let config = JobsConfig {
+            database_path: Some(path.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+
+        let jobs = Arc::new(Jobs::open(10, &config).unwrap());
+        jobs.start("done".to_string(), prepared(|_| Ok(1)));
+        finished(&jobs, "done").await;
+        let hanging = |_| work(std::future::pending::<Result<(), ApiError>>());
+        jobs.start(
+            "running".to_string(),
+            PreparedJob {
+                request: review(),
+                total: 2,
+                runner: Box::new(hanging),
+            },
+        );
+        let database = jobs.database.as_ref().unwrap();
+        assert!(database.request("done").unwrap().is_none());
+        assert!(database.request("running").unwrap().is_some());
+        drop(jobs);
+
+        // After a restart the finished job is still there, and the other one is
+        // handed back to run again
+        let jobs = Arc::new(Jobs::open(10, &config).unwrap());
+        let done = jobs.get("done").map_err(ApiError::into_problem).unwrap();
+        assert_eq!(done.result, Some(serde_json::json!(1)));
+        assert!(jobs.get("running").is_err());
+        let unfinished = jobs.unfinished();
+        assert_eq!(unfinished.len(), 1);
+        let (response, request) = unfinished.into_iter().next().unwrap();
+        assert_eq!(response.job_id, "running");
+        assert!(matches!(request, JobRequest::Review(_)));
+        jobs.resume(response, prepared(|_| Ok(2)));
+        let resumed = finished(&jobs, "running").await;
+        assert_eq!(resumed.status, JobStatus::Completed);
+        assert!(jobs.unfinished().is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,337 @@
+use crate::api::{BoardCorner, JosekiContinuation, JosekiMatch};
+use crate::board::{Board, Stone};
+
+/// The joseki library. Each move is written from its corner: `1` for the player who
+/// played first in the corner or `2` for the other one, then the distance from the
+/// side edge and from the top or bottom edge as letters (`a` is the first line, so
+/// `dd` is the 4-4 point). Every prefix of a line is listed with its own name, and
+/// mirror images across the corner's diagonal match too.
+const LIBRARY: &[(&str, &str)] = &[
+    ("4-4 point", "1dd"),
+    ("4-4 point, low approach", "1dd 2cf"),
+    (
+        "4-4 point, low approach, small knight response",
+        "1dd 2cf 1fc",
+    ),
+    ("4-4 point, high approach", "1dd 2df"),
+    ("4-4 point, 3-3 invasion", "1dd 2cc"),
+    ("4-4 point, 3-3 invasion, block", "1dd 2cc 1dc"),
+    ("4-4 point, 3-3 invasion, block, hane", "1dd 2cc 1dc 2cd"),
+    ("4-4 point, small knight enclosure", "1dd 1cf"),
+    ("4-4 point, large knight enclosure", "1dd 1cg"),
+    ("4-4 point, one-space enclosure", "1dd 1df"),
+    ("3-4 point", "1dc"),
+    ("3-4 point, small knight approach", "1dc 2ce"),
+    (
+        "3-4 point, small knight approach, attachment",
+        "1dc 2ce 1cd",
+    ),
+    ("3-4 point, one-space high approach", "1dc 2de"),
+    ("3-4 point, small knight enclosure", "1dc 1ce"),
+    ("3-4 point, one-space enclosure", "1dc 1de"),
+    ("3-3 point", "1cc"),
+    ("3-3 point, shoulder hit", "1cc 2dd"),
+    ("5-3 point", "1ec"),
+    ("5-3 point, approach at the 3-4 point", "1ec 2cd"),
+    ("5-4 point", "1ed"),
+];
+
+/// Moves up to this line from both edges belong to a corner (less on small boards,
+/// where corners end at the middle)
+const CORNER_SIZE: u8 = 7;
+
+const CORNERS: [BoardCorner; 4] = [
+    BoardCorner::TopLeft,
+    BoardCorner::TopRight,
+    BoardCorner::BottomLeft,
+    BoardCorner::BottomRight,
+];
+
+/// A move seen from its corner: whether the corner's first player made it, and its
+/// distances from the side and the top or bottom edge
+type LocalMove = (bool, u8, u8);
+
+fn parse_line(line: &str) -> Vec<LocalMove> {
+    line.split_whitespace()
+        .map(|mv| {
+            let bytes = mv.as_bytes();
+            (bytes[0] == b'1', bytes[1] - b'a', bytes[2] - b'a')
+        })
+        .collect()
+}
+
+/// Maps board points to distances from one corner's edges and back
+struct CornerFrame {
+    corner: BoardCorner,
+    width: u8,
+    height: u8,
+    size_x: u8,
+    size_y: u8,
+}
+
+impl CornerFrame {
+    fn new(corner: BoardCorner, width: u8, height: u8) -> Self {
+        Self {
+            corner,
+            width,
+            height,
+            size_x: CORNER_SIZE.min(width / 2),
+            size_y: CORNER_SIZE.min(height / 2),
+        }
+    }
+
+    fn is_right(&self) -> bool {
+        matches!(
+            self.corner,
+            BoardCorner::TopRight | BoardCorner::BottomRight
+        )
+    }
+
+    fn is_top(&self) -> bool {
+        matches!(self.corner, BoardCorner::TopLeft | BoardCorner::TopRight)
+    }
+
+    /// Distances of board point (x, y) from the corner's edges, if it is in the corner
+    fn to_local(&self, x: u8, y: u8) -> Option<(u8, u8)> {
+        let a = if self.is_right() {
+            self.width - 1 - x
+        } else {
+            x
+        };
+        let b = if self.is_top() {
+            self.height - 1 - y
+        } else {
+            y
+        };
+        (a < self.size_x && b < self.size_y).then_some((a, b))
+    }
+
+    /// Board point at distances (a, b) from the corner's edges, if it is in the corner
+    fn to_board(&self, a: u8, b: u8) -> Option<(u8, u8)> {
+        if a >= self.size_x || b >= self.size_y {
+            return None;
+        }
+        let x = if self.is_right() {
+            self.width - 1 - a
+        } else {
+            a
+        };
+        let y = if self.is_top() {
+            self.height - 1 - b
+        } else {
+            b
+        };
+        Some((x, y))
+    }
+}
+
+/// Known josekis the corners of a board follow after `moves` ([color, coord] pairs),
+/// with the library's continuations. Corners without moves, with setup stones, or
+/// whose moves left the library are not listed.
+pub fn recognize(
+    width: u8,
+    height: u8,
+    initial_stones: &[Vec<String>],
+    moves: &[Vec<String>],
+) -> Vec<JosekiMatch> {
+    let board = Board::new(width, height);
+    let point = |coord: &str| board.parse_point(coord).ok();
+    let setup: Vec<(u8, u8)> = initial_stones
+        .iter()
+        .filter_map(|stone| point(&stone[1]))
+        .collect();
+    let played: Vec<(Stone, (u8, u8))> = moves
+        .iter()
+        .filter_map(|mv| Some((Stone::parse(&mv[0])?, point(&mv[1])?)))
+        .collect();
+
+    let mut matches = Vec::new();
+    for corner in CORNERS {
+        let frame = CornerFrame::new(corner, width, height);
+        if setup.iter().any(|&(x, y)| frame.to_local(x, y).is_some()) {
+            continue;
+        }
+        let local: Vec<(Stone, u8, u8)> = played
+            .iter()
+            .filter_map(|&(stone, (x, y))| {
+                let (a, b) = frame.to_local(x, y)?;
+                Some((stone, a, b))
+            })
+            .collect();
+        let Some(&(first, _, _)) = local.first() else {
+            continue;
+        };
+        let sequence: Vec<LocalMove> = local
+            .iter()
+            .map(|&(stone, a, b)| (stone == first, a, b))
+            .collect();
+        if let Some(found) = match_corner(&frame, first, &sequence) {
+            matches.push(found);
+        }
+    }
+    matches
+}
+
+/// The library line `sequence` is, in either orientation, and the lines one move
+/// longer as continuations
+fn match_corner(frame: &CornerFrame, first: Stone, sequence: &[LocalMove]) -> Option<JosekiMatch> {
+    let mut name = None;
+    let mut continuations: Vec<JosekiContinuation> = Vec::new();
+    for mirrored in [false, true] {
+        let oriented: Vec<LocalMove> = sequence
+            .iter()
+            .map(|&(is_first, a, b)| {
+                if mirrored {
+                    (is_first, b, a)
+                } else {
+                    (is_first, a, b)
+                }
+            })
+            .collect();
+        for &(line_name, line) in LIBRARY {
+            let line = parse_line(line);
+            if line == oriented {
+                name.get_or_insert(line_name);
+            } else if line.len() == oriented.len() + 1 && line.starts_with(&oriented) {
+                let (is_first, a, b) = line[oriented.len()];
+                let (a, b) = if mirrored { (b, a) } else { (a, b) };
+                let Some((x, y)) = frame.to_board(a, b) else {
+                    continue;
+                };
+                let player = if is_first { first } else { first.opponent() };
+                let continuation = JosekiContinuation {
+                    player: player.as_str().to_string(),
+                    move_coord: Board::coord_name(x, y),
+                    name: line_name.to_string(),
+                };
+                // Symmetric lines meet themselves in the mirror
+                if !continuations.iter().any(|c| {
+                    c.player == continuation.player && c.move_coord == continuation.move_coord
+                }) {
+                    continuations.push(continuation);
+                }
+            }
+        }
+    }
+    Some(JosekiMatch {
+        corner: frame.corner,
+        name: name?.to_string(),
+        continuations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(list: &[(&str, &str)]) -> Vec<Vec<String>> {
+        list.iter()
+            .map(|(color, coord)| vec![color.to_string(), coord.to_string()])
+            .collect()
+    }
+
+    #[test]
+    fn test_library_lines_are_well_formed() {
+        for (name, line) in LIBRARY {
+            let parsed = parse_line(line);
+            assert!(parsed[0].0, "{} starts with the first player", name);
+            assert!(
+                parsed
+                    .iter()
+                    .all(|&(_, a, b)| a < CORNER_SIZE && b < CORNER_SIZE),
+                "{} stays in the corner",
+                name
+            );
+            let prefix = &parsed[..parsed.len() - 1];
+            assert!(
+                prefix.is_empty() || LIBRARY.iter().any(|(_, l)| parse_line(l) == prefix),
+                "{} has its prefix listed",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_corners_are_recognized_in_any_orientation() {
+        // Top-right: 4-4 point, low approach, small knight response, with a move in
+        // another corner in between
+        let found = recognize(
+            19,
+            19,
+            &[],
+            &moves(&[
+                ("b", "Q16"),
+                ("w", "R14"),
+                ("b", "D4"),
+                ("w", "pass"),
+                ("b", "O17"),
+            ]),
+        );
+        assert_eq!(found.len(), 2);
+        let top_right = found
+            .iter()
+            .find(|m| m.corner == BoardCorner::TopRight)
+            .unwrap();
+        assert_eq!(
+            top_right.name,
+            "4-4 point, low approach, small knight response"
+        );
+        assert!(top_right.continuations.is_empty());
+
+        // The same shape mirrored across the corner's diagonal
+        let found = recognize(
+            19,
+            19,
+            &[],
+            &moves(&[("w", "Q16"), ("b", "O17"), ("w", "R14")]),
+        );
+        assert_eq!(
+            found[0].name,
+            "4-4 point, low approach, small knight response"
+        );
+    }
+
+    #[test]
+    fn test_continuations_cover_both_sides_of_a_symmetric_corner() {
+        let found = recognize(19, 19, &[], &moves(&[("b", "D4")]));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].corner, BoardCorner::BottomLeft);
+        assert_eq!(found[0].name, "4-4 point");
+        let next: Vec<_> = found[0]
+            .continuations
+            .iter()
+            .map(|c| format!("{} {}", c.player, c.move_coord))
+            .collect();
+        for expected in ["W C6", "W F3", "W C3", "B C6", "B F3"] {
+            assert!(next.contains(&expected.to_string()), "{:?}", next);
+        }
+        assert_eq!(next.iter().filter(|c| *c == "W C3").count(), 1);
+    }
+
+    #[test]
+    fn test_unknown_or_set_up_corners_are_not_tagged() {
+        assert!(recognize(19, 19, &[], &moves(&[("b", "B2")])).is_empty());
+        // Off the library's lines after a known start
+        assert!(recognize(19, 19, &[], &moves(&[("b", "D4"), ("w", "A1")])).is_empty());
+        let handicap = moves(&[("B", "D4")]);
+        assert!(recognize(19, 19, &handicap, &moves(&[("w", "C3")])).is_empty());
+    }
+
+    #[test]
+    fn test_small_boards_have_small_corners() {
+        // On 9x9 the 3-6 point is past the middle: C6 is a 3-4 point of the top-left
+        let found = recognize(9, 9, &[], &moves(&[("b", "D4"), ("w", "C6")]));
+        let names: Vec<_> = found.iter().map(|m| (m.corner, m.name.as_str())).collect();
+        assert_eq!(
+            names,
+            [
+                (BoardCorner::TopLeft, "3-4 point"),
+                (BoardCorner::BottomLeft, "4-4 point")
+            ]
+        );
+        assert!(found[1]
+            .continuations
+            .iter()
+            .all(|c| c.move_coord != "C6" && c.move_coord != "F3"));
+    }
+}
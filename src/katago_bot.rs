@@ -23,7 +23,6 @@ static INFO_SCORELEAD_RE: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"scoreLead\s+([^\s]+)\s+").unwrap());
 
 #[derive(Debug, Clone)]
-#[allow(dead_code)] // Used by legacy select_move, may be useful for future features
 pub struct MoveCandidate {
     pub mv: String,
     pub psv: i32,
@@ -53,9 +52,10 @@ pub struct KatagoBot {
     process: Arc<StdMutex<Option<Child>>>,
     stdin: Arc<StdMutex<Option<ChildStdin>>>,
     response_rx: Arc<TokioMutex<mpsc::UnboundedReceiver<String>>>,
-    #[allow(dead_code)] // Used by legacy select_move, may be useful for future features
     last_move_color: Arc<TokioMutex<String>>,
     diagnostics: Arc<RwLock<Diagnostics>>,
+    /// Held for a whole select_move/score: GTP has one board, so games can't overlap
+    game: TokioMutex<()>,
 }
 
 impl KatagoBot {
@@ -69,6 +69,7 @@ impl KatagoBot {
             response_rx: Arc::new(TokioMutex::new(response_rx)),
             last_move_color: Arc::new(TokioMutex::new(String::new())),
             diagnostics: Arc::new(RwLock::new(Diagnostics::default())),
+            game: TokioMutex::new(()),
         };
 
         bot.start_process(response_tx)?;
@@ -107,13 +108,16 @@ impl KatagoBot {
         *self.stdin.lock().unwrap() = Some(stdin);
         *self.process.lock().unwrap() = Some(cmd);
 
-        // Spawn stderr reader thread
+        // Spawn stderr reader thread; with ogsChatToStderr and logSearchInfo in the
+        // GTP config, KataGo reports its evaluation and candidates here
+        let diagnostics = Arc::clone(&self.diagnostics);
         thread::spawn(move || {
             let reader = BufReader::new(stderr);
             for line in reader.lines() {
                 match line {
                     Ok(line) => {
                         debug!("KataGo stderr: {}", line);
+                        Self::handle_response(&line, &diagnostics);
                     }
                     Err(e) => {
                         error!("Error reading stderr from KataGo: {}", e);
@@ -215,6 +219,19 @@ impl KatagoBot {
         Ok(())
     }
 
+    /// Send a GTP command and wait for its `=` reply
+    async fn command(&self, cmd: &str) -> Result<String> {
+        self.send_command(cmd)?;
+        self.wait_for_response(self.config.move_timeout_secs).await
+    }
+
+    /// Discard output left over from an earlier command, such as kata-analyze lines
+    async fn drain(&self) {
+        let mut rx = self.response_rx.lock().await;
+        while rx.try_recv().is_ok() {}
+    }
+
+    /// Next GTP reply: the `=` line, or an error for a `?` line
     async fn wait_for_response(&self, timeout_secs: u64) -> Result<String> {
         let duration = Duration::from_secs(timeout_secs);
 
@@ -222,9 +239,12 @@ impl KatagoBot {
             loop {
                 let mut rx = self.response_rx.lock().await;
                 if let Some(response) = rx.recv().await {
-                    if response.starts_with('=') || response.starts_with("info ") {
+                    if response.starts_with('=') {
                         return Ok(response);
                     }
+                    if let Some(message) = response.strip_prefix('?') {
+                        return Err(KatagoError::ResponseError(message.trim().to_string()));
+                    }
                 } else {
                     return Err(KatagoError::ProcessDied);
                 }
@@ -234,6 +254,32 @@ impl KatagoBot {
         .map_err(|_| KatagoError::Timeout(timeout_secs))?
     }
 
+    /// A whole GTP reply as KataGo wrote it: its `=` or `?` line and any lines
+    /// after it, up to the empty line that ends every reply
+    async fn wait_for_raw_response(&self, timeout_secs: u64) -> Result<String> {
+        let duration = Duration::from_secs(timeout_secs);
+
+        timeout(duration, async {
+            let mut rx = self.response_rx.lock().await;
+            let mut reply: Vec<String> = Vec::new();
+            loop {
+                let Some(line) = rx.recv().await else {
+                    return Err(KatagoError::ProcessDied);
+                };
+                if !reply.is_empty() {
+                    if line.trim().is_empty() {
+                        return Ok(reply.join("\n"));
+                    }
+                    reply.push(line);
+                } else if line.starts_with('=') || line.starts_with('?') {
+                    reply.push(line);
+                }
+            }
+        })
+        .await
+        .map_err(|_| KatagoError::Timeout(timeout_secs))?
+    }
+
     async fn wait_for_analysis_response(&self, timeout_secs: u64) -> Result<String> {
         let duration = Duration::from_secs(timeout_secs);
         let mut collected_lines = Vec::new();
@@ -279,7 +325,7 @@ impl KatagoBot {
         .map_err(|_| KatagoError::Timeout(timeout_secs))?
     }
 
-    fn set_rules(&self, komi: f32, config: &RequestConfig) -> Result<()> {
+    async fn set_rules(&self, komi: f32, config: &RequestConfig) -> Result<()> {
         let rules = if config.client.as_deref() == Some("kifucam") {
             "chinese"
         } else if komi != komi.floor() {
@@ -293,44 +339,66 @@ impl KatagoBot {
             "japanese"
         };
 
-        self.send_command(&format!("kata-set-rules {}", rules))?;
+        self.command(&format!("kata-set-rules {}", rules)).await?;
         Ok(())
     }
 
-    fn set_komi(&self, komi: f32) -> Result<()> {
-        self.send_command(&format!("komi {}", komi))?;
+    async fn set_komi(&self, komi: f32) -> Result<()> {
+        self.command(&format!("komi {}", komi)).await?;
         Ok(())
     }
 
-    #[allow(dead_code)] // Legacy method, may be useful for future features
-    pub async fn select_move(&self, moves: &[String], config: &RequestConfig) -> Result<String> {
-        info!("Selecting move for position with {} moves", moves.len());
-
-        // Reset diagnostics
-        {
-            let mut diag = self.diagnostics.write().unwrap();
-            *diag = Diagnostics::default();
-        }
-
+    /// Set up the position after `moves` (alternating from Black); returns the
+    /// color to play next
+    async fn play_moves(&self, moves: &[String], config: &RequestConfig) -> Result<&'static str> {
+        self.drain().await;
         let komi = config.komi.unwrap_or(7.5);
-        self.set_komi(komi)?;
+        self.set_komi(komi).await?;
 
         // Reset board
-        self.send_command("clear_board")?;
-        self.send_command("clear_cache")?;
+        self.command("clear_board").await?;
+        self.command("clear_cache").await?;
 
-        self.set_rules(komi, config)?;
+        self.set_rules(komi, config).await?;
 
         // Play moves
         let mut color = "b";
         for (idx, mv) in moves.iter().enumerate() {
             // Skip early passes (before move 20) for chinese handicap komi
             if mv != "pass" || idx > 20 {
-                self.send_command(&format!("play {} {}", color, mv))?;
+                self.command(&format!("play {} {}", color, mv))
+                    .await
+                    .map_err(|e| match e {
+                        KatagoError::ResponseError(msg) => KatagoError::InvalidCommand(format!(
+                            "move {} ({}) rejected: {}",
+                            idx + 1,
+                            mv,
+                            msg
+                        )),
+                        other => other,
+                    })?;
             }
             color = if color == "b" { "w" } else { "b" };
         }
+        Ok(color)
+    }
+
+    /// The bot's move after `moves`, with what KataGo reported while choosing it
+    pub async fn select_move(
+        &self,
+        moves: &[String],
+        config: &RequestConfig,
+    ) -> Result<(String, Diagnostics)> {
+        let _game = self.game.lock().await;
+        info!("Selecting move for position with {} moves", moves.len());
+
+        // Reset diagnostics
+        {
+            let mut diag = self.diagnostics.write().unwrap();
+            *diag = Diagnostics::default();
+        }
 
+        let color = self.play_moves(moves, config).await?;
         *self.last_move_color.lock().await = color.to_string();
 
         // Request move
@@ -344,13 +412,16 @@ impl KatagoBot {
         if let Some(stripped) = response.strip_prefix('=') {
             let mv = stripped.trim().to_string();
             info!("KataGo selected move: {}", mv);
-            Ok(mv)
+            let mut diagnostics = self.diagnostics();
+            diagnostics.bot_move = mv.clone();
+            Ok((mv, diagnostics))
         } else {
             Err(KatagoError::ParseError("Invalid move response".to_string()))
         }
     }
 
     pub async fn score(&self, moves: &[String], config: &RequestConfig) -> Result<Vec<f32>> {
+        let _game = self.game.lock().await;
         info!("Getting score for position with {} moves", moves.len());
 
         // Reset diagnostics
@@ -360,24 +431,7 @@ impl KatagoBot {
         }
 
         let ownership = config.ownership.unwrap_or(true);
-        let komi = config.komi.unwrap_or(7.5);
-
-        self.set_komi(komi)?;
-
-        // Reset board
-        self.send_command("clear_board")?;
-        self.send_command("clear_cache")?;
-
-        self.set_rules(komi, config)?;
-
-        // Play moves
-        let mut color = "b";
-        for (idx, mv) in moves.iter().enumerate() {
-            if mv != "pass" || idx > 20 {
-                self.send_command(&format!("play {} {}", color, mv))?;
-            }
-            color = if color == "b" { "w" } else { "b" };
-        }
+        self.play_moves(moves, config).await?;
 
         // Request ownership analysis
         // kata-analyze runs continuously until we send 'stop' or another command
@@ -439,16 +493,25 @@ impl KatagoBot {
         Ok(probs)
     }
 
-    pub fn diagnostics(&self) -> Diagnostics {
-        self.diagnostics.read().unwrap().clone()
+    /// Send a GTP command as given and return KataGo's reply unchanged, for
+    /// operators inspecting or tuning the engine (e.g. kata-get-param)
+    pub async fn raw_command(&self, cmd: &str) -> Result<String> {
+        let _game = self.game.lock().await;
+        info!("Forwarding GTP command: {}", cmd);
+        self.drain().await;
+        self.send_command(cmd)?;
+        self.wait_for_raw_response(self.config.move_timeout_secs)
+            .await
     }
 
-    pub fn model_path(&self) -> &str {
-        &self.config.model_path
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.diagnostics.read().unwrap().clone()
     }
 
     pub async fn clear_cache(&self) -> Result<()> {
+        let _game = self.game.lock().await;
         info!("Clearing KataGo cache");
+        self.drain().await;
         self.send_command("clear_cache")?;
         // Wait for acknowledgment
         self.wait_for_response(5).await?;
@@ -493,6 +556,7 @@ mod integration_tests {
             config_path: env::var("KATAGO_CONFIG_PATH")
                 .unwrap_or_else(|_| "./gtp_config.cfg".to_string()),
             move_timeout_secs: 20,
+            ..Default::default()
         };
 
         // Test that process can be created without immediate crash
@@ -532,6 +596,7 @@ mod integration_tests {
             config_path: env::var("KATAGO_CONFIG_PATH")
                 .unwrap_or_else(|_| "./gtp_config.cfg".to_string()),
             move_timeout_secs: 5,
+            ..Default::default()
         };
 
         // This should fail, but we should see stderr logs
@@ -553,6 +618,7 @@ mod integration_tests {
             human_model_path: None,
             config_path: "/nonexistent/config.cfg".to_string(),
             move_timeout_secs: 20,
+            ..Default::default()
         };
 
         let result = KatagoBot::new(config);
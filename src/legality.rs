@@ -0,0 +1,295 @@
+use crate::analysis_engine::AnalysisEngine;
+use crate::api::{AnalysisRequest, Rules};
+use crate::board::{Board, BoardError, Stone};
+use crate::rules::RuleSet;
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Which earlier positions a move may not recreate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KoRule {
+    /// Only the position before the opponent's last move: no immediate recapture
+    Simple,
+    /// Any earlier stone layout
+    Positional,
+    /// Any earlier stone layout with the same player to move
+    Situational,
+}
+
+/// The parts of a ruleset that decide whether a move is legal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveRules {
+    pub ko_rule: KoRule,
+    pub multi_stone_suicide_legal: bool,
+}
+
+impl MoveRules {
+    /// The move rules of named or spelled-out rules. Names missing from RULE_SETS
+    /// and unset fields get the most lenient rules, simple ko and multi-stone
+    /// suicide, so nothing KataGo would accept is rejected.
+    pub fn of(rules: &Rules) -> Self {
+        let (ko_rule, multi_stone_suicide_legal) = match rules {
+            Rules::Named(name) => match RuleSet::find(name) {
+                Some(rules) => (Some(rules.ko_rule), Some(rules.multi_stone_suicide_legal)),
+                None => (None, None),
+            },
+            Rules::Custom(settings) => (
+                settings.ko_rule.as_deref(),
+                settings.multi_stone_suicide_legal,
+            ),
+        };
+        let ko_rule = match ko_rule.map(str::to_ascii_uppercase).as_deref() {
+            Some("POSITIONAL") => KoRule::Positional,
+            Some("SITUATIONAL") => KoRule::Situational,
+            _ => KoRule::Simple,
+        };
+        Self {
+            ko_rule,
+            multi_stone_suicide_legal: multi_stone_suicide_legal.unwrap_or(true),
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum LegalityError {
+    #[error("Invalid initial stones: {0}")]
+    Setup(BoardError),
+
+    #[error("Move {move_coord} (index {index}) is illegal: {error}")]
+    Move {
+        /// Position in the request's `moves`, from 0
+        index: usize,
+        move_coord: String,
+        error: BoardError,
+    },
+}
+
+/// Replay a request's moves from its initial stones under its rules (with the
+/// `[analysis]` defaults already applied), failing at the first illegal one
+pub fn check(request: &AnalysisRequest) -> Result<Board, LegalityError> {
+    replay(
+        request.board_x_size,
+        request.board_y_size,
+        &AnalysisEngine::katago_initial_stones(request),
+        &AnalysisEngine::katago_moves(request),
+        MoveRules::of(&AnalysisEngine::rules(request)),
+    )
+}
+
+/// Replay KataGo-format moves ([color, coord] pairs), resolving captures and
+/// rejecting moves onto stones, suicide, and ko or superko violations
+pub fn replay(
+    width: u8,
+    height: u8,
+    initial_stones: &[Vec<String>],
+    moves: &[Vec<String>],
+    rules: MoveRules,
+) -> Result<Board, LegalityError> {
+    let board =
+        Board::from_setup(width, height, initial_stones, &[]).map_err(LegalityError::Setup)?;
+    let first = moves
+        .first()
+        .and_then(|mv| mv.first())
+        .and_then(|color| Stone::parse(color))
+        .unwrap_or(Stone::Black);
+    let mut history = History::new(&board, first);
+    let mut board = board;
+    for (index, mv) in moves.iter().enumerate() {
+        let illegal = |error| LegalityError::Move {
+            index,
+            move_coord: mv.get(1).cloned().unwrap_or_default(),
+            error,
+        };
+        let (stone, coord) = match mv.as_slice() {
+            [color, coord] => (
+                Stone::parse(color)
+                    .ok_or_else(|| illegal(BoardError::InvalidColor(color.clone())))?,
+                coord.as_str(),
+            ),
+            _ => return Err(illegal(BoardError::InvalidCoordinate(mv.join(" ")))),
+        };
+        let mut next = board.clone();
+        next.play_under(stone, coord, rules.multi_stone_suicide_legal)
+            .map_err(illegal)?;
+        if !coord.eq_ignore_ascii_case("pass") {
+            history
+                .check(&next, stone.opponent(), rules.ko_rule)
+                .map_err(|()| {
+                    illegal(match rules.ko_rule {
+                        KoRule::Simple => BoardError::Ko(coord.to_string()),
+                        _ => BoardError::Superko(coord.to_string()),
+                    })
+                })?;
+        }
+        history.push(&next, stone.opponent());
+        board = next;
+    }
+    Ok(board)
+}
+
+/// The positions a game went through, as hashes
+struct History {
+    /// Stone layout after each move, starting with the initial stones
+    layouts: Vec<u64>,
+    /// Stone layout and player to move after each move
+    situations: HashSet<u64>,
+}
+
+impl History {
+    fn new(board: &Board, first: Stone) -> Self {
+        let mut history = Self {
+            layouts: Vec::new(),
+            situations: HashSet::new(),
+        };
+        history.push(board, first);
+        history
+    }
+
+    fn push(&mut self, board: &Board, to_move: Stone) {
+        self.layouts.push(Self::layout(board));
+        self.situations.insert(board.position_hash(to_move));
+    }
+
+    /// Fail if `board`, after a stone was played, repeats a position the ko rule
+    /// forbids
+    fn check(&self, board: &Board, to_move: Stone, ko_rule: KoRule) -> Result<(), ()> {
+        let layout = Self::layout(board);
+        let repeated = match ko_rule {
+            KoRule::Simple => self.layouts.iter().rev().nth(1) == Some(&layout),
+            KoRule::Positional => self.layouts.contains(&layout),
+            KoRule::Situational => self.situations.contains(&board.position_hash(to_move)),
+        };
+        if repeated {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Hash of the stones alone: the position hash with the player to move fixed
+    fn layout(board: &Board) -> u64 {
+        board.position_hash(Stone::Black)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(list: &[(&str, &str)]) -> Vec<Vec<String>> {
+        list.iter()
+            .map(|(c, m)| vec![c.to_string(), m.to_string()])
+            .collect()
+    }
+
+    /// A ko on the left edge: Black takes at C2, capturing B2
+    fn ko() -> (Vec<Vec<String>>, Vec<Vec<String>>) {
+        let stones = pairs(&[
+            ("B", "B3"),
+            ("B", "A2"),
+            ("B", "B1"),
+            ("W", "C3"),
+            ("W", "B2"),
+            ("W", "D2"),
+            ("W", "C1"),
+        ]);
+        (stones, pairs(&[("B", "C2")]))
+    }
+
+    fn rules(name: &str) -> MoveRules {
+        MoveRules::of(&name.into())
+    }
+
+    #[test]
+    fn test_immediate_ko_recapture_is_illegal_under_all_rules() {
+        let (stones, mut moves) = ko();
+        moves.extend(pairs(&[("W", "B2")]));
+        for name in ["japanese", "tromp-taylor", "aga"] {
+            let error = replay(9, 9, &stones, &moves, rules(name)).unwrap_err();
+            let LegalityError::Move { index, error, .. } = error else {
+                panic!("expected an illegal move");
+            };
+            assert_eq!(index, 1, "{}", name);
+            assert!(matches!(error, BoardError::Ko(_) | BoardError::Superko(_)));
+        }
+
+        // A move elsewhere first lifts a simple ko
+        let (stones, mut moves) = ko();
+        moves.extend(pairs(&[("W", "G7"), ("B", "G3"), ("W", "B2")]));
+        let board = replay(9, 9, &stones, &moves, rules("japanese")).unwrap();
+        assert_eq!(board.stones().len(), 9);
+    }
+
+    #[test]
+    fn test_superko_forbids_retaking_after_passes() {
+        let (stones, mut moves) = ko();
+        moves.extend(pairs(&[("W", "pass"), ("B", "pass"), ("W", "B2")]));
+        assert!(replay(9, 9, &stones, &moves, rules("chinese")).is_ok());
+        for name in ["tromp-taylor", "aga"] {
+            let error = replay(9, 9, &stones, &moves, rules(name)).unwrap_err();
+            assert_eq!(
+                error,
+                LegalityError::Move {
+                    index: 3,
+                    move_coord: "B2".to_string(),
+                    error: BoardError::Superko("B2".to_string()),
+                },
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_suicide_follows_the_rules() {
+        let stones = pairs(&[("W", "A1"), ("B", "A2"), ("B", "B2"), ("B", "C1")]);
+        let moves = pairs(&[("W", "B1")]);
+        let error = replay(9, 9, &stones, &moves, rules("chinese")).unwrap_err();
+        assert!(matches!(
+            error,
+            LegalityError::Move {
+                index: 0,
+                error: BoardError::Suicide(_),
+                ..
+            }
+        ));
+        let board = replay(9, 9, &stones, &moves, rules("new-zealand")).unwrap();
+        assert_eq!(board.stones().len(), 3);
+    }
+
+    #[test]
+    fn test_occupied_points_and_bad_setups_are_reported() {
+        let moves = pairs(&[("B", "D4"), ("W", "E5"), ("B", "d4")]);
+        let error = replay(9, 9, &[], &moves, rules("chinese")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Move d4 (index 2) is illegal: Point d4 is already occupied"
+        );
+
+        let stones = pairs(&[("B", "D4"), ("W", "D4")]);
+        assert!(matches!(
+            replay(9, 9, &stones, &[], rules("chinese")),
+            Err(LegalityError::Setup(BoardError::Occupied(_)))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_rules_are_lenient() {
+        let lenient = MoveRules {
+            ko_rule: KoRule::Simple,
+            multi_stone_suicide_legal: true,
+        };
+        assert_eq!(rules("some-future-rules"), lenient);
+        assert_eq!(
+            MoveRules::of(&Rules::Custom(crate::api::RuleSettings {
+                ko_rule: Some("situational".to_string()),
+                multi_stone_suicide_legal: Some(false),
+                ..Default::default()
+            })),
+            MoveRules {
+                ko_rule: KoRule::Situational,
+                multi_stone_suicide_legal: false,
+            }
+        );
+    }
+}
@@ -1,67 +1,359 @@
 mod analysis_engine;
+mod animation;
 mod api;
+mod audit;
+mod board;
+mod cache_backend;
+mod coalesce;
+mod compat;
 mod config;
+mod console;
+mod disk_cache;
+mod engine_pool;
 mod error;
+mod games;
+mod graphql;
+mod guess;
+mod handicap;
+mod history;
+mod human_compare;
+mod human_profiles;
+mod ingest;
+mod jobs;
+mod joseki;
+mod legality;
+mod metrics;
+mod msgpack;
+mod ogs;
+mod openapi;
+mod partial;
+mod play;
+mod positions;
+mod preflight;
+mod presets;
+mod processors;
+mod protobuf;
+mod queue;
+mod redis_cache;
+mod render;
+mod result_cache;
+mod review;
+mod rules;
+mod salvage;
+mod schema;
+mod scoring;
+mod selfplay;
+mod sgf;
+mod smoke;
+mod store;
+mod subscriptions;
+mod telemetry;
+mod throughput;
+mod tls;
+mod webhooks;
 
-#[allow(dead_code)] // GTP bot - kept for potential future interactive features
 mod katago_bot;
 
-use crate::analysis_engine::AnalysisEngine;
-use crate::api::create_router;
-use crate::config::Config;
+use crate::api::{create_router, resume_jobs, AppState};
+use crate::audit::AuditLog;
+use crate::config::{Config, ServerConfig, CONFIG_FILE};
+use crate::engine_pool::EnginePool;
+use crate::games::GameDatabase;
+use crate::history::AnalysisHistory;
+use crate::jobs::Jobs;
+use crate::katago_bot::KatagoBot;
+use crate::metrics::HttpMetrics;
+use crate::play::PlaySessions;
+use crate::positions::PositionStore;
+use crate::store::BoundedStore;
+use crate::subscriptions::Subscriptions;
+use axum::http::HeaderName;
 use std::sync::Arc;
+use std::time::Duration;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
-use tracing::info;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing::{info, warn};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+/// Maximum number of concurrent guess-the-move sessions kept in memory
+const MAX_GUESS_SESSIONS: usize = 1_000;
+
+/// Maximum number of finished SGF import jobs whose status is kept
+const MAX_IMPORT_JOBS: usize = 100;
+
+/// Maximum number of background jobs whose status and result are kept
+const MAX_JOBS: usize = 1_000;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
+    // Load configuration: file -> defaults -> env overrides
+    // Environment variables always take precedence
+    let file_config = Config::from_file(CONFIG_FILE).ok();
+    let found_config = file_config.is_some();
+    let mut config = file_config.unwrap_or_default();
+    config.apply_env_overrides();
+
+    // Initialize tracing, exporting spans when [telemetry] names a collector
+    let tracer_provider = telemetry::tracer_provider(&config.telemetry)?;
     tracing_subscriber::registry()
         .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "katago_server=debug,tower_http=debug".into()),
+            tracing_subscriber::fmt::layer().with_filter(
+                tracing_subscriber::EnvFilter::try_from_default_env()
+                    .unwrap_or_else(|_| "katago_server=debug,tower_http=debug".into()),
+            ),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracer_provider.as_ref().map(telemetry::layer))
         .init();
-
-    // Load configuration: file -> defaults -> env overrides
-    // Environment variables always take precedence
-    let mut config = Config::from_file("config.toml").unwrap_or_else(|_| {
+    if !found_config {
         info!("No config.toml found, using defaults");
-        Config::default()
-    });
-    config.apply_env_overrides();
+    }
+    if let Some(endpoint) = &config.telemetry.otlp_endpoint {
+        info!("Exporting traces to {}", endpoint);
+    }
+
+    // Misconfigured paths would otherwise only show as a KataGo that won't start
+    preflight::check(&config).await?;
+
+    // --smoke-test: check the config and engines, then exit
+    if std::env::args().skip(1).any(|arg| arg == "--smoke-test") {
+        return smoke::run(&config).await;
+    }
 
     info!("Starting KataGo server with config: {:?}", config);
 
-    // Initialize KataGo analysis engine (JSON mode)
-    let engine = Arc::new(AnalysisEngine::new(config.katago)?);
+    // Initialize KataGo analysis engines (JSON mode), routed by board size
+    let engine = Arc::new(EnginePool::start(&config)?);
+
+    // /readyz reports ready once every engine has answered a first analysis
+    let pool = engine.clone();
+    tokio::spawn(async move { pool.warm_up().await });
+
+    // Cache KataGo's version for /api/v1/version; it answers once the model loaded
+    let primary = engine.primary().clone();
+    tokio::spawn(async move {
+        if let Err(e) = primary.query_version().await {
+            warn!("Could not query the KataGo version: {}", e);
+        }
+    });
+
+    // Optional KataGo GTP engine for playing moves
+    let bot = if config.gtp.enabled {
+        let bot = KatagoBot::new(config.gtp.katago_config(&config.katago))?;
+        Some(Arc::new(bot))
+    } else {
+        None
+    };
+
+    // Optional SQLite record of completed analyses
+    let history = match &config.history.database_path {
+        Some(path) => {
+            info!("Recording analyses in {}", path);
+            Some(Arc::new(AnalysisHistory::open(path)?))
+        }
+        None => None,
+    };
+
+    // Optional append-only log of analysis requests, for replaying them
+    let audit = match &config.audit.log_path {
+        Some(path) => {
+            info!("Logging analysis requests to {}", path);
+            Some(Arc::new(AuditLog::open(path)?))
+        }
+        None => None,
+    };
+
+    // Background jobs, kept in SQLite across restarts with [jobs] database_path
+    if let Some(path) = &config.jobs.database_path {
+        info!("Keeping jobs in {}", path);
+    }
+    let jobs = Arc::new(Jobs::open(MAX_JOBS, &config.jobs)?);
+
+    let state = AppState {
+        engine,
+        positions: Arc::new(PositionStore::new()),
+        games: Arc::new(GameDatabase::new()),
+        guess_sessions: Arc::new(BoundedStore::with_capacity(MAX_GUESS_SESSIONS)),
+        play_sessions: Arc::new(PlaySessions::new(
+            Duration::from_secs(config.play.session_ttl_secs),
+            config.play.max_sessions,
+        )),
+        import_jobs: Arc::new(BoundedStore::with_capacity(MAX_IMPORT_JOBS)),
+        jobs,
+        admin_token: config.server.admin_token.as_deref().map(Arc::from),
+        subscriptions: Arc::new(Subscriptions::new(config.subscriptions.clone())),
+        validate_responses: config.server.validate_responses,
+        swagger_ui: config.server.swagger_ui,
+        compression: config.server.compression,
+        max_body_bytes: config.server.max_body_bytes,
+        metrics: Arc::new(HttpMetrics::new()),
+        bot,
+        history,
+        audit,
+        config: Arc::new(std::sync::Mutex::new(config.clone())),
+        started: std::time::Instant::now(),
+        webhooks: webhooks::Webhooks::new(&config.webhooks)?.map(Arc::new),
+        ogs: ogs::Ogs::new(&config.ogs)?.map(Arc::new),
+    };
+
+    // Reviews and analyses a previous run left unfinished
+    resume_jobs(&state);
 
     // Create router with CORS and tracing
-    let app = create_router(engine)
+    let app = create_router(state)
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
                 .allow_methods(Any)
-                .allow_headers(Any),
+                .allow_headers(Any)
+                .expose_headers([HeaderName::from_static("x-request-id")]),
         )
         .layer(TraceLayer::new_for_http());
 
-    // Start server
-    let addr = format!("{}:{}", config.server.host, config.server.port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    // Start server, over HTTPS when [server] names a certificate and key
+    let tls = match (&config.server.tls_cert_path, &config.server.tls_key_path) {
+        (Some(cert), Some(key)) => {
+            let tls = tls::load(cert, key).await?;
+            let interval = std::time::Duration::from_secs(config.server.tls_reload_secs.max(1));
+            tls::watch(tls.clone(), cert.clone(), key.clone(), interval);
+            Some(tls)
+        }
+        (None, None) => None,
+        _ => anyhow::bail!("server.tls_cert_path and server.tls_key_path must be set together"),
+    };
+    let (listener, addr) = bind(&config.server).await?;
 
-    info!("Listening on http://{}", addr);
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    match &listener {
+        Listener::Tcp(_) => info!("Listening on {}://{}", scheme, addr),
+        #[cfg(unix)]
+        Listener::Unix(_) => info!("Listening on {} ({})", addr, scheme),
+    }
+    if config.server.validate_responses {
+        info!("Validating responses against their JSON Schemas (debug mode)");
+    }
     info!("");
     info!("API endpoints:");
     info!("  POST /api/v1/analysis      - Comprehensive position analysis");
+    info!("  POST /api/v1/analysis/turns - Several turns of a game in one query");
+    info!("  POST /api/v1/analysis/{{id}}/cancel - Cancel a running analysis");
+    info!("  POST /api/v1/game/move     - Play: the GTP bot's next move");
+    info!("  POST /api/v1/game/score    - Play: ownership estimate from the GTP bot");
+    info!("  POST /api/v1/analysis/trade - Evaluate an exchange (furikawari)");
+    info!("  POST /api/v1/score - Estimate the final score from ownership");
+    info!("  POST /api/v1/policy - Raw policy heatmap of a position (one visit)");
+    info!("  POST /api/v1/render/heatmap - Ownership or policy as an SVG or PNG image");
+    info!("  POST /api/v1/render/review - Reviewed game as an animated SVG or GIF");
+    info!(
+        "  POST /api/v1/analysis/human - Engine moves next to a human profile's, with point loss"
+    );
+    info!("  POST /api/v1/analysis/stream - Partial results during search (SSE)");
+    info!("  GET  /api/v1/analysis/subscribe - Keep refining a position (WebSocket)");
+    info!("  GET  /api/v1/analysis/channels/{{channel}} - Spectate a published subscription (SSE)");
+    info!("  POST /api/v1/selfplay      - Engine plays the position out against itself (SSE)");
+    info!("  POST /api/v1/katago/analysis - KataGo-native JSON queries (NDJSON in/out)");
+    info!("  GET  /api/v1/presets       - Board-size presets (9x9, 13x13, 19x19)");
+    info!("  GET  /api/v1/rules         - Named rulesets and the rules they stand for");
+    info!("  GET  /api/v1/handicap      - Standard handicap stones, komi, and first player");
+    info!("  GET  /api/v1/human/profiles - humanSLProfile values of the loaded human model");
+    info!("  POST /api/v1/positions     - Store a position for editing/analysis");
+    info!("  POST /api/v1/positions/{{id}}/edit - Derive a position by adding/removing stones");
+    info!("  POST /api/v1/games         - Upload a game to the archive");
+    info!("  POST /api/v1/guess/sessions - Start a guess-the-next-move session");
+    info!("  POST /api/v1/play/sessions - Start a game against the engine");
+    info!("  POST /api/v1/play/sessions/{{id}}/move - Play a move and get the engine's reply");
+    info!("  POST /api/v1/review        - Per-move review of an SGF game (JSON)");
+    info!("  POST /api/v1/review/graph  - Winrate graph of an SGF game");
+    info!("  POST /api/v1/review/sgf    - Stream a game review as annotated SGF");
+    info!("  POST /api/v1/import/ogs    - Per-move review of a game from online-go.com");
+    info!("  POST /api/v1/jobs          - Start an analysis or review in the background");
+    info!("  GET  /api/v1/jobs/{{id}}     - Status, progress and result of a job");
+    info!("  DELETE /api/v1/jobs/{{id}}   - Cancel a running job");
+    info!("  POST /api/v1/admin/import  - Bulk-import an SGF directory or archive (admin)");
+    info!("  GET  /api/v1/admin/duplicates - Suspected duplicate games (admin)");
+    info!("  PUT  /api/v1/admin/human-model - Load or unload the human SL model (admin)");
+    info!("  POST /api/v1/admin/config/reload - Apply changed settings from config.toml (admin)");
+    info!("  POST /api/v1/admin/model - Switch KataGo to another model without downtime (admin)");
+    info!("  POST /api/v1/admin/replay - Re-run a logged request and compare (admin)");
+    info!("  POST /api/v1/admin/gtp    - Raw GTP command to the bot (admin)");
+    info!("  GET  /api/v1/admin/engine - KataGo process and monitor state (admin)");
+    info!("  POST /api/v1/admin/engine/restart - Kill and respawn KataGo (admin)");
+    info!("  GET  /api/v1/admin/throughput - Visit rate and utilization per engine (admin)");
     info!("  GET  /api/v1/health        - Health check with details");
+    info!("  GET  /api/v1/engine/protocol - Engine stdin/stdout protocol counters");
     info!("  GET  /api/v1/version       - Server and KataGo version");
+    info!("  GET  /api/v1/schema        - JSON Schemas of request and response bodies");
+    info!("  GET  /api/v1/analysis.proto - Protocol Buffers schema of analysis responses");
+    info!("  GET  /api/v1/openapi.json  - OpenAPI document");
+    info!("  POST /graphql              - GraphQL: analysis, review, health, version");
+    info!("  GET  /api/v1/analyses      - Recorded analyses (with [history])");
     info!("  POST /api/v1/cache/clear   - Clear neural network cache");
+    info!("  GET  /api/v1/cache/stats   - Result cache hits and misses");
+    info!("  GET  /console              - Operator console (admin)");
+    info!("  GET  /metrics              - Prometheus metrics");
+    info!("  GET  /livez                - Liveness probe: the server is up");
+    info!("  GET  /readyz               - Readiness probe: every engine has warmed up");
 
-    axum::serve(listener, app).await?;
+    match (listener, tls) {
+        (Listener::Tcp(listener), None) => axum::serve(listener, app).await?,
+        (Listener::Tcp(listener), Some(tls)) => {
+            axum_server::from_tcp_rustls(listener.into_std()?, tls)?
+                .serve(app.into_make_service())
+                .await?
+        }
+        #[cfg(unix)]
+        (Listener::Unix(listener), None) => axum::serve(listener, app).await?,
+        #[cfg(unix)]
+        (Listener::Unix(listener), Some(tls)) => {
+            axum_server::from_unix_rustls(listener.into_std()?, tls)?
+                .serve(app.into_make_service())
+                .await?
+        }
+    }
 
     Ok(())
 }
+
+enum Listener {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+/// Bind the TCP port, or the Unix socket of `listen = "unix:<path>"`; returns the
+/// listener and the address to log
+async fn bind(server: &ServerConfig) -> anyhow::Result<(Listener, String)> {
+    let Some(listen) = &server.listen else {
+        let addr = format!("{}:{}", server.host, server.port);
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        return Ok((Listener::Tcp(listener), addr));
+    };
+    let path = listen
+        .strip_prefix("unix:")
+        .filter(|path| !path.is_empty())
+        .ok_or_else(|| {
+            anyhow::anyhow!("server.listen must be unix:<socket path>, not {}", listen)
+        })?;
+    bind_unix(path).map(|listener| (listener, listen.clone()))
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &str) -> anyhow::Result<Listener> {
+    use anyhow::Context;
+    use std::os::unix::fs::FileTypeExt;
+
+    // A socket left behind by a previous run would make the bind fail; other
+    // files are not touched
+    let stale =
+        std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket());
+    if stale {
+        std::fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("Failed to listen on Unix socket {}", path))?;
+    Ok(Listener::Unix(listener))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(path: &str) -> anyhow::Result<Listener> {
+    anyhow::bail!("Unix sockets are not available on this platform ({})", path)
+}
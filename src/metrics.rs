@@ -0,0 +1,401 @@
+use crate::api::AppState;
+use crate::telemetry;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Upper bounds (seconds) of the latency histogram buckets; analyses take from
+/// milliseconds (cached) to the move timeout
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0,
+];
+
+/// Route label of requests that matched no route, so unknown paths don't each
+/// get their own series
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Exposition format of a scrape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Prometheus text format 0.0.4
+    Prometheus,
+    /// OpenMetrics 1.0, which can carry exemplars
+    OpenMetrics,
+}
+
+impl Format {
+    /// OpenMetrics when the scraper accepts it, as Prometheus does
+    fn negotiate(headers: &HeaderMap) -> Self {
+        let accepts_openmetrics = headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains("application/openmetrics-text"));
+        if accepts_openmetrics {
+            Format::OpenMetrics
+        } else {
+            Format::Prometheus
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Prometheus => "text/plain; version=0.0.4; charset=utf-8",
+            Format::OpenMetrics => "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        }
+    }
+}
+
+/// The latest traced request of a latency bucket
+struct Exemplar {
+    trace_id: String,
+    seconds: f64,
+}
+
+#[derive(Default)]
+struct RouteStats {
+    /// Responses by status code
+    responses: BTreeMap<u16, u64>,
+    /// Requests per latency bucket (not cumulative), plus one for +Inf
+    buckets: Vec<u64>,
+    /// Latest exemplar per latency bucket, while trace export is on
+    exemplars: Vec<Option<Exemplar>>,
+    seconds_sum: f64,
+}
+
+/// Request counts and latencies per endpoint
+#[derive(Default)]
+pub struct HttpMetrics {
+    /// Keyed by (method, route template)
+    routes: Mutex<BTreeMap<(String, String), RouteStats>>,
+}
+
+impl HttpMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(
+        &self,
+        method: &str,
+        route: &str,
+        status: u16,
+        seconds: f64,
+        trace_id: Option<String>,
+    ) {
+        let mut routes = self.routes.lock().unwrap();
+        let stats = routes
+            .entry((method.to_string(), route.to_string()))
+            .or_default();
+        *stats.responses.entry(status).or_default() += 1;
+        if stats.buckets.is_empty() {
+            stats.buckets = vec![0; LATENCY_BUCKETS.len() + 1];
+            stats.exemplars = (0..=LATENCY_BUCKETS.len()).map(|_| None).collect();
+        }
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        stats.buckets[bucket] += 1;
+        if let Some(trace_id) = trace_id {
+            stats.exemplars[bucket] = Some(Exemplar { trace_id, seconds });
+        }
+        stats.seconds_sum += seconds;
+    }
+
+    fn render(&self, out: &mut String, format: Format) {
+        let routes = self.routes.lock().unwrap();
+
+        header(
+            out,
+            format,
+            "katago_server_http_requests_total",
+            "counter",
+            "HTTP requests by endpoint and response status",
+        );
+        for ((method, route), stats) in routes.iter() {
+            for (status, count) in &stats.responses {
+                let _ = writeln!(
+                    out,
+                    "katago_server_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                    escape(method),
+                    escape(route),
+                    status,
+                    count
+                );
+            }
+        }
+
+        header(
+            out,
+            format,
+            "katago_server_http_request_duration_seconds",
+            "histogram",
+            "Time until the response headers were sent (streams stay open longer)",
+        );
+        for ((method, route), stats) in routes.iter() {
+            let labels = format!("method=\"{}\",route=\"{}\"", escape(method), escape(route));
+            let bounds = LATENCY_BUCKETS
+                .iter()
+                .map(f64::to_string)
+                .chain(["+Inf".to_string()]);
+            let mut cumulative = 0;
+            for ((bound, count), exemplar) in bounds.zip(&stats.buckets).zip(&stats.exemplars) {
+                cumulative += count;
+                let _ = write!(
+                    out,
+                    "katago_server_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulative
+                );
+                // The text format has no exemplars
+                if let (Format::OpenMetrics, Some(exemplar)) = (format, exemplar) {
+                    let _ = write!(
+                        out,
+                        " # {{trace_id=\"{}\"}} {}",
+                        exemplar.trace_id, exemplar.seconds
+                    );
+                }
+                out.push('\n');
+            }
+            let total: u64 = stats.buckets.iter().sum();
+            let _ = writeln!(
+                out,
+                "katago_server_http_request_duration_seconds_sum{{{}}} {}",
+                labels, stats.seconds_sum
+            );
+            let _ = writeln!(
+                out,
+                "katago_server_http_request_duration_seconds_count{{{}}} {}",
+                labels, total
+            );
+        }
+    }
+}
+
+/// Middleware recording each request's endpoint, status, and latency
+pub async fn track_requests(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+    let method = request.method().to_string();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    state.metrics.record(
+        &method,
+        &route,
+        response.status().as_u16(),
+        started.elapsed().as_secs_f64(),
+        telemetry::current_trace_id(),
+    );
+    response
+}
+
+/// GET /metrics
+///
+/// Prometheus text format, or OpenMetrics with trace id exemplars on the latency
+/// buckets when the scraper accepts it: request counts and latencies recorded by
+/// `track_requests`, then engine gauges and counters read when scraped.
+pub async fn metrics(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let format = Format::negotiate(&headers);
+    let mut out = String::new();
+    state.metrics.render(&mut out, format);
+    render_engines(&state, &mut out, format);
+    if format == Format::OpenMetrics {
+        out.push_str("# EOF\n");
+    }
+    ([(header::CONTENT_TYPE, format.content_type())], out)
+}
+
+fn render_engines(state: &AppState, out: &mut String, format: Format) {
+    type Read = fn(&crate::analysis_engine::AnalysisEngine) -> u64;
+    let series: [(&str, &str, &str, Read); 5] = [
+        (
+            "katago_server_engine_up",
+            "gauge",
+            "Whether the engine's KataGo process is running",
+            |engine| engine.is_alive() as u64,
+        ),
+        (
+            "katago_server_engine_restarts_total",
+            "counter",
+            "KataGo restarts after the process died",
+            |engine| engine.restart_count(),
+        ),
+        (
+            "katago_server_engine_timeouts_total",
+            "counter",
+            "Analysis queries that got no response in time",
+            |engine| engine.timeout_count(),
+        ),
+        (
+            "katago_server_queue_depth",
+            "gauge",
+            "Analysis queries waiting for a place in KataGo",
+            |engine| engine.queued_count() as u64,
+        ),
+        (
+            "katago_server_analyses_in_flight",
+            "gauge",
+            "Analysis queries sent to KataGo and not yet answered",
+            |engine| engine.in_flight_count() as u64,
+        ),
+    ];
+    for (name, kind, help, read) in series {
+        header(out, format, name, kind, help);
+        for (engine_name, engine) in state.engine.named() {
+            let _ = writeln!(
+                out,
+                "{}{{engine=\"{}\"}} {}",
+                name,
+                escape(engine_name),
+                read(engine)
+            );
+        }
+    }
+}
+
+fn header(out: &mut String, format: Format, name: &str, kind: &str, help: &str) {
+    // OpenMetrics names a counter family without the _total of its sample
+    let name = match format {
+        Format::OpenMetrics if kind == "counter" => name.trim_end_matches("_total"),
+        _ => name,
+    };
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// A label value with the characters the text format reserves escaped
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram_is_cumulative() {
+        let metrics = HttpMetrics::new();
+        metrics.record("POST", "/api/v1/analysis", 200, 0.003, None);
+        metrics.record("POST", "/api/v1/analysis", 200, 0.3, None);
+        metrics.record("POST", "/api/v1/analysis", 504, 90.0, None);
+
+        let mut out = String::new();
+        metrics.render(&mut out, Format::Prometheus);
+        let labels = r#"method="POST",route="/api/v1/analysis""#;
+        for line in [
+            format!(
+                r#"katago_server_http_requests_total{{{},status="200"}} 2"#,
+                labels
+            ),
+            format!(
+                r#"katago_server_http_requests_total{{{},status="504"}} 1"#,
+                labels
+            ),
+            format!(
+                r#"katago_server_http_request_duration_seconds_bucket{{{},le="0.005"}} 1"#,
+                labels
+            ),
+            format!(
+                r#"katago_server_http_request_duration_seconds_bucket{{{},le="0.5"}} 2"#,
+                labels
+            ),
+            format!(
+                r#"katago_server_http_request_duration_seconds_bucket{{{},le="60"}} 2"#,
+                labels
+            ),
+            format!(
+                r#"katago_server_http_request_duration_seconds_bucket{{{},le="+Inf"}} 3"#,
+                labels
+            ),
+            format!(
+                r#"katago_server_http_request_duration_seconds_count{{{}}} 3"#,
+                labels
+            ),
+        ] {
+            assert!(out.lines().any(|l| l == line), "missing {}\n{}", line, out);
+        }
+    }
+
+    #[test]
+    fn test_openmetrics_carries_trace_exemplars() {
+        let metrics = HttpMetrics::new();
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+        metrics.record("POST", "/api/v1/analysis", 200, 0.2, None);
+        metrics.record(
+            "POST",
+            "/api/v1/analysis",
+            200,
+            0.3,
+            Some(trace_id.to_string()),
+        );
+        metrics.record(
+            "POST",
+            "/api/v1/analysis",
+            504,
+            90.0,
+            Some("slow".to_string()),
+        );
+
+        let mut out = String::new();
+        metrics.render(&mut out, Format::OpenMetrics);
+        let labels = r#"method="POST",route="/api/v1/analysis""#;
+        for line in [
+            "# TYPE katago_server_http_requests counter".to_string(),
+            format!(
+                r#"katago_server_http_request_duration_seconds_bucket{{{},le="0.25"}} 1"#,
+                labels
+            ),
+            format!(
+                r#"katago_server_http_request_duration_seconds_bucket{{{},le="0.5"}} 2 # {{trace_id="{}"}} 0.3"#,
+                labels, trace_id
+            ),
+            format!(
+                r#"katago_server_http_request_duration_seconds_bucket{{{},le="+Inf"}} 3 # {{trace_id="slow"}} 90"#,
+                labels
+            ),
+        ] {
+            assert!(out.lines().any(|l| l == line), "missing {}\n{}", line, out);
+        }
+
+        // The Prometheus text format has no exemplars
+        let mut out = String::new();
+        metrics.render(&mut out, Format::Prometheus);
+        assert!(!out.contains("trace_id"));
+        assert!(out.contains("# TYPE katago_server_http_requests_total counter"));
+    }
+
+    #[test]
+    fn test_openmetrics_is_served_when_accepted() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(Format::negotiate(&headers), Format::Prometheus);
+        headers.insert(
+            header::ACCEPT,
+            "application/openmetrics-text;version=1.0.0,text/plain;version=0.0.4;q=0.5"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(Format::negotiate(&headers), Format::OpenMetrics);
+    }
+
+    #[test]
+    fn test_label_values_are_escaped() {
+        assert_eq!(escape(r#"a"b\c"#), r#"a\"b\\c"#);
+        assert_eq!(escape("/api/v1/games/{id}"), "/api/v1/games/{id}");
+    }
+}
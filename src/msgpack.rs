@@ -0,0 +1,184 @@
+use crate::api::ApiError;
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::convert::Infallible;
+
+/// Content type of MessagePack bodies
+pub const MSGPACK: &str = "application/msgpack";
+
+/// MessagePack media types; the unregistered `application/x-msgpack` is still what
+/// many libraries send
+const MSGPACK_TYPES: [&str; 2] = [MSGPACK, "application/x-msgpack"];
+
+/// Whether a media type (parameters allowed) is one of `media_types`
+fn is_one_of(media_type: &str, media_types: &[&str]) -> bool {
+    let essence = media_type.split(';').next().unwrap_or_default().trim();
+    media_types
+        .iter()
+        .any(|candidate| essence.eq_ignore_ascii_case(candidate))
+}
+
+/// Whether an Accept header lists one of `media_types` with a non-zero quality
+pub fn accepts(headers: &HeaderMap, media_types: &[&str]) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|range| {
+            let quality = range
+                .split(';')
+                .skip(1)
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            is_one_of(range, media_types) && quality > 0.0
+        })
+}
+
+/// A request body in JSON, or in MessagePack when sent with
+/// `Content-Type: application/msgpack`
+pub struct Body<T>(pub T);
+
+impl<T, S> FromRequest<S> for Body<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let msgpack = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|content_type| is_one_of(content_type, &MSGPACK_TYPES));
+        if !msgpack {
+            let Json(value) = Json::<T>::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Ok(Self(value));
+        }
+        let bytes = Bytes::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        rmp_serde::from_slice(&bytes).map(Self).map_err(|e| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Invalid MessagePack",
+                &e.to_string(),
+            )
+            .into_response()
+        })
+    }
+}
+
+/// The body format a client asked for: MessagePack if its Accept header lists
+/// `application/msgpack`, JSON otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    MessagePack,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Format {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(if accepts(&parts.headers, &MSGPACK_TYPES) {
+            Self::MessagePack
+        } else {
+            Self::Json
+        })
+    }
+}
+
+impl Format {
+    /// `value` as a response body in this format. Problem details stay JSON
+    /// whatever the client accepts.
+    pub fn respond<T: Serialize>(self, value: &T) -> Response {
+        let mut response = match self {
+            Self::Json => Json(value).into_response(),
+            // Named fields, so the maps carry the same keys as the JSON objects
+            Self::MessagePack => match rmp_serde::to_vec_named(value) {
+                Ok(bytes) => ([(header::CONTENT_TYPE, MSGPACK)], bytes).into_response(),
+                Err(e) => ApiError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Serialization Error",
+                    &e.to_string(),
+                )
+                .into_response(),
+            },
+        };
+        // The same URL answers in either format, so caches must key on Accept
+        response
+            .headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("accept"));
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{AnalysisRequest, AnalysisResponse, Ownership};
+
+    #[test]
+    fn test_accept_header_selects_msgpack() {
+        let msgpack = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_str(value).unwrap());
+            accepts(&headers, &MSGPACK_TYPES)
+        };
+        assert!(msgpack("application/msgpack"));
+        assert!(msgpack("application/json;q=0.5, application/x-msgpack"));
+        assert!(msgpack("Application/MsgPack; q=0.8"));
+        assert!(!msgpack("application/json"));
+        assert!(!msgpack("*/*"));
+        assert!(!msgpack("application/msgpack;q=0"));
+        assert!(!accepts(&HeaderMap::new(), &MSGPACK_TYPES));
+    }
+
+    #[test]
+    fn test_wire_types_round_trip_through_msgpack() {
+        let request: AnalysisRequest = serde_json::from_value(serde_json::json!({
+            "moves": [["B", "Q16"], ["W", "D4"]],
+            "rules": "japanese",
+            "komi": 6.5,
+            "boardXSize": 19,
+            "boardYSize": 19,
+            "maxVisits": 50,
+            "includeOwnership": true,
+            "ownershipFormat": "map"
+        }))
+        .unwrap();
+        let bytes = rmp_serde::to_vec_named(&request).unwrap();
+        let decoded: AnalysisRequest = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&request).unwrap()
+        );
+
+        let response: AnalysisResponse = serde_json::from_value(serde_json::json!({
+            "id": "a1",
+            "turnNumber": 2,
+            "isDuringSearch": false,
+            "moveInfos": [],
+            "ownership": {"A1": 0.5, "T19": -0.25}
+        }))
+        .unwrap();
+        let bytes = rmp_serde::to_vec_named(&response).unwrap();
+        let decoded: AnalysisResponse = rmp_serde::from_slice(&bytes).unwrap();
+        assert!(matches!(decoded.ownership, Some(Ownership::Map(ref map)) if map["A1"] == 0.5));
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&response).unwrap()
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use crate::config::OgsConfig;
+use reqwest::{StatusCode, Url};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::info;
+
+/// Largest SGF accepted from OGS; a game record is a few kilobytes
+const MAX_SGF_BYTES: usize = 1 << 20;
+
+#[derive(Error, Debug)]
+pub enum OgsError {
+    #[error("OGS has no public game {0}")]
+    NotFound(u64),
+
+    #[error("OGS answered the request for game {0} with status {1}")]
+    Status(u64, StatusCode),
+
+    #[error("The SGF of OGS game {0} is larger than {MAX_SGF_BYTES} bytes")]
+    TooLarge(u64),
+
+    #[error("Failed to fetch game {0} from OGS: {1}")]
+    Request(u64, reqwest::Error),
+}
+
+/// Fetches game records from the public OGS (online-go.com) API
+pub struct Ogs {
+    http: reqwest::Client,
+    api_url: Url,
+}
+
+impl Ogs {
+    /// The configured OGS API, or None when `[ogs] enabled` is false
+    pub fn new(config: &OgsConfig) -> anyhow::Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let api_url = Url::parse(&config.api_url)
+            .map_err(|e| anyhow::anyhow!("[ogs] api_url {}: {}", config.api_url, e))?;
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .user_agent(concat!("katago-server/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Some(Self { http, api_url }))
+    }
+
+    /// URL of the SGF of game `game_id`
+    fn sgf_url(&self, game_id: u64) -> Url {
+        let mut url = self.api_url.clone();
+        let path = format!(
+            "{}/api/v1/games/{}/sgf",
+            url.path().trim_end_matches('/'),
+            game_id
+        );
+        url.set_path(&path);
+        url
+    }
+
+    /// The SGF of a finished or ongoing public game
+    pub async fn fetch_sgf(&self, game_id: u64) -> Result<String, OgsError> {
+        let url = self.sgf_url(game_id);
+        let mut response = self
+            .http
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(|e| OgsError::Request(game_id, e))?;
+        match response.status() {
+            status if status.is_success() => {}
+            // Private games are hidden the same way as missing ones
+            StatusCode::NOT_FOUND | StatusCode::FORBIDDEN => {
+                return Err(OgsError::NotFound(game_id))
+            }
+            status => return Err(OgsError::Status(game_id, status)),
+        }
+        if response
+            .content_length()
+            .is_some_and(|length| length as usize > MAX_SGF_BYTES)
+        {
+            return Err(OgsError::TooLarge(game_id));
+        }
+        // A reply without Content-Length is read only up to the limit
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| OgsError::Request(game_id, e))?
+        {
+            if bytes.len() + chunk.len() > MAX_SGF_BYTES {
+                return Err(OgsError::TooLarge(game_id));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        info!("Fetched OGS game {} from {}", game_id, url);
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sgf_url_keeps_the_base_path() {
+        let ogs = |api_url: &str| {
+            Ogs::new(&OgsConfig {
+                enabled: true,
+                api_url: api_url.to_string(),
+                ..Default::default()
+            })
+            .unwrap()
+            .unwrap()
+        };
+        assert_eq!(
+            ogs("https://online-go.com").sgf_url(123).as_str(),
+            "https://online-go.com/api/v1/games/123/sgf"
+        );
+        assert_eq!(
+            ogs("http://127.0.0.1:8080/ogs/").sgf_url(7).as_str(),
+            "http://127.0.0.1:8080/ogs/api/v1/games/7/sgf"
+        );
+        assert!(Ogs::new(&OgsConfig::default()).unwrap().is_none());
+        assert!(Ogs::new(&OgsConfig {
+            enabled: true,
+            api_url: "not a url".to_string(),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
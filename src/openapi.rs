@@ -0,0 +1,588 @@
+use crate::msgpack::MSGPACK;
+use crate::protobuf::PROTOBUF;
+use crate::schema::{CALLBACK_OPERATIONS, REQUEST_SCHEMAS, RESPONSE_SCHEMAS, SCHEMAS};
+use axum::Json;
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+use serde_json::{json, Map, Value};
+use std::sync::OnceLock;
+
+/// Documented operations: method, path, summary, and the content type of a
+/// successful response
+const OPERATIONS: &[(&str, &str, &str, &str)] = &[
+    ("POST", "/api/v1/analysis", "Analyze a position", JSON),
+    (
+        "POST",
+        "/api/v1/analysis/turns",
+        "Analyze several turns of a game in one query",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/analysis/{id}/cancel",
+        "Cancel a running analysis by requestId",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/analysis/trade",
+        "Evaluate an exchange (furikawari)",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/analysis/human",
+        "Engine moves next to a human profile's, with point loss",
+        JSON,
+    ),
+    ("POST", "/api/v1/game/move", "The GTP bot's next move", JSON),
+    (
+        "POST",
+        "/api/v1/game/score",
+        "Ownership estimate from the GTP bot",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/score",
+        "Estimated final score and territory",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/policy",
+        "Raw network policy of a position, without a search",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/render/heatmap",
+        "Ownership or policy drawn over the board as SVG or PNG",
+        SVG,
+    ),
+    (
+        "POST",
+        "/api/v1/render/review",
+        "A reviewed game drawn move by move, as animated SVG or GIF",
+        SVG,
+    ),
+    (
+        "GET",
+        "/api/v1/analysis/stream",
+        "Partial results of a stored position during search",
+        SSE,
+    ),
+    (
+        "POST",
+        "/api/v1/analysis/stream",
+        "Partial results during search",
+        SSE,
+    ),
+    (
+        "POST",
+        "/api/v1/selfplay",
+        "The engine plays the position out against itself, move by move",
+        SSE,
+    ),
+    (
+        "GET",
+        "/api/v1/analysis/subscribe",
+        "Keep refining a position (WebSocket upgrade)",
+        JSON,
+    ),
+    (
+        "GET",
+        "/api/v1/analysis/channels/{channel}",
+        "Spectate the subscription publishing to a channel",
+        SSE,
+    ),
+    (
+        "POST",
+        "/api/v1/katago/analysis",
+        "KataGo-native JSON queries, one per line",
+        NDJSON,
+    ),
+    ("GET", "/api/v1/presets", "Board-size presets", JSON),
+    (
+        "GET",
+        "/api/v1/rules",
+        "Named rulesets and the rules they stand for",
+        JSON,
+    ),
+    (
+        "GET",
+        "/api/v1/handicap",
+        "Standard handicap stones, komi, and first player",
+        JSON,
+    ),
+    (
+        "GET",
+        "/api/v1/human/profiles",
+        "humanSLProfile values of the loaded human model",
+        JSON,
+    ),
+    ("POST", "/api/v1/positions", "Store a position", JSON),
+    ("GET", "/api/v1/positions/{id}", "A stored position", JSON),
+    (
+        "POST",
+        "/api/v1/positions/{id}/edit",
+        "Derive a position by adding or removing stones",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/games",
+        "Upload a game to the archive",
+        JSON,
+    ),
+    ("GET", "/api/v1/games", "List archived games", JSON),
+    (
+        "POST",
+        "/api/v1/games/search/position",
+        "Find archived games reaching a position",
+        JSON,
+    ),
+    ("GET", "/api/v1/games/{id}", "An archived game", JSON),
+    (
+        "PUT",
+        "/api/v1/games/{id}/visibility",
+        "Share or unshare a game",
+        JSON,
+    ),
+    ("GET", "/api/v1/shared/{token}", "A shared game", JSON),
+    (
+        "POST",
+        "/api/v1/review",
+        "Per-move review of an SGF game, or the game annotated for KaTrain or Lizzie",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/review/graph",
+        "Winrate and score lead of every turn of an SGF game",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/review/sgf",
+        "Stream a game review as annotated SGF",
+        SGF,
+    ),
+    (
+        "POST",
+        "/api/v1/import/ogs",
+        "Per-move review of a game from online-go.com",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/jobs",
+        "Start an analysis or review in the background",
+        JSON,
+    ),
+    (
+        "GET",
+        "/api/v1/jobs/{id}",
+        "Status, progress and result of a job",
+        JSON,
+    ),
+    ("DELETE", "/api/v1/jobs/{id}", "Cancel a running job", JSON),
+    (
+        "POST",
+        "/api/v1/guess/sessions",
+        "Start a guess-the-next-move session",
+        JSON,
+    ),
+    (
+        "GET",
+        "/api/v1/guess/sessions/{id}",
+        "A guess session",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/guess/sessions/{id}/guess",
+        "Guess the next move",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/play/sessions",
+        "Start a game against the engine",
+        JSON,
+    ),
+    ("GET", "/api/v1/play/sessions/{id}", "A play session", JSON),
+    (
+        "DELETE",
+        "/api/v1/play/sessions/{id}",
+        "End a game against the engine",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/play/sessions/{id}/move",
+        "Play a move and get the engine's reply",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/admin/import",
+        "Bulk-import an SGF directory or archive",
+        JSON,
+    ),
+    (
+        "GET",
+        "/api/v1/admin/import/{id}",
+        "Progress of an import job",
+        JSON,
+    ),
+    (
+        "GET",
+        "/api/v1/admin/duplicates",
+        "Suspected duplicate games",
+        JSON,
+    ),
+    (
+        "GET",
+        "/api/v1/admin/human-model",
+        "Whether the human SL model is loaded",
+        JSON,
+    ),
+    (
+        "PUT",
+        "/api/v1/admin/human-model",
+        "Load or unload the human SL model",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/admin/config/reload",
+        "Re-read the configuration file and apply live settings",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/admin/model",
+        "Switch KataGo to another model without downtime",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/admin/replay",
+        "Analyze a logged request again and compare the results",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/admin/gtp",
+        "Send a raw GTP command to the bot",
+        JSON,
+    ),
+    (
+        "GET",
+        "/api/v1/admin/engine",
+        "KataGo process, restart, and keepalive state of an engine",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/admin/engine/restart",
+        "Kill and respawn an engine's KataGo process",
+        JSON,
+    ),
+    (
+        "GET",
+        "/api/v1/admin/throughput",
+        "Visit rate, utilization, and search latency of each engine",
+        JSON,
+    ),
+    ("GET", "/api/v1/health", "Health check", JSON),
+    (
+        "GET",
+        "/api/v1/engine/protocol",
+        "Engine stdin/stdout protocol counters",
+        JSON,
+    ),
+    ("GET", "/api/v1/version", "Server and KataGo version", JSON),
+    (
+        "GET",
+        "/api/v1/analyses",
+        "Recorded analyses, oldest first",
+        JSON,
+    ),
+    (
+        "POST",
+        "/api/v1/cache/clear",
+        "Clear the neural network cache",
+        JSON,
+    ),
+    (
+        "GET",
+        "/api/v1/cache/stats",
+        "Hits and misses of the result cache",
+        JSON,
+    ),
+    (
+        "GET",
+        "/api/v1/analysis.proto",
+        "Protocol Buffers schema of analysis responses",
+        "text/plain",
+    ),
+    (
+        "POST",
+        "/graphql",
+        "GraphQL queries of analyses, reviews, health, and version",
+        JSON,
+    ),
+    ("GET", "/api/v1/schema", "Index of the JSON Schemas", JSON),
+    ("GET", "/api/v1/schema/{name}", "A JSON Schema", JSON),
+];
+
+const JSON: &str = "application/json";
+const SSE: &str = "text/event-stream";
+const NDJSON: &str = "application/x-ndjson";
+const SGF: &str = "application/x-go-sgf";
+const SVG: &str = "image/svg+xml";
+
+/// Operations that also answer in binary formats, which clients select with the
+/// Accept header
+const BINARY_RESPONSES: &[(&str, &str, &[&str])] = &[
+    ("POST", "/api/v1/analysis", &[MSGPACK, PROTOBUF]),
+    ("POST", "/api/v1/analysis/turns", &[MSGPACK]),
+    ("POST", "/api/v1/policy", &[MSGPACK]),
+    ("POST", "/api/v1/score", &[MSGPACK]),
+];
+
+/// GET /api/v1/openapi.json
+pub async fn openapi() -> Json<Value> {
+    static DOCUMENT: OnceLock<Value> = OnceLock::new();
+    Json(DOCUMENT.get_or_init(document).clone())
+}
+
+/// GET /api/docs (with `server.swagger_ui`): Swagger UI for the OpenAPI document
+pub async fn swagger_ui() -> Markup {
+    html! {
+        (DOCTYPE)
+        html {
+            head {
+                title { "KataGo Server API" }
+                link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css";
+            }
+            body {
+                div #swagger-ui {}
+                script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" {}
+                script {
+                    (PreEscaped("SwaggerUIBundle({ url: '/api/v1/openapi.json', dom_id: '#swagger-ui' });"))
+                }
+            }
+        }
+    }
+}
+
+/// The OpenAPI 3.1 document: the operations, with the published JSON Schemas as
+/// components
+fn document() -> Value {
+    let mut paths = Map::new();
+    for &(method, path, summary, content_type) in OPERATIONS {
+        let item = paths
+            .entry(path.to_string())
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .unwrap();
+        item.insert(
+            method.to_lowercase(),
+            operation(method, path, summary, content_type),
+        );
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "KataGo Server",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "REST API for KataGo analysis. Errors are RFC 7807 problem details.",
+        },
+        "paths": paths,
+        "components": {
+            "schemas": components(),
+            "securitySchemes": {
+                "adminToken": { "type": "http", "scheme": "bearer" },
+            },
+        },
+    })
+}
+
+fn operation(method: &str, path: &str, summary: &str, content_type: &str) -> Value {
+    let find = |table: &[(&str, &str, &'static str)]| {
+        table
+            .iter()
+            .find(|(m, p, _)| *m == method && *p == path)
+            .map(|(_, _, name)| *name)
+    };
+    let mut success = match find(RESPONSE_SCHEMAS) {
+        Some(name) => json!({
+            "description": "Success",
+            "content": { JSON: { "schema": reference(name) } },
+        }),
+        None => json!({
+            "description": "Success",
+            "content": { content_type: {} },
+        }),
+    };
+
+    let binary = BINARY_RESPONSES
+        .iter()
+        .find(|(m, p, _)| *m == method && *p == path)
+        .map_or(&[][..], |(_, _, types)| *types);
+    for content_type in binary {
+        success["content"][*content_type] = json!({});
+    }
+
+    let mut operation = json!({
+        "summary": summary,
+        "responses": {
+            "200": success,
+            "default": {
+                "description": "Error",
+                "content": { "application/problem+json": { "schema": reference("problem-detail") } },
+            },
+        },
+    });
+    let parameters: Vec<Value> = path
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } }))
+        .collect();
+    if !parameters.is_empty() {
+        operation["parameters"] = json!(parameters);
+    }
+    if CALLBACK_OPERATIONS.contains(&(method, path)) {
+        operation["responses"]["202"] = json!({
+            "description": "Accepted: the result is POSTed to callbackUrl",
+            "content": { JSON: { "schema": reference("callback-accepted") } },
+        });
+    }
+    if let Some(name) = find(REQUEST_SCHEMAS) {
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": { JSON: { "schema": reference(name) } },
+        });
+        // The endpoints that answer in MessagePack read it as well
+        if binary.contains(&MSGPACK) {
+            operation["requestBody"]["content"][MSGPACK] = json!({});
+        }
+    }
+    if path.starts_with("/api/v1/admin/") {
+        operation["security"] = json!([{ "adminToken": [] }]);
+    }
+    operation
+}
+
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// Every published schema under its name, plus the type definitions they share
+/// (schemars puts those in each schema's `$defs`), with references rewritten to
+/// point into the components
+fn components() -> Map<String, Value> {
+    let mut components = Map::new();
+    for (name, generate) in SCHEMAS {
+        let mut schema = generate().to_value();
+        if let Some(object) = schema.as_object_mut() {
+            object.remove("$schema");
+            if let Some(Value::Object(defs)) = object.remove("$defs") {
+                components.extend(defs);
+            }
+        }
+        components.insert(name.to_string(), schema);
+    }
+    let mut components = Value::Object(components);
+    rewrite_refs(&mut components);
+    match components {
+        Value::Object(components) => components,
+        _ => unreachable!(),
+    }
+}
+
+fn rewrite_refs(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(target)) = object.get_mut("$ref") {
+                if let Some(name) = target.strip_prefix("#/$defs/") {
+                    *target = format!("#/components/schemas/{}", name);
+                }
+            }
+            object.values_mut().for_each(rewrite_refs);
+        }
+        Value::Array(items) => items.iter_mut().for_each(rewrite_refs),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refs(value: &Value, found: &mut Vec<String>) {
+        match value {
+            Value::Object(object) => {
+                if let Some(Value::String(target)) = object.get("$ref") {
+                    found.push(target.clone());
+                }
+                object.values().for_each(|v| refs(v, found));
+            }
+            Value::Array(items) => items.iter().for_each(|v| refs(v, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_every_reference_resolves() {
+        let document = document();
+        let mut found = Vec::new();
+        refs(&document, &mut found);
+        assert!(!found.is_empty());
+        for target in found {
+            let pointer = target.strip_prefix('#').unwrap();
+            assert!(document.pointer(pointer).is_some(), "dangling {}", target);
+        }
+    }
+
+    #[test]
+    fn test_schema_tables_only_name_documented_operations() {
+        for (method, path, _) in RESPONSE_SCHEMAS.iter().chain(REQUEST_SCHEMAS) {
+            assert!(
+                OPERATIONS
+                    .iter()
+                    .any(|(m, p, _, _)| m == method && p == path),
+                "{} {} is not in OPERATIONS",
+                method,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_operation_has_path_parameters_and_body() {
+        let document = document();
+        let edit = &document["paths"]["/api/v1/positions/{id}/edit"]["post"];
+        assert_eq!(edit["parameters"][0]["name"], "id");
+        assert_eq!(
+            edit["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/edit-position-request"
+        );
+        let import = &document["paths"]["/api/v1/admin/import"]["post"];
+        assert!(import["security"].is_array());
+    }
+
+    #[test]
+    fn test_binary_formats_are_listed() {
+        let document = document();
+        let analysis = &document["paths"]["/api/v1/analysis"]["post"];
+        let content = analysis["responses"]["200"]["content"].as_object().unwrap();
+        assert!(content.contains_key(MSGPACK) && content.contains_key(PROTOBUF));
+        assert!(analysis["requestBody"]["content"][MSGPACK].is_object());
+        let score = &document["paths"]["/api/v1/score"]["post"];
+        assert!(score["responses"]["200"]["content"][PROTOBUF].is_null());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::api::{resolve_position, shape_ownership, AnalysisRequest, ApiError, AppState};
+use axum::{
+    extract::{Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+
+/// Seconds between partial results when the request sets no reportDuringSearchEvery
+const DEFAULT_REPORT_EVERY_SECS: f32 = 0.5;
+
+/// Shortest interval between partial results, so a client can't flood itself
+const MIN_REPORT_EVERY_SECS: f32 = 0.1;
+
+/// Events buffered ahead of a slow client
+const EVENT_BUFFER: usize = 16;
+
+/// Query string of GET /api/v1/analysis/stream, for EventSource clients that can't
+/// send a body: a stored position plus the most common settings
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamQuery {
+    position_id: String,
+    preset: Option<String>,
+    max_visits: Option<u32>,
+    max_time_secs: Option<f32>,
+    report_during_search_every: Option<f32>,
+    include_ownership: Option<bool>,
+    human_model: Option<String>,
+    model: Option<String>,
+}
+
+impl From<StreamQuery> for AnalysisRequest {
+    fn from(query: StreamQuery) -> Self {
+        AnalysisRequest {
+            position_id: Some(query.position_id),
+            preset: query.preset,
+            max_visits: query.max_visits,
+            max_time_secs: query.max_time_secs,
+            report_during_search_every: query.report_during_search_every,
+            include_ownership: query.include_ownership,
+            human_model: query.human_model,
+            model: query.model,
+            ..Default::default()
+        }
+    }
+}
+
+/// GET /api/v1/analysis/stream?positionId=...
+pub async fn stream_get(
+    State(state): State<AppState>,
+    Query(query): Query<StreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    stream(state, query.into())
+}
+
+/// POST /api/v1/analysis/stream
+///
+/// Analyzes like POST /api/v1/analysis, but sends KataGo's partial results as
+/// `partial` events while the search runs, then the final result as a `result`
+/// event (or a problem detail as an `error` event).
+pub async fn stream_post(
+    State(state): State<AppState>,
+    Json(request): Json<AnalysisRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    stream(state, request)
+}
+
+fn stream(
+    state: AppState,
+    request: AnalysisRequest,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let request = resolve_position(&state.positions, request)?;
+    // Routing errors are still an HTTP status, not an event
+    state.engine.route_request(&request)?;
+    let report_every = request
+        .report_during_search_every
+        .unwrap_or(DEFAULT_REPORT_EVERY_SECS)
+        .max(MIN_REPORT_EVERY_SECS);
+
+    let (events, rx) = mpsc::channel(EVENT_BUFFER);
+    let engine = state.engine.clone();
+    tokio::spawn(async move {
+        let (results_tx, mut results_rx) = mpsc::channel(EVENT_BUFFER);
+        let analysis = engine.analyze_partial(&request, report_every, results_tx);
+        // Dropping results_rx when the client goes away stops the search
+        let forward = {
+            let events = events.clone();
+            let request = request.clone();
+            async move {
+                while let Some(mut result) = results_rx.recv().await {
+                    shape_ownership(&mut result, &request);
+                    let name = if result.is_during_search {
+                        "partial"
+                    } else {
+                        "result"
+                    };
+                    if events.send(event(name, &result)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        };
+        let (outcome, ()) = tokio::join!(analysis, forward);
+        if let Err(e) = outcome {
+            let _ = events
+                .send(event("error", &ApiError::from(e).into_problem()))
+                .await;
+        }
+    });
+
+    let events = ReceiverStream::new(rx).map(Ok);
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn event<T: Serialize>(name: &str, data: &T) -> Event {
+    Event::default()
+        .event(name)
+        .json_data(data)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_query_string() {
+        let Query(query): Query<StreamQuery> = Query::try_from_uri(
+            &"/api/v1/analysis/stream?positionId=p1&maxVisits=500&reportDuringSearchEvery=0.25"
+                .parse()
+                .unwrap(),
+        )
+        .unwrap();
+        let request = AnalysisRequest::from(query);
+        assert_eq!(request.position_id.as_deref(), Some("p1"));
+        assert_eq!(request.max_visits, Some(500));
+        assert_eq!(request.report_during_search_every, Some(0.25));
+        assert!(request.include_ownership.is_none());
+    }
+}
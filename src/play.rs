@@ -0,0 +1,290 @@
+use crate::analysis_engine::AnalysisEngine;
+use crate::api::{AnalysisRequest, AnalysisResponse, MoveInput, Rules};
+use crate::board::{Board, BoardError, Stone};
+use crate::legality::{self, LegalityError, MoveRules};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+
+/// A game a client plays against the engine, one move at a time
+#[derive(Debug, Clone)]
+pub struct PlaySession {
+    pub id: String,
+    pub board_size: u8,
+    pub komi: Option<f32>,
+    pub rules: Option<Rules>,
+    /// Black's handicap stones; none in an even game
+    pub initial_stones: Vec<(String, String)>,
+    /// Player to move before the first move: White in handicap games
+    pub initial_player: Stone,
+    /// Color the client plays; the engine plays the other
+    pub human_color: Stone,
+    pub moves: Vec<(Stone, String)>,
+    /// Visits the engine searches for each of its moves
+    pub max_visits: Option<u32>,
+    /// Humanlike strength: the engine plays the searched move a player of this
+    /// profile would most likely choose
+    pub human_sl_profile: Option<String>,
+    board: Board,
+}
+
+impl PlaySession {
+    /// A new game with no moves played, from Black's handicap stones (if any);
+    /// komi, rules, and strength are left to the caller
+    pub fn new(
+        id: String,
+        board_size: u8,
+        initial_stones: Vec<(String, String)>,
+        human_color: Stone,
+    ) -> Result<Self, BoardError> {
+        let mut board = Board::new(board_size, board_size);
+        for (color, coord) in &initial_stones {
+            let stone =
+                Stone::parse(color).ok_or_else(|| BoardError::InvalidColor(color.clone()))?;
+            board.place(stone, coord)?;
+        }
+        Ok(Self {
+            id,
+            board_size,
+            komi: None,
+            rules: None,
+            initial_player: if initial_stones.is_empty() {
+                Stone::Black
+            } else {
+                Stone::White
+            },
+            initial_stones,
+            human_color,
+            moves: Vec::new(),
+            max_visits: None,
+            human_sl_profile: None,
+            board,
+        })
+    }
+
+    pub fn player_to_move(&self) -> Stone {
+        self.moves
+            .last()
+            .map_or(self.initial_player, |(stone, _)| stone.opponent())
+    }
+
+    /// The game ends when both players pass in a row
+    pub fn finished(&self) -> bool {
+        let passes = self
+            .moves
+            .iter()
+            .rev()
+            .take_while(|(_, coord)| coord == "pass")
+            .count();
+        passes >= 2
+    }
+
+    /// Play a move for the player to move, returning the points it captured. The
+    /// game is replayed under its rules, so ko and superko are enforced too.
+    pub fn play(&mut self, coord: &str) -> Result<Vec<String>, BoardError> {
+        let stone = self.player_to_move();
+        let coord = if coord.eq_ignore_ascii_case("pass") {
+            "pass".to_string()
+        } else {
+            let (x, y) = self.board.parse_point(coord)?;
+            Board::coord_name(x, y)
+        };
+        let request = self.analysis_request();
+        let mut moves = AnalysisEngine::katago_moves(&request);
+        moves.push(vec![stone.as_str().to_string(), coord.clone()]);
+        let board = legality::replay(
+            self.board_size,
+            self.board_size,
+            &AnalysisEngine::katago_initial_stones(&request),
+            &moves,
+            MoveRules::of(&AnalysisEngine::rules(&request)),
+        )
+        .map_err(|e| match e {
+            LegalityError::Setup(error) | LegalityError::Move { error, .. } => error,
+        })?;
+
+        let remaining: HashSet<(Stone, String)> = board.stones().into_iter().collect();
+        let captured = self
+            .board
+            .stones()
+            .into_iter()
+            .filter(|point| point.0 == stone.opponent() && !remaining.contains(point))
+            .map(|(_, point)| point)
+            .collect();
+        self.board = board;
+        self.moves.push((stone, coord));
+        Ok(captured)
+    }
+
+    /// The query for the engine's next move
+    pub fn analysis_request(&self) -> AnalysisRequest {
+        AnalysisRequest {
+            moves: self
+                .moves
+                .iter()
+                .map(|(stone, coord)| {
+                    MoveInput::WithColor([stone.as_str().to_string(), coord.clone()])
+                })
+                .collect(),
+            initial_stones: Some(self.initial_stones.clone()),
+            initial_player: Some(self.initial_player.as_str().to_string()),
+            board_x_size: self.board_size,
+            board_y_size: self.board_size,
+            komi: self.komi,
+            rules: self.rules.clone(),
+            max_visits: self.max_visits,
+            override_settings: self
+                .human_sl_profile
+                .as_ref()
+                .map(|profile| serde_json::json!({ "humanSLProfile": profile })),
+            ..Default::default()
+        }
+    }
+
+    /// The engine's move in its analysis of the position: its best move, or with a
+    /// human profile the searched move with the highest human prior
+    pub fn choose_move(&self, response: &AnalysisResponse) -> Option<String> {
+        let infos = response.move_infos.as_deref().unwrap_or_default();
+        let best = infos.iter().min_by_key(|info| info.order);
+        let chosen = match self.human_sl_profile {
+            Some(_) => infos
+                .iter()
+                .filter(|info| info.human_prior.is_some())
+                .max_by(|a, b| a.human_prior.partial_cmp(&b.human_prior).unwrap())
+                .or(best),
+            None => best,
+        };
+        chosen.map(|info| info.move_coord.clone())
+    }
+}
+
+/// Play sessions by id. A session unused for the TTL is discarded, and once
+/// `capacity` sessions exist, creating one discards the least recently used.
+pub struct PlaySessions {
+    sessions: StdMutex<HashMap<String, (Instant, PlaySession)>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl PlaySessions {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            sessions: StdMutex::new(HashMap::new()),
+            ttl,
+            capacity,
+        }
+    }
+
+    pub fn insert(&self, session: PlaySession) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        sessions.retain(|_, (used, _)| now.duration_since(*used) < self.ttl);
+        while sessions.len() >= self.capacity.max(1) {
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(id, _)| id.clone());
+            match oldest {
+                Some(id) => sessions.remove(&id),
+                None => break,
+            };
+        }
+        sessions.insert(session.id.clone(), (now, session));
+    }
+
+    /// The session, if it exists and hasn't been idle for the TTL; counts as a use
+    pub fn get(&self, id: &str) -> Option<PlaySession> {
+        self.update(id, |session| session.clone())
+    }
+
+    /// Apply `f` to the session in place, returning its result (None if the id is
+    /// unknown or the session expired); counts as a use
+    pub fn update<R>(&self, id: &str, f: impl FnOnce(&mut PlaySession) -> R) -> Option<R> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        let (used, session) = sessions.get_mut(id)?;
+        if now.duration_since(*used) >= self.ttl {
+            sessions.remove(id);
+            return None;
+        }
+        *used = now;
+        Some(f(session))
+    }
+
+    pub fn remove(&self, id: &str) -> Option<PlaySession> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .remove(id)
+            .map(|(_, session)| session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(id: &str, handicap: bool) -> PlaySession {
+        let stones = if handicap {
+            vec![("B".to_string(), "D4".to_string())]
+        } else {
+            Vec::new()
+        };
+        PlaySession::new(id.to_string(), 9, stones, Stone::Black).unwrap()
+    }
+
+    #[test]
+    fn test_moves_alternate_and_two_passes_finish() {
+        let mut game = session("g", true);
+        assert_eq!(game.player_to_move(), Stone::White);
+        assert_eq!(
+            game.play("d4").unwrap_err(),
+            BoardError::Occupied("D4".to_string())
+        );
+        game.play("e5").unwrap();
+        assert_eq!(game.moves[0], (Stone::White, "E5".to_string()));
+        game.play("pass").unwrap();
+        assert!(!game.finished());
+        game.play("PASS").unwrap();
+        assert!(game.finished());
+
+        let request = game.analysis_request();
+        assert_eq!(request.initial_player.as_deref(), Some("W"));
+        assert_eq!(request.moves.len(), 3);
+        assert_eq!(request.moves[0].color(), Some("W"));
+    }
+
+    #[test]
+    fn test_immediate_ko_recapture_is_rejected() {
+        let mut game = session("g", false);
+        for coord in ["B3", "C3", "A2", "B2", "B1", "D2", "E5", "C1"] {
+            game.play(coord).unwrap();
+        }
+        assert_eq!(game.play("C2").unwrap(), ["B2".to_string()]);
+        assert_eq!(game.play("B2"), Err(BoardError::Ko("B2".to_string())));
+        assert_eq!(game.moves.len(), 9, "the session is unchanged");
+
+        // After a move elsewhere, White may retake
+        game.play("G7").unwrap();
+        game.play("G3").unwrap();
+        assert_eq!(game.play("B2").unwrap(), ["C2".to_string()]);
+    }
+
+    #[test]
+    fn test_idle_and_oldest_sessions_are_evicted() {
+        let sessions = PlaySessions::new(Duration::from_millis(50), 2);
+        sessions.insert(session("a", false));
+        sessions.insert(session("b", false));
+        assert!(sessions.get("a").is_some());
+        sessions.insert(session("c", false));
+        assert!(
+            sessions.get("b").is_none(),
+            "least recently used is evicted"
+        );
+        assert!(sessions.get("a").is_some());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(sessions.get("a").is_none());
+        assert!(sessions.update("c", |_| ()).is_none());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::api::Rules;
+use crate::board::{Board, BoardError, Stone};
+use crate::store::BoundedStore;
+
+/// Maximum number of positions kept in memory before the oldest are evicted
+const MAX_STORED_POSITIONS: usize = 10_000;
+
+/// A stored board position that can be analyzed or edited by id
+#[derive(Debug, Clone)]
+pub struct Position {
+    pub id: String,
+    pub parent_id: Option<String>,
+    pub board: Board,
+    pub player_to_move: Stone,
+    pub rules: Option<Rules>,
+    pub komi: Option<f32>,
+}
+
+/// Edits applied to a stored position to derive a new one
+#[derive(Debug, Default)]
+pub struct PositionEdit {
+    /// Stones to add as (color, coordinate)
+    pub add: Vec<(Stone, String)>,
+    /// Coordinates to clear
+    pub remove: Vec<String>,
+    /// Override the player to move in the derived position
+    pub player_to_move: Option<Stone>,
+}
+
+impl Position {
+    /// Derive a new position by removing then adding stones.
+    /// Adding to an occupied point, removing from an empty one, or leaving any group
+    /// without liberties is rejected.
+    pub fn derive(&self, edit: &PositionEdit) -> Result<Position, BoardError> {
+        let mut board = self.board.clone();
+        for coord in &edit.remove {
+            board.remove(coord)?;
+        }
+        for (stone, coord) in &edit.add {
+            board.place(*stone, coord)?;
+        }
+        board.check_liberties()?;
+
+        Ok(Position {
+            id: uuid::Uuid::new_v4().to_string(),
+            parent_id: Some(self.id.clone()),
+            board,
+            player_to_move: edit.player_to_move.unwrap_or(self.player_to_move),
+            rules: self.rules.clone(),
+            komi: self.komi,
+        })
+    }
+}
+
+/// In-memory store of positions keyed by id
+pub type PositionStore = BoundedStore<Position>;
+
+impl PositionStore {
+    pub fn new() -> Self {
+        Self::with_capacity(MAX_STORED_POSITIONS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stone_at(board: &Board, coord: &str) -> Option<Stone> {
+        board
+            .stones()
+            .into_iter()
+            .find(|(_, c)| c == coord)
+            .map(|(stone, _)| stone)
+    }
+
+    fn position(id: &str) -> Position {
+        Position {
+            id: id.to_string(),
+            parent_id: None,
+            board: Board::new(9, 9),
+            player_to_move: Stone::Black,
+            rules: None,
+            komi: Some(7.0),
+        }
+    }
+
+    #[test]
+    fn test_derive_adds_and_removes_stones() {
+        let mut base = position("base");
+        base.board.place(Stone::White, "E5").unwrap();
+
+        let edit = PositionEdit {
+            add: vec![(Stone::Black, "C3".to_string())],
+            remove: vec!["E5".to_string()],
+            player_to_move: Some(Stone::White),
+        };
+        let derived = base.derive(&edit).unwrap();
+
+        assert_eq!(derived.parent_id.as_deref(), Some("base"));
+        assert_ne!(derived.id, base.id);
+        assert_eq!(stone_at(&derived.board, "C3"), Some(Stone::Black));
+        assert_eq!(stone_at(&derived.board, "E5"), None);
+        assert_eq!(derived.player_to_move, Stone::White);
+        assert_eq!(derived.komi, Some(7.0));
+        // The original position is untouched
+        assert_eq!(stone_at(&base.board, "E5"), Some(Stone::White));
+    }
+
+    #[test]
+    fn test_derive_rejects_stone_without_liberties() {
+        let mut base = position("base");
+        base.board.place(Stone::Black, "A2").unwrap();
+        base.board.place(Stone::Black, "B1").unwrap();
+
+        let edit = PositionEdit {
+            add: vec![(Stone::White, "A1".to_string())],
+            ..Default::default()
+        };
+        assert_eq!(
+            base.derive(&edit).unwrap_err(),
+            BoardError::NoLiberties("A1".to_string())
+        );
+    }
+}
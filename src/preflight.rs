@@ -0,0 +1,289 @@
+use crate::config::Config;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::info;
+
+/// How long `katago version` may take; it loads no model, so this is generous
+const VERSION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Check the KataGo binary, model, and config files of every configured engine
+/// before any of them starts, failing with every problem found at once
+pub async fn check(config: &Config) -> anyhow::Result<()> {
+    let mut problems = Vec::new();
+
+    let katago = match executable(&config.katago.katago_path) {
+        Ok(path) => Some(path),
+        Err(problem) => {
+            problems.push(format!("katago.katago_path: {}", problem));
+            None
+        }
+    };
+
+    let mut config_files = BTreeSet::new();
+    for (setting, path, is_config) in files(config) {
+        match check_file(path) {
+            Err(problem) => problems.push(format!("{}: {}", setting, problem)),
+            Ok(()) if is_config => {
+                config_files.insert(path);
+            }
+            Ok(()) => {}
+        }
+    }
+    for path in config_files {
+        if let Err(problem) = check_katago_config(Path::new(path)) {
+            problems.push(format!("{}: {}", path, problem));
+        }
+    }
+
+    if let Some(katago) = katago {
+        match katago_version(&katago).await {
+            Ok(version) => info!("Found {} at {}", version, katago.display()),
+            Err(problem) => problems.push(format!(
+                "katago.katago_path: `{} version` {}",
+                katago.display(),
+                problem
+            )),
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "{} configuration problem(s) found before starting KataGo:\n  - {}",
+        problems.len(),
+        problems.join("\n  - ")
+    )
+}
+
+/// Every file an engine is started with: the setting naming it, its path, and
+/// whether it is a KataGo config file
+fn files(config: &Config) -> Vec<(String, &str, bool)> {
+    let katago = &config.katago;
+    let mut files = vec![
+        (
+            "katago.model_path".to_string(),
+            katago.model_path.as_str(),
+            false,
+        ),
+        (
+            "katago.config_path".to_string(),
+            katago.config_path.as_str(),
+            true,
+        ),
+    ];
+    if let Some(path) = &katago.human_model_path {
+        files.push(("katago.human_model_path".to_string(), path, false));
+    }
+    for route in &config.engines {
+        let setting = |field: &str| format!("engines '{}' {}", route.name, field);
+        files.push((setting("model_path"), &route.model_path, false));
+        if let Some(path) = &route.config_path {
+            files.push((setting("config_path"), path, true));
+        }
+        if let Some(path) = &route.human_model_path {
+            files.push((setting("human_model_path"), path, false));
+        }
+    }
+    for (name, model) in &katago.models {
+        let setting = |field: &str| format!("katago.models.{}.{}", name, field);
+        files.push((setting("model_path"), &model.model_path, false));
+        if let Some(path) = &model.config_path {
+            files.push((setting("config_path"), path, true));
+        }
+        if let Some(path) = &model.human_model_path {
+            files.push((setting("human_model_path"), path, false));
+        }
+    }
+    if config.gtp.enabled {
+        // Not an analysis config, but parsed by KataGo the same way
+        files.push(("gtp.config_path".to_string(), &config.gtp.config_path, true));
+        if let Some(path) = &config.gtp.model_path {
+            files.push(("gtp.model_path".to_string(), path, false));
+        }
+    }
+    files
+}
+
+fn check_file(path: &str) -> Result<(), String> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => Ok(()),
+        Ok(_) => Err(format!("{} is not a file", path)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(format!("{} does not exist", path))
+        }
+        Err(e) => Err(format!("{} can't be read: {}", path, e)),
+    }
+}
+
+/// The KataGo binary `katago_path` names: a path, or a command looked up on PATH
+fn executable(katago_path: &str) -> Result<PathBuf, String> {
+    let path = if katago_path.contains(std::path::MAIN_SEPARATOR) || katago_path.contains('/') {
+        PathBuf::from(katago_path)
+    } else {
+        std::env::var_os("PATH")
+            .iter()
+            .flat_map(std::env::split_paths)
+            .map(|dir| dir.join(katago_path))
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| format!("{} was not found on PATH", katago_path))?
+    };
+    check_file(&path.display().to_string())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path)
+            .map_err(|e| e.to_string())?
+            .permissions()
+            .mode();
+        if mode & 0o111 == 0 {
+            return Err(format!(
+                "{} is not executable (chmod +x {})",
+                path.display(),
+                path.display()
+            ));
+        }
+    }
+    Ok(path)
+}
+
+/// Check a KataGo config file the way KataGo reads it: `key = value` lines,
+/// `#` comments, and `@include` directives
+fn check_katago_config(path: &Path) -> Result<(), String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("can't be read: {}", e))?;
+    let mut keys = BTreeSet::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() || line.starts_with("@include") {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!(
+                "line {}: expected `key = value`, found {:?}",
+                number + 1,
+                line
+            ));
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("line {}: invalid key {:?}", number + 1, key));
+        }
+        if value.trim().is_empty() {
+            return Err(format!("line {}: {} has no value", number + 1, key));
+        }
+        if !keys.insert(key) {
+            return Err(format!("line {}: {} is set twice", number + 1, key));
+        }
+    }
+    Ok(())
+}
+
+/// First line `katago version` prints, e.g. "KataGo v1.16.0"
+async fn katago_version(katago: &Path) -> Result<String, String> {
+    let output = tokio::time::timeout(
+        VERSION_TIMEOUT,
+        Command::new(katago)
+            .arg("version")
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| format!("did not finish within {} s", VERSION_TIMEOUT.as_secs()))?
+    .map_err(|e| format!("could not run: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "failed ({}): {}",
+            output.status,
+            stderr.lines().last().unwrap_or_default().trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .unwrap_or("KataGo")
+        .trim()
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "katago-preflight-{}-{}",
+            name,
+            uuid::Uuid::new_v4()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_every_problem_is_reported_at_once() {
+        let dir = temp_dir("missing");
+        let mut config = Config::default();
+        config.katago.katago_path = dir.join("katago").display().to_string();
+        config.katago.model_path = dir.join("model.bin.gz").display().to_string();
+        config.katago.config_path = dir.display().to_string();
+
+        let message = check(&config).await.unwrap_err().to_string();
+        assert!(
+            message.starts_with("3 configuration problem(s)"),
+            "{}",
+            message
+        );
+        assert!(message.contains("katago.katago_path: "), "{}", message);
+        assert!(
+            message.contains("model.bin.gz does not exist"),
+            "{}",
+            message
+        );
+        assert!(message.contains("is not a file"), "{}", message);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_katago_must_be_executable() {
+        let dir = temp_dir("exec");
+        let katago = dir.join("katago");
+        fs::write(&katago, "#!/bin/sh\necho KataGo v1.16.0\n").unwrap();
+        let error = executable(katago.to_str().unwrap()).unwrap_err();
+        assert!(error.contains("is not executable"), "{}", error);
+
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&katago, fs::Permissions::from_mode(0o755)).unwrap();
+        let path = executable(katago.to_str().unwrap()).unwrap();
+        assert_eq!(katago_version(&path).await.unwrap(), "KataGo v1.16.0");
+    }
+
+    #[test]
+    fn test_config_lines_must_be_settings() {
+        let dir = temp_dir("cfg");
+        let path = dir.join("analysis.cfg");
+        fs::write(
+            &path,
+            "# Analysis\nnumAnalysisThreads = 4 # per GPU\n\n@include base.cfg\nlogDir = logs\n",
+        )
+        .unwrap();
+        assert!(check_katago_config(&path).is_ok());
+
+        fs::write(&path, "numAnalysisThreads = 4\nnnCacheSizePowerOfTwo 20\n").unwrap();
+        let error = check_katago_config(&path).unwrap_err();
+        assert!(
+            error.starts_with("line 2: expected `key = value`"),
+            "{}",
+            error
+        );
+
+        fs::write(&path, "maxVisits = 10\nmaxVisits = 20\n").unwrap();
+        assert_eq!(
+            check_katago_config(&path).unwrap_err(),
+            "line 2: maxVisits is set twice"
+        );
+    }
+}
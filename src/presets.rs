@@ -0,0 +1,118 @@
+use crate::api::{AnalysisRequest, Rules};
+use crate::scoring::is_area_scoring;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Built-in defaults for a board size, selected with `"preset": "9x9"`.
+/// Values the request sets explicitly always win.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Preset {
+    pub name: &'static str,
+    pub board_size: u8,
+    /// Rules used when the request names none
+    pub default_rules: &'static str,
+    /// Komi under area scoring (chinese, aga, tromp-taylor, ...)
+    pub area_komi: f32,
+    /// Komi under territory scoring (japanese, korean)
+    pub territory_komi: f32,
+    /// Visits per analysis when the request sets no maxVisits
+    pub max_visits: u32,
+}
+
+/// Small boards need far fewer visits per position, so their presets afford more
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "9x9",
+        board_size: 9,
+        default_rules: "chinese",
+        area_komi: 7.0,
+        territory_komi: 5.5,
+        max_visits: 100,
+    },
+    Preset {
+        name: "13x13",
+        board_size: 13,
+        default_rules: "chinese",
+        area_komi: 7.5,
+        territory_komi: 6.5,
+        max_visits: 50,
+    },
+    Preset {
+        name: "19x19",
+        board_size: 19,
+        default_rules: "chinese",
+        area_komi: 7.5,
+        territory_komi: 6.5,
+        max_visits: 10,
+    },
+];
+
+impl Preset {
+    pub fn find(name: &str) -> Option<&'static Preset> {
+        PRESETS.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Komi for a ruleset; territory rules get the lower komi
+    pub fn komi_for(&self, rules: &Rules) -> f32 {
+        if is_area_scoring(rules) {
+            self.area_komi
+        } else {
+            self.territory_komi
+        }
+    }
+
+    /// Set the board size and fill in rules, komi, and visits the request leaves unset
+    pub fn apply(&self, request: &mut AnalysisRequest) {
+        request.board_x_size = self.board_size;
+        request.board_y_size = self.board_size;
+        let rules = request
+            .rules
+            .get_or_insert_with(|| self.default_rules.into());
+        let komi = self.komi_for(rules);
+        request.komi.get_or_insert(komi);
+        request.max_visits.get_or_insert(self.max_visits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_board_komi_by_ruleset() {
+        let preset = Preset::find("9X9").unwrap();
+        assert_eq!(preset.komi_for(&"chinese".into()), 7.0);
+        assert_eq!(preset.komi_for(&"Japanese".into()), 5.5);
+        assert!(Preset::find("7x7").is_none());
+    }
+
+    #[test]
+    fn test_apply_fills_only_missing_values() {
+        let preset = Preset::find("9x9").unwrap();
+
+        let mut request = AnalysisRequest::default();
+        preset.apply(&mut request);
+        assert_eq!(request.board_x_size, 9);
+        assert_eq!(request.board_y_size, 9);
+        assert_eq!(request.rules, Some("chinese".into()));
+        assert_eq!(request.komi, Some(7.0));
+        assert_eq!(request.max_visits, Some(100));
+
+        let mut request = AnalysisRequest {
+            rules: Some("japanese".into()),
+            max_visits: Some(5),
+            ..Default::default()
+        };
+        preset.apply(&mut request);
+        assert_eq!(request.komi, Some(5.5));
+        assert_eq!(request.max_visits, Some(5));
+
+        let mut request = AnalysisRequest {
+            komi: Some(0.5),
+            ..Default::default()
+        };
+        preset.apply(&mut request);
+        assert_eq!(request.komi, Some(0.5));
+    }
+}
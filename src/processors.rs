@@ -0,0 +1,206 @@
+use crate::api::{AnalysisRequest, AnalysisResponse};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// What a processor can see besides the result itself
+pub struct ResultContext<'a> {
+    pub request: &'a AnalysisRequest,
+    /// Human SL model loaded by the engine that answered
+    pub human_model: Option<String>,
+}
+
+/// A post-processing step for analysis results. Processors run in the order
+/// configured in `[katago] result_processors`, each on the previous one's output.
+pub trait ResultProcessor: Send + Sync {
+    fn process(&self, context: &ResultContext, response: &mut AnalysisResponse);
+}
+
+/// A configured processor, e.g. `{ kind = "trim-moves", max_moves = 5 }`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum ProcessorConfig {
+    /// Log results without candidate moves, which usually mean an illegal position
+    EmptyResultWarning,
+    /// Name the human model in results that contain human-model output
+    HumanModelTag,
+    /// Keep only the best candidate moves
+    TrimMoves { max_moves: usize },
+}
+
+impl ProcessorConfig {
+    fn build(&self) -> Box<dyn ResultProcessor> {
+        match self {
+            ProcessorConfig::EmptyResultWarning => Box::new(EmptyResultWarning),
+            ProcessorConfig::HumanModelTag => Box::new(HumanModelTag),
+            ProcessorConfig::TrimMoves { max_moves } => Box::new(TrimMoves {
+                max_moves: *max_moves,
+            }),
+        }
+    }
+}
+
+/// The processors every engine ran before the pipeline was configurable
+pub fn default_processors() -> Vec<ProcessorConfig> {
+    vec![
+        ProcessorConfig::EmptyResultWarning,
+        ProcessorConfig::HumanModelTag,
+    ]
+}
+
+/// An engine's configured processors, in order
+pub struct Pipeline {
+    processors: Vec<Box<dyn ResultProcessor>>,
+}
+
+impl Pipeline {
+    pub fn new(configs: &[ProcessorConfig]) -> Self {
+        Self {
+            processors: configs.iter().map(ProcessorConfig::build).collect(),
+        }
+    }
+
+    pub fn run(&self, context: &ResultContext, response: &mut AnalysisResponse) {
+        for processor in &self.processors {
+            processor.process(context, response);
+        }
+    }
+}
+
+struct EmptyResultWarning;
+
+impl ResultProcessor for EmptyResultWarning {
+    fn process(&self, context: &ResultContext, response: &mut AnalysisResponse) {
+        if response.move_infos.as_ref().is_some_and(|m| !m.is_empty()) {
+            return;
+        }
+        let request = context.request;
+        warn!(
+            "KataGo returned empty moveInfos for request {}: board={}x{}, moves={:?}",
+            response.id, request.board_x_size, request.board_y_size, request.moves
+        );
+        if response.root_info.is_none() {
+            warn!("No rootInfo either - the position may be invalid or moves may be illegal");
+        }
+    }
+}
+
+struct HumanModelTag;
+
+impl ResultProcessor for HumanModelTag {
+    fn process(&self, context: &ResultContext, response: &mut AnalysisResponse) {
+        let human_output = response.human_policy.is_some()
+            || response
+                .root_info
+                .as_ref()
+                .is_some_and(|ri| ri.human_winrate.is_some())
+            || response
+                .move_infos
+                .iter()
+                .flatten()
+                .any(|mi| mi.human_prior.is_some());
+        if human_output {
+            response.human_model = context.human_model.clone();
+        }
+    }
+}
+
+struct TrimMoves {
+    max_moves: usize,
+}
+
+impl ResultProcessor for TrimMoves {
+    fn process(&self, _context: &ResultContext, response: &mut AnalysisResponse) {
+        if let Some(move_infos) = &mut response.move_infos {
+            move_infos.sort_by_key(|mi| mi.order);
+            move_infos.truncate(self.max_moves);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn move_info(coord: &str, order: u32, human_prior: Option<f32>) -> serde_json::Value {
+        serde_json::json!({
+            "moveCoord": coord,
+            "visits": 10,
+            "winrate": 0.5,
+            "scoreMean": 0.0,
+            "scoreStdev": 10.0,
+            "scoreLead": 0.0,
+            "utility": 0.0,
+            "lcb": 0.5,
+            "prior": 0.1,
+            "humanPrior": human_prior,
+            "order": order,
+        })
+    }
+
+    fn response(move_infos: Vec<serde_json::Value>) -> AnalysisResponse {
+        serde_json::from_value(serde_json::json!({
+            "id": "r",
+            "turnNumber": 0,
+            "isDuringSearch": false,
+            "moveInfos": move_infos,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_pipeline_runs_in_configured_order() {
+        let request = AnalysisRequest::default();
+        let context = ResultContext {
+            request: &request,
+            human_model: Some("humanv0.bin.gz".to_string()),
+        };
+        let pipeline = Pipeline::new(&[
+            ProcessorConfig::TrimMoves { max_moves: 2 },
+            ProcessorConfig::HumanModelTag,
+        ]);
+
+        let mut result = response(vec![
+            move_info("Q16", 2, Some(0.2)),
+            move_info("D4", 0, None),
+            move_info("C3", 1, None),
+        ]);
+        pipeline.run(&context, &mut result);
+        let kept: Vec<_> = result
+            .move_infos
+            .unwrap()
+            .into_iter()
+            .map(|mi| mi.move_coord)
+            .collect();
+        assert_eq!(kept, ["D4", "C3"]);
+        // The only human-model output was trimmed away first
+        assert!(result.human_model.is_none());
+
+        let mut result = response(vec![move_info("D4", 0, Some(0.4))]);
+        pipeline.run(&context, &mut result);
+        assert_eq!(result.human_model.as_deref(), Some("humanv0.bin.gz"));
+    }
+
+    #[test]
+    fn test_processor_config_toml() {
+        #[derive(Deserialize)]
+        struct Processors {
+            result_processors: Vec<ProcessorConfig>,
+        }
+        let parsed: Processors = toml::from_str(
+            r#"
+result_processors = [
+    { kind = "empty-result-warning" },
+    { kind = "trim-moves", max_moves = 5 },
+]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parsed.result_processors,
+            [
+                ProcessorConfig::EmptyResultWarning,
+                ProcessorConfig::TrimMoves { max_moves: 5 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! Protocol Buffers responses of POST /api/v1/analysis
+//!
+//! The messages mirror proto/analysis.proto by hand (prost derives, no protoc at
+//! build time); keep the two in step.
+
+use crate::api::{self, BoardCorner, Ownership};
+use axum::{
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use prost::Message;
+
+/// Content type of Protocol Buffers responses
+pub const PROTOBUF: &str = "application/x-protobuf";
+
+/// The schema the messages are encoded with, served at GET /api/v1/analysis.proto
+pub const SCHEMA: &str = include_str!("../proto/analysis.proto");
+
+/// Whether the client asked for Protocol Buffers with its Accept header
+pub fn accepted(headers: &HeaderMap) -> bool {
+    crate::msgpack::accepts(headers, &[PROTOBUF, "application/protobuf"])
+}
+
+/// `response` encoded as an `AnalysisResponse` message
+pub fn respond(response: &api::AnalysisResponse) -> Response {
+    let mut response = (
+        [(header::CONTENT_TYPE, PROTOBUF)],
+        AnalysisResponse::from(response).encode_to_vec(),
+    )
+        .into_response();
+    // The same URL answers in JSON or MessagePack too
+    response
+        .headers_mut()
+        .insert(header::VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+/// GET /api/v1/analysis.proto
+pub async fn schema() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        SCHEMA,
+    )
+}
+
+/// Ownership as the flat array the schema has; reshaped ownership is not sent
+/// as protobuf, since the handler asks for the flat format
+fn flat(ownership: Option<&Ownership>) -> Vec<f32> {
+    ownership
+        .and_then(Ownership::as_flat)
+        .map(<[f32]>::to_vec)
+        .unwrap_or_default()
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AnalysisResponse {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(uint32, tag = "2")]
+    pub turn_number: u32,
+    #[prost(bool, tag = "3")]
+    pub is_during_search: bool,
+    #[prost(message, repeated, tag = "4")]
+    pub move_infos: Vec<MoveInfo>,
+    #[prost(message, optional, tag = "5")]
+    pub root_info: Option<RootInfo>,
+    #[prost(float, repeated, tag = "6")]
+    pub ownership: Vec<f32>,
+    #[prost(float, repeated, tag = "7")]
+    pub ownership_stdev: Vec<f32>,
+    #[prost(float, repeated, tag = "8")]
+    pub policy: Vec<f32>,
+    #[prost(float, repeated, tag = "9")]
+    pub human_policy: Vec<f32>,
+    #[prost(string, optional, tag = "10")]
+    pub human_model: Option<String>,
+    #[prost(message, repeated, tag = "11")]
+    pub josekis: Vec<JosekiMatch>,
+    #[prost(message, repeated, tag = "12")]
+    pub warnings: Vec<EngineWarning>,
+    #[prost(message, optional, tag = "13")]
+    pub meta: Option<AnalysisMeta>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MoveInfo {
+    #[prost(string, tag = "1")]
+    pub move_coord: String,
+    #[prost(uint32, tag = "2")]
+    pub visits: u32,
+    #[prost(float, tag = "3")]
+    pub winrate: f32,
+    #[prost(float, tag = "4")]
+    pub score_mean: f32,
+    #[prost(float, tag = "5")]
+    pub score_stdev: f32,
+    #[prost(float, tag = "6")]
+    pub score_lead: f32,
+    #[prost(float, tag = "7")]
+    pub utility: f32,
+    #[prost(float, optional, tag = "8")]
+    pub utility_lcb: Option<f32>,
+    #[prost(float, tag = "9")]
+    pub lcb: f32,
+    #[prost(float, tag = "10")]
+    pub prior: f32,
+    #[prost(float, optional, tag = "11")]
+    pub human_prior: Option<f32>,
+    #[prost(uint32, tag = "12")]
+    pub order: u32,
+    #[prost(string, repeated, tag = "13")]
+    pub pv: Vec<String>,
+    #[prost(uint32, repeated, tag = "14")]
+    pub pv_visits: Vec<u32>,
+    #[prost(float, repeated, tag = "15")]
+    pub ownership: Vec<f32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RootInfo {
+    #[prost(float, tag = "1")]
+    pub winrate: f32,
+    #[prost(float, tag = "2")]
+    pub score_lead: f32,
+    #[prost(float, tag = "3")]
+    pub utility: f32,
+    #[prost(uint32, tag = "4")]
+    pub visits: u32,
+    #[prost(string, tag = "5")]
+    pub current_player: String,
+    #[prost(float, optional, tag = "6")]
+    pub raw_winrate: Option<f32>,
+    #[prost(float, optional, tag = "7")]
+    pub raw_score_mean: Option<f32>,
+    #[prost(float, optional, tag = "8")]
+    pub raw_st_score_error: Option<f32>,
+    #[prost(float, optional, tag = "9")]
+    pub human_winrate: Option<f32>,
+    #[prost(float, optional, tag = "10")]
+    pub human_score_mean: Option<f32>,
+    #[prost(float, optional, tag = "11")]
+    pub human_score_stdev: Option<f32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct JosekiMatch {
+    #[prost(enumeration = "Corner", tag = "1")]
+    pub corner: i32,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(message, repeated, tag = "3")]
+    pub continuations: Vec<JosekiContinuation>,
+}
+
+/// JosekiMatch.Corner
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Corner {
+    Unspecified = 0,
+    TopLeft = 1,
+    TopRight = 2,
+    BottomLeft = 3,
+    BottomRight = 4,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct JosekiContinuation {
+    #[prost(string, tag = "1")]
+    pub player: String,
+    #[prost(string, tag = "2")]
+    pub r#move: String,
+    #[prost(string, tag = "3")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct EngineWarning {
+    #[prost(string, optional, tag = "1")]
+    pub field: Option<String>,
+    #[prost(string, tag = "2")]
+    pub message: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AnalysisMeta {
+    #[prost(uint64, tag = "1")]
+    pub queue_wait_ms: u64,
+    #[prost(uint64, tag = "2")]
+    pub search_ms: u64,
+    #[prost(uint64, tag = "3")]
+    pub total_ms: u64,
+    #[prost(uint32, tag = "4")]
+    pub effective_visits: u32,
+    #[prost(double, optional, tag = "5")]
+    pub visits_per_second: Option<f64>,
+    #[prost(bool, tag = "6")]
+    pub cached: bool,
+    #[prost(bool, tag = "7")]
+    pub coalesced: bool,
+}
+
+impl From<&api::AnalysisResponse> for AnalysisResponse {
+    fn from(response: &api::AnalysisResponse) -> Self {
+        Self {
+            id: response.id.clone(),
+            turn_number: response.turn_number,
+            is_during_search: response.is_during_search,
+            move_infos: response
+                .move_infos
+                .iter()
+                .flatten()
+                .map(MoveInfo::from)
+                .collect(),
+            root_info: response.root_info.as_ref().map(RootInfo::from),
+            ownership: flat(response.ownership.as_ref()),
+            ownership_stdev: flat(response.ownership_stdev.as_ref()),
+            policy: response.policy.clone().unwrap_or_default(),
+            human_policy: response.human_policy.clone().unwrap_or_default(),
+            human_model: response.human_model.clone(),
+            josekis: response
+                .josekis
+                .iter()
+                .flatten()
+                .map(|joseki| JosekiMatch {
+                    corner: Corner::from(joseki.corner) as i32,
+                    name: joseki.name.clone(),
+                    continuations: joseki
+                        .continuations
+                        .iter()
+                        .map(|continuation| JosekiContinuation {
+                            player: continuation.player.clone(),
+                            r#move: continuation.move_coord.clone(),
+                            name: continuation.name.clone(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            warnings: response
+                .warnings
+                .iter()
+                .map(|warning| EngineWarning {
+                    field: warning.field.clone(),
+                    message: warning.message.clone(),
+                })
+                .collect(),
+            meta: response.meta.as_ref().map(|meta| AnalysisMeta {
+                queue_wait_ms: meta.queue_wait_ms,
+                search_ms: meta.search_ms,
+                total_ms: meta.total_ms,
+                effective_visits: meta.effective_visits,
+                visits_per_second: meta.visits_per_second,
+                cached: meta.cached,
+                coalesced: meta.coalesced,
+            }),
+        }
+    }
+}
+
+impl From<&api::MoveInfo> for MoveInfo {
+    fn from(info: &api::MoveInfo) -> Self {
+        Self {
+            move_coord: info.move_coord.clone(),
+            visits: info.visits,
+            winrate: info.winrate,
+            score_mean: info.score_mean,
+            score_stdev: info.score_stdev,
+            score_lead: info.score_lead,
+            utility: info.utility,
+            utility_lcb: info.utility_lcb,
+            lcb: info.lcb,
+            prior: info.prior,
+            human_prior: info.human_prior,
+            order: info.order,
+            pv: info.pv.clone().unwrap_or_default(),
+            pv_visits: info.pv_visits.clone().unwrap_or_default(),
+            ownership: flat(info.ownership.as_ref()),
+        }
+    }
+}
+
+impl From<&api::RootInfo> for RootInfo {
+    fn from(info: &api::RootInfo) -> Self {
+        Self {
+            winrate: info.winrate,
+            score_lead: info.score_lead,
+            utility: info.utility,
+            visits: info.visits,
+            current_player: info.current_player.clone(),
+            raw_winrate: info.raw_winrate,
+            raw_score_mean: info.raw_score_mean,
+            raw_st_score_error: info.raw_st_score_error,
+            human_winrate: info.human_winrate,
+            human_score_mean: info.human_score_mean,
+            human_score_stdev: info.human_score_stdev,
+        }
+    }
+}
+
+impl From<BoardCorner> for Corner {
+    fn from(corner: BoardCorner) -> Self {
+        match corner {
+            BoardCorner::TopLeft => Self::TopLeft,
+            BoardCorner::TopRight => Self::TopRight,
+            BoardCorner::BottomLeft => Self::BottomLeft,
+            BoardCorner::BottomRight => Self::BottomRight,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analysis_response_encodes_per_schema() {
+        let response: api::AnalysisResponse = serde_json::from_value(serde_json::json!({
+            "id": "a1",
+            "turnNumber": 3,
+            "isDuringSearch": false,
+            "moveInfos": [{
+                "moveCoord": "D4", "visits": 10, "winrate": 0.5, "scoreMean": 1.0,
+                "scoreStdev": 2.0, "scoreLead": 1.0, "utility": 0.1, "lcb": 0.4,
+                "prior": 0.25, "order": 0, "pv": ["D4", "Q16"]
+            }],
+            "rootInfo": {
+                "winrate": 0.5, "scoreLead": 1.0, "utility": 0.1, "visits": 10,
+                "currentPlayer": "W"
+            },
+            "ownership": [0.5, -0.25],
+            "meta": {
+                "queueWaitMs": 2, "searchMs": 40, "totalMs": 45, "effectiveVisits": 10,
+                "visitsPerSecond": 250.0, "cached": false
+            }
+        }))
+        .unwrap();
+        let bytes = AnalysisResponse::from(&response).encode_to_vec();
+        // Field 1 (id) is length-delimited: tag 0x0a, length 2, "a1"
+        assert_eq!(&bytes[..4], b"\x0a\x02a1");
+
+        let decoded = AnalysisResponse::decode(bytes.as_slice()).unwrap();
+        assert_eq!(decoded.turn_number, 3);
+        assert_eq!(decoded.move_infos[0].move_coord, "D4");
+        assert_eq!(decoded.move_infos[0].pv, ["D4", "Q16"]);
+        assert_eq!(decoded.move_infos[0].utility_lcb, None);
+        assert_eq!(decoded.root_info.unwrap().current_player, "W");
+        assert_eq!(decoded.ownership, [0.5, -0.25]);
+        assert!(decoded.policy.is_empty());
+        assert_eq!(decoded.meta.unwrap().search_ms, 40);
+    }
+
+    #[test]
+    fn test_schema_declares_every_message() {
+        for message in [
+            "AnalysisResponse",
+            "MoveInfo",
+            "RootInfo",
+            "JosekiMatch",
+            "JosekiContinuation",
+            "EngineWarning",
+            "AnalysisMeta",
+        ] {
+            assert!(SCHEMA.contains(&format!("message {} {{", message)));
+        }
+    }
+}
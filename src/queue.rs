@@ -0,0 +1,207 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// Admission control in front of one KataGo process: at most `max_in_flight`
+/// queries are sent to KataGo at a time, and up to `max_queued` more wait their
+/// turn, highest `priority` first (first come, first served within a priority).
+/// Beyond that, requests are turned away instead of piling up until they time out.
+pub struct RequestQueue {
+    max_in_flight: usize,
+    /// Changed by a configuration reload
+    max_queued: AtomicUsize,
+    state: Arc<Mutex<QueueState>>,
+}
+
+struct QueueState {
+    in_flight: usize,
+    waiting: BinaryHeap<Waiting>,
+    next_seq: u64,
+}
+
+struct Waiting {
+    priority: i32,
+    seq: u64,
+    admit: oneshot::Sender<Ticket>,
+}
+
+impl Ord for Waiting {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Waiting {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiting {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiting {}
+
+/// A query's place among the in-flight ones; handed to the next waiter when dropped
+pub struct Ticket {
+    state: Arc<Mutex<QueueState>>,
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        while let Some(waiting) = state.waiting.pop() {
+            let next = Ticket {
+                state: self.state.clone(),
+            };
+            match waiting.admit.send(next) {
+                Ok(()) => return,
+                // The waiter gave up; its ticket must not release the place again
+                Err(ticket) => std::mem::forget(ticket),
+            }
+        }
+        state.in_flight -= 1;
+    }
+}
+
+/// The queue is full
+#[derive(Debug)]
+pub struct QueueFull {
+    pub queued: usize,
+}
+
+impl RequestQueue {
+    pub fn new(max_in_flight: usize, max_queued: usize) -> Self {
+        Self {
+            max_in_flight: max_in_flight.max(1),
+            max_queued: AtomicUsize::new(max_queued),
+            state: Arc::new(Mutex::new(QueueState {
+                in_flight: 0,
+                waiting: BinaryHeap::new(),
+                next_seq: 0,
+            })),
+        }
+    }
+
+    /// Wait for a place among the in-flight queries
+    pub async fn admit(&self, priority: i32) -> Result<Ticket, QueueFull> {
+        let admitted = {
+            let mut state = self.state.lock().unwrap();
+            if state.in_flight < self.max_in_flight && state.waiting.is_empty() {
+                state.in_flight += 1;
+                return Ok(Ticket {
+                    state: self.state.clone(),
+                });
+            }
+            if state.waiting.len() >= self.max_queued.load(AtomicOrdering::Relaxed) {
+                return Err(QueueFull {
+                    queued: state.waiting.len(),
+                });
+            }
+            let (admit, admitted) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiting.push(Waiting {
+                priority,
+                seq,
+                admit,
+            });
+            admitted
+        };
+        // The sender is only dropped with the queue itself
+        admitted.await.map_err(|_| QueueFull { queued: 0 })
+    }
+
+    /// Change how many queries may wait; ones already waiting keep their place
+    pub fn set_max_queued(&self, max_queued: usize) {
+        self.max_queued.store(max_queued, AtomicOrdering::Relaxed);
+    }
+
+    /// Queries waiting for a place
+    pub fn queued(&self) -> usize {
+        self.state.lock().unwrap().waiting.len()
+    }
+
+    /// Queries sent to KataGo under this queue's control
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_waiters_are_admitted_by_priority() {
+        let queue = Arc::new(RequestQueue::new(1, 8));
+        let running = queue.admit(0).await.unwrap();
+
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        for (name, priority) in [("low", -10), ("first", 0), ("high", 5), ("second", 0)] {
+            let queue = queue.clone();
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let _ticket = queue.admit(priority).await.unwrap();
+                order_tx.send(name).unwrap();
+            });
+            // Keep arrival order deterministic
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(queue.queued(), 4);
+
+        drop(running);
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            order.push(order_rx.recv().await.unwrap());
+        }
+        assert_eq!(order, ["high", "first", "second", "low"]);
+        assert_eq!(queue.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_rejects() {
+        let queue = Arc::new(RequestQueue::new(1, 1));
+        let running = queue.admit(0).await.unwrap();
+        let waiting = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.admit(0).await.is_ok() })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        assert!(queue.admit(100).await.is_err());
+        drop(running);
+        assert!(waiting.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_zero_max_queued_rejects_beyond_in_flight() {
+        let queue = RequestQueue::new(2, 0);
+        let _first = queue.admit(0).await.unwrap();
+        let second = queue.admit(0).await.unwrap();
+        assert!(matches!(queue.admit(0).await, Err(QueueFull { queued: 0 })));
+
+        drop(second);
+        assert!(queue.admit(0).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_waiter_frees_its_place() {
+        let queue = RequestQueue::new(1, 4);
+        let running = queue.admit(0).await.unwrap();
+        let abandoned = tokio::time::timeout(Duration::from_millis(10), queue.admit(0)).await;
+        assert!(abandoned.is_err());
+
+        drop(running);
+        assert_eq!(queue.in_flight(), 0);
+        assert!(queue.admit(0).await.is_ok());
+    }
+}
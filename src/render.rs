@@ -0,0 +1,587 @@
+use crate::api::HeatmapLayer;
+use crate::board::{Board, Stone};
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::ops::RangeInclusive;
+
+/// Pixels between two lines when the request sets none
+pub const DEFAULT_POINT_SIZE: u32 = 32;
+
+/// Point sizes a request may ask for: smaller is unreadable, larger is a
+/// multi-megabyte PNG of a 19x19 board
+pub const POINT_SIZES: RangeInclusive<u32> = 8..=64;
+
+pub type Rgb = [u8; 3];
+
+pub const BOARD_COLOR: Rgb = [0xdc, 0xb3, 0x5c];
+pub const LINE_COLOR: Rgb = [0x20, 0x18, 0x08];
+pub const BLACK: Rgb = [0x10, 0x10, 0x10];
+pub const WHITE: Rgb = [0xf8, 0xf8, 0xf8];
+const POLICY_COLOR: Rgb = [0xd0, 0x20, 0x40];
+
+/// Radius of a stone as a fraction of the point size
+const STONE_RADIUS: f32 = 0.47;
+
+/// Ownership closer to 0 than this is left unshaded
+const MIN_OWNERSHIP: f32 = 0.05;
+
+/// Opacity of the strongest shading, so the lines stay visible under it
+const MAX_OPACITY: f32 = 0.85;
+
+/// Geometry of a board drawing: the grid, with a margin of one point on each side
+/// for the coordinates
+pub struct Grid {
+    pub width: u8,
+    pub height: u8,
+    pub point_size: u32,
+}
+
+impl Grid {
+    /// Width and height of the board in pixels, margins included
+    pub fn dimensions(&self) -> (u32, u32) {
+        (
+            (self.width as u32 + 1) * self.point_size,
+            (self.height as u32 + 1) * self.point_size,
+        )
+    }
+
+    /// Pixel position of a point, `row` counted from the top
+    pub fn center(&self, x: u8, row: u8) -> (f32, f32) {
+        let p = self.point_size as f32;
+        ((x as f32 + 1.0) * p, (row as f32 + 1.0) * p)
+    }
+
+    /// Pixel position of a GTP coordinate; None for a pass
+    pub fn point(&self, coord: &str) -> Option<(f32, f32)> {
+        let (x, y) = Board::new(self.width, self.height)
+            .parse_point(coord)
+            .ok()?;
+        Some(self.center(x, self.height - 1 - y))
+    }
+
+    /// Star points of the usual board sizes: the corner points, the center of an
+    /// odd board, and the sides of a large one
+    fn star_points(&self) -> Vec<(u8, u8)> {
+        let lines = |size: u8| -> Vec<u8> {
+            if size < 7 {
+                return Vec::new();
+            }
+            let edge = if size >= 13 { 3 } else { 2 };
+            let mut lines = vec![edge, size - 1 - edge];
+            if size % 2 == 1 && size >= 15 {
+                lines.insert(1, size / 2);
+            }
+            lines
+        };
+        let (width, height) = (self.width, self.height);
+        let mut points: Vec<(u8, u8)> = lines(width)
+            .into_iter()
+            .flat_map(|x| lines(height).into_iter().map(move |row| (x, row)))
+            .collect();
+        if width % 2 == 1 && height % 2 == 1 && width >= 7 && height >= 7 {
+            points.push((width / 2, height / 2));
+        }
+        points.sort();
+        points.dedup();
+        points
+    }
+
+    /// SVG lines and star points
+    pub fn svg_lines(&self, svg: &mut String) {
+        let p = self.point_size as f32;
+        let (left, top) = self.center(0, 0);
+        let (right, bottom) = self.center(self.width - 1, self.height - 1);
+        let _ = write!(svg, r#"<g stroke="{}" stroke-width="1">"#, hex(LINE_COLOR));
+        for x in 0..self.width {
+            let (cx, _) = self.center(x, 0);
+            let _ = write!(
+                svg,
+                r#"<line x1="{cx}" y1="{top}" x2="{cx}" y2="{bottom}"/>"#
+            );
+        }
+        for row in 0..self.height {
+            let (_, cy) = self.center(0, row);
+            let _ = write!(
+                svg,
+                r#"<line x1="{left}" y1="{cy}" x2="{right}" y2="{cy}"/>"#
+            );
+        }
+        svg.push_str("</g>");
+        for (x, row) in self.star_points() {
+            let (cx, cy) = self.center(x, row);
+            let _ = write!(
+                svg,
+                r#"<circle cx="{cx}" cy="{cy}" r="{}" fill="{}"/>"#,
+                p * 0.1,
+                hex(LINE_COLOR)
+            );
+        }
+    }
+
+    /// SVG column letters under the board and row numbers left of it
+    pub fn svg_coordinates(&self, svg: &mut String) {
+        let p = self.point_size as f32;
+        let (left, _) = self.center(0, 0);
+        let (_, bottom) = self.center(self.width - 1, self.height - 1);
+        let _ = write!(
+            svg,
+            r#"<g font-family="sans-serif" font-size="{}" fill="{}" text-anchor="middle" dominant-baseline="central">"#,
+            p * 0.4,
+            hex(LINE_COLOR)
+        );
+        for x in 0..self.width {
+            let (cx, _) = self.center(x, 0);
+            let column = Board::coord_name(x, 0);
+            let column = column.trim_end_matches(|c: char| c.is_ascii_digit());
+            let _ = write!(
+                svg,
+                r#"<text x="{cx}" y="{}">{column}</text>"#,
+                bottom + p * 0.6
+            );
+        }
+        for row in 0..self.height {
+            let (_, cy) = self.center(0, row);
+            let _ = write!(
+                svg,
+                r#"<text x="{}" y="{cy}">{}</text>"#,
+                left - p * 0.6,
+                self.height - row
+            );
+        }
+        svg.push_str("</g>");
+    }
+
+    /// Width of a line in a raster image
+    pub fn line_width(&self) -> f32 {
+        (self.point_size as f32 / 32.0).round().max(1.0)
+    }
+
+    /// Lines and star points on a raster image
+    pub fn draw_lines(&self, canvas: &mut Canvas) {
+        let p = self.point_size as f32;
+        let line = self.line_width();
+        let (left, top) = self.center(0, 0);
+        let (right, bottom) = self.center(self.width - 1, self.height - 1);
+        for x in 0..self.width {
+            let (cx, _) = self.center(x, 0);
+            let (x0, x1) = (cx - line / 2.0, cx + line / 2.0);
+            canvas.rect(
+                x0,
+                top - line / 2.0,
+                x1,
+                bottom + line / 2.0,
+                LINE_COLOR,
+                1.0,
+            );
+        }
+        for row in 0..self.height {
+            let (_, cy) = self.center(0, row);
+            let (y0, y1) = (cy - line / 2.0, cy + line / 2.0);
+            canvas.rect(
+                left - line / 2.0,
+                y0,
+                right + line / 2.0,
+                y1,
+                LINE_COLOR,
+                1.0,
+            );
+        }
+        for (x, row) in self.star_points() {
+            let (cx, cy) = self.center(x, row);
+            canvas.circle(cx, cy, p * 0.1, LINE_COLOR);
+        }
+    }
+
+    /// An SVG stone. `attributes` are added to the circle and `children` go inside
+    /// it, e.g. an animation.
+    pub fn svg_stone(
+        &self,
+        svg: &mut String,
+        (cx, cy): (f32, f32),
+        stone: Stone,
+        attributes: &str,
+        children: &str,
+    ) {
+        let fill = match stone {
+            Stone::Black => BLACK,
+            Stone::White => WHITE,
+        };
+        let _ = write!(
+            svg,
+            r#"<circle cx="{cx}" cy="{cy}" r="{}" fill="{}" stroke="{}" stroke-width="1"{attributes}"#,
+            self.point_size as f32 * STONE_RADIUS,
+            hex(fill),
+            hex(BLACK)
+        );
+        if children.is_empty() {
+            svg.push_str("/>");
+        } else {
+            let _ = write!(svg, ">{children}</circle>");
+        }
+    }
+
+    /// A stone on a raster image
+    pub fn draw_stone(&self, canvas: &mut Canvas, (cx, cy): (f32, f32), stone: Stone) {
+        let radius = self.point_size as f32 * STONE_RADIUS;
+        canvas.circle(cx, cy, radius, BLACK);
+        if stone == Stone::White {
+            canvas.circle(cx, cy, radius - self.line_width(), WHITE);
+        }
+    }
+}
+
+/// `#rrggbb` of a color
+pub fn hex([r, g, b]: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// A board with one value per point to shade, row-major from the top row like
+/// KataGo's arrays
+pub struct Heatmap<'a> {
+    pub board: &'a Board,
+    pub values: &'a [f32],
+    pub layer: HeatmapLayer,
+    pub point_size: u32,
+}
+
+/// Shading of one point: a square centered on it
+struct Shade {
+    x: u8,
+    row: u8,
+    color: Rgb,
+    opacity: f32,
+    /// Side as a fraction of the point size: smaller on stones, so the stone
+    /// stays visible around it
+    side: f32,
+}
+
+impl Heatmap<'_> {
+    fn grid(&self) -> Grid {
+        Grid {
+            width: self.board.width(),
+            height: self.board.height(),
+            point_size: self.point_size,
+        }
+    }
+
+    fn stone(&self, x: u8, row: u8) -> Option<Stone> {
+        self.board.stone(x, self.board.height() - 1 - row)
+    }
+
+    fn shades(&self) -> Vec<Shade> {
+        let width = self.board.width() as usize;
+        let max_policy = self.values.iter().copied().fold(0.0f32, f32::max);
+        let mut shades = Vec::new();
+        for (index, &value) in self.values.iter().enumerate() {
+            let (x, row) = ((index % width) as u8, (index / width) as u8);
+            let (color, opacity) = match self.layer {
+                HeatmapLayer::Ownership if value.abs() < MIN_OWNERSHIP => continue,
+                HeatmapLayer::Ownership => (
+                    if value > 0.0 { BLACK } else { WHITE },
+                    value.abs().min(1.0) * MAX_OPACITY,
+                ),
+                // Priors fall off quickly; the square root keeps the second and
+                // third choices visible
+                HeatmapLayer::Policy if value <= 0.0 || max_policy <= 0.0 => continue,
+                HeatmapLayer::Policy => (POLICY_COLOR, (value / max_policy).sqrt() * MAX_OPACITY),
+            };
+            let side = if self.stone(x, row).is_some() {
+                0.4
+            } else {
+                0.9
+            };
+            shades.push(Shade {
+                x,
+                row,
+                color,
+                opacity,
+                side,
+            });
+        }
+        shades
+    }
+
+    pub fn svg(&self) -> String {
+        let grid = self.grid();
+        let (width, height) = grid.dimensions();
+        let p = self.point_size as f32;
+
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+        );
+        let _ = write!(
+            svg,
+            r#"<rect width="{width}" height="{height}" fill="{}"/>"#,
+            hex(BOARD_COLOR)
+        );
+        grid.svg_lines(&mut svg);
+
+        let shades = self.shades();
+        let square = |svg: &mut String, shade: &Shade| {
+            let (cx, cy) = grid.center(shade.x, shade.row);
+            let side = p * shade.side;
+            let _ = write!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{side}" height="{side}" fill="{}" fill-opacity="{:.2}"/>"#,
+                cx - side / 2.0,
+                cy - side / 2.0,
+                hex(shade.color),
+                shade.opacity
+            );
+        };
+        // Empty points are shaded under where a stone would be, stones on top
+        for shade in shades.iter().filter(|shade| shade.side > 0.5) {
+            square(&mut svg, shade);
+        }
+        for row in 0..grid.height {
+            for x in 0..grid.width {
+                if let Some(stone) = self.stone(x, row) {
+                    grid.svg_stone(&mut svg, grid.center(x, row), stone, "", "");
+                }
+            }
+        }
+        for shade in shades.iter().filter(|shade| shade.side <= 0.5) {
+            square(&mut svg, shade);
+        }
+        grid.svg_coordinates(&mut svg);
+        svg.push_str("</svg>");
+        svg
+    }
+
+    /// The heatmap as a PNG. It is drawn like the SVG, without the coordinates.
+    pub fn png(&self) -> Vec<u8> {
+        let grid = self.grid();
+        let (width, height) = grid.dimensions();
+        let p = self.point_size as f32;
+
+        let mut canvas = Canvas::new(width, height, BOARD_COLOR);
+        grid.draw_lines(&mut canvas);
+        let shades = self.shades();
+        let square = |canvas: &mut Canvas, shade: &Shade| {
+            let (cx, cy) = grid.center(shade.x, shade.row);
+            let half = p * shade.side / 2.0;
+            canvas.rect(
+                cx - half,
+                cy - half,
+                cx + half,
+                cy + half,
+                shade.color,
+                shade.opacity,
+            );
+        };
+        for shade in shades.iter().filter(|shade| shade.side > 0.5) {
+            square(&mut canvas, shade);
+        }
+        for row in 0..grid.height {
+            for x in 0..grid.width {
+                if let Some(stone) = self.stone(x, row) {
+                    grid.draw_stone(&mut canvas, grid.center(x, row), stone);
+                }
+            }
+        }
+        for shade in shades.iter().filter(|shade| shade.side <= 0.5) {
+            square(&mut canvas, shade);
+        }
+        canvas.encode_png()
+    }
+}
+
+/// RGB pixels, row by row from the top
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32, background: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            pixels: background.repeat(width as usize * height as usize),
+        }
+    }
+
+    fn blend(&mut self, x: i64, y: i64, color: Rgb, opacity: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let start = (y as usize * self.width as usize + x as usize) * 3;
+        for (pixel, channel) in self.pixels[start..start + 3].iter_mut().zip(color) {
+            let mixed = *pixel as f32 * (1.0 - opacity) + channel as f32 * opacity;
+            *pixel = mixed.round() as u8;
+        }
+    }
+
+    /// Fill the pixels whose centers lie within [x0, x1) and [y0, y1)
+    pub fn rect(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, color: Rgb, opacity: f32) {
+        let first = |edge: f32| (edge - 0.5).ceil() as i64;
+        for y in first(y0)..first(y1) {
+            for x in first(x0)..first(x1) {
+                self.blend(x, y, color, opacity);
+            }
+        }
+    }
+
+    /// Fill a disc, with its edge smoothed over one pixel
+    pub fn circle(&mut self, cx: f32, cy: f32, radius: f32, color: Rgb) {
+        let (x0, x1) = ((cx - radius - 1.0) as i64, (cx + radius + 1.0) as i64);
+        let (y0, y1) = ((cy - radius - 1.0) as i64, (cy + radius + 1.0) as i64);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let distance = (x as f32 + 0.5 - cx).hypot(y as f32 + 0.5 - cy);
+                let coverage = (radius + 0.5 - distance).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    self.blend(x, y, color, coverage);
+                }
+            }
+        }
+    }
+
+    /// Draw a circle's outline `width` pixels wide, inside `radius`
+    pub fn ring(&mut self, cx: f32, cy: f32, radius: f32, width: f32, color: Rgb) {
+        let (x0, x1) = ((cx - radius - 1.0) as i64, (cx + radius + 1.0) as i64);
+        let (y0, y1) = ((cy - radius - 1.0) as i64, (cy + radius + 1.0) as i64);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let distance = (x as f32 + 0.5 - cx).hypot(y as f32 + 0.5 - cy);
+                let outside = radius + 0.5 - distance;
+                let inside = distance - (radius - width) + 0.5;
+                let coverage = outside.min(inside).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    self.blend(x, y, color, coverage);
+                }
+            }
+        }
+    }
+
+    /// An 8-bit RGB PNG without filtering: the flat board colors compress well
+    /// as they are
+    fn encode_png(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        // Bit depth 8, color type 2 (RGB), deflate, no filter method, no interlace
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        for row in self.pixels.chunks(self.width as usize * 3) {
+            zlib.write_all(&[0])
+                .and_then(|()| zlib.write_all(row))
+                .expect("writing to a Vec cannot fail");
+        }
+        let data = zlib.finish().expect("writing to a Vec cannot fail");
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, chunk) in [
+            (b"IHDR", header.as_slice()),
+            (b"IDAT", &data),
+            (b"IEND", &[]),
+        ] {
+            png.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            png.extend_from_slice(kind);
+            png.extend_from_slice(chunk);
+            let mut crc = Crc::new();
+            crc.update(kind);
+            crc.update(chunk);
+            png.extend_from_slice(&crc.sum().to_be_bytes());
+        }
+        png
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heatmap<'a>(board: &'a Board, values: &'a [f32], layer: HeatmapLayer) -> Heatmap<'a> {
+        Heatmap {
+            board,
+            values,
+            layer,
+            point_size: 10,
+        }
+    }
+
+    #[test]
+    fn test_svg_draws_stones_and_shading() {
+        let mut board = Board::new(3, 3);
+        board.place(Stone::Black, "A3").unwrap();
+        board.place(Stone::White, "C1").unwrap();
+        // A3 is Black's, B2 White's; the rest is unsettled
+        let values = [0.8, 0.0, 0.0, 0.0, -0.6, 0.0, 0.0, 0.0, 0.02];
+        let svg = heatmap(&board, &values, HeatmapLayer::Ownership).svg();
+
+        assert!(
+            svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="40""#)
+        );
+        assert_eq!(svg.matches("<line ").count(), 6);
+        assert_eq!(svg.matches("<circle ").count(), 2);
+        // A small square on the A3 stone, a full one on B2
+        assert!(svg.contains(
+            r##"<rect x="8" y="8" width="4" height="4" fill="#101010" fill-opacity="0.68"/>"##
+        ));
+        assert!(svg.contains(
+            r##"<rect x="15.5" y="15.5" width="9" height="9" fill="#f8f8f8" fill-opacity="0.51"/>"##
+        ));
+        assert_eq!(svg.matches("fill-opacity").count(), 2);
+        assert!(
+            svg.contains(">A</text>") && svg.contains(">C</text>") && svg.contains(">3</text>")
+        );
+    }
+
+    #[test]
+    fn test_policy_is_shaded_relative_to_the_best_move() {
+        let board = Board::new(2, 2);
+        let values = [0.5, 0.18, -1.0, 0.0];
+        let svg = heatmap(&board, &values, HeatmapLayer::Policy).svg();
+        assert!(svg.contains(r#"fill-opacity="0.85""#));
+        assert!(svg.contains(r#"fill-opacity="0.51""#));
+        assert_eq!(svg.matches("fill-opacity").count(), 2);
+    }
+
+    #[test]
+    fn test_star_points() {
+        let star_points = |size| {
+            Grid {
+                width: size,
+                height: size,
+                point_size: 10,
+            }
+            .star_points()
+        };
+        assert_eq!(star_points(19).len(), 9);
+        assert!(star_points(19).contains(&(3, 15)));
+        assert_eq!(star_points(13), [(3, 3), (3, 9), (6, 6), (9, 3), (9, 9)]);
+        assert_eq!(star_points(9), [(2, 2), (2, 6), (4, 4), (6, 2), (6, 6)]);
+        assert!(star_points(5).is_empty());
+    }
+
+    #[test]
+    fn test_png_is_well_formed() {
+        let board = Board::new(5, 5);
+        let png = heatmap(&board, &[0.5; 25], HeatmapLayer::Ownership).png();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        // IHDR: 60x60, 8-bit RGB
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 60, 0, 0, 0, 60]);
+        assert_eq!(&png[24..26], &[8, 2]);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+
+        let mut decoded = Vec::new();
+        let length = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        std::io::Read::read_to_end(
+            &mut flate2::read::ZlibDecoder::new(&png[41..41 + length]),
+            &mut decoded,
+        )
+        .unwrap();
+        assert_eq!(decoded.len(), 60 * (1 + 60 * 3));
+        // The margin keeps the board color
+        assert_eq!(&decoded[1..4], &BOARD_COLOR);
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Completed analyses by query (position, rules, komi, visits and every other
+/// setting), so repeating a position is answered without searching it again.
+/// Least recently used entries are evicted at capacity; entries older than the
+/// TTL are not served.
+pub struct ResultCache {
+    state: Mutex<CacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct CacheState {
+    max_entries: usize,
+    ttl: Duration,
+    /// Cache key -> (raw KataGo response line, when it was stored, last use)
+    entries: HashMap<u64, (String, Instant, u64)>,
+    /// Last use -> cache key, oldest first
+    recency: BTreeMap<u64, u64>,
+    next_use: u64,
+}
+
+/// Counters of a cache, summed over engines for GET /api/v1/cache/stats
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheCounts {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+impl std::ops::Add for CacheCounts {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            entries: self.entries + other.entries,
+        }
+    }
+}
+
+impl CacheState {
+    /// Evict least recently used entries until at most `len` remain
+    fn evict_to(&mut self, len: usize) {
+        while self.entries.len() > len {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+impl ResultCache {
+    /// A cache of up to `max_entries` results (0 disables it)
+    pub fn new(max_entries: usize, ttl: Duration) -> Self {
+        Self {
+            state: Mutex::new(CacheState {
+                max_entries,
+                ttl,
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                next_use: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Change the capacity and TTL, evicting least recently used entries beyond
+    /// the new capacity
+    pub fn resize(&self, max_entries: usize, ttl: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.max_entries = max_entries;
+        state.ttl = ttl;
+        state.evict_to(max_entries);
+    }
+
+    /// Cached response for a query key, counting the hit or miss
+    pub fn get(&self, key: u64) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        if state.max_entries == 0 {
+            return None;
+        }
+        let state = &mut *state;
+        let response = match state.entries.get_mut(&key) {
+            Some((response, stored, last_use)) if stored.elapsed() < state.ttl => {
+                state.recency.remove(last_use);
+                *last_use = state.next_use;
+                state.recency.insert(state.next_use, key);
+                state.next_use += 1;
+                Some(response.clone())
+            }
+            Some((_, _, last_use)) => {
+                state.recency.remove(last_use);
+                state.entries.remove(&key);
+                None
+            }
+            None => None,
+        };
+        let counter = if response.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        response
+    }
+
+    /// Store a successful response, evicting the least recently used entry at capacity
+    pub fn insert(&self, key: u64, response: String) {
+        let mut state = self.state.lock().unwrap();
+        if state.max_entries == 0 {
+            return;
+        }
+        if let Some((_, _, last_use)) = state.entries.remove(&key) {
+            state.recency.remove(&last_use);
+        }
+        let room = state.max_entries - 1;
+        state.evict_to(room);
+        let last_use = state.next_use;
+        state.next_use += 1;
+        state.recency.insert(last_use, key);
+        state
+            .entries
+            .insert(key, (response, Instant::now(), last_use));
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.recency.clear();
+    }
+
+    pub fn counts(&self) -> CacheCounts {
+        CacheCounts {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.state.lock().unwrap().entries.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_least_recently_used_entry_is_evicted() {
+        let cache = ResultCache::new(2, Duration::from_secs(60));
+        cache.insert(1, "one".into());
+        cache.insert(2, "two".into());
+        assert_eq!(cache.get(1).as_deref(), Some("one"));
+        cache.insert(3, "three".into());
+
+        assert!(cache.get(2).is_none());
+        assert_eq!(cache.get(1).as_deref(), Some("one"));
+        assert_eq!(cache.get(3).as_deref(), Some("three"));
+        assert_eq!(
+            cache.counts(),
+            CacheCounts {
+                hits: 3,
+                misses: 1,
+                entries: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_expired_entries_are_not_served() {
+        let cache = ResultCache::new(4, Duration::ZERO);
+        cache.insert(1, "one".into());
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.counts().entries, 0);
+    }
+
+    #[test]
+    fn test_shrinking_evicts_least_recently_used() {
+        let cache = ResultCache::new(3, Duration::from_secs(60));
+        for key in 1..=3 {
+            cache.insert(key, key.to_string());
+        }
+        cache.get(1);
+        cache.resize(1, Duration::from_secs(60));
+        assert_eq!(cache.counts().entries, 1);
+        assert_eq!(cache.get(1).as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_the_cache() {
+        let cache = ResultCache::new(0, Duration::from_secs(60));
+        cache.insert(1, "one".into());
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.counts(), CacheCounts::default());
+    }
+}
@@ -0,0 +1,641 @@
+use crate::api::{
+    black_perspective, AnalysisResponse, GameReviewResponse, LossThresholds, MoveClassification,
+    MoveReview, ReviewFormat, ReviewGraphResponse, ReviewSummary, TradeEvaluation,
+};
+use crate::board::Stone;
+use crate::config::ReviewConfig;
+use crate::engine_pool::EnginePool;
+use crate::error::{KatagoError, Result};
+use crate::games::Game;
+use crate::sgf;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
+
+/// Chunks buffered ahead of a slow client before analysis pauses
+const STREAM_BUFFER_CHUNKS: usize = 4;
+
+/// Positions of one game review analyzed at the same time; more would only queue
+/// inside KataGo, where they count against the move timeout
+const REVIEW_CONCURRENCY: usize = 8;
+
+/// Turns of a winrate graph sent to KataGo in one analyzeTurns query, so a long
+/// game is still spread over the engines
+const GRAPH_BATCH_TURNS: usize = 32;
+
+/// Root node of an annotated review: game info, setup stones, and the review settings
+pub fn sgf_header(game: &Game, max_visits: Option<u32>) -> String {
+    let mut root = String::from("(;FF[4]GM[1]CA[UTF-8]");
+    root.push_str(&format!(
+        "AP[katago-server:{}]",
+        sgf::escape(env!("CARGO_PKG_VERSION"))
+    ));
+    if game.board_x_size == game.board_y_size {
+        root.push_str(&format!("SZ[{}]", game.board_x_size));
+    } else {
+        root.push_str(&format!("SZ[{}:{}]", game.board_x_size, game.board_y_size));
+    }
+    if let Some(komi) = game.komi {
+        root.push_str(&format!("KM[{}]", komi));
+    }
+    let text_properties = [
+        ("RU", &game.rules),
+        ("PB", &game.info.black_player),
+        ("PW", &game.info.white_player),
+        ("EV", &game.info.event),
+        ("DT", &game.info.date),
+        ("RE", &game.info.result),
+    ];
+    for (ident, value) in text_properties {
+        if let Some(value) = value {
+            root.push_str(&format!("{}[{}]", ident, sgf::escape(value)));
+        }
+    }
+    for (stone, ident) in [(Stone::Black, "AB"), (Stone::White, "AW")] {
+        let points: Vec<String> = game
+            .initial_stones
+            .iter()
+            .filter(|(s, _)| *s == stone)
+            .filter_map(|(_, coord)| sgf::to_sgf_point(coord, game.board_x_size, game.board_y_size))
+            .collect();
+        if !points.is_empty() {
+            root.push_str(ident);
+            for point in points {
+                root.push_str(&format!("[{}]", point));
+            }
+        }
+    }
+    root.push_str(&format!("PL[{}]", game.initial_player.as_str()));
+    let visits = max_visits.map_or("default".to_string(), |v| v.to_string());
+    root.push_str(&format!(
+        "C[{}]\n",
+        sgf::escape(&format!("Reviewed by KataGo ({} visits per move)", visits))
+    ));
+    root
+}
+
+/// Node for move `turn` of the game, without properties beyond the move
+fn move_node(game: &Game, turn: usize) -> String {
+    let (stone, coord) = &game.moves[turn];
+    let point = sgf::to_sgf_point(coord, game.board_x_size, game.board_y_size).unwrap_or_default();
+    format!(";{}[{}]", stone.as_str(), point)
+}
+
+/// Node for move `turn` of the game, with an optional comment
+pub fn sgf_move(game: &Game, turn: usize, comment: Option<&str>) -> String {
+    let mut node = move_node(game, turn);
+    if let Some(comment) = comment {
+        node.push_str(&format!("C[{}]", sgf::escape(comment)));
+    }
+    node.push('\n');
+    node
+}
+
+/// Comment for a move: Black's evaluation before it and the engine's preferred move
+pub fn review_comment(response: &AnalysisResponse, played: &str) -> Option<String> {
+    let evaluation = black_perspective(response)?;
+    let mut comment = format!(
+        "Before this move: Black {:.1}%, {}",
+        evaluation.winrate * 100.0,
+        score_text(evaluation.score_lead)
+    );
+    if let Some(best) = evaluation.best_move {
+        if best.eq_ignore_ascii_case(played) {
+            comment.push_str(&format!("\nKataGo agrees: {}", best));
+        } else {
+            comment.push_str(&format!("\nKataGo prefers {} (played {})", best, played));
+        }
+    }
+    Some(comment)
+}
+
+/// Black's score lead as the leader's margin, e.g. "W+3.5"
+fn score_text(score_lead: f32) -> String {
+    if score_lead >= 0.0 {
+        format!("B+{:.1}", score_lead)
+    } else {
+        format!("W+{:.1}", -score_lead)
+    }
+}
+
+/// A finished review as an SGF file for KaTrain or Lizzie. Every move carries the
+/// SGF move quality tag of its classification, and KaTrain's comment or Lizzie's
+/// LZ analysis of the position after it.
+pub fn export_review_sgf(
+    game: &Game,
+    review: &GameReviewResponse,
+    format: ReviewFormat,
+    max_visits: Option<u32>,
+) -> String {
+    let mut sgf_text = sgf_header(game, max_visits);
+    for (turn, reviewed) in review.moves.iter().enumerate() {
+        let mut node = move_node(game, turn);
+        node.push_str(quality_tag(reviewed.classification));
+        let next_best = review
+            .moves
+            .get(turn + 1)
+            .and_then(|next| next.best_move.as_deref());
+        match format {
+            ReviewFormat::Lizzie => {
+                let analysis = lizzie_analysis(reviewed, next_best);
+                node.push_str(&format!("LZ[{}]", sgf::escape(&analysis)));
+            }
+            ReviewFormat::Katrain | ReviewFormat::Json => {
+                let comment = katrain_comment(reviewed);
+                node.push_str(&format!("C[{}]", sgf::escape(&comment)));
+            }
+        }
+        node.push('\n');
+        sgf_text.push_str(&node);
+    }
+    sgf_text.push_str(")\n");
+    sgf_text
+}
+
+/// SGF's bad move (BM), doubtful (DO) and tesuji (TE) properties for a class
+fn quality_tag(classification: MoveClassification) -> &'static str {
+    match classification {
+        MoveClassification::Best => "TE[1]",
+        MoveClassification::Good => "",
+        MoveClassification::Inaccuracy => "DO[]",
+        MoveClassification::Mistake => "BM[1]",
+        MoveClassification::Blunder => "BM[2]",
+    }
+}
+
+/// KaTrain's move comment: score and winrate after the move, the engine's
+/// preferred move, and the points the played one lost
+fn katrain_comment(reviewed: &MoveReview) -> String {
+    let winrate = if reviewed.winrate >= 0.5 {
+        format!("B {:.1}%", reviewed.winrate * 100.0)
+    } else {
+        format!("W {:.1}%", (1.0 - reviewed.winrate) * 100.0)
+    };
+    let mut comment = format!(
+        "Move {}: {} {}\nScore: {}\nWin rate: {}\n",
+        reviewed.turn + 1,
+        reviewed.player,
+        reviewed.move_coord,
+        score_text(reviewed.score_lead),
+        winrate
+    );
+    if let Some(best) = &reviewed.best_move {
+        comment.push_str(&format!(
+            "Predicted top move was {} ({}).\n",
+            best,
+            score_text(reviewed.best_score_lead)
+        ));
+    }
+    comment.push_str(&format!(
+        "Estimated point loss: {:.1}",
+        reviewed.score_loss.max(0.0)
+    ));
+    comment
+}
+
+/// Lizzie's LZ property of the position after a move: "<engine> <winrate%>
+/// <visits>", then the engine's preferred reply in kata-analyze's format. Both are
+/// from the perspective of the player to move.
+fn lizzie_analysis(reviewed: &MoveReview, next_best: Option<&str>) -> String {
+    let black_to_move = reviewed.player != Stone::Black.as_str();
+    let (winrate, score) = if black_to_move {
+        (reviewed.winrate, reviewed.score_lead)
+    } else {
+        (1.0 - reviewed.winrate, -reviewed.score_lead)
+    };
+    let mut analysis = format!(
+        "katago-server-{} {:.1} {}",
+        env!("CARGO_PKG_VERSION"),
+        winrate * 100.0,
+        reviewed.visits
+    );
+    if let Some(best) = next_best {
+        analysis.push_str(&format!(
+            "\ninfo move {} visits {} winrate {} scoreMean {:.2} scoreLead {:.2} order 0 pv {}",
+            best,
+            reviewed.visits,
+            (winrate * 10000.0).round() as i32,
+            score,
+            score,
+            best
+        ));
+    }
+    analysis
+}
+
+/// Review a game turn by turn, sending the annotated SGF in chunks as each turn
+/// completes. The stream always ends with a complete SGF; after an analysis failure
+/// the remaining moves are sent without comments.
+pub fn stream_annotated_sgf(
+    engine: Arc<EnginePool>,
+    game: Game,
+    max_visits: Option<u32>,
+) -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER_CHUNKS);
+    tokio::spawn(async move {
+        if tx.send(sgf_header(&game, max_visits)).await.is_err() {
+            return;
+        }
+        let mut failed = false;
+        for turn in 0..game.moves.len() {
+            let comment = if failed {
+                None
+            } else {
+                let request = game.analysis_request(turn, max_visits);
+                match engine.analyze(&request).await {
+                    Ok(response) => review_comment(&response, &game.moves[turn].1),
+                    Err(e) => {
+                        warn!("Review of game {} stopped at turn {}: {}", game.id, turn, e);
+                        failed = true;
+                        Some(format!("Analysis failed: {}", e))
+                    }
+                }
+            };
+            if tx
+                .send(sgf_move(&game, turn, comment.as_deref()))
+                .await
+                .is_err()
+            {
+                debug!("Client disconnected from review of game {}", game.id);
+                return;
+            }
+        }
+        let _ = tx.send(")\n".to_string()).await;
+    });
+    rx
+}
+
+/// Review every move of a game: each position from the start to the final one is
+/// analyzed, and the first failure fails the review. Moves losing more than the
+/// thresholds are classified. `progress` is told how many positions are analyzed
+/// after each one.
+pub async fn review_moves(
+    engine: Arc<EnginePool>,
+    game: &Game,
+    max_visits: Option<u32>,
+    thresholds: &ReviewConfig,
+    mut progress: impl FnMut(usize),
+) -> Result<Vec<MoveReview>> {
+    let permits = Arc::new(Semaphore::new(REVIEW_CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    for turn in 0..=game.moves.len() {
+        let request = game.analysis_request(turn, max_visits);
+        let engine = engine.clone();
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            (turn, engine.analyze(&request).await)
+        });
+    }
+
+    // Dropping the JoinSet on an early return cancels the remaining analyses
+    let mut evaluations = vec![None; game.moves.len() + 1];
+    while let Some(joined) = tasks.join_next().await {
+        let (turn, result) = joined.map_err(|e| KatagoError::ResponseError(e.to_string()))?;
+        let evaluation = black_perspective(&result?).ok_or_else(|| {
+            KatagoError::ParseError(format!("No rootInfo in the analysis of turn {}", turn))
+        })?;
+        evaluations[turn] = Some(evaluation);
+        progress(game.moves.len() + 1 - tasks.len());
+    }
+    let evaluations: Vec<TradeEvaluation> = evaluations.into_iter().flatten().collect();
+    Ok(move_reviews(game, &evaluations, thresholds))
+}
+
+/// Black's winrate and score lead at every turn of a game, for plotting. The turns
+/// are analyzed in batches of one analyzeTurns query each.
+pub async fn winrate_graph(
+    engine: Arc<EnginePool>,
+    game: &Game,
+    max_visits: Option<u32>,
+) -> Result<ReviewGraphResponse> {
+    let permits = Arc::new(Semaphore::new(REVIEW_CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    for batch in graph_batches(game.moves.len()) {
+        let mut request = game.analysis_request(game.moves.len(), max_visits);
+        request.analyze_turns = Some(batch);
+        let engine = engine.clone();
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            engine.analyze_turns(&request).await
+        });
+    }
+
+    let mut graph = ReviewGraphResponse {
+        turns: Vec::new(),
+        winrate: Vec::new(),
+        score_lead: Vec::new(),
+    };
+    let mut evaluations = Vec::with_capacity(game.moves.len() + 1);
+    while let Some(joined) = tasks.join_next().await {
+        let responses = joined.map_err(|e| KatagoError::ResponseError(e.to_string()))??;
+        for response in responses {
+            let evaluation = black_perspective(&response).ok_or_else(|| {
+                KatagoError::ParseError(format!(
+                    "No rootInfo in the analysis of turn {}",
+                    response.turn_number
+                ))
+            })?;
+            evaluations.push((response.turn_number, evaluation));
+        }
+    }
+    evaluations.sort_by_key(|(turn, _)| *turn);
+    for (turn, evaluation) in evaluations {
+        graph.turns.push(turn);
+        graph.winrate.push(evaluation.winrate);
+        graph.score_lead.push(evaluation.score_lead);
+    }
+    Ok(graph)
+}
+
+/// Every turn of a game of `moves` moves (0 to `moves`), in analyzeTurns batches
+fn graph_batches(moves: usize) -> Vec<Vec<u32>> {
+    (0..=moves as u32)
+        .collect::<Vec<_>>()
+        .chunks(GRAPH_BATCH_TURNS)
+        .map(<[u32]>::to_vec)
+        .collect()
+}
+
+/// The most severe class whose threshold `loss` reaches
+fn classify_loss(loss: f32, thresholds: &LossThresholds) -> MoveClassification {
+    if loss >= thresholds.blunder {
+        MoveClassification::Blunder
+    } else if loss >= thresholds.mistake {
+        MoveClassification::Mistake
+    } else if loss >= thresholds.inaccuracy {
+        MoveClassification::Inaccuracy
+    } else {
+        MoveClassification::Good
+    }
+}
+
+/// Class of a move: best if the engine preferred it (whatever noise the losses
+/// show), else from its winrate and point losses, whichever is worse
+fn classify(
+    played_best: bool,
+    winrate_loss: f32,
+    score_loss: f32,
+    thresholds: &ReviewConfig,
+) -> MoveClassification {
+    if played_best {
+        return MoveClassification::Best;
+    }
+    classify_loss(winrate_loss, &thresholds.winrate_loss)
+        .max(classify_loss(score_loss, &thresholds.score_loss))
+}
+
+/// How many moves of each class each player made
+pub fn review_summary(moves: &[MoveReview]) -> ReviewSummary {
+    let mut summary = ReviewSummary::default();
+    for review in moves {
+        let counts = if review.player == Stone::Black.as_str() {
+            &mut summary.black
+        } else {
+            &mut summary.white
+        };
+        counts.add(review.classification);
+    }
+    summary
+}
+
+/// Reviews of the played moves from Black's evaluation of every position
+/// (`evaluations[turn]` is the position before move `turn`)
+fn move_reviews(
+    game: &Game,
+    evaluations: &[TradeEvaluation],
+    thresholds: &ReviewConfig,
+) -> Vec<MoveReview> {
+    game.moves
+        .iter()
+        .zip(evaluations.windows(2))
+        .enumerate()
+        .map(|(turn, ((stone, coord), pair))| {
+            let (before, after) = (&pair[0], &pair[1]);
+            // Losses are from the mover's side: Black's numbers flipped for White
+            let sign = if *stone == Stone::Black { 1.0 } else { -1.0 };
+            let winrate_loss = sign * (before.winrate - after.winrate);
+            let score_loss = sign * (before.score_lead - after.score_lead);
+            let played_best = before
+                .best_move
+                .as_deref()
+                .is_some_and(|best| best.eq_ignore_ascii_case(coord));
+            MoveReview {
+                turn: turn as u32,
+                player: stone.as_str().to_string(),
+                move_coord: coord.clone(),
+                winrate: after.winrate,
+                score_lead: after.score_lead,
+                visits: after.visits,
+                best_move: before.best_move.clone(),
+                best_winrate: before.winrate,
+                best_score_lead: before.score_lead,
+                winrate_loss,
+                score_loss,
+                classification: classify(played_best, winrate_loss, score_loss, thresholds),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::{GameInfo, Visibility};
+
+    fn game() -> Game {
+        Game {
+            id: "g1".to_string(),
+            info: GameInfo {
+                black_player: Some("Lee [9p]".to_string()),
+                ..Default::default()
+            },
+            board_x_size: 9,
+            board_y_size: 9,
+            komi: Some(7.0),
+            rules: Some("chinese".to_string()),
+            initial_stones: vec![],
+            initial_player: Stone::Black,
+            moves: vec![
+                (Stone::Black, "E5".to_string()),
+                (Stone::White, "pass".to_string()),
+            ],
+            source: None,
+            visibility: Visibility::Public,
+            owner_token: None,
+            share_token: None,
+        }
+    }
+
+    fn response(current_player: &str, winrate: f32, best: &str) -> AnalysisResponse {
+        serde_json::from_value(serde_json::json!({
+            "id": "r",
+            "turnNumber": 0,
+            "isDuringSearch": false,
+            "moveInfos": [{
+                "moveCoord": best,
+                "visits": 10,
+                "winrate": winrate,
+                "scoreMean": 0.0,
+                "scoreStdev": 0.0,
+                "scoreLead": 2.0,
+                "utility": 0.0,
+                "lcb": 0.0,
+                "prior": 0.5,
+                "order": 0,
+            }],
+            "rootInfo": {
+                "winrate": winrate,
+                "scoreLead": 2.0,
+                "utility": 0.0,
+                "visits": 10,
+                "currentPlayer": current_player,
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_streamed_sgf_parses_back() {
+        let game = game();
+        let mut sgf_text = sgf_header(&game, Some(100));
+        sgf_text.push_str(&sgf_move(&game, 0, Some("good ] move")));
+        sgf_text.push_str(&sgf_move(&game, 1, None));
+        sgf_text.push(')');
+
+        let parsed = sgf::parse(&sgf_text).unwrap();
+        assert_eq!(parsed.board_x_size, 9);
+        assert_eq!(parsed.komi, Some(7.0));
+        assert_eq!(parsed.black_player.as_deref(), Some("Lee [9p]"));
+        assert_eq!(parsed.moves, game.moves);
+    }
+
+    #[test]
+    fn test_review_comment_uses_black_perspective() {
+        let comment = review_comment(&response("W", 0.7, "C3"), "E5").unwrap();
+        assert!(comment.contains("Black 30.0%"));
+        assert!(comment.contains("W+2.0"));
+        assert!(comment.contains("KataGo prefers C3 (played E5)"));
+
+        let agreed = review_comment(&response("B", 0.6, "E5"), "e5").unwrap();
+        assert!(agreed.contains("Black 60.0%, B+2.0"));
+        assert!(agreed.contains("KataGo agrees: E5"));
+    }
+
+    #[test]
+    fn test_move_losses_are_from_the_movers_side() {
+        let evaluation = |winrate: f32, score_lead: f32, best: &str| TradeEvaluation {
+            winrate,
+            score_lead,
+            visits: 10,
+            best_move: Some(best.to_string()),
+        };
+        let evaluations = [
+            evaluation(0.5, 0.5, "E5"),
+            evaluation(0.6, 1.5, "C3"),
+            evaluation(0.8, 4.5, "D4"),
+        ];
+
+        let reviews = move_reviews(&game(), &evaluations, &ReviewConfig::default());
+        assert_eq!(reviews.len(), 2);
+        // Black's E5 gained, so it lost nothing
+        assert_eq!(reviews[0].best_move.as_deref(), Some("E5"));
+        assert_eq!(reviews[0].score_lead, 1.5);
+        assert!(reviews[0].score_loss < 0.0);
+        assert_eq!(reviews[0].classification, MoveClassification::Best);
+        // White's pass handed Black 3 points
+        assert_eq!(reviews[1].player, "W");
+        assert_eq!(reviews[1].move_coord, "pass");
+        assert!((reviews[1].score_loss - 3.0).abs() < 1e-6);
+        assert!((reviews[1].winrate_loss - 0.2).abs() < 1e-6);
+        assert!(reviews[1].classification >= MoveClassification::Mistake);
+
+        let summary = review_summary(&reviews);
+        assert_eq!(summary.black.best, 1);
+        assert_eq!(summary.white.best + summary.white.good, 0);
+    }
+
+    #[test]
+    fn test_exported_review_follows_each_tool() {
+        let game = game();
+        let evaluation = |winrate: f32, score_lead: f32, best: &str| TradeEvaluation {
+            winrate,
+            score_lead,
+            visits: 10,
+            best_move: Some(best.to_string()),
+        };
+        let evaluations = [
+            evaluation(0.5, 0.5, "E5"),
+            evaluation(0.6, 1.5, "C3"),
+            evaluation(0.8, 4.5, "D4"),
+        ];
+        let moves = move_reviews(&game, &evaluations, &ReviewConfig::default());
+        let review = GameReviewResponse {
+            board_x_size: 9,
+            board_y_size: 9,
+            komi: Some(7.0),
+            rules: None,
+            black_player: None,
+            white_player: None,
+            result: None,
+            summary: review_summary(&moves),
+            moves,
+        };
+
+        let katrain = export_review_sgf(&game, &review, ReviewFormat::Katrain, Some(10));
+        assert_eq!(sgf::parse(&katrain).unwrap().moves, game.moves);
+        assert!(katrain.contains(";B[ee]TE[1]C[Move 1: B E5\nScore: B+1.5\nWin rate: B 60.0%"));
+        assert!(katrain.contains("Predicted top move was C3 (B+1.5).\nEstimated point loss: 3.0]"));
+        assert!(katrain.contains(";W[]BM["));
+
+        // After Black's E5 White is to move: 40%, and C3 is White's best reply
+        let lizzie = export_review_sgf(&game, &review, ReviewFormat::Lizzie, Some(10));
+        assert_eq!(sgf::parse(&lizzie).unwrap().moves, game.moves);
+        let version = env!("CARGO_PKG_VERSION");
+        assert!(lizzie.contains(&format!(
+            ";B[ee]TE[1]LZ[katago-server-{} 40.0 10\ninfo move C3 visits 10 winrate 4000 \
+             scoreMean -1.50 scoreLead -1.50 order 0 pv C3]",
+            version
+        )));
+        assert!(lizzie.contains(&format!("LZ[katago-server-{} 80.0 10]", version)));
+    }
+
+    #[test]
+    fn test_worse_of_winrate_and_point_loss_classifies() {
+        let thresholds = ReviewConfig::default();
+        assert_eq!(
+            classify(false, 0.01, 0.5, &thresholds),
+            MoveClassification::Good
+        );
+        assert_eq!(
+            classify(false, 0.06, 0.5, &thresholds),
+            MoveClassification::Inaccuracy
+        );
+        // A small winrate change can still cost many points (e.g. in a won game)
+        assert_eq!(
+            classify(false, 0.01, 8.0, &thresholds),
+            MoveClassification::Blunder
+        );
+        assert_eq!(
+            classify(false, 0.15, 1.0, &thresholds),
+            MoveClassification::Mistake
+        );
+        // The engine's own choice is never worse than best
+        assert_eq!(
+            classify(true, 0.06, 0.5, &thresholds),
+            MoveClassification::Best
+        );
+    }
+
+    #[test]
+    fn test_graph_covers_every_turn_in_batches() {
+        assert_eq!(graph_batches(0), [vec![0]]);
+        let batches = graph_batches(70);
+        let sizes: Vec<_> = batches.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [32, 32, 7]);
+        assert_eq!(batches[1][0], 32);
+        assert_eq!(batches[2].last(), Some(&70));
+    }
+}
@@ -0,0 +1,191 @@
+use crate::api::{RuleSettings, Rules};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// A ruleset KataGo knows by name, with the individual rules it stands for
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleSet {
+    pub name: &'static str,
+    /// Other names KataGo accepts for the same rules
+    pub aliases: &'static [&'static str],
+    pub ko_rule: &'static str,
+    pub scoring_rule: &'static str,
+    pub tax_rule: &'static str,
+    pub multi_stone_suicide_legal: bool,
+    pub has_button: bool,
+    pub white_handicap_bonus: &'static str,
+}
+
+pub const RULE_SETS: &[RuleSet] = &[
+    RuleSet {
+        name: "tromp-taylor",
+        aliases: &[],
+        ko_rule: "POSITIONAL",
+        scoring_rule: "AREA",
+        tax_rule: "NONE",
+        multi_stone_suicide_legal: true,
+        has_button: false,
+        white_handicap_bonus: "0",
+    },
+    RuleSet {
+        name: "chinese",
+        aliases: &[],
+        ko_rule: "SIMPLE",
+        scoring_rule: "AREA",
+        tax_rule: "NONE",
+        multi_stone_suicide_legal: false,
+        has_button: false,
+        white_handicap_bonus: "N",
+    },
+    RuleSet {
+        name: "chinese-ogs",
+        aliases: &["chinese-kgs"],
+        ko_rule: "POSITIONAL",
+        scoring_rule: "AREA",
+        tax_rule: "NONE",
+        multi_stone_suicide_legal: false,
+        has_button: false,
+        white_handicap_bonus: "N",
+    },
+    RuleSet {
+        name: "japanese",
+        aliases: &["korean"],
+        ko_rule: "SIMPLE",
+        scoring_rule: "TERRITORY",
+        tax_rule: "SEKI",
+        multi_stone_suicide_legal: false,
+        has_button: false,
+        white_handicap_bonus: "0",
+    },
+    RuleSet {
+        name: "aga",
+        aliases: &["bga", "french"],
+        ko_rule: "SITUATIONAL",
+        scoring_rule: "AREA",
+        tax_rule: "NONE",
+        multi_stone_suicide_legal: false,
+        has_button: false,
+        white_handicap_bonus: "N-1",
+    },
+    RuleSet {
+        name: "aga-button",
+        aliases: &[],
+        ko_rule: "SITUATIONAL",
+        scoring_rule: "AREA",
+        tax_rule: "NONE",
+        multi_stone_suicide_legal: false,
+        has_button: true,
+        white_handicap_bonus: "N-1",
+    },
+    RuleSet {
+        name: "new-zealand",
+        aliases: &[],
+        ko_rule: "SITUATIONAL",
+        scoring_rule: "AREA",
+        tax_rule: "NONE",
+        multi_stone_suicide_legal: true,
+        has_button: false,
+        white_handicap_bonus: "0",
+    },
+    RuleSet {
+        name: "stone-scoring",
+        aliases: &[],
+        ko_rule: "SIMPLE",
+        scoring_rule: "AREA",
+        tax_rule: "ALL",
+        multi_stone_suicide_legal: false,
+        has_button: false,
+        white_handicap_bonus: "0",
+    },
+];
+
+const KO_RULES: &[&str] = &["SIMPLE", "POSITIONAL", "SITUATIONAL"];
+const SCORING_RULES: &[&str] = &["AREA", "TERRITORY"];
+const TAX_RULES: &[&str] = &["NONE", "SEKI", "ALL"];
+const WHITE_HANDICAP_BONUSES: &[&str] = &["0", "N-1", "N"];
+
+impl RuleSet {
+    pub fn find(name: &str) -> Option<&'static RuleSet> {
+        RULE_SETS.iter().find(|rules| {
+            rules.name.eq_ignore_ascii_case(name)
+                || rules.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+        })
+    }
+}
+
+/// Check the values of a rules object and uppercase them as KataGo spells them,
+/// so a typo is a 400 rather than a KataGo error. Names are left to KataGo,
+/// which knows more of them than RULE_SETS.
+pub fn normalize(rules: &mut Rules) -> Result<(), String> {
+    let Rules::Custom(settings) = rules else {
+        return Ok(());
+    };
+    let RuleSettings {
+        ko_rule,
+        scoring_rule,
+        tax_rule,
+        white_handicap_bonus,
+        ..
+    } = settings;
+    let fields = [
+        ("koRule", ko_rule, KO_RULES),
+        ("scoringRule", scoring_rule, SCORING_RULES),
+        ("taxRule", tax_rule, TAX_RULES),
+        (
+            "whiteHandicapBonus",
+            white_handicap_bonus,
+            WHITE_HANDICAP_BONUSES,
+        ),
+    ];
+    for (field, value, allowed) in fields {
+        if let Some(value) = value {
+            *value = value.to_ascii_uppercase();
+            if !allowed.contains(&value.as_str()) {
+                return Err(format!(
+                    "{} must be one of {}, not \"{}\"",
+                    field,
+                    allowed.join(", "),
+                    value
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_matches_names_and_aliases() {
+        assert_eq!(RuleSet::find("Japanese").unwrap().tax_rule, "SEKI");
+        assert_eq!(RuleSet::find("korean").unwrap().name, "japanese");
+        assert_eq!(RuleSet::find("bga").unwrap().white_handicap_bonus, "N-1");
+        assert!(RuleSet::find("ing").is_none());
+    }
+
+    #[test]
+    fn test_normalize_uppercases_and_rejects_unknown_values() {
+        let mut rules = Rules::Custom(RuleSettings {
+            ko_rule: Some("positional".to_string()),
+            tax_rule: Some("Seki".to_string()),
+            ..Default::default()
+        });
+        normalize(&mut rules).unwrap();
+        let Rules::Custom(settings) = &rules else {
+            unreachable!()
+        };
+        assert_eq!(settings.ko_rule.as_deref(), Some("POSITIONAL"));
+        assert_eq!(settings.tax_rule.as_deref(), Some("SEKI"));
+
+        let mut rules = Rules::Custom(RuleSettings {
+            white_handicap_bonus: Some("N+1".to_string()),
+            ..Default::default()
+        });
+        let error = normalize(&mut rules).unwrap_err();
+        assert!(error.contains("whiteHandicapBonus"), "{}", error);
+        assert!(normalize(&mut "anything".into()).is_ok());
+    }
+}
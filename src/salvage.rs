@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+
+/// How long a timed-out query id is remembered, and how long a salvaged result is served
+const SALVAGE_TTL: Duration = Duration::from_secs(300);
+
+/// Upper bound on remembered ids and salvaged results (each)
+const MAX_SALVAGE_ENTRIES: usize = 1_000;
+
+/// Keeps results that KataGo delivers after their query already timed out server-side,
+/// keyed by the query's position/settings, so a client retry is answered immediately.
+#[derive(Default)]
+pub struct SalvageStore {
+    /// Query id -> cache key, for queries that timed out while KataGo kept searching
+    timed_out: StdMutex<HashMap<String, (u64, Instant)>>,
+    /// Cache key -> raw KataGo response line
+    results: StdMutex<HashMap<u64, (String, Instant)>>,
+}
+
+impl SalvageStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember a query that timed out so its late response can be kept
+    pub fn expect(&self, id: &str, key: u64) {
+        let mut timed_out = self.timed_out.lock().unwrap();
+        prune(&mut timed_out);
+        timed_out.insert(id.to_string(), (key, Instant::now()));
+    }
+
+    /// Keep a late response if its id timed out recently; returns whether it was kept
+    pub fn offer(&self, id: &str, response: &str) -> bool {
+        let key = match self.timed_out.lock().unwrap().remove(id) {
+            Some((key, at)) if at.elapsed() < SALVAGE_TTL => key,
+            _ => return false,
+        };
+        let mut results = self.results.lock().unwrap();
+        prune(&mut results);
+        results.insert(key, (response.to_string(), Instant::now()));
+        true
+    }
+
+    /// Salvaged response for a query key, if still fresh
+    pub fn get(&self, key: u64) -> Option<String> {
+        self.results
+            .lock()
+            .unwrap()
+            .get(&key)
+            .filter(|(_, at)| at.elapsed() < SALVAGE_TTL)
+            .map(|(response, _)| response.clone())
+    }
+}
+
+/// Drop expired entries, then the oldest ones if still at capacity
+fn prune<K: Clone + Eq + std::hash::Hash, V>(entries: &mut HashMap<K, (V, Instant)>) {
+    entries.retain(|_, (_, at)| at.elapsed() < SALVAGE_TTL);
+    while entries.len() >= MAX_SALVAGE_ENTRIES {
+        let oldest = entries
+            .iter()
+            .min_by_key(|(_, (_, at))| *at)
+            .map(|(k, _)| k.clone());
+        match oldest {
+            Some(k) => entries.remove(&k),
+            None => break,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_late_response_is_salvaged() {
+        let store = SalvageStore::new();
+        store.expect("req-1", 42);
+        assert!(store.offer("req-1", r#"{"id":"req-1"}"#));
+        assert_eq!(store.get(42).as_deref(), Some(r#"{"id":"req-1"}"#));
+        // Each timed-out id is only salvaged once
+        assert!(!store.offer("req-1", "again"));
+    }
+
+    #[test]
+    fn test_unknown_ids_are_ignored() {
+        let store = SalvageStore::new();
+        assert!(!store.offer("never-timed-out", "{}"));
+        assert!(store.get(42).is_none());
+    }
+
+    #[test]
+    fn test_prune_caps_entries() {
+        let mut entries = HashMap::new();
+        for i in 0..MAX_SALVAGE_ENTRIES {
+            entries.insert(i, ((), Instant::now()));
+        }
+        prune(&mut entries);
+        assert_eq!(entries.len(), MAX_SALVAGE_ENTRIES - 1);
+    }
+}
@@ -0,0 +1,435 @@
+use crate::analysis_engine::ProtocolStatsSnapshot;
+use crate::api::*;
+use crate::handicap::HandicapSetup;
+use crate::human_profiles::HumanProfilesResponse;
+use crate::presets::Preset;
+use crate::rules::RuleSet;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{MatchedPath, Path, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use schemars::{JsonSchema, Schema};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tracing::error;
+
+fn schema<T: JsonSchema>() -> Schema {
+    schemars::schema_for!(T)
+}
+
+type GenerateSchema = fn() -> Schema;
+
+/// JSON Schemas of the API's request and response bodies, by name
+pub const SCHEMAS: &[(&str, GenerateSchema)] = &[
+    ("analysis-request", schema::<AnalysisRequest>),
+    ("analysis-response", schema::<AnalysisResponse>),
+    ("analysis-batch-response", schema::<AnalysisBatchResponse>),
+    ("trade-request", schema::<TradeRequest>),
+    ("trade-response", schema::<TradeResponse>),
+    ("human-comparison-request", schema::<HumanComparisonRequest>),
+    (
+        "human-comparison-response",
+        schema::<HumanComparisonResponse>,
+    ),
+    ("score-request", schema::<ScoreRequest>),
+    ("score-estimate-response", schema::<ScoreEstimateResponse>),
+    ("selfplay-request", schema::<SelfPlayRequest>),
+    ("selfplay-move", schema::<SelfPlayMove>),
+    ("selfplay-result", schema::<SelfPlayResult>),
+    ("policy-response", schema::<PolicyResponse>),
+    ("heatmap-request", schema::<HeatmapRequest>),
+    ("review-animation-request", schema::<ReviewAnimationRequest>),
+    ("game-request", schema::<GameRequest>),
+    ("bot-move-response", schema::<BotMoveResponse>),
+    ("game-score-response", schema::<GameScoreResponse>),
+    ("review-request", schema::<ReviewRequest>),
+    ("game-review-response", schema::<GameReviewResponse>),
+    ("job-request", schema::<JobRequest>),
+    ("job-response", schema::<JobResponse>),
+    ("review-graph-response", schema::<ReviewGraphResponse>),
+    ("review-sgf-request", schema::<ReviewSgfRequest>),
+    ("ogs-import-request", schema::<OgsImportRequest>),
+    ("subscription-request", schema::<SubscriptionRequest>),
+    ("subscription-event", schema::<SubscriptionEvent>),
+    ("presets", schema::<Vec<Preset>>),
+    ("rule-sets", schema::<Vec<RuleSet>>),
+    ("handicap-setup", schema::<HandicapSetup>),
+    ("human-profiles-response", schema::<HumanProfilesResponse>),
+    ("edit-position-request", schema::<EditPositionRequest>),
+    ("position-response", schema::<PositionResponse>),
+    ("position-search-response", schema::<PositionSearchResponse>),
+    ("create-game-request", schema::<CreateGameRequest>),
+    ("visibility-request", schema::<VisibilityRequest>),
+    ("game-response", schema::<GameResponse>),
+    ("game-list-response", schema::<GameListResponse>),
+    (
+        "create-guess-session-request",
+        schema::<CreateGuessSessionRequest>,
+    ),
+    ("guess-request", schema::<GuessRequest>),
+    ("guess-session-response", schema::<GuessSessionResponse>),
+    ("guess-response", schema::<GuessResponse>),
+    (
+        "create-play-session-request",
+        schema::<CreatePlaySessionRequest>,
+    ),
+    ("play-move-request", schema::<PlayMoveRequest>),
+    ("play-session-response", schema::<PlaySessionResponse>),
+    ("play-move-response", schema::<PlayMoveResponse>),
+    ("import-request", schema::<ImportRequest>),
+    ("import-job-response", schema::<ImportJobResponse>),
+    ("duplicate-list-response", schema::<DuplicateListResponse>),
+    ("human-model-request", schema::<HumanModelRequest>),
+    ("human-model-response", schema::<HumanModelResponse>),
+    ("config-reload-response", schema::<ConfigReloadResponse>),
+    ("model-switch-request", schema::<ModelSwitchRequest>),
+    ("model-switch-response", schema::<ModelSwitchResponse>),
+    ("replay-request", schema::<ReplayRequest>),
+    ("replay-response", schema::<ReplayResponse>),
+    ("gtp-command-request", schema::<GtpCommandRequest>),
+    ("gtp-command-response", schema::<GtpCommandResponse>),
+    ("engine-status-response", schema::<EngineStatusResponse>),
+    ("engine-restart-response", schema::<EngineRestartResponse>),
+    ("throughput-response", schema::<ThroughputResponse>),
+    ("health-response", schema::<HealthResponse>),
+    ("protocol-stats", schema::<ProtocolStatsSnapshot>),
+    ("version-response", schema::<VersionResponse>),
+    (
+        "analysis-history-response",
+        schema::<AnalysisHistoryResponse>,
+    ),
+    ("cache-clear-response", schema::<CacheClearResponse>),
+    ("cache-stats-response", schema::<CacheStatsResponse>),
+    ("cancel-response", schema::<CancelResponse>),
+    ("problem-detail", schema::<ProblemDetail>),
+    ("callback-accepted", schema::<CallbackAccepted>),
+];
+
+/// Schema of the JSON body each endpoint accepts
+pub const REQUEST_SCHEMAS: &[(&str, &str, &str)] = &[
+    ("POST", "/api/v1/analysis", "analysis-request"),
+    ("POST", "/api/v1/analysis/turns", "analysis-request"),
+    ("POST", "/api/v1/analysis/trade", "trade-request"),
+    ("POST", "/api/v1/analysis/human", "human-comparison-request"),
+    ("POST", "/api/v1/analysis/stream", "analysis-request"),
+    ("POST", "/api/v1/game/move", "game-request"),
+    ("POST", "/api/v1/game/score", "game-request"),
+    ("POST", "/api/v1/score", "score-request"),
+    ("POST", "/api/v1/selfplay", "selfplay-request"),
+    ("POST", "/api/v1/policy", "analysis-request"),
+    ("POST", "/api/v1/render/heatmap", "heatmap-request"),
+    ("POST", "/api/v1/render/review", "review-animation-request"),
+    ("POST", "/api/v1/review", "review-request"),
+    ("POST", "/api/v1/review/graph", "review-request"),
+    ("POST", "/api/v1/review/sgf", "review-sgf-request"),
+    ("POST", "/api/v1/import/ogs", "ogs-import-request"),
+    ("POST", "/api/v1/jobs", "job-request"),
+    ("POST", "/api/v1/positions", "analysis-request"),
+    (
+        "POST",
+        "/api/v1/positions/{id}/edit",
+        "edit-position-request",
+    ),
+    ("POST", "/api/v1/games", "create-game-request"),
+    ("POST", "/api/v1/games/search/position", "analysis-request"),
+    ("PUT", "/api/v1/games/{id}/visibility", "visibility-request"),
+    (
+        "POST",
+        "/api/v1/guess/sessions",
+        "create-guess-session-request",
+    ),
+    ("POST", "/api/v1/guess/sessions/{id}/guess", "guess-request"),
+    (
+        "POST",
+        "/api/v1/play/sessions",
+        "create-play-session-request",
+    ),
+    (
+        "POST",
+        "/api/v1/play/sessions/{id}/move",
+        "play-move-request",
+    ),
+    ("POST", "/api/v1/admin/import", "import-request"),
+    ("PUT", "/api/v1/admin/human-model", "human-model-request"),
+    ("POST", "/api/v1/admin/model", "model-switch-request"),
+    ("POST", "/api/v1/admin/replay", "replay-request"),
+    ("POST", "/api/v1/admin/gtp", "gtp-command-request"),
+];
+
+/// Operations that answer 202 and POST their result to the request's callbackUrl
+pub const CALLBACK_OPERATIONS: &[(&str, &str)] = &[
+    ("POST", "/api/v1/analysis"),
+    ("POST", "/api/v1/review"),
+    ("POST", "/api/v1/import/ogs"),
+];
+
+/// Schema of the JSON body each endpoint responds with (errors use problem-detail)
+pub const RESPONSE_SCHEMAS: &[(&str, &str, &str)] = &[
+    ("POST", "/api/v1/analysis", "analysis-response"),
+    ("POST", "/api/v1/analysis/turns", "analysis-batch-response"),
+    ("POST", "/api/v1/analysis/{id}/cancel", "cancel-response"),
+    ("POST", "/api/v1/analysis/trade", "trade-response"),
+    (
+        "POST",
+        "/api/v1/analysis/human",
+        "human-comparison-response",
+    ),
+    ("POST", "/api/v1/game/move", "bot-move-response"),
+    ("POST", "/api/v1/game/score", "game-score-response"),
+    ("POST", "/api/v1/score", "score-estimate-response"),
+    ("POST", "/api/v1/policy", "policy-response"),
+    ("POST", "/api/v1/review", "game-review-response"),
+    ("POST", "/api/v1/review/graph", "review-graph-response"),
+    ("POST", "/api/v1/import/ogs", "game-review-response"),
+    ("POST", "/api/v1/jobs", "job-response"),
+    ("GET", "/api/v1/jobs/{id}", "job-response"),
+    ("DELETE", "/api/v1/jobs/{id}", "job-response"),
+    ("GET", "/api/v1/presets", "presets"),
+    ("GET", "/api/v1/rules", "rule-sets"),
+    ("GET", "/api/v1/handicap", "handicap-setup"),
+    ("GET", "/api/v1/human/profiles", "human-profiles-response"),
+    ("POST", "/api/v1/positions", "position-response"),
+    ("GET", "/api/v1/positions/{id}", "position-response"),
+    ("POST", "/api/v1/positions/{id}/edit", "position-response"),
+    ("POST", "/api/v1/games", "game-response"),
+    ("GET", "/api/v1/games", "game-list-response"),
+    (
+        "POST",
+        "/api/v1/games/search/position",
+        "position-search-response",
+    ),
+    ("GET", "/api/v1/games/{id}", "game-response"),
+    ("PUT", "/api/v1/games/{id}/visibility", "game-response"),
+    ("GET", "/api/v1/shared/{token}", "game-response"),
+    ("POST", "/api/v1/guess/sessions", "guess-session-response"),
+    (
+        "GET",
+        "/api/v1/guess/sessions/{id}",
+        "guess-session-response",
+    ),
+    (
+        "POST",
+        "/api/v1/guess/sessions/{id}/guess",
+        "guess-response",
+    ),
+    ("POST", "/api/v1/play/sessions", "play-session-response"),
+    ("GET", "/api/v1/play/sessions/{id}", "play-session-response"),
+    (
+        "DELETE",
+        "/api/v1/play/sessions/{id}",
+        "play-session-response",
+    ),
+    (
+        "POST",
+        "/api/v1/play/sessions/{id}/move",
+        "play-move-response",
+    ),
+    ("POST", "/api/v1/admin/import", "import-job-response"),
+    ("GET", "/api/v1/admin/import/{id}", "import-job-response"),
+    ("GET", "/api/v1/admin/duplicates", "duplicate-list-response"),
+    ("GET", "/api/v1/admin/human-model", "human-model-response"),
+    ("PUT", "/api/v1/admin/human-model", "human-model-response"),
+    (
+        "POST",
+        "/api/v1/admin/config/reload",
+        "config-reload-response",
+    ),
+    ("POST", "/api/v1/admin/model", "model-switch-response"),
+    ("POST", "/api/v1/admin/replay", "replay-response"),
+    ("POST", "/api/v1/admin/gtp", "gtp-command-response"),
+    ("GET", "/api/v1/admin/engine", "engine-status-response"),
+    (
+        "POST",
+        "/api/v1/admin/engine/restart",
+        "engine-restart-response",
+    ),
+    ("GET", "/api/v1/admin/throughput", "throughput-response"),
+    ("GET", "/api/v1/health", "health-response"),
+    ("GET", "/api/v1/engine/protocol", "protocol-stats"),
+    ("GET", "/api/v1/version", "version-response"),
+    ("GET", "/api/v1/analyses", "analysis-history-response"),
+    ("POST", "/api/v1/cache/clear", "cache-clear-response"),
+    ("GET", "/api/v1/cache/stats", "cache-stats-response"),
+];
+
+/// Errors reported per violating response
+const MAX_REPORTED_VIOLATIONS: usize = 5;
+
+#[derive(Debug, Serialize)]
+pub struct SchemaIndex {
+    pub schemas: Vec<SchemaLink>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchemaLink {
+    pub name: &'static str,
+    pub url: String,
+}
+
+/// GET /api/v1/schema
+pub async fn schema_index() -> Json<SchemaIndex> {
+    Json(SchemaIndex {
+        schemas: SCHEMAS
+            .iter()
+            .map(|(name, _)| SchemaLink {
+                name,
+                url: format!("/api/v1/schema/{}", name),
+            })
+            .collect(),
+    })
+}
+
+/// GET /api/v1/schema/{name}
+pub async fn schema_by_name(Path(name): Path<String>) -> Result<Json<Schema>, ApiError> {
+    SCHEMAS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, generate)| Json(generate()))
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::NOT_FOUND,
+                "Unknown Schema",
+                &format!("No schema named '{}'; see /api/v1/schema", name),
+            )
+        })
+}
+
+fn validators() -> &'static HashMap<&'static str, jsonschema::Validator> {
+    static VALIDATORS: OnceLock<HashMap<&'static str, jsonschema::Validator>> = OnceLock::new();
+    VALIDATORS.get_or_init(|| {
+        SCHEMAS
+            .iter()
+            .map(|(name, generate)| {
+                let validator = jsonschema::validator_for(generate().as_value())
+                    .unwrap_or_else(|e| panic!("Invalid generated schema '{}': {}", name, e));
+                (*name, validator)
+            })
+            .collect()
+    })
+}
+
+/// Schema violations of a JSON body, at most MAX_REPORTED_VIOLATIONS of them
+fn violations(schema_name: &str, body: &serde_json::Value) -> Vec<String> {
+    validators()
+        .get(schema_name)
+        .map(|validator| {
+            validator
+                .iter_errors(body)
+                .take(MAX_REPORTED_VIOLATIONS)
+                .map(|e| format!("{} at '{}'", e, e.instance_path()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Response-validation debug mode (`server.validate_responses`): JSON responses of
+/// known endpoints are checked against their published schema, and a response that
+/// does not conform is replaced by a 500 listing the violations
+pub async fn validate_response(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let method = request.method().clone();
+    let response = next.run(request).await;
+
+    let Some(route) = route else {
+        return response;
+    };
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let schema_name = if content_type.starts_with("application/problem+json") {
+        "problem-detail"
+    } else if response.status() == StatusCode::ACCEPTED
+        && CALLBACK_OPERATIONS.contains(&(method.as_str(), route.as_str()))
+    {
+        // A request with a callbackUrl, answered later
+        "callback-accepted"
+    } else if content_type.starts_with("application/json") {
+        match RESPONSE_SCHEMAS
+            .iter()
+            .find(|(m, path, _)| *m == method.as_str() && *path == route)
+        {
+            Some((_, _, name)) => name,
+            None => return response,
+        }
+    } else {
+        return response;
+    };
+
+    let (parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to buffer response of {} {}: {}", method, route, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let problems = match serde_json::from_slice(&bytes) {
+        Ok(value) => violations(schema_name, &value),
+        Err(e) => vec![format!("body is not JSON: {}", e)],
+    };
+    if problems.is_empty() {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+
+    error!(
+        "{} {} response violates schema '{}': {}",
+        method,
+        route,
+        schema_name,
+        problems.join("; ")
+    );
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Response Schema Violation",
+        &format!(
+            "The {} response does not match schema '{}': {}",
+            parts.status,
+            schema_name,
+            problems.join("; ")
+        ),
+    )
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_schema_compiles() {
+        assert_eq!(validators().len(), SCHEMAS.len());
+        for (_, _, name) in RESPONSE_SCHEMAS.iter().chain(REQUEST_SCHEMAS) {
+            assert!(validators().contains_key(name), "unknown schema {}", name);
+        }
+    }
+
+    #[test]
+    fn test_violations_are_reported() {
+        let valid = serde_json::to_value(CacheClearResponse {
+            status: "ok".to_string(),
+            timestamp: "2025-01-01T00:00:00Z".to_string(),
+        })
+        .unwrap();
+        assert!(violations("cache-clear-response", &valid).is_empty());
+
+        let invalid = serde_json::json!({ "status": 1 });
+        assert!(!violations("cache-clear-response", &invalid).is_empty());
+    }
+
+    #[test]
+    fn test_optional_request_fields_may_be_omitted() {
+        let request: AnalysisRequest = serde_json::from_str(r#"{"moves": ["D4"]}"#).unwrap();
+        let value = serde_json::to_value(request).unwrap();
+        assert!(violations("analysis-request", &value).is_empty());
+    }
+}
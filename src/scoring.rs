@@ -0,0 +1,232 @@
+use crate::api::{Rules, ScoringComparison, ScoringOutcome};
+use crate::board::{Board, Prisoners, Stone};
+use crate::rules::RuleSet;
+
+/// Ownership at which a point counts for a player when the request sets none
+pub const DEFAULT_OWNERSHIP_THRESHOLD: f32 = 0.5;
+
+/// A position counted from ownership, before komi
+#[derive(Debug, Clone, PartialEq)]
+pub struct Count {
+    pub black_territory: u32,
+    pub white_territory: u32,
+    pub black_prisoners: u32,
+    pub white_prisoners: u32,
+    /// Owner of each point, row by row from the top: 1 Black, -1 White, 0 neither
+    pub territory: Vec<i8>,
+}
+
+impl Count {
+    /// Black's points minus White's and komi
+    pub fn score(&self, komi: f32) -> f32 {
+        let black = self.black_territory + self.black_prisoners;
+        let white = self.white_territory + self.white_prisoners;
+        black as f32 - white as f32 - komi
+    }
+}
+
+/// Whether the rules count stones and territory; Japanese and Korean rules, and
+/// rules objects with a TERRITORY scoringRule, count territory and prisoners
+/// instead. Names missing from RULE_SETS are taken as area rules.
+pub fn is_area_scoring(rules: &Rules) -> bool {
+    let scoring_rule = match rules {
+        Rules::Named(name) => RuleSet::find(name).map(|rules| rules.scoring_rule),
+        Rules::Custom(settings) => settings.scoring_rule.as_deref(),
+    };
+    !scoring_rule.is_some_and(|rule| rule.eq_ignore_ascii_case("TERRITORY"))
+}
+
+/// Count a position from Black's ownership (-1 to 1, row by row from the top).
+/// Points at or beyond `threshold` belong to a player, opposing stones on them
+/// are dead; the rest are dame or unsettled and count for nobody.
+pub fn count(
+    board: &Board,
+    captured: Prisoners,
+    ownership: &[f32],
+    threshold: f32,
+    area_scoring: bool,
+) -> Count {
+    let mut stones = vec![None; board.width() as usize * board.height() as usize];
+    for (stone, coord) in board.stones() {
+        if let Ok(index) = board.policy_index(&coord) {
+            stones[index] = Some(stone);
+        }
+    }
+
+    let mut count = Count {
+        black_territory: 0,
+        white_territory: 0,
+        black_prisoners: if area_scoring { 0 } else { captured.black },
+        white_prisoners: if area_scoring { 0 } else { captured.white },
+        territory: Vec::with_capacity(stones.len()),
+    };
+    for (index, stone) in stones.iter().enumerate() {
+        let value = ownership.get(index).copied().unwrap_or(0.0);
+        let owner = if value >= threshold {
+            Some(Stone::Black)
+        } else if value <= -threshold {
+            Some(Stone::White)
+        } else {
+            None
+        };
+        count.territory.push(match owner {
+            Some(Stone::Black) => 1,
+            Some(Stone::White) => -1,
+            None => 0,
+        });
+
+        let Some(owner) = owner else { continue };
+        // Under territory scoring a player's own stones score nothing, and a dead
+        // stone scores its point plus a prisoner
+        let scores = area_scoring || *stone != Some(owner);
+        let dead = !area_scoring && *stone == Some(owner.opponent());
+        match owner {
+            Stone::Black => {
+                count.black_territory += scores as u32;
+                count.black_prisoners += dead as u32;
+            }
+            Stone::White => {
+                count.white_territory += scores as u32;
+                count.white_prisoners += dead as u32;
+            }
+        }
+    }
+    count
+}
+
+/// Count a position under territory and under area scoring, with the same
+/// ownership and komi, to show whether the rules decide the game
+pub fn compare(
+    board: &Board,
+    captured: Prisoners,
+    ownership: &[f32],
+    threshold: f32,
+    komi: f32,
+) -> ScoringComparison {
+    let outcome = |area_scoring| {
+        let count = count(board, captured, ownership, threshold, area_scoring);
+        let score = count.score(komi);
+        ScoringOutcome {
+            black_points: count.black_territory + count.black_prisoners,
+            white_points: count.white_territory + count.white_prisoners,
+            score,
+            result: result(score),
+        }
+    };
+    let territory_scoring = outcome(false);
+    let area_scoring = outcome(true);
+    ScoringComparison {
+        winner_differs: winner(territory_scoring.score) != winner(area_scoring.score),
+        territory_scoring,
+        area_scoring,
+    }
+}
+
+/// 1 when Black wins, -1 when White does, 0 for a draw
+fn winner(score: f32) -> i8 {
+    if score > 0.0 {
+        1
+    } else if score < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+/// Result in SGF notation: "B+3.5", "W+0.5", or "Draw"
+pub fn result(score: f32) -> String {
+    if score > 0.0 {
+        format!("B+{}", score)
+    } else if score < 0.0 {
+        format!("W+{}", -score)
+    } else {
+        "Draw".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::RuleSettings;
+
+    fn pair(color: &str, coord: &str) -> Vec<String> {
+        vec![color.to_string(), coord.to_string()]
+    }
+
+    /// 3x3 board: Black's wall on column A, a dead White stone at C1, the rest
+    /// split down the middle. Ownership rows are from the top.
+    fn position() -> (Board, Vec<f32>) {
+        let stones = [
+            pair("B", "A1"),
+            pair("B", "A2"),
+            pair("B", "A3"),
+            pair("W", "C1"),
+        ];
+        let board = Board::from_setup(3, 3, &stones, &[]).unwrap();
+        #[rustfmt::skip]
+        let ownership = vec![
+            0.9, 0.1, -0.9,
+            0.9, 0.2, -0.9,
+            0.9, 0.9, 0.8,
+        ];
+        (board, ownership)
+    }
+
+    #[test]
+    fn test_area_scoring_counts_stones_and_territory() {
+        let (board, ownership) = position();
+        let count = count(&board, Prisoners::default(), &ownership, 0.5, true);
+        assert_eq!(count.black_territory, 5);
+        assert_eq!(count.white_territory, 2);
+        assert_eq!(count.black_prisoners, 0);
+        assert_eq!(count.territory, [1, 0, -1, 1, 0, -1, 1, 1, 1]);
+        assert_eq!(count.score(0.5), 2.5);
+    }
+
+    #[test]
+    fn test_territory_scoring_counts_dead_stones_as_prisoners() {
+        let (board, ownership) = position();
+        let captured = Prisoners { black: 1, white: 2 };
+        let count = count(&board, captured, &ownership, 0.5, false);
+        // B1 and the dead stone's C1; Black's own stones score nothing
+        assert_eq!(count.black_territory, 2);
+        assert_eq!(count.black_prisoners, 2);
+        assert_eq!(count.white_territory, 2);
+        assert_eq!(count.white_prisoners, 2);
+        assert_eq!(count.score(6.5), -6.5);
+        assert_eq!(result(count.score(6.5)), "W+6.5");
+    }
+
+    #[test]
+    fn test_comparison_flags_a_winner_that_depends_on_the_scoring() {
+        let (board, ownership) = position();
+        let captured = Prisoners { black: 1, white: 2 };
+        // Territory 4-4, area 5-2
+        let comparison = compare(&board, captured, &ownership, 0.5, 2.5);
+        assert_eq!(comparison.territory_scoring.black_points, 4);
+        assert_eq!(comparison.territory_scoring.result, "W+2.5");
+        assert_eq!(comparison.area_scoring.black_points, 5);
+        assert_eq!(comparison.area_scoring.white_points, 2);
+        assert_eq!(comparison.area_scoring.result, "B+0.5");
+        assert!(comparison.winner_differs);
+
+        let comparison = compare(&board, captured, &ownership, 0.5, 6.5);
+        assert!(!comparison.winner_differs);
+    }
+
+    #[test]
+    fn test_rules_choose_the_scoring() {
+        assert!(is_area_scoring(&"chinese".into()));
+        assert!(is_area_scoring(&"tromp-taylor".into()));
+        assert!(is_area_scoring(&"stone-scoring".into()));
+        assert!(!is_area_scoring(&"Japanese".into()));
+        assert!(!is_area_scoring(&"korean".into()));
+        assert!(!is_area_scoring(&Rules::Custom(RuleSettings {
+            scoring_rule: Some("territory".to_string()),
+            ..Default::default()
+        })));
+        assert!(is_area_scoring(&Rules::Custom(RuleSettings::default())));
+        assert_eq!(result(0.0), "Draw");
+        assert_eq!(result(3.0), "B+3");
+    }
+}
@@ -0,0 +1,242 @@
+use crate::api::{
+    black_perspective, current_request_id, resolve_position, AnalysisResponse, ApiError, AppState,
+    MoveInput, SelfPlayEnd, SelfPlayMove, SelfPlayRequest, SelfPlayResult,
+};
+use crate::error::{self, KatagoError};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    Json,
+};
+use serde::Serialize;
+use std::convert::Infallible;
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+use tracing::debug;
+
+/// Moves played when the request sets no maxMoves
+const DEFAULT_MAX_MOVES: u32 = 50;
+
+/// Most moves one request may play; each is a full search
+const MAX_MOVES: u32 = 500;
+
+/// Events buffered ahead of a slow client
+const EVENT_BUFFER: usize = 16;
+
+/// POST /api/v1/selfplay
+///
+/// Plays the position out with the engine choosing every move, sending each as a
+/// `move` event once chosen, then the whole game as a `result` event (or a problem
+/// detail as an `error` event).
+pub async fn selfplay(
+    State(state): State<AppState>,
+    Json(request): Json<SelfPlayRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let request_id = request
+        .position
+        .request_id
+        .clone()
+        .unwrap_or_else(current_request_id);
+    let max_moves = request.max_moves.unwrap_or(DEFAULT_MAX_MOVES);
+    if !(1..=MAX_MOVES).contains(&max_moves) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            &format!("maxMoves must be between 1 and {}", MAX_MOVES),
+        )
+        .with_request_id(request_id));
+    }
+    if request.position.analyze_turns.is_some() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request",
+            "analyzeTurns is not supported by POST /api/v1/selfplay",
+        )
+        .with_request_id(request_id));
+    }
+
+    let mut position = resolve_position(&state.positions, request.position)
+        .map_err(|e| e.with_request_id(request_id.clone()))?;
+    // Each move is its own query; the request id names the game
+    position.request_id = None;
+    // Routing errors are still an HTTP status, not an event
+    state
+        .engine
+        .route_request(&position)
+        .map_err(|e| ApiError::from(e).with_request_id(request_id.clone()))?;
+
+    let (events, rx) = mpsc::channel(EVENT_BUFFER);
+    let engine = state.engine.clone();
+    tokio::spawn(async move {
+        let mut game = Game::new(position.moves.clone(), max_moves);
+        let end = loop {
+            if let Some(end) = game.end() {
+                break end;
+            }
+            let chosen = engine
+                .analyze(&position)
+                .await
+                .and_then(|response| choose(&response));
+            let chosen = match chosen {
+                Ok(chosen) => chosen,
+                Err(e) => {
+                    let problem = ApiError::from(e).with_request_id(request_id).into_problem();
+                    let _ = events.send(event("error", &problem)).await;
+                    return;
+                }
+            };
+            let played = event("move", game.play(chosen));
+            position.moves = game.moves.clone();
+            if events.send(played).await.is_err() {
+                debug!("Client disconnected from self-play {}", request_id);
+                return;
+            }
+        };
+        let result = SelfPlayResult {
+            request_id,
+            played: game.played,
+            moves: game.moves,
+            end,
+        };
+        let _ = events.send(event("result", &result)).await;
+    });
+
+    let events = ReceiverStream::new(rx).map(Ok);
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// The engine's best move in an analyzed position, with the position's evaluation
+fn choose(response: &AnalysisResponse) -> error::Result<SelfPlayMove> {
+    let root = response
+        .root_info
+        .as_ref()
+        .ok_or_else(|| KatagoError::ParseError("No rootInfo in the analysis".to_string()))?;
+    let best = response
+        .move_infos
+        .iter()
+        .flatten()
+        .min_by_key(|info| info.order)
+        .ok_or_else(|| KatagoError::ParseError("No moveInfos in the analysis".to_string()))?;
+    let evaluation = black_perspective(response)
+        .ok_or_else(|| KatagoError::ParseError("No rootInfo in the analysis".to_string()))?;
+    Ok(SelfPlayMove {
+        move_number: response.turn_number + 1,
+        player: root.current_player.to_uppercase(),
+        move_coord: best.move_coord.clone(),
+        winrate: evaluation.winrate,
+        score_lead: evaluation.score_lead,
+        visits: root.visits,
+    })
+}
+
+/// A self-play game in progress
+struct Game {
+    moves: Vec<MoveInput>,
+    played: Vec<SelfPlayMove>,
+    max_moves: u32,
+    /// Passes in a row, counting from the latest move
+    passes: u32,
+}
+
+impl Game {
+    fn new(moves: Vec<MoveInput>, max_moves: u32) -> Self {
+        Self {
+            moves,
+            played: Vec::new(),
+            max_moves,
+            passes: 0,
+        }
+    }
+
+    /// Why the game is over, if it is
+    fn end(&self) -> Option<SelfPlayEnd> {
+        if self.passes >= 2 {
+            Some(SelfPlayEnd::TwoPasses)
+        } else if self.played.len() as u32 >= self.max_moves {
+            Some(SelfPlayEnd::MaxMoves)
+        } else {
+            None
+        }
+    }
+
+    /// Add a chosen move, with its color so handicap positions stay unambiguous
+    fn play(&mut self, chosen: SelfPlayMove) -> &SelfPlayMove {
+        if chosen.move_coord.eq_ignore_ascii_case("pass") {
+            self.passes += 1;
+        } else {
+            self.passes = 0;
+        }
+        self.moves.push(MoveInput::WithColor([
+            chosen.player.clone(),
+            chosen.move_coord.clone(),
+        ]));
+        self.played.push(chosen);
+        self.played.last().unwrap()
+    }
+}
+
+fn event<T: Serialize>(name: &str, data: &T) -> Event {
+    Event::default()
+        .event(name)
+        .json_data(data)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(turn: u32, player: &str, moves: &[&str]) -> AnalysisResponse {
+        let infos: Vec<_> = moves
+            .iter()
+            .enumerate()
+            .map(|(order, mv)| {
+                serde_json::json!({
+                    "moveCoord": mv, "visits": 10, "winrate": 0.5, "scoreMean": 0.0,
+                    "scoreStdev": 10.0, "scoreLead": 0.0, "utility": 0.0, "lcb": 0.5,
+                    "prior": 0.1, "order": moves.len() - 1 - order,
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "id": "q", "turnNumber": turn, "isDuringSearch": false, "moveInfos": infos,
+            "rootInfo": {
+                "winrate": 0.7, "scoreLead": 3.0, "utility": 0.2, "visits": 100,
+                "currentPlayer": player,
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_best_move_is_chosen_from_blacks_perspective() {
+        let chosen = choose(&response(4, "W", &["Q16", "D4"])).unwrap();
+        assert_eq!(chosen.move_coord, "D4");
+        assert_eq!(chosen.move_number, 5);
+        assert_eq!(chosen.player, "W");
+        assert!((chosen.winrate - 0.3).abs() < 1e-6);
+        assert_eq!(chosen.score_lead, -3.0);
+        assert!(choose(&response(0, "B", &[])).is_err());
+    }
+
+    #[test]
+    fn test_game_ends_after_two_passes_or_max_moves() {
+        let mut game = Game::new(vec![MoveInput::Simple("D4".to_string())], 4);
+        for (turn, (player, mv)) in [("W", "Q16"), ("B", "pass"), ("W", "pass")]
+            .into_iter()
+            .enumerate()
+        {
+            assert_eq!(game.end(), None);
+            game.play(choose(&response(turn as u32 + 1, player, &[mv])).unwrap());
+        }
+        assert_eq!(game.end(), Some(SelfPlayEnd::TwoPasses));
+        assert_eq!(game.moves.len(), 4);
+        assert_eq!(game.moves[3].color(), Some("W"));
+
+        let mut game = Game::new(Vec::new(), 2);
+        game.play(choose(&response(0, "B", &["pass"])).unwrap());
+        game.play(choose(&response(1, "W", &["D4"])).unwrap());
+        assert_eq!(game.end(), Some(SelfPlayEnd::MaxMoves));
+    }
+}
@@ -0,0 +1,443 @@
+use crate::board::{Board, Stone};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SgfError {
+    #[error("Unexpected end of SGF input")]
+    UnexpectedEof,
+
+    #[error("Unexpected character '{0}' at offset {1}")]
+    UnexpectedChar(char, usize),
+
+    #[error("SGF contains no game")]
+    NoGame,
+
+    #[error("Invalid SGF point '{0}'")]
+    InvalidPoint(String),
+
+    #[error("Unsupported board size '{0}'")]
+    InvalidSize(String),
+}
+
+/// A node of the SGF game tree; sequences are represented as single-child chains
+#[derive(Debug, Clone, Default)]
+struct SgfNode {
+    properties: Vec<(String, Vec<String>)>,
+    children: Vec<SgfNode>,
+}
+
+impl SgfNode {
+    fn values(&self, ident: &str) -> &[String] {
+        self.properties
+            .iter()
+            .find(|(id, _)| id == ident)
+            .map(|(_, values)| values.as_slice())
+            .unwrap_or(&[])
+    }
+
+    fn value(&self, ident: &str) -> Option<&str> {
+        self.values(ident).first().map(String::as_str)
+    }
+}
+
+/// Main line of an SGF game record, converted to GTP coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct SgfGame {
+    pub board_x_size: u8,
+    pub board_y_size: u8,
+    pub komi: Option<f32>,
+    pub rules: Option<String>,
+    pub handicap: u32,
+    pub black_player: Option<String>,
+    pub white_player: Option<String>,
+    pub event: Option<String>,
+    pub date: Option<String>,
+    pub result: Option<String>,
+    /// Setup stones (AB/AW on the root node)
+    pub initial_stones: Vec<(Stone, String)>,
+    /// Explicit player to move at the start (PL), if given
+    pub initial_player: Option<Stone>,
+    pub moves: Vec<(Stone, String)>,
+}
+
+/// Parse every game in an SGF collection
+pub fn parse_collection(input: &str) -> Result<Vec<SgfGame>, SgfError> {
+    let mut parser = Parser {
+        chars: input.char_indices().collect(),
+        pos: 0,
+    };
+    let mut games = Vec::new();
+    loop {
+        parser.skip_whitespace();
+        if parser.peek().is_none() {
+            break;
+        }
+        let root = parser.parse_tree()?;
+        games.push(SgfGame::from_root(&root)?);
+    }
+    if games.is_empty() {
+        return Err(SgfError::NoGame);
+    }
+    Ok(games)
+}
+
+/// Parse the first game of an SGF file
+pub fn parse(input: &str) -> Result<SgfGame, SgfError> {
+    parse_collection(input)?
+        .into_iter()
+        .next()
+        .ok_or(SgfError::NoGame)
+}
+
+impl SgfGame {
+    fn from_root(root: &SgfNode) -> Result<Self, SgfError> {
+        let (board_x_size, board_y_size) = match root.value("SZ") {
+            None => (19, 19),
+            Some(size) => parse_size(size)?,
+        };
+        let text = |ident: &str| {
+            root.value(ident)
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+
+        let mut initial_stones = Vec::new();
+        for (ident, stone) in [("AB", Stone::Black), ("AW", Stone::White)] {
+            for value in root.values(ident) {
+                for point in expand_point_list(value) {
+                    initial_stones.push((stone, to_gtp(&point, board_x_size, board_y_size)?));
+                }
+            }
+        }
+
+        let mut moves = Vec::new();
+        let mut node = Some(root);
+        while let Some(current) = node {
+            for (ident, stone) in [("B", Stone::Black), ("W", Stone::White)] {
+                if let Some(point) = current.value(ident) {
+                    moves.push((stone, to_gtp(point, board_x_size, board_y_size)?));
+                }
+            }
+            node = current.children.first();
+        }
+
+        Ok(SgfGame {
+            board_x_size,
+            board_y_size,
+            komi: root.value("KM").and_then(|k| k.trim().parse().ok()),
+            rules: root.value("RU").and_then(normalize_rules),
+            handicap: root
+                .value("HA")
+                .and_then(|h| h.trim().parse().ok())
+                .unwrap_or(0),
+            black_player: text("PB"),
+            white_player: text("PW"),
+            event: text("EV"),
+            date: text("DT"),
+            result: text("RE"),
+            initial_stones,
+            initial_player: root.value("PL").and_then(Stone::parse),
+            moves,
+        })
+    }
+}
+
+fn parse_size(size: &str) -> Result<(u8, u8), SgfError> {
+    let invalid = || SgfError::InvalidSize(size.to_string());
+    let parse = |s: &str| -> Result<u8, SgfError> {
+        match s.trim().parse::<u8>() {
+            Ok(n) if (2..=25).contains(&n) => Ok(n),
+            _ => Err(invalid()),
+        }
+    };
+    match size.split_once(':') {
+        Some((x, y)) => Ok((parse(x)?, parse(y)?)),
+        None => {
+            let n = parse(size)?;
+            Ok((n, n))
+        }
+    }
+}
+
+/// Map SGF RU values onto KataGo rules names
+fn normalize_rules(rules: &str) -> Option<String> {
+    let lower = rules.trim().to_lowercase();
+    let name = match lower.as_str() {
+        "japanese" | "jp" => "japanese",
+        "chinese" | "cn" => "chinese",
+        "korean" => "korean",
+        "aga" => "aga",
+        "nz" | "new zealand" | "new-zealand" => "new-zealand",
+        "tromp-taylor" | "tromp taylor" => "tromp-taylor",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Expand compressed point lists ("aa:cc") into individual points
+fn expand_point_list(value: &str) -> Vec<String> {
+    let Some((from, to)) = value.split_once(':') else {
+        return vec![value.to_string()];
+    };
+    let (Some((x1, y1)), Some((x2, y2))) = (sgf_xy(from), sgf_xy(to)) else {
+        return vec![value.to_string()];
+    };
+    let mut points = Vec::new();
+    for x in x1.min(x2)..=x1.max(x2) {
+        for y in y1.min(y2)..=y1.max(y2) {
+            points.push(format!("{}{}", sgf_letter(x), sgf_letter(y)));
+        }
+    }
+    points
+}
+
+fn sgf_xy(point: &str) -> Option<(u8, u8)> {
+    let bytes = point.as_bytes();
+    if bytes.len() != 2 {
+        return None;
+    }
+    let decode = |b: u8| match b {
+        b'a'..=b'z' => Some(b - b'a'),
+        b'A'..=b'Z' => Some(b - b'A' + 26),
+        _ => None,
+    };
+    Some((decode(bytes[0])?, decode(bytes[1])?))
+}
+
+fn sgf_letter(n: u8) -> char {
+    if n < 26 {
+        (b'a' + n) as char
+    } else {
+        (b'A' + n - 26) as char
+    }
+}
+
+/// Convert an SGF point ("pd", top-left origin) to GTP ("Q16"); "" and "tt" are passes
+fn to_gtp(point: &str, width: u8, height: u8) -> Result<String, SgfError> {
+    if point.is_empty() || (point == "tt" && width <= 19 && height <= 19) {
+        return Ok("pass".to_string());
+    }
+    let (x, y) = sgf_xy(point).ok_or_else(|| SgfError::InvalidPoint(point.to_string()))?;
+    if x >= width || y >= height {
+        return Err(SgfError::InvalidPoint(point.to_string()));
+    }
+    Ok(Board::coord_name(x, height - 1 - y))
+}
+
+/// Convert a GTP coordinate ("Q16") to an SGF point ("pd"); passes become ""
+pub fn to_sgf_point(coord: &str, width: u8, height: u8) -> Option<String> {
+    if coord.eq_ignore_ascii_case("pass") {
+        return Some(String::new());
+    }
+    let (x, y) = Board::new(width, height).parse_point(coord).ok()?;
+    Some(format!("{}{}", sgf_letter(x), sgf_letter(height - 1 - y)))
+}
+
+/// Escape a property value (backslash and closing bracket)
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(']', "\\]")
+}
+
+struct Parser {
+    chars: Vec<(usize, char)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|(_, c)| *c)
+    }
+
+    fn offset(&self) -> usize {
+        self.chars.get(self.pos).map(|(i, _)| *i).unwrap_or(0)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SgfError> {
+        self.skip_whitespace();
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(SgfError::UnexpectedChar(c, self.offset())),
+            None => Err(SgfError::UnexpectedEof),
+        }
+    }
+
+    /// GameTree = "(" Node+ GameTree* ")"
+    fn parse_tree(&mut self) -> Result<SgfNode, SgfError> {
+        self.expect('(')?;
+        let mut sequence = Vec::new();
+        let mut variations = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(';') => {
+                    self.pos += 1;
+                    sequence.push(self.parse_node()?);
+                }
+                Some('(') => variations.push(self.parse_tree()?),
+                Some(')') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(c) => return Err(SgfError::UnexpectedChar(c, self.offset())),
+                None => return Err(SgfError::UnexpectedEof),
+            }
+        }
+
+        // Fold the sequence into a chain, attaching variations to the last node
+        let mut node = sequence.pop().unwrap_or_default();
+        node.children.extend(variations);
+        while let Some(mut parent) = sequence.pop() {
+            parent.children.push(node);
+            node = parent;
+        }
+        Ok(node)
+    }
+
+    fn parse_node(&mut self) -> Result<SgfNode, SgfError> {
+        let mut node = SgfNode::default();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(c) if c.is_ascii_uppercase() => {
+                    let mut ident = String::new();
+                    while let Some(c) = self.peek().filter(char::is_ascii_alphabetic) {
+                        // Lowercase letters in identifiers are an FF[1-3] relic; ignore them
+                        if c.is_ascii_uppercase() {
+                            ident.push(c);
+                        }
+                        self.pos += 1;
+                    }
+                    let mut values = Vec::new();
+                    self.skip_whitespace();
+                    while self.peek() == Some('[') {
+                        values.push(self.parse_value()?);
+                        self.skip_whitespace();
+                    }
+                    node.properties.push((ident, values));
+                }
+                _ => return Ok(node),
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<String, SgfError> {
+        self.expect('[')?;
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('\\') => match self.bump() {
+                    // Escaped line break is a soft break and is removed
+                    Some('\n') => {}
+                    Some(c) => value.push(c),
+                    None => return Err(SgfError::UnexpectedEof),
+                },
+                Some(']') => return Ok(value),
+                Some(c) => value.push(c),
+                None => return Err(SgfError::UnexpectedEof),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_sgf_point_roundtrip() {
+        assert_eq!(to_sgf_point("Q16", 19, 19).as_deref(), Some("pd"));
+        assert_eq!(to_sgf_point("pass", 19, 19).as_deref(), Some(""));
+        assert_eq!(to_sgf_point("Z99", 19, 19), None);
+        assert_eq!(to_gtp("pd", 19, 19).unwrap(), "Q16");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(r"a]b\c"), r"a\]b\\c");
+    }
+
+    #[test]
+    fn test_parse_simple_game() {
+        let sgf = "(;GM[1]FF[4]SZ[19]KM[6.5]RU[Japanese]PB[Honinbo Shusaku]PW[Gennan Inseki]\
+                   RE[B+2];B[qd];W[dc];B[pq](;W[oc])(;W[dp]))";
+        let game = parse(sgf).unwrap();
+        assert_eq!((game.board_x_size, game.board_y_size), (19, 19));
+        assert_eq!(game.komi, Some(6.5));
+        assert_eq!(game.rules.as_deref(), Some("japanese"));
+        assert_eq!(game.black_player.as_deref(), Some("Honinbo Shusaku"));
+        assert_eq!(game.result.as_deref(), Some("B+2"));
+        let moves: Vec<&str> = game.moves.iter().map(|(_, m)| m.as_str()).collect();
+        // Main line follows the first variation
+        assert_eq!(moves, vec!["R16", "D17", "Q3", "P17"]);
+        assert_eq!(game.moves[1].0, Stone::White);
+    }
+
+    #[test]
+    fn test_parse_handicap_setup_and_pass() {
+        let sgf = "(;SZ[9]HA[2]AB[cc][gg]PL[W];W[ee];B[];W[tt])";
+        let game = parse(sgf).unwrap();
+        assert_eq!(game.handicap, 2);
+        assert_eq!(
+            game.initial_stones,
+            vec![
+                (Stone::Black, "C7".to_string()),
+                (Stone::Black, "G3".to_string())
+            ]
+        );
+        assert_eq!(game.initial_player, Some(Stone::White));
+        let moves: Vec<&str> = game.moves.iter().map(|(_, m)| m.as_str()).collect();
+        assert_eq!(moves, vec!["E5", "pass", "pass"]);
+    }
+
+    #[test]
+    fn test_compressed_point_list() {
+        let game = parse("(;SZ[5]AW[aa:bb])").unwrap();
+        assert_eq!(game.initial_stones.len(), 4);
+    }
+
+    #[test]
+    fn test_escaped_values() {
+        let game = parse(r"(;EV[Meijin \] League]C[a\\b])").unwrap();
+        assert_eq!(game.event.as_deref(), Some("Meijin ] League"));
+    }
+
+    #[test]
+    fn test_collection_with_multiple_games() {
+        let games = parse_collection("(;B[aa])\n(;B[bb])").unwrap();
+        assert_eq!(games.len(), 2);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse(""), Err(SgfError::NoGame));
+        assert_eq!(parse("(;B[aa]"), Err(SgfError::UnexpectedEof));
+        assert_eq!(
+            parse("(;SZ[9];B[zz])"),
+            Err(SgfError::InvalidPoint("zz".to_string()))
+        );
+        assert_eq!(
+            parse("(;SZ[99])"),
+            Err(SgfError::InvalidSize("99".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_to_gtp() {
+        assert_eq!(to_gtp("pd", 19, 19).unwrap(), "Q16");
+        assert_eq!(to_gtp("aa", 9, 9).unwrap(), "A9");
+        assert_eq!(to_gtp("", 19, 19).unwrap(), "pass");
+    }
+}
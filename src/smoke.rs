@@ -0,0 +1,59 @@
+use crate::api::AnalysisRequest;
+use crate::config::Config;
+use crate::engine_pool::EnginePool;
+use crate::error::KatagoError;
+use std::time::{Duration, Instant};
+
+/// How long an engine may take to load its model and answer the first query
+const STARTUP_DEADLINE: Duration = Duration::from_secs(300);
+
+/// `--smoke-test`: start every configured engine, run one single-visit analysis of
+/// an empty board on each, print the results and timings, and fail on any error
+pub async fn run(config: &Config) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let pool = EnginePool::start(config)?;
+    println!(
+        "Started {} engine(s) in {} ms",
+        pool.statuses().len(),
+        started.elapsed().as_millis()
+    );
+
+    for (name, size, engine) in pool.smallest_boards() {
+        let request = AnalysisRequest {
+            board_x_size: size,
+            board_y_size: size,
+            max_visits: Some(1),
+            ..Default::default()
+        };
+        let query_started = Instant::now();
+        let response = loop {
+            match engine.analyze(&request).await {
+                // KataGo answers once the model is loaded; the late result of a
+                // timed-out attempt is salvaged and serves the retry
+                Err(KatagoError::Timeout(_)) if query_started.elapsed() < STARTUP_DEADLINE => {
+                    continue
+                }
+                result => {
+                    break result.map_err(|e| anyhow::anyhow!("Engine '{}' failed: {}", name, e))?
+                }
+            }
+        };
+        let best = response
+            .move_infos
+            .as_deref()
+            .and_then(|infos| infos.iter().min_by_key(|mi| mi.order))
+            .ok_or_else(|| anyhow::anyhow!("Engine '{}' returned no candidate moves", name))?;
+        println!(
+            "Engine '{}' ({}x{}): {} with winrate {:.1}% in {} ms",
+            name,
+            size,
+            size,
+            best.move_coord,
+            best.winrate * 100.0,
+            query_started.elapsed().as_millis()
+        );
+    }
+
+    println!("Smoke test passed in {} ms", started.elapsed().as_millis());
+    Ok(())
+}
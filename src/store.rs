@@ -0,0 +1,87 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex as StdMutex;
+
+/// In-memory map keyed by id that evicts the oldest entries once full
+pub struct BoundedStore<V> {
+    inner: StdMutex<StoreInner<V>>,
+    capacity: usize,
+}
+
+struct StoreInner<V> {
+    entries: HashMap<String, V>,
+    order: VecDeque<String>,
+}
+
+impl<V: Clone> BoundedStore<V> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: StdMutex::new(StoreInner {
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+            capacity,
+        }
+    }
+
+    pub fn insert(&self, id: String, value: V) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.entries.insert(id.clone(), value).is_some() {
+            return;
+        }
+        inner.order.push_back(id);
+        while inner.order.len() > self.capacity {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<V> {
+        self.inner.lock().unwrap().entries.get(id).cloned()
+    }
+
+    /// Apply `f` to the entry in place, returning its result (None if the id is unknown)
+    pub fn update<R>(&self, id: &str, f: impl FnOnce(&mut V) -> R) -> Option<R> {
+        self.inner.lock().unwrap().entries.get_mut(id).map(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_evicts_oldest() {
+        let store = BoundedStore::with_capacity(2);
+        store.insert("a".to_string(), 1);
+        store.insert("b".to_string(), 2);
+        store.insert("c".to_string(), 3);
+        assert!(store.get("a").is_none());
+        assert_eq!(store.get("b"), Some(2));
+        assert_eq!(store.get("c"), Some(3));
+    }
+
+    #[test]
+    fn test_reinsert_does_not_duplicate_order() {
+        let store = BoundedStore::with_capacity(2);
+        store.insert("a".to_string(), 1);
+        store.insert("a".to_string(), 10);
+        store.insert("b".to_string(), 2);
+        assert_eq!(store.get("a"), Some(10));
+        assert_eq!(store.get("b"), Some(2));
+    }
+
+    #[test]
+    fn test_update_in_place() {
+        let store = BoundedStore::with_capacity(2);
+        store.insert("a".to_string(), 1);
+        assert_eq!(
+            store.update("a", |v| {
+                *v += 1;
+                *v
+            }),
+            Some(2)
+        );
+        assert_eq!(store.update("missing", |v: &mut i32| *v), None);
+    }
+}
@@ -0,0 +1,656 @@
+use crate::api::{
+    resolve_position, AnalysisRequest, AnalysisResponse, ApiError, AppState, DoneReason, MoveInput,
+    SubscriptionEvent, SubscriptionRequest,
+};
+use crate::config::SubscriptionConfig;
+use crate::engine_pool::EnginePool;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, State,
+    },
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Response,
+    },
+};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+use tracing::debug;
+
+/// Visits of the first evaluation when the request sets no maxVisits
+const FIRST_VISITS: u32 = 50;
+
+/// KataGo priority of refinement queries, so interactive requests go first
+const BACKGROUND_PRIORITY: i32 = -10;
+
+/// Winrate change (0-1) worth pushing to the client
+const WINRATE_THRESHOLD: f32 = 0.01;
+
+/// Score lead change (points) worth pushing to the client
+const SCORE_LEAD_THRESHOLD: f32 = 0.5;
+
+/// Events buffered ahead of a slow client
+const EVENT_BUFFER: usize = 8;
+
+/// Events a channel buffers ahead of its slowest spectator; one further behind
+/// skips to the newer events
+const CHANNEL_BUFFER: usize = 16;
+
+/// Longest channel name
+const MAX_CHANNEL_LEN: usize = 128;
+
+/// Refinement budget, the number of subscriptions currently refining, and the
+/// named channels spectators watch
+pub struct Subscriptions {
+    config: StdMutex<SubscriptionConfig>,
+    active: Arc<AtomicUsize>,
+    channels: Arc<StdMutex<HashMap<String, Channel>>>,
+}
+
+/// Events of one subscription fanned out to every spectator of its channel
+struct Channel {
+    tx: broadcast::Sender<SubscriptionEvent>,
+    /// Latest event, sent first to spectators joining mid-stream
+    last: Option<SubscriptionEvent>,
+    /// Whether a subscription currently publishes to the channel
+    published: bool,
+}
+
+impl Channel {
+    fn new() -> Self {
+        Self {
+            tx: broadcast::channel(CHANNEL_BUFFER).0,
+            last: None,
+            published: false,
+        }
+    }
+}
+
+/// A subscription's claim on a channel; releases it when dropped
+struct Publisher {
+    channels: Arc<StdMutex<HashMap<String, Channel>>>,
+    name: String,
+}
+
+impl Publisher {
+    fn send(&self, event: &SubscriptionEvent) {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(channel) = channels.get_mut(&self.name) {
+            channel.last = Some(event.clone());
+            // No spectators is fine
+            let _ = channel.tx.send(event.clone());
+        }
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(channel) = channels.get_mut(&self.name) {
+            channel.published = false;
+            if channel.tx.receiver_count() == 0 {
+                channels.remove(&self.name);
+            }
+        }
+    }
+}
+
+/// One refining subscription; releases its place when dropped
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Subscriptions {
+    pub fn new(config: SubscriptionConfig) -> Self {
+        Self {
+            config: StdMutex::new(config),
+            active: Arc::new(AtomicUsize::new(0)),
+            channels: Arc::new(StdMutex::new(HashMap::new())),
+        }
+    }
+
+    /// The current budget; subscriptions already refining keep the one they started with
+    pub fn config(&self) -> SubscriptionConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: SubscriptionConfig) {
+        *self.config.lock().unwrap() = config;
+    }
+
+    fn acquire(&self) -> Option<Slot> {
+        let max_active = self.config.lock().unwrap().max_active;
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < max_active).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Slot(self.active.clone()))
+    }
+
+    /// Claim a channel for a subscription to publish to; one at a time per channel
+    fn publish(&self, name: &str) -> Result<Publisher, ApiError> {
+        check_channel_name(name)?;
+        let mut channels = self.channels.lock().unwrap();
+        prune(&mut channels);
+        let channel = channels
+            .entry(name.to_string())
+            .or_insert_with(Channel::new);
+        if channel.published {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                "Channel Taken",
+                &format!("Another subscription publishes to channel {}", name),
+            ));
+        }
+        channel.published = true;
+        channel.last = None;
+        Ok(Publisher {
+            channels: self.channels.clone(),
+            name: name.to_string(),
+        })
+    }
+
+    /// Spectate a channel: its latest event, if any, and the events to come. A
+    /// channel may be watched before a subscription publishes to it.
+    fn watch(
+        &self,
+        name: &str,
+    ) -> Result<
+        (
+            Option<SubscriptionEvent>,
+            broadcast::Receiver<SubscriptionEvent>,
+        ),
+        ApiError,
+    > {
+        check_channel_name(name)?;
+        let mut channels = self.channels.lock().unwrap();
+        prune(&mut channels);
+        let channel = channels
+            .entry(name.to_string())
+            .or_insert_with(Channel::new);
+        Ok((channel.last.clone(), channel.tx.subscribe()))
+    }
+}
+
+/// Forget channels nobody publishes to or watches any more
+fn prune(channels: &mut HashMap<String, Channel>) {
+    channels.retain(|_, channel| channel.published || channel.tx.receiver_count() > 0);
+}
+
+fn check_channel_name(name: &str) -> Result<(), ApiError> {
+    if name.is_empty() || name.len() > MAX_CHANNEL_LEN {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Channel",
+            &format!("Channel names have 1 to {} bytes", MAX_CHANNEL_LEN),
+        ));
+    }
+    Ok(())
+}
+
+/// GET /api/v1/analysis/subscribe
+///
+/// WebSocket on which a client subscribes to a position; the server keeps analyzing
+/// it with growing visit counts and pushes evaluations that changed materially.
+pub async fn subscribe(State(state): State<AppState>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| run_socket(state, socket))
+}
+
+/// GET /api/v1/analysis/channels/{channel}
+///
+/// The events a subscription publishes to a channel, as Server-Sent Events named
+/// by their type, for spectators that don't need a WebSocket.
+pub async fn watch_channel(
+    State(state): State<AppState>,
+    Path(channel): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let (last, watching) = state.subscriptions.watch(&channel)?;
+    let mut watching = Some(watching);
+    let (events, rx) = mpsc::channel(EVENT_BUFFER);
+    tokio::spawn(async move {
+        if let Some(event) = last {
+            if events.send(sse_event(&event)).await.is_err() {
+                return;
+            }
+        }
+        while let Some(event) = next_watched(&mut watching).await {
+            if events.send(sse_event(&event)).await.is_err() {
+                break;
+            }
+        }
+    });
+    let events = ReceiverStream::new(rx).map(Ok);
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn sse_event(event: &SubscriptionEvent) -> Event {
+    let name = match event {
+        SubscriptionEvent::Update { .. } => "update",
+        SubscriptionEvent::Done { .. } => "done",
+        SubscriptionEvent::Error { .. } => "error",
+    };
+    Event::default()
+        .event(name)
+        .json_data(event)
+        .unwrap_or_default()
+}
+
+async fn run_socket(state: AppState, mut socket: WebSocket) {
+    // Each subscription gets its own channel so events of a replaced one are dropped
+    let mut events: Option<mpsc::Receiver<SubscriptionEvent>> = None;
+    let mut task: Option<JoinHandle<()>> = None;
+    // The subscribed position, which `play` messages extend
+    let mut position: Option<AnalysisRequest> = None;
+    // The channel this connection's subscription publishes to
+    let mut publisher: Option<Publisher> = None;
+    // The channel this connection spectates instead of subscribing
+    let mut watching: Option<broadcast::Receiver<SubscriptionEvent>> = None;
+
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => {
+                let text = match incoming {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                if let Some(old) = task.take() {
+                    old.abort();
+                }
+                events = None;
+                watching = None;
+                let started = match serde_json::from_str::<SubscriptionRequest>(&text) {
+                    Ok(SubscriptionRequest::Subscribe { position: request, channel }) => {
+                        // Release the old claim first, so resubscribing keeps the channel
+                        publisher = None;
+                        resolve_position(&state.positions, *request).and_then(|request| {
+                            let claimed = channel
+                                .map(|name| state.subscriptions.publish(&name))
+                                .transpose()?;
+                            position = Some(request.clone());
+                            let started = start(&state, request)?;
+                            publisher = claimed;
+                            Ok(started)
+                        })
+                    }
+                    Ok(SubscriptionRequest::Play { moves }) => {
+                        play(&mut position, moves).and_then(|request| start(&state, request))
+                    }
+                    Ok(SubscriptionRequest::Unsubscribe) => {
+                        position = None;
+                        publisher = None;
+                        continue;
+                    }
+                    Ok(SubscriptionRequest::Watch { channel }) => {
+                        position = None;
+                        publisher = None;
+                        match state.subscriptions.watch(&channel) {
+                            Ok((last, rx)) => {
+                                watching = Some(rx);
+                                if let Some(event) = last {
+                                    if send(&mut socket, &event).await.is_err() {
+                                        break;
+                                    }
+                                }
+                                continue;
+                            }
+                            Err(e) => Err(e),
+                        }
+                    }
+                    Err(e) => Err(ApiError::new(
+                        StatusCode::BAD_REQUEST,
+                        "Invalid Message",
+                        &e.to_string(),
+                    )),
+                };
+                match started {
+                    Ok((handle, rx)) => {
+                        task = Some(handle);
+                        events = Some(rx);
+                    }
+                    Err(e) => {
+                        let error = SubscriptionEvent::Error { problem: e.into_problem() };
+                        if send(&mut socket, &error).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+            event = next_event(&mut events) => match event {
+                Some(event) => {
+                    if let Some(publisher) = &publisher {
+                        publisher.send(&event);
+                    }
+                    if send(&mut socket, &event).await.is_err() {
+                        break;
+                    }
+                }
+                // Refinement finished; wait for the next subscription
+                None => events = None,
+            },
+            event = next_watched(&mut watching) => match event {
+                Some(event) => {
+                    if send(&mut socket, &event).await.is_err() {
+                        break;
+                    }
+                }
+                None => watching = None,
+            },
+        }
+    }
+
+    if let Some(task) = task {
+        task.abort();
+    }
+    debug!("Analysis subscription connection closed");
+}
+
+async fn next_event(
+    events: &mut Option<mpsc::Receiver<SubscriptionEvent>>,
+) -> Option<SubscriptionEvent> {
+    match events {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// The next event of a watched channel; a spectator that fell behind skips to
+/// the newer events
+async fn next_watched(
+    watching: &mut Option<broadcast::Receiver<SubscriptionEvent>>,
+) -> Option<SubscriptionEvent> {
+    let Some(rx) = watching else {
+        return std::future::pending().await;
+    };
+    loop {
+        match rx.recv().await {
+            Ok(event) => return Some(event),
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, event: &SubscriptionEvent) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(event).map_err(axum::Error::new)?;
+    socket.send(Message::Text(text.into())).await
+}
+
+type Started = (JoinHandle<()>, mpsc::Receiver<SubscriptionEvent>);
+
+/// Append `moves` to the subscribed position; returns the new position to refine
+fn play(
+    position: &mut Option<AnalysisRequest>,
+    moves: Vec<MoveInput>,
+) -> Result<AnalysisRequest, ApiError> {
+    let position = position.as_mut().ok_or_else(|| {
+        ApiError::new(
+            StatusCode::CONFLICT,
+            "No Subscription",
+            "Subscribe to a position before playing moves on it",
+        )
+    })?;
+    position.moves.extend(moves);
+    Ok(position.clone())
+}
+
+/// Start refining a resolved position
+fn start(state: &AppState, request: AnalysisRequest) -> Result<Started, ApiError> {
+    let slot = state.subscriptions.acquire().ok_or_else(|| {
+        ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Too Many Subscriptions",
+            "The server is refining the maximum number of positions; try again later",
+        )
+    })?;
+    let (tx, rx) = mpsc::channel(EVENT_BUFFER);
+    let engine = state.engine.clone();
+    let config = state.subscriptions.config();
+    let handle = tokio::spawn(async move {
+        refine(engine, request, config, tx).await;
+        drop(slot);
+    });
+    Ok((handle, rx))
+}
+
+/// Analyze with doubling visit counts until the budget runs out, sending every
+/// evaluation that changed materially since the last one sent
+async fn refine(
+    engine: Arc<EnginePool>,
+    mut request: AnalysisRequest,
+    config: SubscriptionConfig,
+    tx: mpsc::Sender<SubscriptionEvent>,
+) {
+    let deadline = Instant::now() + Duration::from_secs(config.max_secs);
+    request.priority.get_or_insert(BACKGROUND_PRIORITY);
+    let mut visits = request
+        .max_visits
+        .unwrap_or(FIRST_VISITS)
+        .clamp(1, config.max_visits.max(1));
+    let mut last_sent: Option<AnalysisResponse> = None;
+
+    loop {
+        request.max_visits = Some(visits);
+        let event = match tokio::time::timeout_at(deadline, engine.analyze(&request)).await {
+            Err(_) => Some(SubscriptionEvent::Done {
+                visits,
+                reason: DoneReason::TimeLimit,
+            }),
+            Ok(Err(e)) => Some(SubscriptionEvent::Error {
+                problem: ApiError::from(e).into_problem(),
+            }),
+            Ok(Ok(analysis)) => {
+                let changed = match &last_sent {
+                    Some(last) => changed_materially(last, &analysis),
+                    None => true,
+                };
+                if changed {
+                    last_sent = Some(analysis.clone());
+                    if tx
+                        .send(SubscriptionEvent::Update {
+                            visits,
+                            analysis: Box::new(analysis),
+                        })
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                (visits >= config.max_visits).then_some(SubscriptionEvent::Done {
+                    visits,
+                    reason: DoneReason::MaxVisits,
+                })
+            }
+        };
+        if let Some(event) = event {
+            let _ = tx.send(event).await;
+            return;
+        }
+        visits = visits.saturating_mul(2).min(config.max_visits);
+    }
+}
+
+/// Whether a new evaluation is worth pushing: the best move changed, or the
+/// winrate or score lead moved past its threshold
+fn changed_materially(last: &AnalysisResponse, new: &AnalysisResponse) -> bool {
+    let best_move = |r: &AnalysisResponse| {
+        r.move_infos
+            .as_ref()
+            .and_then(|infos| infos.iter().min_by_key(|mi| mi.order))
+            .map(|mi| mi.move_coord.clone())
+    };
+    if best_move(last) != best_move(new) {
+        return true;
+    }
+    match (&last.root_info, &new.root_info) {
+        (Some(a), Some(b)) => {
+            (a.winrate - b.winrate).abs() >= WINRATE_THRESHOLD
+                || (a.score_lead - b.score_lead).abs() >= SCORE_LEAD_THRESHOLD
+        }
+        (None, None) => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(best: &str, winrate: f32, score_lead: f32) -> AnalysisResponse {
+        serde_json::from_value(serde_json::json!({
+            "id": "s",
+            "turnNumber": 0,
+            "isDuringSearch": false,
+            "moveInfos": [{
+                "moveCoord": best,
+                "visits": 10,
+                "winrate": winrate,
+                "scoreMean": score_lead,
+                "scoreStdev": 10.0,
+                "scoreLead": score_lead,
+                "utility": 0.0,
+                "lcb": winrate,
+                "prior": 0.5,
+                "order": 0,
+            }],
+            "rootInfo": {
+                "winrate": winrate,
+                "scoreLead": score_lead,
+                "utility": 0.0,
+                "visits": 10,
+                "currentPlayer": "B",
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_small_changes_are_not_pushed() {
+        let last = response("D4", 0.50, 1.0);
+        assert!(!changed_materially(&last, &response("D4", 0.505, 1.2)));
+        assert!(changed_materially(&last, &response("D4", 0.52, 1.0)));
+        assert!(changed_materially(&last, &response("D4", 0.50, 1.6)));
+        assert!(changed_materially(&last, &response("Q16", 0.50, 1.0)));
+    }
+
+    #[test]
+    fn test_played_moves_extend_the_subscribed_position() {
+        let mut position = None;
+        let Err(error) = play(&mut position, vec![MoveInput::Simple("D4".into())]) else {
+            panic!("playing needs a subscription");
+        };
+        assert_eq!(error.into_problem().status, 409);
+
+        position = Some(AnalysisRequest {
+            moves: vec![MoveInput::Simple("D4".into())],
+            komi: Some(6.5),
+            ..Default::default()
+        });
+        assert!(play(&mut position, vec![MoveInput::Simple("Q16".into())]).is_ok());
+        let moves = vec![
+            MoveInput::Simple("D16".into()),
+            MoveInput::WithColor(["W".into(), "Q4".into()]),
+        ];
+        let Ok(next) = play(&mut position, moves) else {
+            panic!("moves should be played on the subscribed position");
+        };
+        let coords: Vec<&str> = next.moves.iter().map(MoveInput::coord).collect();
+        assert_eq!(coords, ["D4", "Q16", "D16", "Q4"]);
+        assert_eq!(next.komi, Some(6.5));
+        assert_eq!(position.unwrap().moves.len(), 4);
+    }
+
+    #[test]
+    fn test_active_subscriptions_are_limited() {
+        let subscriptions = Subscriptions::new(SubscriptionConfig {
+            max_active: 1,
+            ..Default::default()
+        });
+        let slot = subscriptions.acquire().unwrap();
+        assert!(subscriptions.acquire().is_none());
+        drop(slot);
+        assert!(subscriptions.acquire().is_some());
+    }
+
+    #[test]
+    fn test_channel_events_reach_every_spectator() {
+        let subscriptions = Subscriptions::new(SubscriptionConfig::default());
+        let Ok((None, mut early)) = subscriptions.watch("game-42") else {
+            panic!("a channel can be watched before it is published");
+        };
+        let Ok(publisher) = subscriptions.publish("game-42") else {
+            panic!("the channel should be free");
+        };
+        let Err(taken) = subscriptions.publish("game-42") else {
+            panic!("a channel has one publisher");
+        };
+        assert_eq!(taken.into_problem().status, 409);
+
+        let update = SubscriptionEvent::Update {
+            visits: 50,
+            analysis: Box::new(response("D4", 0.5, 1.0)),
+        };
+        publisher.send(&update);
+        assert!(matches!(
+            early.try_recv(),
+            Ok(SubscriptionEvent::Update { visits: 50, .. })
+        ));
+
+        // A late spectator starts from the latest event
+        let Ok((Some(SubscriptionEvent::Update { visits: 50, .. }), _late)) =
+            subscriptions.watch("game-42")
+        else {
+            panic!("a late spectator should get the latest event");
+        };
+
+        // The channel stays while watched, and can be published again
+        drop(publisher);
+        assert!(subscriptions
+            .channels
+            .lock()
+            .unwrap()
+            .contains_key("game-42"));
+        assert!(subscriptions.publish("game-42").is_ok());
+    }
+
+    #[test]
+    fn test_unused_channels_are_forgotten() {
+        let subscriptions = Subscriptions::new(SubscriptionConfig::default());
+        let Ok(publisher) = subscriptions.publish("game-1") else {
+            panic!("the channel should be free");
+        };
+        drop(publisher);
+        assert!(subscriptions.channels.lock().unwrap().is_empty());
+
+        let Ok(watched) = subscriptions.watch("game-2") else {
+            panic!("the channel name is valid");
+        };
+        drop(watched);
+        assert!(subscriptions.publish("game-3").is_ok());
+        assert!(!subscriptions
+            .channels
+            .lock()
+            .unwrap()
+            .contains_key("game-2"));
+
+        assert!(subscriptions.watch("").is_err());
+        assert!(subscriptions
+            .publish(&"x".repeat(MAX_CHANNEL_LEN + 1))
+            .is_err());
+    }
+}
@@ -0,0 +1,295 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+
+/// Weight of the newest search in the moving average
+const SMOOTHING: f64 = 0.3;
+
+/// Searches shorter than this say more about overhead than about search speed
+const MIN_SAMPLE: Duration = Duration::from_millis(20);
+
+/// Recent search speed of one KataGo process, in visits per second of a query's
+/// wall-clock time (so it reflects the current load), for turning a latency
+/// target into a visit budget
+#[derive(Default)]
+pub struct VisitRate {
+    /// Exponential moving average; None until a search was measured
+    rate: StdMutex<Option<f64>>,
+}
+
+impl VisitRate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Account for a search of `visits` that took `elapsed`
+    pub fn record(&self, visits: u32, elapsed: Duration) {
+        if visits == 0 || elapsed < MIN_SAMPLE {
+            return;
+        }
+        let sample = visits as f64 / elapsed.as_secs_f64();
+        let mut rate = self.rate.lock().unwrap();
+        *rate = Some(match *rate {
+            Some(rate) => rate + SMOOTHING * (sample - rate),
+            None => sample,
+        });
+    }
+
+    pub fn visits_per_second(&self) -> Option<f64> {
+        *self.rate.lock().unwrap()
+    }
+
+    /// Visits a search can do in `target` at the recent rate (at least one);
+    /// None until a search was measured
+    pub fn budget(&self, target: Duration) -> Option<u32> {
+        let visits = self.visits_per_second()? * target.as_secs_f64();
+        Some(visits.clamp(1.0, u32::MAX as f64) as u32)
+    }
+}
+
+/// Searches the rolling visit rate and utilization look back over
+pub const WINDOW: Duration = Duration::from_secs(60);
+
+/// Largest visit count of each latency bucket; bigger searches go in a last,
+/// open-ended bucket
+const VISIT_BUCKETS: [u32; 4] = [10, 100, 1_000, 10_000];
+
+/// Most searches kept for the window, bounding memory under heavy load
+const MAX_RECENT: usize = 100_000;
+
+/// A finished search, as KataGo took it
+struct Search {
+    sent: Instant,
+    finished: Instant,
+    visits: u32,
+}
+
+/// What one KataGo process got through: its recent searches for a rolling
+/// visit rate and utilization, and search latency by visit count since start
+pub struct Throughput {
+    started: Instant,
+    state: StdMutex<ThroughputState>,
+}
+
+#[derive(Default)]
+struct ThroughputState {
+    /// Searches finished within the window, oldest first
+    recent: VecDeque<Search>,
+    /// Searches and their total latency per visit bucket
+    buckets: [(u64, Duration); VISIT_BUCKETS.len() + 1],
+}
+
+/// A process's throughput at one moment
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ThroughputSnapshot {
+    /// Searches finished within the window
+    pub searches: usize,
+    /// Visits of those searches per second of the window
+    pub visits_per_second: f64,
+    /// Share of the window (0-1) with at least one search running
+    pub utilization: f64,
+    /// Average latency of searches since start, by visit count
+    pub latency_by_visits: Vec<VisitBucketLatency>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VisitBucketLatency {
+    pub min_visits: u32,
+    /// Absent for the last bucket, which has no upper bound
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_visits: Option<u32>,
+    pub searches: u64,
+    /// Absent while the bucket has no searches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_latency_ms: Option<f64>,
+}
+
+impl Default for Throughput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Throughput {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            state: StdMutex::new(ThroughputState::default()),
+        }
+    }
+
+    /// Account for a search of `visits` sent to KataGo at `sent` and just finished
+    pub fn record(&self, visits: u32, sent: Instant) {
+        self.record_at(visits, sent, Instant::now());
+    }
+
+    fn record_at(&self, visits: u32, sent: Instant, finished: Instant) {
+        let mut state = self.state.lock().unwrap();
+        let bucket = VISIT_BUCKETS
+            .iter()
+            .position(|&max| visits <= max)
+            .unwrap_or(VISIT_BUCKETS.len());
+        let (searches, latency) = &mut state.buckets[bucket];
+        *searches += 1;
+        *latency += finished.saturating_duration_since(sent);
+
+        if state.recent.len() == MAX_RECENT {
+            state.recent.pop_front();
+        }
+        state.recent.push_back(Search {
+            sent,
+            finished,
+            visits,
+        });
+        Self::prune(&mut state, finished);
+    }
+
+    /// The throughput now, with the send times of the searches still running
+    pub fn snapshot(&self, running: &[Instant]) -> ThroughputSnapshot {
+        self.snapshot_at(Instant::now(), running)
+    }
+
+    fn snapshot_at(&self, now: Instant, running: &[Instant]) -> ThroughputSnapshot {
+        let mut state = self.state.lock().unwrap();
+        Self::prune(&mut state, now);
+        // A process up for less than the window is measured over its uptime
+        let window = now.saturating_duration_since(self.started).min(WINDOW);
+        let window_start = now - window;
+
+        let visits: u64 = state.recent.iter().map(|s| s.visits as u64).sum();
+        let mut busy: Vec<(Instant, Instant)> = state
+            .recent
+            .iter()
+            .map(|s| (s.sent.max(window_start), s.finished))
+            .chain(running.iter().map(|&sent| (sent.max(window_start), now)))
+            .collect();
+        busy.sort_unstable();
+        let mut busy_time = Duration::ZERO;
+        let mut covered_until = window_start;
+        for (from, until) in busy {
+            let from = from.max(covered_until);
+            if until > from {
+                busy_time += until - from;
+                covered_until = until;
+            }
+        }
+
+        let secs = window.as_secs_f64();
+        let latency_by_visits = state
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(index, &(searches, latency))| VisitBucketLatency {
+                min_visits: index
+                    .checked_sub(1)
+                    .map_or(0, |previous| VISIT_BUCKETS[previous] + 1),
+                max_visits: VISIT_BUCKETS.get(index).copied(),
+                searches,
+                average_latency_ms: (searches > 0)
+                    .then(|| latency.as_secs_f64() * 1000.0 / searches as f64),
+            })
+            .collect();
+        ThroughputSnapshot {
+            searches: state.recent.len(),
+            visits_per_second: if secs > 0.0 {
+                visits as f64 / secs
+            } else {
+                0.0
+            },
+            utilization: if secs > 0.0 {
+                (busy_time.as_secs_f64() / secs).min(1.0)
+            } else {
+                0.0
+            },
+            latency_by_visits,
+        }
+    }
+
+    /// Forget searches that finished before the window
+    fn prune(state: &mut ThroughputState, now: Instant) {
+        while state
+            .recent
+            .front()
+            .is_some_and(|s| now.saturating_duration_since(s.finished) > WINDOW)
+        {
+            state.recent.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_follows_the_recent_rate() {
+        let rate = VisitRate::new();
+        assert_eq!(rate.budget(Duration::from_secs(1)), None);
+
+        rate.record(1000, Duration::from_secs(1));
+        assert_eq!(rate.budget(Duration::from_millis(500)), Some(500));
+
+        // A slower search pulls the average down, without replacing it
+        rate.record(100, Duration::from_secs(1));
+        assert!((rate.visits_per_second().unwrap() - 730.0).abs() < 1e-6);
+        assert_eq!(rate.budget(Duration::from_millis(1)), Some(1));
+    }
+
+    #[test]
+    fn test_instant_searches_are_not_measured() {
+        let rate = VisitRate::new();
+        rate.record(500, Duration::from_millis(1));
+        rate.record(0, Duration::from_secs(1));
+        assert_eq!(rate.visits_per_second(), None);
+    }
+
+    #[test]
+    fn test_rate_and_utilization_cover_the_window() {
+        let throughput = Throughput::new();
+        let start = throughput.started;
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        // Busy 10-20s (two overlapping searches) and 30-35s, then one running from 50s
+        throughput.record_at(600, at(10), at(20));
+        throughput.record_at(60, at(15), at(18));
+        throughput.record_at(540, at(30), at(35));
+
+        let snapshot = throughput.snapshot_at(at(60), &[at(50)]);
+        assert_eq!(snapshot.searches, 3);
+        assert!((snapshot.visits_per_second - 20.0).abs() < 1e-9);
+        assert!((snapshot.utilization - 25.0 / 60.0).abs() < 1e-9);
+
+        // The first searches leave the window, their latency stays
+        let later = throughput.snapshot_at(at(85), &[]);
+        assert_eq!(later.searches, 1);
+        assert!((later.utilization - 5.0 / 60.0).abs() < 1e-9);
+        let buckets: Vec<_> = later
+            .latency_by_visits
+            .iter()
+            .map(|b| (b.min_visits, b.max_visits, b.searches, b.average_latency_ms))
+            .collect();
+        assert_eq!(
+            buckets,
+            [
+                (0, Some(10), 0, None),
+                (11, Some(100), 1, Some(3000.0)),
+                (101, Some(1000), 2, Some(7500.0)),
+                (1001, Some(10000), 0, None),
+                (10001, None, 0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_a_young_process_is_measured_over_its_uptime() {
+        let throughput = Throughput::new();
+        let start = throughput.started;
+        throughput.record_at(100, start, start + Duration::from_secs(5));
+        let snapshot = throughput.snapshot_at(start + Duration::from_secs(10), &[]);
+        assert!((snapshot.visits_per_second - 10.0).abs() < 1e-9);
+        assert!((snapshot.utilization - 0.5).abs() < 1e-9);
+    }
+}
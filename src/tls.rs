@@ -0,0 +1,50 @@
+use anyhow::Context;
+use axum_server::tls_rustls::RustlsConfig;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+/// Load the PEM certificate chain and private key served over HTTPS
+pub async fn load(cert_path: &str, key_path: &str) -> anyhow::Result<RustlsConfig> {
+    // Already installed when called again; either way ring is the provider
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    RustlsConfig::from_pem_file(cert_path, key_path)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to load TLS certificate {} and key {}",
+                cert_path, key_path
+            )
+        })
+}
+
+/// Modification times of the certificate and key, None while either is missing
+fn modified(cert_path: &str, key_path: &str) -> Option<(SystemTime, SystemTime)> {
+    let mtime = |path: &str| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    Some((mtime(cert_path)?, mtime(key_path)?))
+}
+
+/// Check the certificate and key every `interval` and reload them once either
+/// changed, so a renewed certificate is served without a restart. New connections
+/// get the new certificate; a reload that fails (e.g. the key is not written yet)
+/// keeps the old one and is retried at the next check.
+pub fn watch(tls: RustlsConfig, cert_path: String, key_path: String, interval: Duration) {
+    tokio::spawn(async move {
+        let mut loaded = modified(&cert_path, &key_path);
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let current = modified(&cert_path, &key_path);
+            if current.is_none() || current == loaded {
+                continue;
+            }
+            match tls.reload_from_pem_file(&cert_path, &key_path).await {
+                Ok(()) => {
+                    info!("Reloaded TLS certificate {}", cert_path);
+                    loaded = current;
+                }
+                Err(e) => warn!("Failed to reload TLS certificate {}: {}", cert_path, e),
+            }
+        }
+    });
+}
@@ -0,0 +1,298 @@
+use crate::config::WebhookConfig;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Url;
+use ring::hmac;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Header carrying `sha256=<hex>`, the HMAC-SHA256 of the callback body keyed with
+/// `[webhooks] secret`
+pub const SIGNATURE_HEADER: &str = "x-katago-signature";
+
+/// Header telling a result ("completed") from a problem detail ("failed")
+pub const EVENT_HEADER: &str = "x-katago-event";
+
+/// Delivers the results of requests with a `callbackUrl`
+pub struct Webhooks {
+    http: reqwest::Client,
+    key: hmac::Key,
+    max_attempts: u32,
+    retry_backoff: Duration,
+    allowed_hosts: AllowedHosts,
+}
+
+/// Hosts exempt from the public-address rule, from `[webhooks] allowed_hosts`
+#[derive(Debug, Clone, Default)]
+struct AllowedHosts(Arc<Vec<String>>);
+
+impl AllowedHosts {
+    fn new(hosts: &[String]) -> Self {
+        Self(Arc::new(hosts.iter().map(|host| normalize(host)).collect()))
+    }
+
+    fn contains(&self, host: &str) -> bool {
+        let host = normalize(host);
+        self.0.contains(&host)
+    }
+}
+
+/// A host name or address as listed and compared: lowercase, without the
+/// brackets of an IPv6 address
+fn normalize(host: &str) -> String {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_ascii_lowercase()
+}
+
+/// Whether an address is on the server's own machine or network rather than
+/// the internet: loopback, link-local (cloud metadata), private, or unspecified
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_internal(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    // Unique local, fc00::/7
+                    || (first & 0xfe00) == 0xfc00
+                    // Link-local, fe80::/10
+                    || (first & 0xffc0) == 0xfe80
+            }
+        },
+    }
+}
+
+/// Resolves callback hosts to their public addresses only, so a name pointing
+/// into the server's network is refused like the address itself
+struct PublicResolver {
+    allowed_hosts: AllowedHosts,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let allowed = self.allowed_hosts.contains(name.as_str());
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| allowed || !is_internal(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!(
+                    "{} resolves to no public address; list it in [webhooks] allowed_hosts to allow it",
+                    name.as_str()
+                )
+                .into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// What a callback reports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// The body is the result the endpoint would have returned
+    Completed,
+    /// The body is the problem detail the endpoint would have returned
+    Failed,
+}
+
+impl Webhooks {
+    /// Callbacks signed with the configured secret, or None without one
+    pub fn new(config: &WebhookConfig) -> anyhow::Result<Option<Self>> {
+        let Some(secret) = &config.secret else {
+            return Ok(None);
+        };
+        anyhow::ensure!(!secret.is_empty(), "[webhooks] secret must not be empty");
+        let allowed_hosts = AllowedHosts::new(&config.allowed_hosts);
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            // A callback URL is the client's endpoint; redirects could point anywhere
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(PublicResolver {
+                allowed_hosts: allowed_hosts.clone(),
+            }))
+            .build()?;
+        Ok(Some(Self {
+            http,
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()),
+            max_attempts: config.max_attempts.max(1),
+            retry_backoff: Duration::from_millis(config.retry_backoff_ms),
+            allowed_hosts,
+        }))
+    }
+
+    /// The callback URL of a request, if it is an absolute http(s) URL of a
+    /// public host or one of `[webhooks] allowed_hosts`. Names are checked again
+    /// when they are resolved for a delivery.
+    pub fn parse_url(&self, url: &str) -> Result<Url, String> {
+        let parsed = Url::parse(url).map_err(|e| format!("{}: {}", url, e))?;
+        let host = match (parsed.scheme(), parsed.host_str()) {
+            ("http" | "https", Some(host)) => host,
+            _ => return Err(format!("{} is not an http(s) URL", url)),
+        };
+        if self.allowed_hosts.contains(host) {
+            return Ok(parsed);
+        }
+        let internal = match normalize(host).parse::<IpAddr>() {
+            Ok(ip) => is_internal(ip),
+            Err(_) => {
+                let host = normalize(host);
+                host == "localhost" || host.ends_with(".localhost")
+            }
+        };
+        if internal {
+            return Err(format!(
+                "{} is a loopback, link-local, or private address; list it in [webhooks] allowed_hosts to allow it",
+                host
+            ));
+        }
+        Ok(parsed)
+    }
+
+    /// Value of the signature header for `body`
+    pub fn sign(&self, body: &[u8]) -> String {
+        let tag = hmac::sign(&self.key, body);
+        let hex: String = tag
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!("sha256={}", hex)
+    }
+
+    /// POST `body` to `url` until it answers with a 2xx status, waiting twice as
+    /// long after each failed attempt. Gives up after `max_attempts`.
+    pub async fn deliver(&self, url: Url, request_id: &str, outcome: Outcome, body: Vec<u8>) {
+        let (event, content_type) = match outcome {
+            Outcome::Completed => ("completed", "application/json"),
+            Outcome::Failed => ("failed", "application/problem+json"),
+        };
+        let signature = self.sign(&body);
+        let mut backoff = self.retry_backoff;
+        for attempt in 1..=self.max_attempts {
+            let sent = self
+                .http
+                .post(url.clone())
+                .header(CONTENT_TYPE, content_type)
+                .header("x-request-id", request_id)
+                .header(EVENT_HEADER, event)
+                .header(SIGNATURE_HEADER, &signature)
+                .body(body.clone())
+                .send()
+                .await;
+            let failure = match sent {
+                Ok(response) if response.status().is_success() => {
+                    info!("Delivered callback of {} to {}", request_id, url);
+                    return;
+                }
+                Ok(response) => format!("status {}", response.status()),
+                Err(e) => e.to_string(),
+            };
+            warn!(
+                "Callback of {} to {} failed (attempt {}/{}): {}",
+                request_id, url, attempt, self.max_attempts, failure
+            );
+            if attempt < self.max_attempts {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+        warn!(
+            "Gave up delivering the callback of {} to {}",
+            request_id, url
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_is_hmac_sha256_hex() {
+        let webhooks = Webhooks::new(&WebhookConfig {
+            secret: Some("key".to_string()),
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap();
+        // The usual example of HMAC-SHA256
+        assert_eq!(
+            webhooks.sign(b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        assert!(Webhooks::new(&WebhookConfig::default()).unwrap().is_none());
+    }
+
+    fn webhooks(allowed_hosts: &[&str]) -> Webhooks {
+        Webhooks::new(&WebhookConfig {
+            secret: Some("key".to_string()),
+            allowed_hosts: allowed_hosts.iter().map(|h| h.to_string()).collect(),
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_only_http_urls_are_callbacks() {
+        let webhooks = webhooks(&[]);
+        assert!(webhooks.parse_url("https://example.com/hook?id=1").is_ok());
+        assert!(webhooks.parse_url("http://93.184.216.34:8080/hook").is_ok());
+        assert!(webhooks.parse_url("file:///etc/passwd").is_err());
+        assert!(webhooks.parse_url("/relative").is_err());
+    }
+
+    #[test]
+    fn test_internal_hosts_are_refused_unless_allowed() {
+        let refusing = webhooks(&[]);
+        for url in [
+            "http://127.0.0.1/",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.2:8080/hook",
+            "http://192.168.1.1/",
+            "http://localhost:8080/hook",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://0.0.0.0/",
+        ] {
+            assert!(refusing.parse_url(url).is_err(), "{}", url);
+        }
+
+        let allowing = webhooks(&["10.0.0.2", "[::1]", "Hooks.internal"]);
+        assert!(allowing.parse_url("http://10.0.0.2:8080/hook").is_ok());
+        assert!(allowing.parse_url("http://[::1]:9/").is_ok());
+        assert!(allowing.parse_url("http://hooks.internal/").is_ok());
+        assert!(allowing.parse_url("http://10.0.0.3/").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_names_resolving_to_internal_addresses_are_refused() {
+        let resolve = |allowed: &[&str]| {
+            let resolver = PublicResolver {
+                allowed_hosts: AllowedHosts::new(
+                    &allowed.iter().map(|h| h.to_string()).collect::<Vec<_>>(),
+                ),
+            };
+            resolver.resolve("localhost".parse().unwrap())
+        };
+        assert!(resolve(&[]).await.is_err());
+        assert!(resolve(&["localhost"]).await.unwrap().next().is_some());
+    }
+}
@@ -0,0 +1,2626 @@
+//! End-to-end tests: the full server, talking to the scripted fake KataGo
+
+mod support;
+
+use katago_server_client::types::{
+    AnalysisRequest, AnalysisResponse, AnimationFormat, BoardCorner, GameRequest, HeatmapRequest,
+    HumanComparisonRequest, ImageFormat, JobKind, JobRequest, JobResponse, JobStatus,
+    LossThresholds, MoveClassification, MoveInput, OgsImportRequest, Ownership, OwnershipFormat,
+    Quality, ReviewAnimationRequest, ReviewFormat, ReviewRequest, ScoreRequest, SelfPlayEnd,
+    SelfPlayMove, SelfPlayRequest, SelfPlayResult,
+};
+use katago_server_client::ClientError;
+use serde_json::{json, Value};
+use std::time::Duration;
+use support::TestServer;
+
+/// A query the fake engine handles as `behavior` (see src/bin/fake_katago.rs)
+fn scripted(behavior: &str) -> AnalysisRequest {
+    AnalysisRequest {
+        moves: vec![MoveInput::Simple("D4".to_string())],
+        override_settings: Some(json!({ "fakeKatago": behavior })),
+        ..Default::default()
+    }
+}
+
+fn problem_status(error: ClientError) -> u16 {
+    match error {
+        ClientError::Api(problem) => problem.status,
+        other => panic!("expected a problem response, got {}", other),
+    }
+}
+
+async fn protocol_stats(server: &TestServer) -> Value {
+    reqwest::get(server.url("/api/v1/engine/protocol"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn analysis_round_trip() {
+    let server = TestServer::start().await;
+    let request = AnalysisRequest {
+        moves: vec![
+            MoveInput::Simple("D4".to_string()),
+            MoveInput::Simple("Q16".to_string()),
+        ],
+        include_ownership: Some(true),
+        ..Default::default()
+    };
+
+    let response = server.client().analyze(&request).await.unwrap();
+
+    assert_eq!(response.turn_number, 2);
+    assert!(!response.move_infos.unwrap().is_empty());
+    assert_eq!(response.root_info.unwrap().current_player, "B");
+    assert_eq!(
+        response.ownership.unwrap().as_flat().unwrap().len(),
+        19 * 19
+    );
+    assert!(response.warnings.is_empty());
+}
+
+#[tokio::test]
+async fn ownership_is_keyed_by_coordinate() {
+    let server = TestServer::start().await;
+    let request = AnalysisRequest {
+        moves: vec![MoveInput::Simple("D4".to_string())],
+        board_x_size: 9,
+        board_y_size: 9,
+        include_ownership: Some(true),
+        ownership_format: Some(OwnershipFormat::Map),
+        ..Default::default()
+    };
+
+    let response = server.client().analyze(&request).await.unwrap();
+    let Some(Ownership::Map(ownership)) = response.ownership else {
+        panic!("expected ownership by coordinate");
+    };
+    assert_eq!(ownership.len(), 81);
+    assert!(ownership.contains_key("D4") && ownership.contains_key("J9"));
+
+    let rows = AnalysisRequest {
+        ownership_format: Some(OwnershipFormat::Rows),
+        analyze_turns: Some(vec![0, 1]),
+        ..request
+    };
+    let turns = server.client().analyze_turns(&rows).await;
+    let Some(Ownership::Rows(rows)) = turns.unwrap().turns[0].ownership.clone() else {
+        panic!("expected ownership rows");
+    };
+    assert_eq!(rows.len(), 9);
+    assert!(rows.iter().all(|row| row.len() == 9));
+}
+
+#[tokio::test]
+async fn policy_is_returned_as_board_rows() {
+    let server = TestServer::start().await;
+    let request = AnalysisRequest {
+        moves: vec![MoveInput::Simple("E5".to_string())],
+        board_x_size: 9,
+        board_y_size: 7,
+        max_visits: Some(500),
+        ..Default::default()
+    };
+
+    let policy = server.client().policy(&request).await.unwrap();
+
+    assert_eq!(policy.current_player, "W");
+    assert_eq!(policy.policy.len(), 7);
+    assert!(policy.policy.iter().all(|row| row.len() == 9));
+    assert!(policy.pass_policy > 0.0);
+    assert!(policy.human_policy.is_none());
+
+    // The query was a one-visit search, whatever the request asked for
+    let search = server
+        .client()
+        .analyze(&AnalysisRequest {
+            max_visits: Some(1),
+            include_policy: Some(true),
+            ..request
+        })
+        .await
+        .unwrap();
+    assert_eq!(search.root_info.unwrap().visits, 1);
+    assert_eq!(server.client().cache_stats().await.unwrap().hits, 1);
+}
+
+#[tokio::test]
+async fn corners_are_tagged_with_josekis() {
+    let server = TestServer::start().await;
+    let mut request = AnalysisRequest {
+        moves: ["Q16", "R14", "D4"]
+            .map(|coord| MoveInput::Simple(coord.to_string()))
+            .to_vec(),
+        ..Default::default()
+    };
+
+    let response = server.client().analyze(&request).await.unwrap();
+    assert!(response.josekis.is_none());
+
+    request.include_josekis = Some(true);
+    let response = server.client().analyze(&request).await.unwrap();
+    let josekis = response.josekis.unwrap();
+    let names: Vec<_> = josekis.iter().map(|j| j.name.as_str()).collect();
+    assert_eq!(names, ["4-4 point, low approach", "4-4 point"]);
+    assert_eq!(josekis[0].corner, BoardCorner::TopRight);
+    let next = &josekis[0].continuations[0];
+    assert_eq!(
+        (next.player.as_str(), next.move_coord.as_str()),
+        ("B", "O17")
+    );
+
+    // Each turn of a multi-turn analysis has its own corners
+    request.analyze_turns = Some(vec![0, 3]);
+    let batch = server.client().analyze_turns(&request).await.unwrap();
+    assert_eq!(batch.turns[0].josekis.as_deref(), Some(&[][..]));
+    assert_eq!(batch.turns[1].josekis.as_ref().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn engine_error_fails_only_that_request() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let error = client.analyze(&scripted("error")).await.unwrap_err();
+    assert_eq!(problem_status(error), 500);
+
+    assert!(client.analyze(&AnalysisRequest::default()).await.is_ok());
+}
+
+#[tokio::test]
+async fn unanswered_query_times_out() {
+    let server = TestServer::start().await;
+
+    let error = server
+        .client()
+        .analyze(&scripted("hang"))
+        .await
+        .unwrap_err();
+
+    assert_eq!(problem_status(error), 504);
+}
+
+#[tokio::test]
+async fn warnings_are_attached_to_the_response() {
+    let server = TestServer::start().await;
+
+    let response = server.client().analyze(&scripted("warning")).await.unwrap();
+
+    assert_eq!(response.warnings.len(), 1);
+    assert_eq!(
+        response.warnings[0].field.as_deref(),
+        Some("overrideSettings")
+    );
+}
+
+#[tokio::test]
+async fn unparseable_lines_are_counted_and_skipped() {
+    let server = TestServer::start().await;
+
+    assert!(server.client().analyze(&scripted("garbage")).await.is_ok());
+
+    let stats = protocol_stats(&server).await;
+    assert!(stats["parseFailures"].as_u64().unwrap() >= 1);
+}
+
+#[tokio::test]
+async fn slow_queries_are_answered_concurrently() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let slow = AnalysisRequest {
+        override_settings: Some(json!({ "fakeDelayMs": 1500 })),
+        ..Default::default()
+    };
+
+    // Serialized, four of these would overrun the 2 second move timeout
+    let results = tokio::join!(
+        client.analyze(&slow),
+        client.analyze(&slow),
+        client.analyze(&slow),
+        client.analyze(&slow),
+    );
+
+    assert!(results.0.is_ok() && results.1.is_ok() && results.2.is_ok() && results.3.is_ok());
+}
+
+#[tokio::test]
+async fn max_time_extends_the_response_wait() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    // Longer than the 2 second move timeout
+    let slow = AnalysisRequest {
+        override_settings: Some(json!({ "fakeDelayMs": 3000 })),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        problem_status(client.analyze(&slow).await.unwrap_err()),
+        504
+    );
+    let bounded = AnalysisRequest {
+        max_time_secs: Some(1.0),
+        request_id: Some("bounded".to_string()),
+        ..slow
+    };
+    assert!(client.analyze(&bounded).await.is_ok());
+
+    let invalid = AnalysisRequest {
+        max_time_secs: Some(0.0),
+        ..Default::default()
+    };
+    assert_eq!(
+        problem_status(client.analyze(&invalid).await.unwrap_err()),
+        400
+    );
+}
+
+#[tokio::test]
+async fn latency_target_budgets_visits_from_measured_speed() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    // Searches of ~100 visits taking ~200 ms measure about 500 visits per second
+    for visits in 100..103 {
+        let request = AnalysisRequest {
+            max_visits: Some(visits),
+            override_settings: Some(json!({ "fakeDelayMs": 200 })),
+            ..Default::default()
+        };
+        client.analyze(&request).await.unwrap();
+    }
+
+    let targeted = AnalysisRequest {
+        target_latency_ms: Some(1000),
+        ..Default::default()
+    };
+    let response = client.analyze(&targeted).await.unwrap();
+    let visits = response.root_info.unwrap().visits;
+    assert!((200..=520).contains(&visits), "{} visits", visits);
+
+    let capped = AnalysisRequest {
+        max_visits: Some(50),
+        ..targeted
+    };
+    let response = client.analyze(&capped).await.unwrap();
+    assert_eq!(response.root_info.unwrap().visits, 50);
+}
+
+#[tokio::test]
+async fn strict_validation_rejects_moves_off_the_board() {
+    let server = TestServer::start_with_env(&[("KATAGO_STRICT_VALIDATION", "true")]).await;
+    let client = server.client();
+    let request: AnalysisRequest =
+        serde_json::from_value(json!({ "moves": ["D4", "I5", "Q16", "A20"] })).unwrap();
+
+    let ClientError::Api(problem) = client.analyze(&request).await.unwrap_err() else {
+        panic!("expected a problem response");
+    };
+    assert_eq!(problem.status, 400);
+    let invalid: Vec<(usize, String)> = problem
+        .invalid_moves
+        .unwrap()
+        .into_iter()
+        .map(|m| (m.index, m.move_coord))
+        .collect();
+    assert_eq!(invalid, [(1, "I5".to_string()), (3, "A20".to_string())]);
+
+    // A request can still opt out
+    let lenient = AnalysisRequest {
+        strict_validation: Some(false),
+        ..request
+    };
+    assert!(client.analyze(&lenient).await.is_ok());
+}
+
+#[tokio::test]
+async fn strict_validation_rejects_ko_recaptures() {
+    let server = TestServer::start_with_env(&[("KATAGO_STRICT_VALIDATION", "true")]).await;
+    let client = server.client();
+    // Black takes the ko at C2 and White retakes at once
+    let request: AnalysisRequest = serde_json::from_value(json!({
+        "moves": ["B3", "C3", "A2", "B2", "B1", "D2", "E5", "C1", "C2", "B2"]
+    }))
+    .unwrap();
+
+    let ClientError::Api(problem) = client.analyze(&request).await.unwrap_err() else {
+        panic!("expected a problem response");
+    };
+    assert_eq!(problem.status, 400);
+    assert_eq!(problem.title, "Illegal Move");
+    let illegal: Vec<(usize, String)> = problem
+        .invalid_moves
+        .unwrap()
+        .into_iter()
+        .map(|m| (m.index, m.move_coord))
+        .collect();
+    assert_eq!(illegal, [(9, "B2".to_string())]);
+
+    // Retaking after a move elsewhere is fine
+    let mut moves = request.moves.clone();
+    moves.insert(9, MoveInput::Simple("Q16".to_string()));
+    moves.insert(10, MoveInput::Simple("D16".to_string()));
+    let legal = AnalysisRequest {
+        moves,
+        ..request.clone()
+    };
+    assert!(client.analyze(&legal).await.is_ok());
+
+    let lenient = AnalysisRequest {
+        strict_validation: Some(false),
+        ..request
+    };
+    assert!(client.analyze(&lenient).await.is_ok());
+}
+
+#[tokio::test]
+async fn rules_can_be_named_or_spelled_out() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let rule_sets: Value = reqwest::get(server.url("/api/v1/rules"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let japanese = rule_sets
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|rules| rules["name"] == "japanese")
+        .unwrap();
+    assert_eq!(japanese["scoringRule"], "TERRITORY");
+    assert_eq!(japanese["aliases"], json!(["korean"]));
+
+    let request: AnalysisRequest = serde_json::from_value(json!({
+        "moves": ["D4"],
+        "rules": { "koRule": "positional", "scoringRule": "AREA", "whiteHandicapBonus": "N" }
+    }))
+    .unwrap();
+    assert!(client.analyze(&request).await.is_ok());
+
+    let request: AnalysisRequest =
+        serde_json::from_value(json!({ "moves": ["D4"], "rules": { "koRule": "superko" } }))
+            .unwrap();
+    let ClientError::Api(problem) = client.analyze(&request).await.unwrap_err() else {
+        panic!("expected a problem response");
+    };
+    assert_eq!(problem.status, 400);
+    assert_eq!(problem.title, "Invalid Rules");
+}
+
+#[tokio::test]
+async fn engine_crash_is_reported_and_recovered() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let error = client.analyze(&scripted("crash")).await.unwrap_err();
+    assert_eq!(problem_status(error), 503);
+
+    // The monitor restarts the engine (after its restart delay)
+    assert!(server.wait_healthy(Duration::from_secs(30)).await);
+    assert!(client.analyze(&AnalysisRequest::default()).await.is_ok());
+    assert_eq!(client.health().await.unwrap().restarts, Some(1));
+}
+
+#[tokio::test]
+async fn version_reports_the_engine_answer() {
+    let server = TestServer::start().await;
+
+    let katago = server.client().version().await.unwrap().katago.unwrap();
+    assert_eq!(katago.version, "1.16.0");
+    assert_eq!(katago.git_hash.as_deref(), Some("fake"));
+}
+
+#[tokio::test]
+async fn health_reports_engine_activity() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let health = client.health().await.unwrap();
+    assert_eq!(health.status, "healthy");
+    assert!(health.uptime.is_some() && health.engine_uptime.is_some());
+    assert_eq!(health.restarts, Some(0));
+    assert_eq!(health.pending, Some(0));
+    assert!(health.last_success.is_none());
+    assert!(health.model.is_some());
+
+    client.analyze(&AnalysisRequest::default()).await.unwrap();
+    assert!(client.health().await.unwrap().last_success.is_some());
+}
+
+#[tokio::test]
+async fn native_endpoint_keeps_caller_ids() {
+    let server = TestServer::start().await;
+    let body = [
+        json!({ "id": "first", "moves": [["B", "D4"]], "rules": "japanese", "komi": 6.5,
+                "boardXSize": 19, "boardYSize": 19 }),
+        json!({ "id": "second", "moves": [], "rules": "japanese", "komi": 6.5,
+                "boardXSize": 9, "boardYSize": 9, "overrideSettings": { "fakeKatago": "error" } }),
+    ]
+    .map(|query| query.to_string())
+    .join("\n");
+
+    let text = reqwest::Client::new()
+        .post(server.url("/api/v1/katago/analysis"))
+        .body(body)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let lines: Vec<Value> = text
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert_eq!(lines.len(), 2);
+    let first = lines.iter().find(|l| l["id"] == "first").unwrap();
+    assert!(first["moveInfos"].is_array());
+    let second = lines.iter().find(|l| l["id"] == "second").unwrap();
+    assert!(second["error"].is_string());
+}
+
+#[tokio::test]
+async fn partial_results_are_streamed_before_the_result() {
+    let server = TestServer::start().await;
+    let request = AnalysisRequest {
+        report_during_search_every: Some(0.2),
+        ..Default::default()
+    };
+
+    let body = reqwest::Client::new()
+        .post(server.url("/api/v1/analysis/stream"))
+        .json(&request)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let events: Vec<&str> = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("event: "))
+        .collect();
+
+    assert_eq!(events, ["partial", "result"]);
+}
+
+#[tokio::test]
+async fn selfplay_streams_moves_until_both_players_pass() {
+    let server = TestServer::start().await;
+    // The fake engine suggests D4, F6, and E5 on 9x9, then passes once they are taken
+    let request = SelfPlayRequest {
+        position: AnalysisRequest {
+            moves: vec![MoveInput::WithColor(["B".to_string(), "E5".to_string()])],
+            board_x_size: 9,
+            board_y_size: 9,
+            max_visits: Some(20),
+            ..Default::default()
+        },
+        max_moves: Some(10),
+    };
+    let post = |request: &SelfPlayRequest| {
+        reqwest::Client::new()
+            .post(server.url("/api/v1/selfplay"))
+            .json(request)
+            .send()
+    };
+
+    let body = post(&request).await.unwrap().text().await.unwrap();
+    let events: Vec<(&str, &str)> = body
+        .split("\n\n")
+        .filter_map(|event| {
+            let name = event.lines().find_map(|l| l.strip_prefix("event: "))?;
+            let data = event.lines().find_map(|l| l.strip_prefix("data: "))?;
+            Some((name, data))
+        })
+        .collect();
+    let names: Vec<_> = events.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, ["move", "move", "move", "move", "result"]);
+
+    let first: SelfPlayMove = serde_json::from_str(events[0].1).unwrap();
+    assert_eq!(first.move_number, 2);
+    assert_eq!(first.player, "W");
+    assert_eq!(first.move_coord, "D4");
+    assert_eq!(first.visits, 20);
+    let result: SelfPlayResult = serde_json::from_str(events[4].1).unwrap();
+    assert_eq!(result.end, SelfPlayEnd::TwoPasses);
+    let played: Vec<_> = result
+        .played
+        .iter()
+        .map(|m| (m.player.as_str(), m.move_coord.as_str()))
+        .collect();
+    assert_eq!(
+        played,
+        [("W", "D4"), ("B", "F6"), ("W", "pass"), ("B", "pass")]
+    );
+    assert_eq!(result.moves.len(), 5);
+
+    let too_long = SelfPlayRequest {
+        max_moves: Some(100_000),
+        ..request
+    };
+    assert_eq!(post(&too_long).await.unwrap().status(), 400);
+}
+
+#[tokio::test]
+async fn play_session_answers_each_move_until_both_pass() {
+    let server = TestServer::start().await;
+    let http = reqwest::Client::new();
+    let post = |path: String, body: Value| http.post(server.url(&path)).json(&body).send();
+
+    // The fake engine plays D4, F6, and E5 on 9x9, then passes once they are taken
+    let session: Value = post(
+        "/api/v1/play/sessions".to_string(),
+        json!({ "boardSize": 9, "color": "W", "maxVisits": 20 }),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(session["moves"], json!([["B", "D4"]]), "{}", session);
+    assert_eq!(session["playerToMove"], "W");
+    let moves = format!(
+        "/api/v1/play/sessions/{}/move",
+        session["sessionId"].as_str().unwrap()
+    );
+
+    let reply: Value = post(moves.clone(), json!({ "move": "c3" }))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(reply["move"], "C3");
+    assert_eq!(reply["botMove"], "F6");
+    assert_eq!(reply["evaluation"]["visits"], 20);
+
+    let occupied = post(moves.clone(), json!({ "move": "D4" })).await.unwrap();
+    assert_eq!(occupied.status(), 400);
+    let problem: Value = occupied.json().await.unwrap();
+    assert_eq!(problem["title"], "Illegal Move");
+
+    let reply: Value = post(moves.clone(), json!({ "move": "E5" }))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(reply["botMove"], "pass");
+    assert_eq!(reply["session"]["finished"], false);
+    // Passing back ends the game without another engine move
+    let reply: Value = post(moves.clone(), json!({ "move": "pass" }))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(reply.get("botMove").is_none(), "{}", reply);
+    assert_eq!(reply["session"]["finished"], true);
+    assert_eq!(reply["session"]["moves"].as_array().unwrap().len(), 6);
+
+    let finished = post(moves, json!({ "move": "A1" })).await.unwrap();
+    assert_eq!(finished.status(), 409);
+    let unknown = http
+        .get(server.url("/api/v1/play/sessions/nope"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unknown.status(), 404);
+}
+
+#[tokio::test]
+async fn handicap_play_session_starts_with_the_engine_as_white() {
+    let server = TestServer::start().await;
+    let session: Value = reqwest::Client::new()
+        .post(server.url("/api/v1/play/sessions"))
+        .json(&json!({ "boardSize": 9, "handicap": 2 }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(session["handicap"], 2, "{}", session);
+    assert_eq!(session["komi"], 0.5);
+    assert_eq!(session["initialStones"], json!([["B", "C3"], ["B", "G7"]]));
+    assert_eq!(session["moves"], json!([["W", "D4"]]));
+    assert_eq!(session["playerToMove"], "B");
+}
+
+#[tokio::test]
+async fn sgf_game_is_reviewed_move_by_move() {
+    let server = TestServer::start().await;
+    let request = ReviewRequest {
+        sgf: "(;GM[1]SZ[9]KM[7]PB[Black];B[ee];W[cc];B[gg])".to_string(),
+        max_visits: Some(50),
+        ..Default::default()
+    };
+
+    let review = server.client().review(&request).await.unwrap();
+
+    assert_eq!(review.board_x_size, 9);
+    assert_eq!(review.black_player.as_deref(), Some("Black"));
+    let played: Vec<_> = review.moves.iter().map(|m| m.move_coord.as_str()).collect();
+    assert_eq!(played, ["E5", "C7", "G3"]);
+    assert_eq!(review.moves[1].player, "W");
+    assert!(review.moves.iter().all(|m| m.best_move.is_some()));
+
+    // The SGF file itself works as the body too
+    let upload = |sgf: &'static str| {
+        reqwest::Client::new()
+            .post(server.url("/api/v1/review?maxVisits=50"))
+            .header("Content-Type", "application/x-go-sgf")
+            .body(sgf)
+            .send()
+    };
+    assert_eq!(upload("(;SZ[9];B[ee];W[cc])").await.unwrap().status(), 200);
+    assert_eq!(upload("(;SZ[9];B[ee];W[zz])").await.unwrap().status(), 400);
+}
+
+#[tokio::test]
+async fn reviewed_moves_are_classified_by_loss() {
+    let server = TestServer::start().await;
+    // The fake engine's score lead flips between the players, so every move loses
+    // 3 points
+    let mut request = ReviewRequest {
+        sgf: "(;SZ[9];B[ee];W[cc])".to_string(),
+        ..Default::default()
+    };
+    let review = server.client().review(&request).await.unwrap();
+    let classes: Vec<_> = review.moves.iter().map(|m| m.classification).collect();
+    assert_eq!(classes, [MoveClassification::Mistake; 2]);
+    assert_eq!(review.summary.black.mistake, 1);
+    assert_eq!(review.summary.white.mistake, 1);
+
+    request.score_loss = Some(LossThresholds {
+        blunder: 10.0,
+        mistake: 5.0,
+        inaccuracy: 2.0,
+    });
+    let review = server.client().review(&request).await.unwrap();
+    assert_eq!(
+        review.moves[0].classification,
+        MoveClassification::Inaccuracy
+    );
+
+    // D4 is the fake engine's preferred move, whatever it loses
+    request.sgf = "(;SZ[9];B[df];W[cc])".to_string();
+    let review = server.client().review(&request).await.unwrap();
+    assert_eq!(review.moves[0].classification, MoveClassification::Best);
+    assert_eq!(review.summary.black.best, 1);
+    assert_eq!(review.summary.white.inaccuracy, 1);
+}
+
+#[tokio::test]
+async fn review_is_exported_for_katrain_and_lizzie() {
+    let server = TestServer::start().await;
+    let mut request = ReviewRequest {
+        sgf: "(;SZ[9]KM[7];B[ee];W[cc])".to_string(),
+        format: Some(ReviewFormat::Katrain),
+        ..Default::default()
+    };
+
+    let katrain = server.client().review_export(&request).await.unwrap();
+    assert!(katrain.starts_with("(;FF[4]"));
+    assert!(katrain.contains(";B[ee]BM[1]C[Move 1: B E5\n"));
+    assert!(katrain.contains("Estimated point loss: 3.0"));
+
+    request.format = Some(ReviewFormat::Lizzie);
+    let lizzie = server.client().review_export(&request).await.unwrap();
+    assert_eq!(lizzie.matches("LZ[katago-server-").count(), 2);
+
+    // The SGF file needs the response; callbacks and jobs carry JSON
+    let upload = reqwest::Client::new()
+        .post(server.url("/api/v1/review?format=lizzie"))
+        .header("Content-Type", "application/x-go-sgf")
+        .body("(;SZ[9];B[ee])")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(upload.status(), 200);
+    assert!(upload.text().await.unwrap().contains("LZ["));
+    request.callback_url = Some("http://127.0.0.1:9/hook".to_string());
+    let error = server.client().review_export(&request).await.unwrap_err();
+    assert_eq!(problem_status(error), 400);
+}
+
+#[tokio::test]
+async fn ogs_game_is_fetched_and_reviewed() {
+    use axum::response::IntoResponse;
+
+    // OGS's API, serving game 1234, and an endless record as 5678 without a
+    // Content-Length
+    let app = axum::Router::new().route(
+        "/api/v1/games/{id}/sgf",
+        axum::routing::get(
+            |axum::extract::Path(id): axum::extract::Path<u64>| async move {
+                match id {
+                    1234 => "(;GM[1]SZ[9]KM[6.5]PB[alice]PW[bob];B[ee];W[cc])".into_response(),
+                    5678 => {
+                        let chunks = std::iter::repeat_with(|| {
+                            Ok::<_, std::io::Error>(axum::body::Bytes::from(vec![b' '; 64 * 1024]))
+                        });
+                        axum::body::Body::from_stream(tokio_stream::iter(chunks)).into_response()
+                    }
+                    _ => axum::http::StatusCode::NOT_FOUND.into_response(),
+                }
+            },
+        ),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let ogs_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    let server = TestServer::start_with_env(&[
+        ("KATAGO_OGS_ENABLED", "true"),
+        ("KATAGO_OGS_API_URL", &ogs_url),
+    ])
+    .await;
+
+    let mut request = OgsImportRequest {
+        game_id: 1234,
+        max_visits: Some(50),
+        ..Default::default()
+    };
+    let review = server.client().import_ogs(&request).await.unwrap();
+    assert_eq!(review.black_player.as_deref(), Some("alice"));
+    assert_eq!(review.komi, Some(6.5));
+    let played: Vec<_> = review.moves.iter().map(|m| m.move_coord.as_str()).collect();
+    assert_eq!(played, ["E5", "C7"]);
+
+    request.game_id = 99;
+    let error = server.client().import_ogs(&request).await.unwrap_err();
+    assert_eq!(problem_status(error), 404);
+
+    // The endless reply is cut off at the size limit
+    request.game_id = 5678;
+    let error = server.client().import_ogs(&request).await.unwrap_err();
+    assert_eq!(problem_status(error), 502);
+
+    // Fetching is opt-in
+    let disabled = TestServer::start().await;
+    let error = disabled.client().import_ogs(&request).await.unwrap_err();
+    assert_eq!(problem_status(error), 404);
+}
+
+#[tokio::test]
+async fn winrate_graph_covers_every_turn() {
+    let server = TestServer::start().await;
+    // 40 moves, so the turns span two analyzeTurns batches
+    let moves: String = (0..40)
+        .map(|i| {
+            let color = if i % 2 == 0 { 'B' } else { 'W' };
+            let (x, y) = (b'a' + i / 19, b'a' + i % 19);
+            format!(";{}[{}{}]", color, x as char, y as char)
+        })
+        .collect();
+    let request = ReviewRequest {
+        sgf: format!("(;SZ[19]{})", moves),
+        max_visits: Some(50),
+        ..Default::default()
+    };
+
+    let graph = server.client().review_graph(&request).await.unwrap();
+
+    assert_eq!(graph.turns, (0..=40).collect::<Vec<u32>>());
+    assert_eq!(graph.winrate.len(), 41);
+    // The fake engine gives the side to move a 1.5 point lead; the graph is Black's
+    assert_eq!(graph.score_lead[..3], [1.5, -1.5, 1.5]);
+    assert_eq!(graph.score_lead[33], -1.5);
+
+    let invalid = reqwest::Client::new()
+        .post(server.url("/api/v1/review/graph"))
+        .header("Content-Type", "application/x-go-sgf")
+        .body("(;SZ[9];B[zz])")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(invalid.status(), 400);
+}
+
+#[tokio::test]
+async fn final_score_is_estimated_from_ownership() {
+    let server = TestServer::start().await;
+    let request = ScoreRequest {
+        position: AnalysisRequest {
+            moves: vec![MoveInput::Simple("E5".to_string())],
+            board_x_size: 9,
+            board_y_size: 9,
+            komi: Some(6.5),
+            rules: Some("japanese".into()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    // The fake engine owns no point for anyone, so only komi counts
+    let estimate = server.client().score(&request).await.unwrap();
+    assert_eq!(estimate.scoring, "territory");
+    assert_eq!(estimate.territory.len(), 81);
+    assert_eq!(estimate.black_territory + estimate.white_territory, 0);
+    assert_eq!(estimate.score, -6.5);
+    assert_eq!(estimate.result, "W+6.5");
+    assert!(estimate.comparison.is_none());
+
+    let compared = ScoreRequest {
+        compare_scoring: Some(true),
+        ..request.clone()
+    };
+    let estimate = server.client().score(&compared).await.unwrap();
+    let comparison = estimate.comparison.unwrap();
+    assert_eq!(comparison.territory_scoring.result, "W+6.5");
+    assert_eq!(comparison.area_scoring.result, "W+6.5");
+    assert!(!comparison.winner_differs);
+
+    let invalid = ScoreRequest {
+        ownership_threshold: Some(1.5),
+        ..request
+    };
+    let error = server.client().score(&invalid).await.unwrap_err();
+    assert_eq!(problem_status(error), 400);
+}
+
+#[tokio::test]
+async fn request_id_header_is_echoed_and_reported() {
+    let server = TestServer::start().await;
+    let http = reqwest::Client::new();
+
+    let response = http
+        .post(server.url("/api/v1/analysis"))
+        .header("X-Request-Id", "trace-42")
+        .json(&json!({ "moves": ["D4"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-request-id"], "trace-42");
+    let analysis: Value = response.json().await.unwrap();
+    assert_eq!(analysis["id"], "trace-42");
+
+    // Problems carry it too, including those of handlers that never see it
+    let response = http
+        .get(server.url("/api/v1/positions/missing"))
+        .header("X-Request-Id", "trace-43")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    assert_eq!(response.headers()["x-request-id"], "trace-43");
+    let problem: Value = response.json().await.unwrap();
+    assert_eq!(problem["requestId"], "trace-43");
+
+    // Without the header, the server makes one up
+    let response = reqwest::get(server.url("/api/v1/health")).await.unwrap();
+    assert!(!response.headers()["x-request-id"].is_empty());
+}
+
+/// Accept one OTLP/HTTP export on `listener` and return its path and body
+async fn receive_export(listener: &tokio::net::TcpListener) -> (String, Vec<u8>) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let mut received = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let (head_len, body_len) = loop {
+        let n = stream.read(&mut chunk).await.unwrap();
+        assert!(n > 0, "export ended early");
+        received.extend_from_slice(&chunk[..n]);
+        if let Some(end) = received.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&received[..end]).to_lowercase();
+            let length = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .map(|len| len.trim().parse::<usize>().unwrap())
+                .unwrap_or(0);
+            break (end + 4, length);
+        }
+    };
+    while received.len() < head_len + body_len {
+        let n = stream.read(&mut chunk).await.unwrap();
+        received.extend_from_slice(&chunk[..n]);
+    }
+    stream
+        .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+        .await
+        .unwrap();
+    let head = String::from_utf8_lossy(&received[..head_len]);
+    let path = head.split_whitespace().nth(1).unwrap().to_string();
+    (path, received[head_len..].to_vec())
+}
+
+#[tokio::test]
+async fn analysis_spans_are_exported_over_otlp() {
+    let collector = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", collector.local_addr().unwrap());
+    let server = TestServer::start_with_env(&[
+        ("KATAGO_OTLP_ENDPOINT", &endpoint),
+        ("KATAGO_OTLP_SERVICE_NAME", "analysis-under-test"),
+        ("OTEL_BSP_SCHEDULE_DELAY", "100"),
+    ])
+    .await;
+    server
+        .client()
+        .analyze(&AnalysisRequest::default())
+        .await
+        .unwrap();
+
+    // Health checks during startup may have been exported in an earlier batch
+    let names = [
+        "analysis-under-test",
+        "request",
+        "queue_wait",
+        "stdin_write",
+        "katago_response",
+    ];
+    let mut exported = Vec::new();
+    let contains = |body: &[u8], text: &str| body.windows(text.len()).any(|w| w == text.as_bytes());
+    while !names.iter().all(|name| contains(&exported, name)) {
+        let (path, body) =
+            tokio::time::timeout(Duration::from_secs(10), receive_export(&collector))
+                .await
+                .expect("analysis spans not exported");
+        assert_eq!(path, "/v1/traces");
+        exported.extend(body);
+    }
+}
+
+/// Self-signed certificate and key for 127.0.0.1, in PEM
+fn self_signed() -> (String, String) {
+    let cert = rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+    (cert.cert.pem(), cert.key_pair.serialize_pem())
+}
+
+/// Client trusting only `cert`
+fn trusting(cert: &str) -> reqwest::Client {
+    reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(cert.as_bytes()).unwrap())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn https_serves_a_renewed_certificate_without_restart() {
+    let (cert, key) = self_signed();
+    let server = TestServer::start_with_tls(&cert, &key, &[("KATAGO_TLS_RELOAD_SECS", "1")]).await;
+    assert!(server.base_url.starts_with("https://"));
+    let analysis: Value = trusting(&cert)
+        .post(server.url("/api/v1/analysis"))
+        .json(&json!({ "moves": ["D4"] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(analysis["moveInfos"].is_array());
+
+    let (renewed_cert, renewed_key) = self_signed();
+    std::fs::write(server.dir().join("key.pem"), &renewed_key).unwrap();
+    std::fs::write(server.dir().join("cert.pem"), &renewed_cert).unwrap();
+
+    let renewed = trusting(&renewed_cert);
+    let deadline = std::time::Instant::now() + Duration::from_secs(10);
+    loop {
+        match renewed.get(server.url("/api/v1/health")).send().await {
+            Ok(response) => {
+                assert!(response.status().is_success());
+                break;
+            }
+            Err(_) if std::time::Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+            Err(e) => panic!("renewed certificate not served: {}", e),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_socket_listener_serves_the_api() {
+    let server = TestServer::start_on_unix_socket(&[]).await;
+
+    let (status, body) = server
+        .unix_request("POST", "/api/v1/analysis", r#"{"moves": ["D4"]}"#)
+        .await
+        .unwrap();
+    assert_eq!(status, 200);
+    let analysis: Value = serde_json::from_str(&body).unwrap();
+    assert!(analysis["moveInfos"].is_array());
+}
+
+#[tokio::test]
+async fn several_turns_are_analyzed_in_one_query() {
+    let server = TestServer::start().await;
+    let request = AnalysisRequest {
+        moves: ["D4", "Q16", "C3"]
+            .map(|coord| MoveInput::Simple(coord.to_string()))
+            .to_vec(),
+        analyze_turns: Some(vec![3, 0, 1]),
+        ..Default::default()
+    };
+
+    let batch = server.client().analyze_turns(&request).await.unwrap();
+
+    let turns: Vec<u32> = batch.turns.iter().map(|t| t.turn_number).collect();
+    assert_eq!(turns, [0, 1, 3]);
+    assert!(batch.turns.iter().all(|t| t.id == batch.id));
+    let to_move = batch.turns[1]
+        .root_info
+        .as_ref()
+        .unwrap()
+        .current_player
+        .as_str();
+    assert_eq!(to_move, "W");
+
+    // The single-result endpoint points to this one
+    let error = server.client().analyze(&request).await.unwrap_err();
+    assert_eq!(problem_status(error), 400);
+}
+
+#[tokio::test]
+async fn sgf_game_is_analyzed_at_its_turns() {
+    let server = TestServer::start().await;
+    let request = AnalysisRequest {
+        sgf: Some("(;GM[1]SZ[19]KM[0.5]HA[2]AB[dd][pp];W[qd];B[dp])".to_string()),
+        analyze_turns: Some(vec![0, 2]),
+        ..Default::default()
+    };
+
+    let batch = server.client().analyze_turns(&request).await.unwrap();
+
+    let turns: Vec<u32> = batch.turns.iter().map(|t| t.turn_number).collect();
+    assert_eq!(turns, [0, 2]);
+    // White moved first after the handicap stones, then Black
+    let to_move = batch.turns[1].root_info.as_ref().unwrap();
+    assert_eq!(to_move.current_player, "W");
+
+    let final_position = AnalysisRequest {
+        analyze_turns: None,
+        ..request
+    };
+    let analysis = server.client().analyze(&final_position).await.unwrap();
+    assert_eq!(analysis.root_info.unwrap().current_player, "W");
+}
+
+#[tokio::test]
+async fn full_queue_is_rejected_with_retry_after() {
+    let server =
+        TestServer::start_with_env(&[("KATAGO_MAX_IN_FLIGHT", "1"), ("KATAGO_MAX_QUEUED", "1")])
+            .await;
+    let running = tokio::spawn({
+        let client = server.client();
+        async move { client.analyze(&scripted("hang")).await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let queued = tokio::spawn({
+        let client = server.client();
+        async move { client.analyze(&AnalysisRequest::default()).await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let rejected = reqwest::Client::new()
+        .post(server.url("/api/v1/analysis"))
+        .json(&AnalysisRequest::default())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(rejected.status(), 429);
+    assert_eq!(rejected.headers()["retry-after"], "1");
+
+    // The queued request runs once the hanging one times out
+    assert_eq!(problem_status(running.await.unwrap().unwrap_err()), 504);
+    assert!(queued.await.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn running_query_can_be_cancelled() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let running = tokio::spawn({
+        let client = server.client();
+        let request = AnalysisRequest {
+            request_id: Some("deep-search".to_string()),
+            ..scripted("hang")
+        };
+        async move { client.analyze(&request).await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let cancelled = client.cancel("deep-search").await.unwrap();
+    assert_eq!(cancelled.id, "deep-search");
+    assert_eq!(problem_status(running.await.unwrap().unwrap_err()), 409);
+
+    let error = client.cancel("deep-search").await.unwrap_err();
+    assert_eq!(problem_status(error), 404);
+}
+
+/// Poll a job until it is no longer running
+async fn finished_job(server: &TestServer, id: &str) -> JobResponse {
+    let client = server.client();
+    for _ in 0..100 {
+        let job = client.job(id).await.unwrap();
+        if job.status != JobStatus::Running {
+            return job;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("job {} is still running", id);
+}
+
+#[tokio::test]
+async fn review_job_is_polled_until_done() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let request = JobRequest::Review(ReviewRequest {
+        sgf: "(;GM[1]SZ[9]KM[7];B[ee];W[cc];B[gg])".to_string(),
+        max_visits: Some(50),
+        ..Default::default()
+    });
+
+    let response = reqwest::Client::new()
+        .post(server.url("/api/v1/jobs"))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+    let location = response.headers()["location"].to_str().unwrap().to_string();
+    let started: JobResponse = response.json().await.unwrap();
+    assert_eq!(location, format!("/api/v1/jobs/{}", started.job_id));
+    assert_eq!(started.kind, JobKind::Review);
+    assert_eq!(started.progress.total, 4);
+
+    let done = finished_job(&server, &started.job_id).await;
+    assert_eq!(done.status, JobStatus::Completed);
+    assert_eq!(done.progress.completed, 4);
+    let result = done.result.unwrap();
+    assert_eq!(result["moves"].as_array().unwrap().len(), 3);
+    assert!(done.finished_at.is_some());
+
+    // Malformed requests fail before a job starts
+    let invalid = JobRequest::Review(ReviewRequest {
+        sgf: "(;SZ[9];B[zz])".to_string(),
+        ..Default::default()
+    });
+    assert_eq!(
+        problem_status(client.submit_job(&invalid).await.unwrap_err()),
+        400
+    );
+    assert_eq!(
+        problem_status(client.job("missing").await.unwrap_err()),
+        404
+    );
+}
+
+#[tokio::test]
+async fn running_job_can_be_cancelled() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let job = client
+        .submit_job(&JobRequest::Analysis(scripted("hang")))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(
+        client.job(&job.job_id).await.unwrap().status,
+        JobStatus::Running
+    );
+
+    let cancelled = client.cancel_job(&job.job_id).await.unwrap();
+    assert_eq!(cancelled.status, JobStatus::Cancelled);
+    // KataGo was told to stop the job's query
+    assert_eq!(
+        problem_status(client.cancel(&job.job_id).await.unwrap_err()),
+        404
+    );
+    let error = client.cancel_job(&job.job_id).await.unwrap_err();
+    assert_eq!(problem_status(error), 409);
+
+    // A failed analysis keeps its problem detail
+    let job = client
+        .submit_job(&JobRequest::Analysis(scripted("error")))
+        .await
+        .unwrap();
+    let failed = finished_job(&server, &job.job_id).await;
+    assert_eq!(failed.status, JobStatus::Failed);
+    assert!(failed.error.is_some());
+}
+
+#[tokio::test]
+async fn unfinished_jobs_resume_after_a_restart() {
+    let path = std::env::temp_dir().join(format!("katago-jobs-{}.db", std::process::id()));
+    let database = ("KATAGO_JOBS_DATABASE_PATH", path.to_str().unwrap());
+    let request = JobRequest::Review(ReviewRequest {
+        sgf: "(;GM[1]SZ[9];B[ee];W[cc])".to_string(),
+        max_visits: Some(50),
+        ..Default::default()
+    });
+
+    // Stopped while KataGo is still answering
+    let server = TestServer::start_with_env(&[database, ("FAKE_KATAGO_LATENCY_MS", "10000")]).await;
+    let job = server.client().submit_job(&request).await.unwrap();
+    drop(server);
+
+    let server = TestServer::start_with_env(&[database]).await;
+    let done = finished_job(&server, &job.job_id).await;
+    assert_eq!(done.status, JobStatus::Completed);
+    assert_eq!(done.created_at, job.created_at);
+    assert_eq!(done.result.unwrap()["moves"].as_array().unwrap().len(), 2);
+    drop(server);
+
+    // Finished jobs can still be polled after the next restart
+    let server = TestServer::start_with_env(&[database]).await;
+    let again = server.client().job(&job.job_id).await.unwrap();
+    assert_eq!(again.status, JobStatus::Completed);
+    drop(server);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn metrics_count_requests_and_timeouts() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    client.analyze(&AnalysisRequest::default()).await.unwrap();
+    client.analyze(&scripted("hang")).await.unwrap_err();
+
+    let metrics = reqwest::get(server.url("/metrics"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    for line in [
+        r#"katago_server_http_requests_total{method="POST",route="/api/v1/analysis",status="200"} 1"#,
+        r#"katago_server_http_requests_total{method="POST",route="/api/v1/analysis",status="504"} 1"#,
+        r#"katago_server_http_request_duration_seconds_count{method="POST",route="/api/v1/analysis"} 2"#,
+        r#"katago_server_engine_up{engine="default"} 1"#,
+        r#"katago_server_engine_timeouts_total{engine="default"} 1"#,
+        r#"katago_server_analyses_in_flight{engine="default"} 0"#,
+    ] {
+        assert!(metrics.lines().any(|l| l == line), "missing {}", line);
+    }
+}
+
+#[tokio::test]
+async fn openmetrics_latency_buckets_link_to_traces() {
+    let collector = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", collector.local_addr().unwrap());
+    let server = TestServer::start_with_env(&[("KATAGO_OTLP_ENDPOINT", &endpoint)]).await;
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let response = reqwest::Client::new()
+        .post(server.url("/api/v1/analysis"))
+        .header(
+            "traceparent",
+            format!("00-{}-00f067aa0ba902b7-01", trace_id),
+        )
+        .json(&json!({ "moves": ["D4"] }))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let response = reqwest::Client::new()
+        .get(server.url("/metrics"))
+        .header("accept", "application/openmetrics-text;version=1.0.0")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/openmetrics-text; version=1.0.0; charset=utf-8"
+    );
+    let metrics = response.text().await.unwrap();
+    let exemplar = format!("# {{trace_id=\"{}\"}}", trace_id);
+    assert!(
+        metrics.lines().any(|l| l
+            .starts_with(r#"katago_server_http_request_duration_seconds_bucket{method="POST",route="/api/v1/analysis""#)
+            && l.contains(&exemplar)),
+        "no exemplar for the trace\n{}",
+        metrics
+    );
+    assert!(metrics.ends_with("# EOF\n"));
+}
+
+#[tokio::test]
+async fn repeated_analysis_is_served_from_the_cache() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let request = AnalysisRequest {
+        moves: vec![MoveInput::Simple("Q16".to_string())],
+        ..Default::default()
+    };
+
+    let first = client.analyze(&request).await.unwrap();
+    let again = client
+        .analyze(&AnalysisRequest {
+            request_id: Some("again".to_string()),
+            ..request.clone()
+        })
+        .await
+        .unwrap();
+    assert_eq!(again.id, "again");
+    assert_eq!(
+        again.move_infos.unwrap().len(),
+        first.move_infos.unwrap().len()
+    );
+
+    let stats = client.cache_stats().await.unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+
+    client.clear_cache().await.unwrap();
+    client.analyze(&request).await.unwrap();
+    assert_eq!(client.cache_stats().await.unwrap().misses, 2);
+}
+
+#[tokio::test]
+async fn analysis_meta_reports_where_the_time_went() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let request = AnalysisRequest {
+        moves: vec![MoveInput::Simple("D4".to_string())],
+        ..Default::default()
+    };
+
+    let searched = client.analyze(&request).await.unwrap();
+    let meta = searched.meta.unwrap();
+    assert!(!meta.cached);
+    assert_eq!(meta.effective_visits, searched.root_info.unwrap().visits);
+    assert!(meta.total_ms >= meta.queue_wait_ms + meta.search_ms);
+
+    let cached = client.analyze(&request).await.unwrap().meta.unwrap();
+    assert!(cached.cached);
+    assert_eq!(cached.search_ms, 0);
+    assert_eq!(cached.visits_per_second, None);
+}
+
+#[tokio::test]
+async fn disk_cache_answers_after_a_restart() {
+    let dir = std::env::temp_dir().join(format!("katago-results-{}", std::process::id()));
+    let env = [("KATAGO_RESULT_CACHE_DIR", dir.to_str().unwrap())];
+    let request = AnalysisRequest {
+        moves: vec![MoveInput::Simple("D4".to_string())],
+        ..Default::default()
+    };
+
+    let server = TestServer::start_with_env(&env).await;
+    let first = server.client().analyze(&request).await.unwrap();
+    let stats = server.client().cache_stats().await.unwrap();
+    let disk = stats.backend.unwrap();
+    assert_eq!(
+        (disk.kind.as_str(), disk.hits, disk.entries),
+        ("disk", 0, Some(1))
+    );
+    drop(server);
+
+    let server = TestServer::start_with_env(&env).await;
+    let client = server.client();
+    let again = client.analyze(&request).await.unwrap();
+    assert_eq!(
+        again.root_info.unwrap().visits,
+        first.root_info.unwrap().visits
+    );
+    let stats = client.cache_stats().await.unwrap();
+    assert_eq!((stats.misses, stats.entries), (1, 1));
+    assert_eq!(stats.backend.unwrap().hits, 1);
+
+    client.clear_cache().await.unwrap();
+    let stats = client.cache_stats().await.unwrap();
+    assert_eq!(stats.backend.unwrap().entries, Some(0));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn salvaged_results_are_cached() {
+    let dir = std::env::temp_dir().join(format!("katago-salvage-{}", std::process::id()));
+    let server =
+        TestServer::start_with_env(&[("KATAGO_RESULT_CACHE_DIR", dir.to_str().unwrap())]).await;
+    let client = server.client();
+    // Longer than the 2 second move timeout
+    let slow = AnalysisRequest {
+        override_settings: Some(json!({ "fakeDelayMs": 2500 })),
+        ..Default::default()
+    };
+    assert_eq!(
+        problem_status(client.analyze(&slow).await.unwrap_err()),
+        504
+    );
+
+    // The late result answers the retry and goes into both caches
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    client.analyze(&slow).await.unwrap();
+    let stats = client.cache_stats().await.unwrap();
+    assert_eq!(stats.entries, 1);
+    assert_eq!(stats.backend.unwrap().entries, Some(1));
+    drop(server);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn redis_cache_is_shared_between_servers() {
+    let redis = support::fake_redis();
+    let env = [
+        ("KATAGO_CACHE_BACKEND", "redis"),
+        ("KATAGO_REDIS_URL", &redis),
+    ];
+    let (first, second) = tokio::join!(
+        TestServer::start_with_env(&env),
+        TestServer::start_with_env(&env)
+    );
+    let request = AnalysisRequest {
+        moves: vec![MoveInput::Simple("Q16".to_string())],
+        ..Default::default()
+    };
+
+    first.client().analyze(&request).await.unwrap();
+    let client = second.client();
+    client.analyze(&request).await.unwrap();
+    let redis_hits = || async {
+        let stats = client.cache_stats().await.unwrap();
+        let backend = stats.backend.unwrap();
+        assert_eq!((backend.kind.as_str(), backend.entries), ("redis", None));
+        backend.hits
+    };
+    assert_eq!(redis_hits().await, 1);
+
+    // Clearing on one server empties the shared cache
+    client.clear_cache().await.unwrap();
+    client.analyze(&request).await.unwrap();
+    assert_eq!(redis_hits().await, 1);
+}
+
+#[tokio::test]
+async fn identical_request_joins_the_running_search() {
+    let server = TestServer::start().await;
+    let slow = AnalysisRequest {
+        request_id: Some("first".to_string()),
+        override_settings: Some(json!({ "fakeDelayMs": 1000 })),
+        ..Default::default()
+    };
+    let running = tokio::spawn({
+        let client = server.client();
+        let slow = slow.clone();
+        async move { client.analyze(&slow).await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let client = server.client();
+    let joined = client
+        .analyze(&AnalysisRequest {
+            request_id: Some("second".to_string()),
+            ..slow
+        })
+        .await
+        .unwrap();
+    assert_eq!(joined.id, "second");
+    // The wait for the running search is search time, not a cache hit
+    let meta = joined.meta.unwrap();
+    assert_eq!((meta.coalesced, meta.cached), (true, false));
+    assert!(meta.search_ms >= 500, "{:?}", meta);
+    assert_eq!(meta.queue_wait_ms, 0);
+    assert_eq!(meta.visits_per_second, None);
+    let first = running.await.unwrap().unwrap();
+    assert_eq!(first.id, "first");
+    assert!(!first.meta.unwrap().coalesced);
+
+    let stats = client.cache_stats().await.unwrap();
+    assert_eq!((stats.joined, stats.misses, stats.entries), (1, 2, 1));
+}
+
+#[tokio::test]
+async fn completed_analyses_are_recorded_in_the_history() {
+    let server =
+        TestServer::start_with_env(&[("KATAGO_HISTORY_DATABASE_PATH", "history.db")]).await;
+    let request = AnalysisRequest {
+        moves: vec![MoveInput::Simple("D4".to_string())],
+        request_id: Some("batch-1".to_string()),
+        ..Default::default()
+    };
+    server.client().analyze(&request).await.unwrap();
+
+    // The history is written in the background
+    let mut analyses = Value::Null;
+    for _ in 0..50 {
+        let listed: Value = reqwest::get(server.url("/api/v1/analyses?since=2000-01-01T00:00:00Z"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        analyses = listed["analyses"].clone();
+        if analyses.as_array().is_some_and(|a| !a.is_empty()) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(analyses[0]["requestId"], "batch-1");
+    assert_eq!(analyses[0]["request"]["requestId"], "batch-1");
+    assert!(analyses[0]["response"]["moveInfos"].is_array());
+
+    let later = reqwest::get(server.url("/api/v1/analyses?since=2999-01-01T00:00:00Z"))
+        .await
+        .unwrap()
+        .json::<Value>()
+        .await
+        .unwrap();
+    assert_eq!(later["analyses"], json!([]));
+}
+
+#[tokio::test]
+async fn history_is_off_by_default() {
+    let server = TestServer::start().await;
+    let response = reqwest::get(server.url("/api/v1/analyses")).await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn config_reload_applies_live_settings() {
+    let server = TestServer::start_with_env(&[
+        ("KATAGO_ADMIN_TOKEN", "secret"),
+        ("KATAGO_MAX_IN_FLIGHT", "1"),
+    ])
+    .await;
+    std::fs::write(
+        server.dir().join("config.toml"),
+        "[katago]\nmax_queued = 0\nmodel_path = \"./other.bin.gz\"\n",
+    )
+    .unwrap();
+
+    let http = reqwest::Client::new();
+    let reload = || {
+        http.post(server.url("/api/v1/admin/config/reload"))
+            .bearer_auth("secret")
+    };
+    let changes: Value = reload().send().await.unwrap().json().await.unwrap();
+    assert_eq!(changes["applied"], json!(["katago.max_queued"]));
+    assert_eq!(changes["restartRequired"], json!(["katago.model_path"]));
+
+    // With no room left to wait, a query arriving while another runs is turned away
+    let running = tokio::spawn({
+        let client = server.client();
+        async move { client.analyze(&scripted("hang")).await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let error = server
+        .client()
+        .analyze(&AnalysisRequest::default())
+        .await
+        .unwrap_err();
+    assert_eq!(problem_status(error), 429);
+    assert_eq!(problem_status(running.await.unwrap().unwrap_err()), 504);
+
+    std::fs::write(server.dir().join("config.toml"), "[katago\n").unwrap();
+    let invalid = reload().send().await.unwrap();
+    assert_eq!(invalid.status(), 400);
+}
+
+#[tokio::test]
+async fn console_requires_the_admin_token() {
+    let server = TestServer::start_with_env(&[("KATAGO_ADMIN_TOKEN", "secret")]).await;
+    let http = reqwest::Client::new();
+    let anonymous = http.get(server.url("/console")).send().await.unwrap();
+    assert_eq!(anonymous.status(), 401);
+    let analysis = http
+        .post(server.url("/console/analyze"))
+        .form(&[("moves", "D4")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(analysis.status(), 401);
+
+    let page = http
+        .get(server.url("/console"))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(page.status(), 200);
+    assert!(page.text().await.unwrap().contains("default"));
+}
+
+#[tokio::test]
+async fn model_switch_keeps_serving_queries() {
+    let server = TestServer::start_with_env(&[("KATAGO_ADMIN_TOKEN", "secret")]).await;
+    let model = server.dir().join("other.bin.gz");
+    std::fs::write(&model, b"").unwrap();
+
+    let slow = AnalysisRequest {
+        override_settings: Some(json!({ "fakeDelayMs": 1000 })),
+        ..Default::default()
+    };
+    let running = tokio::spawn({
+        let client = server.client();
+        async move { client.analyze(&slow).await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let http = reqwest::Client::new();
+    let switch = |body: Value| {
+        http.post(server.url("/api/v1/admin/model"))
+            .bearer_auth("secret")
+            .json(&body)
+            .send()
+    };
+    let switched: Value = switch(json!({ "modelPath": model }))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(switched["engine"], "default");
+    assert_eq!(switched["modelPath"], json!(model));
+
+    // The query sent to the old process is still answered
+    running.await.unwrap().unwrap();
+    let version: Value = reqwest::get(server.url("/api/v1/version"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(version["model"]["name"], "other.bin.gz");
+    server
+        .client()
+        .analyze(&AnalysisRequest::default())
+        .await
+        .unwrap();
+
+    let missing = switch(json!({ "modelPath": "./missing.bin.gz" }))
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), 400);
+    let unknown = switch(json!({ "modelPath": model, "engine": "nine" }))
+        .await
+        .unwrap();
+    assert_eq!(unknown.status(), 404);
+}
+
+#[tokio::test]
+async fn admin_restart_fails_queries_in_flight_and_respawns() {
+    let server = TestServer::start_with_env(&[("KATAGO_ADMIN_TOKEN", "secret")]).await;
+    let client = server.client();
+    let stuck = tokio::spawn({
+        let client = server.client();
+        async move { client.analyze(&scripted("hang")).await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let http = reqwest::Client::new();
+    let restart = |path: &str| http.post(server.url(path)).bearer_auth("secret").send();
+    let restarted: Value = restart("/api/v1/admin/engine/restart")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(restarted["engine"], "default");
+    assert_eq!(restarted["failedQueries"], 1);
+    assert!(restarted["pid"].as_u64().unwrap() > 0);
+
+    match stuck.await.unwrap().unwrap_err() {
+        ClientError::Api(problem) => {
+            assert_eq!(problem.status, 503);
+            assert_eq!(problem.title, "Engine Restarted");
+        }
+        other => panic!("expected a problem response, got {}", other),
+    }
+    assert!(client.analyze(&AnalysisRequest::default()).await.is_ok());
+    // A deliberate restart is not a crash recovery
+    assert_eq!(client.health().await.unwrap().restarts, Some(0));
+
+    let again: Value = restart("/api/v1/admin/engine/restart")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_ne!(again["pid"], restarted["pid"]);
+    let unknown = restart("/api/v1/admin/engine/restart?engine=nine")
+        .await
+        .unwrap();
+    assert_eq!(unknown.status(), 404);
+}
+
+#[tokio::test]
+async fn throughput_reports_each_engines_searches() {
+    let server = TestServer::start_with_env(&[("KATAGO_ADMIN_TOKEN", "secret")]).await;
+    let http = reqwest::Client::new();
+    server
+        .client()
+        .analyze(&AnalysisRequest {
+            moves: vec![MoveInput::Simple("D4".to_string())],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let anonymous = http
+        .get(server.url("/api/v1/admin/throughput"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(anonymous.status(), 401);
+
+    let report: Value = http
+        .get(server.url("/api/v1/admin/throughput"))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["windowSecs"], 60);
+    let engine = &report["engines"][0];
+    assert_eq!(engine["engine"], "default");
+    assert!(engine["searches"].as_u64().unwrap() >= 1, "{}", report);
+    assert!(engine["visitsPerSecond"].as_f64().unwrap() > 0.0);
+    let utilization = engine["utilization"].as_f64().unwrap();
+    assert!((0.0..=1.0).contains(&utilization));
+
+    let buckets = engine["latencyByVisits"].as_array().unwrap();
+    assert_eq!(buckets.len(), 5);
+    assert_eq!(buckets[0]["minVisits"], 0);
+    assert!(buckets[4].get("maxVisits").is_none());
+    let searched: u64 = buckets
+        .iter()
+        .map(|b| b["searches"].as_u64().unwrap())
+        .sum();
+    assert!(searched >= 1);
+}
+
+#[tokio::test]
+async fn engine_status_reports_pending_queries_and_keepalive() {
+    let server = TestServer::start_with_env(&[("KATAGO_ADMIN_TOKEN", "secret")]).await;
+    let http = reqwest::Client::new();
+    let status = |path: &str| http.get(server.url(path)).bearer_auth("secret").send();
+
+    // The harness pings every second
+    let mut engine = Value::Null;
+    for _ in 0..50 {
+        engine = status("/api/v1/admin/engine")
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if engine["lastKeepalive"]["answeredAt"].is_string() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(
+        engine["lastKeepalive"]["answeredAt"].is_string(),
+        "{}",
+        engine
+    );
+    assert!(engine["lastKeepalive"].get("error").is_none());
+    assert_eq!(engine["engine"], "default");
+    assert_eq!(engine["alive"], true);
+    assert!(engine["pid"].as_u64().unwrap() > 0);
+    assert_eq!(engine["restartAttempts"], 0);
+    assert_eq!(engine["maxRestartAttempts"], 5);
+    assert_eq!(engine["restartsGivenUp"], false);
+    assert_eq!(engine["pending"], json!([]));
+
+    let _stuck = tokio::spawn({
+        let client = server.client();
+        let request = AnalysisRequest {
+            request_id: Some("stuck".to_string()),
+            ..scripted("hang")
+        };
+        async move { client.analyze(&request).await }
+    });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let engine: Value = status("/api/v1/admin/engine")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(engine["pending"][0]["id"], "stuck");
+    assert!(engine["pending"][0]["ageMs"].as_u64().unwrap() >= 200);
+
+    let unknown = status("/api/v1/admin/engine?engine=nine").await.unwrap();
+    assert_eq!(unknown.status(), 404);
+}
+
+async fn human_profiles(server: &TestServer) -> Value {
+    reqwest::get(server.url("/api/v1/human/profiles"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn human_profiles_are_listed_with_a_human_model() {
+    let server = TestServer::start().await;
+    let none = human_profiles(&server).await;
+    assert_eq!(none, json!({ "humanModels": [], "profiles": [] }));
+
+    let server =
+        TestServer::start_with_env(&[("KATAGO_HUMAN_MODEL_PATH", "./b18-humanv0.bin.gz")]).await;
+    let loaded = human_profiles(&server).await;
+    assert_eq!(loaded["humanModels"], json!(["b18-humanv0.bin.gz"]));
+    let names: Vec<&str> = loaded["profiles"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect();
+    assert!(names.contains(&"preaz_5k") && names.contains(&"proyear_2020"));
+}
+
+#[tokio::test]
+async fn human_moves_are_compared_with_the_engine() {
+    let server =
+        TestServer::start_with_env(&[("KATAGO_HUMAN_MODEL_PATH", "./b18-humanv0.bin.gz")]).await;
+    let client = server.client();
+    let request = HumanComparisonRequest {
+        position: AnalysisRequest {
+            board_x_size: 9,
+            board_y_size: 9,
+            ..Default::default()
+        },
+        human_sl_profile: "rank_5k".to_string(),
+        max_moves: Some(3),
+    };
+
+    let comparison = client.compare_human(&request).await.unwrap();
+    assert_eq!(comparison.human_sl_profile, "rank_5k");
+    let engine: Vec<_> = comparison
+        .engine_moves
+        .iter()
+        .map(|m| (m.move_coord.as_str(), m.point_loss))
+        .collect();
+    assert_eq!(
+        engine,
+        [("D4", Some(0.0)), ("F6", Some(1.0)), ("E5", Some(2.0))]
+    );
+    // The fake human policy is uniform, so the first points are the likeliest; the
+    // search never visited them and a second query evaluates them
+    let human: Vec<_> = comparison
+        .human_moves
+        .iter()
+        .map(|m| (m.move_coord.as_str(), m.point_loss))
+        .collect();
+    assert_eq!(
+        human,
+        [("A9", Some(0.0)), ("B9", Some(1.0)), ("C9", Some(2.0))]
+    );
+
+    let typo = HumanComparisonRequest {
+        human_sl_profile: "rank_5kyu".to_string(),
+        ..request.clone()
+    };
+    let error = client.compare_human(&typo).await.unwrap_err();
+    assert_eq!(problem_status(error), 400);
+
+    let without_model = TestServer::start().await;
+    let error = without_model
+        .client()
+        .compare_human(&request)
+        .await
+        .unwrap_err();
+    assert_eq!(problem_status(error), 400);
+}
+
+/// A callback receiver that fails its first delivery, then sends each delivery's
+/// headers and body down the channel
+async fn callback_receiver() -> (
+    String,
+    tokio::sync::mpsc::UnboundedReceiver<(axum::http::HeaderMap, String)>,
+) {
+    use axum::http::StatusCode;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    let (sender, received) = tokio::sync::mpsc::unbounded_channel();
+    let failed_once = Arc::new(AtomicBool::new(false));
+    let app = axum::Router::new().route(
+        "/hook",
+        axum::routing::post(move |headers: axum::http::HeaderMap, body: String| {
+            let sender = sender.clone();
+            let failed_once = failed_once.clone();
+            async move {
+                if !failed_once.swap(true, Ordering::SeqCst) {
+                    return StatusCode::SERVICE_UNAVAILABLE;
+                }
+                let _ = sender.send((headers, body));
+                StatusCode::OK
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (url, received)
+}
+
+#[tokio::test]
+async fn callbacks_deliver_signed_results() {
+    let server = TestServer::start_with_config(
+        "[webhooks]\n\
+         secret = \"s3cret\"\n\
+         retry_backoff_ms = 50\n\
+         allowed_hosts = [\"127.0.0.1\"]\n",
+        &[],
+    )
+    .await;
+    let (url, mut received) = callback_receiver().await;
+    let http = reqwest::Client::new();
+
+    let accepted = http
+        .post(server.url("/api/v1/analysis"))
+        .json(&json!({ "moves": [["B", "D4"]], "requestId": "later", "callbackUrl": url }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(accepted.status(), 202);
+    let accepted: Value = accepted.json().await.unwrap();
+    assert_eq!(
+        accepted,
+        json!({ "requestId": "later", "callbackUrl": url })
+    );
+
+    // Delivered on the second attempt, signed with the secret
+    let (headers, body) = received.recv().await.unwrap();
+    assert_eq!(headers["x-katago-event"], "completed");
+    assert_eq!(headers["x-request-id"], "later");
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, b"s3cret");
+    let signature = headers["x-katago-signature"].to_str().unwrap();
+    let tag: Vec<u8> = (7..signature.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&signature[i..i + 2], 16).unwrap())
+        .collect();
+    assert!(signature.starts_with("sha256="));
+    ring::hmac::verify(&key, body.as_bytes(), &tag).unwrap();
+    let result: AnalysisResponse = serde_json::from_str(&body).unwrap();
+    assert_eq!(result.id, "later");
+
+    // A review of a raw SGF takes the URL from the query string
+    let review = http
+        .post(server.url("/api/v1/review"))
+        .query(&[("callbackUrl", url.as_str())])
+        .body("(;GM[1]SZ[9];B[ee];W[cc])")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(review.status(), 202);
+    let (_, body) = received.recv().await.unwrap();
+    let review: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(review["moves"].as_array().unwrap().len(), 2);
+
+    for callback_url in ["file:///etc/passwd", "http://169.254.169.254/latest"] {
+        let invalid = http
+            .post(server.url("/api/v1/analysis"))
+            .json(&json!({ "callbackUrl": callback_url }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(invalid.status(), 400, "{}", callback_url);
+    }
+}
+
+#[tokio::test]
+async fn callbacks_to_the_servers_network_are_refused() {
+    let server = TestServer::start_with_env(&[("KATAGO_WEBHOOK_SECRET", "s3cret")]).await;
+    for callback_url in [
+        "http://127.0.0.1/",
+        "http://169.254.169.254/",
+        "http://localhost:9/hook",
+    ] {
+        let response = reqwest::Client::new()
+            .post(server.url("/api/v1/analysis"))
+            .json(&json!({ "callbackUrl": callback_url }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400, "{}", callback_url);
+        let problem: Value = response.json().await.unwrap();
+        assert_eq!(problem["title"], "Invalid Callback URL");
+    }
+}
+
+#[tokio::test]
+async fn callbacks_need_a_secret() {
+    let server = TestServer::start().await;
+    let response = reqwest::Client::new()
+        .post(server.url("/api/v1/analysis"))
+        .json(&json!({ "callbackUrl": "http://127.0.0.1:9/hook" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let problem: Value = response.json().await.unwrap();
+    assert_eq!(problem["title"], "Callbacks Disabled");
+}
+
+#[tokio::test]
+async fn requests_select_a_named_model() {
+    let server = TestServer::start_with_config(
+        "[katago.models.human]\n\
+         model_path = \"./b18.bin.gz\"\n\
+         human_model_path = \"./b18-humanv0.bin.gz\"\n",
+        &[],
+    )
+    .await;
+    let client = server.client();
+    let profiled = |model: Option<&str>| AnalysisRequest {
+        model: model.map(str::to_string),
+        override_settings: Some(json!({ "humanSLProfile": "rank_5k" })),
+        ..Default::default()
+    };
+
+    // Only the model's engine runs with the human model
+    let human = client.analyze(&profiled(Some("human"))).await.unwrap();
+    assert_eq!(human.human_model.as_deref(), Some("b18-humanv0.bin.gz"));
+    let default = client.analyze(&profiled(None)).await.unwrap();
+    assert!(default.human_model.is_none());
+
+    let error = client.analyze(&profiled(Some("strong"))).await.unwrap_err();
+    assert_eq!(problem_status(error), 400);
+}
+
+#[tokio::test]
+async fn fast_tier_takes_low_priority_requests() {
+    // The fast model's engine is told apart by its human model
+    let server = TestServer::start_with_config(
+        "[katago.models.small]\n\
+         model_path = \"./b10.bin.gz\"\n\
+         human_model_path = \"./b18-humanv0.bin.gz\"\n\
+         [tiers]\n\
+         fast_model = \"small\"\n\
+         fast_below_priority = 0\n",
+        &[],
+    )
+    .await;
+    let client = server.client();
+    let request = |quality: Option<Quality>, priority: Option<i32>| AnalysisRequest {
+        quality,
+        priority,
+        override_settings: Some(json!({ "humanSLProfile": "rank_5k" })),
+        ..Default::default()
+    };
+    let cases = [
+        (Some(Quality::Fast), Some(5), true),
+        (None, Some(-1), true),
+        (None, None, false),
+        (Some(Quality::Deep), Some(-1), false),
+    ];
+    for (quality, priority, fast) in cases {
+        let response = client.analyze(&request(quality, priority)).await.unwrap();
+        assert_eq!(
+            response.human_model.is_some(),
+            fast,
+            "{:?} {:?}",
+            quality,
+            priority
+        );
+    }
+}
+
+#[tokio::test]
+async fn gtp_bot_plays_and_scores() {
+    let server = TestServer::start_with_env(&[("KATAGO_GTP_ENABLED", "true")]).await;
+    let client = server.client();
+    let game = GameRequest {
+        moves: vec!["D4".to_string(), "Q4".to_string()],
+        request_id: Some("game-1".to_string()),
+        ..Default::default()
+    };
+
+    let played = client.game_move(&game).await.unwrap();
+    assert_eq!(played.bot_move, "Q16");
+    assert_eq!(played.request_id.as_deref(), Some("game-1"));
+    assert!((played.diagnostics.winprob.unwrap() - 0.55).abs() < 1e-4);
+    assert_eq!(played.diagnostics.best_ten.len(), 2);
+    assert_eq!(played.diagnostics.best_ten[0].move_coord, "Q16");
+
+    let scored = client.game_score(&game).await.unwrap();
+    assert_eq!(scored.probs.len(), 19 * 19);
+
+    let illegal = GameRequest {
+        moves: vec!["Z99".to_string()],
+        ..Default::default()
+    };
+    let error = client.game_move(&illegal).await.unwrap_err();
+    assert_eq!(problem_status(error), 400);
+    // The bot is still usable afterwards
+    assert_eq!(client.game_move(&game).await.unwrap().bot_move, "Q16");
+}
+
+#[tokio::test]
+async fn admins_send_raw_gtp_commands_to_the_bot() {
+    let server = TestServer::start_with_env(&[
+        ("KATAGO_GTP_ENABLED", "true"),
+        ("KATAGO_ADMIN_TOKEN", "secret"),
+    ])
+    .await;
+    let http = reqwest::Client::new();
+    let gtp = |command: &str| {
+        http.post(server.url("/api/v1/admin/gtp"))
+            .bearer_auth("secret")
+            .json(&json!({ "command": command }))
+            .send()
+    };
+    let reply = |command: &'static str| async move {
+        let response = gtp(command).await.unwrap();
+        assert_eq!(response.status(), 200, "{}", command);
+        response.json::<Value>().await.unwrap()
+    };
+
+    let set = reply("kata-set-param playoutDoublingAdvantage 1.5").await;
+    assert_eq!(set["success"], true);
+    let get = reply("kata-get-param playoutDoublingAdvantage").await;
+    assert_eq!(get["response"], "= 1.5");
+    let unknown = reply("kata-get-param noSuchParam").await;
+    assert_eq!(unknown["success"], false);
+    assert_eq!(unknown["response"], "? Invalid parameter");
+    // Replies of several lines come back whole
+    let commands = reply("list_commands").await;
+    assert_eq!(
+        commands["response"],
+        "= genmove\nplay\nkata-get-param\nkata-set-param"
+    );
+
+    assert_eq!(gtp("kata-analyze 10").await.unwrap().status(), 400);
+    assert_eq!(
+        gtp("kata-search_analyze_cancellable b 10")
+            .await
+            .unwrap()
+            .status(),
+        400
+    );
+    // A GTP command id before the name doesn't get them through
+    assert_eq!(gtp("1 quit").await.unwrap().status(), 400);
+    assert_eq!(gtp("5 kata-analyze 10").await.unwrap().status(), 400);
+    assert_eq!(gtp("name\nquit").await.unwrap().status(), 400);
+    let anonymous = http
+        .post(server.url("/api/v1/admin/gtp"))
+        .json(&json!({ "command": "name" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(anonymous.status(), 401);
+}
+
+#[tokio::test]
+async fn gtp_bot_is_off_by_default() {
+    let server = TestServer::start().await;
+    let error = server
+        .client()
+        .game_move(&GameRequest::default())
+        .await
+        .unwrap_err();
+    assert_eq!(problem_status(error), 404);
+}
+
+#[tokio::test]
+async fn responses_are_compressed_and_bodies_limited() {
+    let server = TestServer::start_with_env(&[("KATAGO_MAX_BODY_BYTES", "4096")]).await;
+    let http = reqwest::Client::new();
+    let analyze = |body: String, encoding: &'static str| {
+        http.post(server.url("/api/v1/analysis"))
+            .header("Content-Type", "application/json")
+            .header("Accept-Encoding", encoding)
+            .body(body)
+            .send()
+    };
+    let request = json!({ "moves": ["D4"], "includeOwnership": true }).to_string();
+
+    for encoding in ["gzip", "br"] {
+        let response = analyze(request.clone(), encoding).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-encoding"], encoding);
+    }
+    let plain = analyze(request, "identity").await.unwrap();
+    assert!(plain.headers().get("content-encoding").is_none());
+    assert!(plain.json::<Value>().await.unwrap()["ownership"].is_array());
+
+    let moves = vec!["pass"; 1000];
+    let oversized = analyze(json!({ "moves": moves }).to_string(), "identity")
+        .await
+        .unwrap();
+    assert_eq!(oversized.status(), 413);
+    assert_eq!(
+        oversized.headers()["content-type"],
+        "application/problem+json"
+    );
+    let problem: Value = oversized.json().await.unwrap();
+    assert_eq!(problem["title"], "Request Body Too Large");
+}
+
+#[tokio::test]
+async fn heatmaps_are_rendered_as_svg_and_png() {
+    let server = TestServer::start().await;
+    let position = AnalysisRequest {
+        moves: vec![MoveInput::Simple("E5".to_string())],
+        board_x_size: 9,
+        board_y_size: 9,
+        ..Default::default()
+    };
+
+    let response = reqwest::Client::new()
+        .post(server.url("/api/v1/render/heatmap"))
+        .json(&json!({ "moves": ["E5"], "boardXSize": 9, "boardYSize": 9, "layer": "policy" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/svg+xml");
+    let svg = response.text().await.unwrap();
+    assert!(svg.starts_with("<svg") && svg.ends_with("</svg>"));
+    // The E5 stone and the shaded priors
+    assert!(svg.contains(r##"fill="#101010" stroke="##));
+    assert!(svg.contains("fill-opacity"));
+
+    let request = HeatmapRequest {
+        position: position.clone(),
+        values: Some(Ownership::Map([("A9".to_string(), -1.0)].into())),
+        format: Some(ImageFormat::Png),
+        point_size: Some(16),
+        ..Default::default()
+    };
+    let png = server.client().render_heatmap(&request).await.unwrap();
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    // 10 points of 16 pixels on each side
+    assert_eq!(&png[16..24], &[0, 0, 0, 160, 0, 0, 0, 160]);
+
+    let misfit = HeatmapRequest {
+        position,
+        values: Some(Ownership::Flat(vec![0.5; 19 * 19])),
+        ..Default::default()
+    };
+    let err = server.client().render_heatmap(&misfit).await.unwrap_err();
+    assert_eq!(problem_status(err), 400);
+}
+
+#[tokio::test]
+async fn reviewed_game_is_animated() {
+    let server = TestServer::start().await;
+    let mut request = ReviewAnimationRequest {
+        sgf: "(;SZ[9]KM[7];B[ee];W[cc];B[gc])".to_string(),
+        frame_ms: Some(500),
+        ..Default::default()
+    };
+
+    let svg = server.client().render_review(&request).await.unwrap();
+    let svg = String::from_utf8(svg.to_vec()).unwrap();
+    assert!(svg.starts_with("<svg") && svg.ends_with("</svg>"));
+    // Four frames: the empty board and one per move
+    assert!(svg.contains(r#"dur="2s""#));
+    assert!(
+        svg.matches(r##"stroke="#101010" stroke-width="1""##)
+            .count()
+            >= 3
+    );
+
+    request.format = Some(AnimationFormat::Gif);
+    request.point_size = Some(8);
+    let gif = server.client().render_review(&request).await.unwrap();
+    assert!(gif.starts_with(b"GIF89a"));
+    // 10 points of 8 pixels, and a row for the winrate bar
+    assert_eq!(&gif[6..10], &[80, 0, 88, 0]);
+
+    request.frame_ms = Some(10);
+    let error = server.client().render_review(&request).await.unwrap_err();
+    assert_eq!(problem_status(error), 400);
+    let illegal = ReviewAnimationRequest {
+        sgf: "(;SZ[9];B[ee];W[ee])".to_string(),
+        ..Default::default()
+    };
+    let error = server.client().render_review(&illegal).await.unwrap_err();
+    assert_eq!(problem_status(error), 400);
+}
+
+#[tokio::test]
+async fn analysis_speaks_msgpack_on_request() {
+    let server = TestServer::start().await;
+    let request = AnalysisRequest {
+        moves: vec![MoveInput::Simple("E5".to_string())],
+        board_x_size: 9,
+        board_y_size: 9,
+        include_ownership: Some(true),
+        ownership_format: Some(OwnershipFormat::Map),
+        ..Default::default()
+    };
+
+    let response = reqwest::Client::new()
+        .post(server.url("/api/v1/analysis"))
+        .header("content-type", "application/msgpack")
+        .header("accept", "application/msgpack")
+        .body(rmp_serde::to_vec_named(&request).unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/msgpack");
+    assert_eq!(response.headers()["vary"], "accept");
+    let analysis: AnalysisResponse =
+        rmp_serde::from_slice(&response.bytes().await.unwrap()).unwrap();
+    assert!(analysis.root_info.is_some());
+    assert!(matches!(analysis.ownership, Some(Ownership::Map(ref map)) if map.contains_key("A9")));
+
+    // Without the Accept header a MessagePack body is answered in JSON
+    let response = reqwest::Client::new()
+        .post(server.url("/api/v1/policy"))
+        .header("content-type", "application/x-msgpack")
+        .body(rmp_serde::to_vec_named(&request).unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("application/json"));
+
+    // Errors stay problem details
+    let response = reqwest::Client::new()
+        .post(server.url("/api/v1/analysis"))
+        .header("content-type", "application/msgpack")
+        .header("accept", "application/msgpack")
+        .body(vec![0xc1])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/problem+json"
+    );
+}
+
+#[tokio::test]
+async fn analysis_is_encoded_as_protobuf_on_request() {
+    let server = TestServer::start().await;
+    let response = reqwest::Client::new()
+        .post(server.url("/api/v1/analysis"))
+        .header("accept", "application/x-protobuf")
+        .json(&json!({
+            "moves": ["E5"],
+            "boardXSize": 9,
+            "boardYSize": 9,
+            "includeOwnership": true,
+            "ownershipFormat": "rows"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/x-protobuf");
+    let body = response.bytes().await.unwrap();
+    // Field 1, the id, comes first as a length-delimited string
+    assert_eq!(body[0], 0x0a);
+    assert!(body[1] > 0);
+    // Ownership is the flat packed array whatever ownershipFormat says: field 6,
+    // 81 floats of 4 bytes (length 324 as a varint)
+    assert!(body.windows(3).any(|bytes| bytes == [0x32, 0xc4, 0x02]));
+
+    let schema = reqwest::get(server.url("/api/v1/analysis.proto"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(schema.contains("message AnalysisResponse {"));
+}
+
+#[tokio::test]
+async fn graphql_returns_only_the_selected_fields() {
+    let server = TestServer::start().await;
+    let query = r#"
+        query ($position: JSON!) {
+            analysis(request: $position, maxMoves: 1) {
+                rootInfo { currentPlayer }
+                moveInfos { moveCoord winrate pv }
+            }
+            version { server { name } }
+        }
+    "#;
+    let body: Value = reqwest::Client::new()
+        .post(server.url("/graphql"))
+        .json(&json!({
+            "query": query,
+            "variables": { "position": { "moves": ["E5"], "boardXSize": 9, "boardYSize": 9 } }
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(body.get("errors").is_none(), "{}", body);
+    let analysis = &body["data"]["analysis"];
+    assert_eq!(analysis["rootInfo"], json!({ "currentPlayer": "W" }));
+    let moves = analysis["moveInfos"].as_array().unwrap();
+    assert_eq!(moves.len(), 1);
+    let fields: Vec<_> = moves[0].as_object().unwrap().keys().collect();
+    assert_eq!(fields, ["moveCoord", "pv", "winrate"]);
+    assert_eq!(body["data"]["version"]["server"]["name"], "katago-server");
+
+    // Problems are GraphQL errors carrying the problem's fields
+    let body: Value = reqwest::Client::new()
+        .post(server.url("/graphql"))
+        .json(&json!({
+            "query": r#"{ analysis(request: {moves: ["E5"], maxTimeSecs: -1}) { id } }"#
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["errors"][0]["extensions"]["status"], 400, "{}", body);
+    assert_eq!(body["errors"][0]["extensions"]["title"], "Invalid Request");
+}
+
+#[tokio::test]
+async fn readiness_waits_for_the_warm_up_analysis() {
+    let server = TestServer::start_with_env(&[("FAKE_KATAGO_STARTUP_MS", "3000")]).await;
+    let http = reqwest::Client::new();
+
+    let live = http.get(server.url("/livez")).send().await.unwrap();
+    assert_eq!(live.status(), 200);
+    assert_eq!(live.text().await.unwrap(), "ok");
+
+    let ready = http.get(server.url("/readyz")).send().await.unwrap();
+    assert_eq!(ready.status(), 503);
+    assert_eq!(ready.text().await.unwrap(), "engine default: warming up");
+
+    // The model "loads" after 3 s, then answers the warm-up analysis
+    let deadline = std::time::Instant::now() + Duration::from_secs(20);
+    loop {
+        let ready = http.get(server.url("/readyz")).send().await.unwrap();
+        if ready.status() == 200 {
+            assert_eq!(ready.text().await.unwrap(), "ok");
+            break;
+        }
+        assert!(std::time::Instant::now() < deadline, "never became ready");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+#[test]
+fn misconfigured_paths_are_reported_together_before_starting() {
+    let dir = std::env::temp_dir().join(format!("katago-server-preflight-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("analysis_config.cfg"), "maxVisits 10\n").unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_katago-server"))
+        .current_dir(&dir)
+        .env("KATAGO_KATAGO_PATH", env!("CARGO_BIN_EXE_fake_katago"))
+        .env("KATAGO_HUMAN_MODEL_PATH", "./human.bin.gz")
+        .env("RUST_LOG", "off")
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("3 configuration problem(s)"), "{}", stderr);
+    assert!(stderr.contains("katago.model_path: ./model.bin.gz does not exist"));
+    assert!(stderr.contains("katago.human_model_path: ./human.bin.gz does not exist"));
+    assert!(stderr.contains("./analysis_config.cfg: line 1: expected `key = value`"));
+}
+
+#[tokio::test]
+async fn requests_select_a_named_profile() {
+    let server = TestServer::start_with_config(
+        "[analysis.profiles.fast]\n\
+         max_visits = 50\n\
+         include_ownership = false\n\
+         [analysis.profiles.deep]\n\
+         max_visits = 500\n\
+         include_ownership = true\n\
+         include_policy = true\n\
+         [analysis.profiles.teach]\n\
+         human_sl_profile = \"preaz_8k\"\n",
+        &[("KATAGO_HUMAN_MODEL_PATH", "./b18-humanv0.bin.gz")],
+    )
+    .await;
+    let client = server.client();
+    let profiled = |profile: &str| AnalysisRequest {
+        moves: vec![MoveInput::Simple("D4".to_string())],
+        profile: Some(profile.to_string()),
+        ..Default::default()
+    };
+
+    let fast = client.analyze(&profiled("fast")).await.unwrap();
+    assert_eq!(fast.root_info.unwrap().visits, 50);
+    assert!(fast.ownership.is_none());
+
+    let deep = client.analyze(&profiled("deep")).await.unwrap();
+    assert_eq!(deep.root_info.unwrap().visits, 500);
+    assert!(deep.ownership.is_some() && deep.policy.is_some());
+
+    // The request's own settings win over the profile's
+    let capped = AnalysisRequest {
+        max_visits: Some(20),
+        ..profiled("deep")
+    };
+    let capped = client.analyze(&capped).await.unwrap();
+    assert_eq!(capped.root_info.unwrap().visits, 20);
+
+    let teach = client.analyze(&profiled("teach")).await.unwrap();
+    assert_eq!(teach.human_model.as_deref(), Some("b18-humanv0.bin.gz"));
+
+    let error = client.analyze(&profiled("thorough")).await.unwrap_err();
+    assert_eq!(problem_status(error), 400);
+}
+
+#[tokio::test]
+async fn logged_requests_are_replayed_against_the_engine() {
+    let server = TestServer::start_with_env(&[
+        ("KATAGO_ADMIN_TOKEN", "secret"),
+        ("KATAGO_AUDIT_LOG_PATH", "audit.jsonl"),
+    ])
+    .await;
+    let http = reqwest::Client::new();
+    let replay = |request_id: &str| {
+        http.post(server.url("/api/v1/admin/replay"))
+            .bearer_auth("secret")
+            .json(&json!({ "requestId": request_id }))
+            .send()
+    };
+
+    let request = AnalysisRequest {
+        moves: vec![MoveInput::Simple("D4".to_string())],
+        max_visits: Some(30),
+        request_id: Some("yesterday".to_string()),
+        ..Default::default()
+    };
+    let original = server.client().analyze(&request).await.unwrap();
+    let failed = AnalysisRequest {
+        request_id: Some("broken".to_string()),
+        ..scripted("error")
+    };
+    server.client().analyze(&failed).await.unwrap_err();
+
+    // Entries are written in the background
+    let mut replayed = replay("yesterday").await.unwrap();
+    for _ in 0..20 {
+        if replayed.status() != 404 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        replayed = replay("yesterday").await.unwrap();
+    }
+    assert_eq!(replayed.status(), 200);
+    let replayed: Value = replayed.json().await.unwrap();
+    assert_eq!(replayed["original"]["kind"], "analysis");
+    assert_eq!(replayed["original"]["request"]["maxVisits"], 30);
+    assert_eq!(replayed["original"]["responses"][0]["id"], "yesterday");
+    assert_eq!(replayed["replayed"][0]["rootInfo"]["visits"], 30);
+    let best = original.move_infos.unwrap()[0].move_coord.clone();
+    assert_eq!(replayed["comparison"][0]["bestMoveBefore"], best.as_str());
+    assert_eq!(replayed["comparison"][0]["bestMoveAfter"], best.as_str());
+
+    // Searched again rather than served from the cache
+    let stats: Value = reqwest::get(server.url("/api/v1/cache/stats"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["hits"], 0);
+
+    // Failures are logged with their problem
+    let log = std::fs::read_to_string(server.dir().join("audit.jsonl")).unwrap();
+    let broken = log
+        .lines()
+        .find(|line| line.contains("\"broken\""))
+        .unwrap();
+    let broken: Value = serde_json::from_str(broken).unwrap();
+    assert_eq!(broken["error"]["status"], 500);
+    assert_eq!(broken["responses"], json!([]));
+
+    assert_eq!(replay("never").await.unwrap().status(), 404);
+}
@@ -0,0 +1,294 @@
+//! Boots the real server binary against the scripted fake KataGo (src/bin/fake_katago.rs)
+
+use katago_server_client::Client;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long the server gets to start answering health checks
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How the server is reached
+#[derive(Clone, Copy)]
+enum Transport<'a> {
+    Http,
+    /// HTTPS with this PEM certificate and key
+    Https(&'a str, &'a str),
+    /// HTTP on katago.sock in the working directory
+    UnixSocket,
+}
+
+/// A katago-server process on a free local port, killed when dropped
+pub struct TestServer {
+    process: Child,
+    dir: PathBuf,
+    /// Trusts the server's certificate when it speaks HTTPS
+    http: reqwest::Client,
+    pub base_url: String,
+}
+
+impl TestServer {
+    pub async fn start() -> Self {
+        Self::start_with_env(&[]).await
+    }
+
+    /// Start with extra environment variables (e.g. `FAKE_KATAGO_LATENCY_MS`, which
+    /// the fake engine inherits through the server)
+    pub async fn start_with_env(env: &[(&str, &str)]) -> Self {
+        Self::spawn(None, Transport::Http, env).await
+    }
+
+    /// Start with a config.toml in the working directory
+    pub async fn start_with_config(config: &str, env: &[(&str, &str)]) -> Self {
+        Self::spawn(Some(config), Transport::Http, env).await
+    }
+
+    /// Start over HTTPS with a PEM certificate and key, written to cert.pem and
+    /// key.pem in the working directory
+    pub async fn start_with_tls(cert: &str, key: &str, env: &[(&str, &str)]) -> Self {
+        Self::spawn(None, Transport::Https(cert, key), env).await
+    }
+
+    /// Start listening on katago.sock in the working directory instead of a port;
+    /// talk to it with `unix_request`
+    pub async fn start_on_unix_socket(env: &[(&str, &str)]) -> Self {
+        Self::spawn(None, Transport::UnixSocket, env).await
+    }
+
+    async fn spawn(config: Option<&str>, transport: Transport<'_>, env: &[(&str, &str)]) -> Self {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("no free port")
+            .port();
+        // Own working directory so no config.toml or data files are shared
+        let dir = std::env::temp_dir().join(format!("katago-server-test-{}", port));
+        std::fs::create_dir_all(&dir).unwrap();
+        // The model and config files the tests name, which the server checks exist
+        for file in [
+            "model.bin.gz",
+            "b10.bin.gz",
+            "b18.bin.gz",
+            "b18-humanv0.bin.gz",
+            "analysis_config.cfg",
+            "gtp_config.cfg",
+        ] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        if let Some(config) = config {
+            std::fs::write(dir.join("config.toml"), config).unwrap();
+        }
+        let mut http = reqwest::Client::builder();
+        if let Transport::Https(cert, key) = transport {
+            std::fs::write(dir.join("cert.pem"), cert).unwrap();
+            std::fs::write(dir.join("key.pem"), key).unwrap();
+            http =
+                http.add_root_certificate(reqwest::Certificate::from_pem(cert.as_bytes()).unwrap());
+        }
+
+        let mut command = Command::new(env!("CARGO_BIN_EXE_katago-server"));
+        command
+            .current_dir(&dir)
+            .env("KATAGO_SERVER_HOST", "127.0.0.1")
+            .env("KATAGO_SERVER_PORT", port.to_string())
+            .env("KATAGO_KATAGO_PATH", env!("CARGO_BIN_EXE_fake_katago"))
+            .env("KATAGO_MOVE_TIMEOUT_SECS", "2")
+            .env("KATAGO_KEEPALIVE_INTERVAL_SECS", "1")
+            .env("RUST_LOG", "katago_server=warn")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        match transport {
+            Transport::Http => {}
+            Transport::Https(..) => {
+                command
+                    .env("KATAGO_TLS_CERT_PATH", "cert.pem")
+                    .env("KATAGO_TLS_KEY_PATH", "key.pem");
+            }
+            Transport::UnixSocket => {
+                command.env("KATAGO_SERVER_LISTEN", "unix:katago.sock");
+            }
+        }
+        for (name, value) in env {
+            command.env(name, value);
+        }
+        let process = command.spawn().expect("failed to start katago-server");
+
+        let base_url = match transport {
+            Transport::Http => format!("http://127.0.0.1:{}", port),
+            Transport::Https(..) => format!("https://127.0.0.1:{}", port),
+            Transport::UnixSocket => format!("unix:{}", dir.join("katago.sock").display()),
+        };
+        let server = TestServer {
+            process,
+            dir,
+            http: http.build().unwrap(),
+            base_url,
+        };
+        assert!(
+            server.wait_healthy(STARTUP_TIMEOUT).await,
+            "katago-server did not become healthy"
+        );
+        server
+    }
+
+    /// Client without retries, so every failure reaches the test
+    pub fn client(&self) -> Client {
+        Client::new(&self.base_url).with_retries(0, Duration::ZERO)
+    }
+
+    /// The server's working directory, where it looks for config.toml
+    pub fn dir(&self) -> &std::path::Path {
+        &self.dir
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Send one HTTP/1.1 request over the Unix socket of a server started with
+    /// `start_on_unix_socket`; returns the status and body
+    #[cfg(unix)]
+    pub async fn unix_request(
+        &self,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> std::io::Result<(u16, String)> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let socket = self
+            .base_url
+            .strip_prefix("unix:")
+            .expect("not on a Unix socket");
+        let mut stream = tokio::net::UnixStream::connect(socket).await?;
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+        let status = head
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .unwrap_or(0);
+        Ok((status, body.to_string()))
+    }
+
+    /// Poll the health endpoint until it answers 200
+    pub async fn wait_healthy(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            #[cfg(unix)]
+            if self.base_url.starts_with("unix:") {
+                if let Ok((200, _)) = self.unix_request("GET", "/api/v1/health", "").await {
+                    return true;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+            if let Ok(response) = self.http.get(self.url("/api/v1/health")).send().await {
+                if response.status().is_success() {
+                    return true;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        false
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        // The fake engine exits when the server's end of its stdin closes
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A Redis server on a free local port that understands the commands of the
+/// Redis cache backend (GET, SET, SCAN, DEL), keeping keys in memory until the
+/// test ends; returns its redis:// URL
+pub fn fake_redis() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("redis://{}", listener.local_addr().unwrap());
+    let keys = Arc::new(Mutex::new(HashMap::new()));
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let keys = keys.clone();
+            std::thread::spawn(move || serve_redis(stream, &keys));
+        }
+    });
+    url
+}
+
+fn serve_redis(stream: TcpStream, keys: &Mutex<HashMap<Vec<u8>, Vec<u8>>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    while let Some(args) = read_redis_command(&mut reader) {
+        let mut keys = keys.lock().unwrap();
+        let reply = match args[0].to_ascii_uppercase().as_slice() {
+            b"GET" => match keys.get(&args[1]) {
+                Some(value) => bulk(value),
+                None => b"$-1\r\n".to_vec(),
+            },
+            b"SET" => {
+                keys.insert(args[1].clone(), args[2].clone());
+                b"+OK\r\n".to_vec()
+            }
+            b"SCAN" => {
+                // Everything in one batch, cursor 0
+                let prefix = args[3].strip_suffix(b"*").unwrap_or(&args[3]);
+                let matching: Vec<&Vec<u8>> =
+                    keys.keys().filter(|key| key.starts_with(prefix)).collect();
+                let mut reply = format!("*2\r\n$1\r\n0\r\n*{}\r\n", matching.len()).into_bytes();
+                for key in matching {
+                    reply.extend(bulk(key));
+                }
+                reply
+            }
+            b"DEL" => {
+                let deleted = args[1..].iter().filter(|key| keys.remove(*key).is_some());
+                format!(":{}\r\n", deleted.count()).into_bytes()
+            }
+            _ => b"-ERR unknown command\r\n".to_vec(),
+        };
+        if writer.write_all(&reply).is_err() {
+            break;
+        }
+    }
+}
+
+/// A RESP array of bulk strings, as clients send commands
+fn read_redis_command(reader: &mut impl BufRead) -> Option<Vec<Vec<u8>>> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let count: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+    (0..count)
+        .map(|_| {
+            line.clear();
+            reader.read_line(&mut line).ok()?;
+            let len: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+            let mut arg = vec![0; len + 2];
+            reader.read_exact(&mut arg).ok()?;
+            arg.truncate(len);
+            Some(arg)
+        })
+        .collect()
+}
+
+fn bulk(value: &[u8]) -> Vec<u8> {
+    let mut reply = format!("${}\r\n", value.len()).into_bytes();
+    reply.extend_from_slice(value);
+    reply.extend_from_slice(b"\r\n");
+    reply
+}